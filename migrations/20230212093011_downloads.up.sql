@@ -0,0 +1,11 @@
+-- Files downloaded into cache.
+CREATE TABLE downloads (
+       -- Source URL.
+       url TEXT NOT NULL PRIMARY KEY,
+       -- Name of the file within cache directory.
+       file TEXT NOT NULL,
+       -- Size of the file in bytes.
+       size INTEGER NOT NULL,
+       -- Timestamp of last access of the file.
+       accessed TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,2 @@
+-- Detected image format of downloaded file.
+ALTER TABLE downloads ADD COLUMN format TEXT NULL;
@@ -0,0 +1,3 @@
+ALTER TABLE downloads DROP COLUMN validated;
+ALTER TABLE downloads DROP COLUMN last_modified;
+ALTER TABLE downloads DROP COLUMN etag;
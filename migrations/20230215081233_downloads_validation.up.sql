@@ -0,0 +1,6 @@
+-- Value of ETag header of the downloaded file.
+ALTER TABLE downloads ADD COLUMN etag TEXT NULL;
+-- Value of Last-Modified header of the downloaded file.
+ALTER TABLE downloads ADD COLUMN last_modified TEXT NULL;
+-- Timestamp of last download or successful revalidation. NULL if unknown.
+ALTER TABLE downloads ADD COLUMN validated TEXT NULL;
@@ -0,0 +1,13 @@
+-- Previews of web pages that text notes link to.
+CREATE TABLE link_previews (
+       -- URL of the web page.
+       url TEXT NOT NULL PRIMARY KEY,
+       -- Title of the page. NULL if the page could not be previewed.
+       title TEXT NULL,
+       -- Description of the page.
+       description TEXT NULL,
+       -- URL of image representing the page.
+       image TEXT NULL,
+       -- Timestamp of when the preview was obtained.
+       fetched TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
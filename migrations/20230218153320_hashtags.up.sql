@@ -0,0 +1,10 @@
+-- Hashtags of text notes, both from `t` tags and from content.
+CREATE TABLE hashtags (
+       -- Text note's event id.
+       event_id BLOB NOT NULL,
+       -- Lowercased hashtag without leading `#`.
+       tag TEXT NOT NULL,
+       PRIMARY KEY (event_id, tag) ON CONFLICT IGNORE
+);
+
+CREATE INDEX hashtags_tag ON hashtags (tag);
@@ -0,0 +1,2 @@
+DROP INDEX textnotes_expiration;
+ALTER TABLE textnotes DROP COLUMN expiration;
@@ -0,0 +1,4 @@
+-- Time (Unix timestamp) after which the text note should be removed according to NIP-40.
+ALTER TABLE textnotes ADD COLUMN expiration INTEGER NULL;
+
+CREATE INDEX textnotes_expiration ON textnotes (expiration) WHERE expiration IS NOT NULL;
@@ -0,0 +1,30 @@
+-- Events published by us, kept until they are delivered to relays.
+CREATE TABLE outbox (
+       -- Event id.
+       event_id BLOB NOT NULL PRIMARY KEY,
+       -- Original event JSON.
+       event TEXT NOT NULL,
+       -- Number of delivery attempts so far.
+       attempts INTEGER NOT NULL DEFAULT 0,
+       -- Timestamp of next delivery attempt.
+       next_attempt TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+       -- Timestamp of when the event was first accepted by a relay. NULL if not yet.
+       delivered TEXT NULL,
+       -- Timestamp of when the event was published.
+       created TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Delivery status of published events for every relay.
+CREATE TABLE outbox_relays (
+       -- Event id.
+       event_id BLOB NOT NULL,
+       -- Relay URL.
+       relay TEXT NOT NULL,
+       -- Whether the relay accepted the event. NULL if it has not responded yet.
+       accepted INTEGER NULL,
+       -- Message that came from the relay together with its response.
+       message TEXT NULL,
+       -- Timestamp of the relay's response.
+       responded TEXT NULL,
+       PRIMARY KEY (event_id, relay) ON CONFLICT IGNORE
+);
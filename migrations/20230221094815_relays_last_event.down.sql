@@ -0,0 +1 @@
+ALTER TABLE relays DROP COLUMN last_event_at;
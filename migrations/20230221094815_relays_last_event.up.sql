@@ -0,0 +1,2 @@
+-- Date and time when an event was last received from the relay.
+ALTER TABLE relays ADD COLUMN last_event_at TEXT NULL;
@@ -0,0 +1 @@
+ALTER TABLE relays DROP COLUMN fetched_at;
@@ -0,0 +1,2 @@
+-- Date and time when relay information (NIP-11) was last fetched, successfully or not.
+ALTER TABLE relays ADD COLUMN fetched_at TEXT NULL;
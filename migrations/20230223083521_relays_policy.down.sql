@@ -0,0 +1,2 @@
+ALTER TABLE relays DROP COLUMN write;
+ALTER TABLE relays DROP COLUMN read;
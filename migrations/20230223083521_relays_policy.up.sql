@@ -0,0 +1,4 @@
+-- Whether events are requested from the relay.
+ALTER TABLE relays ADD COLUMN read INTEGER NOT NULL DEFAULT 1;
+-- Whether our events are published to the relay.
+ALTER TABLE relays ADD COLUMN write INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,9 @@
+-- Latest NIP-65 relay lists (kind 10002) of other users.
+CREATE TABLE "relay_lists" (
+       -- Pubkey of the author of the relay list.
+       author BLOB PRIMARY KEY,
+       -- Creation time of the event, older lists never replace newer ones.
+       created_at INTEGER NOT NULL,
+       -- The complete event as JSON.
+       event TEXT NOT NULL
+);
@@ -0,0 +1,16 @@
+-- Events that concern us, e. g. mentions and replies. Every event is notified only once.
+CREATE TABLE "notifications" (
+       -- Event id.
+       event_id BLOB PRIMARY KEY ON CONFLICT IGNORE,
+       -- What the event does to us: 0 = mention, 1 = reply.
+       kind INTEGER NOT NULL,
+       -- Pubkey of the author of the event.
+       author BLOB NOT NULL,
+       -- Date and time when the event was received.
+       created TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Authors whose events never cause a desktop notification.
+CREATE TABLE "notification_mutes" (
+       author BLOB PRIMARY KEY ON CONFLICT IGNORE
+);
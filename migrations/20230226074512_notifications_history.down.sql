@@ -0,0 +1,5 @@
+DROP INDEX notifications_created_at;
+ALTER TABLE notifications DROP COLUMN read;
+ALTER TABLE notifications DROP COLUMN created_at;
+ALTER TABLE notifications DROP COLUMN content;
+ALTER TABLE notifications DROP COLUMN target;
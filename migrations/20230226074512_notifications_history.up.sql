@@ -0,0 +1,12 @@
+-- Notifications are kept as history of everything that concerns us.
+-- Kinds: 0 = mention, 1 = reply, 2 = reaction, 3 = zap, 4 = new follower.
+-- Our event that the notification relates to, e. g. reacted note.
+ALTER TABLE notifications ADD COLUMN target BLOB NULL;
+-- Reaction, zapped amount in sats or snippet of the note.
+ALTER TABLE notifications ADD COLUMN content TEXT NOT NULL DEFAULT '';
+-- Creation time of the event.
+ALTER TABLE notifications ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0;
+-- Whether user has seen the notification.
+ALTER TABLE notifications ADD COLUMN read INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX notifications_created_at ON notifications (created_at);
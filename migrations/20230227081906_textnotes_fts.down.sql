@@ -0,0 +1,3 @@
+DROP TABLE muted_pubkeys;
+DROP TRIGGER textnotes_fts_delete;
+DROP TABLE textnotes_fts;
@@ -0,0 +1,30 @@
+-- Full-text index of text notes, rowid is the rowid of the note in textnotes.
+CREATE VIRTUAL TABLE textnotes_fts USING fts5(
+       -- Content of the note.
+       content,
+       -- Name of the author at the time of indexing.
+       author_name,
+       -- Pubkey of the author as hex, to update the name.
+       author UNINDEXED
+);
+
+-- Index the notes stored so far.
+INSERT INTO textnotes_fts (rowid, content, author_name, author)
+SELECT t.rowid,
+       json_extract(t.event, '$.content'),
+       json_extract(json_extract(m.event, '$.content'), '$.name'),
+       json_extract(t.event, '$.pubkey')
+FROM textnotes t
+LEFT JOIN metadata m ON lower(hex(m.author)) = json_extract(t.event, '$.pubkey');
+
+-- Removed notes must not be found.
+CREATE TRIGGER textnotes_fts_delete AFTER DELETE ON textnotes BEGIN
+       DELETE FROM textnotes_fts WHERE rowid = old.rowid;
+END;
+
+-- Authors whose notes are not displayed.
+CREATE TABLE "muted_pubkeys" (
+       pubkey BLOB PRIMARY KEY ON CONFLICT IGNORE,
+       -- Date and time of muting.
+       created TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
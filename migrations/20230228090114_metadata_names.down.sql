@@ -0,0 +1,4 @@
+DROP TABLE contacts;
+ALTER TABLE metadata DROP COLUMN nip05;
+ALTER TABLE metadata DROP COLUMN display_name;
+ALTER TABLE metadata DROP COLUMN name;
@@ -0,0 +1,17 @@
+-- Parsed fields of metadata, so that users can be searched for.
+ALTER TABLE metadata ADD COLUMN name TEXT NULL;
+ALTER TABLE metadata ADD COLUMN display_name TEXT NULL;
+ALTER TABLE metadata ADD COLUMN nip05 TEXT NULL;
+
+UPDATE metadata SET
+  name = json_extract(json_extract(event, '$.content'), '$.name'),
+  display_name = json_extract(json_extract(event, '$.content'), '$.display_name'),
+  nip05 = json_extract(json_extract(event, '$.content'), '$.nip05')
+WHERE json_valid(json_extract(event, '$.content'));
+
+-- Users we follow.
+CREATE TABLE "contacts" (
+       pubkey BLOB PRIMARY KEY ON CONFLICT IGNORE,
+       -- Date and time when we started following.
+       created TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,10 @@
+-- Long-form articles (NIP-23). Only the latest version of every
+-- article is kept.
+CREATE TABLE "articles" (
+       author BLOB NOT NULL,
+       -- Value of `d` tag.
+       identifier TEXT NOT NULL,
+       created_at INTEGER NOT NULL,
+       event TEXT NOT NULL,
+       PRIMARY KEY (author, identifier)
+);
@@ -0,0 +1,9 @@
+-- Notes and users we have reported (NIP-56). Target is either ID of
+-- the note or public key of the user.
+CREATE TABLE "reported" (
+       target BLOB NOT NULL PRIMARY KEY ON CONFLICT REPLACE,
+       report_type TEXT NOT NULL,
+       -- Whether the target should not be displayed anymore.
+       hidden BOOLEAN NOT NULL,
+       reported_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
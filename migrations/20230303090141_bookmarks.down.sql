@@ -0,0 +1,2 @@
+DROP TABLE "own_lists";
+DROP TABLE "bookmarks";
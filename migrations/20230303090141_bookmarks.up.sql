@@ -0,0 +1,16 @@
+-- Notes in our bookmark list (NIP-51). Removed bookmarks are kept,
+-- with `bookmarked` unset, to know when they have been removed.
+CREATE TABLE "bookmarks" (
+       event_id BLOB NOT NULL PRIMARY KEY,
+       bookmarked BOOLEAN NOT NULL,
+       -- When the note was bookmarked or removed, in seconds since epoch.
+       changed_at INTEGER NOT NULL
+);
+
+-- The newest known version of our replaceable lists, by their kind.
+-- Tags and content we do not understand are published again unchanged.
+CREATE TABLE "own_lists" (
+       kind INTEGER NOT NULL PRIMARY KEY ON CONFLICT REPLACE,
+       event TEXT NOT NULL,
+       created_at INTEGER NOT NULL
+);
@@ -0,0 +1 @@
+DROP TABLE "unmuted_pubkeys";
@@ -0,0 +1,7 @@
+-- Authors who have been unmuted, here or by another client, and when.
+-- Needed to tell whether our mute list (NIP-51) is newer than unmuting.
+CREATE TABLE "unmuted_pubkeys" (
+       pubkey BLOB NOT NULL PRIMARY KEY ON CONFLICT REPLACE,
+       -- In seconds since epoch.
+       unmuted_at INTEGER NOT NULL
+);
@@ -0,0 +1,6 @@
+-- When maintenance tasks, such as pruning of old events, have last run.
+CREATE TABLE "maintenance" (
+       task TEXT NOT NULL PRIMARY KEY ON CONFLICT REPLACE,
+       -- In seconds since epoch.
+       last_run INTEGER NOT NULL
+);
@@ -0,0 +1,5 @@
+-- Creation time of the stored metadata, older metadata never replace newer ones.
+ALTER TABLE metadata ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0;
+
+UPDATE metadata SET created_at = json_extract(event, '$.created_at')
+WHERE json_valid(event);
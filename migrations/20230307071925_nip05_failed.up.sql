@@ -0,0 +1,2 @@
+-- Date and time of latest failed NIP05 verification. NULL if the latest one succeeded.
+ALTER TABLE metadata ADD COLUMN nip05_failed TEXT NULL DEFAULT NULL;
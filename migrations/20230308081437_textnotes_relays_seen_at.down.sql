@@ -0,0 +1 @@
+ALTER TABLE textnotes_relays DROP COLUMN seen_at;
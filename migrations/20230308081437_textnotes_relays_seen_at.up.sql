@@ -0,0 +1,3 @@
+-- When the text note was first seen on the relay, in seconds since epoch.
+-- NULL for notes stored before it was recorded.
+ALTER TABLE textnotes_relays ADD COLUMN seen_at INTEGER NULL;
@@ -0,0 +1,13 @@
+-- Numbers of events received from relays over all sessions.
+CREATE TABLE relay_stats (
+       -- Relay URL.
+       url TEXT PRIMARY KEY,
+       -- Number of received events.
+       events_received INTEGER NOT NULL DEFAULT 0,
+       -- Size of received events as JSON.
+       bytes_received INTEGER NOT NULL DEFAULT 0,
+       -- When an event was last received, in seconds since epoch.
+       last_event_at INTEGER NULL,
+       -- Number of sessions in which events were received.
+       sessions INTEGER NOT NULL DEFAULT 0
+);
@@ -0,0 +1 @@
+-- Original forms of relay URLs are lost, there is nothing to revert.
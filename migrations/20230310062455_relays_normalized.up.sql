@@ -0,0 +1,170 @@
+-- Relay URLs are stored normalized: lowercase scheme and host, no default
+-- port, credentials, fragment or trailing slash in path, see
+-- `normalize_relay_url`. Those stored before are normalized here, rows
+-- of spellings of the same relay are merged, and those that cannot be
+-- relays are dropped.
+
+CREATE TEMPORARY TABLE relay_urls (
+       original TEXT PRIMARY KEY,
+       -- NULL if the original cannot be a relay.
+       normalized TEXT NULL
+);
+
+INSERT INTO relay_urls (original, normalized)
+WITH
+    stored (original) AS (
+        SELECT url FROM relays
+        UNION SELECT relay FROM textnotes_relays
+        UNION SELECT relay FROM outbox_relays
+        UNION SELECT url FROM relay_stats
+    ),
+    split AS (
+        SELECT original,
+               lower(substr(original, 1, instr(original, '://') + 2)) AS scheme,
+               substr(original, instr(original, '://') + 3) AS rest
+        FROM stored
+    ),
+    -- Fragment goes, authority ends where path or query starts.
+    unfragmented AS (
+        SELECT original, scheme,
+               CASE instr(rest, '#') WHEN 0 THEN rest ELSE substr(rest, 1, instr(rest, '#') - 1) END AS rest
+        FROM split
+    ),
+    bounded AS (
+        SELECT original, scheme, rest,
+               CASE
+                   WHEN instr(rest, '/') = 0 THEN instr(rest, '?')
+                   WHEN instr(rest, '?') = 0 THEN instr(rest, '/')
+                   ELSE min(instr(rest, '/'), instr(rest, '?'))
+               END AS stop
+        FROM unfragmented
+    ),
+    parts AS (
+        SELECT original, scheme,
+               CASE stop WHEN 0 THEN rest ELSE substr(rest, 1, stop - 1) END AS authority,
+               CASE stop WHEN 0 THEN '' ELSE substr(rest, stop) END AS tail
+        FROM bounded
+    ),
+    -- Credentials are whatever precedes the last `@`.
+    hosts AS (
+        SELECT original, scheme, tail,
+               lower(substr(authority, length(rtrim(authority, replace(authority, '@', ''))) + 1)) AS host
+        FROM parts
+    ),
+    ported AS (
+        SELECT original, scheme, tail,
+               CASE
+                   WHEN scheme = 'wss://' AND host LIKE '%:443' THEN substr(host, 1, length(host) - 4)
+                   WHEN scheme = 'ws://' AND host LIKE '%:80' THEN substr(host, 1, length(host) - 3)
+                   ELSE host
+               END AS host,
+               rtrim(CASE instr(host, ':') WHEN 0 THEN host ELSE substr(host, 1, instr(host, ':') - 1) END, '.') AS domain,
+               CASE instr(tail, '?') WHEN 0 THEN tail ELSE substr(tail, 1, instr(tail, '?') - 1) END AS path,
+               CASE instr(tail, '?') WHEN 0 THEN '' ELSE substr(tail, instr(tail, '?')) END AS query
+        FROM hosts
+    )
+SELECT original,
+       CASE
+           WHEN scheme NOT IN ('ws://', 'wss://') THEN NULL
+           -- IPv6 addresses are left as they are.
+           WHEN host LIKE '[%' THEN scheme || host || (CASE WHEN rtrim(path, '/') = '' THEN '/' ELSE rtrim(path, '/') END) || query
+           WHEN domain = '' OR domain GLOB '*[^a-z0-9.-]*' OR domain LIKE '.%' OR domain LIKE '%..%'
+                OR domain LIKE '-%' OR domain LIKE '%-' OR domain LIKE '%.-%' OR domain LIKE '%-.%' THEN NULL
+           ELSE scheme || host || (CASE WHEN rtrim(path, '/') = '' THEN '/' ELSE rtrim(path, '/') END) || query
+       END
+FROM ported;
+
+DELETE FROM relay_urls WHERE original = normalized;
+
+-- Rows of a relay spelled differently are merged into one: counters are
+-- summed, policies and responses to our events are kept if any has them.
+CREATE TEMPORARY TABLE merged_relays AS
+WITH
+    renamed AS (
+        SELECT coalesce(u.normalized, r.url) AS target, u.original IS NOT NULL AS moved, r.*
+        FROM relays r LEFT JOIN relay_urls u ON u.original = r.url
+        WHERE u.original IS NULL OR u.normalized IS NOT NULL
+    ),
+    -- Information of the spelling fetched last is kept.
+    ranked AS (
+        SELECT *, row_number() OVER (
+            PARTITION BY target ORDER BY fetched_at IS NULL, fetched_at DESC, updated DESC
+        ) AS n
+        FROM renamed
+        WHERE target IN (SELECT target FROM renamed GROUP BY target HAVING max(moved))
+    )
+SELECT g.target AS url, c.information, g.updated, g.last_event_at, g.fetched_at, g.read, g.write
+FROM (
+    SELECT target, max(updated) AS updated, max(last_event_at) AS last_event_at,
+           max(fetched_at) AS fetched_at, max(read) AS read, max(write) AS write
+    FROM ranked GROUP BY target
+) g
+JOIN ranked c ON c.target = g.target AND c.n = 1;
+
+DELETE FROM relays
+WHERE url IN (SELECT original FROM relay_urls) OR url IN (SELECT url FROM merged_relays);
+INSERT INTO relays (url, information, updated, last_event_at, fetched_at, read, write)
+SELECT url, information, updated, last_event_at, fetched_at, read, write FROM merged_relays;
+DROP TABLE merged_relays;
+
+-- A note is first seen on the relay when any spelling has first seen it.
+CREATE TEMPORARY TABLE merged_textnotes_relays AS
+WITH renamed AS (
+    SELECT coalesce(u.normalized, t.relay) AS target, u.original IS NOT NULL AS moved, t.*
+    FROM textnotes_relays t LEFT JOIN relay_urls u ON u.original = t.relay
+    WHERE u.original IS NULL OR u.normalized IS NOT NULL
+)
+SELECT textnote, target AS relay, min(seen_at) AS seen_at
+FROM renamed GROUP BY textnote, target HAVING max(moved);
+
+DELETE FROM textnotes_relays
+WHERE relay IN (SELECT original FROM relay_urls)
+   OR (textnote, relay) IN (SELECT textnote, relay FROM merged_textnotes_relays);
+INSERT INTO textnotes_relays (textnote, relay, seen_at)
+SELECT textnote, relay, seen_at FROM merged_textnotes_relays;
+DROP TABLE merged_textnotes_relays;
+
+-- Acceptance wins over rejection, which wins over no response yet; the
+-- message and time of the winning response stay.
+CREATE TEMPORARY TABLE merged_outbox_relays AS
+WITH
+    renamed AS (
+        SELECT coalesce(u.normalized, o.relay) AS target, u.original IS NOT NULL AS moved, o.*
+        FROM outbox_relays o LEFT JOIN relay_urls u ON u.original = o.relay
+        WHERE u.original IS NULL OR u.normalized IS NOT NULL
+    ),
+    ranked AS (
+        SELECT *, row_number() OVER (
+            PARTITION BY event_id, target ORDER BY accepted IS NULL, accepted DESC, responded DESC
+        ) AS n
+        FROM renamed
+        WHERE (event_id, target) IN (
+            SELECT event_id, target FROM renamed GROUP BY event_id, target HAVING max(moved)
+        )
+    )
+SELECT event_id, target AS relay, accepted, message, responded FROM ranked WHERE n = 1;
+
+DELETE FROM outbox_relays
+WHERE relay IN (SELECT original FROM relay_urls)
+   OR (event_id, relay) IN (SELECT event_id, relay FROM merged_outbox_relays);
+INSERT INTO outbox_relays (event_id, relay, accepted, message, responded)
+SELECT event_id, relay, accepted, message, responded FROM merged_outbox_relays;
+DROP TABLE merged_outbox_relays;
+
+CREATE TEMPORARY TABLE merged_relay_stats AS
+WITH renamed AS (
+    SELECT coalesce(u.normalized, s.url) AS target, u.original IS NOT NULL AS moved, s.*
+    FROM relay_stats s LEFT JOIN relay_urls u ON u.original = s.url
+    WHERE u.original IS NULL OR u.normalized IS NOT NULL
+)
+SELECT target AS url, sum(events_received) AS events_received, sum(bytes_received) AS bytes_received,
+       max(last_event_at) AS last_event_at, sum(sessions) AS sessions
+FROM renamed GROUP BY target HAVING max(moved);
+
+DELETE FROM relay_stats
+WHERE url IN (SELECT original FROM relay_urls) OR url IN (SELECT url FROM merged_relay_stats);
+INSERT INTO relay_stats (url, events_received, bytes_received, last_event_at, sessions)
+SELECT url, events_received, bytes_received, last_event_at, sessions FROM merged_relay_stats;
+DROP TABLE merged_relay_stats;
+
+DROP TABLE relay_urls;
@@ -0,0 +1,3 @@
+DROP TABLE handler_recommendations;
+DROP TABLE handler_kinds;
+DROP TABLE handlers;
@@ -0,0 +1,35 @@
+-- Applications which can display events of other kinds (NIP-89).
+CREATE TABLE handlers (
+       -- Public key of the application.
+       author BLOB NOT NULL,
+       -- Identifier of the handler, from its `d` tag.
+       identifier TEXT NOT NULL,
+       -- The latest handler information event as JSON.
+       event TEXT NOT NULL,
+       created_at INTEGER NOT NULL,
+       PRIMARY KEY (author, identifier)
+);
+
+-- Kinds of events which handlers can display.
+CREATE TABLE handler_kinds (
+       author BLOB NOT NULL,
+       identifier TEXT NOT NULL,
+       kind INTEGER NOT NULL,
+       PRIMARY KEY (author, identifier, kind),
+       FOREIGN KEY (author, identifier) REFERENCES handlers (author, identifier) ON DELETE CASCADE
+);
+
+CREATE INDEX handler_kinds_kind ON handler_kinds (kind);
+
+-- Handlers recommended by users for kinds of events.
+CREATE TABLE handler_recommendations (
+       -- Who recommends the handler.
+       recommender BLOB NOT NULL,
+       kind INTEGER NOT NULL,
+       -- The recommended handler, which may not be known yet.
+       author BLOB NOT NULL,
+       identifier TEXT NOT NULL,
+       -- Creation time of the recommendation event.
+       created_at INTEGER NOT NULL,
+       PRIMARY KEY (recommender, kind, author, identifier)
+);
@@ -0,0 +1,20 @@
+-- Reactions (NIP-25) and zaps (NIP-57) of events, so that notes show
+-- them even when they have arrived before the notes themselves.
+CREATE TABLE reactions (
+       -- Reaction or zap receipt event id.
+       id BLOB PRIMARY KEY,
+       -- Event reacted to or zapped.
+       target BLOB NOT NULL,
+       -- 7 for reactions, 9735 for zap receipts.
+       kind INTEGER NOT NULL,
+       -- Content of reaction, empty for zaps.
+       content TEXT NOT NULL,
+       -- Custom emoji the reaction consists of (NIP-30).
+       emoji_shortcode TEXT NULL,
+       emoji_url TEXT NULL,
+       -- Zapped amount in sats.
+       sats INTEGER NULL,
+       created_at INTEGER NOT NULL
+);
+
+CREATE INDEX reactions_target ON reactions (target);
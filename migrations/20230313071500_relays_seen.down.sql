@@ -0,0 +1,2 @@
+ALTER TABLE relays DROP COLUMN last_seen_at;
+ALTER TABLE relays DROP COLUMN first_seen_at;
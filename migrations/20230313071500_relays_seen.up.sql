@@ -0,0 +1,5 @@
+-- When the relay first became known and when it was last seen, either
+-- delivering events or recommended in them, in seconds since epoch.
+-- NULL for relays stored before it was recorded.
+ALTER TABLE relays ADD COLUMN first_seen_at INTEGER NULL;
+ALTER TABLE relays ADD COLUMN last_seen_at INTEGER NULL;
@@ -0,0 +1,2 @@
+DROP TABLE contact_list_entries;
+DROP TABLE contact_lists;
@@ -0,0 +1,18 @@
+-- Newest contact lists (NIP-02) of all users, so that profiles show
+-- whom they follow and who of known users follows them.
+CREATE TABLE contact_lists (
+       author BLOB PRIMARY KEY,
+       -- Creation time of the stored list, in seconds since epoch.
+       created_at INTEGER NOT NULL
+);
+
+-- Users in the stored contact lists.
+CREATE TABLE contact_list_entries (
+       -- Author of the contact list.
+       author BLOB NOT NULL,
+       -- User followed by the author.
+       pubkey BLOB NOT NULL,
+       PRIMARY KEY (author, pubkey)
+);
+
+CREATE INDEX contact_list_entries_pubkey ON contact_list_entries (pubkey);
@@ -0,0 +1,18 @@
+-- Newest pin lists (NIP-51) of all users, so that profiles show notes
+-- their authors want to showcase.
+CREATE TABLE pin_lists (
+       author BLOB PRIMARY KEY,
+       -- Tags and content we do not understand are published again
+       -- unchanged when we change our own list.
+       event TEXT NOT NULL,
+       created_at INTEGER NOT NULL
+);
+
+-- Notes in the stored pin lists.
+CREATE TABLE pins (
+       author BLOB NOT NULL,
+       event_id BLOB NOT NULL,
+       -- Order of the note in the list.
+       position INTEGER NOT NULL,
+       PRIMARY KEY (author, event_id)
+);
@@ -0,0 +1,17 @@
+-- Newest stored versions of replaceable events (kinds 0, 3 and 10000 to
+-- 19999) and parameterized replaceable events (30000 to 39999), whose
+-- older versions are removed from textnotes. Events stored before this
+-- table existed are not in it.
+CREATE TABLE replaceable_events (
+       author BLOB NOT NULL,
+       kind INTEGER NOT NULL,
+       -- Identifier (d tag) of parameterized replaceable events, empty
+       -- for the others.
+       d_tag TEXT NOT NULL,
+       event_id BLOB NOT NULL,
+       -- Creation time of the version, in seconds since epoch. Older
+       -- versions arriving later are not stored, even after the newest
+       -- one has been pruned from textnotes.
+       created_at INTEGER NOT NULL,
+       PRIMARY KEY (author, kind, d_tag)
+);
@@ -0,0 +1 @@
+ALTER TABLE textnotes DROP COLUMN language;
@@ -0,0 +1,3 @@
+-- ISO 639-3 code of language detected in text notes, such as eng, NULL
+-- if the note is too short or detection is unsure.
+ALTER TABLE textnotes ADD COLUMN language TEXT NULL;
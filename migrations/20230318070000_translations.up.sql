@@ -0,0 +1,12 @@
+-- Translations of notes obtained from the configured translation server,
+-- so that the content of a note is sent there only once.
+CREATE TABLE translations (
+       event_id BLOB NOT NULL,
+       -- Language into which the note has been translated, as the
+       -- server calls it.
+       language TEXT NOT NULL,
+       translation TEXT NOT NULL,
+       -- When the translation was obtained, in seconds since epoch.
+       translated_at INTEGER NOT NULL,
+       PRIMARY KEY (event_id, language)
+);
@@ -0,0 +1,23 @@
+-- Notes being written, saved every few seconds and when the composer is
+-- closed, so that they are not lost if it is closed by accident or
+-- Gnostique crashes.
+CREATE TABLE drafts (
+       -- Chosen by the composer when it starts writing the note.
+       id INTEGER PRIMARY KEY NOT NULL,
+       subject TEXT NOT NULL,
+       content TEXT NOT NULL,
+       -- Note being replied to, as JSON, if any.
+       reply_to TEXT,
+       -- ID of the note being replied to, to find drafts of a reply.
+       reply_to_id BLOB,
+       -- Note being quoted, as JSON, if any.
+       quote TEXT,
+       -- ID of the note being quoted, to find drafts of a quote.
+       quote_id BLOB,
+       -- Relays where the quoted note has been seen, as JSON array.
+       quote_relays TEXT NOT NULL DEFAULT '[]',
+       -- When the draft was last saved, in seconds since epoch.
+       updated_at INTEGER NOT NULL
+);
+
+CREATE INDEX drafts_updated_at ON drafts (updated_at);
@@ -0,0 +1,10 @@
+-- Labels we have given to authors, such as nsfw or spam, by reporting
+-- them or by marking them ourselves. Their notes are hidden behind a bar.
+-- Labels are never published.
+CREATE TABLE labels (
+       author BLOB NOT NULL,
+       label TEXT NOT NULL,
+       -- When the author was labelled, in seconds since epoch.
+       created_at INTEGER NOT NULL,
+       PRIMARY KEY (author, label)
+);
@@ -0,0 +1,14 @@
+-- Every distinct metadata (kind 0) of authors we have seen, so that
+-- changes of their names or NIP-05 can be looked at. `metadata` keeps
+-- only the latest of them.
+CREATE TABLE metadata_history (
+       author BLOB NOT NULL,
+       created_at INTEGER NOT NULL,
+       -- The whole event, as JSON.
+       event TEXT NOT NULL,
+       PRIMARY KEY (author, created_at)
+);
+
+INSERT INTO metadata_history (author, created_at, event)
+SELECT author, created_at, event FROM metadata
+WHERE json_valid(event);
@@ -0,0 +1 @@
+DROP TABLE nip05_relays;
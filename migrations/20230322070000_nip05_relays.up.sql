@@ -0,0 +1,8 @@
+-- Relays listed for authors in `/.well-known/nostr.json` of their
+-- verified NIP-05, as JSON array of URLs.
+CREATE TABLE nip05_relays (
+       author BLOB PRIMARY KEY NOT NULL,
+       relays TEXT NOT NULL,
+       -- When the document was fetched, in seconds since epoch.
+       fetched_at INTEGER NOT NULL
+);
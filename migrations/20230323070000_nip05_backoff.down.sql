@@ -0,0 +1,2 @@
+ALTER TABLE metadata DROP COLUMN nip05_mismatch;
+ALTER TABLE metadata DROP COLUMN nip05_attempts;
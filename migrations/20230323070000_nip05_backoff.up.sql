@@ -0,0 +1,7 @@
+-- Failed NIP-05 verifications in a row, they are retried less and less often.
+ALTER TABLE metadata ADD COLUMN nip05_attempts INTEGER NOT NULL DEFAULT 0;
+-- Whether the latest failure has been the identifier leading to another
+-- pubkey, rather than its domain being unreachable.
+ALTER TABLE metadata ADD COLUMN nip05_mismatch BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE metadata SET nip05_attempts = 1 WHERE nip05_failed IS NOT NULL;
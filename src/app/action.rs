@@ -1,41 +1,395 @@
+use std::str::FromStr;
+
 use gtk::gdk;
-use gtk::gio::SimpleActionGroup;
-use gtk::prelude::DisplayExt;
+use gtk::gio::{SimpleAction, SimpleActionGroup};
+use gtk::glib::VariantTy;
+use gtk::prelude::{ActionMapExt, Cast, DisplayExt, GtkApplicationExt, GtkWindowExt, ToVariant};
+use nostr_sdk::prelude::{EventId, XOnlyPublicKey};
 use relm4::actions::{RelmAction, RelmActionGroup};
 use relm4::AsyncComponentSender;
 
+use crate::config::Config;
+use crate::nostr::ReportSubject;
+use crate::ui::lane::LaneKind;
 use crate::ui::main::{Main, MainInput};
+use crate::ui::toast::Toast;
 
-relm4::new_action_group!(pub AuthorActionGroup, "author");
-relm4::new_stateful_action!(pub Copy, AuthorActionGroup, "copy-hex", String, ());
+relm4::new_action_group!(pub MainMenuActionGroup, "main");
+relm4::new_stateless_action!(pub EditProfile, MainMenuActionGroup, "profile");
+relm4::new_stateless_action!(pub ClearCache, MainMenuActionGroup, "clear-cache");
+relm4::new_stateless_action!(pub ShowOutbox, MainMenuActionGroup, "outbox");
+relm4::new_stateless_action!(pub ShowRelays, MainMenuActionGroup, "relays");
+relm4::new_stateless_action!(pub ExportBackup, MainMenuActionGroup, "export-backup");
+relm4::new_stateless_action!(pub ShowArchive, MainMenuActionGroup, "archive");
+relm4::new_stateless_action!(pub ImportFollows, MainMenuActionGroup, "import-follows");
+relm4::new_stateless_action!(pub ShowOpen, MainMenuActionGroup, "open");
+relm4::new_stateless_action!(pub ShowPrune, MainMenuActionGroup, "prune");
+relm4::new_stateless_action!(pub ShowSettings, MainMenuActionGroup, "settings");
+relm4::new_stateless_action!(pub ShowShortcuts, MainMenuActionGroup, "shortcuts");
+relm4::new_stateless_action!(pub OpenLogFolder, MainMenuActionGroup, "open-log-folder");
+relm4::new_stateless_action!(pub Quit, MainMenuActionGroup, "quit");
+relm4::new_stateless_action!(pub ShowNotifications, MainMenuActionGroup, "show-notifications");
+relm4::new_stateless_action!(pub ShowSearch, MainMenuActionGroup, "search");
+relm4::new_stateless_action!(pub ShowBookmarks, MainMenuActionGroup, "show-bookmarks");
+relm4::new_stateless_action!(pub ShowPeriod, MainMenuActionGroup, "show-period");
+relm4::new_stateful_action!(pub Copy, MainMenuActionGroup, "copy", String, ());
+relm4::new_stateful_action!(pub DesktopNotifications, MainMenuActionGroup, "notifications", (), bool);
+relm4::new_stateful_action!(pub RelativeTime, MainMenuActionGroup, "relative-time", (), bool);
+relm4::new_stateful_action!(pub Clock24h, MainMenuActionGroup, "clock-24h", (), bool);
+relm4::new_stateful_action!(pub MuteNotifications, MainMenuActionGroup, "mute-notifications", String, ());
+relm4::new_stateful_action!(pub UnmuteNotifications, MainMenuActionGroup, "unmute-notifications", String, ());
+relm4::new_stateful_action!(pub Mute, MainMenuActionGroup, "mute", String, ());
+relm4::new_stateful_action!(pub Unmute, MainMenuActionGroup, "unmute", String, ());
+relm4::new_stateful_action!(pub ReportNote, MainMenuActionGroup, "report-note", (String, String), ());
+relm4::new_stateful_action!(pub ReportUser, MainMenuActionGroup, "report-user", String, ());
+relm4::new_stateful_action!(pub LabelAuthor, MainMenuActionGroup, "label-author", (String, String), ());
+relm4::new_stateful_action!(pub UnlabelAuthor, MainMenuActionGroup, "unlabel-author", (String, String), ());
+relm4::new_stateful_action!(pub ShowFollowing, MainMenuActionGroup, "show-following", String, ());
+relm4::new_stateful_action!(pub ShowFollowers, MainMenuActionGroup, "show-followers", String, ());
+relm4::new_stateful_action!(pub DeleteNote, MainMenuActionGroup, "delete-note", String, ());
+relm4::new_stateful_action!(pub PinNote, MainMenuActionGroup, "pin-note", String, ());
+relm4::new_stateful_action!(pub UnpinNote, MainMenuActionGroup, "unpin-note", String, ());
 
-/// Creates a GTK action group for author-related actions.
-pub fn make_author_actions() -> SimpleActionGroup {
-    let group = RelmActionGroup::<AuthorActionGroup>::new();
+pub fn make_main_menu_actions(
+    sender: AsyncComponentSender<Main>,
+    config: &Config,
+) -> SimpleActionGroup {
+    let group = RelmActionGroup::<MainMenuActionGroup>::new();
 
-    group.add_action(&copy_action());
+    group.add_action(&profile_action(sender.clone()));
+    group.add_action(&clear_cache_action(sender.clone()));
+    group.add_action(&outbox_action(sender.clone()));
+    group.add_action(&relays_action(sender.clone()));
+    group.add_action(&export_backup_action(sender.clone()));
+    group.add_action(&archive_action(sender.clone()));
+    group.add_action(&import_follows_action(sender.clone()));
+    group.add_action(&open_action(sender.clone()));
+    group.add_action(&prune_action(sender.clone()));
+    group.add_action(&settings_action(sender.clone()));
+    group.add_action(&shortcuts_action(sender.clone()));
+    group.add_action(&open_log_folder_action(sender.clone()));
+    group.add_action(&quit_action(sender.clone()));
+    group.add_action(&show_notifications_action(sender.clone()));
+    group.add_action(&search_action(sender.clone()));
+    group.add_action(&show_bookmarks_action(sender.clone()));
+    group.add_action(&show_period_action(sender.clone()));
+    group.add_action(&mute_action(sender.clone()));
+    group.add_action(&unmute_action(sender.clone()));
+    group.add_action(&report_note_action(sender.clone()));
+    group.add_action(&report_user_action(sender.clone()));
+    group.add_action(&label_author_action(sender.clone()));
+    group.add_action(&unlabel_author_action(sender.clone()));
+    group.add_action(&show_following_action(sender.clone()));
+    group.add_action(&show_followers_action(sender.clone()));
+    group.add_action(&delete_note_action(sender.clone()));
+    group.add_action(&pin_note_action(sender.clone()));
+    group.add_action(&unpin_note_action(sender.clone()));
+    group.add_action(&notifications_action(
+        sender.clone(),
+        config.general.desktop_notifications,
+    ));
+    group.add_action(&relative_time_action(sender.clone(), config.time.relative));
+    group.add_action(&clock_24h_action(sender.clone(), config.time.clock_24h));
+    group.add_action(&mute_notifications_action(sender.clone()));
+    group.add_action(&unmute_notifications_action(sender.clone()));
+    group.add_action(&copy_action(sender));
     group.into_action_group()
 }
 
-/// Copies a textual value into clipboard.
-fn copy_action() -> RelmAction<Copy> {
-    RelmAction::new_with_target_value(|_, string: String| {
+/// Updates check marks of toggles in main menu after `config`
+/// has been changed somewhere else, such as in settings dialog.
+pub fn sync_main_menu_actions(group: &SimpleActionGroup, config: &Config) {
+    let toggles = [
+        ("notifications", config.general.desktop_notifications),
+        ("relative-time", config.time.relative),
+        ("clock-24h", config.time.clock_24h),
+    ];
+
+    for (name, value) in toggles {
+        if let Some(action) = group
+            .lookup_action(name)
+            .and_then(|a| a.downcast::<SimpleAction>().ok())
+        {
+            action.set_state(&value.to_variant());
+        }
+    }
+}
+
+/// Registers actions of the whole application, such as those
+/// activated by clicking on desktop notifications.
+pub fn register_app_actions(sender: AsyncComponentSender<Main>) {
+    let app = relm4::main_application();
+
+    let show_window = SimpleAction::new("show-window", None);
+    show_window.connect_activate(|_, _| present_window());
+    app.add_action(&show_window);
+
+    let quit = SimpleAction::new("quit", None);
+    quit.connect_activate({
+        let sender = sender.clone();
+        move |_, _| sender.input(MainInput::Quit)
+    });
+    app.add_action(&quit);
+
+    let open_thread = SimpleAction::new("open-thread", Some(VariantTy::STRING));
+    open_thread.connect_activate(move |_, target| {
+        let id = target
+            .and_then(|t| t.get::<String>())
+            .and_then(|s| EventId::from_hex(s).ok());
+
+        if let Some(id) = id {
+            present_window();
+            sender.input(MainInput::OpenThread(id, None));
+        }
+    });
+    app.add_action(&open_thread);
+}
+
+/// Shows the main window, also if it has been hidden to keep running without it.
+pub fn present_window() {
+    let app = relm4::main_application();
+    if let Some(window) = app
+        .active_window()
+        .or_else(|| app.windows().into_iter().next())
+    {
+        window.present();
+    }
+}
+
+/// Turns desktop notifications on and off.
+fn notifications_action(
+    sender: AsyncComponentSender<Main>,
+    enabled: bool,
+) -> RelmAction<DesktopNotifications> {
+    RelmAction::new_stateful(&enabled, move |_, enabled: &mut bool| {
+        *enabled = !*enabled;
+        sender.input(MainInput::SetDesktopNotifications(*enabled));
+    })
+}
+
+/// Switches between relative and absolute times of notes.
+fn relative_time_action(
+    sender: AsyncComponentSender<Main>,
+    relative: bool,
+) -> RelmAction<RelativeTime> {
+    RelmAction::new_stateful(&relative, move |_, relative: &mut bool| {
+        *relative = !*relative;
+        sender.input(MainInput::SetRelativeTime(*relative));
+    })
+}
+
+/// Switches between 24-hour and 12-hour clock.
+fn clock_24h_action(sender: AsyncComponentSender<Main>, clock_24h: bool) -> RelmAction<Clock24h> {
+    RelmAction::new_stateful(&clock_24h, move |_, clock_24h: &mut bool| {
+        *clock_24h = !*clock_24h;
+        sender.input(MainInput::SetClock24h(*clock_24h));
+    })
+}
+
+fn mute_notifications_action(sender: AsyncComponentSender<Main>) -> RelmAction<MuteNotifications> {
+    RelmAction::new_with_target_value(move |_, pubkey: String| {
+        if let Ok(pubkey) = XOnlyPublicKey::from_str(&pubkey) {
+            sender.input(MainInput::MuteNotifications(pubkey, true));
+        }
+    })
+}
+
+fn unmute_notifications_action(
+    sender: AsyncComponentSender<Main>,
+) -> RelmAction<UnmuteNotifications> {
+    RelmAction::new_with_target_value(move |_, pubkey: String| {
+        if let Ok(pubkey) = XOnlyPublicKey::from_str(&pubkey) {
+            sender.input(MainInput::MuteNotifications(pubkey, false));
+        }
+    })
+}
+
+/// Copies a textual value into clipboard and lets user know.
+fn copy_action(sender: AsyncComponentSender<Main>) -> RelmAction<Copy> {
+    RelmAction::new_with_target_value(move |_, string: String| {
         let display = gdk::Display::default().unwrap();
         let clipboard = display.clipboard();
         clipboard.set_text(&string);
+        sender.input(MainInput::Toast(Toast::info("Copied")));
     })
 }
 
-relm4::new_action_group!(pub MainMenuActionGroup, "main");
-relm4::new_stateless_action!(pub EditProfile, MainMenuActionGroup, "profile");
+fn profile_action(sender: AsyncComponentSender<Main>) -> RelmAction<EditProfile> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::EditProfile))
+}
 
-pub fn make_main_menu_actions(sender: AsyncComponentSender<Main>) -> SimpleActionGroup {
-    let group = RelmActionGroup::<MainMenuActionGroup>::new();
+fn outbox_action(sender: AsyncComponentSender<Main>) -> RelmAction<ShowOutbox> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::ShowOutbox))
+}
 
-    group.add_action(&profile_action(sender));
-    group.into_action_group()
+fn relays_action(sender: AsyncComponentSender<Main>) -> RelmAction<ShowRelays> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::ShowRelays))
 }
 
-fn profile_action(sender: AsyncComponentSender<Main>) -> RelmAction<EditProfile> {
-    RelmAction::new_stateless(move |_| sender.input(MainInput::EditProfile))
+fn export_backup_action(sender: AsyncComponentSender<Main>) -> RelmAction<ExportBackup> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::ExportBackup))
+}
+
+fn archive_action(sender: AsyncComponentSender<Main>) -> RelmAction<ShowArchive> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::ShowArchive))
+}
+
+fn import_follows_action(sender: AsyncComponentSender<Main>) -> RelmAction<ImportFollows> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::ShowImportFollows))
+}
+
+fn open_action(sender: AsyncComponentSender<Main>) -> RelmAction<ShowOpen> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::ShowOpen))
+}
+
+fn prune_action(sender: AsyncComponentSender<Main>) -> RelmAction<ShowPrune> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::ShowPrune))
+}
+
+fn settings_action(sender: AsyncComponentSender<Main>) -> RelmAction<ShowSettings> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::ShowSettings))
+}
+
+fn shortcuts_action(sender: AsyncComponentSender<Main>) -> RelmAction<ShowShortcuts> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::ShowShortcuts))
+}
+
+fn quit_action(sender: AsyncComponentSender<Main>) -> RelmAction<Quit> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::Quit))
+}
+
+fn open_log_folder_action(sender: AsyncComponentSender<Main>) -> RelmAction<OpenLogFolder> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::OpenLogFolder))
+}
+
+fn show_notifications_action(sender: AsyncComponentSender<Main>) -> RelmAction<ShowNotifications> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::OpenLane(LaneKind::Notifications)))
+}
+
+fn search_action(sender: AsyncComponentSender<Main>) -> RelmAction<ShowSearch> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::OpenLane(LaneKind::Search)))
+}
+
+fn show_bookmarks_action(sender: AsyncComponentSender<Main>) -> RelmAction<ShowBookmarks> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::OpenLane(LaneKind::Bookmarks)))
+}
+
+fn show_period_action(sender: AsyncComponentSender<Main>) -> RelmAction<ShowPeriod> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::OpenPeriod))
+}
+
+fn mute_action(sender: AsyncComponentSender<Main>) -> RelmAction<Mute> {
+    RelmAction::new_with_target_value(move |_, pubkey: String| {
+        if let Ok(pubkey) = XOnlyPublicKey::from_str(&pubkey) {
+            sender.input(MainInput::Mute(pubkey, true));
+        }
+    })
+}
+
+fn unmute_action(sender: AsyncComponentSender<Main>) -> RelmAction<Unmute> {
+    RelmAction::new_with_target_value(move |_, pubkey: String| {
+        if let Ok(pubkey) = XOnlyPublicKey::from_str(&pubkey) {
+            sender.input(MainInput::Mute(pubkey, false));
+        }
+    })
+}
+
+/// Reports note given by its ID and author, both in hex.
+fn report_note_action(sender: AsyncComponentSender<Main>) -> RelmAction<ReportNote> {
+    RelmAction::new_with_target_value(move |_, (id, author): (String, String)| {
+        if let (Ok(event_id), Ok(author)) =
+            (EventId::from_hex(id), XOnlyPublicKey::from_str(&author))
+        {
+            sender.input(MainInput::Report(ReportSubject::Note { event_id, author }));
+        }
+    })
+}
+
+fn report_user_action(sender: AsyncComponentSender<Main>) -> RelmAction<ReportUser> {
+    RelmAction::new_with_target_value(move |_, pubkey: String| {
+        if let Ok(pubkey) = XOnlyPublicKey::from_str(&pubkey) {
+            sender.input(MainInput::Report(ReportSubject::User(pubkey)));
+        }
+    })
+}
+
+/// Gives author, given by pubkey in hex, the label without publishing anything.
+fn label_author_action(sender: AsyncComponentSender<Main>) -> RelmAction<LabelAuthor> {
+    RelmAction::new_with_target_value(move |_, (author, label): (String, String)| {
+        if let Ok(author) = XOnlyPublicKey::from_str(&author) {
+            sender.input(MainInput::Label {
+                author,
+                label,
+                add: true,
+            });
+        }
+    })
+}
+
+/// Removes the label of author given by pubkey in hex.
+fn unlabel_author_action(sender: AsyncComponentSender<Main>) -> RelmAction<UnlabelAuthor> {
+    RelmAction::new_with_target_value(move |_, (author, label): (String, String)| {
+        if let Ok(author) = XOnlyPublicKey::from_str(&author) {
+            sender.input(MainInput::Label {
+                author,
+                label,
+                add: false,
+            });
+        }
+    })
+}
+
+/// Lists users followed by the user given by pubkey in hex.
+fn show_following_action(sender: AsyncComponentSender<Main>) -> RelmAction<ShowFollowing> {
+    RelmAction::new_with_target_value(move |_, pubkey: String| {
+        if let Ok(pubkey) = XOnlyPublicKey::from_str(&pubkey) {
+            sender.input(MainInput::ShowContacts {
+                pubkey,
+                followers: false,
+            });
+        }
+    })
+}
+
+/// Lists followers, known to this client, of the user given by pubkey in hex.
+fn show_followers_action(sender: AsyncComponentSender<Main>) -> RelmAction<ShowFollowers> {
+    RelmAction::new_with_target_value(move |_, pubkey: String| {
+        if let Ok(pubkey) = XOnlyPublicKey::from_str(&pubkey) {
+            sender.input(MainInput::ShowContacts {
+                pubkey,
+                followers: true,
+            });
+        }
+    })
+}
+
+/// Pins our note given by its ID in hex to our profile.
+fn pin_note_action(sender: AsyncComponentSender<Main>) -> RelmAction<PinNote> {
+    RelmAction::new_with_target_value(move |_, id: String| {
+        if let Ok(event_id) = EventId::from_hex(id) {
+            sender.input(MainInput::Pin(event_id, true));
+        }
+    })
+}
+
+/// Unpins our note given by its ID in hex from our profile.
+fn unpin_note_action(sender: AsyncComponentSender<Main>) -> RelmAction<UnpinNote> {
+    RelmAction::new_with_target_value(move |_, id: String| {
+        if let Ok(event_id) = EventId::from_hex(id) {
+            sender.input(MainInput::Pin(event_id, false));
+        }
+    })
+}
+
+/// Deletes our note given by its ID in hex, once user confirms it.
+fn delete_note_action(sender: AsyncComponentSender<Main>) -> RelmAction<DeleteNote> {
+    RelmAction::new_with_target_value(move |_, id: String| {
+        if let Ok(event_id) = EventId::from_hex(id) {
+            sender.input(MainInput::ConfirmDelete(event_id));
+        }
+    })
+}
+
+fn clear_cache_action(sender: AsyncComponentSender<Main>) -> RelmAction<ClearCache> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::ClearCache))
 }
@@ -0,0 +1,70 @@
+//! Headless mode printing received events, for diagnostics and scripts.
+
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::time::Duration;
+
+use nostr_sdk::prelude::*;
+use nostr_sdk::RelayPoolNotification;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::app::init::{add_session_relays, Setup};
+use crate::cli::DumpFeed;
+
+/// Subscribes to relays of the session with filter given by `args` and
+/// prints every received event as a line of JSON, until the duration
+/// elapses. Does not need GTK, nor our key, nor the database.
+pub async fn dump_feed(setup: &Setup, args: &DumpFeed) -> Result<(), String> {
+    if setup.relays.is_empty() {
+        return Err("dump-feed needs at least one --relay".to_string());
+    }
+
+    let authors = args
+        .authors
+        .iter()
+        .map(|a| {
+            XOnlyPublicKey::from_bech32(a)
+                .or_else(|_| XOnlyPublicKey::from_str(a))
+                .map_err(|_| format!("Invalid author: {a}"))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let mut filter = SubscriptionFilter::new();
+    if !authors.is_empty() {
+        filter = filter.authors(authors);
+    }
+    if !args.kinds.is_empty() {
+        filter = filter.kinds(args.kinds.iter().map(|k| Kind::from(*k)).collect());
+    }
+    if let Some(limit) = args.limit {
+        filter = filter.limit(limit);
+    }
+
+    let client = Client::new(&Keys::generate());
+    let mut notifications = client.notifications();
+    add_session_relays(&client, &setup.relays).await?;
+    client.subscribe(vec![filter]).await;
+
+    let deadline = tokio::time::sleep(Duration::from_secs(args.duration));
+    tokio::pin!(deadline);
+
+    // The same event comes from every relay that has it.
+    let mut printed = HashSet::new();
+
+    loop {
+        tokio::select! {
+            _ = &mut deadline => break,
+            notification = notifications.recv() => match notification {
+                Ok(RelayPoolNotification::Event(_, event)) => {
+                    if printed.insert(event.id) {
+                        println!("{}", event.as_json().map_err(|e| e.to_string())?);
+                    }
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+
+    client.disconnect().await.map_err(|e| e.to_string())
+}
@@ -1,40 +1,57 @@
 use age::Decryptor;
-use directories::ProjectDirs;
 use nostr_sdk::prelude::*;
+use reqwest::Url;
 use secrecy::SecretString;
-use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
-use tracing_subscriber::EnvFilter;
 
+use crate::config::Config;
+use crate::dirs::Dirs;
 use crate::follow::Follow;
 use crate::identity::Identity;
 use crate::Gnostique;
 
+/// What Gnostique has been started with.
+#[derive(Clone, Debug)]
+pub struct Setup {
+    pub dirs: Dirs,
+    pub config: Config,
+    /// Relays used in this session only, they are not stored.
+    pub relays: Vec<Url>,
+    /// Offer tools for debugging.
+    pub debug: bool,
+}
+
+/// Adds `relays` to `client` and connects them.
+pub async fn add_session_relays(client: &Client, relays: &[Url]) -> Result<(), String> {
+    for url in relays {
+        client
+            .add_relay(url.as_str(), None)
+            .await
+            .map_err(|e| format!("Could not add relay {url}: {e}"))?;
+        client
+            .connect_relay(url.as_str(), false)
+            .await
+            .map_err(|e| format!("Could not connect relay {url}: {e}"))?;
+    }
+    Ok(())
+}
+
 /// Initializes the application, reads all the configurations and databases
 /// and all that and returns it all inside [`Gnostique`].
 ///
 /// Requires Tokio.
-pub async fn make_gnostique(password: SecretString) -> Result<Gnostique, String> {
-    // Logging, tracing
-    let subscriber = tracing_subscriber::FmtSubscriber::builder()
-        // .pretty()
-        .compact()
-        .with_max_level(tracing::Level::TRACE)
-        .with_file(true)
-        .with_line_number(true)
-        .with_ansi(true)
-        .with_env_filter(EnvFilter::new("debug,hyper=info,relm4=warn"))
-        .with_span_events(tracing_subscriber::fmt::format::FmtSpan::FULL)
-        .finish();
-
-    tracing::subscriber::set_global_default(subscriber).unwrap();
-
+pub async fn make_gnostique(password: SecretString, setup: Setup) -> Result<Gnostique, String> {
     use std::io::prelude::*;
 
-    let ciph = std::fs::File::open("key");
     let mut buf = Vec::new();
-    ciph.unwrap()
-        .read_to_end(&mut buf)
-        .map_err(|e| e.to_string())?;
+    std::fs::File::open(setup.dirs.key_file())
+        .and_then(|mut f| f.read_to_end(&mut buf))
+        .map_err(|e| {
+            format!(
+                "Could not read key {}: {}",
+                setup.dirs.key_file().display(),
+                e
+            )
+        })?;
 
     let id: Identity = if let Ok(Decryptor::Passphrase(d)) = Decryptor::new(buf.as_slice()) {
         let rea = d.decrypt(&password, Some(18)).map_err(|e| e.to_string())?;
@@ -43,25 +60,31 @@ pub async fn make_gnostique(password: SecretString) -> Result<Gnostique, String>
         Err("Can't".to_string())?
     };
 
-    let dirs = ProjectDirs::from("com.jirijakes", "", "Gnostique").unwrap();
+    let Setup {
+        dirs,
+        config,
+        relays,
+        debug,
+    } = setup;
     tokio::fs::create_dir_all(dirs.data_dir()).await.unwrap();
 
     // Database
-    let pool = SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect_with(
-            SqliteConnectOptions::new()
-                .filename(dirs.data_dir().join("gnostique.db"))
-                .create_if_missing(true),
-        )
-        .await
-        .unwrap();
+    let pool = crate::db::connect(&dirs.data_dir().join("gnostique.db"), &config.database).await?;
+    crate::db::check(&pool).await;
 
     sqlx::migrate!().run(&pool).await.unwrap();
 
     // Nostr
     let client = Client::new(&id.nostr_key());
-    let gnostique = Gnostique::new(pool, dirs, client);
+    add_session_relays(&client, &relays).await?;
+    let gnostique = Gnostique::new(pool, dirs, client, relays, config, debug);
+
+    let stats = gnostique.cache_stats().await;
+    tracing::info!(
+        "Download cache contains {} files, {} bytes",
+        stats.files,
+        stats.size
+    );
 
     // gnostique
     //     .client()
@@ -0,0 +1,61 @@
+//! Logging into console and, optionally, into daily rotated files.
+
+use std::path::PathBuf;
+
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::EnvFilter;
+
+use crate::config::LoggingConfig;
+use crate::dirs::Dirs;
+
+/// Directory with log files.
+pub fn log_dir(dirs: &Dirs) -> PathBuf {
+    dirs.data_dir().join("logs")
+}
+
+/// Sets up global logging according to `config`. The returned guard
+/// flushes the log file when dropped, it has to be kept until the end.
+/// Does nothing if logging has already been set up.
+pub fn init_logging(dirs: &Dirs, config: &LoggingConfig) -> Option<WorkerGuard> {
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(&config.filter))
+        .unwrap_or_else(|_| EnvFilter::new("info"));
+
+    let console = tracing_subscriber::fmt::layer()
+        .compact()
+        .with_file(true)
+        .with_line_number(true);
+
+    let (file, guard) = match config.file.then(|| file_appender(dirs, config)).flatten() {
+        Some(appender) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let layer = tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(writer);
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(console)
+        .with(file)
+        .try_init()
+        .ok()?;
+
+    guard
+}
+
+fn file_appender(dirs: &Dirs, config: &LoggingConfig) -> Option<RollingFileAppender> {
+    RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("gnostique")
+        .filename_suffix("log")
+        .max_log_files(config.keep_files.max(1))
+        .build(log_dir(dirs))
+        .map_err(|e| eprintln!("Could not write log into files: {e}"))
+        .ok()
+}
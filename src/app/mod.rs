@@ -1,3 +1,5 @@
 pub mod action;
+pub mod dump;
 pub mod init;
+pub mod logging;
 pub mod task;
@@ -1,15 +1,15 @@
 use std::collections::HashSet;
-use std::sync::Arc;
 use std::time::Duration;
 
 use futures_util::future;
-use nostr_sdk::nostr::nips::nip11;
-use nostr_sdk::prelude::Event;
+use nostr_sdk::prelude::{EventId, RelayMessage};
+use nostr_sdk::RelayPoolNotification;
 use relm4::AsyncComponentSender;
-use reqwest::Url;
-use sqlx::query;
-use tracing::info;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::{self};
+use tracing::{info, warn};
 
+use crate::outbox::Outbox;
 use crate::ui::main::{Main, MainInput};
 use crate::Gnostique;
 
@@ -26,10 +26,10 @@ pub async fn receive_events(gnostique: Gnostique, sender: AsyncComponentSender<M
     //     "../../resources/febbaba219357c6c64adfa2e01789f274aa60e90c289938bfc80dd91facb2899.json"
     //     ))
     //     .lines()
-    //     .map(|s| (url.clone(), Event::from_json(s).unwrap()))
+    //     .map(|s| RelayPoolNotification::Event(url.clone(), Event::from_json(s).unwrap()))
     //     .collect::<Vec<_>>(),
     // )));
-    let s: Option<Box<futures_util::stream::Empty<(Url, Event)>>> = None;
+    let s: Option<Box<futures_util::stream::Empty<RelayPoolNotification>>> = None;
 
     use futures_util::StreamExt;
 
@@ -42,63 +42,185 @@ pub async fn receive_events(gnostique: Gnostique, sender: AsyncComponentSender<M
         .await;
 }
 
-/// Regularly, and in the background, obtain information about relays.
-pub async fn refresh_relay_information(gnostique: Arc<Gnostique>) {
+/// Regularly removes text notes that have expired according to NIP-40.
+pub async fn purge_expired(gnostique: Gnostique, sender: AsyncComponentSender<Main>) {
     let mut int = tokio::time::interval(Duration::from_secs(60));
     loop {
         int.tick().await;
 
-        let client_relays = gnostique.client().relays().await;
-        let mut client_relays: HashSet<Url> = client_relays.keys().cloned().collect();
+        for id in gnostique.purge_expired().await {
+            info!("Text note {} has expired", id);
+            sender.input(MainInput::Expired(id));
+        }
+    }
+}
 
-        let old_info = query!(
-            r#"
-SELECT
-  url,
-  information IS NULL OR unixepoch('now') - unixepoch(updated) > 60 * 60 AS "old: bool"
-FROM relays
-"#
-        )
-        .fetch_all(gnostique.pool())
-        .await;
+/// Regularly lets notes refresh their ages, such as `5m`.
+pub async fn tick(sender: AsyncComponentSender<Main>) {
+    let start = tokio::time::Instant::now() + Duration::from_secs(30);
+    let mut int = tokio::time::interval_at(start, Duration::from_secs(30));
+    loop {
+        int.tick().await;
+        sender.input(MainInput::Tick);
+    }
+}
 
-        let old_info: HashSet<_> = if let Ok(rec) = old_info {
-            rec.iter()
-                .filter_map(|r| {
-                    let url: reqwest::Url = r.url.parse().unwrap();
-                    client_relays.remove(&url);
-
-                    if r.old {
-                        Some(url)
-                    } else {
-                        None
-                    }
-                })
-                .collect()
-        } else {
-            HashSet::new()
-        };
-
-        for url in old_info.union(&client_relays) {
-            if let Ok(info) = nip11::RelayInformationDocument::get(url.clone(), None).await {
-                let url_s = url.to_string();
-                let info_json = serde_json::to_string(&info).unwrap();
-                let _ = query!(
-                    r#"
-INSERT INTO relays(url, information, updated)
-VALUES (?, ?, CURRENT_TIMESTAMP)
-ON CONFLICT(url) DO UPDATE SET
-  information = EXCLUDED.information,
-  updated = EXCLUDED.updated
-"#,
-                    url_s,
-                    info_json
-                )
-                .execute(gnostique.pool())
-                .await;
-
-                info!("Stored fresh relay information of {}", url);
+/// Prunes old events from the database whenever it has not been done
+/// for a while. The first check comes shortly after start.
+pub async fn prune_when_overdue(gnostique: Gnostique, sender: AsyncComponentSender<Main>) {
+    let start = tokio::time::Instant::now() + Duration::from_secs(60);
+    let mut int = tokio::time::interval_at(start, Duration::from_secs(60 * 60));
+    loop {
+        int.tick().await;
+
+        if gnostique.retention().is_overdue().await {
+            sender.input(MainInput::PruneOverdue);
+        }
+    }
+}
+
+/// Records responses of relays to events published through the outbox.
+/// Events accepted by a relay for the first time are announced.
+pub async fn acknowledge_published(gnostique: Gnostique, sender: AsyncComponentSender<Main>) {
+    acknowledge(
+        gnostique.outbox(),
+        gnostique.client().notifications(),
+        |event_id| sender.input(MainInput::Delivered(event_id)),
+    )
+    .await;
+}
+
+/// Records responses of relays among `notifications` into `outbox` until
+/// there are no more, telling `delivered` about events accepted for the
+/// first time. Responses missed when lagging behind are left for retries.
+async fn acknowledge<F>(
+    outbox: &Outbox,
+    mut notifications: broadcast::Receiver<RelayPoolNotification>,
+    delivered: F,
+) where
+    F: Fn(EventId),
+{
+    loop {
+        match notifications.recv().await {
+            Ok(RelayPoolNotification::Message(
+                relay,
+                RelayMessage::Ok {
+                    event_id,
+                    status,
+                    message,
+                },
+            )) => {
+                if outbox.acknowledge(&relay, event_id, status, &message).await {
+                    delivered(event_id);
+                }
             }
+            Ok(_) => {}
+            Err(RecvError::Lagged(n)) => warn!("Missed {} notifications of relays", n),
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+/// Regularly sends again events that have not been delivered yet. Relays
+/// that have just (re)connected get all events waiting for them at once.
+pub async fn retry_outbox(gnostique: Gnostique) {
+    let outbox = gnostique.outbox();
+    let mut connected = HashSet::new();
+    let mut int = tokio::time::interval(Duration::from_secs(15));
+
+    loop {
+        int.tick().await;
+
+        let now_connected = outbox.connected_relays().await;
+        for relay in now_connected.difference(&connected) {
+            outbox.retry(Some(relay)).await;
+            tokio::spawn({
+                let gnostique = gnostique.clone();
+                let relay = relay.clone();
+                async move { gnostique.relay_connected(&relay).await }
+            });
         }
+        connected = now_connected;
+
+        outbox.retry(None).await;
+        outbox.cleanup().await;
+    }
+}
+
+/// Regularly writes numbers of events received from relays into database.
+pub async fn flush_relay_stats(gnostique: Gnostique) {
+    let start = tokio::time::Instant::now() + Duration::from_secs(60);
+    let mut int = tokio::time::interval_at(start, Duration::from_secs(60));
+    loop {
+        int.tick().await;
+
+        if let Err(e) = gnostique.relay_stats().flush().await {
+            warn!("Could not store relay statistics: {}", e);
+        }
+    }
+}
+
+/// Regularly, and in the background, obtain information about relays.
+pub async fn refresh_relay_information(gnostique: Gnostique) {
+    let mut int = tokio::time::interval(Duration::from_secs(60));
+    loop {
+        int.tick().await;
+
+        for url in gnostique.client().relays().await.into_keys() {
+            if gnostique.relay_info().is_stale(&url).await {
+                gnostique.relay_info().refresh(&url).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use nostr_sdk::prelude::{Keys, Url};
+    use nostr_sdk::Client;
+
+    use super::*;
+    use crate::testing::{Fixtures, TestDb};
+
+    #[tokio::test]
+    async fn responses_are_recorded_after_lagging_behind() {
+        let db = TestDb::in_memory().await.unwrap();
+        let outbox = Outbox::new(db.pool().clone(), Client::new(&Keys::generate()));
+        let relay = Url::parse("wss://relay.example.com").unwrap();
+        let note = Fixtures::new().text_note("Hello");
+        outbox
+            .publish_to(&note, std::slice::from_ref(&relay))
+            .await
+            .unwrap();
+
+        let (notifications, receiver) = broadcast::channel(2);
+        // More than the channel holds come before the response.
+        for _ in 0..5 {
+            notifications.send(RelayPoolNotification::Shutdown).unwrap();
+        }
+        notifications
+            .send(RelayPoolNotification::Message(
+                relay,
+                RelayMessage::Ok {
+                    event_id: note.id,
+                    status: true,
+                    message: String::new(),
+                },
+            ))
+            .unwrap();
+        drop(notifications);
+
+        let delivered = Mutex::new(Vec::new());
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            acknowledge(&outbox, receiver, |id| delivered.lock().unwrap().push(id)),
+        )
+        .await
+        .expect("Closed channel ends acknowledging");
+
+        assert_eq!(delivered.into_inner().unwrap(), [note.id]);
+        assert!(!outbox.is_pending(note.id).await);
     }
 }
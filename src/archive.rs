@@ -0,0 +1,40 @@
+//! Raw events exported into and imported from JSONL files, one event JSON
+//! per line, as relays send them.
+
+use nostr_sdk::prelude::{Timestamp, XOnlyPublicKey};
+
+/// Which stored events are exported. Everything is exported by default.
+#[derive(Clone, Debug, Default)]
+pub struct EventFilter {
+    pub kind: Option<u64>,
+    pub author: Option<XOnlyPublicKey>,
+    /// Events created earlier are left out.
+    pub since: Option<Timestamp>,
+    /// Events created later are left out.
+    pub until: Option<Timestamp>,
+}
+
+/// Outcome of importing events, by lines of the file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// Events stored now.
+    pub imported: u64,
+    /// Events that have already been stored.
+    pub skipped: u64,
+    /// Lines that are not events or whose signatures do not match.
+    pub invalid: u64,
+}
+
+impl ImportReport {
+    /// Number of processed lines with events.
+    pub fn lines(&self) -> u64 {
+        self.imported + self.skipped + self.invalid
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} imported, {} already stored, {} invalid",
+            self.imported, self.skipped, self.invalid
+        )
+    }
+}
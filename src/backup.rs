@@ -0,0 +1,286 @@
+//! Backup of the whole account in a single JSON file: secret key, relays,
+//! follows, muted users and settings. Every field may be missing, so that
+//! backups made by older versions can still be restored.
+
+use std::path::Path;
+
+use nostr_sdk::prelude::XOnlyPublicKey;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::nostr::RelayPolicy;
+use crate::Gnostique;
+
+/// Version of backup format written by this version of Gnostique.
+pub const VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Backup {
+    pub version: u32,
+    pub key: BackupKey,
+    pub relays: Vec<BackupRelay>,
+    /// Users we follow.
+    pub contacts: Vec<XOnlyPublicKey>,
+    /// Users whose notes are hidden.
+    pub muted: Vec<XOnlyPublicKey>,
+    pub settings: BackupSettings,
+}
+
+/// Secret key of the account.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BackupKey {
+    /// Key file encrypted by password, as it is stored, in hex.
+    Encrypted { age: String },
+    /// Key is held somewhere else and the backup does not contain it.
+    #[default]
+    External,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupRelay {
+    pub url: Url,
+    #[serde(default = "yes")]
+    pub read: bool,
+    #[serde(default = "yes")]
+    pub write: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupSettings {
+    pub desktop_notifications: bool,
+    /// Minimum proof of work (NIP-13) of displayed notes.
+    pub min_pow: u8,
+    /// Languages in which notes are shown, all if none.
+    pub languages: Vec<String>,
+    /// Authors whose events are not announced by desktop notifications.
+    pub muted_notifications: Vec<XOnlyPublicKey>,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        BackupSettings {
+            desktop_notifications: true,
+            min_pow: 0,
+            languages: Vec::new(),
+            muted_notifications: Vec::new(),
+        }
+    }
+}
+
+fn yes() -> bool {
+    true
+}
+
+impl Backup {
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+
+    pub fn from_json(json: &str) -> Result<Backup, String> {
+        serde_json::from_str(json).map_err(|e| format!("Invalid backup: {e}"))
+    }
+
+    /// Reads backup from file at `path`.
+    pub fn read(path: &Path) -> Result<Backup, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Backup::from_json(&json)
+    }
+}
+
+/// Collects everything about the account into a backup.
+pub async fn collect(gnostique: &Gnostique) -> Result<Backup, String> {
+    let key = match std::fs::read(gnostique.dirs().key_file()) {
+        Ok(bytes) => BackupKey::Encrypted {
+            age: bytes.iter().map(|b| format!("{b:02x}")).collect(),
+        },
+        Err(_) => BackupKey::External,
+    };
+
+    let relays = gnostique
+        .relay_policies()
+        .await
+        .into_iter()
+        .map(|p| BackupRelay {
+            url: p.url,
+            read: p.read,
+            write: p.write,
+        })
+        .collect();
+
+    Ok(Backup {
+        version: VERSION,
+        key,
+        relays,
+        contacts: gnostique.follows().await,
+        muted: gnostique.muted_pubkeys().await,
+        settings: BackupSettings {
+            desktop_notifications: gnostique.notifications().enabled(),
+            min_pow: gnostique.min_pow(),
+            languages: gnostique.languages(),
+            muted_notifications: gnostique.notifications().muted().await,
+        },
+    })
+}
+
+/// Writes backup of the account into file at `path`.
+pub async fn export(gnostique: &Gnostique, path: &Path) -> Result<(), String> {
+    let json = collect(gnostique).await?.to_json()?;
+    tokio::fs::write(path, json)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    info!("Backup written to {}", path.display());
+    Ok(())
+}
+
+/// Stores secret key from `backup`, if it has one, into `key_file`, so
+/// that it can be unlocked. The current key is kept next to it with `.old` suffix.
+pub fn restore_key(backup: &Backup, key_file: &Path) -> Result<(), String> {
+    let hex = match &backup.key {
+        BackupKey::Encrypted { age } => age,
+        BackupKey::External => return Ok(()),
+    };
+
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+        })
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| "Invalid key in backup".to_string())?;
+
+    if key_file.exists() {
+        std::fs::rename(key_file, key_file.with_extension("old")).map_err(|e| e.to_string())?;
+    }
+
+    std::fs::write(key_file, bytes).map_err(|e| e.to_string())
+}
+
+/// Restores relays, follows, muted users and settings from `backup`.
+/// If `republish` is set, our contact list, mute list and relay list
+/// are published again, as they are now.
+pub async fn restore(gnostique: &Gnostique, backup: Backup, republish: bool) -> Result<(), String> {
+    let relays = backup
+        .relays
+        .into_iter()
+        .map(|r| RelayPolicy {
+            url: r.url,
+            read: r.read,
+            write: r.write,
+        })
+        .collect();
+    gnostique.import_relay_list(relays).await?;
+
+    for pubkey in backup.contacts {
+        gnostique.follow(pubkey).await?;
+    }
+
+    let now = nostr_sdk::prelude::Timestamp::now().as_i64();
+    for pubkey in backup.muted {
+        gnostique.store_muted(pubkey, true, now).await?;
+    }
+
+    let settings = backup.settings;
+    gnostique.update_config(|config| {
+        config.general.desktop_notifications = settings.desktop_notifications;
+        config.general.min_pow = settings.min_pow;
+        config.general.languages = settings.languages;
+    })?;
+    for pubkey in settings.muted_notifications {
+        gnostique.notifications().set_muted(pubkey, true).await?;
+    }
+
+    if republish {
+        gnostique.publish_contact_list().await?;
+        gnostique.publish_mute_list().await?;
+        gnostique.publish_relay_list().await?;
+    }
+
+    info!("Backup restored");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use nostr_sdk::prelude::Keys;
+
+    use super::*;
+    use crate::testing::{Fixtures, TestDb};
+
+    /// Backup with everything sorted, as the order of follows does not matter.
+    async fn collect_sorted(gnostique: &Gnostique) -> Backup {
+        let mut backup = collect(gnostique).await.unwrap();
+        backup.contacts.sort();
+        backup.muted.sort();
+        backup.settings.muted_notifications.sort();
+        backup
+    }
+
+    #[tokio::test]
+    async fn backup_is_restored_into_fresh_database() {
+        let keys = Keys::generate();
+        let (alice, bob, carol) = (
+            Fixtures::new().public_key(),
+            Fixtures::new().public_key(),
+            Fixtures::new().public_key(),
+        );
+
+        let original_db = TestDb::new().await.unwrap();
+        let original = original_db.gnostique(&keys);
+        std::fs::write(original.dirs().key_file(), b"age key").unwrap();
+        original
+            .import_relay_list(vec![
+                RelayPolicy {
+                    url: Url::parse("ws://127.0.0.1:9").unwrap(),
+                    read: true,
+                    write: false,
+                },
+                RelayPolicy {
+                    url: Url::parse("ws://127.0.0.2:9").unwrap(),
+                    read: false,
+                    write: true,
+                },
+            ])
+            .await
+            .unwrap();
+        original.follow(alice).await.unwrap();
+        original.follow(bob).await.unwrap();
+        original.store_muted(carol, true, 1).await.unwrap();
+        original.notifications().set_muted(bob, true).await.unwrap();
+        original
+            .update_config(|config| {
+                config.general.desktop_notifications = false;
+                config.general.min_pow = 8;
+                config.general.languages = vec!["cs".to_string()];
+            })
+            .unwrap();
+
+        let expected = collect_sorted(&original).await;
+        assert_eq!(expected.relays.len(), 2);
+        assert_eq!(expected.contacts.len(), 2);
+        let backup = Backup::from_json(&expected.to_json().unwrap()).unwrap();
+
+        let restored_db = TestDb::new().await.unwrap();
+        let restored = restored_db.gnostique(&keys);
+        restore_key(&backup, restored.dirs().key_file()).unwrap();
+        restore(&restored, backup, false).await.unwrap();
+
+        assert_eq!(collect_sorted(&restored).await, expected);
+    }
+
+    #[test]
+    fn missing_fields_take_defaults() {
+        let backup =
+            Backup::from_json(r#"{"version": 1, "relays": [{"url": "wss://relay.example.com"}]}"#)
+                .unwrap();
+
+        assert_eq!(backup.key, BackupKey::External);
+        assert!(backup.relays[0].read && backup.relays[0].write);
+        assert_eq!(backup.settings, BackupSettings::default());
+    }
+}
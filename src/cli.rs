@@ -0,0 +1,59 @@
+//! Command-line arguments.
+
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+use reqwest::Url;
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Directory with database, cache and key, instead of the default one.
+    #[arg(long, value_name = "DIR")]
+    pub data_dir: Option<PathBuf>,
+
+    /// Configuration file, instead of config.toml in the configuration directory.
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Relay to use in this session only, may be given several times.
+    #[arg(long = "relay", value_name = "URL", value_parser = relay_url)]
+    pub relays: Vec<Url>,
+
+    /// Open lane of all received events, with numbers of their kinds.
+    #[arg(long)]
+    pub debug: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+fn relay_url(url: &str) -> Result<Url, String> {
+    crate::nostr::parse_relay_url(url).ok_or_else(|| format!("{url} is not a relay URL"))
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Prints events received from relays as JSON lines, without
+    /// starting the window.
+    DumpFeed(DumpFeed),
+}
+
+#[derive(Debug, Args)]
+pub struct DumpFeed {
+    /// Author of events, as npub or hex, may be given several times.
+    #[arg(long = "author", value_name = "PUBKEY")]
+    pub authors: Vec<String>,
+
+    /// Kind of events, may be given several times.
+    #[arg(long = "kind", value_name = "KIND")]
+    pub kinds: Vec<u64>,
+
+    /// At most this many stored events are requested from every relay.
+    #[arg(long)]
+    pub limit: Option<usize>,
+
+    /// For how many seconds events are received.
+    #[arg(long, default_value_t = 30, value_name = "SECONDS")]
+    pub duration: u64,
+}
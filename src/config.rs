@@ -0,0 +1,397 @@
+//! Configuration read from `config.toml` in the configuration directory,
+//! or from file given on the command line.
+//! Every value has a default, so the file may miss some or not exist at all.
+//! Values changed in settings are written back into the file, keys which
+//! Gnostique does not know are kept there.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use nostr_sdk::prelude::XOnlyPublicKey;
+use serde::{Deserialize, Serialize};
+
+use crate::content::Collapse;
+use crate::retention::DEFAULT_KEEP_DAYS;
+use crate::timestamps::TimeFormat;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    pub general: GeneralConfig,
+    pub network: NetworkConfig,
+    pub privacy: PrivacyConfig,
+    pub appearance: AppearanceConfig,
+    pub spam: SpamConfig,
+    pub database: DatabaseConfig,
+    pub stream: StreamConfig,
+    pub logging: LoggingConfig,
+    pub translation: TranslationConfig,
+    pub spelling: SpellingConfig,
+    pub upload: UploadConfig,
+    /// How times of notes are shown.
+    pub time: TimeFormat,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GeneralConfig {
+    pub desktop_notifications: bool,
+    /// Text notes with lower proof of work (NIP-13) are ignored.
+    pub min_pow: u8,
+    /// ISO 639-3 codes of languages in which text notes are shown, unless
+    /// their lane shows all. Empty means all languages.
+    pub languages: Vec<String>,
+    /// Events are kept this many days in the database, zero means forever.
+    pub keep_days: u32,
+    /// Whether closing the window only hides it, so that events keep
+    /// being collected and announced.
+    pub keep_running: bool,
+}
+
+impl Default for GeneralConfig {
+    fn default() -> Self {
+        GeneralConfig {
+            desktop_notifications: true,
+            min_pow: 0,
+            languages: Vec::new(),
+            keep_days: DEFAULT_KEEP_DAYS,
+            keep_running: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Once cached files exceed this size in megabytes, least recently
+    /// used are removed.
+    pub max_cache_mb: u64,
+    /// Information about relays (NIP-11) is obtained again after this many hours.
+    pub relay_info_hours: u64,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        NetworkConfig {
+            max_cache_mb: 500,
+            relay_info_hours: 24,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PrivacyConfig {
+    /// Whether web pages linked from notes are fetched to show their previews.
+    pub link_previews: bool,
+    /// Whether published events tell that they come from Gnostique.
+    pub client_tag: bool,
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        PrivacyConfig {
+            link_previews: true,
+            client_tag: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AppearanceConfig {
+    pub theme: Theme,
+    /// Text notes longer than this are collapsed until expanded.
+    pub collapse: Collapse,
+}
+
+/// Whether light or dark variant of GTK theme is used.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// Whichever the desktop prefers.
+    System,
+    Light,
+    #[default]
+    Dark,
+}
+
+/// Server translating notes on request, with LibreTranslate API.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TranslationConfig {
+    /// URL of the server, notes are not translated if empty. Content of
+    /// translated notes is sent there.
+    pub url: String,
+    /// Key which the server may require, none if empty.
+    pub api_key: String,
+    /// Language into which notes are translated, as the server calls it.
+    pub target: String,
+}
+
+impl Default for TranslationConfig {
+    fn default() -> Self {
+        TranslationConfig {
+            url: String::new(),
+            api_key: String::new(),
+            target: "en".to_string(),
+        }
+    }
+}
+
+/// Servers storing files attached to notes, with NIP-96 API.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct UploadConfig {
+    /// URL of the server, files cannot be attached if empty. It learns
+    /// public key of the identity which attaches them.
+    pub server: String,
+    /// Servers tried in this order when the server fails.
+    pub fallbacks: Vec<String>,
+    /// Attached files are uploaded once the note is sent rather than
+    /// right away, so that files attached by mistake never leave.
+    pub on_send: bool,
+}
+
+/// Spell checking of notes being written.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SpellingConfig {
+    /// Dictionary last chosen by every identity, by its public key in hex.
+    /// Empty name means that the identity does not want spelling checked.
+    /// Identities which have not chosen any get the one of their locale.
+    pub dictionaries: BTreeMap<String, String>,
+}
+
+/// When authors are flooding with text notes and what happens to them.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SpamConfig {
+    /// Authors with more text notes than this within `window_secs`
+    /// are flooding, zero means that nobody is.
+    pub max_notes: u32,
+    pub window_secs: u64,
+    pub action: FloodAction,
+    /// Whether people we follow may write as much as they like.
+    pub exempt_follows: bool,
+    /// Authors who are never flooding.
+    pub allowed: Vec<XOnlyPublicKey>,
+}
+
+impl Default for SpamConfig {
+    fn default() -> Self {
+        SpamConfig {
+            max_notes: 20,
+            window_secs: 60,
+            action: FloodAction::Collapse,
+            exempt_follows: true,
+            allowed: Vec::new(),
+        }
+    }
+}
+
+/// What happens to text notes of flooding authors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FloodAction {
+    /// Lanes collapse them into a single row.
+    #[default]
+    Collapse,
+    /// They are not stored or displayed at all.
+    Drop,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DatabaseConfig {
+    /// How many connections to the database may be open at once.
+    pub max_connections: u32,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig { max_connections: 5 }
+    }
+}
+
+/// How received events are processed. Both values are used from the
+/// next start.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct StreamConfig {
+    /// How many received events may be processed at once.
+    pub concurrency: usize,
+    /// How many requests of processing for more data may wait, those
+    /// which do not fit are dropped.
+    pub feedback_queue: usize,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        StreamConfig {
+            concurrency: 64,
+            feedback_queue: 10,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Which events are logged, in syntax of `RUST_LOG`, which takes
+    /// precedence if set.
+    pub filter: String,
+    /// Whether log is also written into files in data directory.
+    pub file: bool,
+    /// Log files are rotated daily, at most this many are kept.
+    pub keep_files: usize,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            filter: "info,relm4=warn".to_string(),
+            file: true,
+            keep_files: 7,
+        }
+    }
+}
+
+impl Config {
+    /// Reads configuration of Gnostique. Defaults are used if the file
+    /// does not exist, an error is returned if it cannot be understood.
+    pub fn read(path: &Path) -> Result<Config, String> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(_) => return Ok(Config::default()),
+        };
+
+        toml::from_str(&text)
+            .map_err(|e| format!("Invalid configuration in {}: {}", path.display(), e))
+    }
+
+    /// Writes configuration into file at `path`. Whatever else the file
+    /// contains, such as keys of newer versions, stays there.
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let mut document = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|text| text.parse::<toml::Value>().ok())
+            .unwrap_or_else(|| toml::Value::Table(Default::default()));
+
+        merge(
+            &mut document,
+            toml::Value::try_from(self).map_err(|e| e.to_string())?,
+        );
+        let text = toml::to_string_pretty(&document).map_err(|e| e.to_string())?;
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(path, text).map_err(|e| {
+            format!(
+                "Could not write configuration into {}: {}",
+                path.display(),
+                e
+            )
+        })
+    }
+}
+
+/// Puts values of `from` into `into`, tables are merged key by key.
+fn merge(into: &mut toml::Value, from: toml::Value) {
+    match (into, from) {
+        (toml::Value::Table(into), toml::Value::Table(from)) => {
+            for (key, value) in from {
+                match into.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        into.insert(key, value);
+                    }
+                }
+            }
+        }
+        (into, from) => *into = from,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn missing_file_means_defaults() {
+        let dir = TempDir::new();
+        assert_eq!(
+            Config::read(&dir.path().join("config.toml")),
+            Ok(Config::default())
+        );
+    }
+
+    #[test]
+    fn written_config_is_read_back() {
+        let dir = TempDir::new();
+        let path = dir.path().join("config.toml");
+
+        let mut config = Config::default();
+        config.general.min_pow = 16;
+        config.general.languages = vec!["ces".into(), "eng".into()];
+        config.network.max_cache_mb = 100;
+        config.privacy.client_tag = false;
+        config.appearance.theme = Theme::Light;
+        config.translation.url = "https://translate.example.com".into();
+        config.time.clock_24h = false;
+
+        config.write(&path).unwrap();
+        assert_eq!(Config::read(&path), Ok(config));
+    }
+
+    #[test]
+    fn old_file_gets_defaults_for_missing_fields() {
+        let dir = TempDir::new();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "[general]\nmin_pow = 8\n\n[privacy]\nlink_previews = false\n",
+        )
+        .unwrap();
+
+        let config = Config::read(&path).unwrap();
+
+        let mut expected = Config::default();
+        expected.general.min_pow = 8;
+        expected.privacy.link_previews = false;
+        assert_eq!(config, expected);
+        assert!(config.privacy.client_tag);
+        assert_eq!(config.general.keep_days, DEFAULT_KEEP_DAYS);
+    }
+
+    #[test]
+    fn unknown_keys_survive_writing() {
+        let dir = TempDir::new();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "future = 1\n\n[general]\nmin_pow = 8\nfuture = \"yes\"\n",
+        )
+        .unwrap();
+
+        let mut config = Config::read(&path).unwrap();
+        config.general.min_pow = 4;
+        config.write(&path).unwrap();
+
+        let document: toml::Value = std::fs::read_to_string(&path).unwrap().parse().unwrap();
+        assert_eq!(document["future"].as_integer(), Some(1));
+        assert_eq!(document["general"]["future"].as_str(), Some("yes"));
+        assert_eq!(document["general"]["min_pow"].as_integer(), Some(4));
+    }
+
+    #[test]
+    fn invalid_file_is_an_error() {
+        let dir = TempDir::new();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[general]\nmin_pow = \"lots\"\n").unwrap();
+
+        assert!(Config::read(&path).is_err());
+    }
+}
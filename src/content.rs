@@ -0,0 +1,691 @@
+//! Parsing of text note content into pieces that are displayed
+//! differently: web links, references to nostr entities, hashtags
+//! and lightning payment requests.
+
+use linkify::{LinkFinder, LinkKind};
+use nostr_sdk::prelude::*;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::lightning::PaymentRequest;
+
+/// One piece of text note content.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Token<'a> {
+    /// Plain text.
+    Text(&'a str),
+    /// Web link.
+    Link(&'a str),
+    /// Bech32 entity (`npub1…`, `note1…` etc.), possibly written as `nostr:` URI.
+    /// Contains just the entity, without the `nostr:` prefix.
+    Nostr(&'a str),
+    /// Hashtag, without the leading `#`.
+    Hashtag(&'a str),
+    /// Reference to tag with this index, written as `#[index]`.
+    TagRef(usize),
+    /// Valid lightning invoice or LNURL, possibly written as `lightning:` URI.
+    /// Contains just the request, without the `lightning:` prefix.
+    Lightning(&'a str),
+}
+
+/// Entity that a link in rendered content points to, with relays
+/// hinted by the link, where the entity can be found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+    Profile(XOnlyPublicKey, Vec<Url>),
+    Note(EventId, Vec<Url>),
+    Hashtag(String),
+}
+
+/// Which entities an input field takes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntityKind {
+    /// `npub`, `nprofile` or hex public key.
+    Profile,
+    /// Any of `npub`, `nprofile`, `note` or `nevent`, but not hex, which
+    /// could be either.
+    Any,
+}
+
+/// Why text entered as nostr entity is not one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EntityError {
+    /// Nothing has been entered.
+    Empty,
+    /// Neither bech32 entity nor hex.
+    Unknown,
+    /// Bech32 entity with this prefix that cannot be decoded.
+    Invalid {
+        prefix: &'static str,
+        reason: String,
+    },
+    /// Hex of other length than 64 characters.
+    HexLength(usize),
+    /// 64 hex characters which are not a public key.
+    NotPublicKey,
+    /// Hex where it cannot be told whether it is a profile or a note.
+    Ambiguous,
+    /// Note where a profile is wanted.
+    NotProfile,
+}
+
+impl std::fmt::Display for EntityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EntityError::Empty => write!(f, "Nothing entered"),
+            EntityError::Unknown => write!(f, "Not an npub, nprofile, note, nevent or hex"),
+            EntityError::Invalid { prefix, reason } => write!(f, "Invalid {prefix}: {reason}"),
+            EntityError::HexLength(len) => {
+                write!(f, "Hex must have 64 characters, this has {len}")
+            }
+            EntityError::NotPublicKey => write!(f, "Hex is not a valid public key"),
+            EntityError::Ambiguous => {
+                write!(f, "Hex could be a profile or a note, use npub or note")
+            }
+            EntityError::NotProfile => write!(f, "This is a note, a profile is expected"),
+        }
+    }
+}
+
+impl Target {
+    /// Parses text entered by user as entity of `kind`. Bech32 entities
+    /// may be written as `nostr:` URIs, hex is taken as `kind` says.
+    pub fn parse(text: &str, kind: EntityKind) -> Result<Target, EntityError> {
+        let text = text.trim();
+        let text = text.strip_prefix("nostr:").unwrap_or(text);
+        if text.is_empty() {
+            return Err(EntityError::Empty);
+        }
+
+        let target = if text.chars().all(|c| c.is_ascii_hexdigit()) {
+            if text.len() != 64 {
+                return Err(EntityError::HexLength(text.len()));
+            }
+            match kind {
+                EntityKind::Profile => text
+                    .parse::<XOnlyPublicKey>()
+                    .map(|pubkey| Target::Profile(pubkey, Vec::new()))
+                    .map_err(|_| EntityError::NotPublicKey)?,
+                EntityKind::Any => return Err(EntityError::Ambiguous),
+            }
+        } else {
+            let prefix = ["npub", "nprofile", "note", "nevent"]
+                .into_iter()
+                .find(|p| text.starts_with(&format!("{p}1")))
+                .ok_or(EntityError::Unknown)?;
+            let invalid = |reason: String| EntityError::Invalid { prefix, reason };
+
+            // Decoding errors tell what is wrong, e.g. a mistyped character.
+            match prefix {
+                "npub" => XOnlyPublicKey::from_bech32(text)
+                    .map(|_| ())
+                    .map_err(|e| invalid(e.to_string()))?,
+                "nprofile" => Profile::from_bech32(text)
+                    .map(|_| ())
+                    .map_err(|e| invalid(e.to_string()))?,
+                "note" => EventId::from_bech32(text)
+                    .map(|_| ())
+                    .map_err(|e| invalid(e.to_string()))?,
+                _ => nostr_sdk::nostr::nips::nip19::Nip19Event::from_bech32(text)
+                    .map(|_| ())
+                    .map_err(|e| invalid(e.to_string()))?,
+            }
+            Target::from_bech32(text).ok_or_else(|| invalid("cannot be read".to_string()))?
+        };
+
+        match (&target, kind) {
+            (Target::Note(..), EntityKind::Profile) => Err(EntityError::NotProfile),
+            _ => Ok(target),
+        }
+    }
+
+    /// Resolves bech32-encoded `npub`, `nprofile`, `note` or `nevent` entity.
+    pub fn from_bech32(entity: &str) -> Option<Target> {
+        if entity.starts_with("npub1") {
+            XOnlyPublicKey::from_bech32(entity)
+                .ok()
+                .map(|pubkey| Target::Profile(pubkey, Vec::new()))
+        } else if entity.starts_with("nprofile1") {
+            Profile::from_bech32(entity).ok().map(|p| {
+                let relays = p
+                    .relays
+                    .iter()
+                    .filter_map(|r| crate::nostr::parse_relay_url(r))
+                    .collect();
+                Target::Profile(p.public_key, relays)
+            })
+        } else if entity.starts_with("note1") {
+            EventId::from_bech32(entity)
+                .ok()
+                .map(|id| Target::Note(id, Vec::new()))
+        } else if entity.starts_with("nevent1") {
+            decode_nevent(entity).map(|(id, relays)| Target::Note(id, relays))
+        } else {
+            None
+        }
+    }
+
+    /// Resolves `nostr:` URI produced by [`to_markup`].
+    pub fn from_uri(uri: &Url) -> Option<Target> {
+        if uri.scheme() != "nostr" {
+            return None;
+        }
+
+        match uri.path() {
+            "search" => uri
+                .query_pairs()
+                .find(|(k, _)| k == "t")
+                .map(|(_, t)| Target::Hashtag(t.into_owned())),
+            entity => Target::from_bech32(entity),
+        }
+    }
+}
+
+/// How long content of text notes can be before it is collapsed.
+/// Zero means no limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Collapse {
+    /// Lines of the content, not counting those wrapped when displayed.
+    pub lines: usize,
+    /// Characters of the content, entities count as displayed.
+    pub chars: usize,
+}
+
+impl Default for Collapse {
+    fn default() -> Self {
+        Collapse {
+            lines: 15,
+            chars: 1000,
+        }
+    }
+}
+
+static ENTITY: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?:nostr:)?((?:npub|note|nprofile|nevent|nrelay|naddr)1[02-9ac-hj-np-z]+)|#\[(\d+)\]|#([\p{L}\p{N}_]+)|(?:lightning:)?((?i:ln(?:bc|tb|bcrt|tbs)[0-9]*[munp]?1[02-9ac-hj-np-z]+|lnurl1[02-9ac-hj-np-z]+))").unwrap()
+});
+
+/// Splits `content` into tokens.
+pub fn tokenize(content: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+
+    for span in LinkFinder::new().kinds(&[LinkKind::Url]).spans(content) {
+        let s = span.as_str();
+        match span.kind() {
+            Some(LinkKind::Url) if is_web(s) => tokens.push(Token::Link(s)),
+            _ => tokenize_text(s, &mut tokens),
+        }
+    }
+
+    tokens
+}
+
+fn is_web(link: &str) -> bool {
+    let link = link.to_lowercase();
+    link.starts_with("http://") || link.starts_with("https://")
+}
+
+/// Finds entities, tag references and hashtags in text without web links.
+fn tokenize_text<'a>(text: &'a str, tokens: &mut Vec<Token<'a>>) {
+    let mut last = 0;
+
+    for caps in ENTITY.captures_iter(text) {
+        let whole = caps.get(0).unwrap();
+
+        // Entities and hashtags must not be glued to a preceding word.
+        let glued = text[..whole.start()]
+            .chars()
+            .next_back()
+            .map(|c| c.is_alphanumeric() || c == '_')
+            .unwrap_or(false);
+
+        let token = if glued {
+            None
+        } else if let Some(entity) = caps.get(1) {
+            Some(Token::Nostr(entity.as_str()))
+        } else if let Some(index) = caps.get(2) {
+            index.as_str().parse().ok().map(Token::TagRef)
+        } else if let Some(request) = caps.get(4) {
+            // What only looks like an invoice stays plain text.
+            PaymentRequest::decode(request.as_str()).map(|_| Token::Lightning(request.as_str()))
+        } else {
+            // Hashtags consisting of digits only (‘#1’) are rather ordinals.
+            caps.get(3)
+                .filter(|t| !t.as_str().chars().all(|c| c.is_numeric()))
+                .map(|t| Token::Hashtag(t.as_str()))
+        };
+
+        if let Some(token) = token {
+            if whole.start() > last {
+                tokens.push(Token::Text(&text[last..whole.start()]));
+            }
+            tokens.push(token);
+            last = whole.end();
+        }
+    }
+
+    if last < text.len() {
+        tokens.push(Token::Text(&text[last..]));
+    }
+}
+
+/// Renders `content` of an event with `tags` as Pango markup. Links, entities
+/// and hashtags become `<a>` elements, everything else is escaped.
+/// `name_of` provides names of known personas, mentions of whom are
+/// displayed as `@name`.
+pub fn to_markup<F>(content: &str, tags: &[Tag], name_of: F) -> String
+where
+    F: Fn(&XOnlyPublicKey) -> Option<String>,
+{
+    render(content, tags, name_of, None).0
+}
+
+/// Renders beginning of `content` like [`to_markup`], if the content is
+/// longer than `collapse` allows. Text is cut between characters, links,
+/// entities and hashtags are either whole or left out.
+pub fn to_collapsed_markup<F>(
+    content: &str,
+    tags: &[Tag],
+    name_of: F,
+    collapse: Collapse,
+) -> Option<String>
+where
+    F: Fn(&XOnlyPublicKey) -> Option<String>,
+{
+    match render(content, tags, name_of, Some(collapse)) {
+        (markup, true) => Some(markup),
+        (_, false) => None,
+    }
+}
+
+/// Renders `content` as markup, stopping when it exceeds `collapse`.
+/// Tells also whether it has stopped.
+fn render<F>(content: &str, tags: &[Tag], name_of: F, collapse: Option<Collapse>) -> (String, bool)
+where
+    F: Fn(&XOnlyPublicKey) -> Option<String>,
+{
+    use html_escape::{encode_double_quoted_attribute as attr, encode_text as text};
+
+    // Links keep the entity as written, so that relays it hints at stay.
+    let profile = |pubkey: &XOnlyPublicKey, entity: Option<&str>| {
+        let bech32 = pubkey.to_bech32().unwrap_or_default();
+        let label = name_of(pubkey).unwrap_or_else(|| shortened(&bech32));
+        format!(
+            r#"<a href="nostr:{}" title="{bech32}">@{}</a>"#,
+            attr(entity.unwrap_or(&bech32)),
+            text(&label)
+        )
+    };
+
+    let note = |id: &EventId, entity: Option<&str>| {
+        let bech32 = id.to_bech32().unwrap_or_default();
+        format!(
+            r#"<a href="nostr:{}" title="{bech32}">{}</a>"#,
+            attr(entity.unwrap_or(&bech32)),
+            shortened(&bech32)
+        )
+    };
+
+    let render_token = |token: &Token| match *token {
+        Token::Text(s) => text(s).to_string(),
+        Token::Link(s) => format!(
+            r#"<a href="{}" title="{}">{}</a>"#,
+            attr(s),
+            attr(s),
+            text(s)
+        ),
+        Token::Nostr(entity) => match Target::from_bech32(entity) {
+            Some(Target::Profile(pubkey, _)) => profile(&pubkey, Some(entity)),
+            Some(Target::Note(id, _)) => note(&id, Some(entity)),
+            _ => format!(r#"<a href="nostr:{entity}">{}</a>"#, shortened(entity)),
+        },
+        Token::Hashtag(tag) => format!(
+            r#"<a href="nostr:search?t={}" title="{}">{}</a>"#,
+            attr(&urlencode(&tag.to_lowercase())),
+            attr(&format!("#{}", tag.to_lowercase())),
+            text(&format!("#{tag}"))
+        ),
+        Token::TagRef(index) => match tags.get(index) {
+            Some(Tag::PubKey(pubkey, _)) => profile(pubkey, None),
+            Some(Tag::Event(id, _, _)) => note(id, None),
+            _ => format!("#[{index}]"),
+        },
+        Token::Lightning(request) => format!(
+            r#"<a href="lightning:{}" title="Lightning payment request">⚡{}</a>"#,
+            attr(&request.to_lowercase()),
+            text(&shortened(&request.to_lowercase()))
+        ),
+    };
+
+    let limit = |n: usize| if n == 0 { usize::MAX } else { n };
+    let (max_lines, max_chars) = collapse
+        .map(|c| (limit(c.lines), limit(c.chars)))
+        .unwrap_or((usize::MAX, usize::MAX));
+    let (mut lines, mut chars) = (1, 0);
+    let mut markup = String::new();
+
+    for token in tokenize(content.trim()) {
+        let rendered = render_token(&token);
+
+        if let Token::Text(s) = token {
+            // Text can be cut at the character where a limit is reached.
+            for (i, c) in s.char_indices() {
+                chars += 1;
+                if c == '\n' {
+                    lines += 1;
+                }
+                if lines > max_lines || chars > max_chars {
+                    markup.push_str(&text(s[..i].trim_end()));
+                    markup.push('…');
+                    return (markup, true);
+                }
+            }
+        } else {
+            chars += match token {
+                Token::Link(s) => s.chars().count(),
+                Token::Nostr(entity) | Token::Lightning(entity) => {
+                    shortened(entity).chars().count()
+                }
+                Token::Hashtag(tag) => tag.chars().count() + 1,
+                _ => 1,
+            };
+            if chars > max_chars {
+                markup.push('…');
+                return (markup, true);
+            }
+        }
+
+        markup.push_str(&rendered);
+    }
+
+    (markup, false)
+}
+
+/// Text notes referenced from `content` by `note` or `nevent`, together with
+/// relays where they may be found, in order of their first appearance.
+pub fn quoted(content: &str) -> Vec<(EventId, Vec<Url>)> {
+    let mut quoted: Vec<(EventId, Vec<Url>)> = Vec::new();
+
+    for token in tokenize(content) {
+        let found = match token {
+            Token::Nostr(entity) if entity.starts_with("note1") => {
+                EventId::from_bech32(entity).ok().map(|id| (id, vec![]))
+            }
+            Token::Nostr(entity) if entity.starts_with("nevent1") => decode_nevent(entity),
+            _ => None,
+        };
+
+        if let Some((id, relays)) = found {
+            if !quoted.iter().any(|(i, _)| i == &id) {
+                quoted.push((id, relays));
+            }
+        }
+    }
+
+    quoted
+}
+
+/// Lightning payment requests written in `content`, in order of their
+/// first appearance.
+pub fn payment_requests(content: &str) -> Vec<PaymentRequest> {
+    let mut requests: Vec<PaymentRequest> = Vec::new();
+
+    for token in tokenize(content) {
+        if let Token::Lightning(text) = token {
+            match PaymentRequest::decode(text) {
+                Some(r) if !requests.contains(&r) => requests.push(r),
+                _ => {}
+            }
+        }
+    }
+
+    requests
+}
+
+/// Event ID and relay hints of `nevent` entity. The decoded entity does
+/// not expose its parts, they are obtained from its serialized form.
+fn decode_nevent(entity: &str) -> Option<(EventId, Vec<Url>)> {
+    #[derive(serde::Deserialize)]
+    struct Parts {
+        event_id: EventId,
+        relays: Vec<String>,
+    }
+
+    let nevent = nostr_sdk::nostr::nips::nip19::Nip19Event::from_bech32(entity).ok()?;
+    let parts: Parts = serde_json::to_value(nevent)
+        .and_then(serde_json::from_value)
+        .ok()?;
+    let relays = parts
+        .relays
+        .iter()
+        .filter_map(|r| crate::nostr::parse_relay_url(r))
+        .collect();
+
+    Some((parts.event_id, relays))
+}
+
+/// Shortens bech32 entity to its prefix and a few characters at both ends.
+fn shortened(entity: &str) -> String {
+    match entity.find('1') {
+        Some(sep) if entity.len() > sep + 17 => {
+            format!("{}…{}", &entity[..sep + 9], &entity[entity.len() - 8..])
+        }
+        _ => entity.to_string(),
+    }
+}
+
+/// Percent-encodes `s` for use in URI query.
+fn urlencode(s: &str) -> String {
+    Url::parse_with_params("nostr:search", &[("t", s)])
+        .ok()
+        .and_then(|u| u.query().map(|q| q.trim_start_matches("t=").to_string()))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Fixtures;
+
+    #[test]
+    fn links_leave_out_trailing_punctuation() {
+        assert_eq!(
+            tokenize("See https://example.com/a?b=c, or (https://example.org/x)."),
+            vec![
+                Token::Text("See "),
+                Token::Link("https://example.com/a?b=c"),
+                Token::Text(", or ("),
+                Token::Link("https://example.org/x"),
+                Token::Text(")."),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_web_links_are_links() {
+        assert_eq!(
+            tokenize("ftp://example.com/file"),
+            vec![Token::Text("ftp://example.com/file")]
+        );
+    }
+
+    #[test]
+    fn mentions_are_found_with_or_without_prefix() {
+        let npub = Fixtures::new().public_key().to_bech32().unwrap();
+        let content = format!("Hi nostr:{npub}! And {npub}, #[1].");
+
+        assert_eq!(
+            tokenize(&content),
+            vec![
+                Token::Text("Hi "),
+                Token::Nostr(&npub),
+                Token::Text("! And "),
+                Token::Nostr(&npub),
+                Token::Text(", "),
+                Token::TagRef(1),
+                Token::Text("."),
+            ]
+        );
+    }
+
+    #[test]
+    fn hashtags_end_at_punctuation() {
+        assert_eq!(
+            tokenize("#foo. (#bar), #baz-qux #ñandú!"),
+            vec![
+                Token::Hashtag("foo"),
+                Token::Text(". ("),
+                Token::Hashtag("bar"),
+                Token::Text("), "),
+                Token::Hashtag("baz"),
+                Token::Text("-qux "),
+                Token::Hashtag("ñandú"),
+                Token::Text("!"),
+            ]
+        );
+    }
+
+    #[test]
+    fn glued_and_numeric_hashtags_are_text() {
+        assert_eq!(
+            tokenize("issue#12 is #1 in a#b"),
+            vec![Token::Text("issue#12 is #1 in a#b")]
+        );
+    }
+
+    #[test]
+    fn hashtags_in_links_stay_in_links() {
+        assert_eq!(
+            tokenize("https://example.com/#section #tag"),
+            vec![
+                Token::Link("https://example.com/#section"),
+                Token::Text(" "),
+                Token::Hashtag("tag"),
+            ]
+        );
+    }
+
+    #[test]
+    fn markup_is_escaped() {
+        let markup = to_markup("<b>bold</b> & https://example.com/?a=1&b=2", &[], |_| None);
+
+        assert_eq!(
+            markup,
+            "&lt;b&gt;bold&lt;/b&gt; &amp; \
+             <a href=\"https://example.com/?a=1&amp;b=2\" title=\"https://example.com/?a=1&amp;b=2\">\
+             https://example.com/?a=1&amp;b=2</a>"
+        );
+    }
+
+    #[test]
+    fn mentions_are_rendered_by_name() {
+        let pubkey = Fixtures::new().public_key();
+        let npub = pubkey.to_bech32().unwrap();
+        let tags = [Tag::PubKey(pubkey, None)];
+        let name_of = |p: &XOnlyPublicKey| {
+            Some(p)
+                .filter(|p| **p == pubkey)
+                .map(|_| "<Bob>".to_string())
+        };
+
+        let expected = format!(r#"<a href="nostr:{npub}" title="{npub}">@&lt;Bob&gt;</a>"#);
+        assert_eq!(
+            to_markup(&format!("nostr:{npub}"), &tags, name_of),
+            expected
+        );
+        assert_eq!(to_markup("#[0]", &tags, name_of), expected);
+    }
+
+    #[test]
+    fn entered_entities_are_parsed_as_field_takes_them() {
+        use nostr_sdk::nostr::nips::nip19::Nip19Event;
+        use EntityKind::{Any, Profile as ProfileField};
+
+        let pubkey = Fixtures::new().public_key();
+        let note = Fixtures::new().text_note("Hello");
+        let relay = crate::nostr::parse_relay_url("wss://relay.example.com").unwrap();
+        let npub = pubkey.to_bech32().unwrap();
+        let nprofile = Profile::new(pubkey, vec![relay.to_string()])
+            .to_bech32()
+            .unwrap();
+        let note1 = note.id.to_bech32().unwrap();
+        let nevent = Nip19Event::new(note.id, vec![relay.to_string()])
+            .to_bech32()
+            .unwrap();
+        let hex = pubkey.to_string();
+        let profile = || Target::Profile(pubkey, Vec::new());
+        let hinted_note = || Target::Note(note.id, vec![relay.clone()]);
+
+        let cases = [
+            (npub.clone(), ProfileField, Ok(profile())),
+            (format!("  nostr:{npub}\n"), Any, Ok(profile())),
+            (
+                nprofile.clone(),
+                ProfileField,
+                Ok(Target::Profile(pubkey, vec![relay.clone()])),
+            ),
+            (note1.clone(), Any, Ok(Target::Note(note.id, Vec::new()))),
+            (note1, ProfileField, Err(EntityError::NotProfile)),
+            (nevent.clone(), Any, Ok(hinted_note())),
+            (format!("nostr:{nevent}"), Any, Ok(hinted_note())),
+            (nevent, ProfileField, Err(EntityError::NotProfile)),
+            (hex.clone(), ProfileField, Ok(profile())),
+            (hex.to_uppercase(), ProfileField, Ok(profile())),
+            (hex.clone(), Any, Err(EntityError::Ambiguous)),
+            (
+                hex[..63].to_string(),
+                ProfileField,
+                Err(EntityError::HexLength(63)),
+            ),
+            ("f".repeat(64), ProfileField, Err(EntityError::NotPublicKey)),
+            ("".to_string(), Any, Err(EntityError::Empty)),
+            (" nostr: ".to_string(), Any, Err(EntityError::Empty)),
+            (
+                "alice@example.com".to_string(),
+                Any,
+                Err(EntityError::Unknown),
+            ),
+            (
+                "nsec1abc".to_string(),
+                ProfileField,
+                Err(EntityError::Unknown),
+            ),
+            (
+                "npub:alice".to_string(),
+                ProfileField,
+                Err(EntityError::Unknown),
+            ),
+        ];
+        for (text, kind, expected) in cases {
+            assert_eq!(Target::parse(&text, kind), expected, "{text:?} as {kind:?}");
+        }
+    }
+
+    #[test]
+    fn mistyped_bech32_entities_are_invalid() {
+        let npub = Fixtures::new().public_key().to_bech32().unwrap();
+        let note = Fixtures::new().text_note("Hello").id.to_bech32().unwrap();
+        // Last character changed, the checksum does not match.
+        let mistyped = |entity: &str| {
+            let last = if entity.ends_with('q') { "p" } else { "q" };
+            format!("{}{last}", &entity[..entity.len() - 1])
+        };
+
+        for (text, kind, expected) in [
+            (mistyped(&npub), EntityKind::Profile, "npub"),
+            (npub[..20].to_string(), EntityKind::Profile, "npub"),
+            (mistyped(&note), EntityKind::Any, "note"),
+            ("nprofile1qqqq".to_string(), EntityKind::Any, "nprofile"),
+            ("nevent1qqqq".to_string(), EntityKind::Any, "nevent"),
+        ] {
+            match Target::parse(&text, kind) {
+                Err(EntityError::Invalid { prefix, reason }) => {
+                    assert_eq!(prefix, expected, "{text}");
+                    assert!(!reason.is_empty(), "{text}");
+                }
+                other => panic!("{text} is invalid, got {other:?}"),
+            }
+        }
+    }
+}
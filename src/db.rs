@@ -0,0 +1,106 @@
+//! Connection to the database and handling of its contention.
+
+use std::future::Future;
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
+use sqlx::SqlitePool;
+use tracing::{info, warn};
+
+use crate::config::DatabaseConfig;
+
+/// How long a connection waits for a lock held by another one.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait before trying a busy operation again.
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Persistence is considered broken after this many consecutive failures.
+const DEGRADED_AFTER: u32 = 5;
+
+/// Tracks whether storing into the database works. When it keeps failing,
+/// received events are still displayed, they are just not stored.
+#[derive(Debug, Default)]
+pub struct Health {
+    /// Failures since the last success.
+    failures: AtomicU32,
+}
+
+impl Health {
+    pub fn succeeded(&self) {
+        self.failures.store(0, Ordering::Relaxed);
+    }
+
+    /// Records a failure. Returns `true` if it has just made
+    /// the database degraded.
+    pub fn failed(&self) -> bool {
+        self.failures.fetch_add(1, Ordering::Relaxed) + 1 == DEGRADED_AFTER
+    }
+
+    /// Whether storing into the database keeps failing.
+    pub fn is_degraded(&self) -> bool {
+        self.failures.load(Ordering::Relaxed) >= DEGRADED_AFTER
+    }
+}
+
+/// Opens database at `path`, creating it if it does not exist yet.
+pub async fn connect(path: &Path, config: &DatabaseConfig) -> Result<SqlitePool, String> {
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .busy_timeout(BUSY_TIMEOUT)
+        .synchronous(SqliteSynchronous::Normal)
+        .foreign_keys(true);
+
+    SqlitePoolOptions::new()
+        .max_connections(config.max_connections.max(1))
+        .connect_with(options)
+        .await
+        .map_err(|e| format!("Could not open database {}: {}", path.display(), e))
+}
+
+/// Logs how the database is actually set up, as it may differ from what
+/// has been asked for, e. g. WAL is not available on some file systems.
+pub async fn check(pool: &SqlitePool) {
+    let journal_mode: Result<String, _> = sqlx::query_scalar("PRAGMA journal_mode")
+        .fetch_one(pool)
+        .await;
+
+    match journal_mode {
+        Ok(mode) if mode.eq_ignore_ascii_case("wal") => {
+            info!("Database is in {} journal mode", mode)
+        }
+        Ok(mode) => warn!("Database is in {} journal mode instead of WAL", mode),
+        Err(e) => warn!("Could not find out journal mode of database: {}", e),
+    }
+}
+
+/// Whether `error` is caused by another connection holding a lock.
+pub fn is_busy(error: &sqlx::Error) -> bool {
+    // SQLITE_BUSY and SQLITE_LOCKED, including their extended codes.
+    error
+        .as_database_error()
+        .and_then(|e| e.code())
+        .and_then(|code| code.parse::<i32>().ok())
+        .map(|code| matches!(code & 0xff, 5 | 6))
+        .unwrap_or(false)
+}
+
+/// Runs `operation`, and once more after a while if the database was busy.
+pub async fn retry_on_busy<T, F, Fut>(mut operation: F) -> Result<T, sqlx::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    match operation().await {
+        Err(e) if is_busy(&e) => {
+            warn!("Database is busy, trying again: {}", e);
+            tokio::time::sleep(RETRY_DELAY).await;
+            operation().await
+        }
+        result => result,
+    }
+}
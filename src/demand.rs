@@ -1,12 +1,33 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use nostr_sdk::prelude::hex::*;
 use nostr_sdk::prelude::*;
+use nostr_sdk::RelayOptions;
 use reqwest::Url;
 use tokio::sync::Mutex;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+
+use crate::nostr::{HANDLER_KIND, HANDLER_RECOMMENDATION_KIND};
+
+/// At most this many relays are connected at once just to be asked
+/// for something hinted at them.
+const MAX_ADHOC_RELAYS: usize = 4;
+
+/// Connecting a relay just to ask it is given up after this long.
+const ADHOC_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Relay connected just to ask it is disconnected after this long,
+/// so that it has time to respond.
+const ADHOC_LINGER: Duration = Duration::from_secs(30);
+
+/// Every relay is asked for at most this many handlers (NIP-89).
+const MAX_HANDLERS: usize = 200;
+
+/// Metadata of this many authors at most are asked for in one filter.
+const METADATA_BATCH: usize = 100;
 
 #[derive(Clone)]
 pub struct Demand(Arc<DemandInner>);
@@ -16,6 +37,10 @@ struct DemandInner {
     client: Client,
     notes: Arc<Mutex<HashMap<EventId, Instant>>>,
     metadata: Arc<Mutex<HashMap<XOnlyPublicKey, Instant>>>,
+    /// Relays connected just to be asked for something.
+    adhoc: Arc<Mutex<HashSet<Url>>>,
+    /// Whether handlers have been requested in this session.
+    handlers: Arc<AtomicBool>,
 }
 
 impl Demand {
@@ -24,12 +49,21 @@ impl Demand {
             client,
             notes: Default::default(),
             metadata: Default::default(),
+            adhoc: Default::default(),
+            handlers: Default::default(),
         }))
     }
 
+    /// Whether `relay` has been connected just to be asked for something.
+    pub async fn is_adhoc(&self, relay: &Url) -> bool {
+        self.0.adhoc.lock().await.contains(relay)
+    }
+
     // TODO: Clean up `notes` and `metadata`
 
-    pub async fn metadata(&self, pubkey: XOnlyPublicKey, relay: Url) {
+    /// Requests metadata of `pubkey` from each of `relays`, which are
+    /// ours, and from relays `hinted` by tags, connecting them if needed.
+    pub async fn metadata(&self, pubkey: XOnlyPublicKey, relays: Vec<Url>, hinted: Vec<Url>) {
         let elapsed = self
             .0
             .metadata
@@ -49,21 +83,61 @@ impl Demand {
 
                 info!("Requesting metadata {}.", pubkey.to_hex());
 
-                let relays = self.0.client.relays().await;
-                if let Some(r) = relays.get(&relay) {
-                    r.req_events_of(
-                        vec![SubscriptionFilter::new()
-                            .kind(Kind::Metadata)
-                            .author(pubkey)
-                            .limit(1)],
-                        None,
-                    );
+                let filters = vec![SubscriptionFilter::new()
+                    .kind(Kind::Metadata)
+                    .author(pubkey)
+                    .limit(1)];
+
+                let ours = self.0.client.relays().await;
+                for r in relays.iter().filter_map(|r| ours.get(r)) {
+                    r.req_events_of(filters.clone(), None);
                 }
+                self.ask_hinted(&hinted, &relays, &filters).await;
             }
         };
     }
 
-    pub async fn text_note(&self, event_id: EventId, relay: Option<Url>) {
+    /// Requests metadata of all `pubkeys` from all our relays, in batches
+    /// rather than one request per author. Authors whose metadata have
+    /// just been requested are left out.
+    pub async fn metadata_of(&self, pubkeys: Vec<XOnlyPublicKey>) {
+        let pubkeys: Vec<XOnlyPublicKey> = {
+            let mut requested = self.0.metadata.lock().await;
+            pubkeys
+                .into_iter()
+                .filter(|pk| {
+                    let recent = requested
+                        .get(pk)
+                        .map(|i| i.elapsed().as_millis() < 5000)
+                        .unwrap_or_default();
+                    if !recent {
+                        requested.insert(*pk, Instant::now());
+                    }
+                    !recent
+                })
+                .collect()
+        };
+
+        if pubkeys.is_empty() {
+            return;
+        }
+        info!("Requesting metadata of {} authors.", pubkeys.len());
+
+        let filters = pubkeys
+            .chunks(METADATA_BATCH)
+            .map(|authors| {
+                SubscriptionFilter::new()
+                    .kind(Kind::Metadata)
+                    .authors(authors.to_vec())
+                    .limit(authors.len())
+            })
+            .collect();
+        self.0.client.req_events_of(filters, None).await;
+    }
+
+    /// Requests text note `event_id` from `relay`, or from all our relays,
+    /// and from relays `hinted` by tags, connecting them if needed.
+    pub async fn text_note(&self, event_id: EventId, relay: Option<Url>, hinted: Vec<Url>) {
         let elapsed = self
             .0
             .notes
@@ -92,15 +166,109 @@ impl Demand {
                         .event(event_id),
                 ];
 
-                if let Some(r) = relay {
-                    let relays = self.0.client.relays().await;
-                    if let Some(r) = relays.get(&r) {
-                        r.req_events_of(sub, None);
+                let relays = self.0.client.relays().await;
+                let asked: Vec<Url> = if let Some(r) = relay {
+                    if let Some(relay) = relays.get(&r) {
+                        relay.req_events_of(sub.clone(), None);
                     }
+                    vec![r]
                 } else {
-                    self.0.client.req_events_of(sub, None).await;
-                }
+                    self.0.client.req_events_of(sub.clone(), None).await;
+                    relays.into_keys().collect()
+                };
+                self.ask_hinted(&hinted, &asked, &sub).await;
             }
         };
     }
+
+    /// Asks relays `hinted` by a link, other than ours, for `filters` too.
+    /// Relays that the client does not have are connected just for that.
+    pub async fn request_hinted(&self, filters: Vec<SubscriptionFilter>, hinted: Vec<Url>) {
+        let ours: Vec<Url> = self.0.client.relays().await.into_keys().collect();
+        self.ask_hinted(&hinted, &ours, &filters).await;
+    }
+
+    /// Requests applications handling events of other kinds from all
+    /// our relays, together with handlers recommended by `recommenders`.
+    /// Relays cannot be asked for handlers of a particular kind, so this
+    /// happens only once in a session.
+    pub async fn handlers(&self, recommenders: Vec<XOnlyPublicKey>) {
+        if self.0.handlers.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        info!("Requesting handlers of unsupported kinds.");
+
+        let filters = vec![
+            SubscriptionFilter::new()
+                .kind(Kind::Custom(HANDLER_KIND))
+                .limit(MAX_HANDLERS),
+            SubscriptionFilter::new()
+                .kind(Kind::Custom(HANDLER_RECOMMENDATION_KIND))
+                .authors(recommenders),
+        ];
+        self.0.client.req_events_of(filters, None).await;
+    }
+
+    /// Asks `hinted` relays, except those already `asked`, for `filters`.
+    /// Relays that the client does not have are connected just for that,
+    /// while there are not too many of them.
+    async fn ask_hinted(&self, hinted: &[Url], asked: &[Url], filters: &[SubscriptionFilter]) {
+        let ours = self.0.client.relays().await;
+
+        for url in hinted.iter().filter(|u| !asked.contains(u)) {
+            match ours.get(url) {
+                Some(relay) if relay.opts().read() => relay.req_events_of(filters.to_vec(), None),
+                Some(_) => {}
+                None => self.ask_adhoc(url, filters.to_vec()).await,
+            }
+        }
+    }
+
+    /// Connects `url` to ask it for `filters` and disconnects it after a while.
+    async fn ask_adhoc(&self, url: &Url, filters: Vec<SubscriptionFilter>) {
+        {
+            let mut adhoc = self.0.adhoc.lock().await;
+            if adhoc.len() >= MAX_ADHOC_RELAYS || !adhoc.insert(url.clone()) {
+                debug!(
+                    "Not connecting {} just to ask it, too many such relays",
+                    url
+                );
+                return;
+            }
+        }
+
+        let demand = self.clone();
+        let url = url.clone();
+        tokio::spawn(async move {
+            let client = &demand.0.client;
+            info!("Connecting {} just to ask it", url);
+
+            let added = client
+                .add_relay_with_opts(url.as_str(), None, RelayOptions::new(true, false))
+                .await;
+            if let Err(e) = added {
+                warn!("Could not add relay {}: {}", url, e);
+            } else {
+                let connected = tokio::time::timeout(
+                    ADHOC_CONNECT_TIMEOUT,
+                    client.connect_relay(url.as_str(), true),
+                )
+                .await;
+                match (connected, client.relays().await.get(&url)) {
+                    (Ok(Ok(())), Some(relay)) => {
+                        relay.req_events_of(filters, None);
+                        tokio::time::sleep(ADHOC_LINGER).await;
+                    }
+                    _ => warn!("Could not connect relay {}", url),
+                }
+
+                if let Err(e) = client.remove_relay(url.as_str()).await {
+                    warn!("Could not remove relay {}: {}", url, e);
+                }
+            }
+
+            demand.0.adhoc.lock().await.remove(&url);
+        });
+    }
 }
@@ -0,0 +1,76 @@
+//! Directories and files of Gnostique. By default those of the platform,
+//! unless overridden from the command line, e. g. to run several profiles.
+
+use std::path::{Path, PathBuf};
+
+use directories::ProjectDirs;
+
+/// File with our secret key, encrypted by password.
+const KEY_FILE: &str = "key";
+
+/// Name of the configuration file.
+const CONFIG_FILE: &str = "config.toml";
+
+/// Name of the file remembering state of the window, in data directory.
+const STATE_FILE: &str = "state.toml";
+
+/// Name of the file with user's styles, next to the configuration file.
+const STYLE_FILE: &str = "style.css";
+
+#[derive(Clone, Debug)]
+pub struct Dirs {
+    data: PathBuf,
+    cache: PathBuf,
+    config_file: PathBuf,
+    key_file: PathBuf,
+}
+
+impl Dirs {
+    /// Directories of the platform, with data kept in `data_dir` and
+    /// configuration read from `config_file` if they are given.
+    pub fn new(data_dir: Option<PathBuf>, config_file: Option<PathBuf>) -> Dirs {
+        let project = ProjectDirs::from("com.jirijakes", "", "Gnostique").unwrap();
+
+        // Key of the default profile has always been in working directory.
+        let (data, cache, key_file) = match data_dir {
+            Some(data) => (data.clone(), data.join("cache"), data.join(KEY_FILE)),
+            None => (
+                project.data_dir().to_path_buf(),
+                project.cache_dir().to_path_buf(),
+                PathBuf::from(KEY_FILE),
+            ),
+        };
+
+        Dirs {
+            data,
+            cache,
+            config_file: config_file.unwrap_or_else(|| project.config_dir().join(CONFIG_FILE)),
+            key_file,
+        }
+    }
+
+    pub fn data_dir(&self) -> &Path {
+        &self.data
+    }
+
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache
+    }
+
+    pub fn config_file(&self) -> &Path {
+        &self.config_file
+    }
+
+    /// CSS file with which user can restyle lanes and notes.
+    pub fn style_file(&self) -> PathBuf {
+        self.config_file.with_file_name(STYLE_FILE)
+    }
+
+    pub fn state_file(&self) -> PathBuf {
+        self.data.join(STATE_FILE)
+    }
+
+    pub fn key_file(&self) -> &Path {
+        &self.key_file
+    }
+}
@@ -1,108 +1,937 @@
-use std::collections::HashSet;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use directories::ProjectDirs;
 use futures_util::StreamExt;
 use nostr_sdk::prelude::*;
 use reqwest::{Client, Url};
+use sqlx::{query, SqlitePool};
 use tokio::io::AsyncWriteExt;
 use tokio::sync::Mutex;
-use tracing::info;
+use tracing::{info, warn};
+
+use crate::dirs::Dirs;
 
 #[derive(Clone, Default)]
 struct Status {
     downloading: HashSet<Url>,
+    /// Recently failed downloads, they are not attempted again until some time passes.
+    failed: HashMap<Url, Failure>,
+}
+
+#[derive(Clone)]
+struct Failure {
+    /// Number of consecutive failures.
+    attempts: u32,
+    /// Next download must not be attempted before this instant.
+    retry_after: Instant,
 }
 
 pub enum DownloadResult {
     File(PathBuf),
     Dowloading,
+    /// Download failed recently and will not be attempted for a while.
+    Failed,
 }
 
 impl DownloadResult {
     pub fn file(&self) -> Option<PathBuf> {
         match self {
             DownloadResult::File(f) => Some(f.clone()),
-            DownloadResult::Dowloading => None,
+            DownloadResult::Dowloading | DownloadResult::Failed => None,
+        }
+    }
+}
+
+/// Limits that apply to downloads and to download cache.
+#[derive(Clone, Copy, Debug)]
+pub struct DownloadConfig {
+    /// Once cached files exceed this size (in bytes), least recently used are removed.
+    pub max_cache_size: u64,
+    /// Largest accepted avatar in bytes.
+    pub max_avatar_size: u64,
+    /// Largest accepted banner in bytes.
+    pub max_banner_size: u64,
+    /// Largest accepted image embedded in a text note in bytes.
+    pub max_image_size: u64,
+    /// Largest accepted custom emoji (NIP-30) in bytes.
+    pub max_emoji_size: u64,
+    /// Cached files older than this are revalidated with the server on next access.
+    pub max_age: Duration,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        DownloadConfig {
+            max_cache_size: 500 * 1024 * 1024,
+            max_avatar_size: 5 * 1024 * 1024,
+            max_banner_size: 20 * 1024 * 1024,
+            max_image_size: 20 * 1024 * 1024,
+            max_emoji_size: 256 * 1024,
+            max_age: Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+}
+
+/// Kind of media being downloaded, determines limits applied to it.
+#[derive(Clone, Copy, Debug)]
+pub enum Media {
+    Avatar,
+    Banner,
+    /// Image embedded in a text note.
+    Image,
+    /// Custom emoji (NIP-30) displayed within text.
+    Emoji,
+}
+
+/// Image formats accepted into download cache.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+    Bmp,
+    Svg,
+}
+
+impl ImageFormat {
+    /// Recognizes image format by the first bytes of the file.
+    fn detect(bytes: &[u8]) -> Option<ImageFormat> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFormat::Png)
+        } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(ImageFormat::Jpeg)
+        } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            Some(ImageFormat::Gif)
+        } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+            Some(ImageFormat::Webp)
+        } else if bytes.starts_with(b"BM") {
+            Some(ImageFormat::Bmp)
+        } else {
+            let text = String::from_utf8_lossy(bytes);
+            let text = text.trim_start();
+            if text.starts_with("<svg") || (text.starts_with("<?xml") && text.contains("<svg")) {
+                Some(ImageFormat::Svg)
+            } else {
+                None
+            }
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpeg",
+            ImageFormat::Gif => "gif",
+            ImageFormat::Webp => "webp",
+            ImageFormat::Bmp => "bmp",
+            ImageFormat::Svg => "svg",
         }
     }
 }
 
+/// Longest time to wait for connection with a server.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest time a single request may take, including downloading of the body.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Number of first bytes of a download which are used to detect its format.
+const SNIFF_LENGTH: usize = 256;
+
+/// Rejected downloads, and cached files that turned out unusable, are not
+/// attempted again for this long.
+const REJECTED_RETRY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Validators of a cached file, they are sent with conditional requests.
+#[derive(Clone, Debug, Default)]
+struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// Outcome of a successful request.
+enum Fetched {
+    /// New content was downloaded.
+    Modified {
+        size: u64,
+        format: ImageFormat,
+        validators: Validators,
+    },
+    /// Cached file is still current.
+    NotModified,
+}
+
+/// Reasons why a download did not succeed.
+#[derive(Debug)]
+enum FetchError {
+    /// Problem with network or file system, it may go away soon.
+    Transient(String),
+    /// Response is not acceptable (too large, not an image), it is not
+    /// going to change soon.
+    Rejected(String),
+}
+
+impl std::fmt::Display for FetchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FetchError::Transient(e) => write!(f, "{e}"),
+            FetchError::Rejected(e) => write!(f, "rejected, {e}"),
+        }
+    }
+}
+
+/// Summary of files stored in download cache.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CacheStats {
+    /// Total size of all cached files in bytes.
+    pub size: u64,
+    /// Number of cached files.
+    pub files: u64,
+}
+
 #[derive(Clone)]
 pub struct Download(Arc<DownloadInner>);
 
 pub struct DownloadInner {
-    dirs: ProjectDirs,
+    dirs: Dirs,
+    pool: SqlitePool,
     http: Client,
     status: Arc<Mutex<Status>>,
+    config: DownloadConfig,
 }
 
 impl Download {
-    pub fn new(dirs: ProjectDirs) -> Download {
+    pub fn new(dirs: Dirs, pool: SqlitePool, config: DownloadConfig) -> Download {
         Download(Arc::new(DownloadInner {
             dirs,
-            http: Default::default(),
+            pool,
+            http: Client::builder()
+                .connect_timeout(CONNECT_TIMEOUT)
+                .timeout(TIMEOUT)
+                .build()
+                .expect("HTTP client cannot be created"),
             status: Default::default(),
+            config,
         }))
     }
 
-    pub fn cached(&self, url: &Url) -> Option<PathBuf> {
+    /// HTTP client shared by everything that downloads from the web.
+    pub fn http(&self) -> &Client {
+        &self.0.http
+    }
+
+    pub async fn cached(&self, url: &Url) -> Option<PathBuf> {
         let url_s = url.to_string();
         let filename = sha256::Hash::hash(url_s.as_bytes()).to_string();
         let file = self.0.dirs.cache_dir().join("bitmaps").join(filename);
 
         if file.is_file() {
+            self.touch(url).await;
             Some(file)
         } else {
             None
         }
     }
 
-    pub async fn to_cached_file(&self, url: &Url) -> DownloadResult {
+    /// Summary of what is currently stored in the cache.
+    pub async fn stats(&self) -> CacheStats {
+        query!(
+            r#"SELECT COUNT(*) AS "files!: i64", COALESCE(SUM(size), 0) AS "size!: i64" FROM downloads"#
+        )
+        .fetch_one(&self.0.pool)
+        .await
+        .map(|r| CacheStats {
+            size: r.size as u64,
+            files: r.files as u64,
+        })
+        .unwrap_or_default()
+    }
+
+    /// Removes all cached files. Their records are removed first, so if
+    /// removing files fails, no record points to a missing file and the
+    /// files left behind are simply downloaded again.
+    pub async fn clear(&self) -> Result<(), String> {
+        query!("DELETE FROM downloads")
+            .execute(&self.0.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let cache = self.0.dirs.cache_dir().join("bitmaps");
+        if cache.is_dir() {
+            tokio::fs::remove_dir_all(&cache)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        info!("Download cache cleared");
+        Ok(())
+    }
+
+    pub async fn to_cached_file(&self, url: &Url, media: Media) -> DownloadResult {
         let url_s = url.to_string();
         let filename = sha256::Hash::hash(url_s.as_bytes()).to_string();
 
         let cache = self.0.dirs.cache_dir().join("bitmaps");
-        tokio::fs::create_dir_all(&cache).await.unwrap();
+        if let Err(e) = tokio::fs::create_dir_all(&cache).await {
+            warn!("Cannot create download cache {:?}: {}", cache, e);
+            return DownloadResult::Failed;
+        }
         let file = cache.join(&filename);
 
-        let downloading = self.0.status.lock().await.downloading.contains(url);
+        let cached = file.is_file();
 
-        if downloading {
-            info!("File from {} is already in cache", url_s);
-            DownloadResult::Dowloading
-        } else if file.is_file() {
+        if cached && !self.is_stale(url).await {
             info!(
                 "File from {} is already in cache as {:?}",
                 url_s,
                 file.file_name()
             );
+            self.touch(url).await;
             DownloadResult::File(file)
+        } else if self.backing_off(url).await {
+            info!("Not downloading {}, it failed recently", url_s);
+            if cached {
+                DownloadResult::File(file)
+            } else {
+                DownloadResult::Failed
+            }
+        } else if !self.0.status.lock().await.downloading.insert(url.clone()) {
+            info!("File from {} is already being downloaded", url_s);
+            DownloadResult::Dowloading
         } else {
-            self.0.status.lock().await.downloading.insert(url.clone());
+            // Stale file is revalidated, possibly without downloading it again.
+            let validators = if cached {
+                self.validators(url).await
+            } else {
+                None
+            };
 
             let tmp = cache.join(format!("{filename}.part"));
             info!("Downloading {} to {:?}", url_s, tmp);
 
-            let mut f = tokio::fs::File::create(&tmp).await.unwrap();
-            let response = self.0.http.get(url.clone()).send().await.unwrap();
-            // let content_length = response.headers().get("content-length");
-            let mut bytes = response.bytes_stream();
+            let result = self.fetch(url, media, validators, &tmp, &file).await;
+
+            let mut status = self.0.status.lock().await;
+            status.downloading.remove(url);
+
+            match result {
+                Ok(Fetched::NotModified) => {
+                    info!("File from {} has not been modified", url_s);
+                    status.failed.remove(url);
+                    drop(status);
+
+                    self.revalidated(url).await;
+
+                    DownloadResult::File(file)
+                }
+                Ok(Fetched::Modified {
+                    size,
+                    format,
+                    validators,
+                }) => {
+                    info!(
+                        "Download of {} ({}) finished, cached as {:?}",
+                        url_s,
+                        format.as_str(),
+                        file
+                    );
+                    status.failed.remove(url);
+                    drop(status);
 
-            while let Some(chunk) = bytes.next().await {
-                let c = chunk.unwrap();
-                // println!("{}", c.len());
-                f.write_all(&c).await.unwrap();
+                    self.record(url, &filename, size, format, validators).await;
+                    self.evict().await;
+
+                    DownloadResult::File(file)
+                }
+                Err(e) => {
+                    warn!("Download of {} failed: {}", url_s, e);
+                    let _ = tokio::fs::remove_file(&tmp).await;
+
+                    let attempts = status.failed.get(url).map(|f| f.attempts).unwrap_or(0) + 1;
+                    let delay = match e {
+                        // 2, 4, 8, … minutes, at most about a day.
+                        FetchError::Transient(_) => {
+                            Duration::from_secs(60 * 2u64.pow(attempts.min(10)))
+                        }
+                        // Whatever is there will likely stay there for a while.
+                        FetchError::Rejected(_) => REJECTED_RETRY,
+                    };
+                    status.failed.insert(
+                        url.clone(),
+                        Failure {
+                            attempts,
+                            retry_after: Instant::now() + delay,
+                        },
+                    );
+
+                    // Stale file is still better than nothing.
+                    if cached {
+                        DownloadResult::File(file)
+                    } else {
+                        DownloadResult::Failed
+                    }
+                }
             }
+        }
+    }
+
+    /// Removes cached file from `url` which cannot be used, such as
+    /// a corrupt image. It is not downloaded again for a while.
+    pub async fn mark_bad(&self, url: &Url) {
+        let url_s = url.to_string();
+        let filename = sha256::Hash::hash(url_s.as_bytes()).to_string();
+        let file = self.0.dirs.cache_dir().join("bitmaps").join(filename);
 
-            tokio::fs::rename(&tmp, &file).await.unwrap();
+        warn!("Cached file from {} is unusable, removing it", url_s);
+        let _ = tokio::fs::remove_file(&file).await;
+        let _ = query!("DELETE FROM downloads WHERE url = ?", url_s)
+            .execute(&self.0.pool)
+            .await;
 
-            info!("Download of {} finished, cached as {:?}", url_s, file);
+        let mut status = self.0.status.lock().await;
+        let attempts = status.failed.get(url).map(|f| f.attempts).unwrap_or(0) + 1;
+        status.failed.insert(
+            url.clone(),
+            Failure {
+                attempts,
+                retry_after: Instant::now() + REJECTED_RETRY,
+            },
+        );
+    }
 
-            self.0.status.lock().await.downloading.remove(url);
+    /// Whether cached file from `url` has not been validated for too long.
+    async fn is_stale(&self, url: &Url) -> bool {
+        let url_s = url.to_string();
+        let max_age = self.0.config.max_age.as_secs() as i64;
 
-            DownloadResult::File(file)
+        query!(
+            r#"
+SELECT validated IS NULL OR unixepoch('now') - unixepoch(validated) > ? AS "stale!: bool"
+FROM downloads WHERE url = ?"#,
+            max_age,
+            url_s
+        )
+        .fetch_optional(&self.0.pool)
+        .await
+        .ok()
+        .flatten()
+        .map(|r| r.stale)
+        .unwrap_or(true)
+    }
+
+    /// Validators of cached file from `url`.
+    async fn validators(&self, url: &Url) -> Option<Validators> {
+        let url_s = url.to_string();
+
+        query!(
+            "SELECT etag, last_modified FROM downloads WHERE url = ?",
+            url_s
+        )
+        .fetch_optional(&self.0.pool)
+        .await
+        .ok()
+        .flatten()
+        .map(|r| Validators {
+            etag: r.etag,
+            last_modified: r.last_modified,
+        })
+    }
+
+    /// Remembers that cached file from `url` is still current.
+    async fn revalidated(&self, url: &Url) {
+        let url_s = url.to_string();
+        let _ = query!(
+            r#"
+UPDATE downloads SET validated = CURRENT_TIMESTAMP, accessed = CURRENT_TIMESTAMP
+WHERE url = ?"#,
+            url_s
+        )
+        .execute(&self.0.pool)
+        .await;
+    }
+
+    /// Whether download of `url` failed recently and should not be attempted yet.
+    async fn backing_off(&self, url: &Url) -> bool {
+        matches!(
+            self.0.status.lock().await.failed.get(url),
+            Some(f) if f.retry_after > Instant::now()
+        )
+    }
+
+    /// Remembers that file from `url` was just accessed.
+    async fn touch(&self, url: &Url) {
+        let url_s = url.to_string();
+        let _ = query!(
+            "UPDATE downloads SET accessed = CURRENT_TIMESTAMP WHERE url = ?",
+            url_s
+        )
+        .execute(&self.0.pool)
+        .await;
+    }
+
+    /// Remembers newly downloaded file `filename` of `size` bytes from `url`.
+    async fn record(
+        &self,
+        url: &Url,
+        filename: &str,
+        size: u64,
+        format: ImageFormat,
+        validators: Validators,
+    ) {
+        let url_s = url.to_string();
+        let size = size as i64;
+        let format = format.as_str();
+        let _ = query!(
+            r#"
+INSERT INTO downloads (url, file, size, format, etag, last_modified, validated)
+VALUES (?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP)
+ON CONFLICT (url) DO UPDATE SET
+  file = EXCLUDED.file,
+  size = EXCLUDED.size,
+  format = EXCLUDED.format,
+  etag = EXCLUDED.etag,
+  last_modified = EXCLUDED.last_modified,
+  validated = EXCLUDED.validated,
+  accessed = CURRENT_TIMESTAMP
+"#,
+            url_s,
+            filename,
+            size,
+            format,
+            validators.etag,
+            validators.last_modified
+        )
+        .execute(&self.0.pool)
+        .await;
+    }
+
+    /// Removes least recently used files until the cache fits into its
+    /// maximum size. Files that are being downloaded are kept. Files of
+    /// currently displayed avatars are not tracked here, but they have
+    /// been already loaded and so can be removed safely.
+    async fn evict(&self) {
+        if let Err(e) = self.try_evict().await {
+            warn!("Could not evict files from download cache: {}", e);
+        }
+    }
+
+    /// Chooses files to evict and removes their records in one transaction,
+    /// so that concurrent downloads do not see the cache half-evicted. Files
+    /// themselves are removed only once the records are gone.
+    async fn try_evict(&self) -> Result<(), sqlx::Error> {
+        let mut tx = self.0.pool.begin().await?;
+
+        let total = query!(r#"SELECT COALESCE(SUM(size), 0) AS "size!: i64" FROM downloads"#)
+            .fetch_one(&mut tx)
+            .await?
+            .size as u64;
+
+        if total <= self.0.config.max_cache_size {
+            return Ok(());
+        }
+
+        let downloading = self.0.status.lock().await.downloading.clone();
+        let files = query!("SELECT url, file, size FROM downloads ORDER BY accessed")
+            .fetch_all(&mut tx)
+            .await?;
+
+        let mut excess = total - self.0.config.max_cache_size;
+        let mut evicted = Vec::new();
+
+        for f in files {
+            if excess == 0 {
+                break;
+            }
+
+            if downloading.iter().any(|u| u.as_str() == f.url) {
+                continue;
+            }
+
+            query!("DELETE FROM downloads WHERE url = ?", f.url)
+                .execute(&mut tx)
+                .await?;
+
+            excess = excess.saturating_sub(f.size as u64);
+            evicted.push(f);
+        }
+
+        tx.commit().await?;
+
+        let cache = self.0.dirs.cache_dir().join("bitmaps");
+        for f in evicted {
+            let _ = tokio::fs::remove_file(cache.join(&f.file)).await;
+            info!("Evicted {} from download cache", f.url);
+        }
+
+        Ok(())
+    }
+
+    /// Downloads `url` into `tmp` and, when complete, moves it to `file`.
+    /// Only images within size limit of `media` are accepted. If `validators`
+    /// of already cached file are given, the file is downloaded only if it
+    /// was modified since.
+    async fn fetch(
+        &self,
+        url: &Url,
+        media: Media,
+        validators: Option<Validators>,
+        tmp: &Path,
+        file: &Path,
+    ) -> Result<Fetched, FetchError> {
+        let max_size = match media {
+            Media::Avatar => self.0.config.max_avatar_size,
+            Media::Banner => self.0.config.max_banner_size,
+            Media::Image => self.0.config.max_image_size,
+            Media::Emoji => self.0.config.max_emoji_size,
+        };
+
+        let mut request = self.0.http.get(url.clone());
+
+        if let Some(v) = validators {
+            if let Some(etag) = v.etag {
+                request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = v.last_modified {
+                request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = request
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| FetchError::Transient(e.to_string()))?;
+
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(Fetched::NotModified);
+        }
+
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v: &reqwest::header::HeaderValue| v.to_str().ok())
+                .map(|v| v.to_string())
+        };
+
+        let validators = Validators {
+            etag: header(reqwest::header::ETAG),
+            last_modified: header(reqwest::header::LAST_MODIFIED),
+        };
+
+        if let Some(length) = response.content_length() {
+            if length > max_size {
+                return Err(FetchError::Rejected(format!(
+                    "{length} bytes is over limit of {max_size} bytes"
+                )));
+            }
+        }
+
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_lowercase());
+
+        // Some servers do not know better than octet-stream, magic bytes decide then.
+        if let Some(ref ct) = content_type {
+            if !ct.starts_with("image/") && !ct.starts_with("application/octet-stream") {
+                return Err(FetchError::Rejected(format!("content type {ct}")));
+            }
+        }
+
+        let mut f = tokio::fs::File::create(tmp)
+            .await
+            .map_err(|e| FetchError::Transient(e.to_string()))?;
+
+        let mut bytes = response.bytes_stream();
+        let mut size = 0;
+        let mut head = Vec::with_capacity(SNIFF_LENGTH);
+        let mut format = None;
+
+        while let Some(chunk) = bytes.next().await {
+            let c = chunk.map_err(|e| FetchError::Transient(e.to_string()))?;
+            size += c.len() as u64;
+
+            if size > max_size {
+                return Err(FetchError::Rejected(format!("more than {max_size} bytes")));
+            }
+
+            if format.is_none() && head.len() < SNIFF_LENGTH {
+                head.extend_from_slice(&c[..c.len().min(SNIFF_LENGTH - head.len())]);
+                if head.len() == SNIFF_LENGTH {
+                    format = Some(Self::sniff(&head)?);
+                }
+            }
+
+            f.write_all(&c)
+                .await
+                .map_err(|e| FetchError::Transient(e.to_string()))?;
         }
+
+        // Files shorter than sniffing length.
+        let format = match format {
+            Some(f) => f,
+            None => Self::sniff(&head)?,
+        };
+
+        tokio::fs::rename(tmp, file)
+            .await
+            .map_err(|e| FetchError::Transient(e.to_string()))?;
+
+        Ok(Fetched::Modified {
+            size,
+            format,
+            validators,
+        })
+    }
+
+    fn sniff(head: &[u8]) -> Result<ImageFormat, FetchError> {
+        ImageFormat::detect(head)
+            .ok_or_else(|| FetchError::Rejected("not a supported image".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{http_response, StubServer, TestDb};
+
+    /// Smallest valid PNG, a single transparent pixel.
+    const PNG: &[u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f,
+        0x15, 0xc4, 0x89, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x00,
+        0x01, 0x00, 0x00, 0x05, 0x00, 0x01, 0x0d, 0x0a, 0x2d, 0xb4, 0x00, 0x00, 0x00, 0x00, 0x49,
+        0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+    ];
+
+    /// Puts file of `size` bytes from `url` into cache, as if it was
+    /// downloaded `age` ago.
+    async fn cache(download: &Download, url: &str, size: usize, age: &str) -> PathBuf {
+        let url = Url::parse(url).unwrap();
+        let filename = sha256::Hash::hash(url.as_str().as_bytes()).to_string();
+        let cache = download.0.dirs.cache_dir().join("bitmaps");
+        tokio::fs::create_dir_all(&cache).await.unwrap();
+        let file = cache.join(&filename);
+        tokio::fs::write(&file, vec![0; size]).await.unwrap();
+
+        download
+            .record(
+                &url,
+                &filename,
+                size as u64,
+                ImageFormat::Png,
+                Validators::default(),
+            )
+            .await;
+        sqlx::query("UPDATE downloads SET accessed = datetime('now', ?) WHERE url = ?")
+            .bind(age)
+            .bind(url.as_str())
+            .execute(&download.0.pool)
+            .await
+            .unwrap();
+
+        file
+    }
+
+    fn download(db: &TestDb, max_cache_size: u64) -> Download {
+        let config = DownloadConfig {
+            max_cache_size,
+            ..Default::default()
+        };
+        Download::new(db.dirs(), db.pool().clone(), config)
+    }
+
+    #[tokio::test]
+    async fn eviction_removes_least_recently_used() {
+        let db = TestDb::new().await.unwrap();
+        let download = download(&db, 250);
+
+        let oldest = cache(&download, "https://example.com/a.png", 100, "-3 hours").await;
+        let older = cache(&download, "https://example.com/b.png", 100, "-2 hours").await;
+        let newest = cache(&download, "https://example.com/c.png", 100, "-1 hours").await;
+
+        download.evict().await;
+
+        assert!(!oldest.exists());
+        assert!(older.exists());
+        assert!(newest.exists());
+
+        let stats = download.stats().await;
+        assert_eq!(stats.files, 2);
+        assert_eq!(stats.size, 200);
+    }
+
+    #[tokio::test]
+    async fn clearing_removes_records_and_files() {
+        let db = TestDb::new().await.unwrap();
+        let download = download(&db, 1000);
+
+        let file = cache(&download, "https://example.com/a.png", 100, "-1 hours").await;
+        download.clear().await.unwrap();
+
+        assert!(!file.exists());
+        assert_eq!(download.stats().await.files, 0);
+    }
+
+    /// Download of an image from `server`, whose size is limited to 1000 bytes.
+    async fn fetch_image(db: &TestDb, server: &StubServer) -> DownloadResult {
+        let config = DownloadConfig {
+            max_image_size: 1000,
+            ..Default::default()
+        };
+        Download::new(db.dirs(), db.pool().clone(), config)
+            .to_cached_file(&server.url("/image.png"), Media::Image)
+            .await
+    }
+
+    #[tokio::test]
+    async fn image_is_cached() {
+        let db = TestDb::new().await.unwrap();
+        let server = StubServer::always(http_response(
+            "200 OK",
+            &[("Content-Type", "image/png")],
+            PNG,
+        ))
+        .await;
+
+        let file = fetch_image(&db, &server).await.file().unwrap();
+        assert_eq!(tokio::fs::read(file).await.unwrap(), PNG);
+    }
+
+    #[tokio::test]
+    async fn oversized_image_is_rejected() {
+        let db = TestDb::new().await.unwrap();
+        let large = [PNG, &[0; 1000]].concat();
+
+        // Announced by its length.
+        let server = StubServer::always(http_response(
+            "200 OK",
+            &[("Content-Type", "image/png")],
+            &large,
+        ))
+        .await;
+        assert!(matches!(
+            fetch_image(&db, &server).await,
+            DownloadResult::Failed
+        ));
+
+        // Found out only while downloading.
+        let mut response =
+            b"HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nConnection: close\r\n\r\n".to_vec();
+        response.extend_from_slice(&large);
+        let server = StubServer::always(response).await;
+        assert!(matches!(
+            fetch_image(&db, &server).await,
+            DownloadResult::Failed
+        ));
+
+        assert_eq!(
+            Download::new(db.dirs(), db.pool().clone(), Default::default())
+                .stats()
+                .await
+                .files,
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn html_is_rejected_and_not_asked_for_again() {
+        let db = TestDb::new().await.unwrap();
+        let server = StubServer::always(http_response(
+            "200 OK",
+            &[("Content-Type", "text/html")],
+            b"<html><body>Not found</body></html>",
+        ))
+        .await;
+        let download = Download::new(db.dirs(), db.pool().clone(), Default::default());
+        let url = server.url("/image.png");
+
+        for _ in 0..2 {
+            assert!(matches!(
+                download.to_cached_file(&url, Media::Image).await,
+                DownloadResult::Failed
+            ));
+        }
+
+        assert_eq!(server.requests().len(), 1);
+        assert_eq!(download.stats().await.files, 0);
+    }
+
+    /// Server that sends `PNG` with `etag` `"v1"` and answers conditional
+    /// requests by `revalidated`.
+    async fn revalidating_server(revalidated: Vec<u8>) -> StubServer {
+        StubServer::new(move |head| {
+            if head.to_lowercase().contains("if-none-match: \"v1\"") {
+                revalidated.clone()
+            } else {
+                http_response(
+                    "200 OK",
+                    &[("Content-Type", "image/png"), ("ETag", "\"v1\"")],
+                    PNG,
+                )
+            }
+        })
+        .await
+    }
+
+    /// Downloads `url` into cache and lets it become stale.
+    async fn cache_stale(download: &Download, url: &Url) -> PathBuf {
+        let file = download
+            .to_cached_file(url, Media::Image)
+            .await
+            .file()
+            .unwrap();
+        sqlx::query("UPDATE downloads SET validated = datetime('now', '-30 days')")
+            .execute(&download.0.pool)
+            .await
+            .unwrap();
+        assert!(download.is_stale(url).await);
+        file
+    }
+
+    #[tokio::test]
+    async fn stale_file_is_kept_when_not_modified() {
+        let db = TestDb::new().await.unwrap();
+        let server = revalidating_server(http_response("304 Not Modified", &[], b"")).await;
+        let download = Download::new(db.dirs(), db.pool().clone(), Default::default());
+        let url = server.url("/image.png");
+        let file = cache_stale(&download, &url).await;
+
+        let revalidated = download.to_cached_file(&url, Media::Image).await.file();
+
+        assert_eq!(revalidated, Some(file.clone()));
+        assert_eq!(tokio::fs::read(file).await.unwrap(), PNG);
+        assert_eq!(server.requests().len(), 2);
+        assert!(!download.is_stale(&url).await);
+    }
+
+    #[tokio::test]
+    async fn stale_file_is_replaced_when_modified() {
+        let db = TestDb::new().await.unwrap();
+        let modified = [PNG, b"v2"].concat();
+        let server = revalidating_server(http_response(
+            "200 OK",
+            &[("Content-Type", "image/png"), ("ETag", "\"v2\"")],
+            &modified,
+        ))
+        .await;
+        let download = Download::new(db.dirs(), db.pool().clone(), Default::default());
+        let url = server.url("/image.png");
+        let file = cache_stale(&download, &url).await;
+
+        let revalidated = download.to_cached_file(&url, Media::Image).await.file();
+
+        assert_eq!(revalidated, Some(file.clone()));
+        assert_eq!(tokio::fs::read(file).await.unwrap(), modified);
+        assert_eq!(
+            download.validators(&url).await.unwrap().etag.as_deref(),
+            Some("\"v2\"")
+        );
+        assert!(!download.is_stale(&url).await);
     }
 }
@@ -0,0 +1,361 @@
+//! Drafts of notes being written. The composer saves its note every few
+//! seconds and when it is closed, and offers the latest draft of the same
+//! reply or quote when it is opened again. Published notes are not drafts
+//! anymore.
+
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use nostr_sdk::prelude::*;
+use sqlx::{query, SqlitePool};
+
+/// At most this many characters of content tell drafts apart in their list.
+const SUMMARY_CHARS: usize = 60;
+
+#[derive(Clone, Debug)]
+pub struct Draft {
+    pub id: i64,
+    pub subject: String,
+    pub content: String,
+    /// Note being replied to, if any.
+    pub reply_to: Option<Arc<Event>>,
+    /// Note being quoted, if any, and relays where it has been seen.
+    pub quoting: Option<(Arc<Event>, Vec<Url>)>,
+    pub updated_at: Timestamp,
+}
+
+impl Draft {
+    /// ID of a new draft, unique unless two drafts are started within
+    /// the same microsecond.
+    pub fn new_id() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros() as i64)
+            .unwrap_or_default()
+    }
+
+    /// Whether there is nothing in the draft worth keeping.
+    pub fn is_empty(&self) -> bool {
+        self.subject.trim().is_empty() && self.content.trim().is_empty()
+    }
+
+    /// Beginning of the draft on a single line.
+    pub fn summary(&self) -> String {
+        let text = if self.subject.trim().is_empty() {
+            &self.content
+        } else {
+            &self.subject
+        };
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        let summary = if text.chars().count() > SUMMARY_CHARS {
+            let cut: String = text.chars().take(SUMMARY_CHARS).collect();
+            format!("{}…", cut.trim_end())
+        } else {
+            text
+        };
+
+        match (&self.reply_to, &self.quoting) {
+            (Some(_), _) => format!("Reply: {summary}"),
+            (None, Some(_)) => format!("Quote: {summary}"),
+            (None, None) => summary,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Drafts {
+    pool: SqlitePool,
+}
+
+impl Drafts {
+    pub fn new(pool: SqlitePool) -> Drafts {
+        Drafts { pool }
+    }
+
+    /// Stores `draft`, replacing its earlier version.
+    pub async fn save(&self, draft: &Draft) -> Result<(), String> {
+        let reply_to = draft
+            .reply_to
+            .as_ref()
+            .map(|e| e.as_json())
+            .transpose()
+            .map_err(|e| e.to_string())?;
+        let reply_to_id = draft.reply_to.as_ref().map(|e| e.id.as_bytes().to_vec());
+        let quote = draft
+            .quoting
+            .as_ref()
+            .map(|(e, _)| e.as_json())
+            .transpose()
+            .map_err(|e| e.to_string())?;
+        let quote_id = draft
+            .quoting
+            .as_ref()
+            .map(|(e, _)| e.id.as_bytes().to_vec());
+        let quote_relays = serde_json::to_string(
+            &draft
+                .quoting
+                .as_ref()
+                .map(|(_, relays)| relays.clone())
+                .unwrap_or_default(),
+        )
+        .unwrap();
+        let updated_at = draft.updated_at.as_i64();
+
+        query!(
+            r#"
+INSERT INTO drafts (id, subject, content, reply_to, reply_to_id, quote, quote_id, quote_relays, updated_at)
+VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+ON CONFLICT (id) DO UPDATE SET
+    subject = EXCLUDED.subject,
+    content = EXCLUDED.content,
+    reply_to = EXCLUDED.reply_to,
+    reply_to_id = EXCLUDED.reply_to_id,
+    quote = EXCLUDED.quote,
+    quote_id = EXCLUDED.quote_id,
+    quote_relays = EXCLUDED.quote_relays,
+    updated_at = EXCLUDED.updated_at
+"#,
+            draft.id,
+            draft.subject,
+            draft.content,
+            reply_to,
+            reply_to_id,
+            quote,
+            quote_id,
+            quote_relays,
+            updated_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    pub async fn delete(&self, id: i64) -> Result<(), String> {
+        query!("DELETE FROM drafts WHERE id = ?", id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Most recent draft replying to `reply_to` and quoting `quote`,
+    /// where none of them means a new note.
+    pub async fn latest(
+        &self,
+        reply_to: Option<EventId>,
+        quote: Option<EventId>,
+    ) -> Result<Option<Draft>, String> {
+        let reply_to_id = reply_to.map(|id| id.as_bytes().to_vec());
+        let quote_id = quote.map(|id| id.as_bytes().to_vec());
+
+        let row = query!(
+            r#"
+SELECT id, subject, content, reply_to, quote, quote_relays, updated_at
+FROM drafts
+WHERE reply_to_id IS ? AND quote_id IS ?
+ORDER BY updated_at DESC
+LIMIT 1
+"#,
+            reply_to_id,
+            quote_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(row.map(|r| {
+            draft(
+                r.id,
+                r.subject,
+                r.content,
+                r.reply_to,
+                r.quote,
+                r.quote_relays,
+                r.updated_at,
+            )
+        }))
+    }
+
+    /// All drafts, most recent first.
+    pub async fn list(&self) -> Result<Vec<Draft>, String> {
+        let rows = query!(
+            r#"
+SELECT id, subject, content, reply_to, quote, quote_relays, updated_at
+FROM drafts
+ORDER BY updated_at DESC
+"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                draft(
+                    r.id,
+                    r.subject,
+                    r.content,
+                    r.reply_to,
+                    r.quote,
+                    r.quote_relays,
+                    r.updated_at,
+                )
+            })
+            .collect())
+    }
+}
+
+/// Draft from its row. Notes which cannot be read anymore are dropped,
+/// the draft becomes a new note then.
+fn draft(
+    id: i64,
+    subject: String,
+    content: String,
+    reply_to: Option<String>,
+    quote: Option<String>,
+    quote_relays: String,
+    updated_at: i64,
+) -> Draft {
+    let event = |json: Option<String>| json.and_then(|j| Event::from_json(j).ok()).map(Arc::new);
+    let relays: Vec<Url> = serde_json::from_str(&quote_relays).unwrap_or_default();
+
+    Draft {
+        id,
+        subject,
+        content,
+        reply_to: event(reply_to),
+        quoting: event(quote).map(|e| (e, relays)),
+        updated_at: Timestamp::from(updated_at as u64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Fixtures, TestDb};
+
+    fn draft_of(
+        id: i64,
+        content: &str,
+        reply_to: Option<&Event>,
+        quoting: Option<&Event>,
+        updated_at: u64,
+    ) -> Draft {
+        Draft {
+            id,
+            subject: String::new(),
+            content: content.to_string(),
+            reply_to: reply_to.cloned().map(Arc::new),
+            quoting: quoting.cloned().map(|e| {
+                (
+                    Arc::new(e),
+                    vec![Url::parse("wss://relay.example.com").unwrap()],
+                )
+            }),
+            updated_at: Timestamp::from(updated_at),
+        }
+    }
+
+    #[test]
+    fn summary_is_one_short_line() {
+        let mut draft = draft_of(1, "  Hello\n\n  world  ", None, None, 0);
+        assert_eq!(draft.summary(), "Hello world");
+
+        draft.content = format!("{} end", "word ".repeat(20));
+        let summary = draft.summary();
+        assert!(summary.ends_with("word…"), "{summary}");
+        assert_eq!(summary.chars().count(), SUMMARY_CHARS);
+
+        draft.subject = "Subject".to_string();
+        assert_eq!(draft.summary(), "Subject");
+
+        let note = Fixtures::new().text_note("Hi");
+        assert_eq!(
+            draft_of(1, "Yes", Some(&note), Some(&note), 0).summary(),
+            "Reply: Yes"
+        );
+        assert_eq!(
+            draft_of(1, "Look", None, Some(&note), 0).summary(),
+            "Quote: Look"
+        );
+    }
+
+    #[test]
+    fn whitespace_is_not_worth_keeping() {
+        assert!(draft_of(1, " \n\t", None, None, 0).is_empty());
+        let mut draft = draft_of(1, "", None, None, 0);
+        draft.subject = "Subject".to_string();
+        assert!(!draft.is_empty());
+    }
+
+    #[tokio::test]
+    async fn latest_draft_of_same_reply_or_quote_is_offered() {
+        let db = TestDb::new().await.unwrap();
+        let drafts = Drafts::new(db.pool().clone());
+        let (note, other) = (
+            Fixtures::new().text_note("Note"),
+            Fixtures::new().text_note("Other"),
+        );
+
+        for draft in [
+            draft_of(1, "New, older", None, None, 100),
+            draft_of(2, "New, newer", None, None, 200),
+            draft_of(3, "Reply", Some(&note), None, 300),
+            draft_of(4, "Quote", None, Some(&note), 400),
+        ] {
+            drafts.save(&draft).await.unwrap();
+        }
+        // Saved again with more content.
+        drafts
+            .save(&draft_of(1, "New, newest", None, None, 500))
+            .await
+            .unwrap();
+
+        let content = |d: Option<Draft>| d.map(|d| d.content);
+        assert_eq!(
+            content(drafts.latest(None, None).await.unwrap()).as_deref(),
+            Some("New, newest")
+        );
+        assert_eq!(
+            content(drafts.latest(Some(note.id), None).await.unwrap()).as_deref(),
+            Some("Reply")
+        );
+        let quote = drafts.latest(None, Some(note.id)).await.unwrap().unwrap();
+        assert_eq!(quote.quoting.as_ref().map(|(e, _)| e.id), Some(note.id));
+        assert_eq!(
+            quote.quoting.unwrap().1,
+            vec![Url::parse("wss://relay.example.com").unwrap()]
+        );
+        assert!(drafts.latest(Some(other.id), None).await.unwrap().is_none());
+
+        let listed: Vec<i64> = drafts.list().await.unwrap().iter().map(|d| d.id).collect();
+        assert_eq!(listed, vec![1, 4, 3, 2]);
+
+        drafts.delete(1).await.unwrap();
+        assert_eq!(
+            content(drafts.latest(None, None).await.unwrap()).as_deref(),
+            Some("New, newer")
+        );
+    }
+
+    #[test]
+    fn unreadable_notes_turn_draft_into_new_note() {
+        let draft = draft(
+            1,
+            String::new(),
+            "Text".to_string(),
+            Some("{broken".to_string()),
+            None,
+            "not json".to_string(),
+            100,
+        );
+
+        assert!(draft.reply_to.is_none());
+        assert!(draft.quoting.is_none());
+        assert_eq!(draft.summary(), "Text");
+    }
+}
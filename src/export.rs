@@ -0,0 +1,547 @@
+//! Export of a thread, or of notes of a profile, into a file to be read
+//! outside nostr: a standalone HTML page with avatars embedded in it, or
+//! Markdown with replies nested in blockquotes below notes they reply to.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use base64::Engine;
+use nostr_sdk::prelude::{Event, EventId, Tag, ToBech32, XOnlyPublicKey};
+
+use crate::content::{Target, Token};
+use crate::nostr::EventExt;
+use crate::timestamps::{format_minute, TimeFormat};
+
+/// Page style, kept inside the file so that it needs nothing else.
+const HTML_STYLE: &str = r#"
+body { max-width: 46em; margin: 2em auto; padding: 0 1em; font-family: sans-serif; line-height: 1.4; }
+article { margin: 1em 0; }
+article article { margin-left: 1.2em; padding-left: 1em; border-left: 2px solid #ccc; }
+header { display: flex; align-items: center; gap: 0.5em; }
+.avatar { width: 32px; height: 32px; border-radius: 50%; object-fit: cover; }
+.author { font-weight: bold; }
+time { color: #777; font-size: 0.9em; }
+.content { white-space: pre-wrap; overflow-wrap: anywhere; }
+"#;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Html,
+    Markdown,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Html => "html",
+            Format::Markdown => "md",
+        }
+    }
+}
+
+/// Note as it is exported.
+#[derive(Clone, Debug)]
+pub struct ExportedNote {
+    pub event: Arc<Event>,
+    /// Name of the author as displayed.
+    pub author: String,
+    /// Avatar of the author as `data:` URI, used only by HTML.
+    pub avatar: Option<String>,
+}
+
+/// The whole file with `notes` under `title`. Mentions of users in
+/// `names` show their names.
+pub fn render(
+    format: Format,
+    title: &str,
+    notes: &[ExportedNote],
+    names: &HashMap<XOnlyPublicKey, String>,
+    time: TimeFormat,
+) -> String {
+    match format {
+        Format::Html => html(title, notes, names, time),
+        Format::Markdown => markdown(title, notes, names, time),
+    }
+}
+
+/// Markdown with notes from the oldest, replies quoted below notes they
+/// reply to, one level of blockquote deeper.
+fn markdown(
+    title: &str,
+    notes: &[ExportedNote],
+    names: &HashMap<XOnlyPublicKey, String>,
+    time: TimeFormat,
+) -> String {
+    let mut out = format!("# {}\n", escape_markdown(title));
+
+    for (depth, note) in tree(notes) {
+        let quote = "> ".repeat(depth);
+        let mut push = |line: &str| {
+            out.push_str(format!("{quote}{line}").trim_end());
+            out.push('\n');
+        };
+
+        push("");
+        push(&format!(
+            "**{}** · {}",
+            escape_markdown(&note.author),
+            format_minute(note.event.created_at, time)
+        ));
+        push("");
+        for line in markdown_content(&note.event, names).lines() {
+            push(&escape_line_start(line));
+        }
+    }
+
+    out
+}
+
+/// Standalone HTML page with notes from the oldest, replies nested in
+/// notes they reply to.
+fn html(
+    title: &str,
+    notes: &[ExportedNote],
+    names: &HashMap<XOnlyPublicKey, String>,
+    time: TimeFormat,
+) -> String {
+    let title = html_escape::encode_text(title);
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{HTML_STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n"
+    );
+
+    let mut open = 0;
+    for (depth, note) in tree(notes) {
+        // Closes notes which this one does not reply to.
+        while open > depth {
+            out.push_str("</article>\n");
+            open -= 1;
+        }
+
+        out.push_str("<article>\n<header>");
+        if let Some(avatar) = &note.avatar {
+            out.push_str(&format!(
+                "<img class=\"avatar\" alt=\"\" src=\"{}\">",
+                html_escape::encode_double_quoted_attribute(avatar)
+            ));
+        }
+        out.push_str(&format!(
+            "<span class=\"author\">{}</span> <time>{}</time></header>\n<div class=\"content\">{}</div>\n",
+            html_escape::encode_text(&note.author),
+            html_escape::encode_text(&format_minute(note.event.created_at, time)),
+            html_content(&note.event, names)
+        ));
+        open = depth + 1;
+    }
+    for _ in 0..open {
+        out.push_str("</article>\n");
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Name of file with export titled `title`, such as `thread-by-alice`.
+pub fn file_stem(title: &str) -> String {
+    let stem = title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase();
+    if stem.is_empty() {
+        "notes".to_string()
+    } else {
+        stem
+    }
+}
+
+/// `data:` URI of an image file, `None` if it is not an image that
+/// browsers display.
+pub fn data_uri(bytes: &[u8]) -> Option<String> {
+    let mime = if bytes.starts_with(b"\x89PNG") {
+        "image/png"
+    } else if bytes.starts_with(b"\xff\xd8\xff") {
+        "image/jpeg"
+    } else if bytes.starts_with(b"GIF8") {
+        "image/gif"
+    } else if bytes.len() > 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        "image/webp"
+    } else {
+        return None;
+    };
+
+    Some(format!(
+        "data:{mime};base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    ))
+}
+
+/// `notes` in the order they are written, with how deep they are in
+/// the thread. Replies follow notes they reply to, oldest first. Notes
+/// replying to notes which are not exported start from the top.
+fn tree(notes: &[ExportedNote]) -> Vec<(usize, &ExportedNote)> {
+    let ids: HashSet<EventId> = notes.iter().map(|n| n.event.id).collect();
+    let mut children: HashMap<Option<EventId>, Vec<&ExportedNote>> = HashMap::new();
+    for note in notes {
+        let parent = note.event.replies_to().filter(|p| ids.contains(p));
+        children.entry(parent).or_default().push(note);
+    }
+    for replies in children.values_mut() {
+        replies.sort_by_key(|n| n.event.created_at.as_i64());
+    }
+
+    let mut ordered = Vec::with_capacity(notes.len());
+    let mut stack: Vec<(usize, &ExportedNote)> = children
+        .get(&None)
+        .map(|roots| roots.iter().rev().map(|n| (0, *n)).collect())
+        .unwrap_or_default();
+    while let Some((depth, note)) = stack.pop() {
+        ordered.push((depth, note));
+        if let Some(replies) = children.get(&Some(note.event.id)) {
+            stack.extend(replies.iter().rev().map(|n| (depth + 1, *n)));
+        }
+    }
+
+    ordered
+}
+
+/// Where a nostr entity in content links to and what it shows: `@name`
+/// for users in `names`, the entity itself otherwise. References to tags
+/// which are neither `p` nor `e` are not links.
+fn entity_link(
+    token: &Token,
+    tags: &[Tag],
+    names: &HashMap<XOnlyPublicKey, String>,
+) -> Option<(String, String)> {
+    let profile = |pubkey: &XOnlyPublicKey, entity: String| match names.get(pubkey) {
+        Some(name) => (entity, format!("@{name}")),
+        None => (entity.clone(), entity),
+    };
+
+    match *token {
+        Token::Nostr(entity) => Some(match Target::from_bech32(entity) {
+            Some(Target::Profile(pubkey, _)) => profile(&pubkey, entity.to_string()),
+            _ => (entity.to_string(), entity.to_string()),
+        }),
+        Token::TagRef(index) => match tags.get(index) {
+            Some(Tag::PubKey(pubkey, _)) => Some(profile(pubkey, pubkey.to_bech32().ok()?)),
+            Some(Tag::Event(id, _, _)) => {
+                let note = id.to_bech32().ok()?;
+                Some((note.clone(), note))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Content of `event` as Markdown, with web links and nostr entities
+/// clickable and everything else escaped, so that it reads as written.
+fn markdown_content(event: &Event, names: &HashMap<XOnlyPublicKey, String>) -> String {
+    let link = |(entity, label): (String, String)| {
+        format!("[{}](nostr:{entity})", escape_markdown(&label))
+    };
+
+    crate::content::tokenize(&event.content)
+        .into_iter()
+        .map(|token| match token {
+            Token::Text(text) => escape_markdown(text),
+            Token::Link(url) => format!("<{url}>"),
+            Token::Hashtag(tag) => format!("#{}", escape_markdown(tag)),
+            Token::Lightning(request) => request.to_string(),
+            Token::Nostr(_) => entity_link(&token, &event.tags, names)
+                .map(link)
+                .unwrap_or_default(),
+            Token::TagRef(index) => entity_link(&token, &event.tags, names)
+                .map(link)
+                .unwrap_or_else(|| escape_markdown(&format!("#[{index}]"))),
+        })
+        .collect()
+}
+
+/// Content of `event` as HTML, with web links and nostr entities clickable.
+fn html_content(event: &Event, names: &HashMap<XOnlyPublicKey, String>) -> String {
+    let link = |href: &str, text: &str| {
+        format!(
+            "<a href=\"{}\">{}</a>",
+            html_escape::encode_double_quoted_attribute(href),
+            html_escape::encode_text(text)
+        )
+    };
+
+    crate::content::tokenize(&event.content)
+        .into_iter()
+        .map(|token| match token {
+            Token::Text(text) => html_escape::encode_text(text).to_string(),
+            Token::Link(url) => link(url, url),
+            Token::Hashtag(tag) => html_escape::encode_text(&format!("#{tag}")).to_string(),
+            Token::Lightning(request) => html_escape::encode_text(request).to_string(),
+            Token::Nostr(_) => entity_link(&token, &event.tags, names)
+                .map(|(entity, label)| link(&format!("nostr:{entity}"), &label))
+                .unwrap_or_default(),
+            Token::TagRef(index) => entity_link(&token, &event.tags, names)
+                .map(|(entity, label)| link(&format!("nostr:{entity}"), &label))
+                .unwrap_or_else(|| format!("#[{index}]")),
+        })
+        .collect()
+}
+
+/// `text` with characters that Markdown would format escaped.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '~' | '|'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// `line` of escaped text, which would otherwise start a heading, list,
+/// blockquote or rule.
+fn escape_line_start(line: &str) -> String {
+    let text = line.trim_start();
+    let indent = &line[..line.len() - text.len()];
+    let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+    let at = if text.starts_with(['#', '-', '+', '=']) {
+        Some(0)
+    } else if digits > 0 && text[digits..].starts_with(['.', ')']) {
+        Some(digits)
+    } else {
+        None
+    };
+
+    match at {
+        Some(at) => format!("{indent}{}\\{}", &text[..at], &text[at..]),
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nostr_sdk::prelude::{Kind, Marker, Timestamp};
+
+    use super::*;
+    use crate::testing::Fixtures;
+
+    fn exported(author: &str, event: Event) -> ExportedNote {
+        ExportedNote {
+            event: Arc::new(event),
+            author: author.to_string(),
+            avatar: None,
+        }
+    }
+
+    fn at(seconds: u64) -> Timestamp {
+        Timestamp::from(1_680_000_000 + seconds)
+    }
+
+    fn minute(seconds: u64) -> String {
+        format_minute(at(seconds), TimeFormat::default())
+    }
+
+    fn reply(parent: &Event, root: &Event) -> Vec<Tag> {
+        vec![
+            Tag::Event(root.id, None, Some(Marker::Root)),
+            Tag::Event(parent.id, None, Some(Marker::Reply)),
+        ]
+    }
+
+    #[test]
+    fn markdown_is_escaped() {
+        let alice = Fixtures::new();
+        let note = alice.sign(
+            Kind::TextNote,
+            "*not bold* _nor_ `code` [x](y) <b> a\\b ~x~ |\n# not heading\n- not list\n  12. not numbered\n> not quote\n+ =",
+            &[],
+            at(0),
+        );
+        let notes = [exported("a_lice*", note)];
+
+        assert_eq!(
+            markdown("Notes of `me`", &notes, &HashMap::new(), TimeFormat::default()),
+            format!(
+                "# Notes of \\`me\\`\n\n**a\\_lice\\*** · {}\n\n\\*not bold\\* \\_nor\\_ \\`code\\` \\[x\\](y) \\<b\\> a\\\\b \\~x\\~ \\|\n\\# not heading\n\\- not list\n  12\\. not numbered\n\\> not quote\n\\+ =\n",
+                minute(0)
+            )
+        );
+    }
+
+    #[test]
+    fn hashtags_and_links_stay_as_written() {
+        let alice = Fixtures::new();
+        let note = alice.sign(
+            Kind::TextNote,
+            "#nostr_dev at https://example.com/a_b*c\n#rust",
+            &[],
+            at(0),
+        );
+
+        assert_eq!(
+            markdown_content(&note, &HashMap::new()),
+            "#nostr\\_dev at <https://example.com/a_b*c>\n#rust"
+        );
+        assert_eq!(
+            escape_line_start("#rust"),
+            "\\#rust",
+            "is not taken for heading either"
+        );
+    }
+
+    #[test]
+    fn mentions_are_rendered_by_name() {
+        let (alice, bob, carol) = (Fixtures::new(), Fixtures::new(), Fixtures::new());
+        let quoted = carol.sign(Kind::TextNote, "Quoted", &[], at(0));
+        let (npub_bob, npub_carol) = (
+            bob.public_key().to_bech32().unwrap(),
+            carol.public_key().to_bech32().unwrap(),
+        );
+        let note_id = quoted.id.to_bech32().unwrap();
+        let note = alice.sign(
+            Kind::TextNote,
+            &format!("Hi nostr:{npub_bob}, #[0], {npub_carol} and #[1], see #[2] or #[3]"),
+            &[
+                Tag::PubKey(bob.public_key(), None),
+                Tag::PubKey(carol.public_key(), None),
+                Tag::Event(quoted.id, None, None),
+                Tag::Hashtag("not a mention".to_string()),
+            ],
+            at(60),
+        );
+        let names = HashMap::from([(bob.public_key(), "Bob [the] *builder*".to_string())]);
+
+        assert_eq!(
+            markdown_content(&note, &names),
+            format!(
+                "Hi [@Bob \\[the\\] \\*builder\\*](nostr:{npub_bob}), [@Bob \\[the\\] \\*builder\\*](nostr:{npub_bob}), \
+                 [{npub_carol}](nostr:{npub_carol}) and [{npub_carol}](nostr:{npub_carol}), \
+                 see [{note_id}](nostr:{note_id}) or #\\[3\\]"
+            )
+        );
+        assert_eq!(
+            html_content(&note, &names),
+            format!(
+                "Hi <a href=\"nostr:{npub_bob}\">@Bob [the] *builder*</a>, <a href=\"nostr:{npub_bob}\">@Bob [the] *builder*</a>, \
+                 <a href=\"nostr:{npub_carol}\">{npub_carol}</a> and <a href=\"nostr:{npub_carol}\">{npub_carol}</a>, \
+                 see <a href=\"nostr:{note_id}\">{note_id}</a> or #[3]"
+            )
+        );
+    }
+
+    #[test]
+    fn replies_are_quoted_below_notes_they_reply_to() {
+        let (alice, bob) = (Fixtures::new(), Fixtures::new());
+        let root = alice.sign(Kind::TextNote, "Root", &[], at(100));
+        let first = bob.sign(
+            Kind::TextNote,
+            "First",
+            &[Tag::Event(root.id, None, Some(Marker::Root))],
+            at(200),
+        );
+        let second = bob.sign(
+            Kind::TextNote,
+            "Second",
+            &[Tag::Event(root.id, None, Some(Marker::Root))],
+            at(300),
+        );
+        let nested = alice.sign(
+            Kind::TextNote,
+            "Nested\nlines",
+            &reply(&first, &root),
+            at(250),
+        );
+        let missing = alice.sign(Kind::TextNote, "Missing", &[], at(0));
+        let orphan = bob.sign(Kind::TextNote, "Orphan", &reply(&missing, &missing), at(50));
+
+        // Given in no particular order.
+        let notes = [
+            exported("Bob", second),
+            exported("Alice", nested),
+            exported("Bob", orphan),
+            exported("Alice", root),
+            exported("Bob", first),
+        ];
+
+        assert_eq!(
+            markdown("Thread", &notes, &HashMap::new(), TimeFormat::default()),
+            format!(
+                "# Thread\n\
+                 \n**Bob** · {}\n\nOrphan\n\
+                 \n**Alice** · {}\n\nRoot\n\
+                 >\n> **Bob** · {}\n>\n> First\n\
+                 > >\n> > **Alice** · {}\n> >\n> > Nested\n> > lines\n\
+                 >\n> **Bob** · {}\n>\n> Second\n",
+                minute(50),
+                minute(100),
+                minute(200),
+                minute(250),
+                minute(300)
+            )
+        );
+    }
+
+    #[test]
+    fn html_nests_replies_in_notes_they_reply_to() {
+        let alice = Fixtures::new();
+        let root = alice.sign(Kind::TextNote, "<Root>", &[], at(0));
+        let first = alice.sign(Kind::TextNote, "First", &reply(&root, &root), at(1));
+        let nested = alice.sign(Kind::TextNote, "Nested", &reply(&first, &root), at(2));
+        let second = alice.sign(Kind::TextNote, "Second", &reply(&root, &root), at(3));
+        let notes = [
+            exported("Alice", nested),
+            exported("Alice", root),
+            exported("Alice", second),
+            exported("Alice", first),
+        ];
+
+        let page = html("<Thread>", &notes, &HashMap::new(), TimeFormat::default());
+        let structure: Vec<&str> = page
+            .split(['<', '>'])
+            .filter(|s| {
+                matches!(*s, "article" | "/article")
+                    || s.ends_with("First")
+                    || s.ends_with("Nested")
+                    || s.ends_with("Second")
+                    || s.contains("Root")
+            })
+            .collect();
+
+        assert!(page.contains("<title>&lt;Thread&gt;</title>"));
+        assert_eq!(
+            structure,
+            [
+                "article",
+                "&lt;Root&gt;",
+                "article",
+                "First",
+                "article",
+                "Nested",
+                "/article",
+                "/article",
+                "article",
+                "Second",
+                "/article",
+                "/article"
+            ]
+        );
+    }
+
+    #[test]
+    fn file_stems_and_data_uris() {
+        assert_eq!(
+            file_stem("Thread by Alice (draft)"),
+            "thread-by-alice-draft"
+        );
+        assert_eq!(file_stem("…"), "notes");
+        assert_eq!(
+            data_uri(b"\x89PNG\r\n"),
+            Some("data:image/png;base64,iVBORw0K".to_string())
+        );
+        assert_eq!(data_uri(b"<svg/>"), None);
+    }
+}
@@ -0,0 +1,114 @@
+//! Detection of authors flooding relays with text notes. Notes of every
+//! author are counted over a sliding window of their creation times,
+//! an author with too many of them is flooding. Only a few recent notes
+//! of recently active authors are remembered, so memory stays bounded.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use nostr_sdk::prelude::{EventId, XOnlyPublicKey};
+
+/// At most this many authors are tracked at once.
+const MAX_AUTHORS: usize = 10_000;
+
+/// Authors from whom nothing has come for this long are forgotten first.
+const IDLE: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug)]
+pub struct Flood(Mutex<FloodInner>);
+
+#[derive(Debug)]
+struct FloodInner {
+    /// More notes than this within `window` seconds are flooding,
+    /// zero means that nobody is.
+    max_notes: usize,
+    window: u64,
+    authors: HashMap<XOnlyPublicKey, Recent>,
+}
+
+/// Recent notes of an author.
+#[derive(Debug)]
+struct Recent {
+    /// Creation times and identifiers of notes, in order of arrival.
+    notes: VecDeque<(u64, EventId)>,
+    /// When a note of the author last arrived.
+    active: Instant,
+}
+
+impl Default for Flood {
+    fn default() -> Self {
+        Flood(Mutex::new(FloodInner {
+            max_notes: 0,
+            window: 60,
+            authors: HashMap::new(),
+        }))
+    }
+}
+
+impl Flood {
+    /// Authors are flooding with more than `max_notes` within `window` seconds.
+    pub fn set_limits(&self, max_notes: u32, window: u64) {
+        let mut inner = self.0.lock().unwrap();
+        inner.max_notes = max_notes as usize;
+        inner.window = window.max(1);
+    }
+
+    /// Counts note `id` of `author` created at `created_at`, unless it has
+    /// been counted already. Returns whether the author is flooding
+    /// around the time of the note.
+    pub fn note(&self, author: XOnlyPublicKey, id: EventId, created_at: u64) -> bool {
+        let mut inner = self.0.lock().unwrap();
+        if inner.max_notes == 0 {
+            return false;
+        }
+
+        if !inner.authors.contains_key(&author) && inner.authors.len() >= MAX_AUTHORS {
+            inner.evict();
+        }
+
+        let (max_notes, window) = (inner.max_notes, inner.window);
+        let recent = inner.authors.entry(author).or_insert_with(|| Recent {
+            notes: VecDeque::new(),
+            active: Instant::now(),
+        });
+        recent.active = Instant::now();
+
+        if !recent.notes.iter().any(|(_, i)| *i == id) {
+            recent.notes.push_back((created_at, id));
+            // Enough to tell flooding apart, older notes do not matter.
+            if recent.notes.len() > 2 * (max_notes + 1) {
+                let oldest = recent
+                    .notes
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, (t, _))| *t)
+                    .map(|(i, _)| i)
+                    .unwrap_or_default();
+                recent.notes.remove(oldest);
+            }
+        }
+
+        let within = recent
+            .notes
+            .iter()
+            .filter(|(t, _)| *t <= created_at && created_at - *t < window)
+            .count();
+        within > max_notes
+    }
+}
+
+impl FloodInner {
+    /// Forgets idle authors. If all of them are active, the least
+    /// recently active tenth of them is forgotten.
+    fn evict(&mut self) {
+        self.authors.retain(|_, r| r.active.elapsed() < IDLE);
+
+        if self.authors.len() >= MAX_AUTHORS {
+            let mut active: Vec<Instant> = self.authors.values().map(|r| r.active).collect();
+            active.sort();
+            let until = active[MAX_AUTHORS / 10];
+            self.authors.retain(|_, r| r.active > until);
+        }
+    }
+}
@@ -29,13 +29,31 @@ impl Follow {
 
     // TODO: Batch
     pub fn subscriptions(&self) -> SubscriptionFilter {
+        Follow::subscriptions_of(self.following.iter().copied().collect())
+    }
+
+    /// Subscription for notes of some of followed `authors`.
+    pub fn subscriptions_of(authors: Vec<XOnlyPublicKey>) -> SubscriptionFilter {
         SubscriptionFilter::new()
-            .kinds(vec![Kind::TextNote, Kind::Repost])
-            .authors(self.following.iter().copied().collect())
+            .kinds(vec![
+                Kind::TextNote,
+                Kind::Repost,
+                Kind::Custom(crate::nostr::ARTICLE_KIND),
+            ])
+            .authors(authors)
             .limit(30)
     }
 
+    pub fn following(&self) -> impl Iterator<Item = &XOnlyPublicKey> {
+        self.following.iter()
+    }
+
     pub fn follows(&self, pubkey: &XOnlyPublicKey) -> bool {
         self.following.contains(pubkey)
     }
+
+    /// Starts following `pubkey`. Returns `false` if it has been followed already.
+    pub fn add(&mut self, pubkey: XOnlyPublicKey) -> bool {
+        self.following.insert(pubkey)
+    }
 }
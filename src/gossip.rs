@@ -0,0 +1,225 @@
+//! Outbox model: authors are looked for on relays they declare as their
+//! write relays in NIP-65 relay lists. Relays listed for them with their
+//! NIP-05 are remembered too, as hints where else to look for them.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use nostr_sdk::prelude::*;
+use nostr_sdk::relay::RelayStatus;
+use nostr_sdk::{Client, RelayOptions};
+use sqlx::{query, SqlitePool};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::nostr::EventExt;
+
+/// At most this many relays are connected in addition to our own ones.
+const MAX_EXTRA_RELAYS: usize = 8;
+
+/// Events of a single author are requested from at most this many relays.
+const MAX_RELAYS_PER_AUTHOR: usize = 3;
+
+#[derive(Clone)]
+pub struct Gossip(Arc<GossipInner>);
+
+struct GossipInner {
+    pool: SqlitePool,
+    client: Client,
+    /// Relays connected only because other users write to them.
+    extra: Mutex<HashSet<Url>>,
+}
+
+impl Gossip {
+    pub fn new(pool: SqlitePool, client: Client) -> Gossip {
+        Gossip(Arc::new(GossipInner {
+            pool,
+            client,
+            extra: Default::default(),
+        }))
+    }
+
+    /// Stores relay list `event` of its author, unless a newer one is known.
+    pub async fn store(&self, event: &Event) {
+        let author = event.pubkey.serialize().to_vec();
+        let created_at = event.created_at.as_i64();
+        let json = match event.as_json() {
+            Ok(j) => j,
+            Err(_) => return,
+        };
+
+        let result = query!(
+            r#"
+INSERT INTO relay_lists (author, created_at, event) VALUES (?, ?, ?)
+ON CONFLICT (author) DO UPDATE SET created_at = EXCLUDED.created_at, event = EXCLUDED.event
+WHERE EXCLUDED.created_at > relay_lists.created_at
+"#,
+            author,
+            created_at,
+            json
+        )
+        .execute(&self.0.pool)
+        .await;
+
+        if let Err(e) = result {
+            warn!("Could not store relay list of {}: {}", event.pubkey, e);
+        }
+    }
+
+    /// Remembers `relays` listed for `pubkey` with their verified NIP-05,
+    /// replacing those listed before. No relays forget them.
+    pub async fn store_nip05_relays(&self, pubkey: XOnlyPublicKey, relays: &[Url]) {
+        let author = pubkey.serialize().to_vec();
+        let result = if relays.is_empty() {
+            query!("DELETE FROM nip05_relays WHERE author = ?", author)
+                .execute(&self.0.pool)
+                .await
+        } else {
+            let json = serde_json::to_string(relays).unwrap_or_default();
+            let now = Timestamp::now().as_i64();
+            query!(
+                r#"
+INSERT INTO nip05_relays (author, relays, fetched_at) VALUES (?, ?, ?)
+ON CONFLICT (author) DO UPDATE SET relays = EXCLUDED.relays, fetched_at = EXCLUDED.fetched_at
+"#,
+                author,
+                json,
+                now
+            )
+            .execute(&self.0.pool)
+            .await
+        };
+
+        if let Err(e) = result {
+            warn!("Could not store NIP-05 relays of {}: {}", pubkey, e);
+        }
+    }
+
+    /// Relays to which `pubkey` declares to write.
+    pub async fn write_relays(&self, pubkey: XOnlyPublicKey) -> Vec<Url> {
+        let author = pubkey.serialize().to_vec();
+
+        query!("SELECT event FROM relay_lists WHERE author = ?", author)
+            .fetch_optional(&self.0.pool)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|r| Event::from_json(r.event).ok())
+            .map(|e| {
+                e.relay_list()
+                    .into_iter()
+                    .filter(|r| r.write)
+                    .map(|r| r.url)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Relays listed for `pubkey` with their verified NIP-05.
+    pub async fn nip05_relays(&self, pubkey: XOnlyPublicKey) -> Vec<Url> {
+        let author = pubkey.serialize().to_vec();
+
+        query!("SELECT relays FROM nip05_relays WHERE author = ?", author)
+            .fetch_optional(&self.0.pool)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|r| serde_json::from_str(&r.relays).ok())
+            .unwrap_or_default()
+    }
+
+    /// Whether `relay` has been connected only to reach other users.
+    pub async fn is_extra(&self, relay: &Url) -> bool {
+        self.0.extra.lock().await.contains(relay)
+    }
+
+    /// Relays where events of `pubkey` should be requested. Relays we
+    /// already use are preferred; others are connected only while there
+    /// are not [`MAX_EXTRA_RELAYS`] of them already.
+    pub async fn relays_of(&self, pubkey: XOnlyPublicKey) -> Vec<Url> {
+        let declared = self.write_relays(pubkey).await;
+        let ours = self.0.client.relays().await;
+
+        let mut usable = Vec::new();
+        for url in &declared {
+            if let Some(relay) = ours.get(url) {
+                if relay.opts().read() && relay.status().await == RelayStatus::Connected {
+                    usable.push(url.clone());
+                }
+            }
+        }
+
+        // Relays we know but which are not connected right now come next.
+        for url in &declared {
+            if ours.get(url).map(|r| r.opts().read()).unwrap_or(false) && !usable.contains(url) {
+                usable.push(url.clone());
+            }
+        }
+
+        for url in declared {
+            if usable.len() >= MAX_RELAYS_PER_AUTHOR {
+                break;
+            }
+            if !ours.contains_key(&url) && self.connect_extra(&url).await {
+                usable.push(url);
+            }
+        }
+
+        usable.truncate(MAX_RELAYS_PER_AUTHOR);
+        usable
+    }
+
+    /// Groups `authors` by relays where their events should be requested.
+    /// Authors without a usable relay list are returned separately.
+    pub async fn group_by_relays(
+        &self,
+        authors: impl IntoIterator<Item = XOnlyPublicKey>,
+    ) -> (HashMap<Url, Vec<XOnlyPublicKey>>, Vec<XOnlyPublicKey>) {
+        let mut grouped: HashMap<Url, Vec<XOnlyPublicKey>> = HashMap::new();
+        let mut unknown = Vec::new();
+
+        for author in authors {
+            let relays = self.relays_of(author).await;
+            if relays.is_empty() {
+                unknown.push(author);
+            }
+            for relay in relays {
+                grouped.entry(relay).or_default().push(author);
+            }
+        }
+
+        (grouped, unknown)
+    }
+
+    /// Adds and connects `relay` for reading, if the limit of extra
+    /// relays allows it.
+    async fn connect_extra(&self, relay: &Url) -> bool {
+        let mut extra = self.0.extra.lock().await;
+
+        if extra.contains(relay) {
+            return true;
+        }
+        if extra.len() >= MAX_EXTRA_RELAYS {
+            return false;
+        }
+
+        let url = relay.to_string();
+        let added = self
+            .0
+            .client
+            .add_relay_with_opts(url.as_str(), None, RelayOptions::new(true, false))
+            .await;
+        if let Err(e) = added {
+            warn!("Could not add relay {}: {}", relay, e);
+            return false;
+        }
+
+        info!("Connecting extra relay {}", relay);
+        if let Err(e) = self.0.client.connect_relay(url.as_str(), false).await {
+            warn!("Could not connect relay {}: {}", relay, e);
+        }
+
+        extra.insert(relay.clone());
+        true
+    }
+}
@@ -0,0 +1,414 @@
+//! Applications which can display events that Gnostique cannot (NIP-89).
+//! Applications announce kinds they handle and URLs where they display
+//! events, users recommend them to each other. Handlers recommended by us
+//! or by people we follow are preferred.
+
+use std::str::FromStr;
+
+use nostr_sdk::prelude::*;
+use sqlx::{query, SqlitePool};
+
+use crate::nostr::{EventExt, HANDLER_KIND, HANDLER_RECOMMENDATION_KIND};
+
+/// This is substituted by the entity in URL templates of handlers.
+const PLACEHOLDER: &str = "<bech32>";
+
+/// Application which can display events of some kinds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Handler {
+    /// Public key of the application.
+    pub author: XOnlyPublicKey,
+    pub identifier: String,
+    /// Name of the application, from metadata in content.
+    pub name: Option<String>,
+    pub kinds: Vec<u64>,
+    /// Templates of web URLs displaying events, with type of bech32
+    /// entity they want, if they tell.
+    pub web: Vec<(String, Option<String>)>,
+}
+
+impl Handler {
+    /// Reads handler information `event`, `None` if it is not one.
+    pub fn from_event(event: &Event) -> Option<Handler> {
+        if event.kind.as_u64() != HANDLER_KIND {
+            return None;
+        }
+
+        let mut kinds = Vec::new();
+        let mut web = Vec::new();
+        for tag in &event.tags {
+            let tag = tag.as_vec();
+            match tag.as_slice() {
+                [k, kind, ..] if k == "k" => {
+                    if let Ok(kind) = kind.parse() {
+                        kinds.push(kind);
+                    }
+                }
+                [w, template, rest @ ..] if w == "web" && template.contains(PLACEHOLDER) => {
+                    web.push((template.clone(), rest.first().cloned()));
+                }
+                _ => {}
+            }
+        }
+
+        let metadata = Metadata::from_json(&event.content).ok();
+        let name = metadata
+            .and_then(|m| m.display_name.or(m.name))
+            .filter(|n| !n.is_empty());
+
+        Some(Handler {
+            author: event.pubkey,
+            identifier: event.identifier()?,
+            name,
+            kinds,
+            web,
+        })
+    }
+
+    pub fn shown_name(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None if !self.identifier.is_empty() => self.identifier.clone(),
+            None => "application".to_string(),
+        }
+    }
+
+    /// URL at which the application displays `event`, seen at `relays`,
+    /// `None` if the handler has no template for such event.
+    pub fn url_for(&self, event: &Event, relays: &[Url]) -> Option<Url> {
+        self.web.iter().find_map(|(template, entity)| {
+            let entity = match entity.as_deref() {
+                Some("note") => event.id.to_bech32().ok()?,
+                Some("nevent") => event.nevent(relays),
+                Some("naddr") => event.naddr(relays)?,
+                // Addresses lead to the latest version of replaceable events.
+                None => event.naddr(relays).unwrap_or_else(|| event.nevent(relays)),
+                Some(_) => return None,
+            };
+            let url = Url::parse(&template.replace(PLACEHOLDER, &entity)).ok()?;
+            matches!(url.scheme(), "https" | "http").then_some(url)
+        })
+    }
+}
+
+/// Stored handlers and recommendations of them.
+#[derive(Clone, Debug)]
+pub struct Handlers {
+    pool: SqlitePool,
+}
+
+impl Handlers {
+    pub fn new(pool: SqlitePool) -> Handlers {
+        Handlers { pool }
+    }
+
+    /// Stores handler information `event`, unless a newer one is known.
+    /// Returns the handler if it is new.
+    pub async fn store(&self, event: &Event) -> Result<Option<Handler>, String> {
+        let handler = match Handler::from_event(event) {
+            Some(h) => h,
+            None => return Ok(None),
+        };
+        let author = handler.author.serialize().to_vec();
+        let json = event.as_json().map_err(|e| e.to_string())?;
+        let created_at = event.created_at.as_i64();
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        let stored = query!(
+            r#"
+INSERT INTO handlers (author, identifier, event, created_at) VALUES (?, ?, ?, ?)
+ON CONFLICT (author, identifier) DO UPDATE SET
+    event = EXCLUDED.event,
+    created_at = EXCLUDED.created_at
+WHERE EXCLUDED.created_at > handlers.created_at
+"#,
+            author,
+            handler.identifier,
+            json,
+            created_at
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(|e| format!("Could not store handler {}: {}", handler.identifier, e))?;
+
+        if stored.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        query!(
+            "DELETE FROM handler_kinds WHERE author = ? AND identifier = ?",
+            author,
+            handler.identifier
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(|e| format!("Could not store kinds of {}: {}", handler.identifier, e))?;
+
+        for kind in &handler.kinds {
+            let kind = *kind as i64;
+            query!(
+                r#"
+INSERT INTO handler_kinds (author, identifier, kind) VALUES (?, ?, ?)
+ON CONFLICT DO NOTHING
+"#,
+                author,
+                handler.identifier,
+                kind
+            )
+            .execute(&mut tx)
+            .await
+            .map_err(|e| format!("Could not store kinds of {}: {}", handler.identifier, e))?;
+        }
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        Ok(Some(handler))
+    }
+
+    /// Stores recommendation `event`, which replaces older recommendations
+    /// of the same kind by its author. Returns the recommended kind if
+    /// the recommendation is new.
+    pub async fn store_recommendation(&self, event: &Event) -> Result<Option<u64>, String> {
+        if event.kind.as_u64() != HANDLER_RECOMMENDATION_KIND {
+            return Ok(None);
+        }
+        let kind: u64 = match event.identifier().and_then(|d| d.parse().ok()) {
+            Some(kind) => kind,
+            None => return Ok(None),
+        };
+
+        // Recommended handlers are addressed as `31990:<pubkey>:<identifier>`.
+        let recommended: Vec<(Vec<u8>, String)> = event
+            .tags
+            .iter()
+            .filter_map(|t| {
+                let tag = t.as_vec();
+                let address = match tag.as_slice() {
+                    [a, address, ..] if a == "a" => address.clone(),
+                    _ => return None,
+                };
+                let mut parts = address.splitn(3, ':');
+                if parts.next()?.parse::<u64>().ok()? != HANDLER_KIND {
+                    return None;
+                }
+                let author = XOnlyPublicKey::from_str(parts.next()?).ok()?;
+                Some((author.serialize().to_vec(), parts.next()?.to_string()))
+            })
+            .collect();
+
+        let recommender = event.pubkey.serialize().to_vec();
+        let kind_i = kind as i64;
+        let created_at = event.created_at.as_i64();
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        let newest = query!(
+            r#"
+SELECT MAX(created_at) AS "created_at: i64" FROM handler_recommendations
+WHERE recommender = ? AND kind = ?
+"#,
+            recommender,
+            kind_i
+        )
+        .fetch_one(&mut tx)
+        .await
+        .map_err(|e| format!("Could not find recommendations of {}: {}", event.pubkey, e))?
+        .created_at;
+
+        if newest.map(|t| t >= created_at).unwrap_or_default() {
+            return Ok(None);
+        }
+
+        query!(
+            "DELETE FROM handler_recommendations WHERE recommender = ? AND kind = ?",
+            recommender,
+            kind_i
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(|e| format!("Could not store recommendation of {}: {}", event.pubkey, e))?;
+
+        for (author, identifier) in recommended {
+            query!(
+                r#"
+INSERT INTO handler_recommendations (recommender, kind, author, identifier, created_at)
+VALUES (?, ?, ?, ?, ?)
+ON CONFLICT DO NOTHING
+"#,
+                recommender,
+                kind_i,
+                author,
+                identifier,
+                created_at
+            )
+            .execute(&mut tx)
+            .await
+            .map_err(|e| format!("Could not store recommendation of {}: {}", event.pubkey, e))?;
+        }
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        Ok(Some(kind))
+    }
+
+    /// The best known handler of events of `kind`: recommended by `me`,
+    /// then by people we follow, then the most recently announced one.
+    /// Only handlers which display events on the web are of use.
+    pub async fn for_kind(&self, kind: u64, me: XOnlyPublicKey) -> Option<Handler> {
+        let kind = kind as i64;
+        let me = me.serialize().to_vec();
+
+        query!(
+            r#"
+SELECT h.event AS "event!" FROM handlers h
+JOIN handler_kinds k ON k.author = h.author AND k.identifier = h.identifier
+WHERE k.kind = ?1
+ORDER BY
+  EXISTS (SELECT 1 FROM handler_recommendations r
+          WHERE r.kind = ?1 AND r.author = h.author AND r.identifier = h.identifier
+            AND r.recommender = ?2) DESC,
+  (SELECT COUNT(*) FROM handler_recommendations r
+   WHERE r.kind = ?1 AND r.author = h.author AND r.identifier = h.identifier
+     AND r.recommender IN (SELECT pubkey FROM contacts)) DESC,
+  h.created_at DESC
+LIMIT 20
+"#,
+            kind,
+            me
+        )
+        .fetch_all(&self.pool)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|r| Event::from_json(r.event).ok())
+        .filter_map(|e| Handler::from_event(&e))
+        .find(|h| !h.web.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Fixtures;
+
+    fn tag(name: &str, values: &[&str]) -> Tag {
+        Tag::Generic(
+            TagKind::Custom(name.to_string()),
+            values.iter().map(|v| v.to_string()).collect(),
+        )
+    }
+
+    /// Handler of kind 30023 with `web` templates.
+    fn handler(web: &[&[&str]]) -> Handler {
+        let mut tags = vec![
+            Tag::Generic(TagKind::D, vec!["reader".to_string()]),
+            tag("k", &["30023"]),
+            tag("k", &["not a kind"]),
+        ];
+        tags.extend(web.iter().map(|w| tag("web", w)));
+
+        let event = Fixtures::new().sign(
+            Kind::Custom(HANDLER_KIND),
+            r#"{"name": "Reader"}"#,
+            &tags,
+            Timestamp::now(),
+        );
+        Handler::from_event(&event).unwrap()
+    }
+
+    fn relays() -> Vec<Url> {
+        vec![Url::parse("wss://relay.example.com").unwrap()]
+    }
+
+    #[test]
+    fn handler_is_read_from_event() {
+        let handler = handler(&[
+            &["https://reader.example.com/e/<bech32>", "nevent"],
+            &["https://reader.example.com/static"],
+        ]);
+
+        assert_eq!(handler.identifier, "reader");
+        assert_eq!(handler.shown_name(), "Reader");
+        assert_eq!(handler.kinds, vec![30023]);
+        // Templates without placeholder cannot display anything.
+        assert_eq!(
+            handler.web,
+            vec![(
+                "https://reader.example.com/e/<bech32>".to_string(),
+                Some("nevent".to_string())
+            )]
+        );
+        assert!(Handler::from_event(&Fixtures::new().text_note("Hi")).is_none());
+    }
+
+    #[test]
+    fn placeholder_is_substituted_by_wanted_entity() {
+        let article = Fixtures::new().sign(
+            Kind::Custom(30023),
+            "Article",
+            &[Tag::Generic(TagKind::D, vec!["article".to_string()])],
+            Timestamp::now(),
+        );
+        let note = article.id.to_bech32().unwrap();
+        let nevent = article.nevent(&relays());
+        let naddr = article.naddr(&relays()).unwrap();
+
+        let url = |entity: Option<&str>| {
+            let template = ["https://reader.example.com/<bech32>?again=<bech32>"];
+            let web: Vec<&str> = template.into_iter().chain(entity).collect();
+            handler(&[&web]).url_for(&article, &relays()).unwrap()
+        };
+
+        let expected = |e: &str| format!("https://reader.example.com/{e}?again={e}");
+        assert_eq!(url(Some("note")).as_str(), expected(&note));
+        assert_eq!(url(Some("nevent")).as_str(), expected(&nevent));
+        assert_eq!(url(Some("naddr")).as_str(), expected(&naddr));
+        // Replaceable events are best addressed.
+        assert_eq!(url(None).as_str(), expected(&naddr));
+    }
+
+    #[test]
+    fn events_without_address_fall_back_to_nevent() {
+        let note = Fixtures::new().text_note("Hello");
+        let handler = handler(&[
+            &["https://a.example.com/<bech32>", "naddr"],
+            &["https://b.example.com/<bech32>"],
+        ]);
+
+        assert_eq!(
+            handler.url_for(&note, &relays()).unwrap().as_str(),
+            format!("https://b.example.com/{}", note.nevent(&relays()))
+        );
+    }
+
+    #[test]
+    fn only_web_urls_are_opened() {
+        let note = Fixtures::new().text_note("Hello");
+        for template in [
+            "javascript:alert('<bech32>')",
+            "file:///tmp/<bech32>",
+            "<bech32>",
+        ] {
+            let handler = handler(&[&[template, "note"]]);
+            assert_eq!(handler.url_for(&note, &relays()), None, "{template}");
+        }
+    }
+
+    #[test]
+    fn template_is_escaped_as_url() {
+        let note = Fixtures::new().text_note("Hello");
+        let spaced = handler(&[&["https://reader.example.com/my notes/<bech32>#<x>", "note"]]);
+        let entity = note.id.to_bech32().unwrap();
+
+        assert_eq!(
+            spaced.url_for(&note, &relays()).unwrap().as_str(),
+            format!("https://reader.example.com/my%20notes/{entity}#%3Cx%3E")
+        );
+        assert!(
+            handler(&[&["https://reader.example.com/<bech32>", "nprofile"]])
+                .url_for(&note, &relays())
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Identicons, avatars generated from pubkeys of authors who have none,
+//! so that authors can be told apart at a glance.
+
+use nostr_sdk::prelude::XOnlyPublicKey;
+
+/// Cells of the pattern in each direction. The left half is mirrored
+/// into the right one.
+const GRID: u32 = 5;
+
+/// Background of identicons, as RGBA.
+const BACKGROUND: [u8; 4] = [0xea, 0xea, 0xea, 0xff];
+
+/// PNG image of `size` × `size` pixels with a symmetric pattern in a color,
+/// both determined by `pubkey`.
+pub fn identicon(pubkey: &XOnlyPublicKey, size: u32) -> Vec<u8> {
+    let bytes = pubkey.serialize();
+    let foreground = color(u16::from_be_bytes([bytes[0], bytes[1]]));
+
+    // Which cells of the left half and the middle column are filled.
+    let half = GRID.div_ceil(2);
+    let filled = |column: u32, row: u32| {
+        let column = column.min(GRID - 1 - column);
+        let bit = (row * half + column) as usize;
+        bytes[2 + bit / 8] & (1 << (bit % 8)) != 0
+    };
+
+    let size = size.max(GRID + 1);
+    let cell = size / (GRID + 1);
+    let margin = (size - cell * GRID) / 2;
+
+    let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let inside = (margin..margin + cell * GRID).contains(&x)
+                && (margin..margin + cell * GRID).contains(&y);
+            let pixel = if inside && filled((x - margin) / cell, (y - margin) / cell) {
+                foreground
+            } else {
+                BACKGROUND
+            };
+            pixels.extend_from_slice(&pixel);
+        }
+    }
+
+    encode_png(size, &pixels)
+}
+
+/// Saturated color of medium lightness with the given hue, as RGBA.
+fn color(hue: u16) -> [u8; 4] {
+    let h = hue as f32 / u16::MAX as f32 * 6.0;
+    let (s, l) = (0.55, 0.5);
+    let c = (1.0 - (2.0 * l - 1.0_f32).abs()) * s;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let m = l - c / 2.0;
+
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let channel = |v: f32| ((v + m) * 255.0).round() as u8;
+
+    [channel(r), channel(g), channel(b), 0xff]
+}
+
+fn encode_png(size: u32, pixels: &[u8]) -> Vec<u8> {
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, size, size);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    // Writing into memory does not fail.
+    encoder
+        .write_header()
+        .and_then(|mut w| w.write_image_data(pixels))
+        .expect("Could not encode identicon");
+
+    png
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Fixtures;
+
+    /// Width, height and RGBA pixels of `png`.
+    fn decode(png: &[u8]) -> (u32, u32, Vec<u8>) {
+        let mut reader = png::Decoder::new(png).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+        pixels.truncate(info.buffer_size());
+        (info.width, info.height, pixels)
+    }
+
+    #[test]
+    fn the_same_pubkey_gives_the_same_image() {
+        let pubkey = Fixtures::new().public_key();
+        assert_eq!(identicon(&pubkey, 64), identicon(&pubkey, 64));
+    }
+
+    #[test]
+    fn different_pubkeys_give_different_images() {
+        let images: Vec<Vec<u8>> = (0..10)
+            .map(|_| identicon(&Fixtures::new().public_key(), 64))
+            .collect();
+        for (i, image) in images.iter().enumerate() {
+            assert!(!images[i + 1..].contains(image));
+        }
+    }
+
+    #[test]
+    fn images_have_the_given_size() {
+        let pubkey = Fixtures::new().public_key();
+        for size in [16, 48, 64, 65, 128] {
+            let (width, height, pixels) = decode(&identicon(&pubkey, size));
+            assert_eq!((width, height), (size, size));
+            assert_eq!(pixels.len(), (size * size * 4) as usize);
+        }
+    }
+
+    #[test]
+    fn pattern_is_mirrored() {
+        for _ in 0..10 {
+            let pubkey = Fixtures::new().public_key();
+            for size in [64, 96] {
+                let (_, _, pixels) = decode(&identicon(&pubkey, size));
+                let pixel = |x: u32, y: u32| {
+                    let i = ((y * size + x) * 4) as usize;
+                    &pixels[i..i + 4]
+                };
+
+                for y in 0..size {
+                    for x in 0..size / 2 {
+                        assert_eq!(pixel(x, y), pixel(size - 1 - x, y), "{x} {y}");
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! What is actually arriving from relays, for debugging: numbers of
+//! received events of every kind in this session, and the firehose of
+//! raw events for the debug lane. The firehose costs nothing unless
+//! the lane is open, and even then only a few events a second get
+//! through, so that floods do not overwhelm the window.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// At most this many raw events go to the debug lane every second.
+const MAX_RAW_PER_SECOND: u32 = 20;
+
+#[derive(Debug, Default)]
+pub struct KindStats {
+    /// Received events by their kinds.
+    counts: Mutex<HashMap<u64, u64>>,
+    /// Whether the debug lane wants raw events.
+    firehose: AtomicBool,
+    window: Mutex<Window>,
+}
+
+/// Raw events let through within the current second.
+#[derive(Debug)]
+struct Window {
+    started: Instant,
+    passed: u32,
+    /// Raw events that have not got through since the firehose opened.
+    skipped: u64,
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Window {
+            started: Instant::now(),
+            passed: 0,
+            skipped: 0,
+        }
+    }
+}
+
+impl KindStats {
+    /// Counts received event of `kind`.
+    pub fn count(&self, kind: u64) {
+        *self.counts.lock().unwrap().entry(kind).or_default() += 1;
+    }
+
+    /// Numbers of received events by their kinds, the most frequent first.
+    pub fn counts(&self) -> Vec<(u64, u64)> {
+        let mut counts: Vec<(u64, u64)> = self
+            .counts
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(k, n)| (*k, *n))
+            .collect();
+        counts.sort_by(|(k1, n1), (k2, n2)| n2.cmp(n1).then(k1.cmp(k2)));
+        counts
+    }
+
+    /// Opens or closes the firehose of raw events.
+    pub fn set_firehose(&self, open: bool) {
+        if open && !self.firehose.load(Ordering::SeqCst) {
+            *self.window.lock().unwrap() = Window::default();
+        }
+        self.firehose.store(open, Ordering::SeqCst);
+    }
+
+    pub fn is_firehose(&self) -> bool {
+        self.firehose.load(Ordering::SeqCst)
+    }
+
+    /// Whether a received raw event should go to the debug lane: the
+    /// firehose is open and not too many have gone within this second.
+    pub fn let_through(&self) -> bool {
+        if !self.is_firehose() {
+            return false;
+        }
+
+        let mut window = self.window.lock().unwrap();
+        if window.started.elapsed() >= Duration::from_secs(1) {
+            window.started = Instant::now();
+            window.passed = 0;
+        }
+        if window.passed < MAX_RAW_PER_SECOND {
+            window.passed += 1;
+            true
+        } else {
+            window.skipped += 1;
+            false
+        }
+    }
+
+    /// Number of raw events that have not got through since the firehose
+    /// opened.
+    pub fn skipped(&self) -> u64 {
+        self.window.lock().unwrap().skipped
+    }
+}
@@ -0,0 +1,126 @@
+//! Labels we give to authors, such as NSFW or spam, so that their notes
+//! are hidden behind a bar until revealed. Authors are labelled by our
+//! reports and by marking them, which publishes nothing. Labels are kept
+//! only in the database.
+
+use nostr_sdk::prelude::{Timestamp, XOnlyPublicKey};
+use sqlx::{query, SqlitePool};
+
+use crate::nostr::ReportType;
+
+/// Author posts nudity or pornography.
+pub const NSFW: &str = "nsfw";
+/// Author posts spam.
+pub const SPAM: &str = "spam";
+/// Author has been reported for something else.
+pub const REPORTED: &str = "reported";
+
+/// Label of author reported for `report_type`.
+pub fn of_report(report_type: ReportType) -> &'static str {
+    match report_type {
+        ReportType::Nudity => NSFW,
+        ReportType::Spam => SPAM,
+        ReportType::Impersonation | ReportType::Illegal | ReportType::Other => REPORTED,
+    }
+}
+
+/// How `label` is shown.
+pub fn describe(label: &str) -> &str {
+    match label {
+        NSFW => "NSFW",
+        SPAM => "spam",
+        REPORTED => "reported",
+        other => other,
+    }
+}
+
+#[derive(Clone)]
+pub struct Labels {
+    pool: SqlitePool,
+}
+
+impl Labels {
+    pub fn new(pool: SqlitePool) -> Labels {
+        Labels { pool }
+    }
+
+    pub async fn add(&self, author: XOnlyPublicKey, label: &str) -> Result<(), String> {
+        let author = author.serialize().to_vec();
+        let now = Timestamp::now().as_i64();
+        query!(
+            "INSERT INTO labels (author, label, created_at) VALUES (?, ?, ?) ON CONFLICT DO NOTHING",
+            author,
+            label,
+            now
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub async fn remove(&self, author: XOnlyPublicKey, label: &str) -> Result<(), String> {
+        let author = author.serialize().to_vec();
+        query!(
+            "DELETE FROM labels WHERE author = ? AND label = ?",
+            author,
+            label
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Labels of `author`, oldest first.
+    pub async fn of(&self, author: XOnlyPublicKey) -> Vec<String> {
+        let author = author.serialize().to_vec();
+        query!(
+            "SELECT label FROM labels WHERE author = ? ORDER BY created_at, rowid",
+            author
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map(|rows| rows.into_iter().map(|r| r.label).collect())
+        .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Fixtures, TestDb};
+
+    #[tokio::test]
+    async fn labels_are_kept_once_in_order_given() {
+        let db = TestDb::new().await.unwrap();
+        let labels = Labels::new(db.pool().clone());
+        let (author, other) = (Fixtures::new().public_key(), Fixtures::new().public_key());
+
+        assert!(labels.of(author).await.is_empty());
+        for label in [SPAM, NSFW, SPAM, "bot"] {
+            labels.add(author, label).await.unwrap();
+        }
+        labels.add(other, REPORTED).await.unwrap();
+        assert_eq!(labels.of(author).await, vec![SPAM, NSFW, "bot"]);
+
+        labels.remove(author, NSFW).await.unwrap();
+        labels.remove(author, REPORTED).await.unwrap();
+        assert_eq!(labels.of(author).await, vec![SPAM, "bot"]);
+        assert_eq!(labels.of(other).await, vec![REPORTED]);
+    }
+
+    #[test]
+    fn reports_label_authors() {
+        assert_eq!(describe(of_report(ReportType::Nudity)), "NSFW");
+        assert_eq!(of_report(ReportType::Spam), SPAM);
+        for report_type in [
+            ReportType::Impersonation,
+            ReportType::Illegal,
+            ReportType::Other,
+        ] {
+            assert_eq!(of_report(report_type), REPORTED);
+        }
+        assert_eq!(describe("bot"), "bot");
+    }
+}
@@ -0,0 +1,147 @@
+//! Languages of text notes, so that notes which user cannot read can be
+//! hidden. Languages are identified by their ISO 639-3 codes, such as
+//! `eng` or `deu`. Short notes and those where detection is unsure have
+//! no language and are never hidden.
+
+use whatlang::Lang;
+
+/// Notes with fewer characters, not counting links, mentions and
+/// hashtags, are too short to tell their language.
+const MIN_CHARS: usize = 20;
+
+/// Detection less confident than this is not trusted.
+const MIN_CONFIDENCE: f64 = 0.5;
+
+/// Language detected in `content`, `None` if it cannot be told reliably.
+pub fn detect(content: &str) -> Option<String> {
+    let (lang, confidence) = detection(content)?;
+    (confidence >= MIN_CONFIDENCE).then(|| lang.code().to_string())
+}
+
+/// What detection says about `content`, for debugging of notes in wrong
+/// languages, such as "English (eng), confidence 0.92".
+pub fn describe(content: &str) -> String {
+    match detection(content) {
+        None if prose(content).chars().count() < MIN_CHARS => "Too short to tell".to_string(),
+        None => "Unknown".to_string(),
+        Some((lang, confidence)) => {
+            let trusted = if confidence >= MIN_CONFIDENCE {
+                ""
+            } else {
+                ", too unsure to hide the note"
+            };
+            format!(
+                "{} ({}), confidence {:.2}{}",
+                lang.eng_name(),
+                lang.code(),
+                confidence,
+                trusted
+            )
+        }
+    }
+}
+
+/// Whether note in `language` is shown when only `accepted` languages
+/// are, where none means all.
+pub fn accepts(accepted: &[String], language: Option<&str>) -> bool {
+    match language {
+        Some(language) => accepted.is_empty() || accepted.iter().any(|a| a == language),
+        None => true,
+    }
+}
+
+/// Reads language codes separated by commas or spaces, as entered by
+/// user. Fails on the first unknown code.
+pub fn parse_list(text: &str) -> Result<Vec<String>, String> {
+    let mut codes: Vec<String> = Vec::new();
+    for code in text.split(|c: char| c == ',' || c.is_whitespace()) {
+        let code = code.trim().to_lowercase();
+        if code.is_empty() || codes.contains(&code) {
+            continue;
+        }
+        if Lang::from_code(&code).is_none() {
+            return Err(format!(
+                "Unknown language {code}, languages are written as three letter codes such as eng"
+            ));
+        }
+        codes.push(code);
+    }
+    Ok(codes)
+}
+
+/// Detected language of `content` and how confident the detection is.
+fn detection(content: &str) -> Option<(Lang, f64)> {
+    let prose = prose(content);
+    if prose.chars().count() < MIN_CHARS {
+        return None;
+    }
+    whatlang::detect(&prose).map(|info| (info.lang(), info.confidence()))
+}
+
+/// Words of `content` without links, mentions, hashtags and custom emoji,
+/// which are the same in all languages.
+fn prose(content: &str) -> String {
+    content
+        .split_whitespace()
+        .filter(|word| {
+            !(word.contains("://")
+                || word.starts_with("nostr:")
+                || word.starts_with('#')
+                || word.starts_with('@')
+                || word.starts_with("npub1")
+                || word.starts_with("note1")
+                || (word.len() > 2 && word.starts_with(':') && word.ends_with(':')))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENGLISH: &str = "The weather is lovely today and I am going for a long walk in the park.";
+    const GERMAN: &str = "Das Wetter ist heute herrlich und ich gehe lange im Park spazieren.";
+
+    #[test]
+    fn languages_of_prose_are_detected() {
+        assert_eq!(detect(ENGLISH).as_deref(), Some("eng"));
+        assert_eq!(detect(GERMAN).as_deref(), Some("deu"));
+        assert!(describe(ENGLISH).starts_with("English (eng), confidence "));
+    }
+
+    #[test]
+    fn short_notes_have_no_language() {
+        assert_eq!(detect("Good morning!"), None);
+        assert_eq!(describe("Good morning!"), "Too short to tell");
+        // Long, but apart from a few words all links, mentions and hashtags.
+        let content = "Look https://example.com/a/very/long/path nostr:npub1abcdefghijklmnop \
+                       #nostr #photography #weekend :custom_emoji:";
+        assert_eq!(detect(content), None);
+        assert_eq!(prose(content), "Look");
+    }
+
+    #[test]
+    fn notes_without_language_are_always_accepted() {
+        let accepted = vec!["eng".to_string(), "deu".to_string()];
+
+        assert!(accepts(&accepted, Some("deu")));
+        assert!(!accepts(&accepted, Some("fra")));
+        assert!(accepts(&accepted, None));
+        assert!(accepts(&[], Some("fra")));
+    }
+
+    #[test]
+    fn entered_language_lists_are_read() {
+        assert_eq!(
+            parse_list(" ENG, deu  eng,,fra "),
+            Ok(vec![
+                "eng".to_string(),
+                "deu".to_string(),
+                "fra".to_string()
+            ])
+        );
+        assert_eq!(parse_list(""), Ok(vec![]));
+        assert!(parse_list("eng, english").unwrap_err().contains("english"));
+    }
+}
@@ -0,0 +1,309 @@
+//! Lightning payment requests written in notes: BOLT11 invoices and
+//! LNURL strings. Only what is displayed is decoded, signatures of
+//! invoices are not verified, paying is left to a lightning wallet.
+
+use bech32::{FromBase32, Variant};
+use nostr_sdk::prelude::Timestamp;
+use reqwest::Url;
+
+/// Invoices without expiry field expire after this many seconds.
+const DEFAULT_EXPIRY: u64 = 3600;
+
+/// Signature at the end of invoice data, in 5-bit groups.
+const SIGNATURE_LEN: usize = 104;
+
+/// Creation time at the beginning of invoice data, in 5-bit groups.
+const TIMESTAMP_LEN: usize = 7;
+
+/// Invoice or LNURL which can be paid by a lightning wallet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PaymentRequest {
+    Invoice(Invoice),
+    /// LNURL and URL of the service it encodes.
+    Lnurl {
+        lnurl: String,
+        url: Url,
+    },
+}
+
+impl PaymentRequest {
+    /// Decodes BOLT11 invoice or LNURL, `None` if `text` is neither.
+    pub fn decode(text: &str) -> Option<PaymentRequest> {
+        if text.to_lowercase().starts_with("lnurl") {
+            let url = decode_lnurl(text)?;
+            Some(PaymentRequest::Lnurl {
+                lnurl: text.to_lowercase(),
+                url,
+            })
+        } else {
+            Invoice::decode(text).map(PaymentRequest::Invoice)
+        }
+    }
+
+    /// The request as it is written, in lowercase.
+    pub fn text(&self) -> &str {
+        match self {
+            PaymentRequest::Invoice(invoice) => &invoice.text,
+            PaymentRequest::Lnurl { lnurl, .. } => lnurl,
+        }
+    }
+
+    /// URI which lightning wallets open.
+    pub fn uri(&self) -> String {
+        format!("lightning:{}", self.text())
+    }
+}
+
+/// BOLT11 invoice.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Invoice {
+    /// The invoice as it is written, in lowercase.
+    pub text: String,
+    /// Requested amount in millisats, the payer chooses if there is none.
+    pub amount_msat: Option<u64>,
+    /// What is paid for, unless the invoice has only its hash.
+    pub description: Option<String>,
+    pub created_at: Timestamp,
+    /// Seconds after creation in which the invoice can be paid.
+    pub expiry: u64,
+}
+
+impl Invoice {
+    /// Decodes invoice `text`, `None` if it is not a valid invoice.
+    pub fn decode(text: &str) -> Option<Invoice> {
+        let (hrp, data, variant) = bech32::decode(text).ok()?;
+        if variant != Variant::Bech32 || data.len() < TIMESTAMP_LEN + SIGNATURE_LEN {
+            return None;
+        }
+        let amount_msat = hrp_amount(&hrp)?;
+
+        let data = &data[..data.len() - SIGNATURE_LEN];
+        let created_at = uint(&data[..TIMESTAMP_LEN])?;
+        let mut description = None;
+        let mut expiry = DEFAULT_EXPIRY;
+
+        let mut fields = &data[TIMESTAMP_LEN..];
+        while !fields.is_empty() {
+            let kind = fields[0].to_u8();
+            let len = uint(fields.get(1..3)?)? as usize;
+            let value = fields.get(3..3 + len)?;
+            fields = &fields[3 + len..];
+
+            match kind {
+                // d: description
+                13 => {
+                    let bytes = Vec::<u8>::from_base32(value).ok()?;
+                    description = Some(String::from_utf8(bytes).ok()?);
+                }
+                // x: expiry
+                6 => expiry = uint(value)?,
+                _ => {}
+            }
+        }
+
+        Some(Invoice {
+            text: text.to_lowercase(),
+            amount_msat,
+            description,
+            created_at: Timestamp::from(created_at),
+            expiry,
+        })
+    }
+
+    pub fn expires_at(&self) -> Timestamp {
+        Timestamp::from(self.created_at.as_u64().saturating_add(self.expiry))
+    }
+
+    pub fn is_expired(&self, now: Timestamp) -> bool {
+        now >= self.expires_at()
+    }
+
+    /// Requested amount in whole sats, if any.
+    pub fn amount_sats(&self) -> Option<u64> {
+        self.amount_msat.map(|msats| msats / 1000)
+    }
+}
+
+/// Decodes amount in sats written in BOLT11 `invoice`, without decoding
+/// the rest of it. Invoices without amount or with amount smaller than
+/// one sat give `None`.
+pub fn invoice_amount(invoice: &str) -> Option<u64> {
+    let invoice = invoice.to_lowercase();
+    // Human-readable part ends with the last `1`.
+    let hrp = &invoice[..invoice.rfind('1')?];
+    hrp_amount(hrp)
+        .flatten()
+        .map(|msats| msats / 1000)
+        .filter(|s| *s > 0)
+}
+
+/// Amount in millisats in human-readable part of invoice, which follows
+/// the network prefix. `None` if the part is invalid, `Some(None)` if
+/// it has no amount.
+fn hrp_amount(hrp: &str) -> Option<Option<u64>> {
+    let hrp = hrp.to_lowercase();
+    let amount = hrp
+        .strip_prefix("lnbcrt")
+        .or_else(|| hrp.strip_prefix("lnbc"))
+        .or_else(|| hrp.strip_prefix("lntbs"))
+        .or_else(|| hrp.strip_prefix("lntb"))?;
+    if amount.is_empty() {
+        return Some(None);
+    }
+
+    let (digits, multiplier) = match amount.chars().last()? {
+        c @ ('m' | 'u' | 'n' | 'p') => (&amount[..amount.len() - 1], Some(c)),
+        _ => (amount, None),
+    };
+    let value: u64 = digits.parse().ok()?;
+
+    // Millisats in one unit are 10^11 for BTC, others are fractions of it.
+    let msats = match multiplier {
+        None => value.checked_mul(100_000_000_000)?,
+        Some('m') => value.checked_mul(100_000_000)?,
+        Some('u') => value.checked_mul(100_000)?,
+        Some('n') => value.checked_mul(100)?,
+        _ => value / 10,
+    };
+
+    Some(Some(msats))
+}
+
+/// Number written in 5-bit groups, most significant first.
+fn uint(data: &[bech32::u5]) -> Option<u64> {
+    data.iter()
+        .try_fold(0u64, |n, d| n.checked_mul(32).map(|n| n + d.to_u8() as u64))
+}
+
+/// URL of the service which `lnurl` stands for.
+fn decode_lnurl(lnurl: &str) -> Option<Url> {
+    let (hrp, data, _) = bech32::decode(lnurl).ok()?;
+    if hrp != "lnurl" {
+        return None;
+    }
+
+    let bytes = Vec::<u8>::from_base32(&data).ok()?;
+    let url: Url = String::from_utf8(bytes).ok()?.parse().ok()?;
+    matches!(url.scheme(), "https" | "http").then_some(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use bech32::{u5, ToBase32};
+
+    use super::*;
+
+    /// Examples from BOLT11: donation of any amount, 2500 µBTC coffee expiring
+    /// in a minute and 20 mBTC with only hash of description.
+    const DONATION: &str = "lnbc1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpl2pkx2ctnv5sxxmmwwd5kgetjypeh2ursdae8g6twvus8g6rfwvs8qun0dfjkxaq9qrsgq357wnc5r2ueh7ck6q93dj32dlqnls087fxdwk8qakdyafkq3yap9us6v52vjjsrvywa6rt52cm9r9zqt8r2t7mlcwspyetp5h2tztugp9lfyql";
+    const COFFEE: &str = "lnbc2500u1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5xysxxatsyp3k7enxv4jsxqzpuaztrnwngzn3kdzw5hydlzf03qdgm2hdq27cqv3agm2awhz5se903vruatfhq77w3ls4evs3ch9zw97j25emudupq63nyw24cg27h2rspfj9srp";
+    const HASHED: &str = "lnbc20m1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqhp58yjmdan79s6qqdhdzgynm4zwqd5d7xmw5fk98klysy043l2ahrqscc6gd6ql3jrc5yzme8v4ntcewwz5cnw92tz0pc8qcuufvq7khhr8wpald05e92xw006sq94mg8v2ndf4sefvf9sygkshp5zfem29trqq2yxxz7";
+
+    /// Invoice of `hrp` with `fields` after creation time, with zero signature.
+    fn invoice(hrp: &str, fields: &[u8]) -> String {
+        let data: Vec<u5> = [0; TIMESTAMP_LEN]
+            .iter()
+            .chain(fields)
+            .chain(&[0; SIGNATURE_LEN])
+            .map(|d| u5::try_from_u8(*d).unwrap())
+            .collect();
+        bech32::encode(hrp, data, Variant::Bech32).unwrap()
+    }
+
+    #[test]
+    fn examples_of_bolt11_are_decoded() {
+        let donation = Invoice::decode(DONATION).unwrap();
+        assert_eq!(donation.amount_msat, None);
+        assert_eq!(
+            donation.description.as_deref(),
+            Some("Please consider supporting this project")
+        );
+        assert_eq!(donation.created_at, Timestamp::from(1496314658));
+        assert_eq!(donation.expiry, DEFAULT_EXPIRY);
+
+        let coffee = Invoice::decode(COFFEE).unwrap();
+        assert_eq!(coffee.amount_msat, Some(250_000_000));
+        assert_eq!(coffee.amount_sats(), Some(250_000));
+        assert_eq!(coffee.description.as_deref(), Some("1 cup coffee"));
+        assert_eq!(coffee.expiry, 60);
+        assert_eq!(coffee.expires_at(), Timestamp::from(1496314718));
+
+        let hashed = Invoice::decode(HASHED).unwrap();
+        assert_eq!(hashed.amount_sats(), Some(2_000_000));
+        assert_eq!(hashed.description, None);
+    }
+
+    #[test]
+    fn invoice_expires_exactly_at_expiry() {
+        let coffee = Invoice::decode(COFFEE).unwrap();
+        assert!(!coffee.is_expired(Timestamp::from(1496314717)));
+        assert!(coffee.is_expired(Timestamp::from(1496314718)));
+    }
+
+    #[test]
+    fn uppercase_invoice_is_kept_lowercase() {
+        let coffee = Invoice::decode(&COFFEE.to_uppercase()).unwrap();
+        assert_eq!(coffee.text, COFFEE);
+        assert_eq!(
+            PaymentRequest::Invoice(coffee).uri(),
+            format!("lightning:{COFFEE}")
+        );
+    }
+
+    #[test]
+    fn amounts_are_read_from_prefix() {
+        assert_eq!(invoice_amount(COFFEE), Some(250_000));
+        assert_eq!(invoice_amount(HASHED), Some(2_000_000));
+        assert_eq!(invoice_amount(DONATION), None);
+        assert_eq!(invoice_amount("lntb10n1xyz"), Some(1));
+        assert_eq!(invoice_amount("lnbcrt2u1xyz"), Some(200));
+        // Less than a sat.
+        assert_eq!(invoice_amount("lnbc10p1xyz"), None);
+    }
+
+    #[test]
+    fn malformed_invoices_are_not_decoded() {
+        let truncated = &COFFEE[..COFFEE.len() - 1];
+        let overflowing = invoice("lnbc99999999999", &[]);
+        // Description claims 31 groups, but there are only 2.
+        let long_field = invoice("lnbc", &[13, 0, 31, 1, 2]);
+        let short = bech32::encode("lnbc", [0u8; 4].to_base32(), Variant::Bech32).unwrap();
+
+        for text in [
+            "",
+            "lnbc",
+            "lnbc1",
+            truncated,
+            &overflowing,
+            &long_field,
+            &short,
+            "lnbcx1qqqqqq",
+            "lnxy2500u1pvjluez",
+        ] {
+            assert_eq!(Invoice::decode(text), None, "{text}");
+            assert_eq!(PaymentRequest::decode(text), None, "{text}");
+        }
+
+        assert_eq!(invoice_amount(""), None);
+        assert_eq!(invoice_amount("lnbc"), None);
+        assert_eq!(invoice_amount("lnbc99999999999999999999u1"), None);
+    }
+
+    #[test]
+    fn lnurl_decodes_to_web_service() {
+        let lnurl = |url: &str| {
+            bech32::encode("lnurl", url.as_bytes().to_base32(), Variant::Bech32).unwrap()
+        };
+
+        let https = lnurl("https://service.example.com/pay");
+        assert_eq!(
+            PaymentRequest::decode(&https.to_uppercase()),
+            Some(PaymentRequest::Lnurl {
+                lnurl: https,
+                url: "https://service.example.com/pay".parse().unwrap()
+            })
+        );
+        assert_eq!(PaymentRequest::decode(&lnurl("ftp://example.com")), None);
+        assert_eq!(PaymentRequest::decode("lnurl1invalid"), None);
+    }
+}
@@ -1,95 +1,1910 @@
 mod app;
+mod archive;
+mod backup;
+mod cli;
+mod config;
+mod content;
+mod db;
 mod demand;
+mod dirs;
 mod download;
+mod drafts;
+mod export;
+mod flood;
 mod follow;
+mod gossip;
+mod handlers;
+mod identicon;
 mod identity;
+mod kind_stats;
+mod labels;
+mod language;
+mod lightning;
+mod markdown;
+mod metadata_history;
+mod nip05;
 mod nostr;
+mod notifications;
+mod outbox;
+mod preview;
+mod reactions;
+mod relay_info;
+mod relay_stats;
+mod replaceable;
+mod retention;
+mod search;
+mod shutdown;
+mod spelling;
+mod state;
 mod stream;
+mod stream_metrics;
+mod subscriptions;
+#[cfg(test)]
+mod testing;
+mod timestamps;
+mod translate;
 mod ui;
+mod upload;
 
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 
+use archive::{EventFilter, ImportReport};
+use config::{Config, StreamConfig};
+use content::Collapse;
 use demand::Demand;
-use directories::ProjectDirs;
-use download::Download;
-use nostr::Persona;
-use nostr_sdk::prelude::{Event, EventId, Metadata, XOnlyPublicKey};
-use nostr_sdk::Client;
-use relm4::*;
+use dirs::Dirs;
+use download::{CacheStats, Download, DownloadConfig};
+use drafts::Drafts;
+use flood::Flood;
+use gossip::Gossip;
+use handlers::Handlers;
+use kind_stats::KindStats;
+use labels::Labels;
+use metadata_history::MetadataHistory;
+use nostr::{
+    Article, EventExt, Nip05Status, Persona, RelayPolicy, ReportSubject, ReportType,
+    BOOKMARKS_KIND, MUTE_LIST_KIND, PIN_LIST_KIND, REPORT_KIND,
+};
+use nostr_sdk::prelude::{
+    Contact, Event, EventBuilder, EventId, Kind, Metadata, SubscriptionFilter, Timestamp,
+    XOnlyPublicKey,
+};
+use nostr_sdk::{Client, RelayOptions};
+use notifications::Notifications;
+use outbox::Outbox;
+use preview::Previews;
+use reactions::Reactions;
+use relay_info::RelayInfo;
+use relay_stats::{Activity, RelayStats};
+use replaceable::Stored;
 use reqwest::Url;
+use retention::Retention;
+use shutdown::Shutdown;
 use sqlx::{query, SqlitePool};
+use stream_metrics::{StreamMetrics, StreamStats};
+use subscriptions::{SubscriptionHandle, Subscriptions};
+use timestamps::TimeFormat;
+use translate::Translations;
+use upload::Uploads;
+
+/// Kinds of our replaceable events that every relay we write to should
+/// have: metadata, contact list and relay list, so that others find our
+/// profile, whom we follow and where we write.
+const OWN_REPLACEABLE_KINDS: [u64; 3] = [0, 3, nostr::RELAY_LIST_KIND];
+
+/// Relays are asked this long whether they have our replaceable events.
+const REPUBLISH_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Relays are asked this long for contact list of another account.
+const CONTACT_LIST_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Clone)]
 pub struct Gnostique(Arc<GnostiqueInner>);
 
-impl Debug for Gnostique {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_tuple("Gnostique").field(&self.0.dirs).finish()
+impl Debug for Gnostique {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Gnostique").field(&self.0.dirs).finish()
+    }
+}
+
+struct GnostiqueInner {
+    pool: SqlitePool,
+    dirs: Dirs,
+    client: Client,
+    download: Download,
+    previews: Previews,
+    translations: Translations,
+    uploads: Uploads,
+    drafts: Drafts,
+    labels: Labels,
+    metadata_history: MetadataHistory,
+    outbox: Outbox,
+    notifications: Notifications,
+    relay_info: RelayInfo,
+    demand: Demand,
+    subscriptions: Subscriptions,
+    gossip: Gossip,
+    handlers: Handlers,
+    reactions: Reactions,
+    retention: Retention,
+    relay_stats: RelayStats,
+    kind_stats: KindStats,
+    stream_stats: StreamStats,
+    /// How received events are processed in this session.
+    stream: StreamConfig,
+    flood: Flood,
+    db_health: db::Health,
+    shutdown: Shutdown,
+    /// Current configuration, as it is in the configuration file.
+    config: RwLock<Config>,
+    /// Relays given on the command line, used only in this session.
+    session_relays: Vec<Url>,
+    /// Relays added in this session that get our replaceable events
+    /// once connected.
+    fresh_relays: Mutex<HashSet<Url>>,
+    /// Whether tools for debugging are offered.
+    debug: bool,
+}
+
+impl Gnostique {
+    pub fn new(
+        pool: SqlitePool,
+        dirs: Dirs,
+        client: Client,
+        session_relays: Vec<Url>,
+        config: Config,
+        debug: bool,
+    ) -> Gnostique {
+        let download = Download::new(
+            dirs.clone(),
+            pool.clone(),
+            DownloadConfig {
+                max_cache_size: config.network.max_cache_mb * 1024 * 1024,
+                ..Default::default()
+            },
+        );
+
+        let gnostique = Gnostique(Arc::new(GnostiqueInner {
+            demand: Demand::new(client.clone()),
+            subscriptions: Subscriptions::new(client.clone()),
+            gossip: Gossip::new(pool.clone(), client.clone()),
+            handlers: Handlers::new(pool.clone()),
+            reactions: Reactions::new(pool.clone()),
+            previews: Previews::new(pool.clone(), download.http().clone(), true),
+            translations: Translations::new(pool.clone(), download.http().clone()),
+            uploads: Uploads::new(download.http().clone()),
+            drafts: Drafts::new(pool.clone()),
+            labels: Labels::new(pool.clone()),
+            metadata_history: MetadataHistory::new(pool.clone()),
+            outbox: Outbox::new(pool.clone(), client.clone()),
+            notifications: Notifications::new(pool.clone()),
+            retention: Retention::new(pool.clone()),
+            relay_stats: RelayStats::new(pool.clone()),
+            kind_stats: Default::default(),
+            stream_stats: Default::default(),
+            // Channels and buffers of no size would never let anything through.
+            stream: StreamConfig {
+                concurrency: config.stream.concurrency.max(1),
+                feedback_queue: config.stream.feedback_queue.max(1),
+            },
+            flood: Default::default(),
+            db_health: Default::default(),
+            shutdown: Default::default(),
+            relay_info: RelayInfo::new(
+                pool.clone(),
+                download.http().clone(),
+                Duration::from_secs(config.network.relay_info_hours * 60 * 60),
+            ),
+            config: RwLock::new(config.clone()),
+            session_relays,
+            fresh_relays: Default::default(),
+            debug,
+            download,
+            dirs,
+            client,
+            pool,
+        }));
+
+        gnostique.apply_config(&config);
+        gnostique
+    }
+
+    pub fn demand(&self) -> &Demand {
+        &self.0.demand
+    }
+
+    pub fn gossip(&self) -> &Gossip {
+        &self.0.gossip
+    }
+
+    pub fn handlers(&self) -> &Handlers {
+        &self.0.handlers
+    }
+
+    pub fn reactions(&self) -> &Reactions {
+        &self.0.reactions
+    }
+
+    pub fn download(&self) -> &Download {
+        &self.0.download
+    }
+
+    pub fn previews(&self) -> &Previews {
+        &self.0.previews
+    }
+
+    pub fn translations(&self) -> &Translations {
+        &self.0.translations
+    }
+
+    pub fn uploads(&self) -> &Uploads {
+        &self.0.uploads
+    }
+
+    pub fn drafts(&self) -> &Drafts {
+        &self.0.drafts
+    }
+
+    pub fn labels(&self) -> &Labels {
+        &self.0.labels
+    }
+
+    pub fn metadata_history(&self) -> &MetadataHistory {
+        &self.0.metadata_history
+    }
+
+    pub fn subscriptions(&self) -> &Subscriptions {
+        &self.0.subscriptions
+    }
+
+    /// Asks `relays`, or all relays if none are given, for events
+    /// matching `filters` as long as the handle is kept. `name` tells
+    /// who wants them.
+    pub async fn subscribe(
+        &self,
+        name: &str,
+        filters: Vec<SubscriptionFilter>,
+        relays: Vec<Url>,
+    ) -> SubscriptionHandle {
+        self.subscriptions().subscribe(name, filters, relays).await
+    }
+
+    pub fn outbox(&self) -> &Outbox {
+        &self.0.outbox
+    }
+
+    pub fn notifications(&self) -> &Notifications {
+        &self.0.notifications
+    }
+
+    pub fn relay_info(&self) -> &RelayInfo {
+        &self.0.relay_info
+    }
+
+    pub fn retention(&self) -> &Retention {
+        &self.0.retention
+    }
+
+    pub fn relay_stats(&self) -> &RelayStats {
+        &self.0.relay_stats
+    }
+
+    /// Numbers of received events by kinds and the firehose of them.
+    pub fn kind_stats(&self) -> &KindStats {
+        &self.0.kind_stats
+    }
+
+    pub fn stream_stats(&self) -> &StreamStats {
+        &self.0.stream_stats
+    }
+
+    /// How received events are processed in this session. Changes of
+    /// configuration take effect from the next start.
+    pub fn stream_config(&self) -> &StreamConfig {
+        &self.0.stream
+    }
+
+    /// How the processing of received events keeps up.
+    pub fn stream_metrics(&self) -> StreamMetrics {
+        self.stream_stats().snapshot(
+            self.shutdown_state().in_flight(),
+            self.stream_config().concurrency,
+            self.stream_config().feedback_queue,
+        )
+    }
+
+    /// Whether Gnostique has been started with `--debug`.
+    pub fn is_debug(&self) -> bool {
+        self.0.debug
+    }
+
+    pub fn flood(&self) -> &Flood {
+        &self.0.flood
+    }
+
+    pub fn db_health(&self) -> &db::Health {
+        &self.0.db_health
+    }
+
+    pub fn config(&self) -> Config {
+        self.0.config.read().unwrap().clone()
+    }
+
+    /// Changes configuration by `change`, applies it and writes it into
+    /// the configuration file. Values which need restart, such as size
+    /// of the cache, are only written.
+    pub fn update_config<F>(&self, change: F) -> Result<(), String>
+    where
+        F: FnOnce(&mut Config),
+    {
+        let config = {
+            let mut config = self.0.config.write().unwrap();
+            change(&mut config);
+            config.clone()
+        };
+
+        self.apply_config(&config);
+        config.write(self.dirs().config_file())
+    }
+
+    fn apply_config(&self, config: &Config) {
+        self.notifications()
+            .set_enabled(config.general.desktop_notifications);
+        self.retention().set_keep_days(config.general.keep_days);
+        self.previews().set_enabled(config.privacy.link_previews);
+        self.translations().set_config(&config.translation);
+        self.uploads().set_config(&config.upload);
+        self.flood()
+            .set_limits(config.spam.max_notes, config.spam.window_secs);
+    }
+
+    pub fn min_pow(&self) -> u8 {
+        self.0.config.read().unwrap().general.min_pow
+    }
+
+    /// How times of notes are shown.
+    pub fn time_format(&self) -> TimeFormat {
+        self.0.config.read().unwrap().time
+    }
+
+    /// How long text notes can be before they are collapsed.
+    pub fn collapse(&self) -> Collapse {
+        self.0.config.read().unwrap().appearance.collapse
+    }
+
+    /// Languages in which notes are shown, all if none.
+    pub fn languages(&self) -> Vec<String> {
+        self.0.config.read().unwrap().general.languages.clone()
+    }
+
+    pub fn pool(&self) -> &SqlitePool {
+        &self.0.pool
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.0.client
+    }
+
+    pub fn dirs(&self) -> &Dirs {
+        &self.0.dirs
+    }
+
+    pub fn shutdown_state(&self) -> &Shutdown {
+        &self.0.shutdown
+    }
+
+    /// Ends Gnostique politely. No more received events are processed,
+    /// those being processed are let finish and responses of relays to
+    /// published events are awaited, all at most for [`shutdown::TIMEOUT`].
+    /// Then relays are disconnected and the database is closed.
+    ///
+    /// Calling it again while it waits makes it stop waiting.
+    pub async fn shutdown(&self) {
+        if !self.0.shutdown.close() {
+            return;
+        }
+        tracing::info!("Shutting down");
+
+        let deadline = tokio::time::Instant::now() + shutdown::TIMEOUT;
+        while !self.0.shutdown.is_forced()
+            && tokio::time::Instant::now() < deadline
+            && (self.0.shutdown.in_flight() > 0 || self.outbox().awaiting() > 0)
+        {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        if let Err(e) = self.relay_stats().flush().await {
+            tracing::warn!("Could not store relay statistics: {}", e);
+        }
+
+        match tokio::time::timeout(shutdown::TIMEOUT, self.client().disconnect()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => tracing::warn!("Could not disconnect from relays: {}", e),
+            Err(_) => tracing::warn!("Disconnecting from relays takes too long"),
+        }
+        self.pool().close().await;
+
+        tracing::info!("Shut down");
+    }
+
+    /// Reports size and number of files in download cache.
+    pub async fn cache_stats(&self) -> CacheStats {
+        self.download().stats().await
+    }
+
+    /// Signs event built by `builder` with our keys and publishes it
+    /// through the outbox.
+    pub async fn publish(&self, builder: EventBuilder) -> Result<EventId, String> {
+        let event = builder
+            .to_event(&self.client().keys())
+            .map_err(|e| e.to_string())?;
+
+        self.remember_own_replaceable(&event).await?;
+        if event.kind == Kind::ContactList {
+            self.store_contact_list(&event).await?;
+        }
+        if event.kind == Kind::Custom(PIN_LIST_KIND) {
+            self.store_pin_list(&event).await?;
+        }
+        self.outbox().publish(event).await
+    }
+
+    /// Policies of all relays of the client, as stored in database.
+    /// Relays connected only to reach other users or to ask for something
+    /// are not included, neither are relays of this session only.
+    pub async fn relay_policies(&self) -> Vec<RelayPolicy> {
+        let mut policies = Vec::new();
+
+        for url in self.client().relays().await.into_keys() {
+            if self.gossip().is_extra(&url).await
+                || self.demand().is_adhoc(&url).await
+                || self.0.session_relays.contains(&url)
+            {
+                continue;
+            }
+
+            let url_s = url.to_string();
+            let stored = query!(
+                r#"SELECT read AS "read: bool", write AS "write: bool" FROM relays WHERE url = ?"#,
+                url_s
+            )
+            .fetch_optional(self.pool())
+            .await
+            .ok()
+            .flatten();
+
+            let (read, write) = stored.map(|r| (r.read, r.write)).unwrap_or((true, true));
+            policies.push(RelayPolicy { url, read, write });
+        }
+
+        policies.sort_by(|a, b| a.url.as_str().cmp(b.url.as_str()));
+        policies
+    }
+
+    /// Makes relays of the client honor their stored policies and asks
+    /// for our NIP-65 relay list and NIP-51 bookmark and mute lists.
+    pub async fn apply_relay_policies(&self) {
+        let relays = self.client().relays().await;
+
+        for policy in self.relay_policies().await {
+            if let Some(relay) = relays.get(&policy.url) {
+                relay.opts().set_read(policy.read);
+                relay.opts().set_write(policy.write);
+            }
+        }
+
+        let own = SubscriptionFilter::new()
+            .author(self.client().keys().public_key())
+            .kind(Kind::Custom(nostr::RELAY_LIST_KIND))
+            .limit(1);
+        let bookmarks = SubscriptionFilter::new()
+            .author(self.client().keys().public_key())
+            .kind(Kind::Custom(BOOKMARKS_KIND))
+            .limit(1);
+        let muted = SubscriptionFilter::new()
+            .author(self.client().keys().public_key())
+            .kind(Kind::Custom(MUTE_LIST_KIND))
+            .limit(1);
+        let pins = SubscriptionFilter::new()
+            .author(self.client().keys().public_key())
+            .kind(Kind::Custom(PIN_LIST_KIND))
+            .limit(1);
+        self.client()
+            .req_events_of(vec![own, bookmarks, muted, pins], None)
+            .await;
+    }
+
+    /// Changes what `policy.url` is used for and publishes updated relay list.
+    pub async fn set_relay_policy(&self, policy: RelayPolicy) -> Result<(), String> {
+        self.store_relay_policy(&policy).await?;
+        self.publish_relay_list().await.map(|_| ())
+    }
+
+    /// Adopts `relays` as our relays, e. g. from relay list published
+    /// by another client. Relays not known yet are added and connected.
+    pub async fn import_relay_list(&self, relays: Vec<RelayPolicy>) -> Result<(), String> {
+        for mut policy in relays {
+            policy.url = nostr::normalize_relay_url(&policy.url)
+                .ok_or_else(|| format!("{} is not a relay URL", policy.url))?;
+            self.store_relay_policy(&policy).await?;
+
+            if !self.client().relays().await.contains_key(&policy.url) {
+                if policy.write {
+                    self.0
+                        .fresh_relays
+                        .lock()
+                        .unwrap()
+                        .insert(policy.url.clone());
+                }
+                let url = policy.url.to_string();
+                let opts = RelayOptions::new(policy.read, policy.write);
+                self.client()
+                    .add_relay_with_opts(url.as_str(), None, opts)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                self.client()
+                    .connect_relay(url.as_str(), false)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stores `policy` and applies it to the relay, if the client has it.
+    async fn store_relay_policy(&self, policy: &RelayPolicy) -> Result<(), String> {
+        let url_s = policy.url.to_string();
+
+        query!(
+            r#"
+INSERT INTO relays (url, read, write) VALUES (?, ?, ?)
+ON CONFLICT (url) DO UPDATE SET read = EXCLUDED.read, write = EXCLUDED.write
+"#,
+            url_s,
+            policy.read,
+            policy.write
+        )
+        .execute(self.pool())
+        .await
+        .map_err(|e| e.to_string())?;
+
+        if let Some(relay) = self.client().relays().await.get(&policy.url) {
+            relay.opts().set_read(policy.read);
+            relay.opts().set_write(policy.write);
+        }
+
+        Ok(())
+    }
+
+    /// Publishes NIP-65 relay list generated from policies of our relays.
+    pub async fn publish_relay_list(&self) -> Result<EventId, String> {
+        let tags = nostr::relay_list_tags(&self.relay_policies().await);
+        let builder = EventBuilder::new(Kind::Custom(nostr::RELAY_LIST_KIND), "", &tags);
+        self.publish(builder).await
+    }
+
+    /// Stores event and relay from which it arrives, if known, into database,
+    /// does nothing when already exist. Replaceable events replace their
+    /// older versions, outdated ones are not stored. Returns `true` if the
+    /// event has not been stored before.
+    pub async fn store_event(&self, relay: Option<&Url>, event: &Event) -> Result<bool, String> {
+        let stored = self
+            .store_textnote(event)
+            .await
+            .map_err(|e| format!("Could not store event {} into textnotes: {}", event.id, e))?;
+        if stored == Stored::Outdated {
+            return Ok(false);
+        }
+
+        // Newly stored notes are indexed for full-text search.
+        if let Stored::New(rowid) = stored {
+            let author_name = self.get_persona(event.pubkey).await.and_then(|p| p.name);
+            let author = event.pubkey.to_string();
+
+            query!(
+                "INSERT INTO textnotes_fts (rowid, content, author_name, author) VALUES (?, ?, ?, ?)",
+                rowid,
+                event.content,
+                author_name,
+                author
+            )
+            .execute(self.pool())
+            .await
+            .map_err(|e| format!("Could not index event {} in textnotes_fts: {}", event.id, e))?;
+        }
+
+        if let Some(relay) = relay {
+            self.store_seen_on(event.id, relay).await?;
+        }
+
+        for tag in event.hashtags() {
+            let id = event.id.as_bytes().to_vec();
+
+            db::retry_on_busy(|| {
+                query!(
+                    "INSERT INTO hashtags (event_id, tag) VALUES (?, ?)",
+                    id,
+                    tag
+                )
+                .execute(self.pool())
+            })
+            .await
+            .map_err(|e| {
+                format!(
+                    "Could not store hashtags of event {} into hashtags: {}",
+                    event.id, e
+                )
+            })?;
+        }
+
+        Ok(matches!(stored, Stored::New(_)))
+    }
+
+    /// Stores detected `language` of stored text note `event_id`.
+    pub async fn store_language(
+        &self,
+        event_id: EventId,
+        language: Option<&str>,
+    ) -> Result<bool, String> {
+        let id = event_id.as_bytes().to_vec();
+
+        db::retry_on_busy(|| {
+            query!(
+                "UPDATE textnotes SET language = ? WHERE id = ?",
+                language,
+                id
+            )
+            .execute(self.pool())
+        })
+        .await
+        .map(|r| r.rows_affected() > 0)
+        .map_err(|e| format!("Could not store language of event {}: {}", event_id, e))
+    }
+
+    /// Stores `event` into textnotes, superseding its older version if it
+    /// is replaceable.
+    async fn store_textnote(&self, event: &Event) -> Result<Stored, sqlx::Error> {
+        db::retry_on_busy(|| async {
+            let mut tx = self.pool().begin().await?;
+            let stored = replaceable::store(&mut tx, event).await?;
+            tx.commit().await?;
+            Ok(stored)
+        })
+        .await
+    }
+
+    /// The newest stored version of replaceable event of `kind` by
+    /// `author`, with identifier `d_tag` if it is parameterized.
+    pub async fn latest_replaceable(
+        &self,
+        author: XOnlyPublicKey,
+        kind: u64,
+        d_tag: &str,
+    ) -> Option<Event> {
+        let author = author.serialize().to_vec();
+        let kind = kind as i64;
+
+        query!(
+            r#"
+SELECT t.event FROM replaceable_events r JOIN textnotes t ON t.id = r.event_id
+WHERE r.author = ? AND r.kind = ? AND r.d_tag = ?
+"#,
+            author,
+            kind,
+            d_tag
+        )
+        .fetch_optional(self.pool())
+        .await
+        .ok()
+        .flatten()
+        .and_then(|r| Event::from_json(r.event).ok())
+    }
+
+    /// Writes stored events matching `filter` into file at `path`, one
+    /// event JSON per line, from the oldest. Returns number of written events.
+    pub async fn export_events(&self, path: &Path, filter: &EventFilter) -> Result<u64, String> {
+        use futures_util::TryStreamExt;
+        use tokio::io::AsyncWriteExt;
+
+        let kind = filter.kind.map(|k| k as i64);
+        let author = filter.author.map(|a| a.to_string());
+        let since = filter.since.map(|t| t.as_i64());
+        let until = filter.until.map(|t| t.as_i64());
+
+        let file = tokio::fs::File::create(path)
+            .await
+            .map_err(|e| e.to_string())?;
+        let mut out = tokio::io::BufWriter::new(file);
+        let mut count = 0;
+
+        let mut rows = query!(
+            r#"
+SELECT event AS "event!" FROM textnotes
+WHERE (?1 IS NULL OR json_extract(event, '$.kind') = ?1)
+  AND (?2 IS NULL OR json_extract(event, '$.pubkey') = ?2)
+  AND (?3 IS NULL OR json_extract(event, '$.created_at') >= ?3)
+  AND (?4 IS NULL OR json_extract(event, '$.created_at') <= ?4)
+ORDER BY json_extract(event, '$.created_at')
+"#,
+            kind,
+            author,
+            since,
+            until
+        )
+        .fetch(self.pool());
+
+        while let Some(row) = rows.try_next().await.map_err(|e| e.to_string())? {
+            out.write_all(row.event.as_bytes())
+                .await
+                .map_err(|e| e.to_string())?;
+            out.write_all(b"\n").await.map_err(|e| e.to_string())?;
+            count += 1;
+        }
+
+        out.flush().await.map_err(|e| e.to_string())?;
+        tracing::info!("Exported {} events to {}", count, path.display());
+        Ok(count)
+    }
+
+    /// Stores events from file at `path`, which contains one event JSON per
+    /// line. Events with invalid signatures are refused. `progress` is
+    /// called every now and then with what has been done so far.
+    pub async fn import_events<F>(&self, path: &Path, progress: F) -> Result<ImportReport, String>
+    where
+        F: Fn(&ImportReport),
+    {
+        use tokio::io::AsyncBufReadExt;
+
+        /// Progress is reported after this many lines.
+        const PROGRESS_EVERY: u64 = 1000;
+
+        let file = tokio::fs::File::open(path)
+            .await
+            .map_err(|e| e.to_string())?;
+        let mut lines = tokio::io::BufReader::new(file).lines();
+        let mut report = ImportReport::default();
+
+        while let Some(line) = lines.next_line().await.map_err(|e| e.to_string())? {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match Event::from_json(&line) {
+                Ok(event) if event.verify().is_ok() => {
+                    if self.store_event(None, &event).await? {
+                        report.imported += 1;
+                    } else {
+                        report.skipped += 1;
+                    }
+                }
+                _ => report.invalid += 1,
+            }
+
+            if report.lines() % PROGRESS_EVERY == 0 {
+                progress(&report);
+            }
+        }
+
+        tracing::info!(
+            "Imported {} events from {}, {} already stored, {} invalid",
+            report.imported,
+            path.display(),
+            report.skipped,
+            report.invalid
+        );
+        Ok(report)
+    }
+
+    /// Finds up to `limit` stored text notes matching `input`, from the newest.
+    /// Words may be in any order, text in double quotes is searched for
+    /// as a phrase. Notes of muted authors and those hidden after
+    /// reporting are not included.
+    pub async fn search_notes(&self, input: &str, limit: u32) -> Vec<Event> {
+        let fts = match search::fts_query(input) {
+            Some(q) => q,
+            None => return vec![],
+        };
+
+        let result = query!(
+            r#"
+SELECT t.event AS "event!" FROM textnotes_fts f
+JOIN textnotes t ON t.rowid = f.rowid
+WHERE textnotes_fts MATCH ?
+  AND f.author NOT IN (SELECT lower(hex(pubkey)) FROM muted_pubkeys)
+  AND t.id NOT IN (SELECT target FROM reported WHERE hidden)
+  AND f.author NOT IN (SELECT lower(hex(target)) FROM reported WHERE hidden)
+ORDER BY json_extract(t.event, '$.created_at') DESC
+LIMIT ?
+"#,
+            fts,
+            limit
+        )
+        .fetch_all(self.pool())
+        .await;
+
+        match result {
+            Ok(records) => records
+                .into_iter()
+                .filter_map(|r| Event::from_json(r.event).ok())
+                .collect(),
+            Err(e) => {
+                tracing::warn!("Search for {} failed: {}", fts, e);
+                vec![]
+            }
+        }
+    }
+
+    /// Updates name of `author` in full-text index of text notes.
+    pub async fn reindex_author_name(&self, author: XOnlyPublicKey, name: Option<&str>) {
+        let author = author.to_string();
+        let _ = query!(
+            "UPDATE textnotes_fts SET author_name = ? WHERE author = ?",
+            name,
+            author
+        )
+        .execute(self.pool())
+        .await;
+    }
+
+    /// Finds up to `limit` users whose name, display name or NIP-05
+    /// identifier contains `input`. Followed users go first, then those
+    /// whose name starts with it.
+    pub async fn search_personas(&self, input: &str, limit: u32) -> Vec<Persona> {
+        let input = input.trim();
+        if input.is_empty() {
+            return vec![];
+        }
+
+        let escaped = input
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        let contains = format!("%{escaped}%");
+        let starts = format!("{escaped}%");
+
+        let records = query!(
+            r#"
+SELECT author AS "author!" FROM metadata
+WHERE name LIKE ?1 ESCAPE '\' OR display_name LIKE ?1 ESCAPE '\' OR nip05 LIKE ?1 ESCAPE '\'
+ORDER BY
+  author IN (SELECT pubkey FROM contacts) DESC,
+  (name LIKE ?2 ESCAPE '\' OR display_name LIKE ?2 ESCAPE '\') DESC,
+  name
+LIMIT ?3
+"#,
+            contains,
+            starts,
+            limit
+        )
+        .fetch_all(self.pool())
+        .await
+        .unwrap_or_default();
+
+        let mut personas = Vec::with_capacity(records.len());
+        for record in records {
+            if let Ok(pubkey) = XOnlyPublicKey::from_slice(&record.author) {
+                if let Some(persona) = self.get_persona(pubkey).await {
+                    personas.push(persona);
+                }
+            }
+        }
+
+        personas
+    }
+
+    /// Users we follow, as stored locally.
+    pub async fn follows(&self) -> Vec<XOnlyPublicKey> {
+        query!(r#"SELECT pubkey AS "pubkey!" FROM contacts"#)
+            .fetch_all(self.pool())
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|r| XOnlyPublicKey::from_slice(&r.pubkey).ok())
+            .collect()
+    }
+
+    pub async fn is_followed(&self, pubkey: XOnlyPublicKey) -> bool {
+        let pubkey = pubkey.serialize().to_vec();
+
+        query!("SELECT pubkey FROM contacts WHERE pubkey = ?", pubkey)
+            .fetch_optional(self.pool())
+            .await
+            .ok()
+            .flatten()
+            .is_some()
+    }
+
+    /// Starts following `pubkey`.
+    pub async fn follow(&self, pubkey: XOnlyPublicKey) -> Result<(), String> {
+        let pubkey = pubkey.serialize().to_vec();
+
+        query!("INSERT INTO contacts (pubkey) VALUES (?)", pubkey)
+            .execute(self.pool())
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Starts following all `pubkeys` and publishes our contact list, if
+    /// any of them has not been followed yet. Returns those newly followed.
+    pub async fn follow_all(
+        &self,
+        pubkeys: &[XOnlyPublicKey],
+    ) -> Result<Vec<XOnlyPublicKey>, String> {
+        let mut followed = Vec::new();
+
+        for pubkey in pubkeys {
+            let pk = pubkey.serialize().to_vec();
+            let inserted = query!("INSERT INTO contacts (pubkey) VALUES (?)", pk)
+                .execute(self.pool())
+                .await
+                .map_err(|e| e.to_string())?;
+
+            if inserted.rows_affected() > 0 {
+                followed.push(*pubkey);
+            }
+        }
+
+        if !followed.is_empty() {
+            self.publish_contact_list().await?;
+        }
+
+        Ok(followed)
+    }
+
+    /// Users followed by `pubkey` according to the newest of their
+    /// contact lists that our relays return.
+    pub async fn contact_list_of(
+        &self,
+        pubkey: XOnlyPublicKey,
+    ) -> Result<Vec<XOnlyPublicKey>, String> {
+        let filter = SubscriptionFilter::new()
+            .author(pubkey)
+            .kind(Kind::ContactList)
+            .limit(1);
+
+        self.client()
+            .get_events_of(vec![filter], Some(CONTACT_LIST_TIMEOUT))
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .filter(|e| e.pubkey == pubkey && e.kind == Kind::ContactList)
+            .max_by_key(|e| e.created_at)
+            .map(|e| e.mentions())
+            .ok_or_else(|| "No relay knows whom the account follows".to_string())
+    }
+
+    /// Stores contact list `event` of its author, unless it or a newer one
+    /// is known. Returns whether it has been stored.
+    pub async fn store_contact_list(&self, event: &Event) -> Result<bool, String> {
+        let author = event.pubkey.serialize().to_vec();
+        let created_at = event.created_at.as_i64();
+
+        let mut tx = self.pool().begin().await.map_err(|e| e.to_string())?;
+
+        let stored = replaceable::store(&mut tx, event)
+            .await
+            .map_err(|e| format!("Could not store contact list of {}: {}", event.pubkey, e))?;
+        if !matches!(stored, Stored::New(_)) {
+            return Ok(false);
+        }
+
+        query!(
+            r#"
+INSERT INTO contact_lists (author, created_at) VALUES (?, ?)
+ON CONFLICT (author) DO UPDATE SET created_at = EXCLUDED.created_at
+"#,
+            author,
+            created_at
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(|e| format!("Could not store contact list of {}: {}", event.pubkey, e))?;
+
+        query!("DELETE FROM contact_list_entries WHERE author = ?", author)
+            .execute(&mut tx)
+            .await
+            .map_err(|e| format!("Could not store contact list of {}: {}", event.pubkey, e))?;
+
+        for pubkey in event.mentions() {
+            let pubkey = pubkey.serialize().to_vec();
+            query!(
+                "INSERT OR IGNORE INTO contact_list_entries (author, pubkey) VALUES (?, ?)",
+                author,
+                pubkey
+            )
+            .execute(&mut tx)
+            .await
+            .map_err(|e| format!("Could not store contact list of {}: {}", event.pubkey, e))?;
+        }
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(true)
+    }
+
+    /// Users followed by `pubkey` according to their stored contact list,
+    /// `None` if no contact list of theirs is stored.
+    pub async fn following_of(&self, pubkey: XOnlyPublicKey) -> Option<Vec<XOnlyPublicKey>> {
+        let author = pubkey.serialize().to_vec();
+
+        query!("SELECT author FROM contact_lists WHERE author = ?", author)
+            .fetch_optional(self.pool())
+            .await
+            .ok()
+            .flatten()?;
+
+        let following = query!(
+            r#"SELECT pubkey AS "pubkey!" FROM contact_list_entries WHERE author = ?"#,
+            author
+        )
+        .fetch_all(self.pool())
+        .await
+        .ok()?
+        .into_iter()
+        .filter_map(|r| XOnlyPublicKey::from_slice(&r.pubkey).ok())
+        .collect();
+
+        Some(following)
+    }
+
+    /// Users whose stored contact lists contain `pubkey`. These are only
+    /// followers known to this client, not all of them.
+    pub async fn followers_of(&self, pubkey: XOnlyPublicKey) -> Vec<XOnlyPublicKey> {
+        let pubkey = pubkey.serialize().to_vec();
+
+        query!(
+            r#"SELECT author AS "author!" FROM contact_list_entries WHERE pubkey = ?"#,
+            pubkey
+        )
+        .fetch_all(self.pool())
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|r| XOnlyPublicKey::from_slice(&r.author).ok())
+        .collect()
+    }
+
+    /// Publishes our contact list (NIP-02) of users we follow.
+    pub async fn publish_contact_list(&self) -> Result<EventId, String> {
+        let contacts = self
+            .follows()
+            .await
+            .into_iter()
+            .map(|pk| Contact::new::<String>(pk, None, None))
+            .collect();
+        self.publish(EventBuilder::set_contact_list(contacts)).await
+    }
+
+    /// Notes in our bookmark list, from the most recently bookmarked.
+    pub async fn bookmarks(&self) -> Vec<EventId> {
+        query!(
+            r#"SELECT event_id AS "event_id!" FROM bookmarks WHERE bookmarked ORDER BY changed_at DESC"#
+        )
+        .fetch_all(self.pool())
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|r| EventId::from_slice(&r.event_id).ok())
+        .collect()
+    }
+
+    /// Adds note `event_id` to our bookmark list, or removes it from
+    /// there, and publishes the updated list.
+    pub async fn set_bookmarked(
+        &self,
+        event_id: EventId,
+        bookmarked: bool,
+    ) -> Result<EventId, String> {
+        let id = event_id.as_bytes().to_vec();
+        let now = Timestamp::now().as_i64();
+
+        query!(
+            r#"
+INSERT INTO bookmarks (event_id, bookmarked, changed_at) VALUES (?, ?, ?)
+ON CONFLICT (event_id) DO UPDATE SET
+  bookmarked = EXCLUDED.bookmarked,
+  changed_at = EXCLUDED.changed_at
+"#,
+            id,
+            bookmarked,
+            now
+        )
+        .execute(self.pool())
+        .await
+        .map_err(|e| e.to_string())?;
+
+        self.publish_bookmark_list().await
+    }
+
+    /// Merges our bookmark list `event`, published possibly by another
+    /// client, into stored bookmarks. Bookmarks added or removed here
+    /// after the list was created are kept and the merged list is then
+    /// published. Returns all bookmarks after merging, or `None` if
+    /// `event` is not newer than the list we know.
+    pub async fn merge_bookmark_list(&self, event: &Event) -> Result<Option<Vec<EventId>>, String> {
+        if let Some(known) = self.own_list(BOOKMARKS_KIND).await {
+            if known.created_at >= event.created_at {
+                return Ok(None);
+            }
+        }
+
+        self.store_own_list(event).await?;
+
+        let created_at = event.created_at.as_i64();
+        let listed = event.bookmarks();
+
+        for event_id in &listed {
+            let id = event_id.as_bytes().to_vec();
+
+            query!(
+                r#"
+INSERT INTO bookmarks (event_id, bookmarked, changed_at) VALUES (?, TRUE, ?)
+ON CONFLICT (event_id) DO UPDATE SET
+  bookmarked = TRUE,
+  changed_at = EXCLUDED.changed_at
+WHERE bookmarks.changed_at < EXCLUDED.changed_at
+"#,
+                id,
+                created_at
+            )
+            .execute(self.pool())
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+
+        // Bookmarks that the list does not contain anymore.
+        let removed: Vec<Vec<u8>> = query!(
+            r#"SELECT event_id AS "event_id!" FROM bookmarks WHERE bookmarked AND changed_at < ?"#,
+            created_at
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|r| r.event_id)
+        .filter(|id| !listed.iter().any(|l| l.as_bytes() == id.as_slice()))
+        .collect();
+
+        for id in removed {
+            query!(
+                "UPDATE bookmarks SET bookmarked = FALSE, changed_at = ? WHERE event_id = ?",
+                created_at,
+                id
+            )
+            .execute(self.pool())
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+
+        let changed_here = query!(
+            r#"SELECT COUNT(*) AS "count!: i64" FROM bookmarks WHERE changed_at > ?"#,
+            created_at
+        )
+        .fetch_one(self.pool())
+        .await
+        .map(|r| r.count)
+        .unwrap_or(0);
+
+        if changed_here > 0 {
+            self.publish_bookmark_list().await?;
+        }
+
+        Ok(Some(self.bookmarks().await))
     }
-}
 
-struct GnostiqueInner {
-    pool: SqlitePool,
-    dirs: ProjectDirs,
-    client: Client,
-    download: Download,
-    demand: Demand,
-}
+    /// Publishes NIP-51 bookmark list generated from stored bookmarks.
+    /// Everything else in the previous version of the list, including
+    /// encrypted private bookmarks, is published unchanged.
+    async fn publish_bookmark_list(&self) -> Result<EventId, String> {
+        let previous = self.own_list(BOOKMARKS_KIND).await;
 
-impl Gnostique {
-    pub fn new(pool: SqlitePool, dirs: ProjectDirs, client: Client) -> Gnostique {
-        Gnostique(Arc::new(GnostiqueInner {
-            demand: Demand::new(client.clone()),
-            download: Download::new(dirs.clone()),
-            dirs,
-            client,
-            pool,
-        }))
+        // The oldest bookmarks go first, as if they were appended.
+        let mut bookmarks = self.bookmarks().await;
+        bookmarks.reverse();
+
+        let tags = nostr::bookmark_list_tags(&bookmarks, previous.as_ref());
+        let content = previous.map(|e| e.content).unwrap_or_default();
+        let event = EventBuilder::new(Kind::Custom(BOOKMARKS_KIND), content, &tags)
+            .to_event(&self.client().keys())
+            .map_err(|e| e.to_string())?;
+
+        self.store_own_list(&event).await?;
+        self.outbox().publish(event).await
     }
 
-    pub fn demand(&self) -> &Demand {
-        &self.0.demand
+    /// Stores pin list `event` of its author, unless it or a newer one is
+    /// known. Returns whether it has been stored.
+    pub async fn store_pin_list(&self, event: &Event) -> Result<bool, String> {
+        let author = event.pubkey.serialize().to_vec();
+        let json = event.as_json().map_err(|e| e.to_string())?;
+        let created_at = event.created_at.as_i64();
+
+        let mut tx = self.pool().begin().await.map_err(|e| e.to_string())?;
+
+        let stored = replaceable::store(&mut tx, event)
+            .await
+            .map_err(|e| format!("Could not store pin list of {}: {}", event.pubkey, e))?;
+        if !matches!(stored, Stored::New(_)) {
+            return Ok(false);
+        }
+
+        query!(
+            r#"
+INSERT INTO pin_lists (author, event, created_at) VALUES (?, ?, ?)
+ON CONFLICT (author) DO UPDATE SET event = EXCLUDED.event, created_at = EXCLUDED.created_at
+"#,
+            author,
+            json,
+            created_at
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(|e| format!("Could not store pin list of {}: {}", event.pubkey, e))?;
+
+        query!("DELETE FROM pins WHERE author = ?", author)
+            .execute(&mut tx)
+            .await
+            .map_err(|e| format!("Could not store pin list of {}: {}", event.pubkey, e))?;
+
+        for (position, (event_id, _)) in event.pins().into_iter().enumerate() {
+            let id = event_id.as_bytes().to_vec();
+            let position = position as i64;
+            query!(
+                "INSERT INTO pins (author, event_id, position) VALUES (?, ?, ?)",
+                author,
+                id,
+                position
+            )
+            .execute(&mut tx)
+            .await
+            .map_err(|e| format!("Could not store pin list of {}: {}", event.pubkey, e))?;
+        }
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(true)
     }
 
-    pub fn download(&self) -> &Download {
-        &self.0.download
+    /// Notes pinned by `pubkey`, in order of their pin list.
+    pub async fn pins_of(&self, pubkey: XOnlyPublicKey) -> Vec<EventId> {
+        let author = pubkey.serialize().to_vec();
+
+        query!(
+            r#"SELECT event_id AS "event_id!" FROM pins WHERE author = ? ORDER BY position"#,
+            author
+        )
+        .fetch_all(self.pool())
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|r| EventId::from_slice(&r.event_id).ok())
+        .collect()
     }
 
-    pub fn pool(&self) -> &SqlitePool {
-        &self.0.pool
+    /// Pins our note `event_id` to our profile, or unpins it, and
+    /// publishes the updated pin list. Returns all pinned notes now.
+    pub async fn set_pinned(
+        &self,
+        event_id: EventId,
+        pinned: bool,
+    ) -> Result<Vec<EventId>, String> {
+        let me = self.client().keys().public_key();
+        let mut pins = self.pins_of(me).await;
+        if pinned == pins.contains(&event_id) {
+            return Ok(pins);
+        }
+        if pinned {
+            pins.push(event_id);
+        } else {
+            pins.retain(|id| *id != event_id);
+        }
+
+        let author = me.serialize().to_vec();
+        let previous = query!("SELECT event FROM pin_lists WHERE author = ?", author)
+            .fetch_optional(self.pool())
+            .await
+            .ok()
+            .flatten()
+            .and_then(|r| Event::from_json(r.event).ok());
+
+        let tags = nostr::pin_list_tags(&pins, previous.as_ref());
+        let content = previous.map(|e| e.content).unwrap_or_default();
+        self.publish(EventBuilder::new(
+            Kind::Custom(PIN_LIST_KIND),
+            content,
+            &tags,
+        ))
+        .await?;
+
+        Ok(pins)
     }
 
-    pub fn client(&self) -> &Client {
-        &self.0.client
+    /// The newest known version of our replaceable list of `kind`.
+    async fn own_list(&self, kind: u64) -> Option<Event> {
+        let kind = kind as i64;
+
+        query!("SELECT event FROM own_lists WHERE kind = ?", kind)
+            .fetch_optional(self.pool())
+            .await
+            .ok()
+            .flatten()
+            .and_then(|r| serde_json::from_str(&r.event).ok())
     }
 
-    pub fn dirs(&self) -> &ProjectDirs {
-        &self.0.dirs
+    /// Remembers `event` as the newest version of our replaceable list.
+    async fn store_own_list(&self, event: &Event) -> Result<(), String> {
+        let kind = event.kind.as_u64() as i64;
+        let json = event.as_json().map_err(|e| e.to_string())?;
+        let created_at = event.created_at.as_i64();
+
+        query!(
+            "INSERT INTO own_lists (kind, event, created_at) VALUES (?, ?, ?)",
+            kind,
+            json,
+            created_at
+        )
+        .execute(self.pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
     }
 
-    /// Stores event and relay from which it arrives into database,
-    /// does nothing when already exist.
-    pub async fn store_event(&self, relay: &Url, event: &Event) {
-        let id = event.id.as_bytes().to_vec();
-        let json = serde_json::to_string(event).unwrap();
+    /// Remembers our replaceable `event` so that relays new to us can get
+    /// it, unless a newer one is known. Events of other kinds are ignored.
+    pub async fn remember_own_replaceable(&self, event: &Event) -> Result<(), String> {
+        let kind = event.kind.as_u64();
+        if !OWN_REPLACEABLE_KINDS.contains(&kind) {
+            return Ok(());
+        }
+        if let Some(known) = self.own_list(kind).await {
+            if known.created_at >= event.created_at {
+                return Ok(());
+            }
+        }
+
+        self.store_own_list(event).await
+    }
+
+    /// The newest known versions of our replaceable events of
+    /// [`OWN_REPLACEABLE_KINDS`]. Those published before they were
+    /// remembered are taken from the stored metadata or other stored
+    /// events.
+    async fn own_replaceables(&self) -> Vec<Event> {
+        let me = self.client().keys().public_key();
+        let mut events = Vec::new();
+
+        for kind in OWN_REPLACEABLE_KINDS {
+            match self.own_list(kind).await {
+                Some(event) => events.push(event),
+                None if kind == Kind::Metadata.as_u64() => {
+                    let author = me.serialize().to_vec();
+                    let stored = query!("SELECT event FROM metadata WHERE author = ?", author)
+                        .fetch_optional(self.pool())
+                        .await
+                        .ok()
+                        .flatten()
+                        .and_then(|r| Event::from_json(r.event).ok());
+                    events.extend(stored);
+                }
+                None => events.extend(self.latest_replaceable(me, kind, "").await),
+            }
+        }
+
+        events
+    }
+
+    /// Publishes our replaceable events to `relay`, which we must write
+    /// to, unless it already has them or newer. Returns how many events
+    /// have been published.
+    pub async fn republish_to(&self, relay: &Url) -> Result<usize, String> {
+        let client_relay = self
+            .client()
+            .relays()
+            .await
+            .remove(relay)
+            .filter(|r| r.opts().write())
+            .ok_or_else(|| format!("We do not write to {relay}"))?;
+        let mut published = 0;
+
+        for event in self.own_replaceables().await {
+            let filter = SubscriptionFilter::new()
+                .author(event.pubkey)
+                .kind(event.kind)
+                .limit(1);
+
+            // Relays which cannot be asked, e. g. those we do not read
+            // from, get the event anyway.
+            match client_relay
+                .get_events_of(vec![filter], Some(REPUBLISH_CHECK_TIMEOUT))
+                .await
+            {
+                Ok(known) if known.iter().any(|k| k.created_at >= event.created_at) => continue,
+                Ok(_) => {}
+                Err(e) => tracing::info!("Could not ask {} for event {}: {}", relay, event.id, e),
+            }
+
+            self.outbox()
+                .publish_to(&event, std::slice::from_ref(relay))
+                .await?;
+            published += 1;
+        }
+
+        Ok(published)
+    }
+
+    /// Publishes our replaceable events to all connected relays we write
+    /// to, to those which do not have them. Returns how many events have
+    /// been published to how many relays.
+    pub async fn broadcast_profile(&self) -> (usize, usize) {
+        let relays = self.outbox().connected_relays().await;
+        let results =
+            futures_util::future::join_all(relays.iter().map(|r| self.republish_to(r))).await;
+
+        let mut published = 0;
+        let mut reached = 0;
+        for (relay, result) in relays.iter().zip(results) {
+            match result {
+                Ok(0) => {}
+                Ok(n) => {
+                    published += n;
+                    reached += 1;
+                }
+                Err(e) => tracing::warn!("Could not publish our events to {}: {}", relay, e),
+            }
+        }
+
+        (published, reached)
+    }
+
+    /// Lets `relay`, which has just connected, have our replaceable events
+    /// if it has been added in this session.
+    pub async fn relay_connected(&self, relay: &Url) {
+        if !self.0.fresh_relays.lock().unwrap().remove(relay) {
+            return;
+        }
 
-        query!("INSERT INTO textnotes (id, event) VALUES (?, ?)", id, json)
+        match self.republish_to(relay).await {
+            Ok(n) => tracing::info!("Published {} of our replaceable events to {}", n, relay),
+            Err(e) => tracing::warn!("Could not publish our events to {}: {}", relay, e),
+        }
+    }
+
+    /// Whether notes of `pubkey` are hidden.
+    pub async fn is_muted(&self, pubkey: XOnlyPublicKey) -> bool {
+        let pubkey = pubkey.serialize().to_vec();
+
+        query!("SELECT pubkey FROM muted_pubkeys WHERE pubkey = ?", pubkey)
+            .fetch_optional(self.pool())
+            .await
+            .ok()
+            .flatten()
+            .is_some()
+    }
+
+    /// Hides or shows again notes of `pubkey` and publishes updated mute list.
+    pub async fn set_muted(&self, pubkey: XOnlyPublicKey, muted: bool) -> Result<(), String> {
+        let now = Timestamp::now().as_i64();
+        self.store_muted(pubkey, muted, now).await?;
+        self.publish_mute_list().await.map(|_| ())
+    }
+
+    /// Users whose notes are hidden, from the earliest muted.
+    pub async fn muted_pubkeys(&self) -> Vec<XOnlyPublicKey> {
+        query!(r#"SELECT pubkey AS "pubkey!" FROM muted_pubkeys ORDER BY created"#)
+            .fetch_all(self.pool())
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|r| XOnlyPublicKey::from_slice(&r.pubkey).ok())
+            .collect()
+    }
+
+    /// Remembers that `pubkey` has been muted or unmuted at time `at`,
+    /// in seconds since epoch.
+    async fn store_muted(
+        &self,
+        pubkey: XOnlyPublicKey,
+        muted: bool,
+        at: i64,
+    ) -> Result<(), String> {
+        let pubkey = pubkey.serialize().to_vec();
+
+        if muted {
+            query!(
+                "INSERT INTO muted_pubkeys (pubkey, created) VALUES (?, datetime(?, 'unixepoch'))",
+                pubkey,
+                at
+            )
             .execute(self.pool())
             .await
-            .unwrap();
+            .map_err(|e| e.to_string())?;
+            query!("DELETE FROM unmuted_pubkeys WHERE pubkey = ?", pubkey)
+                .execute(self.pool())
+                .await
+        } else {
+            query!("DELETE FROM muted_pubkeys WHERE pubkey = ?", pubkey)
+                .execute(self.pool())
+                .await
+                .map_err(|e| e.to_string())?;
+            query!(
+                "INSERT INTO unmuted_pubkeys (pubkey, unmuted_at) VALUES (?, ?)",
+                pubkey,
+                at
+            )
+            .execute(self.pool())
+            .await
+        }
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+
+    /// Merges our mute list `event`, published possibly by another
+    /// client, into stored muted users. Whichever is newer wins: users
+    /// muted or unmuted here after the list was created stay so and the
+    /// merged list is then published. Returns users muted by the list
+    /// who have not been muted here, or `None` if `event` is not newer
+    /// than the list we know.
+    pub async fn merge_mute_list(
+        &self,
+        event: &Event,
+    ) -> Result<Option<Vec<XOnlyPublicKey>>, String> {
+        if let Some(known) = self.own_list(MUTE_LIST_KIND).await {
+            if known.created_at >= event.created_at {
+                return Ok(None);
+            }
+        }
+
+        self.store_own_list(event).await?;
+
+        let created_at = event.created_at.as_i64();
+        let listed = event.muted();
+        let mut newly_muted = Vec::new();
+
+        for pubkey in &listed {
+            let pk = pubkey.serialize().to_vec();
+
+            let unmuted_later = query!(
+                "SELECT pubkey FROM unmuted_pubkeys WHERE pubkey = ? AND unmuted_at >= ?",
+                pk,
+                created_at
+            )
+            .fetch_optional(self.pool())
+            .await
+            .map_err(|e| e.to_string())?
+            .is_some();
+
+            if !unmuted_later && !self.is_muted(*pubkey).await {
+                self.store_muted(*pubkey, true, created_at).await?;
+                newly_muted.push(*pubkey);
+            }
+        }
+
+        // Users who were muted before the list was created, but it does
+        // not contain them anymore.
+        let unmuted: Vec<XOnlyPublicKey> = query!(
+            r#"SELECT pubkey AS "pubkey!" FROM muted_pubkeys WHERE unixepoch(created) < ?"#,
+            created_at
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter_map(|r| XOnlyPublicKey::from_slice(&r.pubkey).ok())
+        .filter(|pk| !listed.contains(pk))
+        .collect();
+
+        for pubkey in unmuted {
+            self.store_muted(pubkey, false, created_at).await?;
+        }
+
+        let changed_here = query!(
+            r#"
+SELECT
+  (SELECT COUNT(*) FROM muted_pubkeys WHERE unixepoch(created) > ?1)
+  + (SELECT COUNT(*) FROM unmuted_pubkeys WHERE unmuted_at > ?1) AS "count!: i64"
+"#,
+            created_at
+        )
+        .fetch_one(self.pool())
+        .await
+        .map(|r| r.count)
+        .unwrap_or(0);
 
+        if changed_here > 0 {
+            self.publish_mute_list().await?;
+        }
+
+        Ok(Some(newly_muted))
+    }
+
+    /// Publishes NIP-51 mute list generated from stored muted users.
+    /// Everything else in the previous version of the list, including
+    /// encrypted private part, is published unchanged.
+    async fn publish_mute_list(&self) -> Result<EventId, String> {
+        let previous = self.own_list(MUTE_LIST_KIND).await;
+        let tags = nostr::mute_list_tags(&self.muted_pubkeys().await, previous.as_ref());
+        let content = previous.map(|e| e.content).unwrap_or_default();
+        let event = EventBuilder::new(Kind::Custom(MUTE_LIST_KIND), content, &tags)
+            .to_event(&self.client().keys())
+            .map_err(|e| e.to_string())?;
+
+        self.store_own_list(&event).await?;
+        self.outbox().publish(event).await
+    }
+
+    /// Publishes report of `subject` according to NIP-56 and remembers it.
+    /// If `hide` is set, the subject is not displayed anymore.
+    pub async fn report(
+        &self,
+        subject: ReportSubject,
+        report_type: ReportType,
+        comment: &str,
+        hide: bool,
+    ) -> Result<EventId, String> {
+        let builder = EventBuilder::new(
+            Kind::Custom(REPORT_KIND),
+            comment.trim(),
+            &subject.tags(report_type),
+        );
+        let id = self.publish(builder).await?;
+
+        let (target, author) = match subject {
+            ReportSubject::Note { event_id, author } => (event_id.as_bytes().to_vec(), author),
+            ReportSubject::User(pubkey) => (pubkey.serialize().to_vec(), pubkey),
+        };
+        // Notes of the reported author are hidden behind a bar from now on.
+        self.labels()
+            .add(author, labels::of_report(report_type))
+            .await?;
+        let report_type = report_type.as_str();
+
+        query!(
+            "INSERT INTO reported (target, report_type, hidden) VALUES (?, ?, ?)",
+            target,
+            report_type,
+            hide
+        )
+        .execute(self.pool())
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(id)
+    }
+
+    /// Whether `event`, or its author, has been reported and hidden.
+    pub async fn is_hidden_by_report(&self, event: &Event) -> bool {
         let id = event.id.as_bytes().to_vec();
-        let relay_str = relay.to_string();
+        let author = event.pubkey.serialize().to_vec();
 
         query!(
-            "INSERT INTO textnotes_relays (textnote, relay) VALUES (?, ?)",
+            "SELECT target FROM reported WHERE hidden AND target IN (?, ?)",
             id,
-            relay_str
+            author
+        )
+        .fetch_optional(self.pool())
+        .await
+        .ok()
+        .flatten()
+        .is_some()
+    }
+
+    /// Publishes deletion (NIP-09) of our note `event_id` and removes
+    /// the note from database.
+    pub async fn delete_note(&self, event_id: EventId) -> Result<(), String> {
+        self.publish(EventBuilder::delete::<String>(vec![event_id], None))
+            .await?;
+
+        let id = event_id.as_bytes().to_vec();
+        let mut tx = self.pool().begin().await.map_err(|e| e.to_string())?;
+        query!("DELETE FROM textnotes WHERE id = ?", id)
+            .execute(&mut tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        query!("DELETE FROM textnotes_relays WHERE textnote = ?", id)
+            .execute(&mut tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        query!("DELETE FROM hashtags WHERE event_id = ?", id)
+            .execute(&mut tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())
+    }
+
+    /// When relays have been first and last seen, and when they have last
+    /// delivered an event.
+    pub async fn relay_activity(&self) -> HashMap<Url, Activity> {
+        self.relay_stats().activity().await
+    }
+
+    /// Removes text notes that have expired according to NIP-40 and returns their IDs.
+    pub async fn purge_expired(&self) -> Vec<EventId> {
+        let mut tx = match self.pool().begin().await {
+            Ok(tx) => tx,
+            Err(_) => return vec![],
+        };
+
+        let ids = query!(
+            r#"
+DELETE FROM textnotes WHERE expiration <= unixepoch('now')
+RETURNING id AS "id!: Vec<u8>"
+"#
+        )
+        .fetch_all(&mut tx)
+        .await
+        .unwrap_or_default();
+
+        for record in &ids {
+            let _ = query!("DELETE FROM textnotes_relays WHERE textnote = ?", record.id)
+                .execute(&mut tx)
+                .await;
+            let _ = query!("DELETE FROM hashtags WHERE event_id = ?", record.id)
+                .execute(&mut tx)
+                .await;
+        }
+
+        match tx.commit().await {
+            Ok(()) => ids
+                .into_iter()
+                .filter_map(|r| EventId::from_slice(&r.id).ok())
+                .collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    /// Finds at most `limit` most recent text notes with hashtag `tag`.
+    pub async fn notes_with_hashtag(&self, tag: &str, limit: u32) -> Vec<Event> {
+        let tag = tag.trim_start_matches('#').to_lowercase();
+
+        query!(
+            r#"
+SELECT event AS "event!" FROM textnotes
+WHERE id IN (SELECT event_id FROM hashtags WHERE tag = ?)
+ORDER BY json_extract(event, '$.created_at') DESC
+LIMIT ?
+"#,
+            tag,
+            limit
+        )
+        .fetch_all(self.pool())
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|r| serde_json::from_str(&r.event).ok())
+        .collect()
+    }
+
+    /// Stores long-form `article`, unless a newer version of it is stored
+    /// already. Returns whether `article` is the latest known version.
+    pub async fn store_article(&self, article: &Article) -> bool {
+        let author = article.event.pubkey.serialize().to_vec();
+        let created_at = article.event.created_at.as_i64();
+        let json = match article.event.as_json() {
+            Ok(j) => j,
+            Err(_) => return false,
+        };
+
+        query!(
+            r#"
+INSERT INTO articles (author, identifier, created_at, event) VALUES (?, ?, ?, ?)
+ON CONFLICT (author, identifier) DO UPDATE SET
+  created_at = EXCLUDED.created_at,
+  event = EXCLUDED.event
+WHERE EXCLUDED.created_at > articles.created_at
+"#,
+            author,
+            article.identifier,
+            created_at,
+            json
         )
         .execute(self.pool())
         .await
-        .unwrap();
+        .map(|r| r.rows_affected() > 0)
+        .unwrap_or(false)
+    }
+
+    /// Stored text note `event_id`, if any.
+    pub async fn get_textnote(&self, event_id: EventId) -> Option<Event> {
+        let id: &[u8] = event_id.as_bytes();
+
+        query!(
+            r#"SELECT event AS "event!" FROM textnotes WHERE id = ?"#,
+            id
+        )
+        .fetch_optional(self.pool())
+        .await
+        .ok()
+        .flatten()
+        .and_then(|r| serde_json::from_str(&r.event).ok())
+    }
+
+    /// Stored text notes of `authors` created within `since` and `until`,
+    /// at most `limit` of the newest ones.
+    pub async fn textnotes_between(
+        &self,
+        authors: &[XOnlyPublicKey],
+        since: Timestamp,
+        until: Timestamp,
+        limit: u32,
+    ) -> Vec<Event> {
+        let authors =
+            serde_json::to_string(&authors.iter().map(|a| a.to_string()).collect::<Vec<_>>())
+                .unwrap();
+        let (since, until) = (since.as_i64(), until.as_i64());
+
+        query!(
+            r#"
+SELECT event AS "event!" FROM textnotes
+WHERE json_extract(event, '$.kind') = 1
+  AND json_extract(event, '$.pubkey') IN (SELECT value FROM json_each(?))
+  AND json_extract(event, '$.created_at') BETWEEN ? AND ?
+ORDER BY json_extract(event, '$.created_at') DESC
+LIMIT ?
+"#,
+            authors,
+            since,
+            until,
+            limit
+        )
+        .fetch_all(self.pool())
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|r| serde_json::from_str(&r.event).ok())
+        .collect()
+    }
+
+    /// Remembers that event `event_id` has been seen on `relay`. Returns
+    /// `true` if it has not been seen there before.
+    pub async fn store_seen_on(&self, event_id: EventId, relay: &Url) -> Result<bool, String> {
+        let id = event_id.as_bytes().to_vec();
+        let relay_str = relay.to_string();
+        let now = Timestamp::now().as_i64();
+
+        db::retry_on_busy(|| {
+            query!(
+                "INSERT INTO textnotes_relays (textnote, relay, seen_at) VALUES (?, ?, ?)",
+                id,
+                relay_str,
+                now
+            )
+            .execute(self.pool())
+        })
+        .await
+        .map(|r| r.rows_affected() > 0)
+        .map_err(|e| {
+            format!(
+                "Could not store relay of event {} into textnotes_relays: {}",
+                event_id, e
+            )
+        })
+    }
+
+    /// When the event was first seen on relays, for those where it is known.
+    pub async fn textnote_first_seen(&self, event_id: EventId) -> HashMap<Url, Timestamp> {
+        let id: &[u8] = event_id.as_bytes();
+
+        query!(
+            r#"
+SELECT relay, seen_at AS "seen_at!" FROM textnotes_relays
+WHERE textnote = ? AND seen_at IS NOT NULL"#,
+            id
+        )
+        .fetch_all(self.pool())
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|r| {
+            Some((
+                Url::parse(&r.relay).ok()?,
+                Timestamp::from(r.seen_at as u64),
+            ))
+        })
+        .collect()
     }
 
     pub async fn textnote_relays(&self, event_id: EventId) -> Vec<Url> {
@@ -114,7 +1929,12 @@ WHERE url IN (SELECT relay FROM textnotes_relays WHERE textnote = ?)"#,
 
         query!(
             r#"
-SELECT event, (unixepoch('now') - unixepoch(nip05_verified)) / 3600 AS "nip05_hours: u16"
+SELECT
+  event,
+  (unixepoch('now') - unixepoch(nip05_verified)) / 3600 AS "nip05_hours: u16",
+  unixepoch(nip05_verified) AS "nip05_verified: i64",
+  nip05_failed IS NOT NULL AS "nip05_failed!: bool",
+  nip05_mismatch AS "nip05_mismatch!: bool"
 FROM metadata
 WHERE author = ?
 "#,
@@ -132,10 +1952,15 @@ WHERE author = ?
                 })
                 .ok();
 
-            if matches!(record.nip05_hours, Some(h) if h < 5) {
-                if let Some(ref mut p) = persona {
-                    p.nip05_verified = true
-                }
+            if let Some(ref mut p) = persona {
+                p.nip05_verified = match (record.nip05_hours, record.nip05_verified) {
+                    (Some(h), Some(at)) if h < 5 => {
+                        Nip05Status::Verified(Timestamp::from(at as u64))
+                    }
+                    _ if record.nip05_failed && record.nip05_mismatch => Nip05Status::Mismatch,
+                    _ if record.nip05_failed => Nip05Status::Failed,
+                    _ => Nip05Status::Unknown,
+                };
             };
 
             persona
@@ -144,26 +1969,195 @@ WHERE author = ?
 }
 
 fn main() {
-    let app = RelmApp::new("com.jirijakes.gnostique");
-
-    // GTK and resources
-    gtk::glib::set_application_name("Gnostique");
-    gtk::gio::resources_register_include!("resources.gresource").unwrap();
-    let provider = gtk::CssProvider::new();
-    provider.load_from_resource("/com/jirijakes/gnostique/ui/style.css");
-    if let Some(display) = gtk::gdk::Display::default() {
-        gtk::StyleContext::add_provider_for_display(
-            &display,
-            &provider,
-            gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
-        )
+    use clap::Parser;
+
+    let cli = cli::Cli::parse();
+    let dirs = Dirs::new(cli.data_dir, cli.config);
+    let (config, config_error) = match config::Config::read(dirs.config_file()) {
+        Ok(config) => (config, None),
+        Err(e) => (config::Config::default(), Some(e)),
+    };
+    let _log_guard = app::logging::init_logging(&dirs, &config.logging);
+    if let Some(e) = config_error {
+        tracing::warn!("{}", e);
+    }
+
+    let setup = app::init::Setup {
+        dirs,
+        config,
+        relays: cli.relays,
+        debug: cli.debug,
     };
 
-    let icon_theme = gtk::IconTheme::for_display(&gtk::gdk::Display::default().unwrap());
-    icon_theme.add_resource_path("/com/jirijakes/gnostique/icons");
+    if let Some(cli::Command::DumpFeed(args)) = cli.command {
+        let result = tokio::runtime::Runtime::new()
+            .map_err(|e| e.to_string())
+            .and_then(|rt| rt.block_on(app::dump::dump_feed(&setup, &args)));
+
+        if let Err(e) = result {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    ui::run(setup);
+}
+
+#[cfg(test)]
+mod tests {
+    use nostr_sdk::nostr::{Keys, Tag};
+
+    use super::*;
+    use crate::testing::{Fixtures, TestDb};
+
+    fn contact_list(author: &Fixtures, follows: &[&Fixtures], created_at: u64) -> Event {
+        let tags: Vec<Tag> = follows
+            .iter()
+            .map(|f| Tag::PubKey(f.public_key(), None))
+            .collect();
+        author.sign(Kind::ContactList, "", &tags, Timestamp::from(created_at))
+    }
+
+    #[tokio::test]
+    async fn only_newest_contact_list_is_kept() {
+        let db = TestDb::new().await.unwrap();
+        let gnostique = db.gnostique(&Keys::generate());
+        let (alice, bob, carol) = (Fixtures::new(), Fixtures::new(), Fixtures::new());
+
+        assert_eq!(gnostique.following_of(alice.public_key()).await, None);
+
+        let first = contact_list(&alice, &[&bob], 100);
+        assert_eq!(gnostique.store_contact_list(&first).await, Ok(true));
+        let newer = contact_list(&alice, &[&carol, &carol], 200);
+        assert_eq!(gnostique.store_contact_list(&newer).await, Ok(true));
+        let older = contact_list(&alice, &[&bob, &carol], 150);
+        assert_eq!(gnostique.store_contact_list(&older).await, Ok(false));
+        assert_eq!(gnostique.store_contact_list(&newer).await, Ok(false));
+
+        assert_eq!(
+            gnostique.following_of(alice.public_key()).await,
+            Some(vec![carol.public_key()])
+        );
+        assert!(gnostique.followers_of(bob.public_key()).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn empty_contact_list_is_known() {
+        let db = TestDb::new().await.unwrap();
+        let gnostique = db.gnostique(&Keys::generate());
+        let alice = Fixtures::new();
+
+        let empty = contact_list(&alice, &[], 100);
+        assert_eq!(gnostique.store_contact_list(&empty).await, Ok(true));
+        assert_eq!(
+            gnostique.following_of(alice.public_key()).await,
+            Some(vec![])
+        );
+    }
+
+    #[tokio::test]
+    async fn followers_are_those_with_stored_lists() {
+        let db = TestDb::new().await.unwrap();
+        let gnostique = db.gnostique(&Keys::generate());
+        let (alice, bob, carol) = (Fixtures::new(), Fixtures::new(), Fixtures::new());
+
+        for event in [
+            contact_list(&alice, &[&carol], 100),
+            contact_list(&bob, &[&alice, &carol], 100),
+        ] {
+            gnostique.store_contact_list(&event).await.unwrap();
+        }
+
+        let mut followers = gnostique.followers_of(carol.public_key()).await;
+        followers.sort();
+        let mut expected = vec![alice.public_key(), bob.public_key()];
+        expected.sort();
+        assert_eq!(followers, expected);
+        assert_eq!(
+            gnostique.followers_of(alice.public_key()).await,
+            vec![bob.public_key()]
+        );
+    }
 
-    let settings = gtk::Settings::default().unwrap();
-    settings.set_gtk_application_prefer_dark_theme(true);
+    #[tokio::test]
+    async fn pins_of_newest_pin_list_come_in_its_order() {
+        let db = TestDb::new().await.unwrap();
+        let gnostique = db.gnostique(&Keys::generate());
+        let alice = Fixtures::new();
+        let (a, b, c) = (
+            alice.text_note("A"),
+            alice.text_note("B"),
+            alice.text_note("C"),
+        );
+        let pin_list = |pinned: &[&Event], created_at: u64| {
+            let tags: Vec<Tag> = pinned
+                .iter()
+                .map(|e| Tag::Event(e.id, None, None))
+                .collect();
+            alice.sign(
+                Kind::Custom(PIN_LIST_KIND),
+                "",
+                &tags,
+                Timestamp::from(created_at),
+            )
+        };
 
-    app.run::<crate::ui::app::App>(());
+        assert!(gnostique.pins_of(alice.public_key()).await.is_empty());
+
+        let list = pin_list(&[&c, &a], 200);
+        assert_eq!(gnostique.store_pin_list(&list).await, Ok(true));
+        let older = pin_list(&[&b], 100);
+        assert_eq!(gnostique.store_pin_list(&older).await, Ok(false));
+        assert_eq!(
+            gnostique.pins_of(alice.public_key()).await,
+            vec![c.id, a.id]
+        );
+
+        let newer = pin_list(&[&b, &c], 300);
+        assert_eq!(gnostique.store_pin_list(&newer).await, Ok(true));
+        assert_eq!(
+            gnostique.pins_of(alice.public_key()).await,
+            vec![b.id, c.id]
+        );
+    }
+
+    #[tokio::test]
+    async fn notes_of_period_are_newest_of_authors_within_it() {
+        let db = TestDb::new().await.unwrap();
+        let gnostique = db.gnostique(&Keys::generate());
+        let (alice, bob, carol) = (Fixtures::new(), Fixtures::new(), Fixtures::new());
+        let note = |author: &Fixtures, created_at: u64| {
+            author.sign(Kind::TextNote, "Hi", &[], Timestamp::from(created_at))
+        };
+
+        let notes = [
+            note(&alice, 99),
+            note(&alice, 100),
+            note(&bob, 150),
+            note(&carol, 160),
+            note(&alice, 200),
+            note(&bob, 201),
+        ];
+        for event in &notes {
+            gnostique.store_event(None, event).await.unwrap();
+        }
+        let reaction = alice.sign(Kind::Reaction, "+", &[], Timestamp::from(150));
+        gnostique.store_event(None, &reaction).await.unwrap();
+
+        let authors = [alice.public_key(), bob.public_key()];
+        let between = |limit| {
+            gnostique.textnotes_between(&authors, Timestamp::from(100), Timestamp::from(200), limit)
+        };
+        let ids = |events: Vec<Event>| events.iter().map(|e| e.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(between(10).await),
+            vec![notes[4].id, notes[2].id, notes[1].id]
+        );
+        assert_eq!(ids(between(2).await), vec![notes[4].id, notes[2].id]);
+        assert!(gnostique
+            .textnotes_between(&[], Timestamp::from(0), Timestamp::from(300), 10)
+            .await
+            .is_empty());
+    }
 }
@@ -0,0 +1,248 @@
+//! Markdown of long-form articles (NIP-23). Only what articles commonly use
+//! is understood: headings, paragraphs, lists, block quotes, code blocks,
+//! images, links and emphasis. Text of blocks is turned into Pango markup.
+
+use html_escape::{encode_double_quoted_attribute as attr, encode_text as text};
+use reqwest::Url;
+
+/// Block of article, displayed as a separate widget.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Block {
+    /// Heading of level 1 to 6, with markup.
+    Heading(u8, String),
+    /// Paragraph with markup.
+    Paragraph(String),
+    /// Item of bulleted or numbered list, with the bullet or number and markup.
+    ListItem { marker: String, text: String },
+    /// Block quote with markup.
+    Quote(String),
+    /// Code block, as plain text.
+    Code(String),
+    /// Image standing on its own line.
+    Image { url: Url, alt: String },
+    /// Horizontal rule.
+    Rule,
+}
+
+/// Splits `markdown` into blocks.
+pub fn parse(markdown: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    // Lines of paragraph or block quote being read.
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut quote: Vec<&str> = Vec::new();
+    // Lines of code block being read, if inside one.
+    let mut code: Option<Vec<&str>> = None;
+
+    fn flush(blocks: &mut Vec<Block>, lines: &mut Vec<&str>, quote: bool) {
+        if !lines.is_empty() {
+            let markup = inline(&lines.join(" "));
+            blocks.push(if quote {
+                Block::Quote(markup)
+            } else {
+                Block::Paragraph(markup)
+            });
+            lines.clear();
+        }
+    }
+
+    for line in markdown.lines() {
+        let trimmed = line.trim();
+
+        if let Some(lines) = &mut code {
+            if trimmed.starts_with("```") {
+                blocks.push(Block::Code(lines.join("\n")));
+                code = None;
+            } else {
+                lines.push(line);
+            }
+            continue;
+        }
+
+        if !trimmed.starts_with('>') {
+            flush(&mut blocks, &mut quote, true);
+        }
+
+        if trimmed.is_empty() {
+            flush(&mut blocks, &mut paragraph, false);
+        } else if trimmed.starts_with("```") {
+            flush(&mut blocks, &mut paragraph, false);
+            code = Some(Vec::new());
+        } else if let Some((level, heading)) = heading(trimmed) {
+            flush(&mut blocks, &mut paragraph, false);
+            blocks.push(Block::Heading(level, inline(heading)));
+        } else if is_rule(trimmed) {
+            flush(&mut blocks, &mut paragraph, false);
+            blocks.push(Block::Rule);
+        } else if let Some(quoted) = trimmed.strip_prefix('>') {
+            flush(&mut blocks, &mut paragraph, false);
+            quote.push(quoted.trim());
+        } else if let Some((marker, item)) = list_item(trimmed) {
+            flush(&mut blocks, &mut paragraph, false);
+            blocks.push(Block::ListItem {
+                marker,
+                text: inline(item),
+            });
+        } else if let Some((alt, url, len)) = link(trimmed.strip_prefix('!').unwrap_or_default()) {
+            if len + 1 == trimmed.len() {
+                flush(&mut blocks, &mut paragraph, false);
+                blocks.push(Block::Image {
+                    url,
+                    alt: alt.to_string(),
+                });
+            } else {
+                paragraph.push(trimmed);
+            }
+        } else {
+            paragraph.push(trimmed);
+        }
+    }
+
+    flush(&mut blocks, &mut quote, true);
+    flush(&mut blocks, &mut paragraph, false);
+    if let Some(lines) = code {
+        blocks.push(Block::Code(lines.join("\n")));
+    }
+
+    blocks
+}
+
+/// URLs of images that `blocks` display.
+pub fn images(blocks: &[Block]) -> Vec<Url> {
+    blocks
+        .iter()
+        .filter_map(|b| match b {
+            Block::Image { url, .. } => Some(url.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Level and text of heading such as `## Text`.
+fn heading(line: &str) -> Option<(u8, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let text = line[level..].strip_prefix(' ')?;
+
+    (1..=6)
+        .contains(&level)
+        .then_some((level as u8, text.trim()))
+}
+
+/// Whether `line` is horizontal rule, such as `---`.
+fn is_rule(line: &str) -> bool {
+    let mut chars = line.chars().filter(|c| !c.is_whitespace());
+    let first = chars.next();
+
+    matches!(first, Some('-' | '*' | '_'))
+        && chars.clone().all(|c| Some(c) == first)
+        && chars.count() >= 2
+}
+
+/// Marker and text of list item such as `- Text` or `2. Text`.
+fn list_item(line: &str) -> Option<(String, &str)> {
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(item) = line.strip_prefix(bullet) {
+            return Some(("•".to_string(), item.trim()));
+        }
+    }
+
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    let item = line[digits..].strip_prefix(". ")?;
+    (digits > 0).then(|| (format!("{}.", &line[..digits]), item.trim()))
+}
+
+/// Label and URL of link `[label](url)` at the beginning of `s`,
+/// together with length of the whole link.
+fn link(s: &str) -> Option<(&str, Url, usize)> {
+    let rest = s.strip_prefix('[')?;
+    let close = rest.find("](")?;
+    let label = &rest[..close];
+    let after = &rest[close + 2..];
+    let end = after.find(')')?;
+    let url: Url = after[..end].trim().parse().ok()?;
+
+    matches!(url.scheme(), "http" | "https" | "nostr").then_some((label, url, close + end + 4))
+}
+
+/// Converts inline Markdown of `s` into Pango markup.
+fn inline(s: &str) -> String {
+    let mut markup = String::new();
+    // Emphasis currently open, innermost last.
+    let mut open: Vec<&str> = Vec::new();
+    let mut rest = s;
+
+    while let Some(c) = rest.chars().next() {
+        // code
+        if let Some(code) = rest.strip_prefix('`') {
+            if let Some(end) = code.find('`') {
+                markup.push_str(&format!("<tt>{}</tt>", text(&code[..end])));
+                rest = &code[end + 1..];
+                continue;
+            }
+        }
+
+        // link, or image not standing on its own line, which is linked
+        let image = rest.starts_with("![");
+        if let Some((label, url, len)) = link(if image { &rest[1..] } else { rest }) {
+            let label = if label.is_empty() {
+                url.as_str()
+            } else {
+                label
+            };
+            markup.push_str(&format!(
+                r#"<a href="{}" title="{}">{}</a>"#,
+                attr(url.as_str()),
+                attr(url.as_str()),
+                text(label)
+            ));
+            rest = &rest[len + image as usize..];
+            continue;
+        }
+
+        // bare web link
+        if rest.starts_with("https://") || rest.starts_with("http://") {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let href = rest[..end].trim_end_matches(|c| ".,;:!?)".contains(c));
+            if let Ok(url) = href.parse::<Url>() {
+                markup.push_str(&format!(
+                    r#"<a href="{}">{}</a>"#,
+                    attr(url.as_str()),
+                    text(href)
+                ));
+                rest = &rest[href.len()..];
+                continue;
+            }
+        }
+
+        // emphasis
+        let (delimiter, tag) = if rest.starts_with("**") || rest.starts_with("__") {
+            (&rest[..2], "b")
+        } else if c == '*' {
+            (&rest[..1], "i")
+        } else {
+            ("", "")
+        };
+
+        if !delimiter.is_empty() {
+            if open.last() == Some(&tag) {
+                open.pop();
+                markup.push_str(&format!("</{tag}>"));
+                rest = &rest[delimiter.len()..];
+                continue;
+            } else if !open.contains(&tag) {
+                open.push(tag);
+                markup.push_str(&format!("<{tag}>"));
+                rest = &rest[delimiter.len()..];
+                continue;
+            }
+        }
+
+        markup.push_str(&text(&rest[..c.len_utf8()]));
+        rest = &rest[c.len_utf8()..];
+    }
+
+    while let Some(tag) = open.pop() {
+        markup.push_str(&format!("</{tag}>"));
+    }
+
+    markup
+}
@@ -0,0 +1,331 @@
+//! History of metadata of authors. Every distinct metadata we see is
+//! kept, not only the latest, so that changes of name, picture or NIP-05
+//! can be shown. An author who has just taken a name or NIP-05 of someone
+//! else is likely impersonating them.
+
+use nostr_sdk::prelude::{Event, Metadata, Timestamp, XOnlyPublicKey};
+use sqlx::{query, SqlitePool};
+
+/// Changes within this many days are warned about in profiles.
+pub const RECENT_DAYS: i64 = 30;
+
+/// Fields of `metadata` whose changes are shown, with how they are named.
+fn fields(metadata: &Metadata) -> [(&'static str, Option<String>); 3] {
+    [
+        ("name", metadata.name.clone()),
+        ("picture", metadata.picture.clone()),
+        ("NIP-05", metadata.nip05.clone()),
+    ]
+}
+
+/// One field of metadata which has changed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// Metadata published at `created_at`, with what they have changed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProfileChange {
+    pub created_at: Timestamp,
+    pub fields: Vec<FieldChange>,
+    /// Whether these are the first metadata of the author we know, which
+    /// set their fields rather than change them.
+    pub first: bool,
+}
+
+#[derive(Clone)]
+pub struct MetadataHistory {
+    pool: SqlitePool,
+}
+
+impl MetadataHistory {
+    pub fn new(pool: SqlitePool) -> MetadataHistory {
+        MetadataHistory { pool }
+    }
+
+    /// Adds metadata `event`, given also as `json`, to the history of its
+    /// author, unless it is there already.
+    pub async fn record(&self, event: &Event, json: &str) -> Result<(), String> {
+        let author = event.pubkey.serialize().to_vec();
+        let created_at = event.created_at.as_i64();
+        crate::db::retry_on_busy(|| {
+            query!(
+                r#"
+INSERT INTO metadata_history (author, created_at, event)
+VALUES (?, ?, ?)
+ON CONFLICT DO NOTHING
+"#,
+                author,
+                created_at,
+                json
+            )
+            .execute(&self.pool)
+        })
+        .await
+        .map_err(|e| {
+            format!(
+                "Could not store metadata of {} into history: {}",
+                event.pubkey, e
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Changes of name, picture and NIP-05 of `author`, newest first.
+    /// Metadata republished without changing any of them are left out.
+    pub async fn changes(&self, author: XOnlyPublicKey) -> Vec<ProfileChange> {
+        let author = author.serialize().to_vec();
+        let rows = query!(
+            "SELECT event FROM metadata_history WHERE author = ? ORDER BY created_at",
+            author
+        )
+        .fetch_all(&self.pool)
+        .await
+        .unwrap_or_default();
+
+        changes_of(rows.iter().filter_map(|r| {
+            let event = Event::from_json(&r.event).ok()?;
+            let metadata = Metadata::from_json(&event.content).ok()?;
+            Some((event.created_at, metadata))
+        }))
+    }
+
+    /// Fields among name and NIP-05 of `author` which have changed in the
+    /// last [`RECENT_DAYS`].
+    pub async fn recently_changed(&self, author: XOnlyPublicKey) -> Vec<&'static str> {
+        let since =
+            Timestamp::from((Timestamp::now().as_i64() - RECENT_DAYS * 24 * 60 * 60) as u64);
+        changed_since(&self.changes(author).await, since)
+    }
+}
+
+/// Changes between successive `versions` of metadata, given from the
+/// oldest with when they were published, newest first. Versions which
+/// change none of the shown fields are left out.
+fn changes_of(versions: impl IntoIterator<Item = (Timestamp, Metadata)>) -> Vec<ProfileChange> {
+    let mut changes = Vec::new();
+    let mut previous: Option<Metadata> = None;
+    for (created_at, metadata) in versions {
+        let before = previous.as_ref().map(fields);
+        let changed: Vec<FieldChange> = fields(&metadata)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, (field, after))| {
+                let before = before.as_ref().and_then(|b| b[i].1.clone());
+                (before != after).then_some(FieldChange {
+                    field,
+                    before,
+                    after,
+                })
+            })
+            .collect();
+
+        if !changed.is_empty() {
+            changes.push(ProfileChange {
+                created_at,
+                fields: changed,
+                first: previous.is_none(),
+            });
+        }
+        previous = Some(metadata);
+    }
+
+    changes.reverse();
+    changes
+}
+
+/// Fields among name and NIP-05 which `changes` have changed at or after
+/// `since`, in alphabetical order. Setting them for the first time is not
+/// a change.
+fn changed_since(changes: &[ProfileChange], since: Timestamp) -> Vec<&'static str> {
+    let mut fields: Vec<&'static str> = changes
+        .iter()
+        .filter(|c| !c.first && c.created_at >= since)
+        .flat_map(|c| c.fields.iter().map(|f| f.field))
+        .filter(|f| *f != "picture")
+        .collect();
+    fields.sort();
+    fields.dedup();
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use nostr_sdk::prelude::{Kind, Timestamp};
+
+    use super::*;
+    use crate::testing::{Fixtures, TestDb};
+
+    fn metadata(name: &str, picture: Option<&str>, nip05: Option<&str>) -> Metadata {
+        let mut metadata = Metadata::new().name(name);
+        metadata.picture = picture.map(String::from);
+        metadata.nip05 = nip05.map(String::from);
+        metadata
+    }
+
+    fn change(field: &'static str, before: Option<&str>, after: Option<&str>) -> FieldChange {
+        FieldChange {
+            field,
+            before: before.map(String::from),
+            after: after.map(String::from),
+        }
+    }
+
+    #[test]
+    fn changes_are_listed_from_the_newest() {
+        let versions = [
+            (Timestamp::from(10), metadata("alice", Some("a.png"), None)),
+            // Only the description has changed.
+            (
+                Timestamp::from(20),
+                metadata("alice", Some("a.png"), None).about("Hello"),
+            ),
+            (Timestamp::from(30), metadata("bob", Some("a.png"), None)),
+            (
+                Timestamp::from(40),
+                metadata("bob", None, Some("bob@example.com")),
+            ),
+        ];
+
+        assert_eq!(
+            changes_of(versions),
+            [
+                ProfileChange {
+                    created_at: Timestamp::from(40),
+                    fields: vec![
+                        change("picture", Some("a.png"), None),
+                        change("NIP-05", None, Some("bob@example.com")),
+                    ],
+                    first: false,
+                },
+                ProfileChange {
+                    created_at: Timestamp::from(30),
+                    fields: vec![change("name", Some("alice"), Some("bob"))],
+                    first: false,
+                },
+                ProfileChange {
+                    created_at: Timestamp::from(10),
+                    fields: vec![
+                        change("name", None, Some("alice")),
+                        change("picture", None, Some("a.png")),
+                    ],
+                    first: true,
+                },
+            ]
+        );
+        assert!(changes_of([]).is_empty());
+    }
+
+    #[test]
+    fn first_metadata_without_shown_fields_are_no_change() {
+        let empty = Metadata::new().about("Nothing else");
+        let changes = changes_of([
+            (Timestamp::from(10), empty),
+            (Timestamp::from(20), metadata("alice", None, None)),
+        ]);
+
+        assert_eq!(
+            changes,
+            [ProfileChange {
+                created_at: Timestamp::from(20),
+                fields: vec![change("name", None, Some("alice"))],
+                first: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn recent_changes_of_name_and_nip05_are_warned_about() {
+        let changes = changes_of([
+            (
+                Timestamp::from(10),
+                metadata("alice", None, Some("a@x.com")),
+            ),
+            (Timestamp::from(20), metadata("bob", None, Some("a@x.com"))),
+            (
+                Timestamp::from(30),
+                metadata("carol", Some("c.png"), Some("c@x.com")),
+            ),
+            (
+                Timestamp::from(40),
+                metadata("dave", Some("d.png"), Some("c@x.com")),
+            ),
+        ]);
+
+        assert_eq!(
+            changed_since(&changes, Timestamp::from(0)),
+            ["NIP-05", "name"]
+        );
+        // Changed at the very second.
+        assert_eq!(
+            changed_since(&changes, Timestamp::from(30)),
+            ["NIP-05", "name"]
+        );
+        assert_eq!(changed_since(&changes, Timestamp::from(31)), ["name"]);
+        assert!(changed_since(&changes, Timestamp::from(41)).is_empty());
+
+        // Pictures and the first known metadata are not warned about.
+        let set_once = changes_of([
+            (
+                Timestamp::from(10),
+                metadata("alice", None, Some("a@x.com")),
+            ),
+            (
+                Timestamp::from(20),
+                metadata("alice", Some("a.png"), Some("a@x.com")),
+            ),
+        ]);
+        assert!(changed_since(&set_once, Timestamp::from(0)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn history_is_read_in_order_of_publishing() {
+        let db = TestDb::in_memory().await.unwrap();
+        let history = MetadataHistory::new(db.pool().clone());
+        let alice = Fixtures::new();
+        let older = alice.metadata("alice", Timestamp::from(10));
+        let newer = alice.metadata("bob", Timestamp::from(20));
+        let unreadable = alice.sign(Kind::Metadata, "not json", &[], Timestamp::from(30));
+
+        // Newer first, the older one twice.
+        for event in [&newer, &older, &older, &unreadable] {
+            history
+                .record(event, &event.as_json().unwrap())
+                .await
+                .unwrap();
+        }
+
+        let rows: i64 = sqlx::query_scalar("SELECT count(*) FROM metadata_history")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(rows, 3);
+
+        assert_eq!(
+            history.changes(alice.public_key()).await,
+            [
+                ProfileChange {
+                    created_at: Timestamp::from(20),
+                    fields: vec![change("name", Some("alice"), Some("bob"))],
+                    first: false,
+                },
+                ProfileChange {
+                    created_at: Timestamp::from(10),
+                    fields: vec![change("name", None, Some("alice"))],
+                    first: true,
+                },
+            ]
+        );
+        assert!(history
+            .changes(Fixtures::new().public_key())
+            .await
+            .is_empty());
+        assert_eq!(
+            history.recently_changed(alice.public_key()).await,
+            Vec::<&str>::new()
+        );
+    }
+}
@@ -0,0 +1,450 @@
+//! Verification of NIP-05 identifiers, such as `bob@example.com`, against
+//! `/.well-known/nostr.json` of their domain. The document may also list
+//! relays of the user, which help finding their events.
+//!
+//! Failed verifications are retried less and less often. An identifier
+//! which leads to another pubkey is retried much later than one whose
+//! domain could not be reached.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use nostr_sdk::prelude::{Url, XOnlyPublicKey};
+use reqwest::Client;
+use serde::Deserialize;
+
+use crate::nostr::normalize_relay_url;
+
+/// At most this many relays of a user are taken from the document.
+const MAX_RELAYS: usize = 10;
+
+/// Documents larger than this are refused.
+const MAX_DOCUMENT_SIZE: usize = 64 * 1024;
+
+/// How long to wait for domain to provide the whole document.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Unreachable identifier is verified again after these many seconds,
+/// one for every failed attempt in a row, the last one from then on.
+const RETRY_UNREACHABLE: &[i64] = &[60 * 60, 6 * 60 * 60, 24 * 60 * 60];
+
+/// Identifier leading to another pubkey is verified again after this
+/// many seconds.
+const RETRY_MISMATCH: i64 = 7 * 24 * 60 * 60;
+
+/// Why identifier has not been verified.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Failure {
+    /// Its document could not be obtained, which may be temporary.
+    Unreachable(String),
+    /// It is invalid or it belongs to another pubkey.
+    Mismatch(String),
+}
+
+impl Failure {
+    pub fn is_mismatch(&self) -> bool {
+        matches!(self, Failure::Mismatch(_))
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Unreachable(e) => write!(f, "unreachable: {e}"),
+            Failure::Mismatch(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+/// How long after `attempts` failed attempts in a row, in seconds,
+/// identifier is verified again. `mismatch` tells whether the last of
+/// them has found the identifier to belong to someone else.
+pub fn retry_after(attempts: u32, mismatch: bool) -> i64 {
+    if mismatch {
+        return RETRY_MISMATCH;
+    }
+    let i = (attempts.max(1) as usize - 1).min(RETRY_UNREACHABLE.len() - 1);
+    RETRY_UNREACHABLE[i]
+}
+
+/// Whether identifier whose verification has last failed at `failed_at`
+/// should be verified again at `now`, both in seconds since epoch.
+pub fn retry_due(failed_at: i64, attempts: u32, mismatch: bool, now: i64) -> bool {
+    now - failed_at >= retry_after(attempts, mismatch)
+}
+
+/// Identifier split into its parts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Identifier {
+    /// Name at the domain, in lowercase.
+    pub local: String,
+    /// Domain in ASCII, internationalized domains in punycode.
+    pub domain: String,
+}
+
+/// What `/.well-known/nostr.json` says, only the parts we use.
+#[derive(Debug, Default, Deserialize)]
+struct WellKnown {
+    #[serde(default)]
+    names: HashMap<String, String>,
+    #[serde(default)]
+    relays: HashMap<String, Vec<String>>,
+}
+
+impl Identifier {
+    /// Parses `nip05`. A bare domain stands for its root identifier `_`.
+    /// Names may contain only `a-z0-9-_.`, case-insensitive.
+    pub fn parse(nip05: &str) -> Result<Identifier, Failure> {
+        let (local, domain) = match nip05.trim().rsplit_once('@') {
+            Some((local, domain)) => (local.to_lowercase(), domain),
+            None => ("_".to_string(), nip05.trim()),
+        };
+
+        let valid_local = !local.is_empty()
+            && local
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "-_.".contains(c));
+        if !valid_local {
+            return Err(Failure::Mismatch(format!("Invalid name in NIP-05 {nip05}")));
+        }
+
+        // Parsing a URL converts internationalized domain to punycode.
+        let domain = Url::parse(&format!("https://{domain}/"))
+            .ok()
+            .filter(|url| url.path() == "/" && url.port().is_none())
+            .and_then(|url| url.domain().map(|d| d.to_string()))
+            .filter(|d| d.contains('.'))
+            .ok_or_else(|| Failure::Mismatch(format!("Invalid domain in NIP-05 {nip05}")))?;
+
+        Ok(Identifier { local, domain })
+    }
+
+    /// Where the document verifying the identifier is.
+    pub fn well_known_url(&self) -> Url {
+        let mut url = Url::parse(&format!("https://{}/.well-known/nostr.json", self.domain))
+            .expect("Domain has been parsed already");
+        url.query_pairs_mut().append_pair("name", &self.local);
+        url
+    }
+
+    /// Checks that `json` document gives the name to `pubkey`, and returns
+    /// the relays it lists for them.
+    /// Broken document is taken for unreachable one.
+    pub fn check(&self, json: &str, pubkey: XOnlyPublicKey) -> Result<Vec<Url>, Failure> {
+        let well_known: WellKnown = serde_json::from_str(json)
+            .map_err(|e| Failure::Unreachable(format!("Invalid nostr.json: {e}")))?;
+
+        let named = well_known
+            .names
+            .iter()
+            .find(|(name, _)| name.to_lowercase() == self.local)
+            .map(|(_, hex)| hex.to_lowercase());
+        if named != Some(pubkey.to_string()) {
+            return Err(Failure::Mismatch(format!(
+                "{}@{} does not belong to {}",
+                self.local, self.domain, pubkey
+            )));
+        }
+
+        let mut relays: Vec<Url> = Vec::new();
+        let listed = well_known
+            .relays
+            .iter()
+            .find(|(hex, _)| hex.to_lowercase() == pubkey.to_string())
+            .map(|(_, relays)| relays.as_slice())
+            .unwrap_or_default();
+        for url in listed.iter().filter_map(|r| Url::parse(r).ok()) {
+            if let Some(url) = normalize_relay_url(&url) {
+                if !relays.contains(&url) {
+                    relays.push(url);
+                }
+            }
+        }
+        relays.truncate(MAX_RELAYS);
+
+        Ok(relays)
+    }
+}
+
+/// Verifies that `nip05` belongs to `pubkey` and returns relays listed
+/// for them.
+pub async fn verify(
+    http: &Client,
+    pubkey: XOnlyPublicKey,
+    nip05: &str,
+) -> Result<Vec<Url>, Failure> {
+    let identifier = Identifier::parse(nip05)?;
+    let json = fetch(http, identifier.well_known_url()).await?;
+
+    identifier.check(&json, pubkey)
+}
+
+/// Downloads document at `url`, unless it is larger than
+/// [`MAX_DOCUMENT_SIZE`] or it takes longer than [`TIMEOUT`].
+async fn fetch(http: &Client, url: Url) -> Result<String, Failure> {
+    let too_large = || Failure::Unreachable("nostr.json is too large".to_string());
+
+    let response = http
+        .get(url)
+        .timeout(TIMEOUT)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| Failure::Unreachable(e.to_string()))?;
+    if response.content_length().unwrap_or(0) > MAX_DOCUMENT_SIZE as u64 {
+        return Err(too_large());
+    }
+
+    // Length need not be told in advance.
+    let mut document = Vec::new();
+    let mut bytes = response.bytes_stream();
+    while let Some(chunk) = bytes.next().await {
+        let chunk = chunk.map_err(|e| Failure::Unreachable(e.to_string()))?;
+        if document.len() + chunk.len() > MAX_DOCUMENT_SIZE {
+            return Err(too_large());
+        }
+        document.extend_from_slice(&chunk);
+    }
+
+    String::from_utf8(document)
+        .map_err(|_| Failure::Unreachable("nostr.json is not UTF-8".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{http_response, Fixtures, StubServer};
+
+    fn bob() -> Identifier {
+        Identifier::parse("Bob@Example.com").unwrap()
+    }
+
+    fn relays(urls: &[&str]) -> Vec<Url> {
+        urls.iter().map(|u| Url::parse(u).unwrap()).collect()
+    }
+
+    #[test]
+    fn unreachable_identifiers_are_retried_less_and_less_often() {
+        let hour = 60 * 60;
+
+        // No failed attempt counts as one.
+        assert_eq!(retry_after(0, false), hour);
+        assert_eq!(retry_after(1, false), hour);
+        assert_eq!(retry_after(2, false), 6 * hour);
+        assert_eq!(retry_after(3, false), 24 * hour);
+        assert_eq!(retry_after(10, false), 24 * hour);
+        assert_eq!(retry_after(u32::MAX, false), 24 * hour);
+    }
+
+    #[test]
+    fn mismatched_identifiers_are_retried_after_a_week() {
+        for attempts in [0, 1, 2, 3, 10] {
+            assert_eq!(retry_after(attempts, true), 7 * 24 * 60 * 60, "{attempts}");
+        }
+    }
+
+    #[test]
+    fn retry_is_due_at_the_exact_second() {
+        let failed_at = 1_680_000_000;
+
+        for (attempts, mismatch) in [
+            (0, false),
+            (1, false),
+            (2, false),
+            (3, false),
+            (10, false),
+            (1, true),
+            (10, true),
+        ] {
+            let after = retry_after(attempts, mismatch);
+            assert!(
+                !retry_due(failed_at, attempts, mismatch, failed_at),
+                "{attempts} {mismatch}"
+            );
+            assert!(
+                !retry_due(failed_at, attempts, mismatch, failed_at + after - 1),
+                "{attempts} {mismatch}"
+            );
+            assert!(
+                retry_due(failed_at, attempts, mismatch, failed_at + after),
+                "{attempts} {mismatch}"
+            );
+            assert!(
+                retry_due(failed_at, attempts, mismatch, failed_at + after + 1),
+                "{attempts} {mismatch}"
+            );
+        }
+
+        // Unreachable is retried the next day, mismatch only a week later.
+        let next_day = failed_at + 24 * 60 * 60;
+        assert!(retry_due(failed_at, 3, false, next_day));
+        assert!(!retry_due(failed_at, 3, true, next_day));
+    }
+
+    #[test]
+    fn failures_tell_what_has_happened() {
+        let unreachable = Failure::Unreachable("timed out".to_string());
+        let mismatch = Failure::Mismatch("bob@example.com does not belong to alice".to_string());
+
+        assert!(!unreachable.is_mismatch());
+        assert!(mismatch.is_mismatch());
+        assert_eq!(unreachable.to_string(), "unreachable: timed out");
+        assert_eq!(
+            mismatch.to_string(),
+            "bob@example.com does not belong to alice"
+        );
+    }
+
+    #[test]
+    fn identifiers_are_parsed() {
+        assert_eq!(
+            bob(),
+            Identifier {
+                local: "bob".to_string(),
+                domain: "example.com".to_string()
+            }
+        );
+        assert_eq!(Identifier::parse(" example.com ").unwrap().local, "_");
+        assert_eq!(
+            Identifier::parse("bob@bücher.example").unwrap().domain,
+            "xn--bcher-kva.example"
+        );
+        assert_eq!(
+            bob().well_known_url().as_str(),
+            "https://example.com/.well-known/nostr.json?name=bob"
+        );
+
+        for invalid in [
+            "bob smith@example.com",
+            "@example.com",
+            "bob@localhost",
+            "bob@example.com:8080",
+            "bob@example.com/path",
+            "bob@",
+        ] {
+            assert!(
+                Identifier::parse(invalid).unwrap_err().is_mismatch(),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn names_are_case_insensitive() {
+        let pubkey = Fixtures::new().public_key();
+        let hex = pubkey.to_string().to_uppercase();
+
+        for json in [
+            format!(r#"{{"names": {{"bob": "{pubkey}"}}}}"#),
+            format!(r#"{{"names": {{"BOB": "{pubkey}"}}}}"#),
+            format!(r#"{{"names": {{"Bob": "{hex}"}}}}"#),
+        ] {
+            assert_eq!(bob().check(&json, pubkey), Ok(vec![]), "{json}");
+        }
+    }
+
+    #[test]
+    fn names_of_others_do_not_verify() {
+        let (pubkey, other) = (Fixtures::new().public_key(), Fixtures::new().public_key());
+
+        for json in [
+            format!(r#"{{"names": {{"bob": "{other}"}}}}"#),
+            format!(r#"{{"names": {{"alice": "{pubkey}"}}}}"#),
+            "{}".to_string(),
+        ] {
+            assert_eq!(
+                bob().check(&json, pubkey),
+                Err(Failure::Mismatch(format!(
+                    "bob@example.com does not belong to {pubkey}"
+                ))),
+                "{json}"
+            );
+        }
+
+        assert!(matches!(
+            bob().check("<html>Not found</html>", pubkey),
+            Err(Failure::Unreachable(_))
+        ));
+    }
+
+    #[test]
+    fn relays_are_normalized_once() {
+        let pubkey = Fixtures::new().public_key();
+        let other = Fixtures::new().public_key();
+        let json = format!(
+            r#"{{
+                "names": {{"bob": "{pubkey}"}},
+                "relays": {{
+                    "{}": ["wss://Relay.Example.com/", "wss://relay.example.com", "https://web.example.com", "not a url", "wss://other.example.com:443"],
+                    "{other}": ["wss://not-bobs.example.com"]
+                }}
+            }}"#,
+            pubkey.to_string().to_uppercase()
+        );
+
+        assert_eq!(
+            bob().check(&json, pubkey),
+            Ok(relays(&[
+                "wss://relay.example.com/",
+                "wss://other.example.com/"
+            ]))
+        );
+    }
+
+    #[test]
+    fn relays_are_limited() {
+        let pubkey = Fixtures::new().public_key();
+        let listed: Vec<String> = (0..15)
+            .map(|i| format!(r#""wss://relay{i}.example.com""#))
+            .collect();
+        let json = format!(
+            r#"{{"names": {{"bob": "{pubkey}"}}, "relays": {{"{pubkey}": [{}]}}}}"#,
+            listed.join(", ")
+        );
+
+        let found = bob().check(&json, pubkey).unwrap();
+        assert_eq!(found.len(), MAX_RELAYS);
+        assert_eq!(found[0].as_str(), "wss://relay0.example.com/");
+    }
+
+    #[tokio::test]
+    async fn documents_are_fetched() {
+        let server = StubServer::always(http_response("200 OK", &[], br#"{"names": {}}"#)).await;
+        assert_eq!(
+            fetch(&Client::new(), server.url("/.well-known/nostr.json")).await,
+            Ok(r#"{"names": {}}"#.to_string())
+        );
+
+        let server = StubServer::always(http_response("404 Not Found", &[], b"")).await;
+        assert!(matches!(
+            fetch(&Client::new(), server.url("/")).await,
+            Err(Failure::Unreachable(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn large_documents_are_refused() {
+        let large = vec![b' '; MAX_DOCUMENT_SIZE + 1];
+        let too_large = Err(Failure::Unreachable("nostr.json is too large".to_string()));
+
+        let server = StubServer::always(http_response("200 OK", &[], &large)).await;
+        assert_eq!(fetch(&Client::new(), server.url("/")).await, too_large);
+
+        // Without its length told in advance.
+        let mut response = b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n".to_vec();
+        response.extend_from_slice(&large);
+        let server = StubServer::always(response).await;
+        assert_eq!(fetch(&Client::new(), server.url("/")).await, too_large);
+
+        let fits = vec![b' '; MAX_DOCUMENT_SIZE];
+        let server = StubServer::always(http_response("200 OK", &[], &fits)).await;
+        assert_eq!(
+            fetch(&Client::new(), server.url("/"))
+                .await
+                .map(|d| d.len()),
+            Ok(MAX_DOCUMENT_SIZE)
+        );
+    }
+}
@@ -0,0 +1,358 @@
+//! Events that concern us: mentions of us, replies, reactions and zaps
+//! to our notes and new followers.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use nostr_sdk::prelude::*;
+use sqlx::{query, SqlitePool};
+
+use crate::nostr::{EventExt, ZAP_RECEIPT_KIND};
+
+/// Content of mentions and replies is kept only up to this length.
+const MAX_SNIPPET_LEN: usize = 140;
+
+/// Why an event concerns us.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotificationKind {
+    /// We are tagged in the event.
+    Mention,
+    /// The event replies to our note.
+    Reply,
+    /// The event reacts to our note.
+    Reaction,
+    /// We, or our note, have been zapped.
+    Zap,
+    /// Author of the event has started following us.
+    Follow,
+}
+
+impl NotificationKind {
+    fn as_i64(self) -> i64 {
+        match self {
+            NotificationKind::Mention => 0,
+            NotificationKind::Reply => 1,
+            NotificationKind::Reaction => 2,
+            NotificationKind::Zap => 3,
+            NotificationKind::Follow => 4,
+        }
+    }
+
+    fn from_i64(kind: i64) -> Option<NotificationKind> {
+        match kind {
+            0 => Some(NotificationKind::Mention),
+            1 => Some(NotificationKind::Reply),
+            2 => Some(NotificationKind::Reaction),
+            3 => Some(NotificationKind::Zap),
+            4 => Some(NotificationKind::Follow),
+            _ => None,
+        }
+    }
+}
+
+/// Something that has happened to us.
+#[derive(Clone, Debug)]
+pub struct Notification {
+    pub kind: NotificationKind,
+    pub event_id: EventId,
+    /// Our event that the notification relates to, e. g. the reacted note.
+    pub target: Option<EventId>,
+    /// Who caused the notification. For zaps it is the zapper, not the
+    /// issuer of the receipt.
+    pub author: XOnlyPublicKey,
+    pub author_name: Option<String>,
+    /// Reaction, zapped amount in sats or snippet of the note.
+    pub content: String,
+    pub created_at: Timestamp,
+    pub read: bool,
+}
+
+impl Notification {
+    /// Event that should be shown when user wants to see more.
+    pub fn thread(&self) -> Option<EventId> {
+        match self.kind {
+            NotificationKind::Mention | NotificationKind::Reply => Some(self.event_id),
+            NotificationKind::Reaction | NotificationKind::Zap => self.target,
+            NotificationKind::Follow => None,
+        }
+    }
+
+    /// Short human description, such as "alice reacted ❤ to your note".
+    pub fn describe(&self) -> String {
+        let name = self
+            .author_name
+            .clone()
+            .unwrap_or_else(|| self.author.to_string().chars().take(12).collect());
+
+        match self.kind {
+            NotificationKind::Mention => format!("{name} mentioned you: {}", self.content),
+            NotificationKind::Reply => format!("{name} replied: {}", self.content),
+            NotificationKind::Reaction => {
+                let reaction = match self.content.as_str() {
+                    "+" | "" => "❤",
+                    "-" => "👎",
+                    r => r,
+                };
+                format!("{name} reacted {reaction} to your note")
+            }
+            NotificationKind::Zap if self.target.is_some() => {
+                format!("{name} zapped your note ⚡ {} sats", self.content)
+            }
+            NotificationKind::Zap => format!("{name} zapped you ⚡ {} sats", self.content),
+            NotificationKind::Follow => format!("{name} started following you"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Notifications(Arc<NotificationsInner>);
+
+struct NotificationsInner {
+    pool: SqlitePool,
+    /// Whether desktop notifications are shown.
+    enabled: AtomicBool,
+}
+
+impl Notifications {
+    pub fn new(pool: SqlitePool) -> Notifications {
+        Notifications(Arc::new(NotificationsInner {
+            pool,
+            enabled: AtomicBool::new(true),
+        }))
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.0.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.0.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Finds out whether `event` concerns `me`. Our own events never do.
+    /// Name of the author is not filled in.
+    pub async fn detect(&self, me: XOnlyPublicKey, event: &Event) -> Option<Notification> {
+        let author = event.zapper().unwrap_or(event.pubkey);
+        if author == me {
+            return None;
+        }
+
+        let (kind, target, content) = match event.kind {
+            Kind::TextNote => {
+                let snippet = snippet(&event.content);
+                match event.replies_to() {
+                    Some(parent) if self.is_ours(me, parent).await => {
+                        (NotificationKind::Reply, Some(parent), snippet)
+                    }
+                    _ if event.mentions().contains(&me) => {
+                        (NotificationKind::Mention, None, snippet)
+                    }
+                    _ => return None,
+                }
+            }
+            Kind::Reaction => {
+                let target = event.reacts_to()?;
+                if !self.is_ours(me, target).await {
+                    return None;
+                }
+                (
+                    NotificationKind::Reaction,
+                    Some(target),
+                    event.content.clone(),
+                )
+            }
+            Kind::Custom(ZAP_RECEIPT_KIND) if event.mentions().contains(&me) => {
+                let target = event.zapped_event();
+                let amount = event.zap_amount().unwrap_or_default();
+                (NotificationKind::Zap, target, amount.to_string())
+            }
+            Kind::ContactList if event.mentions().contains(&me) => {
+                (NotificationKind::Follow, None, String::new())
+            }
+            _ => return None,
+        };
+
+        Some(Notification {
+            kind,
+            event_id: event.id,
+            target,
+            author,
+            author_name: None,
+            content,
+            created_at: event.created_at,
+            read: false,
+        })
+    }
+
+    /// Remembers `notification`. Returns `false` if it has already happened
+    /// before, i. e. the same event or, for followers, the same author.
+    pub async fn record(&self, notification: &Notification) -> bool {
+        let id = notification.event_id.as_bytes().to_vec();
+        let author = notification.author.serialize().to_vec();
+        let kind = notification.kind.as_i64();
+        let target = notification.target.map(|t| t.as_bytes().to_vec());
+        let created_at = notification.created_at.as_i64();
+
+        if notification.kind == NotificationKind::Follow {
+            let known = query!(
+                "SELECT event_id FROM notifications WHERE kind = ? AND author = ?",
+                kind,
+                author
+            )
+            .fetch_optional(&self.0.pool)
+            .await
+            .ok()
+            .flatten();
+
+            if known.is_some() {
+                return false;
+            }
+        }
+
+        query!(
+            r#"
+INSERT INTO notifications (event_id, kind, author, target, content, created_at)
+VALUES (?, ?, ?, ?, ?, ?)
+"#,
+            id,
+            kind,
+            author,
+            target,
+            notification.content,
+            created_at
+        )
+        .execute(&self.0.pool)
+        .await
+        .map(|r| r.rows_affected() > 0)
+        .unwrap_or(false)
+    }
+
+    /// Latest `limit` notifications, from the newest.
+    pub async fn history(&self, limit: u32) -> Vec<Notification> {
+        query!(
+            r#"
+SELECT n.event_id AS "event_id!", n.kind, n.author, n.target, n.content, n.created_at, n.read AS "read: bool",
+  json_extract(json_extract(m.event, '$.content'), '$.name') AS "name?: String"
+FROM notifications n LEFT JOIN metadata m ON m.author = n.author
+ORDER BY n.created_at DESC
+LIMIT ?
+"#,
+            limit
+        )
+        .fetch_all(&self.0.pool)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|r| {
+            Some(Notification {
+                kind: NotificationKind::from_i64(r.kind)?,
+                event_id: EventId::from_slice(&r.event_id).ok()?,
+                target: r.target.and_then(|t| EventId::from_slice(&t).ok()),
+                author: XOnlyPublicKey::from_slice(&r.author).ok()?,
+                author_name: r.name,
+                content: r.content,
+                created_at: Timestamp::from(r.created_at as u64),
+                read: r.read,
+            })
+        })
+        .collect()
+    }
+
+    /// Number of notifications user has not seen yet.
+    pub async fn unread_count(&self) -> u32 {
+        query!(r#"SELECT COUNT(*) AS "count: u32" FROM notifications WHERE read = 0"#)
+            .fetch_one(&self.0.pool)
+            .await
+            .map(|r| r.count)
+            .unwrap_or_default()
+    }
+
+    /// Marks notification about `event_id` as seen.
+    pub async fn mark_read(&self, event_id: EventId) {
+        let id = event_id.as_bytes().to_vec();
+
+        let _ = query!("UPDATE notifications SET read = 1 WHERE event_id = ?", id)
+            .execute(&self.0.pool)
+            .await;
+    }
+
+    /// Marks all notifications as seen.
+    pub async fn mark_all_read(&self) {
+        let _ = query!("UPDATE notifications SET read = 1 WHERE read = 0")
+            .execute(&self.0.pool)
+            .await;
+    }
+
+    /// Whether events of `author` should be announced by desktop notification.
+    pub async fn should_notify(&self, author: XOnlyPublicKey) -> bool {
+        self.enabled() && !self.is_muted(author).await
+    }
+
+    pub async fn is_muted(&self, author: XOnlyPublicKey) -> bool {
+        let author = author.serialize().to_vec();
+
+        query!(
+            "SELECT author FROM notification_mutes WHERE author = ?",
+            author
+        )
+        .fetch_optional(&self.0.pool)
+        .await
+        .ok()
+        .flatten()
+        .is_some()
+    }
+
+    /// Authors whose events are not announced by desktop notifications.
+    pub async fn muted(&self) -> Vec<XOnlyPublicKey> {
+        query!(r#"SELECT author AS "author!" FROM notification_mutes"#)
+            .fetch_all(&self.0.pool)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|r| XOnlyPublicKey::from_slice(&r.author).ok())
+            .collect()
+    }
+
+    /// Stops or resumes desktop notifications about events of `author`.
+    pub async fn set_muted(&self, author: XOnlyPublicKey, muted: bool) -> Result<(), String> {
+        let author = author.serialize().to_vec();
+
+        if muted {
+            query!("INSERT INTO notification_mutes (author) VALUES (?)", author)
+                .execute(&self.0.pool)
+                .await
+        } else {
+            query!("DELETE FROM notification_mutes WHERE author = ?", author)
+                .execute(&self.0.pool)
+                .await
+        }
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+
+    /// Whether `event_id` is our text note.
+    async fn is_ours(&self, me: XOnlyPublicKey, event_id: EventId) -> bool {
+        let id = event_id.as_bytes().to_vec();
+
+        let author = query!(
+            r#"SELECT json_extract(event, '$.pubkey') AS "pubkey: String" FROM textnotes WHERE id = ?"#,
+            id
+        )
+        .fetch_optional(&self.0.pool)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|r| r.pubkey);
+
+        author == Some(me.to_string())
+    }
+}
+
+/// Beginning of `content`, to be displayed in notification.
+fn snippet(content: &str) -> String {
+    let mut snippet: String = content.chars().take(MAX_SNIPPET_LEN).collect();
+    if content.chars().count() > MAX_SNIPPET_LEN {
+        snippet.push('…');
+    }
+    snippet
+}
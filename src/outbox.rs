@@ -0,0 +1,554 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use nostr_sdk::prelude::*;
+use nostr_sdk::relay::RelayStatus;
+use nostr_sdk::Client;
+use sqlx::{query, SqlitePool};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+/// Delivered events are forgotten after this many days.
+const RETENTION_DAYS: u32 = 7;
+
+/// Longest delay between two delivery attempts, in minutes.
+const MAX_BACKOFF_MINUTES: u32 = 60;
+
+/// At most this many published events wait to be displayed.
+const PUBLISHED_CAPACITY: usize = 16;
+
+/// Events published by us. Every event is first written into database
+/// and only then sent to relays, so that it can be sent again if
+/// no relay accepts it. Published events are also announced locally,
+/// to be displayed without waiting for relays to send them back.
+#[derive(Clone)]
+pub struct Outbox(Arc<OutboxInner>);
+
+struct OutboxInner {
+    pool: SqlitePool,
+    client: Client,
+    /// Events sent to relays in this session, which have not responded yet.
+    awaiting: Mutex<HashSet<(EventId, Url)>>,
+    /// Events published in this session, as they are published.
+    published: broadcast::Sender<Event>,
+}
+
+/// Event that has not been accepted by any relay yet.
+#[derive(Clone, Debug)]
+pub struct PendingEvent {
+    pub event: Event,
+    pub attempts: u32,
+    pub deliveries: Vec<Delivery>,
+}
+
+/// Response of a relay to a published event.
+#[derive(Clone, Debug)]
+pub struct Delivery {
+    pub relay: Url,
+    /// Whether the relay accepted the event, `None` if it has not responded yet.
+    pub accepted: Option<bool>,
+    /// Explanation that came with the response, e.g. `rate-limited`.
+    pub message: Option<String>,
+}
+
+impl std::fmt::Display for Delivery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self.accepted {
+            None => "⏳",
+            Some(true) => "✅",
+            Some(false) => "❌",
+        };
+
+        match &self.message {
+            Some(m) => write!(f, "{status} {}: {m}", self.relay),
+            None => write!(f, "{status} {}", self.relay),
+        }
+    }
+}
+
+impl Outbox {
+    pub fn new(pool: SqlitePool, client: Client) -> Outbox {
+        Outbox(Arc::new(OutboxInner {
+            pool,
+            client,
+            awaiting: Default::default(),
+            published: broadcast::channel(PUBLISHED_CAPACITY).0,
+        }))
+    }
+
+    /// Receives events from now on, as soon as they are stored into
+    /// outbox, before any relay has accepted them.
+    pub fn published(&self) -> broadcast::Receiver<Event> {
+        self.0.published.subscribe()
+    }
+
+    /// Stores `event` into outbox and sends it to all relays we write to.
+    pub async fn publish(&self, event: Event) -> Result<EventId, String> {
+        let id = event.id.as_bytes().to_vec();
+        let json = event.as_json().map_err(|e| e.to_string())?;
+        let relays: Vec<Url> = self
+            .0
+            .client
+            .relays()
+            .await
+            .into_iter()
+            .filter(|(_, r)| r.opts().write())
+            .map(|(url, _)| url)
+            .collect();
+
+        let mut tx = self.0.pool.begin().await.map_err(|e| e.to_string())?;
+
+        query!(
+            "INSERT INTO outbox (event_id, event) VALUES (?, ?)",
+            id,
+            json
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        for relay in &relays {
+            let relay_s = relay.to_string();
+            query!(
+                "INSERT INTO outbox_relays (event_id, relay) VALUES (?, ?)",
+                id,
+                relay_s
+            )
+            .execute(&mut tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        // Nobody may be listening yet.
+        let _ = self.0.published.send(event.clone());
+        self.deliver(&event, &relays).await;
+
+        Ok(event.id)
+    }
+
+    /// Sends `event`, possibly published before, to `relays` only. The
+    /// event is kept in outbox until they accept it.
+    pub async fn publish_to(&self, event: &Event, relays: &[Url]) -> Result<(), String> {
+        let id = event.id.as_bytes().to_vec();
+        let json = event.as_json().map_err(|e| e.to_string())?;
+
+        let mut tx = self.0.pool.begin().await.map_err(|e| e.to_string())?;
+
+        query!(
+            "INSERT INTO outbox (event_id, event) VALUES (?, ?) ON CONFLICT DO NOTHING",
+            id,
+            json
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        for relay in relays {
+            let relay_s = relay.to_string();
+            query!(
+                "INSERT INTO outbox_relays (event_id, relay) VALUES (?, ?)",
+                id,
+                relay_s
+            )
+            .execute(&mut tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        self.deliver(event, relays).await;
+
+        Ok(())
+    }
+
+    /// Records response of `relay` to event `event_id`. Events that did not
+    /// come from the outbox are ignored. Returns whether it is the first
+    /// relay that has accepted the event.
+    pub async fn acknowledge(
+        &self,
+        relay: &Url,
+        event_id: EventId,
+        accepted: bool,
+        message: &str,
+    ) -> bool {
+        self.0
+            .awaiting
+            .lock()
+            .unwrap()
+            .remove(&(event_id, relay.clone()));
+
+        let id = event_id.as_bytes().to_vec();
+        let relay_s = relay.to_string();
+        let message = Some(message).filter(|m| !m.is_empty());
+
+        let updated = query!(
+            r#"
+UPDATE outbox_relays SET accepted = ?, message = ?, responded = CURRENT_TIMESTAMP
+WHERE event_id = ? AND relay = ?
+"#,
+            accepted,
+            message,
+            id,
+            relay_s
+        )
+        .execute(&self.0.pool)
+        .await;
+
+        match updated {
+            Ok(r) if r.rows_affected() > 0 => {
+                info!(
+                    "Relay {} {} event {}",
+                    relay,
+                    if accepted { "accepted" } else { "rejected" },
+                    event_id
+                );
+            }
+            Ok(_) => return false,
+            Err(e) => {
+                warn!(
+                    "Could not record response of {} to {}: {}",
+                    relay, event_id, e
+                );
+                return false;
+            }
+        }
+
+        if !accepted {
+            return false;
+        }
+        query!(
+            "UPDATE outbox SET delivered = CURRENT_TIMESTAMP WHERE event_id = ? AND delivered IS NULL",
+            id
+        )
+        .execute(&self.0.pool)
+        .await
+        .map(|r| r.rows_affected() > 0)
+        .unwrap_or_default()
+    }
+
+    /// Whether event `event_id` is in outbox and no relay has accepted it yet.
+    pub async fn is_pending(&self, event_id: EventId) -> bool {
+        let id = event_id.as_bytes().to_vec();
+        query!(
+            "SELECT event_id FROM outbox WHERE event_id = ? AND delivered IS NULL",
+            id
+        )
+        .fetch_optional(&self.0.pool)
+        .await
+        .map(|r| r.is_some())
+        .unwrap_or_default()
+    }
+
+    /// Sends again all undelivered events whose time has come. If `relay`
+    /// is given, the events are sent only to that relay, regardless of
+    /// the time of their next attempt. It is useful when the relay has
+    /// just reconnected.
+    pub async fn retry(&self, relay: Option<&Url>) {
+        let force = relay.is_some();
+        let relay_s = relay.map(|r| r.to_string());
+
+        let due = query!(
+            r#"
+SELECT event AS "event!" FROM outbox
+WHERE delivered IS NULL AND (? OR unixepoch(next_attempt) <= unixepoch('now'))
+"#,
+            force
+        )
+        .fetch_all(&self.0.pool)
+        .await
+        .unwrap_or_default();
+
+        let connected = self.connected_relays().await;
+
+        for record in due {
+            let event = match Event::from_json(&record.event) {
+                Ok(e) => e,
+                Err(_) => continue,
+            };
+
+            let relays: Vec<Url> = self
+                .waiting_relays(event.id)
+                .await
+                .into_iter()
+                .filter(|r| connected.contains(r))
+                .filter(|r| relay_s.is_none() || relay_s == Some(r.to_string()))
+                .collect();
+
+            if !relays.is_empty() {
+                self.deliver(&event, &relays).await;
+            }
+        }
+    }
+
+    /// Sends event `event_id` again to all relays we write to that have not accepted it.
+    pub async fn retry_now(&self, event_id: EventId) {
+        let id = event_id.as_bytes().to_vec();
+
+        let record = query!(
+            r#"SELECT event AS "event!" FROM outbox WHERE event_id = ?"#,
+            id
+        )
+        .fetch_optional(&self.0.pool)
+        .await
+        .ok()
+        .flatten();
+
+        if let Some(event) = record.and_then(|r| Event::from_json(r.event).ok()) {
+            // Rejecting relays get another chance.
+            let _ = query!(
+                "UPDATE outbox_relays SET accepted = NULL, message = NULL WHERE event_id = ? AND accepted = 0",
+                id
+            )
+            .execute(&self.0.pool)
+            .await;
+
+            let writable = self.0.client.relays().await;
+            let relays: Vec<Url> = self
+                .waiting_relays(event_id)
+                .await
+                .into_iter()
+                .filter(|r| writable.get(r).map(|r| r.opts().write()).unwrap_or(false))
+                .collect();
+            self.deliver(&event, &relays).await;
+        }
+    }
+
+    /// Removes event `event_id` from outbox, it will not be sent anymore.
+    pub async fn discard(&self, event_id: EventId) {
+        let id = event_id.as_bytes().to_vec();
+
+        let _ = query!("DELETE FROM outbox_relays WHERE event_id = ?", id)
+            .execute(&self.0.pool)
+            .await;
+        let _ = query!("DELETE FROM outbox WHERE event_id = ?", id)
+            .execute(&self.0.pool)
+            .await;
+    }
+
+    /// Forgets events delivered long time ago.
+    pub async fn cleanup(&self) {
+        let older_than = format!("-{RETENTION_DAYS} days");
+
+        let _ = query!(
+            r#"
+DELETE FROM outbox_relays WHERE event_id IN
+  (SELECT event_id FROM outbox WHERE delivered < datetime('now', ?))
+"#,
+            older_than
+        )
+        .execute(&self.0.pool)
+        .await;
+
+        let _ = query!(
+            "DELETE FROM outbox WHERE delivered < datetime('now', ?)",
+            older_than
+        )
+        .execute(&self.0.pool)
+        .await;
+    }
+
+    /// Number of deliveries in this session to which relays have not responded yet.
+    pub fn awaiting(&self) -> usize {
+        self.0.awaiting.lock().unwrap().len()
+    }
+
+    /// Number of events not yet accepted by any relay.
+    pub async fn pending_count(&self) -> u32 {
+        query!(r#"SELECT COUNT(*) AS "count: u32" FROM outbox WHERE delivered IS NULL"#)
+            .fetch_one(&self.0.pool)
+            .await
+            .map(|r| r.count)
+            .unwrap_or_default()
+    }
+
+    /// Events not yet accepted by any relay, from the oldest.
+    pub async fn pending(&self) -> Vec<PendingEvent> {
+        let records = query!(
+            r#"
+SELECT event AS "event!", attempts AS "attempts: u32" FROM outbox
+WHERE delivered IS NULL
+ORDER BY created
+"#
+        )
+        .fetch_all(&self.0.pool)
+        .await
+        .unwrap_or_default();
+
+        let mut pending = Vec::with_capacity(records.len());
+
+        for record in records {
+            if let Ok(event) = Event::from_json(&record.event) {
+                pending.push(PendingEvent {
+                    deliveries: self.deliveries(event.id).await,
+                    attempts: record.attempts,
+                    event,
+                });
+            }
+        }
+
+        pending
+    }
+
+    /// Responses of relays to event `event_id`.
+    pub async fn deliveries(&self, event_id: EventId) -> Vec<Delivery> {
+        let id = event_id.as_bytes().to_vec();
+
+        query!(
+            r#"
+SELECT relay, accepted AS "accepted: bool", message
+FROM outbox_relays WHERE event_id = ?
+ORDER BY relay
+"#,
+            id
+        )
+        .fetch_all(&self.0.pool)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|r| {
+            Some(Delivery {
+                relay: r.relay.parse().ok()?,
+                accepted: r.accepted,
+                message: r.message,
+            })
+        })
+        .collect()
+    }
+
+    /// Relays that have not responded to event `event_id` yet.
+    async fn waiting_relays(&self, event_id: EventId) -> Vec<Url> {
+        self.deliveries(event_id)
+            .await
+            .into_iter()
+            .filter(|d| d.accepted.is_none())
+            .map(|d| d.relay)
+            .collect()
+    }
+
+    /// Relays we write to that are currently connected.
+    pub async fn connected_relays(&self) -> HashSet<Url> {
+        let mut connected = HashSet::new();
+
+        for (url, relay) in self.0.client.relays().await {
+            if relay.opts().write() && relay.status().await == RelayStatus::Connected {
+                connected.insert(url);
+            }
+        }
+
+        connected
+    }
+
+    /// Sends `event` to `relays` and schedules next attempt.
+    async fn deliver(&self, event: &Event, relays: &[Url]) {
+        let id = event.id.as_bytes().to_vec();
+
+        let _ = query!(
+            r#"
+UPDATE outbox SET
+  attempts = attempts + 1,
+  next_attempt = datetime('now', printf('+%d minutes', MIN(1 << attempts, ?)))
+WHERE event_id = ?
+"#,
+            MAX_BACKOFF_MINUTES,
+            id
+        )
+        .execute(&self.0.pool)
+        .await;
+
+        for relay in relays {
+            info!("Sending event {} to {}", event.id, relay);
+
+            if let Err(e) = self
+                .0
+                .client
+                .send_event_to(relay.to_string(), event.clone())
+                .await
+            {
+                warn!("Could not send event {} to {}: {}", event.id, relay, e);
+            } else {
+                self.0
+                    .awaiting
+                    .lock()
+                    .unwrap()
+                    .insert((event.id, relay.clone()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Fixtures, TestDb};
+
+    fn relay(name: &str) -> Url {
+        Url::parse(&format!("wss://{name}.example.com")).unwrap()
+    }
+
+    fn outbox(db: &TestDb) -> Outbox {
+        Outbox::new(db.pool().clone(), Client::new(&Keys::generate()))
+    }
+
+    #[tokio::test]
+    async fn published_events_are_announced_before_relays_accept_them() {
+        let db = TestDb::in_memory().await.unwrap();
+        let outbox = outbox(&db);
+        let mut published = outbox.published();
+        let note = Fixtures::new().text_note("Hello");
+
+        assert!(!outbox.is_pending(note.id).await);
+        assert_eq!(outbox.publish(note.clone()).await, Ok(note.id));
+
+        assert_eq!(published.try_recv().ok(), Some(note.clone()));
+        assert!(outbox.is_pending(note.id).await);
+        // Sending it to chosen relays again announces nothing.
+        outbox.publish_to(&note, &[relay("a")]).await.unwrap();
+        assert!(published.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn only_first_acceptance_delivers() {
+        let db = TestDb::in_memory().await.unwrap();
+        let outbox = outbox(&db);
+        let note = Fixtures::new().text_note("Hello");
+        outbox
+            .publish_to(&note, &[relay("a"), relay("b"), relay("c")])
+            .await
+            .unwrap();
+
+        assert!(
+            !outbox
+                .acknowledge(&relay("a"), note.id, false, "blocked")
+                .await
+        );
+        assert!(outbox.is_pending(note.id).await);
+        // Relays it has not been sent to have nothing to accept.
+        assert!(!outbox.acknowledge(&relay("d"), note.id, true, "").await);
+        assert!(outbox.is_pending(note.id).await);
+
+        assert!(outbox.acknowledge(&relay("b"), note.id, true, "").await);
+        assert!(!outbox.is_pending(note.id).await);
+        assert!(!outbox.acknowledge(&relay("c"), note.id, true, "").await);
+
+        let other = Fixtures::new().text_note("Not published");
+        assert!(!outbox.acknowledge(&relay("a"), other.id, true, "").await);
+
+        let deliveries: Vec<(Url, Option<bool>, Option<String>)> = outbox
+            .deliveries(note.id)
+            .await
+            .into_iter()
+            .map(|d| (d.relay, d.accepted, d.message))
+            .collect();
+        assert_eq!(
+            deliveries,
+            [
+                (relay("a"), Some(false), Some("blocked".to_string())),
+                (relay("b"), Some(true), None),
+                (relay("c"), Some(true), None),
+            ]
+        );
+    }
+}
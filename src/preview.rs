@@ -0,0 +1,232 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::{Client, Url};
+use sqlx::{query, SqlitePool};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+/// At most this many bytes of a web page are downloaded to obtain its preview.
+const MAX_PAGE_SIZE: usize = 64 * 1024;
+
+/// Minimal delay between two consecutive page downloads.
+const MIN_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Preview of a web page, as described by its OpenGraph metadata.
+#[derive(Clone, Debug)]
+pub struct LinkPreview {
+    pub url: Url,
+    pub title: String,
+    pub description: Option<String>,
+    pub image: Option<Url>,
+}
+
+#[derive(Clone)]
+pub struct Previews(Arc<PreviewsInner>);
+
+struct PreviewsInner {
+    pool: SqlitePool,
+    http: Client,
+    /// Whether previews are fetched at all.
+    enabled: AtomicBool,
+    /// Pages being downloaded right now.
+    fetching: Mutex<HashSet<Url>>,
+    /// Time of the last page download.
+    last_fetch: Mutex<Option<Instant>>,
+}
+
+impl Previews {
+    pub fn new(pool: SqlitePool, http: Client, enabled: bool) -> Previews {
+        Previews(Arc::new(PreviewsInner {
+            pool,
+            http,
+            enabled: AtomicBool::new(enabled),
+            fetching: Default::default(),
+            last_fetch: Default::default(),
+        }))
+    }
+
+    /// Starts or stops fetching of previews.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.0.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Obtains preview of web page at `url`, either from database or
+    /// by downloading the page. Returns `None` if previews are disabled
+    /// or if the page cannot be previewed.
+    pub async fn get(&self, url: &Url) -> Option<LinkPreview> {
+        if !self.0.enabled.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        let url_s = url.to_string();
+
+        // Successful previews are kept for a week, failed ones for a day.
+        let stored = query!(
+            r#"
+SELECT title, description, image FROM link_previews
+WHERE url = ?
+  AND unixepoch('now') - unixepoch(fetched) < IIF(title IS NULL, 24, 7 * 24) * 60 * 60
+"#,
+            url_s
+        )
+        .fetch_optional(&self.0.pool)
+        .await
+        .ok()
+        .flatten();
+
+        if let Some(r) = stored {
+            return r.title.map(|title| LinkPreview {
+                url: url.clone(),
+                title,
+                description: r.description,
+                image: r.image.and_then(|i| i.parse().ok()),
+            });
+        }
+
+        if !self.0.fetching.lock().await.insert(url.clone()) {
+            debug!("Preview of {} is already being fetched", url);
+            return None;
+        }
+
+        self.wait_turn().await;
+
+        let preview = self.fetch(url).await;
+        self.0.fetching.lock().await.remove(url);
+
+        let (title, description, image) = match &preview {
+            Some(p) => (
+                Some(p.title.clone()),
+                p.description.clone(),
+                p.image.as_ref().map(|i| i.to_string()),
+            ),
+            None => (None, None, None),
+        };
+
+        let _ = query!(
+            r#"
+INSERT INTO link_previews (url, title, description, image) VALUES (?, ?, ?, ?)
+ON CONFLICT (url) DO UPDATE SET
+  title = EXCLUDED.title,
+  description = EXCLUDED.description,
+  image = EXCLUDED.image,
+  fetched = CURRENT_TIMESTAMP
+"#,
+            url_s,
+            title,
+            description,
+            image
+        )
+        .execute(&self.0.pool)
+        .await;
+
+        preview
+    }
+
+    /// Waits until enough time has passed since the last page download.
+    async fn wait_turn(&self) {
+        let mut last = self.0.last_fetch.lock().await;
+
+        if let Some(l) = *last {
+            tokio::time::sleep_until(l + MIN_INTERVAL).await;
+        }
+
+        *last = Some(Instant::now());
+    }
+
+    /// Downloads beginning of the page at `url` and extracts its preview.
+    async fn fetch(&self, url: &Url) -> Option<LinkPreview> {
+        info!("Fetching preview of {}", url);
+
+        let response = self
+            .0
+            .http
+            .get(url.clone())
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .ok()?;
+
+        let is_html = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_lowercase().starts_with("text/html"))
+            .unwrap_or(false);
+
+        if !is_html {
+            debug!("{} is not a HTML page", url);
+            return None;
+        }
+
+        let mut page = Vec::with_capacity(MAX_PAGE_SIZE);
+        let mut bytes = response.bytes_stream();
+
+        while let Some(Ok(chunk)) = bytes.next().await {
+            page.extend_from_slice(&chunk[..chunk.len().min(MAX_PAGE_SIZE - page.len())]);
+            if page.len() >= MAX_PAGE_SIZE {
+                break;
+            }
+        }
+
+        parse_preview(url, &String::from_utf8_lossy(&page))
+    }
+}
+
+static META: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<meta\s[^>]*>").unwrap());
+static ATTR: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(?is)([a-z][a-z:-]*)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap());
+static TITLE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap());
+
+/// Extracts preview from OpenGraph `<meta>` tags of HTML `page`,
+/// falling back to its `<title>`.
+fn parse_preview(url: &Url, page: &str) -> Option<LinkPreview> {
+    let mut title = None;
+    let mut description = None;
+    let mut image = None;
+
+    for meta in META.find_iter(page) {
+        let mut property = None;
+        let mut content = None;
+
+        for attr in ATTR.captures_iter(meta.as_str()) {
+            let value = attr.get(2).or_else(|| attr.get(3)).map(|v| v.as_str());
+            match attr[1].to_lowercase().as_str() {
+                "property" | "name" => property = value.map(|v| v.to_lowercase()),
+                "content" => content = value.map(|v| v.trim().to_string()),
+                _ => {}
+            }
+        }
+
+        let content = content
+            .filter(|c| !c.is_empty())
+            .map(|c| html_escape::decode_html_entities(&c).to_string());
+
+        match property.as_deref() {
+            Some("og:title") => title = title.or(content),
+            Some("og:description") | Some("description") => description = description.or(content),
+            Some("og:image") => image = image.or(content.and_then(|c| url.join(&c).ok())),
+            _ => {}
+        }
+    }
+
+    let title = title.or_else(|| {
+        TITLE
+            .captures(page)
+            .map(|c| html_escape::decode_html_entities(c[1].trim()).to_string())
+            .filter(|t| !t.is_empty())
+    })?;
+
+    Some(LinkPreview {
+        url: url.clone(),
+        title,
+        description,
+        image,
+    })
+}
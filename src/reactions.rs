@@ -0,0 +1,162 @@
+//! Reactions (NIP-25) and zaps (NIP-57) of events. They are stored with
+//! the events they target, so that notes display their counts from the
+//! first render, no matter whether the reactions have arrived before
+//! the notes or after them. Every reaction counts once, however many
+//! relays deliver it.
+
+use nostr_sdk::prelude::*;
+use sqlx::{query, SqlitePool};
+
+use crate::db;
+use crate::nostr::{Emoji, EventExt, ZAP_RECEIPT_KIND};
+
+/// What a reaction tells about the event it reacts to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Reaction {
+    Like,
+    Dislike,
+    /// Reaction by custom emoji (NIP-30).
+    Emoji(Emoji),
+}
+
+impl Reaction {
+    /// What reaction of `content` and custom `emoji` means, `None` if it is
+    /// none of the reactions that are counted.
+    pub fn of(content: &str, emoji: Option<Emoji>) -> Option<Reaction> {
+        match (content.trim(), emoji) {
+            (_, Some(emoji)) => Some(Reaction::Emoji(emoji)),
+            ("+" | "🤙", None) => Some(Reaction::Like),
+            ("-", None) => Some(Reaction::Dislike),
+            _ => None,
+        }
+    }
+}
+
+/// Counts of reactions to an event and of its zaps.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReactionCounts {
+    pub likes: u32,
+    pub dislikes: u32,
+    /// Reactions by custom emoji, in order of their first use.
+    pub emojis: Vec<(Emoji, u32)>,
+    pub zaps: u32,
+    /// Zapped amount in sats.
+    pub sats: u64,
+}
+
+impl ReactionCounts {
+    /// Counts `count` more of `reaction`.
+    pub fn add(&mut self, reaction: Reaction, count: u32) {
+        match reaction {
+            Reaction::Like => self.likes += count,
+            Reaction::Dislike => self.dislikes += count,
+            Reaction::Emoji(emoji) => match self.emojis.iter_mut().find(|(e, _)| *e == emoji) {
+                Some((_, n)) => *n += count,
+                None => self.emojis.push((emoji, count)),
+            },
+        }
+    }
+
+    /// Counts zap of `sats`.
+    pub fn add_zap(&mut self, sats: u64) {
+        self.zaps += 1;
+        self.sats += sats;
+    }
+}
+
+/// Stored reactions and zaps.
+#[derive(Clone, Debug)]
+pub struct Reactions {
+    pool: SqlitePool,
+}
+
+impl Reactions {
+    pub fn new(pool: SqlitePool) -> Reactions {
+        Reactions { pool }
+    }
+
+    /// Stores reaction or zap receipt `event` with the event it targets.
+    /// Returns whether it has not been stored before. Events which are
+    /// neither, or which target no event, are not stored.
+    pub async fn store(&self, event: &Event) -> Result<bool, String> {
+        let target = match event.reacts_to().or_else(|| event.zapped_event()) {
+            Some(t) => t.as_bytes().to_vec(),
+            None => return Ok(false),
+        };
+        let id = event.id.as_bytes().to_vec();
+        let kind = event.kind.as_u64() as i64;
+        let content = if event.kind == Kind::Reaction {
+            event.content.as_str()
+        } else {
+            ""
+        };
+        let emoji = event.reaction_emoji();
+        let shortcode = emoji.as_ref().map(|e| e.shortcode.clone());
+        let emoji_url = emoji.as_ref().map(|e| e.url.to_string());
+        let sats = event.zap_amount().map(|s| s as i64);
+        let created_at = event.created_at.as_i64();
+
+        let inserted = db::retry_on_busy(|| {
+            query!(
+                r#"
+INSERT INTO reactions (id, target, kind, content, emoji_shortcode, emoji_url, sats, created_at)
+VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+ON CONFLICT DO NOTHING
+"#,
+                id,
+                target,
+                kind,
+                content,
+                shortcode,
+                emoji_url,
+                sats,
+                created_at
+            )
+            .execute(&self.pool)
+        })
+        .await
+        .map_err(|e| format!("Could not store reaction {}: {}", event.id, e))?;
+
+        Ok(inserted.rows_affected() > 0)
+    }
+
+    /// Counts of stored reactions to `event_id` and of its zaps.
+    pub async fn counts(&self, event_id: EventId) -> ReactionCounts {
+        let target = event_id.as_bytes().to_vec();
+        let mut counts = ReactionCounts::default();
+
+        let rows = query!(
+            r#"
+SELECT kind AS "kind!: i64", content AS "content!", emoji_shortcode, emoji_url,
+       COUNT(*) AS "count!: i64", SUM(sats) AS "sats: i64"
+FROM reactions WHERE target = ?
+GROUP BY kind, content, emoji_shortcode, emoji_url
+ORDER BY MIN(created_at)
+"#,
+            target
+        )
+        .fetch_all(&self.pool)
+        .await
+        .unwrap_or_default();
+
+        for row in rows {
+            if row.kind as u64 == ZAP_RECEIPT_KIND {
+                counts.zaps += row.count as u32;
+                counts.sats += row.sats.unwrap_or_default() as u64;
+                continue;
+            }
+
+            let emoji = match (row.emoji_shortcode, row.emoji_url) {
+                (Some(shortcode), Some(url)) => {
+                    Url::parse(&url).ok().map(|url| Emoji { shortcode, url })
+                }
+                _ => None,
+            };
+            if let Some(reaction) = Reaction::of(&row.content, emoji) {
+                counts.add(reaction, row.count as u32);
+            }
+        }
+
+        counts
+    }
+}
@@ -0,0 +1,197 @@
+//! Relay information documents (NIP-11).
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, SqlitePool};
+use tracing::{debug, info};
+
+/// Relay information documents larger than this are refused.
+const MAX_DOCUMENT_SIZE: usize = 64 * 1024;
+
+/// How long to wait for relay to provide its information document.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Information that relay provides about itself.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RelayInformation {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub pubkey: Option<String>,
+    pub contact: Option<String>,
+    #[serde(default)]
+    pub supported_nips: Vec<u16>,
+    pub software: Option<String>,
+    pub version: Option<String>,
+    pub limitation: Option<Limitation>,
+    pub payments_url: Option<String>,
+}
+
+/// Limits that relay imposes on its clients.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Limitation {
+    pub max_message_length: Option<u64>,
+    pub max_subscriptions: Option<u64>,
+    pub max_filters: Option<u64>,
+    pub max_limit: Option<u64>,
+    pub max_subid_length: Option<u64>,
+    pub max_event_tags: Option<u64>,
+    pub max_content_length: Option<u64>,
+    pub min_pow_difficulty: Option<u8>,
+    #[serde(default)]
+    pub auth_required: bool,
+    #[serde(default)]
+    pub payment_required: bool,
+}
+
+impl RelayInformation {
+    pub fn auth_required(&self) -> bool {
+        self.limitation
+            .as_ref()
+            .map(|l| l.auth_required)
+            .unwrap_or(false)
+    }
+
+    pub fn payment_required(&self) -> bool {
+        self.limitation
+            .as_ref()
+            .map(|l| l.payment_required)
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Clone)]
+pub struct RelayInfo(Arc<RelayInfoInner>);
+
+struct RelayInfoInner {
+    pool: SqlitePool,
+    http: Client,
+    /// Documents older than this are fetched again.
+    max_age: Duration,
+}
+
+impl RelayInfo {
+    pub fn new(pool: SqlitePool, http: Client, max_age: Duration) -> RelayInfo {
+        RelayInfo(Arc::new(RelayInfoInner {
+            pool,
+            http,
+            max_age,
+        }))
+    }
+
+    /// Information about `relay` stored in database, if any.
+    pub async fn stored(&self, relay: &Url) -> Option<RelayInformation> {
+        let url_s = relay.to_string();
+
+        query!("SELECT information FROM relays WHERE url = ?", url_s)
+            .fetch_optional(&self.0.pool)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|r| r.information)
+            .and_then(|i| serde_json::from_str(&i).ok())
+    }
+
+    /// Whether `relay` claims to support `nip` in its stored information.
+    pub async fn supports(&self, relay: &Url, nip: u16) -> bool {
+        self.stored(relay)
+            .await
+            .map(|i| i.supported_nips.contains(&nip))
+            .unwrap_or(false)
+    }
+
+    /// Whether stored information about `relay` is missing or too old.
+    pub async fn is_stale(&self, relay: &Url) -> bool {
+        let url_s = relay.to_string();
+
+        let age = query!(
+            r#"SELECT unixepoch('now') - unixepoch(fetched_at) AS "age: i64" FROM relays WHERE url = ?"#,
+            url_s
+        )
+        .fetch_optional(&self.0.pool)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|r| r.age);
+
+        match age {
+            Some(age) => age as u64 > self.0.max_age.as_secs(),
+            None => true,
+        }
+    }
+
+    /// Fetches information document of `relay` and stores it. If the
+    /// document cannot be obtained, previously stored one is kept.
+    pub async fn refresh(&self, relay: &Url) -> Option<RelayInformation> {
+        let url_s = relay.to_string();
+        let fetched = self.fetch(relay).await;
+
+        let json = fetched.as_ref().and_then(|i| serde_json::to_string(i).ok());
+
+        let _ = query!(
+            r#"
+INSERT INTO relays (url, information, fetched_at) VALUES (?, ?, CURRENT_TIMESTAMP)
+ON CONFLICT (url) DO UPDATE SET
+  information = IFNULL(EXCLUDED.information, information),
+  fetched_at = EXCLUDED.fetched_at
+"#,
+            url_s,
+            json
+        )
+        .execute(&self.0.pool)
+        .await;
+
+        match fetched {
+            Some(i) => {
+                info!("Stored fresh relay information of {}", relay);
+                Some(i)
+            }
+            None => self.stored(relay).await,
+        }
+    }
+
+    /// Downloads information document from HTTP endpoint of `relay`.
+    async fn fetch(&self, relay: &Url) -> Option<RelayInformation> {
+        let mut url = relay.clone();
+        let scheme = match url.scheme() {
+            "wss" => "https",
+            "ws" => "http",
+            _ => return None,
+        };
+        url.set_scheme(scheme).ok()?;
+
+        let response = self
+            .0
+            .http
+            .get(url)
+            .header(reqwest::header::ACCEPT, "application/nostr+json")
+            .timeout(TIMEOUT)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+
+        let bytes = match response {
+            Ok(r) if r.content_length().unwrap_or(0) <= MAX_DOCUMENT_SIZE as u64 => {
+                r.bytes().await.ok()?
+            }
+            Ok(_) => {
+                debug!("Relay information of {} is too large", relay);
+                return None;
+            }
+            Err(e) => {
+                debug!("Could not fetch relay information of {}: {}", relay, e);
+                return None;
+            }
+        };
+
+        if bytes.len() > MAX_DOCUMENT_SIZE {
+            return None;
+        }
+
+        serde_json::from_slice(&bytes)
+            .map_err(|e| debug!("Invalid relay information of {}: {}", relay, e))
+            .ok()
+    }
+}
@@ -0,0 +1,309 @@
+//! Numbers of events received from relays, so that relays which bring
+//! little can be told apart, and when relays have been seen. Events are
+//! counted in memory and the counts are written into the database in
+//! batches, never for every event.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use nostr_sdk::prelude::Timestamp;
+use reqwest::Url;
+use sqlx::{query, SqlitePool};
+
+/// Received events of a relay.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub events: u64,
+    /// Size of the events as JSON.
+    pub bytes: u64,
+    /// When an event was last received.
+    pub last_event_at: Option<Timestamp>,
+    /// Number of sessions in which events were received.
+    pub sessions: u64,
+}
+
+impl Counts {
+    fn add(&mut self, other: &Counts) {
+        self.events += other.events;
+        self.bytes += other.bytes;
+        self.last_event_at = self.last_event_at.max(other.last_event_at);
+        self.sessions += other.sessions;
+    }
+}
+
+/// When a relay has been seen, delivering events or recommended in them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Activity {
+    /// When the relay first became known.
+    pub first_seen_at: Option<Timestamp>,
+    pub last_seen_at: Option<Timestamp>,
+    /// When an event was last received from the relay.
+    pub last_event_at: Option<Timestamp>,
+}
+
+impl Activity {
+    fn seen(&mut self, at: Timestamp) {
+        self.first_seen_at = Some(self.first_seen_at.map_or(at, |t| t.min(at)));
+        self.last_seen_at = self.last_seen_at.max(Some(at));
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RelayStats(Arc<RelayStatsInner>);
+
+#[derive(Debug)]
+struct RelayStatsInner {
+    pool: SqlitePool,
+    /// When this session started.
+    started: Instant,
+    counts: Mutex<SessionCounts>,
+}
+
+#[derive(Debug, Default)]
+struct SessionCounts {
+    /// All counts of this session.
+    session: HashMap<Url, Counts>,
+    /// Counts not written into the database yet.
+    unflushed: HashMap<Url, Counts>,
+    /// Relays whose session has been written into the database.
+    counted: HashSet<Url>,
+    /// When relays were last seen, not written into the database yet.
+    seen: HashMap<Url, Timestamp>,
+    /// Relays seen in this session.
+    known: HashSet<Url>,
+}
+
+impl RelayStats {
+    pub fn new(pool: SqlitePool) -> RelayStats {
+        RelayStats(Arc::new(RelayStatsInner {
+            pool,
+            started: Instant::now(),
+            counts: Default::default(),
+        }))
+    }
+
+    /// Counts event of `bytes` received from `relay` just now.
+    pub fn received(&self, relay: &Url, bytes: usize) {
+        let counts = Counts {
+            events: 1,
+            bytes: bytes as u64,
+            last_event_at: Some(Timestamp::now()),
+            sessions: 0,
+        };
+
+        let mut guard = self.0.counts.lock().unwrap();
+        guard.session.entry(relay.clone()).or_default().add(&counts);
+        guard
+            .unflushed
+            .entry(relay.clone())
+            .or_default()
+            .add(&counts);
+    }
+
+    /// Remembers that `relay` has been recommended in an event just now.
+    /// Returns `true` if it has not been seen in this session before.
+    pub fn seen(&self, relay: &Url) -> bool {
+        let mut guard = self.0.counts.lock().unwrap();
+        guard.seen.insert(relay.clone(), Timestamp::now());
+        guard.known.insert(relay.clone())
+    }
+
+    /// How long this session has been running, in seconds.
+    pub fn uptime(&self) -> u64 {
+        self.0.started.elapsed().as_secs()
+    }
+
+    /// Counts of this session.
+    pub fn session(&self) -> HashMap<Url, Counts> {
+        self.0.counts.lock().unwrap().session.clone()
+    }
+
+    /// Counts of all sessions, including what has not been written yet.
+    pub async fn all_time(&self) -> HashMap<Url, Counts> {
+        let mut all: HashMap<Url, Counts> = query!(
+            r#"
+SELECT url AS "url!", events_received AS "events: i64", bytes_received AS "bytes: i64",
+       last_event_at AS "last_event_at: i64", sessions AS "sessions: i64"
+FROM relay_stats"#
+        )
+        .fetch_all(&self.0.pool)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|r| {
+            let counts = Counts {
+                events: r.events as u64,
+                bytes: r.bytes as u64,
+                last_event_at: r.last_event_at.map(|t| Timestamp::from(t as u64)),
+                sessions: r.sessions as u64,
+            };
+            Some((Url::parse(&r.url).ok()?, counts))
+        })
+        .collect();
+
+        let guard = self.0.counts.lock().unwrap();
+        for (url, counts) in &guard.unflushed {
+            let mut counts = *counts;
+            if !guard.counted.contains(url) {
+                counts.sessions = 1;
+            }
+            all.entry(url.clone()).or_default().add(&counts);
+        }
+
+        all
+    }
+
+    /// When relays have been seen, including what has not been written yet.
+    pub async fn activity(&self) -> HashMap<Url, Activity> {
+        let mut activity: HashMap<Url, Activity> = query!(
+            r#"
+SELECT url, first_seen_at AS "first_seen_at: i64", last_seen_at AS "last_seen_at: i64",
+       unixepoch(last_event_at) AS "last_event_at: i64"
+FROM relays"#
+        )
+        .fetch_all(&self.0.pool)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|r| {
+            let activity = Activity {
+                first_seen_at: r.first_seen_at.map(|t| Timestamp::from(t as u64)),
+                last_seen_at: r.last_seen_at.map(|t| Timestamp::from(t as u64)),
+                last_event_at: r.last_event_at.map(|t| Timestamp::from(t as u64)),
+            };
+            Some((Url::parse(&r.url).ok()?, activity))
+        })
+        .collect();
+
+        let guard = self.0.counts.lock().unwrap();
+        for (url, at) in &guard.seen {
+            activity.entry(url.clone()).or_default().seen(*at);
+        }
+        for (url, counts) in &guard.unflushed {
+            if let Some(at) = counts.last_event_at {
+                let activity = activity.entry(url.clone()).or_default();
+                activity.seen(at);
+                activity.last_event_at = activity.last_event_at.max(Some(at));
+            }
+        }
+
+        activity
+    }
+
+    /// Writes counts and times gathered since the last flush into
+    /// the database, all of them in a single transaction.
+    pub async fn flush(&self) -> Result<(), String> {
+        let (batch, seen) = {
+            let mut guard = self.0.counts.lock().unwrap();
+            let unflushed = std::mem::take(&mut guard.unflushed);
+            let batch: Vec<(Url, Counts)> = unflushed
+                .into_iter()
+                .map(|(url, mut counts)| {
+                    if guard.counted.insert(url.clone()) {
+                        counts.sessions = 1;
+                    }
+                    (url, counts)
+                })
+                .collect();
+            let seen: Vec<(Url, Timestamp)> = std::mem::take(&mut guard.seen).into_iter().collect();
+            (batch, seen)
+        };
+
+        if batch.is_empty() && seen.is_empty() {
+            return Ok(());
+        }
+
+        let result = self.write(&batch, &seen).await;
+        if result.is_err() {
+            // Counts and times stay for the next flush.
+            let mut guard = self.0.counts.lock().unwrap();
+            for (url, mut counts) in batch {
+                if counts.sessions > 0 {
+                    guard.counted.remove(&url);
+                }
+                counts.sessions = 0;
+                guard.unflushed.entry(url).or_default().add(&counts);
+            }
+            for (url, at) in seen {
+                let last = guard.seen.entry(url).or_insert(at);
+                *last = (*last).max(at);
+            }
+        }
+
+        result
+    }
+
+    async fn write(
+        &self,
+        batch: &[(Url, Counts)],
+        seen: &[(Url, Timestamp)],
+    ) -> Result<(), String> {
+        let mut tx = self.0.pool.begin().await.map_err(|e| e.to_string())?;
+
+        for (url, counts) in batch {
+            let url_s = url.to_string();
+            let events = counts.events as i64;
+            let bytes = counts.bytes as i64;
+            let last_event_at = counts.last_event_at.map(|t| t.as_i64());
+            let sessions = counts.sessions as i64;
+
+            query!(
+                r#"
+INSERT INTO relay_stats (url, events_received, bytes_received, last_event_at, sessions)
+VALUES (?, ?, ?, ?, ?)
+ON CONFLICT (url) DO UPDATE SET
+    events_received = events_received + EXCLUDED.events_received,
+    bytes_received = bytes_received + EXCLUDED.bytes_received,
+    last_event_at = MAX(COALESCE(last_event_at, 0), EXCLUDED.last_event_at),
+    sessions = sessions + EXCLUDED.sessions
+"#,
+                url_s,
+                events,
+                bytes,
+                last_event_at,
+                sessions
+            )
+            .execute(&mut tx)
+            .await
+            .map_err(|e| format!("Could not store statistics of {}: {}", url, e))?;
+
+            query!(
+                r#"
+INSERT INTO relays (url, last_event_at, first_seen_at, last_seen_at)
+VALUES (?1, datetime(?2, 'unixepoch'), ?2, ?2)
+ON CONFLICT (url) DO UPDATE SET
+    last_event_at = EXCLUDED.last_event_at,
+    first_seen_at = COALESCE(first_seen_at, EXCLUDED.first_seen_at),
+    last_seen_at = MAX(COALESCE(last_seen_at, 0), EXCLUDED.last_seen_at)
+"#,
+                url_s,
+                last_event_at
+            )
+            .execute(&mut tx)
+            .await
+            .map_err(|e| format!("Could not store {} into relays: {}", url, e))?;
+        }
+
+        for (url, at) in seen {
+            let url_s = url.to_string();
+            let at = at.as_i64();
+
+            query!(
+                r#"
+INSERT INTO relays (url, first_seen_at, last_seen_at) VALUES (?1, ?2, ?2)
+ON CONFLICT (url) DO UPDATE SET
+    first_seen_at = COALESCE(first_seen_at, EXCLUDED.first_seen_at),
+    last_seen_at = MAX(COALESCE(last_seen_at, 0), EXCLUDED.last_seen_at)
+"#,
+                url_s,
+                at
+            )
+            .execute(&mut tx)
+            .await
+            .map_err(|e| format!("Could not store {} into relays: {}", url, e))?;
+        }
+
+        tx.commit().await.map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,297 @@
+//! Replaceable events (NIP-16) and parameterized replaceable events
+//! (NIP-33), of which only the newest version by every author counts,
+//! for the latter one per identifier (`d` tag). Stored versions are
+//! indexed in `replaceable_events`. Storing a newer version removes the
+//! older one, and older versions arriving later are not stored at all,
+//! so whatever gets stored is news.
+
+use nostr_sdk::prelude::*;
+use sqlx::{query, Sqlite, Transaction};
+
+use crate::nostr::EventExt;
+
+/// What has happened to an event being stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stored {
+    /// It has been stored with this rowid.
+    New(i64),
+    /// It has been stored before.
+    Known,
+    /// Newer version of the replaceable event is known.
+    Outdated,
+}
+
+/// Whether only the newest event of `kind` by every author counts.
+pub fn is_replaceable(kind: u64) -> bool {
+    kind == Kind::Metadata.as_u64()
+        || kind == Kind::ContactList.as_u64()
+        || (10_000..20_000).contains(&kind)
+}
+
+/// Whether only the newest event of `kind` by every author with the same
+/// identifier counts.
+pub fn is_parameterized(kind: u64) -> bool {
+    (30_000..40_000).contains(&kind)
+}
+
+/// Kind and identifier which all versions of `event` share, `None` if it
+/// is not replaceable. The identifier of plain replaceable events, and
+/// of parameterized ones without `d` tag, is empty.
+pub fn address(event: &Event) -> Option<(u64, String)> {
+    let kind = event.kind.as_u64();
+    if is_replaceable(kind) {
+        Some((kind, String::new()))
+    } else if is_parameterized(kind) {
+        Some((kind, event.identifier().unwrap_or_default()))
+    } else {
+        None
+    }
+}
+
+/// Whether version created at `created_at` with ID `id` replaces known
+/// version created at `known_at` with ID `known_id`. Of versions created
+/// within the same second the one with the lowest ID wins, as NIP-01
+/// says, so that all clients and relays keep the same one.
+pub fn supersedes(created_at: i64, id: &[u8], known_at: i64, known_id: &[u8]) -> bool {
+    created_at > known_at || (created_at == known_at && id < known_id)
+}
+
+/// Stores `event` into textnotes within `tx`. If it is a replaceable
+/// event, it is stored only if it is newer than the known version, which
+/// it replaces.
+pub async fn store(tx: &mut Transaction<'_, Sqlite>, event: &Event) -> Result<Stored, sqlx::Error> {
+    if let Some((kind, d_tag)) = address(event) {
+        if !supersede(tx, event, kind, &d_tag).await? {
+            return Ok(Stored::Outdated);
+        }
+    }
+
+    let id = event.id.as_bytes().to_vec();
+    let json = serde_json::to_string(event).unwrap();
+    let expiration = event.expiration().map(|t| t.as_i64());
+
+    let inserted = query!(
+        "INSERT INTO textnotes (id, event, expiration) VALUES (?, ?, ?)",
+        id,
+        json,
+        expiration
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if inserted.rows_affected() > 0 {
+        Ok(Stored::New(inserted.last_insert_rowid()))
+    } else {
+        Ok(Stored::Known)
+    }
+}
+
+/// Makes `event` the known version of replaceable event of `kind` and
+/// `d_tag` and removes the older version from textnotes. Returns `false`
+/// and changes nothing if a newer version is known. The version itself
+/// being already known is not an error, it is just not stored again.
+async fn supersede(
+    tx: &mut Transaction<'_, Sqlite>,
+    event: &Event,
+    kind: u64,
+    d_tag: &str,
+) -> Result<bool, sqlx::Error> {
+    let author = event.pubkey.serialize().to_vec();
+    let kind = kind as i64;
+    let id = event.id.as_bytes().to_vec();
+    let created_at = event.created_at.as_i64();
+
+    let known = query!(
+        r#"
+SELECT event_id AS "event_id!", created_at AS "created_at!"
+FROM replaceable_events WHERE author = ? AND kind = ? AND d_tag = ?
+"#,
+        author,
+        kind,
+        d_tag
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if let Some(known) = &known {
+        if known.event_id == id {
+            return Ok(true);
+        }
+        if !supersedes(created_at, &id, known.created_at, &known.event_id) {
+            return Ok(false);
+        }
+    }
+
+    query!(
+        r#"
+INSERT INTO replaceable_events (author, kind, d_tag, event_id, created_at)
+VALUES (?, ?, ?, ?, ?)
+ON CONFLICT (author, kind, d_tag) DO UPDATE SET
+    event_id = EXCLUDED.event_id,
+    created_at = EXCLUDED.created_at
+"#,
+        author,
+        kind,
+        d_tag,
+        id,
+        created_at
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if let Some(known) = known {
+        query!("DELETE FROM textnotes WHERE id = ?", known.event_id)
+            .execute(&mut *tx)
+            .await?;
+        query!(
+            "DELETE FROM textnotes_relays WHERE textnote = ?",
+            known.event_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        query!("DELETE FROM hashtags WHERE event_id = ?", known.event_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Fixtures, TestDb};
+
+    /// Stores `event` in its own transaction.
+    async fn store_one(db: &TestDb, event: &Event) -> Stored {
+        let mut tx = db.pool().begin().await.unwrap();
+        let stored = store(&mut tx, event).await.unwrap();
+        tx.commit().await.unwrap();
+        stored
+    }
+
+    async fn is_stored(db: &TestDb, event: &Event) -> bool {
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM textnotes WHERE id = ?")
+            .bind(event.id.as_bytes().to_vec())
+            .fetch_one(db.pool())
+            .await
+            .unwrap()
+            > 0
+    }
+
+    /// Two versions of metadata of one author created within the same
+    /// second, the one with lower ID first.
+    fn tied(author: &Fixtures) -> (Event, Event) {
+        let a = author.metadata("A", Timestamp::from(100));
+        let b = author.metadata("B", Timestamp::from(100));
+        if a.id.as_bytes() < b.id.as_bytes() {
+            (a, b)
+        } else {
+            (b, a)
+        }
+    }
+
+    #[test]
+    fn kinds_are_told_apart() {
+        let author = Fixtures::new();
+        let article = author.sign(
+            Kind::Custom(30023),
+            "Article",
+            &[Tag::Generic(TagKind::D, vec!["intro".to_string()])],
+            Timestamp::now(),
+        );
+        let untagged = author.sign(Kind::Custom(30023), "Article", &[], Timestamp::now());
+
+        assert_eq!(address(&author.text_note("Hi")), None);
+        assert_eq!(
+            address(&author.metadata("Alice", Timestamp::now())),
+            Some((0, String::new()))
+        );
+        assert_eq!(
+            address(&author.sign(Kind::Custom(10002), "", &[], Timestamp::now())),
+            Some((10002, String::new()))
+        );
+        assert_eq!(address(&article), Some((30023, "intro".to_string())));
+        assert_eq!(address(&untagged), Some((30023, String::new())));
+        assert!(!is_replaceable(20_000) && !is_parameterized(40_000));
+    }
+
+    #[test]
+    fn newer_or_lower_id_supersedes() {
+        assert!(supersedes(2, &[9], 1, &[1]));
+        assert!(!supersedes(1, &[1], 2, &[9]));
+        assert!(supersedes(1, &[1], 1, &[2]));
+        assert!(!supersedes(1, &[2], 1, &[1]));
+        assert!(!supersedes(1, &[1], 1, &[1]));
+    }
+
+    #[tokio::test]
+    async fn newest_version_is_kept_whatever_the_order() {
+        let db = TestDb::new().await.unwrap();
+
+        for newer_first in [false, true] {
+            let author = Fixtures::new();
+            let old = author.metadata("Old", Timestamp::from(100));
+            let new = author.metadata("New", Timestamp::from(200));
+
+            if newer_first {
+                assert!(matches!(store_one(&db, &new).await, Stored::New(_)));
+                assert_eq!(store_one(&db, &old).await, Stored::Outdated);
+            } else {
+                assert!(matches!(store_one(&db, &old).await, Stored::New(_)));
+                assert!(matches!(store_one(&db, &new).await, Stored::New(_)));
+            }
+            assert_eq!(store_one(&db, &new).await, Stored::Known);
+
+            assert!(is_stored(&db, &new).await, "newer first: {newer_first}");
+            assert!(!is_stored(&db, &old).await, "newer first: {newer_first}");
+        }
+    }
+
+    #[tokio::test]
+    async fn lowest_id_wins_within_same_second() {
+        let db = TestDb::new().await.unwrap();
+
+        for lower_first in [false, true] {
+            let (lower, higher) = tied(&Fixtures::new());
+
+            if lower_first {
+                assert!(matches!(store_one(&db, &lower).await, Stored::New(_)));
+                assert_eq!(store_one(&db, &higher).await, Stored::Outdated);
+            } else {
+                assert!(matches!(store_one(&db, &higher).await, Stored::New(_)));
+                assert!(matches!(store_one(&db, &lower).await, Stored::New(_)));
+            }
+
+            assert!(is_stored(&db, &lower).await, "lower first: {lower_first}");
+            assert!(!is_stored(&db, &higher).await, "lower first: {lower_first}");
+        }
+    }
+
+    #[tokio::test]
+    async fn versions_are_kept_per_identifier() {
+        let db = TestDb::new().await.unwrap();
+        let author = Fixtures::new();
+        let article = |d: &str, created_at: u64| {
+            author.sign(
+                Kind::Custom(30023),
+                d,
+                &[Tag::Generic(TagKind::D, vec![d.to_string()])],
+                Timestamp::from(created_at),
+            )
+        };
+        let (intro, outro) = (article("intro", 200), article("outro", 100));
+        let older_intro = article("intro", 100);
+        let note = author.text_note("Not replaceable");
+
+        for event in [&intro, &outro, &note] {
+            assert!(matches!(store_one(&db, event).await, Stored::New(_)));
+        }
+        assert_eq!(store_one(&db, &older_intro).await, Stored::Outdated);
+        assert_eq!(store_one(&db, &note).await, Stored::Known);
+
+        for event in [&intro, &outro, &note] {
+            assert!(is_stored(&db, event).await, "{}", event.content);
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Retention of stored events. Events older than the configured number
+//! of days are pruned from the database, except our own events, bookmarked
+//! ones and those that are displayed right now.
+//!
+//! Metadata and contact lists are never pruned, they are needed to render
+//! the events that remain. The same goes for other replaceable events and
+//! for deletions (NIP-09), which would let deleted notes come back. Older
+//! metadata in the history of authors are, except the latest few.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+
+use nostr_sdk::prelude::{EventId, Timestamp, XOnlyPublicKey};
+use sqlx::{query, SqlitePool};
+use tracing::info;
+
+/// Events are kept this many days unless configured otherwise.
+pub const DEFAULT_KEEP_DAYS: u32 = 90;
+
+/// Pruning runs by itself once it has not run for this long, in seconds.
+const PRUNE_INTERVAL: i64 = 24 * 60 * 60;
+
+/// Events are deleted in transactions of at most this many.
+const BATCH_SIZE: i64 = 500;
+
+/// This many latest metadata of every author stay in their history.
+const KEEP_METADATA_HISTORY: i64 = 5;
+
+/// Name of the pruning task in `maintenance` table.
+const PRUNE_TASK: &str = "prune";
+
+/// What pruning has removed so far.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    /// Deleted events, including reactions and zaps.
+    pub events: u64,
+    /// Deleted notifications that have been read.
+    pub notifications: u64,
+    /// How much less space the database takes.
+    pub bytes: i64,
+}
+
+impl PruneReport {
+    /// Human description, such as "1200 events and 15 notifications removed, 3.2 MB reclaimed".
+    pub fn describe(&self) -> String {
+        let mut description = format!(
+            "{} events and {} notifications removed",
+            self.events, self.notifications
+        );
+        if self.bytes > 0 {
+            description.push_str(&format!(
+                ", {:.1} MB reclaimed",
+                self.bytes as f64 / (1024.0 * 1024.0)
+            ));
+        }
+        description
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Retention(Arc<RetentionInner>);
+
+#[derive(Debug)]
+struct RetentionInner {
+    pool: SqlitePool,
+    /// Events are kept this many days, zero means forever.
+    keep_days: AtomicU32,
+    /// Whether pruning is running right now.
+    running: AtomicBool,
+}
+
+impl Retention {
+    pub fn new(pool: SqlitePool) -> Retention {
+        Retention(Arc::new(RetentionInner {
+            pool,
+            keep_days: AtomicU32::new(DEFAULT_KEEP_DAYS),
+            running: AtomicBool::new(false),
+        }))
+    }
+
+    pub fn keep_days(&self) -> u32 {
+        self.0.keep_days.load(Ordering::Relaxed)
+    }
+
+    /// Events will be kept for `days`, or forever if it is zero.
+    pub fn set_keep_days(&self, days: u32) {
+        self.0.keep_days.store(days, Ordering::Relaxed);
+    }
+
+    /// Whether pruning should run because it has not run for a while.
+    pub async fn is_overdue(&self) -> bool {
+        if self.keep_days() == 0 {
+            return false;
+        }
+
+        let last_run = query!(
+            r#"SELECT last_run AS "last_run!: i64" FROM maintenance WHERE task = ?"#,
+            PRUNE_TASK
+        )
+        .fetch_optional(&self.0.pool)
+        .await
+        .ok()
+        .flatten()
+        .map(|r| r.last_run);
+
+        match last_run {
+            Some(last_run) => Timestamp::now().as_i64() - last_run >= PRUNE_INTERVAL,
+            None => true,
+        }
+    }
+
+    /// Deletes events older than [`keep_days`](Self::keep_days), together
+    /// with their relays, hashtags, reactions and full-text index, notifications
+    /// that have been read, and old metadata in the history of authors. Events of `me`, bookmarked ones and those among
+    /// `keep` stay. The database is compacted afterwards if `vacuum` is set.
+    /// `progress` is told about every batch of deleted events.
+    pub async fn prune<F>(
+        &self,
+        me: XOnlyPublicKey,
+        keep: &HashSet<EventId>,
+        vacuum: bool,
+        progress: F,
+    ) -> Result<PruneReport, String>
+    where
+        F: Fn(&PruneReport),
+    {
+        if self.0.running.swap(true, Ordering::SeqCst) {
+            return Err("Pruning is already running".to_string());
+        }
+
+        let result = self.prune_events(me, keep, vacuum, progress).await;
+        self.0.running.store(false, Ordering::SeqCst);
+
+        if let Ok(report) = &result {
+            info!("Database pruned: {}", report.describe());
+        }
+        result
+    }
+
+    async fn prune_events<F>(
+        &self,
+        me: XOnlyPublicKey,
+        keep: &HashSet<EventId>,
+        vacuum: bool,
+        progress: F,
+    ) -> Result<PruneReport, String>
+    where
+        F: Fn(&PruneReport),
+    {
+        let mut report = PruneReport::default();
+        let days = self.keep_days();
+        if days == 0 {
+            return Ok(report);
+        }
+
+        let pool = &self.0.pool;
+        let cutoff = Timestamp::now().as_i64() - days as i64 * 24 * 60 * 60;
+        let me = me.to_string();
+        let used_before = self.used_bytes().await?;
+
+        // Candidates are walked through by rowid, so that kept events
+        // are not looked at again in the next batch.
+        let mut after = 0;
+        loop {
+            let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+
+            let candidates = query!(
+                r#"
+SELECT rowid AS "rowid!: i64", id AS "id!: Vec<u8>" FROM textnotes
+WHERE rowid > ?1
+  AND json_extract(event, '$.created_at') < ?2
+  AND json_extract(event, '$.pubkey') <> ?3
+  AND json_extract(event, '$.kind') NOT IN (0, 3, 5)
+  AND json_extract(event, '$.kind') < 10000
+  AND id NOT IN (SELECT event_id FROM bookmarks WHERE bookmarked)
+ORDER BY rowid
+LIMIT ?4
+"#,
+                after,
+                cutoff,
+                me,
+                BATCH_SIZE
+            )
+            .fetch_all(&mut tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+            after = match candidates.last() {
+                Some(last) => last.rowid,
+                None => break,
+            };
+
+            let displayed = |id: &[u8]| {
+                EventId::from_slice(id)
+                    .map(|id| keep.contains(&id))
+                    .unwrap_or(false)
+            };
+
+            for candidate in candidates.iter().filter(|c| !displayed(&c.id)) {
+                query!("DELETE FROM textnotes WHERE id = ?", candidate.id)
+                    .execute(&mut tx)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                query!(
+                    "DELETE FROM textnotes_relays WHERE textnote = ?",
+                    candidate.id
+                )
+                .execute(&mut tx)
+                .await
+                .map_err(|e| e.to_string())?;
+                query!("DELETE FROM hashtags WHERE event_id = ?", candidate.id)
+                    .execute(&mut tx)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                report.events += query!("DELETE FROM reactions WHERE target = ?", candidate.id)
+                    .execute(&mut tx)
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .rows_affected();
+
+                report.events += 1;
+            }
+
+            tx.commit().await.map_err(|e| e.to_string())?;
+            progress(&report);
+        }
+
+        // Old reactions to events which have never been stored would
+        // stay forever otherwise.
+        report.events += query!(
+            r#"
+DELETE FROM reactions
+WHERE created_at < ? AND target NOT IN (SELECT id FROM textnotes)
+"#,
+            cutoff
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .rows_affected();
+
+        report.notifications = query!(
+            "DELETE FROM notifications WHERE read AND created_at < ?",
+            cutoff
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .rows_affected();
+
+        // The latest history shows what authors have changed even if
+        // they have not changed anything for a long time.
+        report.events += query!(
+            r#"
+DELETE FROM metadata_history
+WHERE created_at < ?1
+  AND rowid NOT IN (
+    SELECT rowid FROM (
+      SELECT rowid, row_number() OVER (PARTITION BY author ORDER BY created_at DESC) AS n
+      FROM metadata_history
+    )
+    WHERE n <= ?2
+  )
+"#,
+            cutoff,
+            KEEP_METADATA_HISTORY
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .rows_affected();
+
+        if vacuum {
+            sqlx::query("VACUUM")
+                .execute(pool)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        report.bytes = used_before - self.used_bytes().await?;
+
+        let now = Timestamp::now().as_i64();
+        query!(
+            "INSERT INTO maintenance (task, last_run) VALUES (?, ?)",
+            PRUNE_TASK,
+            now
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(report)
+    }
+
+    /// Size of the database without free pages, in bytes.
+    async fn used_bytes(&self) -> Result<i64, String> {
+        sqlx::query_scalar(
+            "SELECT (page_count - freelist_count) * page_size
+             FROM pragma_page_count(), pragma_freelist_count(), pragma_page_size()",
+        )
+        .fetch_one(&self.0.pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,66 @@
+//! Translation of what user types into search box into queries.
+
+/// Splits user's `input` into terms: words, and text in double quotes
+/// as single terms. Also tells whether the last word is unfinished,
+/// i. e. there is no whitespace after it.
+fn terms(input: &str) -> (Vec<&str>, bool) {
+    let mut terms = Vec::new();
+    let mut rest = input;
+
+    while let Some(start) = rest.find('"') {
+        terms.extend(rest[..start].split_whitespace());
+
+        let after = &rest[start + 1..];
+        match after.find('"') {
+            Some(end) => {
+                let phrase = after[..end].trim();
+                if !phrase.is_empty() {
+                    terms.push(phrase);
+                }
+                rest = &after[end + 1..];
+            }
+            None => {
+                // Unfinished phrase, user is probably still typing it.
+                rest = after;
+                break;
+            }
+        }
+    }
+
+    let words: Vec<&str> = rest.split_whitespace().collect();
+    let unfinished = !rest.ends_with(char::is_whitespace) && !words.is_empty();
+    terms.extend(words);
+
+    (terms, unfinished)
+}
+
+/// Converts user's `input` into FTS5 query. Words are searched for
+/// regardless of their order, text in double quotes as a phrase. The last
+/// word is treated as a prefix, so that results appear while typing.
+/// Returns `None` if there is nothing to search for.
+pub fn fts_query(input: &str) -> Option<String> {
+    let (terms, prefix) = terms(input);
+    let query = terms.into_iter().map(quote).collect::<Vec<_>>().join(" ");
+
+    if query.is_empty() {
+        None
+    } else if prefix {
+        Some(format!("{query}*"))
+    } else {
+        Some(query)
+    }
+}
+
+/// Whether `text` contains all terms of user's `input`, ignoring case.
+/// It is a rough equivalent of [`fts_query`] for text not in database.
+pub fn matches(input: &str, text: &str) -> bool {
+    let (terms, _) = terms(input);
+    let text = text.to_lowercase();
+
+    !terms.is_empty() && terms.iter().all(|t| text.contains(&t.to_lowercase()))
+}
+
+/// Makes `term` a string in FTS5 syntax, which is matched literally.
+fn quote(term: &str) -> String {
+    format!("\"{}\"", term.replace('"', "\"\""))
+}
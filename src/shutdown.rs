@@ -0,0 +1,58 @@
+//! Ending of Gnostique. Received events that are being processed are let
+//! finish and connections are closed, rather than everything just dropped.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Ending waits at most this long for unfinished work.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether Gnostique is ending and how much work is left.
+#[derive(Debug, Default)]
+pub struct Shutdown {
+    closing: AtomicBool,
+    /// Whether ending should not wait for anything anymore.
+    forced: AtomicBool,
+    /// Number of received events being processed.
+    processing: AtomicUsize,
+}
+
+/// Processing of a received event, which is finished when dropped.
+pub struct Processing<'a>(&'a Shutdown);
+
+impl Drop for Processing<'_> {
+    fn drop(&mut self) {
+        self.0.processing.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Shutdown {
+    /// Starts processing of a received event, unless Gnostique is ending.
+    pub fn processing(&self) -> Option<Processing<'_>> {
+        if self.closing.load(Ordering::SeqCst) {
+            return None;
+        }
+        self.processing.fetch_add(1, Ordering::SeqCst);
+        Some(Processing(self))
+    }
+
+    /// Starts ending. If it has already started, it is forced not to wait
+    /// anymore and `false` is returned.
+    pub fn close(&self) -> bool {
+        if self.closing.swap(true, Ordering::SeqCst) {
+            self.forced.store(true, Ordering::SeqCst);
+            false
+        } else {
+            true
+        }
+    }
+
+    pub fn is_forced(&self) -> bool {
+        self.forced.load(Ordering::SeqCst)
+    }
+
+    /// Number of received events being processed.
+    pub fn in_flight(&self) -> usize {
+        self.processing.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,238 @@
+//! Spell checking of notes being written, with Hunspell dictionaries
+//! installed in the system, such as those of `hunspell-en-us` package.
+//! Dictionaries are named after their files, such as `en_US`. Without
+//! any installed, spelling is just not checked.
+
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use spellbook::Dictionary;
+
+use crate::content::Token;
+
+/// Directories where distributions install Hunspell dictionaries, searched
+/// after those in `DICPATH`.
+const DICTIONARY_DIRS: &[&str] = &[
+    "/usr/share/hunspell",
+    "/usr/share/myspell",
+    "/usr/share/myspell/dicts",
+    "/usr/local/share/hunspell",
+    "/app/share/hunspell",
+];
+
+/// At most this many corrections are offered for a misspelled word.
+const MAX_SUGGESTIONS: usize = 6;
+
+/// Checker of spelling in one language.
+pub struct Speller {
+    name: String,
+    dictionary: Dictionary,
+}
+
+impl fmt::Debug for Speller {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Speller").field("name", &self.name).finish()
+    }
+}
+
+impl Speller {
+    /// Reads installed dictionary `name`. It takes a while, better not
+    /// in the main thread.
+    pub fn load(name: &str) -> Result<Speller, String> {
+        let (aff, dic) = find(name).ok_or_else(|| format!("Dictionary {name} is not installed"))?;
+        let aff = std::fs::read_to_string(aff).map_err(|e| e.to_string())?;
+        let dic = std::fs::read_to_string(dic).map_err(|e| e.to_string())?;
+        let dictionary =
+            Dictionary::new(&aff, &dic).map_err(|e| format!("Dictionary {name} is broken: {e}"))?;
+
+        Ok(Speller {
+            name: name.to_string(),
+            dictionary,
+        })
+    }
+
+    /// Byte ranges of misspelled words in `text`. Links, references to
+    /// nostr entities, hashtags and mentions being typed are not words.
+    pub fn misspelled(&self, text: &str) -> Vec<Range<usize>> {
+        words(text)
+            .into_iter()
+            .filter(|range| !self.dictionary.check(&text[range.clone()]))
+            .collect()
+    }
+
+    /// Corrections of misspelled `word`, likeliest first.
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let mut suggestions = Vec::new();
+        self.dictionary.suggest(word, &mut suggestions);
+        suggestions.truncate(MAX_SUGGESTIONS);
+        suggestions
+    }
+}
+
+/// Names of installed dictionaries, sorted.
+pub fn available() -> Vec<String> {
+    let mut names: Vec<String> = dictionary_dirs()
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "dic" || !path.with_extension("aff").is_file() {
+                return None;
+            }
+            // Hyphenation patterns share the extension.
+            let name = path.file_stem()?.to_str()?;
+            (!name.starts_with("hyph_")).then(|| name.to_string())
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Installed dictionary of the language user's desktop is in, if any.
+pub fn default_dictionary(available: &[String]) -> Option<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())?;
+    let locale = locale.split(['.', '@']).next().unwrap_or_default();
+    let language = locale.split('_').next().unwrap_or_default();
+
+    available
+        .iter()
+        .find(|name| *name == locale)
+        .or_else(|| {
+            available
+                .iter()
+                .find(|name| name.split(['_', '-']).next() == Some(language))
+        })
+        .cloned()
+}
+
+/// Byte ranges of words in `text` whose spelling is checked.
+fn words(text: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+
+    for token in crate::content::tokenize(text) {
+        if let Token::Text(part) = token {
+            let offset = part.as_ptr() as usize - text.as_ptr() as usize;
+            words.extend(
+                words_of(part)
+                    .into_iter()
+                    .map(|r| r.start + offset..r.end + offset),
+            );
+        }
+    }
+
+    words
+}
+
+/// Byte ranges of words in plain `text`, without those glued to `@`,
+/// `/` or dots, which are mentions or addresses, and without numbers.
+fn words_of(text: &str) -> Vec<Range<usize>> {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '\'' || c == '’';
+    let mut words = Vec::new();
+    let mut start = None;
+
+    for (i, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (start, is_word_char(c)) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                start = None;
+                let word = text[s..i].trim_matches(|c| c == '\'' || c == '’');
+                if word.is_empty() || word.contains(|c: char| c.is_numeric()) {
+                    continue;
+                }
+                let before = text[..s].chars().next_back();
+                let after = text[i..].chars().nth(1);
+                let glued = matches!(before, Some('@' | '/' | '.' | '_'))
+                    || matches!(c, '@' | '/' | '_')
+                    || (c == '.' && after.map(char::is_alphanumeric).unwrap_or(false));
+                if !glued {
+                    let s = s + text[s..].find(word).unwrap_or(0);
+                    words.push(s..s + word.len());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    words
+}
+
+fn dictionary_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::env::var_os("DICPATH")
+        .map(|path| std::env::split_paths(&path).collect())
+        .unwrap_or_default();
+    if let Some(data) = directories::BaseDirs::new().map(|d| d.data_dir().join("hunspell")) {
+        dirs.push(data);
+    }
+    dirs.extend(DICTIONARY_DIRS.iter().map(PathBuf::from));
+    dirs
+}
+
+/// Affix and word files of dictionary `name`.
+fn find(name: &str) -> Option<(PathBuf, PathBuf)> {
+    dictionary_dirs().iter().find_map(|dir| {
+        let dic = Path::new(dir).join(format!("{name}.dic"));
+        let aff = dic.with_extension("aff");
+        (dic.is_file() && aff.is_file()).then_some((aff, dic))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Words found in `text`.
+    fn found(text: &str) -> Vec<&str> {
+        words(text).into_iter().map(|r| &text[r]).collect()
+    }
+
+    fn speller(words: &[&str]) -> Speller {
+        let dic = format!("{}\n{}\n", words.len(), words.join("\n"));
+        Speller {
+            name: "test".to_string(),
+            dictionary: Dictionary::new("SET UTF-8\nTRY esiarntolcdugmphbyfvkwz\n", &dic).unwrap(),
+        }
+    }
+
+    #[test]
+    fn words_leave_out_links_addresses_and_numbers() {
+        assert_eq!(
+            found("Helo wrld, it's 'quoted' and don’t."),
+            vec!["Helo", "wrld", "it's", "quoted", "and", "don’t"]
+        );
+        assert_eq!(
+            found("See https://exmaple.com/pth and #hashtg, or nostr:npub1qqqq."),
+            vec!["See", "and", "or"]
+        );
+        assert_eq!(
+            found("Ask @alce or alce@exmaple.com about 21st v2 and 2023."),
+            vec!["Ask", "or", "about", "and"]
+        );
+        assert_eq!(
+            found("Open src/mian.rs or snake_cse, end."),
+            vec!["Open", "or", "end"]
+        );
+    }
+
+    #[test]
+    fn misspelled_words_are_found_where_they_are() {
+        let speller = speller(&["hello", "world", "see"]);
+        let text = "Héllo wrld, see https://wrld.example.com";
+
+        let misspelled = speller.misspelled(text);
+        assert_eq!(
+            misspelled
+                .iter()
+                .map(|r| &text[r.clone()])
+                .collect::<Vec<_>>(),
+            vec!["Héllo", "wrld"]
+        );
+        assert!(speller.suggest("wrld").contains(&"world".to_string()));
+        assert!(speller.misspelled("Hello world").is_empty());
+    }
+}
@@ -0,0 +1,66 @@
+//! State of the window remembered between runs in `state.toml` in data
+//! directory: its size, where lanes were scrolled to, what they hide and
+//! whether they display threads and which notes have been read. Unlike
+//! configuration, it is never edited by user and losing it does not matter.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use nostr_sdk::prelude::{EventId, Timestamp};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::ui::lane::LaneFilter;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct State {
+    pub window: WindowState,
+    /// Topmost visible note of lanes, by [`key`](crate::ui::lane::LaneKind::key)
+    /// of the lane. Lanes scrolled to the top are missing.
+    pub anchors: HashMap<String, EventId>,
+    /// Which notes lanes hide, by key of the lane. Lanes hiding nothing are missing.
+    pub filters: HashMap<String, LaneFilter>,
+    /// Keys of lanes displaying replies below notes they reply to.
+    pub threaded: HashSet<String>,
+    /// Creation time of the newest note read in lanes, by key of the lane.
+    pub last_read: HashMap<String, Timestamp>,
+}
+
+/// Size of the main window. Its position cannot be restored,
+/// GTK 4 leaves that to window managers.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WindowState {
+    /// Zero if not known.
+    pub width: i32,
+    /// Zero if not known.
+    pub height: i32,
+    pub maximized: bool,
+}
+
+impl State {
+    /// Reads remembered state. Nothing is remembered if the file
+    /// does not exist or cannot be understood.
+    pub fn read(path: &Path) -> State {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(_) => return State::default(),
+        };
+
+        toml::from_str(&text).unwrap_or_else(|e| {
+            warn!("Ignoring invalid state in {}: {}", path.display(), e);
+            State::default()
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let text = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(path, text)
+            .map_err(|e| format!("Could not write state into {}: {}", path.display(), e))
+    }
+}
@@ -1,18 +1,35 @@
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use futures_util::*;
-use nostr_sdk::nostr::nips::nip05;
 use nostr_sdk::prelude::*;
 use nostr_sdk::RelayPoolNotification;
 use reqwest::Url;
 use sqlx::query;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, Mutex};
 use tokio_stream::wrappers::{BroadcastStream, ReceiverStream};
-use tracing::info;
+use tracing::{debug, info, warn};
 
-use crate::nostr::{EventExt, Persona, Repost};
+use crate::config::FloodAction;
+use crate::download::Media;
+use crate::handlers::Handler;
+use crate::nostr::{
+    is_unsupported_content, normalize_relay_url, Article, Emoji, EventExt, Nip05Status, Persona,
+    Quote, RelayPolicy, Repost, ARTICLE_KIND, BOOKMARKS_KIND, HANDLER_KIND,
+    HANDLER_RECOMMENDATION_KIND, MUTE_LIST_KIND, PIN_LIST_KIND, RELAY_LIST_KIND, ZAP_RECEIPT_KIND,
+};
+use crate::notifications::Notification;
+use crate::reactions::ReactionCounts;
 use crate::Gnostique;
 
+/// At most this many notes quoted by a note are displayed.
+const MAX_QUOTES: usize = 3;
+
+/// How often metrics of the processing are logged.
+const METRICS_INTERVAL: Duration = Duration::from_secs(60);
+
 #[derive(Debug)]
 pub enum X {
     TextNote {
@@ -21,160 +38,857 @@ pub enum X {
         author: Option<Persona>,
         avatar: Option<PathBuf>,
         repost: Option<Repost>,
+        /// Known users mentioned in the note.
+        mentions: Vec<Persona>,
+        /// Stored notes quoted by the note.
+        quotes: Vec<Quote>,
+        /// Whether the author, or whoever reposted the note, is flooding
+        /// and lanes should collapse the note.
+        flooding: bool,
+        /// Reactions to the note and its zaps stored so far.
+        reactions: ReactionCounts,
+        /// Language of the note, if it could be told.
+        language: Option<String>,
+        /// Labels we have given to the author, such as nsfw.
+        labels: Vec<String>,
+        /// Whether it is our note which no relay has accepted yet.
+        pending: bool,
     },
     Reaction {
         event_id: EventId,
         content: String,
+        /// Custom emoji the reaction consists of (NIP-30).
+        emoji: Option<Emoji>,
     },
+    /// Event `event_id` has been zapped with `sats`.
+    Zap { event_id: EventId, sats: u64 },
     Metadata {
         persona: Persona,
         avatar: Option<PathBuf>,
     },
+    /// Avatar of `pubkey` from `url` has been downloaded into `file`.
+    AvatarAvailable {
+        pubkey: XOnlyPublicKey,
+        url: Url,
+        file: PathBuf,
+    },
+    /// Our NIP-65 relay list, which differs from relays of the client.
+    RelayList {
+        event_id: EventId,
+        relays: Vec<RelayPolicy>,
+    },
+    /// Our bookmarks have changed, e. g. another client has published
+    /// newer bookmark list. These are all of them now.
+    Bookmarks(Vec<EventId>),
+    /// These users have been muted by another client.
+    Muted(Vec<XOnlyPublicKey>),
+    /// Newer pin list of `author` has been stored, these are all notes
+    /// they have pinned now.
+    Pins {
+        author: XOnlyPublicKey,
+        pinned: Vec<EventId>,
+    },
+    /// Newer contact list of `author` has been stored, these are all
+    /// users they follow now.
+    ContactList {
+        author: XOnlyPublicKey,
+        following: Vec<XOnlyPublicKey>,
+    },
+    /// Long-form article, newer than any of its previous versions.
+    Article {
+        article: Box<Article>,
+        relays: Vec<Url>,
+        author: Option<Persona>,
+        avatar: Option<PathBuf>,
+    },
+    /// Event of a kind which Gnostique cannot display, with the best
+    /// known application that can, if any (NIP-89).
+    Unsupported {
+        event: Event,
+        relays: Vec<Url>,
+        author: Option<Persona>,
+        avatar: Option<PathBuf>,
+        handler: Option<Handler>,
+    },
+    /// These handlers are the best known ones for their kinds now.
+    Handlers(Vec<(u64, Handler)>),
+    /// Already stored text note has been seen on another relay.
+    SeenOn { event_id: EventId, relay: Url },
+    /// Something that concerns us has happened, e. g. we have been mentioned.
+    Notification {
+        notification: Notification,
+        event: Event,
+    },
+    /// Storing into the database keeps failing, received events are
+    /// displayed but not stored.
+    Error { message: String },
+    /// Event as it has arrived from `relay`, for the debug lane. Only
+    /// a few of them a second come, and only while the lane is open.
+    Raw { relay: Url, event: Event },
 }
 
 /// Requests requested by processing functions during processing incoming events.
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 enum Feedback {
-    /// Metadata for `pubkey` are requested from `relay`.
-    NeedMetadata { relay: Url, pubkey: XOnlyPublicKey },
+    /// Metadata for `pubkey` are requested from `relay`, if the event
+    /// referring to it has come from one, and from relays
+    /// `hinted` by tags of the event that refers to it.
+    NeedMetadata {
+        relay: Option<Url>,
+        pubkey: XOnlyPublicKey,
+        hinted: Vec<Url>,
+    },
     NeedNote {
         event_id: EventId,
         relay: Option<Url>,
+        hinted: Vec<Url>,
+    },
+    /// Avatar of `pubkey` at `url` is not cached and should be downloaded.
+    NeedAvatar { pubkey: XOnlyPublicKey, url: Url },
+    /// No application is known to display event of some kind.
+    NeedHandlers,
+    /// User should be notified about `event`.
+    Notify {
+        notification: Notification,
+        event: Box<Event>,
     },
+    /// Already stored `event_id` has been seen on `relay` for the first time.
+    SeenOn { event_id: EventId, relay: Url },
+    /// Database has just become degraded because of this error.
+    DatabaseError { message: String },
 }
 
+/// Processes notifications from relays into what the window displays.
+/// Notifications come from the relay pool of `gnostique`, unless given
+/// as `a`.
 pub fn x<'a>(
     gnostique: &'a Gnostique,
-    a: Option<Box<impl Stream<Item = (Url, Event)> + 'a>>,
+    a: Option<Box<impl Stream<Item = RelayPoolNotification> + 'a>>,
 ) -> impl Stream<Item = X> + 'a {
+    let config = gnostique.stream_config();
     // A feedback from processing functions. If they need something,
     // they can ask by sending a message to `tx`.
-    let (feedback, rx) = mpsc::channel(10);
-    tokio::spawn(deal_with_feedback(gnostique.clone(), rx));
+    let (feedback, rx) = mpsc::channel(config.feedback_queue);
+    // Results of feedback that come later are joined into the output stream.
+    let (delayed, delayed_rx) = mpsc::channel(10);
+    tokio::spawn(deal_with_feedback(gnostique.clone(), rx, delayed));
+    tokio::spawn(report_metrics(gnostique.clone()));
+    // Raw events that the debug lane has no time for are dropped.
+    let (raw, raw_rx) = mpsc::channel(10);
+    let relay_feedback = feedback.clone();
+    let own_feedback = feedback.clone();
 
-    let sss = match a {
+    let notifications = match a {
         Some(s) => (*s).left_stream(),
         None => BroadcastStream::new(gnostique.client().notifications())
-            .filter_map(|r| async {
-                if let Ok(RelayPoolNotification::Event(relay, event)) = r {
-                    Some((relay, event))
-                } else {
-                    // println!("\n{r:?}\n");
-                    None
-                }
-            })
+            .filter_map(|r| future::ready(r.ok()))
             .right_stream(),
     };
 
-    sss.then(|(relay, event)| async {
-        offer_relays(gnostique, &relay, &event).await;
-        (relay, event)
-    })
-    .map(move |(relay, event)| received_event(gnostique, feedback.clone(), relay, event))
-    .buffer_unordered(64)
-    .filter_map(future::ready)
+    let sss = notifications
+        .filter_map(|n| async {
+            match n {
+                RelayPoolNotification::Event(relay, event) => Some((relay, event)),
+                RelayPoolNotification::Message(
+                    _,
+                    RelayMessage::Event {
+                        subscription_id, ..
+                    },
+                ) => {
+                    gnostique.subscriptions().count(&subscription_id);
+                    None
+                }
+                // println!("\n{n:?}\n");
+                _ => None,
+            }
+        })
+        .inspect(move |(relay, event)| {
+            let stats = gnostique.kind_stats();
+            stats.count(event.kind.as_u64());
+            if stats.let_through() {
+                let _ = raw.try_send(X::Raw {
+                    relay: relay.clone(),
+                    event: event.clone(),
+                });
+            }
+        })
+        // Expired events are not supposed to be displayed or stored (NIP-40).
+        .filter(|(_, event)| future::ready(!event.is_expired()))
+        .filter(|(_, event)| future::ready(enough_pow(gnostique, event)))
+        .then(move |(relay, event)| {
+            let feedback = relay_feedback.clone();
+            async move {
+                offer_relays(gnostique, &feedback, &relay, &event).await;
+                (relay, event)
+            }
+        })
+        .map(move |(relay, event)| received_event(gnostique, feedback.clone(), relay, event))
+        .buffer_unordered(config.concurrency)
+        .filter_map(future::ready);
+
+    // Our events come as soon as they are published, relays send them
+    // back later, if at all.
+    let own = BroadcastStream::new(gnostique.outbox().published())
+        .filter_map(|r| future::ready(r.ok()))
+        .then(move |event| received_own_event(gnostique, own_feedback.clone(), event))
+        .filter_map(future::ready);
+
+    stream::select(
+        stream::select(stream::select(sss, own), ReceiverStream::new(delayed_rx)),
+        ReceiverStream::new(raw_rx),
+    )
+}
+
+/// Whether `event` satisfies the minimal proof of work required from
+/// text notes. Our own notes always do.
+fn enough_pow(gnostique: &Gnostique, event: &Event) -> bool {
+    event.kind != Kind::TextNote
+        || event.pubkey == gnostique.client().keys().public_key()
+        || event.pow() >= gnostique.min_pow()
+}
+
+/// Logs how the processing keeps up, every [`METRICS_INTERVAL`].
+async fn report_metrics(gnostique: Gnostique) {
+    let mut interval = tokio::time::interval(METRICS_INTERVAL);
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        let metrics = gnostique.stream_metrics();
+        debug!(
+            in_flight = metrics.in_flight,
+            feedback_queued = metrics.feedback_queued,
+            feedback_dropped = metrics.feedback_dropped,
+            latencies = ?metrics.latencies,
+            "Stream: {}",
+            metrics
+        );
+    }
+}
+
+/// Asks for something by sending `f` to feedback. If the queue is full,
+/// the request is dropped and counted, rather than holding up processing.
+fn ask(gnostique: &Gnostique, feedback: &mpsc::Sender<Feedback>, f: Feedback) {
+    match feedback.try_send(f) {
+        Ok(()) => gnostique.stream_stats().queued(),
+        Err(mpsc::error::TrySendError::Full(f)) => {
+            gnostique.stream_stats().dropped();
+            debug!("Feedback queue is full, dropping {:?}", f);
+        }
+        // Nobody listens when Gnostique is ending.
+        Err(mpsc::error::TrySendError::Closed(_)) => {}
+    }
 }
 
 /// Listens to incoming messages asking for some additional actions or data
 /// and processes them.
-async fn deal_with_feedback(gnostique: Gnostique, rx: mpsc::Receiver<Feedback>) {
+async fn deal_with_feedback(
+    gnostique: Gnostique,
+    rx: mpsc::Receiver<Feedback>,
+    delayed: mpsc::Sender<X>,
+) {
+    // Authors waiting for an avatar being downloaded, so that concurrent
+    // requests for the same URL result in only one download.
+    let avatars: Arc<Mutex<HashMap<Url, HashSet<XOnlyPublicKey>>>> = Default::default();
+
     ReceiverStream::new(rx)
         .for_each(|f| async {
+            gnostique.stream_stats().dequeued();
             match f {
-                Feedback::NeedMetadata { relay, pubkey } => {
-                    gnostique.demand().metadata(pubkey, relay).await;
+                Feedback::NeedMetadata {
+                    relay,
+                    pubkey,
+                    mut hinted,
+                } => {
+                    // The delivering relay may just pass the note along,
+                    // the author's own relays are more likely to know them.
+                    let mut relays: Vec<Url> = relay
+                        .as_ref()
+                        .and_then(normalize_relay_url)
+                        .into_iter()
+                        .collect();
+                    for r in gnostique.gossip().relays_of(pubkey).await {
+                        if !relays.contains(&r) {
+                            relays.push(r);
+                        }
+                    }
+                    // Relays from their NIP-05 are tried as if a link hinted them.
+                    for r in gnostique.gossip().nip05_relays(pubkey).await {
+                        if !relays.contains(&r) && !hinted.contains(&r) {
+                            hinted.push(r);
+                        }
+                    }
+                    gnostique.demand().metadata(pubkey, relays, hinted).await;
                 }
-                Feedback::NeedNote { event_id, relay } => {
-                    gnostique.demand().text_note(event_id, relay).await;
+                Feedback::NeedNote {
+                    event_id,
+                    relay,
+                    hinted,
+                } => {
+                    gnostique.demand().text_note(event_id, relay, hinted).await;
+                }
+                Feedback::NeedHandlers => {
+                    let mut recommenders = gnostique.follows().await;
+                    recommenders.push(gnostique.client().keys().public_key());
+                    gnostique.demand().handlers(recommenders).await;
+                }
+                Feedback::Notify {
+                    notification,
+                    event,
+                } => delayed
+                    .send(X::Notification {
+                        notification,
+                        event: *event,
+                    })
+                    .await
+                    .unwrap_or_default(),
+                Feedback::SeenOn { event_id, relay } => delayed
+                    .send(X::SeenOn { event_id, relay })
+                    .await
+                    .unwrap_or_default(),
+                Feedback::DatabaseError { message } => {
+                    delayed.send(X::Error { message }).await.unwrap_or_default()
+                }
+                Feedback::NeedAvatar { pubkey, url } => {
+                    let first = {
+                        let mut waiting = avatars.lock().await;
+                        let pubkeys = waiting.entry(url.clone()).or_default();
+                        pubkeys.insert(pubkey);
+                        pubkeys.len() == 1
+                    };
+
+                    if first {
+                        tokio::spawn(download_avatar(
+                            gnostique.clone(),
+                            url,
+                            avatars.clone(),
+                            delayed.clone(),
+                        ));
+                    }
                 }
             }
         })
         .await
 }
 
+/// Downloads avatar from `url` and announces it to all authors waiting for it.
+async fn download_avatar(
+    gnostique: Gnostique,
+    url: Url,
+    waiting: Arc<Mutex<HashMap<Url, HashSet<XOnlyPublicKey>>>>,
+    delayed: mpsc::Sender<X>,
+) {
+    let result = gnostique
+        .download()
+        .to_cached_file(&url, Media::Avatar)
+        .await;
+    let pubkeys = waiting.lock().await.remove(&url).unwrap_or_default();
+
+    if let Some(file) = result.file() {
+        for pubkey in pubkeys {
+            delayed
+                .send(X::AvatarAvailable {
+                    pubkey,
+                    url: url.clone(),
+                    file: file.clone(),
+                })
+                .await
+                .unwrap_or_default();
+        }
+    }
+}
+
 async fn received_event(
     gnostique: &Gnostique,
     feedback: mpsc::Sender<Feedback>,
     relay: Url,
     event: Event,
 ) -> Option<X> {
+    // Nothing new is taken when Gnostique is ending.
+    let _processing = gnostique.shutdown_state().processing()?;
+    let started = Instant::now();
+    let x = process_event(gnostique, feedback, relay, event).await;
+    gnostique.stream_stats().processed(started.elapsed());
+    x
+}
+
+/// Our `event` just published, processed as if it has arrived, so that
+/// it is displayed without waiting for relays. Only events which lanes
+/// display are, lists are stored already when they are published.
+async fn received_own_event(
+    gnostique: &Gnostique,
+    feedback: mpsc::Sender<Feedback>,
+    event: Event,
+) -> Option<X> {
+    let _processing = gnostique.shutdown_state().processing()?;
+
     match event.kind {
-        Kind::TextNote => Some(received_text_note(gnostique, feedback, relay, event, None).await),
-        Kind::Metadata => Some(received_metadata(gnostique, event).await),
-        Kind::Reaction => event.reacts_to().map(|to| X::Reaction {
-            event_id: to,
-            content: event.content,
-        }),
+        Kind::TextNote => {
+            Some(received_text_note(gnostique, feedback, None, event, None, false).await)
+        }
         Kind::Repost => {
-            if let Ok(inner) = Event::from_json(&event.content) {
-                Some(received_text_note(gnostique, feedback, relay, inner, Some(event)).await)
-            } else {
-                None
+            let inner = Event::from_json(&event.content).ok()?;
+            Some(received_text_note(gnostique, feedback, None, inner, Some(event), false).await)
+        }
+        Kind::Reaction => received_reaction(gnostique, &feedback, event).await,
+        _ => None,
+    }
+}
+
+async fn process_event(
+    gnostique: &Gnostique,
+    feedback: mpsc::Sender<Feedback>,
+    relay: Url,
+    event: Event,
+) -> Option<X> {
+    // Muted authors are not heard at all, neither is what we have
+    // reported and hidden. Delegated events belong to their delegators.
+    let author = event.author();
+    if gnostique.is_muted(event.pubkey).await
+        || (author != event.pubkey && gnostique.is_muted(author).await)
+        || gnostique.is_hidden_by_report(&event).await
+    {
+        return None;
+    }
+
+    if event.pubkey == gnostique.client().keys().public_key() {
+        let remembered = gnostique.remember_own_replaceable(&event).await;
+        check_stored(gnostique, &feedback, remembered).await;
+    }
+
+    match event.kind {
+        Kind::TextNote => {
+            let flooding = flooding(gnostique, &event).await;
+            if flooding == Some(FloodAction::Drop) {
+                return None;
+            }
+            Some(
+                received_text_note(
+                    gnostique,
+                    feedback,
+                    Some(relay),
+                    event,
+                    None,
+                    flooding.is_some(),
+                )
+                .await,
+            )
+        }
+        Kind::Metadata => received_metadata(gnostique, &feedback, &relay, event).await,
+        Kind::Reaction => received_reaction(gnostique, &feedback, event).await,
+        Kind::ContactList => {
+            notify(gnostique, &feedback, &event).await;
+            let stored = gnostique.store_contact_list(&event).await;
+            if check_stored(gnostique, &feedback, stored).await != Some(true) {
+                return None;
+            }
+            Some(X::ContactList {
+                author: event.pubkey,
+                following: event.mentions(),
+            })
+        }
+        Kind::Custom(ZAP_RECEIPT_KIND) => {
+            notify(gnostique, &feedback, &event).await;
+            let stored = gnostique.reactions().store(&event).await;
+            if check_stored(gnostique, &feedback, stored).await == Some(false) {
+                return None;
+            }
+            event.zapped_event().map(|to| X::Zap {
+                event_id: to,
+                sats: event.zap_amount().unwrap_or_default(),
+            })
+        }
+        Kind::Repost => {
+            let flooding = flooding(gnostique, &event).await;
+            if flooding == Some(FloodAction::Drop) {
+                return None;
+            }
+            let inner = Event::from_json(&event.content)
+                .ok()
+                .filter(|e| !e.is_expired() && enough_pow(gnostique, e));
+
+            match inner {
+                Some(inner) if !gnostique.is_muted(inner.pubkey).await => Some(
+                    received_text_note(
+                        gnostique,
+                        feedback,
+                        Some(relay),
+                        inner,
+                        Some(event),
+                        flooding.is_some(),
+                    )
+                    .await,
+                ),
+                _ => None,
             }
         }
+        Kind::Custom(RELAY_LIST_KIND) if event.pubkey == gnostique.client().keys().public_key() => {
+            received_relay_list(gnostique, event).await
+        }
+        Kind::Custom(RELAY_LIST_KIND) => {
+            gnostique.gossip().store(&event).await;
+            None
+        }
+        Kind::Custom(PIN_LIST_KIND) => received_pin_list(gnostique, &feedback, &relay, event).await,
+        Kind::Custom(MUTE_LIST_KIND) if event.pubkey == gnostique.client().keys().public_key() => {
+            match gnostique.merge_mute_list(&event).await {
+                Ok(Some(muted)) if !muted.is_empty() => Some(X::Muted(muted)),
+                Ok(_) => None,
+                Err(e) => {
+                    warn!("Could not merge mute list: {}", e);
+                    None
+                }
+            }
+        }
+        Kind::Custom(BOOKMARKS_KIND) if event.pubkey == gnostique.client().keys().public_key() => {
+            match gnostique.merge_bookmark_list(&event).await {
+                Ok(merged) => merged.map(X::Bookmarks),
+                Err(e) => {
+                    warn!("Could not merge bookmark list: {}", e);
+                    None
+                }
+            }
+        }
+        Kind::Custom(ARTICLE_KIND) => received_article(gnostique, feedback, relay, event).await,
+        kind if kind.as_u64() == HANDLER_KIND || kind.as_u64() == HANDLER_RECOMMENDATION_KIND => {
+            received_handler(gnostique, &feedback, &event).await
+        }
+        kind if is_unsupported_content(kind) => {
+            received_unsupported(gnostique, feedback, relay, event).await
+        }
         _ => None,
     }
 }
 
-async fn received_metadata(gnostique: &Gnostique, event: Event) -> X {
+async fn received_reaction(
+    gnostique: &Gnostique,
+    feedback: &mpsc::Sender<Feedback>,
+    event: Event,
+) -> Option<X> {
+    notify(gnostique, feedback, &event).await;
+    // Reactions delivered by several relays are counted once.
+    let stored = gnostique.reactions().store(&event).await;
+    if check_stored(gnostique, feedback, stored).await == Some(false) {
+        return None;
+    }
+    let emoji = event.reaction_emoji();
+    event.reacts_to().map(|to| X::Reaction {
+        event_id: to,
+        content: event.content,
+        emoji,
+    })
+}
+
+/// What happens to text note or repost `event` if its author is flooding,
+/// `None` if they are not. We, allowed authors and, unless configured
+/// otherwise, people we follow never are.
+async fn flooding(gnostique: &Gnostique, event: &Event) -> Option<FloodAction> {
+    if event.pubkey == gnostique.client().keys().public_key()
+        || !gnostique
+            .flood()
+            .note(event.pubkey, event.id, event.created_at.as_u64())
+    {
+        return None;
+    }
+
+    let spam = gnostique.config().spam;
+    if spam.allowed.contains(&event.pubkey)
+        || (spam.exempt_follows && gnostique.is_followed(event.pubkey).await)
+    {
+        None
+    } else {
+        Some(spam.action)
+    }
+}
+
+/// Records notification about `event`, if it concerns us, and asks
+/// for its announcement. Every event is announced only once.
+async fn notify(gnostique: &Gnostique, feedback: &mpsc::Sender<Feedback>, event: &Event) {
+    let me = gnostique.client().keys().public_key();
+
+    if let Some(mut notification) = gnostique.notifications().detect(me, event).await {
+        if gnostique.is_muted(notification.author).await {
+            return;
+        }
+
+        if gnostique.notifications().record(&notification).await {
+            notification.author_name = gnostique
+                .get_persona(notification.author)
+                .await
+                .and_then(|p| p.name);
+
+            ask(
+                gnostique,
+                feedback,
+                Feedback::Notify {
+                    notification,
+                    event: Box::new(event.clone()),
+                },
+            );
+        }
+    }
+}
+
+async fn received_relay_list(gnostique: &Gnostique, event: Event) -> Option<X> {
+    let mut relays = event.relay_list();
+    relays.sort_by(|a, b| a.url.as_str().cmp(b.url.as_str()));
+
+    // Relays which are used for nothing are not listed.
+    let mut current = gnostique.relay_policies().await;
+    current.retain(|r| r.read || r.write);
+
+    if relays == current {
+        None
+    } else {
+        Some(X::RelayList {
+            event_id: event.id,
+            relays,
+        })
+    }
+}
+
+/// Logs failure to store something into the database. Once failures
+/// keep coming, the window is told that persistence is broken.
+async fn check_stored<T>(
+    gnostique: &Gnostique,
+    feedback: &mpsc::Sender<Feedback>,
+    result: Result<T, String>,
+) -> Option<T> {
+    match result {
+        Ok(value) => {
+            gnostique.db_health().succeeded();
+            Some(value)
+        }
+        Err(message) => {
+            warn!("{}", message);
+            if gnostique.db_health().failed() {
+                ask(gnostique, feedback, Feedback::DatabaseError { message });
+            }
+            None
+        }
+    }
+}
+
+/// Stores pin list of its author and announces the pinned notes, unless
+/// a newer list is already known. Pinned notes not stored yet are asked
+/// for, from relays the list points to and from `relay` it came from.
+async fn received_pin_list(
+    gnostique: &Gnostique,
+    feedback: &mpsc::Sender<Feedback>,
+    relay: &Url,
+    event: Event,
+) -> Option<X> {
+    let stored = gnostique.store_pin_list(&event).await;
+    if check_stored(gnostique, feedback, stored).await != Some(true) {
+        return None;
+    }
+
+    let pins = event.pins();
+    for (event_id, hinted) in &pins {
+        if gnostique.get_textnote(*event_id).await.is_none() {
+            ask(
+                gnostique,
+                feedback,
+                Feedback::NeedNote {
+                    event_id: *event_id,
+                    relay: Some(relay.clone()),
+                    hinted: hinted.iter().cloned().collect(),
+                },
+            );
+        }
+    }
+
+    Some(X::Pins {
+        author: event.pubkey,
+        pinned: pins.into_iter().map(|(id, _)| id).collect(),
+    })
+}
+
+/// Stores metadata of author and announces them, unless newer ones are
+/// already known. Metadata which cannot be read are ignored, their event
+/// is only stored so that it can be looked at. Stale metadata still go
+/// to the history of the author.
+async fn received_metadata(
+    gnostique: &Gnostique,
+    feedback: &mpsc::Sender<Feedback>,
+    relay: &Url,
+    event: Event,
+) -> Option<X> {
+    let parsed = Metadata::from_json(&event.content)
+        .map_err(|e| e.to_string())
+        .and_then(|m| event.as_json().map(|j| (m, j)).map_err(|e| e.to_string()));
+
+    let (metadata, json) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            warn!(
+                "Ignoring invalid metadata of {} from {}: {}",
+                event.pubkey, relay, e
+            );
+            let stored = gnostique.store_event(Some(relay), &event).await;
+            check_stored(gnostique, feedback, stored).await;
+            return None;
+        }
+    };
+
+    let recorded = gnostique.metadata_history().record(&event, &json).await;
+    check_stored(gnostique, feedback, recorded).await;
+
     let pubkey_vec = event.pubkey.serialize().to_vec();
-    let json = event.as_json().unwrap();
+    let created_at = event.created_at.as_i64();
+    let previous_name = gnostique
+        .get_persona(event.pubkey)
+        .await
+        .and_then(|p| p.name);
 
-    let _ = query!(
-        r#"
-INSERT INTO metadata (author, event) VALUES (?, ?)
-ON CONFLICT (author) DO UPDATE SET event = EXCLUDED.event
+    let stored = crate::db::retry_on_busy(|| {
+        query!(
+            r#"
+INSERT INTO metadata (author, event, name, display_name, nip05, created_at)
+VALUES (?, ?, ?, ?, ?, ?)
+ON CONFLICT (author) DO UPDATE SET
+  event = EXCLUDED.event,
+  name = EXCLUDED.name,
+  display_name = EXCLUDED.display_name,
+  nip05 = EXCLUDED.nip05,
+  created_at = EXCLUDED.created_at,
+  -- Verification of another identifier says nothing about the new one.
+  nip05_verified = CASE WHEN EXCLUDED.nip05 IS metadata.nip05 THEN metadata.nip05_verified ELSE NULL END,
+  nip05_failed = CASE WHEN EXCLUDED.nip05 IS metadata.nip05 THEN metadata.nip05_failed ELSE NULL END,
+  nip05_attempts = CASE WHEN EXCLUDED.nip05 IS metadata.nip05 THEN metadata.nip05_attempts ELSE 0 END,
+  nip05_mismatch = CASE WHEN EXCLUDED.nip05 IS metadata.nip05 THEN metadata.nip05_mismatch ELSE FALSE END
+WHERE EXCLUDED.created_at > metadata.created_at
 "#,
-        pubkey_vec,
-        json
-    )
-    .execute(gnostique.pool())
-    .await;
+            pubkey_vec,
+            json,
+            metadata.name,
+            metadata.display_name,
+            metadata.nip05,
+            created_at
+        )
+        .execute(gnostique.pool())
+    })
+    .await
+    .map(|r| r.rows_affected())
+    .map_err(|e| {
+        format!(
+            "Could not store metadata of {} into metadata: {}",
+            event.pubkey, e
+        )
+    });
+
+    // Stale metadata, from a relay that has not heard about newer ones.
+    // Announcing them would show outdated names for a moment.
+    if check_stored(gnostique, feedback, stored).await == Some(0) {
+        return None;
+    }
 
-    let metadata = event.as_metadata().unwrap();
+    // Notes are searchable by the current name of their author.
+    if metadata.name != previous_name {
+        gnostique
+            .reindex_author_name(event.pubkey, metadata.name.as_deref())
+            .await;
+    }
 
     let avatar_url = metadata.picture.as_ref().and_then(|p| Url::parse(p).ok());
     let banner_url = metadata.banner.as_ref().and_then(|p| Url::parse(p).ok());
 
     // If the metadata's picture contains valid URL, download it.
     let avatar = if let Some(ref url) = avatar_url {
-        Some(gnostique.download().to_cached_file(url).await)
+        Some(
+            gnostique
+                .download()
+                .to_cached_file(url, Media::Avatar)
+                .await,
+        )
     } else {
         None
     };
 
-    let verified: bool = if let Some(ref nip05) = metadata.nip05 {
-        verify_nip05(gnostique, event.pubkey, nip05).await
+    let verified = if let Some(ref nip05) = metadata.nip05 {
+        verify_nip05(gnostique, feedback, event.pubkey, nip05).await
     } else {
-        false
+        Nip05Status::Unknown
     };
 
     let p = Persona {
         pubkey: event.pubkey,
         name: metadata.name,
+        display_name: metadata.display_name,
         avatar: avatar_url,
         banner: banner_url,
         about: metadata.about,
         nip05: metadata.nip05,
         nip05_verified: verified,
+        lud16: metadata.lud16,
+        lud06: metadata.lud06,
+        website: metadata.website,
         metadata_json: json,
     };
 
-    X::Metadata {
+    Some(X::Metadata {
         persona: p,
         avatar: avatar.and_then(|d| d.file()),
-    }
+    })
 }
 
+/// Stores text note `event`, possibly reposted by `repost`, and announces
+/// it with whatever is known to display it. `relay` is where it has come
+/// from, our notes just published come from none.
 async fn received_text_note(
     gnostique: &Gnostique,
     feedback: mpsc::Sender<Feedback>,
-    relay: Url,
+    relay: Option<Url>,
     event: Event,
     repost: Option<Event>,
+    flooding: bool,
 ) -> X {
-    gnostique.store_event(&relay, &event).await;
-    let author = gnostique.get_persona(event.pubkey).await;
+    let stored = gnostique.store_event(None, &event).await;
+    let is_new = check_stored(gnostique, &feedback, stored).await;
+    let seen_first = match &relay {
+        Some(relay) => {
+            let seen = gnostique.store_seen_on(event.id, relay).await;
+            check_stored(gnostique, &feedback, seen).await
+        }
+        None => None,
+    };
+
+    let language = crate::language::detect(&event.content);
+    if is_new == Some(true) && language.is_some() {
+        let stored = gnostique
+            .store_language(event.id, language.as_deref())
+            .await;
+        check_stored(gnostique, &feedback, stored).await;
+    }
+
+    // Notes already displayed learn where else they have been seen.
+    if let Some(relay) = relay
+        .as_ref()
+        .filter(|_| is_new == Some(false) && seen_first == Some(true))
+    {
+        ask(
+            gnostique,
+            &feedback,
+            Feedback::SeenOn {
+                event_id: event.id,
+                relay: relay.clone(),
+            },
+        );
+    }
+
+    // Reposts recommend relays of the reposted note and its author.
+    // Delegated notes are attributed to their delegators (NIP-26).
+    let hints = repost.as_ref().unwrap_or(&event).tag_relay_hints();
+    let author_pubkey = event.author();
+    let (author, avatar) = author_of(
+        gnostique,
+        &feedback,
+        relay.as_ref(),
+        author_pubkey,
+        hints.of_pubkey(&author_pubkey),
+    )
+    .await;
 
     // if let Some((root, root_relay)) = event.thread_root() {
     //     feedback
@@ -186,27 +900,34 @@ async fn received_text_note(
     //         .unwrap_or_default();
     // };
 
-    let avatar = match &author {
-        Some(Persona { avatar, .. }) => {
-            // Author is known, let's see if he has a cached avatar
-            avatar
-                .as_ref()
-                .and_then(|url| gnostique.download().cached(url))
-        }
-        None => {
-            // If we do not know the author yet, let us request his metadata.
-            feedback
-                .send(Feedback::NeedMetadata {
+    let mut mentions = Vec::new();
+    for pubkey in event.mentions() {
+        match gnostique.get_persona(pubkey).await {
+            Some(p) => mentions.push(p),
+            None => ask(
+                gnostique,
+                &feedback,
+                Feedback::NeedMetadata {
                     relay: relay.clone(),
-                    pubkey: event.pubkey,
-                })
-                .await
-                .unwrap_or_default();
-            None
+                    pubkey,
+                    hinted: hints.of_pubkey(&pubkey),
+                },
+            ),
         }
-    };
+    }
+
+    let quotes = quotes(gnostique, &feedback, &event).await;
+    let labels = gnostique.labels().of(author_pubkey).await;
+
+    notify(gnostique, &feedback, &event).await;
 
     let relays = gnostique.textnote_relays(event.id).await;
+    // Reactions that have come before the note are displayed right away.
+    let reactions = gnostique.reactions().counts(event.id).await;
+    // Our reposts are not told apart, the note is not ours to wait for.
+    let pending = repost.is_none()
+        && event.pubkey == gnostique.client().keys().public_key()
+        && gnostique.outbox().is_pending(event.id).await;
 
     let (event, repost) = if let Some(r) = repost {
         let author = gnostique.get_persona(r.pubkey).await;
@@ -221,69 +942,823 @@ async fn received_text_note(
         author,
         avatar,
         repost,
+        mentions,
+        quotes,
+        flooding,
+        reactions,
+        language,
+        labels,
+        pending,
+    }
+}
+
+/// Persona of `author` and their cached avatar. Whatever is missing is
+/// asked for, also at relays `hinted` for the author, it will be
+/// announced once available.
+async fn author_of(
+    gnostique: &Gnostique,
+    feedback: &mpsc::Sender<Feedback>,
+    relay: Option<&Url>,
+    author: XOnlyPublicKey,
+    hinted: Vec<Url>,
+) -> (Option<Persona>, Option<PathBuf>) {
+    let persona = gnostique.get_persona(author).await;
+
+    let avatar = match &persona {
+        Some(Persona {
+            avatar: Some(url), ..
+        }) => {
+            // Author is known, let's see if he has a cached avatar.
+            let cached = gnostique.download().cached(url).await;
+
+            if cached.is_none() {
+                // Not yet, it will be announced once downloaded.
+                ask(
+                    gnostique,
+                    feedback,
+                    Feedback::NeedAvatar {
+                        pubkey: author,
+                        url: url.clone(),
+                    },
+                );
+            }
+
+            cached
+        }
+        Some(_) => None,
+        None => {
+            // If we do not know the author yet, let us request his metadata.
+            ask(
+                gnostique,
+                feedback,
+                Feedback::NeedMetadata {
+                    relay: relay.cloned(),
+                    pubkey: author,
+                    hinted,
+                },
+            );
+            None
+        }
+    };
+
+    (persona, avatar)
+}
+
+/// Long-form article, if it is the latest known version.
+async fn received_article(
+    gnostique: &Gnostique,
+    feedback: mpsc::Sender<Feedback>,
+    relay: Url,
+    event: Event,
+) -> Option<X> {
+    let article = Article::from_event(&event)?;
+
+    // Older versions are of no interest.
+    if !gnostique.store_article(&article).await {
+        return None;
+    }
+
+    let hinted = event.tag_relay_hints().of_pubkey(&event.pubkey);
+    let (author, avatar) =
+        author_of(gnostique, &feedback, Some(&relay), event.pubkey, hinted).await;
+
+    Some(X::Article {
+        article: Box::new(article),
+        relays: vec![relay],
+        author,
+        avatar,
+    })
+}
+
+/// Event which Gnostique cannot display, to be shown as a placeholder.
+/// If no application to display it is known, they are asked for.
+async fn received_unsupported(
+    gnostique: &Gnostique,
+    feedback: mpsc::Sender<Feedback>,
+    relay: Url,
+    event: Event,
+) -> Option<X> {
+    let hinted = event.tag_relay_hints().of_pubkey(&event.pubkey);
+    let (author, avatar) =
+        author_of(gnostique, &feedback, Some(&relay), event.pubkey, hinted).await;
+
+    let me = gnostique.client().keys().public_key();
+    let handler = gnostique.handlers().for_kind(event.kind.as_u64(), me).await;
+    if handler.is_none() {
+        ask(gnostique, &feedback, Feedback::NeedHandlers);
+    }
+
+    Some(X::Unsupported {
+        event,
+        relays: vec![relay],
+        author,
+        avatar,
+        handler,
+    })
+}
+
+/// Stores handler information or recommendation `event` and announces
+/// the best handlers of kinds it concerns.
+async fn received_handler(
+    gnostique: &Gnostique,
+    feedback: &mpsc::Sender<Feedback>,
+    event: &Event,
+) -> Option<X> {
+    let kinds = if event.kind.as_u64() == HANDLER_KIND {
+        let stored = gnostique.handlers().store(event).await;
+        check_stored(gnostique, feedback, stored).await??.kinds
+    } else {
+        let stored = gnostique.handlers().store_recommendation(event).await;
+        vec![check_stored(gnostique, feedback, stored).await??]
+    };
+
+    let me = gnostique.client().keys().public_key();
+    let mut best = Vec::new();
+    for kind in kinds {
+        if let Some(handler) = gnostique.handlers().for_kind(kind, me).await {
+            best.push((kind, handler));
+        }
+    }
+
+    (!best.is_empty()).then_some(X::Handlers(best))
+}
+
+/// Stored notes quoted by `event`. Those not stored yet are asked for,
+/// at relays hinted by the quote or by `e` tags of `event`, they will be
+/// displayed once they arrive.
+async fn quotes(
+    gnostique: &Gnostique,
+    feedback: &mpsc::Sender<Feedback>,
+    event: &Event,
+) -> Vec<Quote> {
+    let ours = gnostique.client().relays().await;
+    let hints = event.tag_relay_hints();
+    let mut quotes = Vec::new();
+
+    let mut quoted = crate::content::quoted(&event.content);
+    // Quote reposts may refer to the quoted note only by a `mention` tag.
+    for id in event.quoted_events() {
+        if !quoted.iter().any(|(q, _)| *q == id) {
+            quoted.push((id, vec![]));
+        }
+    }
+
+    for (event_id, mut relays) in quoted.into_iter().take(MAX_QUOTES) {
+        match gnostique.get_textnote(event_id).await {
+            Some(quoted) => quotes.push(Quote {
+                author: gnostique.get_persona(quoted.pubkey).await,
+                event: quoted,
+            }),
+            None => {
+                for r in hints.of_event(&event_id) {
+                    if !relays.contains(&r) {
+                        relays.push(r);
+                    }
+                }
+                ask(
+                    gnostique,
+                    feedback,
+                    Feedback::NeedNote {
+                        event_id,
+                        relay: relays.iter().find(|r| ours.contains_key(r)).cloned(),
+                        hinted: relays,
+                    },
+                )
+            }
+        }
     }
+
+    quotes
 }
 
-async fn offer_relays(gnostique: &Gnostique, relay: &Url, event: &Event) {
-    offer_relay_url(gnostique, relay).await;
+async fn offer_relays(
+    gnostique: &Gnostique,
+    feedback: &mpsc::Sender<Feedback>,
+    relay: &Url,
+    event: &Event,
+) {
+    let bytes = event.as_json().map(|j| j.len()).unwrap_or_default();
+    gnostique.relay_stats().received(relay, bytes);
 
     for r in event.collect_relays() {
-        offer_relay_url(gnostique, &r).await
+        check_stored(gnostique, feedback, offer_relay_url(gnostique, &r).await).await;
     }
 }
 
-async fn offer_relay_url(gnostique: &Gnostique, relay: &Url) {
-    let url_s = relay.to_string();
-    let _ = query!(
-        "INSERT INTO relays(url) VALUES (?) ON CONFLICT(url) DO NOTHING",
-        url_s
+/// Stores recommended `relay`, unless it has been seen in this session
+/// already. When it has been seen is written in batches, see [`RelayStats`].
+///
+/// [`RelayStats`]: crate::relay_stats::RelayStats
+async fn offer_relay_url(gnostique: &Gnostique, relay: &Url) -> Result<(), String> {
+    let url = match normalize_relay_url(relay) {
+        Some(url) => url,
+        None => return Ok(()),
+    };
+    if !gnostique.relay_stats().seen(&url) {
+        return Ok(());
+    }
+
+    let url_s = url.to_string();
+    let now = Timestamp::now().as_i64();
+    query!(
+        r#"
+INSERT INTO relays(url, first_seen_at, last_seen_at) VALUES (?1, ?2, ?2)
+ON CONFLICT(url) DO NOTHING"#,
+        url_s,
+        now
     )
     .execute(gnostique.pool())
-    .await;
+    .await
+    .map(|_| ())
+    .map_err(|e| format!("Could not offer {} into relays: {}", relay, e))
 }
 
-async fn verify_nip05(gnostique: &Gnostique, pubkey: XOnlyPublicKey, nip05: &str) -> bool {
+async fn verify_nip05(
+    gnostique: &Gnostique,
+    feedback: &mpsc::Sender<Feedback>,
+    pubkey: XOnlyPublicKey,
+    nip05: &str,
+) -> Nip05Status {
     let pubkey_bytes = pubkey.serialize().to_vec();
     // If the nip05 is already verified and not for too long, just confirm.
+    // If it has failed recently, it is not tried again either.
     let x = query!(
         r#"
-SELECT (unixepoch('now') - unixepoch(nip05_verified)) / 60 / 60 AS "hours?: u32"
+SELECT
+  (unixepoch('now') - unixepoch(nip05_verified)) / 60 / 60 AS "hours?: u32",
+  unixepoch(nip05_verified) AS "verified_at?: i64",
+  unixepoch(nip05_failed) AS "failed_at?: i64",
+  nip05_attempts AS "attempts!: u32",
+  nip05_mismatch AS "mismatch!: bool"
 FROM metadata WHERE author = ?"#,
         pubkey_bytes
     )
     .fetch_optional(gnostique.pool())
     .await;
 
-    if let Ok(result) = x {
-        let x = result.and_then(|r| r.hours);
+    let record = match x {
+        Ok(Some(record)) => record,
+        Ok(None) => return Nip05Status::Unknown,
+        Err(e) => {
+            warn!("Could not find NIP-05 verification of {}: {}", pubkey, e);
+            return Nip05Status::Unknown;
+        }
+    };
 
-        match x {
-            Some(hours) if hours < 12 => {
-                info!("NIP05: {} verified {} hours ago", nip05, hours);
-                true
+    if let (Some(hours), Some(verified_at)) = (record.hours, record.verified_at) {
+        if hours < 12 {
+            info!("NIP05: {} verified {} hours ago", nip05, hours);
+            return Nip05Status::Verified(Timestamp::from(verified_at as u64));
+        }
+    }
+
+    if let Some(failed_at) = record.failed_at {
+        let now = Timestamp::now().as_i64();
+        if !crate::nip05::retry_due(failed_at, record.attempts, record.mismatch, now) {
+            info!(
+                "NIP05: {} failed {} times, not verifying again yet.",
+                nip05, record.attempts
+            );
+            return if record.mismatch {
+                Nip05Status::Mismatch
+            } else {
+                Nip05Status::Failed
+            };
+        }
+    }
+
+    // It is not verified yet, or it has been verified too long ago.
+    info!("NIP05: Verifying {}.", nip05);
+    match crate::nip05::verify(gnostique.download().http(), pubkey, nip05).await {
+        Ok(relays) => {
+            gnostique.gossip().store_nip05_relays(pubkey, &relays).await;
+
+            let stored = query!(
+                r#"
+UPDATE metadata
+SET nip05_verified = datetime('now'), nip05_failed = NULL, nip05_attempts = 0, nip05_mismatch = FALSE
+WHERE author = ?"#,
+                pubkey_bytes
+            )
+            .execute(gnostique.pool())
+            .await
+            .map_err(|e| format!("Could not store NIP-05 verification of {}: {}", pubkey, e));
+            check_stored(gnostique, feedback, stored).await;
+
+            info!("NIP05: {} verified.", nip05);
+            Nip05Status::Verified(Timestamp::now())
+        }
+        Err(failure) => {
+            // Relays are forgotten only when they have not been the user's.
+            let mismatch = failure.is_mismatch();
+            if mismatch {
+                gnostique.gossip().store_nip05_relays(pubkey, &[]).await;
             }
-            _ => {
-                info!("NIP05: Verifying {}.", nip05);
-                // If it's not yet verified or been verified for very long, update.
-                if nip05::verify(pubkey, nip05, None).await.is_ok() {
-                    let _ = query!(
-                        r#"
-UPDATE metadata SET nip05_verified = datetime('now')
+
+            let stored = query!(
+                r#"
+UPDATE metadata
+SET nip05_failed = datetime('now'), nip05_attempts = nip05_attempts + 1, nip05_mismatch = ?
 WHERE author = ?"#,
-                        pubkey_bytes
-                    )
-                    .execute(gnostique.pool())
-                    .await;
-
-                    info!("NIP05: {} verified.", nip05);
-                    true
-                } else {
-                    info!("NIP05: {} verification failed.", nip05);
-                    false
+                mismatch,
+                pubkey_bytes
+            )
+            .execute(gnostique.pool())
+            .await
+            .map_err(|e| format!("Could not store NIP-05 verification of {}: {}", pubkey, e));
+            check_stored(gnostique, feedback, stored).await;
+
+            info!("NIP05: {} verification failed: {}", nip05, failure);
+            if mismatch {
+                Nip05Status::Mismatch
+            } else {
+                Nip05Status::Failed
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Fixtures, TestDb};
+
+    fn relay() -> Url {
+        Url::parse("wss://relay.example.com").unwrap()
+    }
+
+    async fn metadata_rows(db: &TestDb, author: XOnlyPublicKey) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM metadata WHERE author = ?")
+            .bind(author.serialize().to_vec())
+            .fetch_one(db.pool())
+            .await
+            .unwrap()
+    }
+
+    /// Stores metadata of `pubkey` whose NIP-05 has last been verified,
+    /// or has failed, as long ago as SQLite modifiers such as `-1 hours`
+    /// tell.
+    async fn nip05_record(
+        db: &TestDb,
+        pubkey: XOnlyPublicKey,
+        verified: Option<&str>,
+        failed: Option<&str>,
+        attempts: u32,
+        mismatch: bool,
+    ) {
+        sqlx::query(
+            r#"
+INSERT INTO metadata (author, event, nip05, nip05_verified, nip05_failed, nip05_attempts, nip05_mismatch)
+VALUES (?, '{}', 'alice@example.com', datetime('now', ?), datetime('now', ?), ?, ?)"#,
+        )
+        .bind(pubkey.serialize().to_vec())
+        .bind(verified)
+        .bind(failed)
+        .bind(attempts)
+        .bind(mismatch)
+        .execute(db.pool())
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn notifications_come_out_processed() {
+        let db = TestDb::in_memory().await.unwrap();
+        let gnostique = db.gnostique(&Keys::generate());
+        let author = Fixtures::new();
+        let metadata = author.metadata("Alice", Timestamp::now());
+        let note = author.text_note("Hello");
+
+        let notifications = vec![
+            RelayPoolNotification::Event(relay(), metadata),
+            // Not an event, nothing comes of it.
+            RelayPoolNotification::Shutdown,
+            RelayPoolNotification::Event(relay(), note.clone()),
+        ];
+        let received: Vec<X> = tokio::time::timeout(
+            Duration::from_secs(10),
+            x(&gnostique, Some(Box::new(stream::iter(notifications))))
+                .take(2)
+                .collect(),
+        )
+        .await
+        .expect("Both events are processed");
+
+        assert!(received.iter().any(|x| matches!(
+            x,
+            X::Metadata { persona, .. } if persona.pubkey == author.public_key()
+                && persona.name.as_deref() == Some("Alice")
+        )));
+        assert!(received
+            .iter()
+            .any(|x| matches!(x, X::TextNote { event, .. } if event.id == note.id)));
+        assert_eq!(gnostique.get_textnote(note.id).await, Some(note));
+    }
+
+    #[tokio::test]
+    async fn malformed_metadata_are_stored_raw_and_do_not_stop_stream() {
+        let db = TestDb::in_memory().await.unwrap();
+        let gnostique = db.gnostique(&Keys::generate());
+        let author = Fixtures::new();
+        let malformed = author.sign(Kind::Metadata, "{\"name\": ", &[], Timestamp::now());
+        let note = author.text_note("Still here");
+
+        let notifications = vec![
+            RelayPoolNotification::Event(relay(), malformed.clone()),
+            RelayPoolNotification::Event(relay(), note.clone()),
+        ];
+        let received: Vec<X> = tokio::time::timeout(
+            Duration::from_secs(10),
+            x(&gnostique, Some(Box::new(stream::iter(notifications))))
+                .take(1)
+                .collect(),
+        )
+        .await
+        .expect("Note after malformed metadata is processed");
+
+        assert!(matches!(
+            received.as_slice(),
+            [X::TextNote { event, author: None, .. }] if event.id == note.id
+        ));
+        // Events are processed concurrently, the malformed ones may still be stored.
+        let stored = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                match gnostique.get_textnote(malformed.id).await {
+                    Some(event) => break event,
+                    None => tokio::time::sleep(Duration::from_millis(10)).await,
                 }
             }
+        })
+        .await
+        .expect("Malformed metadata are stored");
+        assert_eq!(stored, malformed);
+        assert_eq!(metadata_rows(&db, author.public_key()).await, 0);
+    }
+
+    #[tokio::test]
+    async fn metadata_are_kept_once_per_author() {
+        let db = TestDb::new().await.unwrap();
+        let gnostique = db.gnostique(&Keys::generate());
+        let (feedback, _rx) = mpsc::channel(10);
+        let author = Fixtures::new();
+
+        let first = author.metadata("Alice", Timestamp::from(100));
+        let x = received_metadata(&gnostique, &feedback, &relay(), first).await;
+        assert!(
+            matches!(x, Some(X::Metadata { ref persona, .. }) if persona.name.as_deref() == Some("Alice"))
+        );
+
+        let newer = author.metadata("Alicia", Timestamp::from(200));
+        let x = received_metadata(&gnostique, &feedback, &relay(), newer).await;
+        assert!(
+            matches!(x, Some(X::Metadata { ref persona, .. }) if persona.name.as_deref() == Some("Alicia"))
+        );
+
+        let older = author.metadata("Al", Timestamp::from(150));
+        assert!(received_metadata(&gnostique, &feedback, &relay(), older)
+            .await
+            .is_none());
+
+        assert_eq!(metadata_rows(&db, author.public_key()).await, 1);
+        let persona = gnostique.get_persona(author.public_key()).await.unwrap();
+        assert_eq!(persona.name.as_deref(), Some("Alicia"));
+    }
+
+    #[tokio::test]
+    async fn newer_metadata_win_in_either_order() {
+        let db = TestDb::new().await.unwrap();
+        let gnostique = db.gnostique(&Keys::generate());
+        let (feedback, _rx) = mpsc::channel(10);
+
+        for newer_first in [false, true] {
+            let author = Fixtures::new();
+            let old = author.metadata("Old", Timestamp::from(100));
+            let new = author.metadata("New", Timestamp::from(200));
+            let arrivals = if newer_first { [new, old] } else { [old, new] };
+
+            for event in arrivals {
+                received_metadata(&gnostique, &feedback, &relay(), event).await;
+            }
+
+            let persona = gnostique.get_persona(author.public_key()).await.unwrap();
+            assert_eq!(
+                persona.name.as_deref(),
+                Some("New"),
+                "newer first: {newer_first}"
+            );
+            assert_eq!(metadata_rows(&db, author.public_key()).await, 1);
         }
-    } else {
-        false
+    }
+
+    #[tokio::test]
+    async fn note_of_unknown_author_asks_for_metadata() {
+        let db = TestDb::new().await.unwrap();
+        let gnostique = db.gnostique(&Keys::generate());
+        let (feedback, mut rx) = mpsc::channel(10);
+        let author = Fixtures::new();
+
+        let note = author.text_note("Hello");
+        let x = received_text_note(&gnostique, feedback, Some(relay()), note, None, false).await;
+        assert!(matches!(x, X::TextNote { author: None, .. }));
+
+        match rx.try_recv() {
+            Ok(Feedback::NeedMetadata {
+                relay: r, pubkey, ..
+            }) => {
+                assert_eq!(pubkey, author.public_key());
+                assert_eq!(r, Some(relay()));
+            }
+            other => panic!("Expected request for metadata, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn note_of_known_author_comes_with_persona() {
+        let db = TestDb::new().await.unwrap();
+        let gnostique = db.gnostique(&Keys::generate());
+        let (feedback, mut rx) = mpsc::channel(10);
+        let author = Fixtures::new();
+
+        let metadata = author.metadata("Alice", Timestamp::now());
+        received_metadata(&gnostique, &feedback, &relay(), metadata).await;
+
+        let note = author.text_note("Hello");
+        let x = received_text_note(&gnostique, feedback, Some(relay()), note, None, false).await;
+        match x {
+            X::TextNote {
+                author: Some(persona),
+                ..
+            } => assert_eq!(persona.name.as_deref(), Some("Alice")),
+            other => panic!("Expected note with author, got {other:?}"),
+        }
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn replies_come_with_what_they_reply_to() {
+        let db = TestDb::new().await.unwrap();
+        let gnostique = db.gnostique(&Keys::generate());
+        let (feedback, _rx) = mpsc::channel(10);
+        let chain = Fixtures::new().reply_chain(3);
+
+        let mut parents = Vec::new();
+        for note in &chain {
+            let x = received_text_note(
+                &gnostique,
+                feedback.clone(),
+                None,
+                note.clone(),
+                None,
+                false,
+            )
+            .await;
+            match x {
+                X::TextNote { event, .. } => parents.push(event.replies_to()),
+                other => panic!("Expected note, got {other:?}"),
+            }
+        }
+
+        assert_eq!(parents, vec![None, Some(chain[0].id), Some(chain[1].id)]);
+        assert_eq!(chain[2].thread_root().map(|(id, _)| id), Some(chain[0].id));
+    }
+
+    #[tokio::test]
+    async fn reaction_is_counted_once() {
+        let db = TestDb::new().await.unwrap();
+        let gnostique = db.gnostique(&Keys::generate());
+        let (feedback, _rx) = mpsc::channel(10);
+        let note = Fixtures::new().text_note("Hello");
+        let reaction = Fixtures::new().reaction(&note, "+");
+
+        let x = received_reaction(&gnostique, &feedback, reaction.clone()).await;
+        assert!(matches!(x, Some(X::Reaction { event_id, .. }) if event_id == note.id));
+        // The same reaction from another relay.
+        assert!(received_reaction(&gnostique, &feedback, reaction)
+            .await
+            .is_none());
+        assert_eq!(gnostique.reactions().counts(note.id).await.likes, 1);
+    }
+
+    #[tokio::test]
+    async fn reactions_before_note_are_counted_from_first_render() {
+        let db = TestDb::new().await.unwrap();
+        let gnostique = db.gnostique(&Keys::generate());
+        let (feedback, _rx) = mpsc::channel(10);
+        let note = Fixtures::new().text_note("Hello");
+
+        // Reactions come first, while nothing displays the note.
+        for content in ["+", "🤙", "-"] {
+            let reaction = Fixtures::new().reaction(&note, content);
+            let x = received_reaction(&gnostique, &feedback, reaction).await;
+            assert!(matches!(x, Some(X::Reaction { event_id, .. }) if event_id == note.id));
+        }
+
+        let x = received_text_note(&gnostique, feedback.clone(), None, note, None, false).await;
+        match x {
+            X::TextNote { reactions, .. } => assert_eq!(
+                reactions,
+                ReactionCounts {
+                    likes: 2,
+                    dislikes: 1,
+                    ..Default::default()
+                }
+            ),
+            other => panic!("Expected note, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn reactions_after_note_come_as_increments() {
+        let db = TestDb::new().await.unwrap();
+        let gnostique = db.gnostique(&Keys::generate());
+        let (feedback, _rx) = mpsc::channel(10);
+        let note = Fixtures::new().text_note("Hello");
+
+        let x = received_text_note(
+            &gnostique,
+            feedback.clone(),
+            None,
+            note.clone(),
+            None,
+            false,
+        )
+        .await;
+        assert!(
+            matches!(x, X::TextNote { reactions, .. } if reactions == ReactionCounts::default())
+        );
+
+        let reaction = Fixtures::new().reaction(&note, "+");
+        let x = received_reaction(&gnostique, &feedback, reaction).await;
+        assert!(matches!(x, Some(X::Reaction { event_id, .. }) if event_id == note.id));
+        assert_eq!(gnostique.reactions().counts(note.id).await.likes, 1);
+    }
+
+    #[tokio::test]
+    async fn own_notes_come_before_relays_send_them_back() {
+        let db = TestDb::in_memory().await.unwrap();
+        let me = Fixtures::new();
+        let gnostique = db.gnostique(me.keys());
+        let note = me.text_note("Hello");
+
+        // Relays send nothing at all.
+        let own = x(&gnostique, Some(Box::new(stream::pending())));
+        gnostique.outbox().publish(note.clone()).await.unwrap();
+        let received: Vec<X> = tokio::time::timeout(Duration::from_secs(10), own.take(1).collect())
+            .await
+            .expect("Published note is processed");
+
+        assert!(matches!(
+            received.as_slice(),
+            [X::TextNote { event, pending: true, .. }] if event.id == note.id
+        ));
+        assert_eq!(gnostique.get_textnote(note.id).await, Some(note));
+    }
+
+    #[tokio::test]
+    async fn own_notes_are_pending_until_accepted() {
+        let db = TestDb::in_memory().await.unwrap();
+        let me = Fixtures::new();
+        let gnostique = db.gnostique(me.keys());
+        let (feedback, _rx) = mpsc::channel(10);
+        let note = me.text_note("Hello");
+        gnostique
+            .outbox()
+            .publish_to(&note, &[relay()])
+            .await
+            .unwrap();
+
+        let x = received_own_event(&gnostique, feedback.clone(), note.clone()).await;
+        assert!(matches!(x, Some(X::TextNote { pending: true, .. })));
+
+        gnostique
+            .outbox()
+            .acknowledge(&relay(), note.id, true, "")
+            .await;
+        // The copy sent back by the relay.
+        let x = received_text_note(&gnostique, feedback, Some(relay()), note, None, false).await;
+        assert!(matches!(x, X::TextNote { pending: false, .. }));
+    }
+
+    #[tokio::test]
+    async fn only_own_notes_are_pending() {
+        let db = TestDb::in_memory().await.unwrap();
+        let me = Fixtures::new();
+        let gnostique = db.gnostique(me.keys());
+        let (feedback, _rx) = mpsc::channel(10);
+
+        // A note of someone else in our outbox, such as one we broadcast.
+        let by_other = Fixtures::new().text_note("Hello");
+        gnostique
+            .outbox()
+            .publish_to(&by_other, &[relay()])
+            .await
+            .unwrap();
+        let x = received_own_event(&gnostique, feedback.clone(), by_other.clone()).await;
+        assert!(matches!(x, Some(X::TextNote { pending: false, .. })));
+
+        // Our repost of the note is displayed as the note.
+        let repost = me.sign(
+            Kind::Repost,
+            &by_other.as_json().unwrap(),
+            &[Tag::Event(by_other.id, None, None)],
+            Timestamp::now(),
+        );
+        gnostique.outbox().publish(repost.clone()).await.unwrap();
+        let x = received_own_event(&gnostique, feedback.clone(), repost).await;
+        assert!(matches!(
+            x,
+            Some(X::TextNote { event, repost: Some(_), pending: false, .. }) if event.id == by_other.id
+        ));
+
+        // Lists are not displayed.
+        let contacts = me.sign(Kind::ContactList, "", &[], Timestamp::now());
+        assert!(received_own_event(&gnostique, feedback, contacts)
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn own_reactions_are_counted_once() {
+        let db = TestDb::in_memory().await.unwrap();
+        let me = Fixtures::new();
+        let gnostique = db.gnostique(me.keys());
+        let (feedback, _rx) = mpsc::channel(10);
+        let note = Fixtures::new().text_note("Hello");
+        let reaction = me.reaction(&note, "+");
+
+        let x = received_own_event(&gnostique, feedback.clone(), reaction.clone()).await;
+        assert!(matches!(x, Some(X::Reaction { event_id, .. }) if event_id == note.id));
+        // The copy sent back by a relay.
+        assert!(received_reaction(&gnostique, &feedback, reaction)
+            .await
+            .is_none());
+        assert_eq!(gnostique.reactions().counts(note.id).await.likes, 1);
+    }
+
+    #[tokio::test]
+    async fn nip05_is_not_verified_again_too_soon() {
+        let db = TestDb::new().await.unwrap();
+        let gnostique = db.gnostique(&Keys::generate());
+        let (feedback, _rx) = mpsc::channel(10);
+        let nip05 = "alice@example.com";
+
+        // None of these go to the network.
+        let unknown = Keys::generate().public_key();
+        assert_eq!(
+            verify_nip05(&gnostique, &feedback, unknown, nip05).await,
+            Nip05Status::Unknown
+        );
+
+        let verified = Keys::generate().public_key();
+        nip05_record(&db, verified, Some("-1 hours"), None, 0, false).await;
+        assert!(matches!(
+            verify_nip05(&gnostique, &feedback, verified, nip05).await,
+            Nip05Status::Verified(_)
+        ));
+
+        let unreachable = Keys::generate().public_key();
+        nip05_record(&db, unreachable, None, Some("-10 minutes"), 1, false).await;
+        assert_eq!(
+            verify_nip05(&gnostique, &feedback, unreachable, nip05).await,
+            Nip05Status::Failed
+        );
+
+        let mismatch = Keys::generate().public_key();
+        nip05_record(&db, mismatch, None, Some("-2 days"), 1, true).await;
+        assert_eq!(
+            verify_nip05(&gnostique, &feedback, mismatch, nip05).await,
+            Nip05Status::Mismatch
+        );
+    }
+
+    #[tokio::test]
+    async fn recommended_relays_are_stored_once() {
+        let db = TestDb::new().await.unwrap();
+        let gnostique = db.gnostique(&Keys::generate());
+        let (feedback, _rx) = mpsc::channel(10);
+        let author = Fixtures::new();
+        let other = Fixtures::new();
+
+        let tags = [
+            Tag::PubKey(
+                other.public_key(),
+                Some("wss://Hint.Example.com/".to_string()),
+            ),
+            Tag::Event(
+                other.text_note("Hi").id,
+                Some("wss://hint.example.com".to_string()),
+                None,
+            ),
+        ];
+        let event = author.sign(Kind::TextNote, "Hi there", &tags, Timestamp::now());
+        offer_relays(&gnostique, &feedback, &relay(), &event).await;
+        offer_relays(&gnostique, &feedback, &relay(), &event).await;
+
+        let urls: Vec<String> =
+            sqlx::query_scalar("SELECT url FROM relays WHERE url LIKE '%hint%'")
+                .fetch_all(gnostique.pool())
+                .await
+                .unwrap();
+        assert_eq!(urls, vec!["wss://hint.example.com/".to_string()]);
     }
 }
@@ -0,0 +1,129 @@
+//! How the processing of received events keeps up with relays. When the
+//! database is slow, events pile up in the stream and feedback waits in
+//! its queue, and these numbers show it.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Upper bounds of buckets of processing latency, in milliseconds. The
+/// last bucket holds everything slower.
+const LATENCY_BUCKETS_MS: [u64; 6] = [1, 5, 25, 100, 500, 2500];
+
+#[derive(Debug, Default)]
+pub struct StreamStats {
+    /// Feedback sent and not yet taken by its processing.
+    queued: AtomicUsize,
+    /// Feedback which has not fitted into the full queue.
+    dropped: AtomicU64,
+    latencies: [AtomicU64; LATENCY_BUCKETS_MS.len() + 1],
+}
+
+/// Snapshot of the processing of received events.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StreamMetrics {
+    /// Received events being processed right now.
+    pub in_flight: usize,
+    /// How many events may be processed at once.
+    pub concurrency: usize,
+    /// Feedback waiting in the queue, and how much fits into it.
+    pub feedback_queued: usize,
+    pub feedback_capacity: usize,
+    /// Feedback dropped in this session because the queue was full.
+    pub feedback_dropped: u64,
+    /// Numbers of events processed within each of the latency buckets,
+    /// with their upper bounds; `None` is the bucket of slower ones.
+    pub latencies: Vec<(Option<Duration>, u64)>,
+}
+
+impl StreamStats {
+    /// Counts feedback that has got into the queue.
+    pub fn queued(&self) {
+        self.queued.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Counts feedback that has been taken from the queue.
+    pub fn dequeued(&self) {
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Counts feedback that has not fitted into the queue.
+    pub fn dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Counts a received event which took `latency` to process.
+    pub fn processed(&self, latency: Duration) {
+        let ms = latency.as_millis() as u64;
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|bound| ms <= *bound)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.latencies[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(
+        &self,
+        in_flight: usize,
+        concurrency: usize,
+        feedback_capacity: usize,
+    ) -> StreamMetrics {
+        let bounds = LATENCY_BUCKETS_MS
+            .iter()
+            .map(|ms| Some(Duration::from_millis(*ms)))
+            .chain([None]);
+
+        StreamMetrics {
+            in_flight,
+            concurrency,
+            feedback_queued: self.queued.load(Ordering::SeqCst),
+            feedback_capacity,
+            feedback_dropped: self.dropped.load(Ordering::SeqCst),
+            latencies: bounds
+                .zip(&self.latencies)
+                .map(|(bound, n)| (bound, n.load(Ordering::Relaxed)))
+                .collect(),
+        }
+    }
+}
+
+impl StreamMetrics {
+    /// Upper bound of the bucket which the median latency falls into,
+    /// `None` if it is the slowest bucket or nothing has been processed.
+    pub fn median_latency(&self) -> Option<Duration> {
+        let total: u64 = self.latencies.iter().map(|(_, n)| n).sum();
+        let mut seen = 0;
+        for (bound, n) in &self.latencies {
+            seen += n;
+            if total > 0 && seen * 2 >= total {
+                return *bound;
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Display for StreamMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} in flight, feedback {}/{} queued, {} dropped",
+            self.in_flight,
+            self.concurrency,
+            self.feedback_queued,
+            self.feedback_capacity,
+            self.feedback_dropped
+        )?;
+        match self.median_latency() {
+            Some(bound) => write!(f, ", median latency ≤ {} ms", bound.as_millis()),
+            None if self.latencies.iter().any(|(_, n)| *n > 0) => {
+                write!(
+                    f,
+                    ", median latency over {} ms",
+                    LATENCY_BUCKETS_MS[LATENCY_BUCKETS_MS.len() - 1]
+                )
+            }
+            None => Ok(()),
+        }
+    }
+}
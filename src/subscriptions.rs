@@ -0,0 +1,382 @@
+//! Subscriptions to relays which stay open while somebody needs their
+//! events, such as a lane. Every subscription is held by handles of its
+//! owners, and the last handle dropped closes it on relays, so closed
+//! lanes do not leave relays sending events nobody shows anymore. Owners
+//! asking for the same filters at the same relays share a subscription.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use nostr_sdk::prelude::*;
+use tracing::{debug, warn};
+
+#[derive(Clone)]
+pub struct Subscriptions(Arc<SubscriptionsInner>);
+
+struct SubscriptionsInner {
+    client: Client,
+    /// Open subscriptions by their IDs.
+    active: Mutex<HashMap<String, Active>>,
+    /// Number in the ID of the next subscription.
+    next: AtomicU64,
+}
+
+struct Active {
+    name: String,
+    filters: Vec<SubscriptionFilter>,
+    /// Relays asked, none means all of them.
+    relays: Vec<Url>,
+    /// How many handles hold the subscription.
+    owners: usize,
+    /// Events that have arrived for the subscription.
+    events: u64,
+}
+
+/// What an open subscription asks for, for debugging.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubscriptionInfo {
+    pub id: String,
+    pub name: String,
+    /// Filters as they are sent to relays.
+    pub filters: Vec<String>,
+    pub relays: Vec<Url>,
+    pub owners: usize,
+    pub events: u64,
+}
+
+/// Holds subscription open. Dropping it leaves the subscription to its
+/// other owners, or closes it if there are none.
+pub struct SubscriptionHandle {
+    id: SubscriptionId,
+    subscriptions: Subscriptions,
+    /// Where closing is sent from, handles are dropped in the main
+    /// thread too.
+    runtime: tokio::runtime::Handle,
+}
+
+impl fmt::Debug for SubscriptionHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SubscriptionHandle").field(&self.id).finish()
+    }
+}
+
+impl Subscriptions {
+    pub fn new(client: Client) -> Subscriptions {
+        Subscriptions(Arc::new(SubscriptionsInner {
+            client,
+            active: Default::default(),
+            next: AtomicU64::new(1),
+        }))
+    }
+
+    /// Asks `relays`, or all relays if none are given, for events
+    /// matching `filters` until the returned handle is dropped. `name`
+    /// tells in the debug lane who wants the events.
+    pub async fn subscribe(
+        &self,
+        name: &str,
+        filters: Vec<SubscriptionFilter>,
+        relays: Vec<Url>,
+    ) -> SubscriptionHandle {
+        let shared = self
+            .0
+            .active
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .find_map(|(id, a)| {
+                (a.filters == filters && a.relays == relays).then(|| {
+                    a.owners += 1;
+                    id.clone()
+                })
+            });
+
+        let id = match shared {
+            Some(id) => SubscriptionId::new(id),
+            None => {
+                let n = self.0.next.fetch_add(1, Ordering::Relaxed);
+                let id = SubscriptionId::new(format!("gnostique-{n}"));
+                self.0.active.lock().unwrap().insert(
+                    id.to_string(),
+                    Active {
+                        name: name.to_string(),
+                        filters: filters.clone(),
+                        relays: relays.clone(),
+                        owners: 1,
+                        events: 0,
+                    },
+                );
+                self.send(ClientMessage::new_req(id.clone(), filters), &relays)
+                    .await;
+                id
+            }
+        };
+
+        SubscriptionHandle {
+            id,
+            subscriptions: self.clone(),
+            runtime: tokio::runtime::Handle::current(),
+        }
+    }
+
+    /// Counts an event which has arrived for subscription `id`.
+    pub fn count(&self, id: &SubscriptionId) {
+        if let Some(active) = self.0.active.lock().unwrap().get_mut(&id.to_string()) {
+            active.events += 1;
+        }
+    }
+
+    /// Open subscriptions, in the order they have been opened.
+    pub fn list(&self) -> Vec<SubscriptionInfo> {
+        let mut list: Vec<SubscriptionInfo> = self
+            .0
+            .active
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, a)| SubscriptionInfo {
+                id: id.clone(),
+                name: a.name.clone(),
+                filters: a
+                    .filters
+                    .iter()
+                    .map(|f| serde_json::to_string(f).unwrap_or_default())
+                    .collect(),
+                relays: a.relays.clone(),
+                owners: a.owners,
+                events: a.events,
+            })
+            .collect();
+        list.sort_by_key(|info| number(&info.id));
+        list
+    }
+
+    /// Lets go of subscription `id`, closing it if nobody else holds it.
+    fn release(&self, id: &SubscriptionId) -> Option<Vec<Url>> {
+        let mut active = self.0.active.lock().unwrap();
+        let a = active.get_mut(&id.to_string())?;
+        a.owners -= 1;
+        if a.owners > 0 {
+            return None;
+        }
+        active.remove(&id.to_string()).map(|a| a.relays)
+    }
+
+    async fn send(&self, msg: ClientMessage, relays: &[Url]) {
+        if relays.is_empty() {
+            if let Err(e) = self.0.client.send_msg(msg).await {
+                warn!("Could not send subscription to relays: {}", e);
+            }
+        } else {
+            for url in relays {
+                if let Err(e) = self.0.client.send_msg_to(url.as_str(), msg.clone()).await {
+                    warn!("Could not send subscription to {}: {}", url, e);
+                }
+            }
+        }
+    }
+}
+
+impl SubscriptionHandle {
+    /// Asks the relays for events matching `filters` instead. Relays
+    /// replace subscription with the same ID, unless it is shared, when
+    /// this owner gets a subscription of its own.
+    pub async fn refresh(&mut self, filters: Vec<SubscriptionFilter>) {
+        let (name, relays, alone) = {
+            let mut active = self.subscriptions.0.active.lock().unwrap();
+            let a = match active.get_mut(&self.id.to_string()) {
+                Some(a) => a,
+                None => return,
+            };
+            let alone = a.owners == 1;
+            if alone {
+                a.filters = filters.clone();
+            }
+            (a.name.clone(), a.relays.clone(), alone)
+        };
+
+        if alone {
+            self.subscriptions
+                .send(ClientMessage::new_req(self.id.clone(), filters), &relays)
+                .await;
+        } else {
+            *self = self.subscriptions.subscribe(&name, filters, relays).await;
+        }
+    }
+}
+
+impl Drop for SubscriptionHandle {
+    fn drop(&mut self) {
+        if let Some(relays) = self.subscriptions.release(&self.id) {
+            debug!("Closing subscription {}", self.id.to_string());
+            let subscriptions = self.subscriptions.clone();
+            let id = self.id.clone();
+            self.runtime.spawn(async move {
+                subscriptions.send(ClientMessage::close(id), &relays).await;
+            });
+        }
+    }
+}
+
+impl fmt::Display for SubscriptionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}): {} events", self.name, self.id, self.events)?;
+        if self.owners > 1 {
+            write!(f, ", {} owners", self.owners)?;
+        }
+        if self.relays.is_empty() {
+            write!(f, ", all relays")?;
+        } else {
+            let relays: Vec<&str> = self.relays.iter().map(|u| u.as_str()).collect();
+            write!(f, ", {}", relays.join(" "))?;
+        }
+        for filter in &self.filters {
+            write!(f, "\n    {filter}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Number in subscription `id`, so that they sort as they have been opened.
+fn number(id: &str) -> u64 {
+    id.rsplit('-')
+        .next()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subscriptions() -> Subscriptions {
+        Subscriptions::new(Client::new(&Keys::generate()))
+    }
+
+    fn notes() -> Vec<SubscriptionFilter> {
+        vec![SubscriptionFilter::new().kind(Kind::TextNote).limit(10)]
+    }
+
+    fn relay() -> Url {
+        Url::parse("wss://relay.example.com").unwrap()
+    }
+
+    /// Owners of open subscriptions, in the order they have been opened.
+    fn owners(subscriptions: &Subscriptions) -> Vec<(String, usize)> {
+        subscriptions
+            .list()
+            .into_iter()
+            .map(|s| (s.name, s.owners))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn last_owner_closes_shared_subscription() {
+        let subscriptions = subscriptions();
+
+        let first = subscriptions.subscribe("Lane", notes(), vec![]).await;
+        let second = subscriptions.subscribe("Other lane", notes(), vec![]).await;
+        let elsewhere = subscriptions
+            .subscribe("Relay", notes(), vec![relay()])
+            .await;
+        assert_eq!(first.id, second.id);
+        assert_ne!(first.id, elsewhere.id);
+        assert_eq!(
+            owners(&subscriptions),
+            vec![("Lane".to_string(), 2), ("Relay".to_string(), 1)]
+        );
+
+        drop(first);
+        assert_eq!(
+            owners(&subscriptions),
+            vec![("Lane".to_string(), 1), ("Relay".to_string(), 1)]
+        );
+        drop(second);
+        drop(elsewhere);
+        assert!(subscriptions.list().is_empty());
+    }
+
+    #[tokio::test]
+    async fn refreshed_filters_are_not_forced_on_others() {
+        let subscriptions = subscriptions();
+        let hashtags = vec![SubscriptionFilter::new().hashtag("nostr")];
+
+        let mut alone = subscriptions
+            .subscribe("Alone", notes(), vec![relay()])
+            .await;
+        let id = alone.id.clone();
+        alone.refresh(hashtags.clone()).await;
+        assert_eq!(alone.id, id);
+
+        let mut shared = subscriptions.subscribe("Shared", notes(), vec![]).await;
+        let other = subscriptions.subscribe("Shared", notes(), vec![]).await;
+        shared.refresh(hashtags.clone()).await;
+        assert_ne!(shared.id, other.id);
+
+        let list = subscriptions.list();
+        let filters = |id: &SubscriptionId| {
+            list.iter()
+                .find(|s| s.id == id.to_string())
+                .map(|s| (s.filters.clone(), s.relays.clone(), s.owners))
+        };
+        let json = |f: &[SubscriptionFilter]| -> Vec<String> {
+            f.iter()
+                .map(|f| serde_json::to_string(f).unwrap())
+                .collect()
+        };
+        assert_eq!(
+            filters(&alone.id),
+            Some((json(&hashtags), vec![relay()], 1))
+        );
+        assert_eq!(filters(&other.id), Some((json(&notes()), vec![], 1)));
+        assert_eq!(filters(&shared.id), Some((json(&hashtags), vec![], 1)));
+    }
+
+    #[tokio::test]
+    async fn events_are_counted_per_subscription() {
+        let subscriptions = subscriptions();
+        let mut handles = Vec::new();
+        for n in 0..11 {
+            let filters = vec![SubscriptionFilter::new().limit(n)];
+            handles.push(subscriptions.subscribe("Lane", filters, vec![]).await);
+        }
+        let last = &handles[10].id;
+
+        subscriptions.count(last);
+        subscriptions.count(last);
+        subscriptions.count(&SubscriptionId::new("unknown"));
+
+        let list = subscriptions.list();
+        // Sorted by number, gnostique-10 after gnostique-9.
+        let ids: Vec<u64> = list.iter().map(|s| number(&s.id)).collect();
+        assert_eq!(ids, (1..=11).collect::<Vec<_>>());
+        assert_eq!(list.last().map(|s| s.events), Some(2));
+        assert_eq!(list[0].events, 0);
+    }
+
+    #[test]
+    fn info_tells_what_subscription_asks_for() {
+        let mut info = SubscriptionInfo {
+            id: "gnostique-3".to_string(),
+            name: "Lane".to_string(),
+            filters: vec![r#"{"kinds":[1]}"#.to_string()],
+            relays: vec![],
+            owners: 1,
+            events: 5,
+        };
+        assert_eq!(
+            info.to_string(),
+            "Lane (gnostique-3): 5 events, all relays\n    {\"kinds\":[1]}"
+        );
+
+        info.owners = 2;
+        info.relays = vec![relay()];
+        info.filters = vec![];
+        assert_eq!(
+            info.to_string(),
+            "Lane (gnostique-3): 5 events, 2 owners, wss://relay.example.com/"
+        );
+    }
+}
@@ -0,0 +1,365 @@
+//! Helpers for exercising Gnostique without GTK and without relays:
+//! a database in temporary directory or in memory with all migrations
+//! applied, signed events made by throwaway keys and HTTP server
+//! with canned responses.
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use nostr_sdk::nostr::secp256k1::{Message, Secp256k1};
+use nostr_sdk::prelude::{
+    sign_delegation, Event, EventBuilder, EventId, Keys, Kind, Marker, Metadata, Tag, Timestamp,
+    XOnlyPublicKey,
+};
+use nostr_sdk::Client;
+use reqwest::Url;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::SqlitePool;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use crate::config::{Config, DatabaseConfig};
+use crate::dirs::Dirs;
+use crate::Gnostique;
+
+/// Distinguishes databases of a single process.
+static NEXT_DATABASE: AtomicU32 = AtomicU32::new(0);
+
+/// Database with its own temporary directory, which is removed when dropped.
+#[derive(Debug)]
+pub struct TestDb {
+    dir: PathBuf,
+    pool: SqlitePool,
+}
+
+impl TestDb {
+    /// Creates empty database and runs all migrations on it.
+    pub async fn new() -> Result<TestDb, String> {
+        let dir = temp_dir()?;
+        let pool =
+            crate::db::connect(&dir.join("gnostique.db"), &DatabaseConfig::default()).await?;
+        TestDb::migrated(dir, pool).await
+    }
+
+    /// Creates empty database in memory, only files such as downloads go
+    /// to the temporary directory. Its only connection is never closed,
+    /// as that would lose the database.
+    pub async fn in_memory() -> Result<TestDb, String> {
+        let dir = temp_dir()?;
+        let options: SqliteConnectOptions = "sqlite::memory:"
+            .parse()
+            .map_err(|e: sqlx::Error| e.to_string())?;
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(options.foreign_keys(true))
+            .await
+            .map_err(|e| e.to_string())?;
+        TestDb::migrated(dir, pool).await
+    }
+
+    /// Creates empty database with only migrations older than `version`,
+    /// so that data can be put there before the migration changes them.
+    /// The rest is applied by [`TestDb::migrate`].
+    pub async fn before_migration(version: i64) -> Result<TestDb, String> {
+        let dir = temp_dir()?;
+        let pool =
+            crate::db::connect(&dir.join("gnostique.db"), &DatabaseConfig::default()).await?;
+
+        let mut migrator = sqlx::migrate!();
+        migrator.migrations = migrator
+            .migrations
+            .iter()
+            .filter(|m| m.version < version)
+            .cloned()
+            .collect();
+        migrator.run(&pool).await.map_err(|e| e.to_string())?;
+
+        Ok(TestDb { dir, pool })
+    }
+
+    /// Applies all migrations not applied yet.
+    pub async fn migrate(&self) -> Result<(), String> {
+        sqlx::migrate!()
+            .run(&self.pool)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn migrated(dir: PathBuf, pool: SqlitePool) -> Result<TestDb, String> {
+        let db = TestDb { dir, pool };
+        db.migrate().await?;
+        Ok(db)
+    }
+
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    /// Directories of profile within the temporary directory.
+    pub fn dirs(&self) -> Dirs {
+        Dirs::new(Some(self.dir.clone()), Some(self.dir.join("config.toml")))
+    }
+
+    /// Gnostique of `keys` on top of this database. Its client has
+    /// no relays, so nothing is sent anywhere.
+    pub fn gnostique(&self, keys: &Keys) -> Gnostique {
+        Gnostique::new(
+            self.pool.clone(),
+            self.dirs(),
+            Client::new(keys),
+            vec![],
+            Config::default(),
+            false,
+        )
+    }
+}
+
+/// Creates new temporary directory.
+fn temp_dir() -> Result<PathBuf, String> {
+    let dir = std::env::temp_dir().join(format!(
+        "gnostique-test-{}-{}",
+        std::process::id(),
+        NEXT_DATABASE.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir)
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Temporary directory for files of a test, which is removed when dropped.
+#[derive(Debug)]
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> TempDir {
+        TempDir(temp_dir().unwrap())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Makes signed events of a single author.
+#[derive(Clone, Debug)]
+pub struct Fixtures {
+    keys: Keys,
+}
+
+impl Default for Fixtures {
+    fn default() -> Self {
+        Fixtures::new()
+    }
+}
+
+impl Fixtures {
+    /// Events of newly generated author.
+    pub fn new() -> Fixtures {
+        Fixtures {
+            keys: Keys::generate(),
+        }
+    }
+
+    pub fn public_key(&self) -> XOnlyPublicKey {
+        self.keys.public_key()
+    }
+
+    /// Keys of the author, such as for [`TestDb::gnostique`] of them.
+    pub fn keys(&self) -> &Keys {
+        &self.keys
+    }
+
+    /// Tag of events of `delegatee` delegated by the author (NIP-26).
+    pub fn delegation(&self, delegatee: &Fixtures, conditions: &str) -> Tag {
+        Tag::Delegation {
+            delegator_pk: self.public_key(),
+            conditions: conditions.to_string(),
+            sig: sign_delegation(&self.keys, delegatee.public_key(), conditions.to_string())
+                .unwrap(),
+        }
+    }
+
+    /// Event signed by the author, created at `created_at`.
+    pub fn sign(&self, kind: Kind, content: &str, tags: &[Tag], created_at: Timestamp) -> Event {
+        let pubkey = self.keys.public_key();
+        let id = EventId::new(&pubkey, created_at, &kind, tags, content);
+        let message = Message::from_slice(id.as_bytes()).unwrap();
+
+        Event {
+            id,
+            pubkey,
+            created_at,
+            kind,
+            tags: tags.to_vec(),
+            content: content.to_string(),
+            sig: Secp256k1::new().sign_schnorr(&message, &self.keys.key_pair().unwrap()),
+            ots: None,
+        }
+    }
+
+    pub fn text_note(&self, content: &str) -> Event {
+        EventBuilder::new_text_note(content, &[])
+            .to_event(&self.keys)
+            .unwrap()
+    }
+
+    /// Metadata with just a name, created at `created_at`, so that
+    /// older and newer versions can be told apart.
+    pub fn metadata(&self, name: &str, created_at: Timestamp) -> Event {
+        let metadata = Metadata::new().name(name);
+        self.sign(
+            Kind::Metadata,
+            &metadata.as_json().unwrap(),
+            &[],
+            created_at,
+        )
+    }
+
+    pub fn reaction(&self, event: &Event, content: &str) -> Event {
+        EventBuilder::new_reaction(event.id, event.pubkey, content)
+            .to_event(&self.keys)
+            .unwrap()
+    }
+
+    /// Reply to `parent`, which belongs to thread started by `root`
+    /// (NIP-10 markers), or starts it if `root` is `None`.
+    pub fn reply(&self, parent: &Event, root: Option<&Event>, content: &str) -> Event {
+        let tags = match root {
+            Some(root) => vec![
+                Tag::Event(root.id, None, Some(Marker::Root)),
+                Tag::Event(parent.id, None, Some(Marker::Reply)),
+                Tag::PubKey(parent.pubkey, None),
+            ],
+            None => vec![
+                Tag::Event(parent.id, None, Some(Marker::Root)),
+                Tag::PubKey(parent.pubkey, None),
+            ],
+        };
+
+        EventBuilder::new_text_note(content, &tags)
+            .to_event(&self.keys)
+            .unwrap()
+    }
+
+    /// Thread of `length` notes, each replying to the previous one.
+    /// The first one is the root.
+    pub fn reply_chain(&self, length: usize) -> Vec<Event> {
+        let mut chain: Vec<Event> = Vec::with_capacity(length);
+
+        for i in 0..length {
+            let content = format!("Note {i}");
+            let event = match chain.last() {
+                None => self.text_note(&content),
+                Some(parent) => {
+                    let root = Some(&chain[0]).filter(|r| r.id != parent.id);
+                    self.reply(parent, root, &content)
+                }
+            };
+            chain.push(event);
+        }
+
+        chain
+    }
+}
+
+/// HTTP server on localhost that answers every request by `respond`,
+/// which gets the request, head and body, and returns the whole response.
+/// Requests are remembered, so that tests can check what was asked for.
+pub struct StubServer {
+    address: SocketAddr,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl StubServer {
+    pub async fn new(respond: impl Fn(&str) -> Vec<u8> + Send + Sync + 'static) -> StubServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let requests: Arc<Mutex<Vec<String>>> = Default::default();
+        let respond = Arc::new(respond);
+
+        let received = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let received = received.clone();
+                let respond = respond.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0; 1024];
+                    let body_at = loop {
+                        if let Some(i) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                            break i + 4;
+                        }
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    };
+                    let length = String::from_utf8_lossy(&request[..body_at])
+                        .lines()
+                        .find_map(|l| {
+                            let (name, value) = l.split_once(':')?;
+                            name.eq_ignore_ascii_case("content-length")
+                                .then(|| value.trim().parse::<usize>().ok())?
+                        })
+                        .unwrap_or_default();
+                    while request.len() < body_at + length {
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let request = String::from_utf8_lossy(&request).to_string();
+                    let response = respond(&request);
+                    received.lock().unwrap().push(request);
+                    let _ = stream.write_all(&response).await;
+                    let _ = stream.shutdown().await;
+                });
+            }
+        });
+
+        StubServer { address, requests }
+    }
+
+    /// Server answering every request with `response`.
+    pub async fn always(response: Vec<u8>) -> StubServer {
+        StubServer::new(move |_| response.clone()).await
+    }
+
+    pub fn url(&self, path: &str) -> Url {
+        Url::parse(&format!("http://{}{path}", self.address)).unwrap()
+    }
+
+    /// All requests received so far.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// HTTP response with `status` (such as "200 OK"), `headers` and `body`.
+/// Length of the body is added to the headers.
+pub fn http_response(status: &str, headers: &[(&str, &str)], body: &[u8]) -> Vec<u8> {
+    let mut response = format!("HTTP/1.1 {status}\r\nConnection: close\r\n");
+    for (name, value) in headers {
+        response.push_str(&format!("{name}: {value}\r\n"));
+    }
+    response.push_str(&format!("Content-Length: {}\r\n\r\n", body.len()));
+
+    let mut response = response.into_bytes();
+    response.extend_from_slice(body);
+    response
+}
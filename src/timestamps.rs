@@ -0,0 +1,198 @@
+//! How times at which events were created are shown: relative to now,
+//! such as `5m` or `yesterday`, or as absolute local date and time.
+
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, Utc};
+use nostr_sdk::prelude::Timestamp;
+use serde::{Deserialize, Serialize};
+
+/// Times older than this many days are shown as dates even if relative
+/// times are preferred.
+const RELATIVE_DAYS: i64 = 7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TimeFormat {
+    /// Whether recent times are shown relative to now.
+    pub relative: bool,
+    /// Whether absolute times use 24-hour clock rather than AM and PM.
+    pub clock_24h: bool,
+}
+
+impl Default for TimeFormat {
+    fn default() -> Self {
+        TimeFormat {
+            relative: true,
+            clock_24h: true,
+        }
+    }
+}
+
+impl TimeFormat {
+    fn clock(&self, seconds: bool) -> &'static str {
+        match (self.clock_24h, seconds) {
+            (true, false) => "%H:%M",
+            (true, true) => "%H:%M:%S",
+            (false, false) => "%l:%M %p",
+            (false, true) => "%l:%M:%S %p",
+        }
+    }
+}
+
+fn local(time: Timestamp) -> DateTime<Local> {
+    let utc = NaiveDateTime::from_timestamp_opt(time.as_i64(), 0).unwrap_or_default();
+    DateTime::<Utc>::from_utc(utc, Utc).with_timezone(&Local)
+}
+
+/// Short description of when `created_at` was, as seen at `now`.
+///
+/// Relative times are `now`, `5m`, `3h`, `yesterday` and `4d`.
+/// Older times, and all absolute ones, are local date, such as `3 Mar`,
+/// with year only if it is not this year. Absolute times of today are
+/// just the time of day.
+pub fn format_age(created_at: Timestamp, now: Timestamp, format: TimeFormat) -> String {
+    // Clocks of authors may be ahead a bit.
+    let seconds = (now.as_i64() - created_at.as_i64()).max(0);
+    let (minutes, hours, days) = (seconds / 60, seconds / 3600, seconds / 86400);
+
+    if format.relative && days < RELATIVE_DAYS {
+        return match (minutes, hours, days) {
+            (0, _, _) => "now".to_string(),
+            (m, 0, _) => format!("{m}m"),
+            (_, h, 0) => format!("{h}h"),
+            (_, _, 1) => "yesterday".to_string(),
+            (_, _, d) => format!("{d}d"),
+        };
+    }
+
+    let time = local(created_at);
+    let today = local(now);
+
+    let date = if time.year() == today.year() {
+        "%e %b"
+    } else {
+        "%e %b %Y"
+    };
+
+    let pattern = if format.relative {
+        date.to_string()
+    } else if time.date_naive() == today.date_naive() {
+        format.clock(false).to_string()
+    } else {
+        format!("{date}, {}", format.clock(false))
+    };
+
+    time.format(&pattern).to_string().trim().to_string()
+}
+
+/// Local date and time of `time` to the minute, such as `3 Mar 2023, 14:05`.
+pub fn format_minute(time: Timestamp, format: TimeFormat) -> String {
+    let pattern = format!("%e %b %Y, {}", format.clock(false));
+
+    local(time)
+        .format(&pattern)
+        .to_string()
+        .trim()
+        .replace("  ", " ")
+}
+
+/// Precise local date and time of `created_at`, such as
+/// `Friday, 3 March 2023, 14:05:12`.
+pub fn format_full(created_at: Timestamp, format: TimeFormat) -> String {
+    let pattern = format!("%A, %e %B %Y, {}", format.clock(true));
+
+    local(created_at)
+        .format(&pattern)
+        .to_string()
+        .replace("  ", " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 15 June 2023, noon UTC, far enough from midnight for dates not
+    /// to depend on time zone.
+    const NOW: i64 = 1_686_830_400;
+
+    fn age(seconds_ago: i64, format: TimeFormat) -> String {
+        format_age(
+            Timestamp::from((NOW - seconds_ago) as u64),
+            Timestamp::from(NOW as u64),
+            format,
+        )
+    }
+
+    fn relative(seconds_ago: i64) -> String {
+        age(seconds_ago, TimeFormat::default())
+    }
+
+    #[test]
+    fn recent_times_are_relative() {
+        const MINUTE: i64 = 60;
+        const HOUR: i64 = 60 * MINUTE;
+        const DAY: i64 = 24 * HOUR;
+
+        assert_eq!(relative(0), "now");
+        assert_eq!(relative(59), "now");
+        assert_eq!(relative(61), "1m");
+        assert_eq!(relative(HOUR - 1), "59m");
+        assert_eq!(relative(HOUR), "1h");
+        assert_eq!(relative(23 * HOUR), "23h");
+        assert_eq!(relative(DAY - 1), "23h");
+        assert_eq!(relative(DAY), "yesterday");
+        assert_eq!(relative(2 * DAY - 1), "yesterday");
+        assert_eq!(relative(2 * DAY), "2d");
+        assert_eq!(relative(7 * DAY - 1), "6d");
+        assert_eq!(relative(7 * DAY), "8 Jun");
+        assert_eq!(relative(8 * DAY), "7 Jun");
+    }
+
+    #[test]
+    fn times_ahead_of_now_are_now() {
+        assert_eq!(relative(-5), "now");
+        assert_eq!(relative(-3600), "now");
+    }
+
+    #[test]
+    fn year_is_shown_only_if_not_this_one() {
+        assert_eq!(relative(365 * 86400), "15 Jun 2022");
+    }
+
+    #[test]
+    fn absolute_times_of_today_are_time_of_day() {
+        let format = TimeFormat {
+            relative: false,
+            clock_24h: true,
+        };
+        let clock = |seconds_ago: i64| {
+            local(Timestamp::from((NOW - seconds_ago) as u64))
+                .format("%H:%M")
+                .to_string()
+        };
+
+        assert_eq!(age(61, format), clock(61));
+        assert_eq!(
+            age(8 * 86400, format),
+            format!("7 Jun, {}", clock(8 * 86400))
+        );
+    }
+
+    #[test]
+    fn minutes_are_shown_with_date_and_no_padding() {
+        let time = Timestamp::from(NOW as u64);
+        let clock_12h = TimeFormat {
+            relative: true,
+            clock_24h: false,
+        };
+
+        let shown = format_minute(time, TimeFormat::default());
+        assert_eq!(
+            shown,
+            format!("15 Jun 2023, {}", local(time).format("%H:%M"))
+        );
+        let shown = format_minute(time, clock_12h);
+        assert!(shown.starts_with("15 Jun 2023, "), "{shown}");
+        assert!(shown.ends_with("AM") || shown.ends_with("PM"), "{shown}");
+        assert!(!shown.contains("  "), "{shown}");
+    }
+}
@@ -0,0 +1,297 @@
+//! Translation of notes by a server with LibreTranslate API, which user
+//! configures. Content of notes is sent to the server only when user asks
+//! for their translation. Translations are kept in the database, so that
+//! every note is sent at most once into every language.
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use nostr_sdk::prelude::{EventId, Timestamp};
+use reqwest::{Client, StatusCode, Url};
+use serde::Deserialize;
+use sqlx::{query, SqlitePool};
+use tracing::{info, warn};
+
+use crate::config::TranslationConfig;
+
+/// How long the server may take to translate a note.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone)]
+pub struct Translations(Arc<TranslationsInner>);
+
+struct TranslationsInner {
+    pool: SqlitePool,
+    http: Client,
+    config: RwLock<TranslationConfig>,
+}
+
+/// What the server responds, either of them.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Response {
+    translated_text: Option<String>,
+    error: Option<String>,
+}
+
+impl Translations {
+    pub fn new(pool: SqlitePool, http: Client) -> Translations {
+        Translations(Arc::new(TranslationsInner {
+            pool,
+            http,
+            config: RwLock::new(TranslationConfig::default()),
+        }))
+    }
+
+    pub fn set_config(&self, config: &TranslationConfig) {
+        *self.0.config.write().unwrap() = config.clone();
+    }
+
+    /// Whether a server is configured, notes cannot be translated otherwise.
+    pub fn is_enabled(&self) -> bool {
+        !self.0.config.read().unwrap().url.trim().is_empty()
+    }
+
+    /// Translation of note `event_id` with `content` into the configured
+    /// language, from the database or from the server.
+    pub async fn translate(&self, event_id: EventId, content: &str) -> Result<String, String> {
+        let config = self.0.config.read().unwrap().clone();
+        let id = event_id.as_bytes().to_vec();
+
+        let stored = query!(
+            "SELECT translation FROM translations WHERE event_id = ? AND language = ?",
+            id,
+            config.target
+        )
+        .fetch_optional(&self.0.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        if let Some(stored) = stored {
+            return Ok(stored.translation);
+        }
+
+        let translation = self.request(&config, content).await?;
+        info!("Translated {} into {}", event_id, config.target);
+
+        let now = Timestamp::now().as_i64();
+        let stored = query!(
+            r#"
+INSERT INTO translations (event_id, language, translation, translated_at) VALUES (?, ?, ?, ?)
+ON CONFLICT (event_id, language) DO UPDATE SET
+    translation = EXCLUDED.translation,
+    translated_at = EXCLUDED.translated_at
+"#,
+            id,
+            config.target,
+            translation,
+            now
+        )
+        .execute(&self.0.pool)
+        .await;
+        if let Err(e) = stored {
+            warn!("Could not store translation of {}: {}", event_id, e);
+        }
+
+        Ok(translation)
+    }
+
+    /// Asks the server to translate `content`.
+    async fn request(&self, config: &TranslationConfig, content: &str) -> Result<String, String> {
+        let url = endpoint(&config.url)?;
+        let mut body = serde_json::json!({
+            "q": content,
+            "source": "auto",
+            "target": config.target,
+            "format": "text",
+        });
+        if !config.api_key.is_empty() {
+            body["api_key"] = config.api_key.clone().into();
+        }
+
+        let response = self
+            .0
+            .http
+            .post(url)
+            .timeout(TIMEOUT)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            .map_err(|e| format!("Translation server cannot be reached: {e}"))?;
+
+        let status = response.status();
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| format!("Translation server has not responded: {e}"))?;
+        let parsed: Option<Response> = serde_json::from_slice(&bytes).ok();
+
+        match (status, parsed) {
+            (StatusCode::TOO_MANY_REQUESTS, _) => {
+                Err("Translation server does not take more requests now, try again later".into())
+            }
+            (
+                status,
+                Some(Response {
+                    translated_text: Some(text),
+                    ..
+                }),
+            ) if status.is_success() => Ok(text),
+            (
+                status,
+                Some(Response {
+                    error: Some(error), ..
+                }),
+            ) => Err(format!(
+                "Translation server has refused ({status}): {error}"
+            )),
+            (status, _) => Err(format!("Translation server has failed ({status})")),
+        }
+    }
+}
+
+/// URL where translations are requested from server at `base`.
+pub fn endpoint(base: &str) -> Result<Url, String> {
+    let mut base = base.trim().to_string();
+    if !base.ends_with('/') {
+        base.push('/');
+    }
+
+    Url::parse(&base)
+        .ok()
+        .filter(|u| u.scheme() == "https" || u.scheme() == "http")
+        .and_then(|u| u.join("translate").ok())
+        .ok_or_else(|| {
+            format!(
+                "Invalid URL of translation server: {}",
+                base.trim_end_matches('/')
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{http_response, Fixtures, StubServer, TestDb};
+
+    fn translations(db: &TestDb, server: &StubServer, api_key: &str) -> Translations {
+        let translations = Translations::new(db.pool().clone(), Client::new());
+        translations.set_config(&TranslationConfig {
+            url: server.url("/libre").to_string(),
+            api_key: api_key.to_string(),
+            target: "de".to_string(),
+        });
+        translations
+    }
+
+    fn json(status: &str, body: &str) -> Vec<u8> {
+        http_response(
+            status,
+            &[("Content-Type", "application/json")],
+            body.as_bytes(),
+        )
+    }
+
+    #[test]
+    fn endpoint_is_below_configured_url() {
+        for base in ["https://tr.example.com", " https://tr.example.com/ "] {
+            assert_eq!(
+                endpoint(base).unwrap().as_str(),
+                "https://tr.example.com/translate"
+            );
+        }
+        assert_eq!(
+            endpoint("http://localhost:5000/libre").unwrap().as_str(),
+            "http://localhost:5000/libre/translate"
+        );
+        assert!(endpoint("").is_err());
+        assert_eq!(
+            endpoint("ftp://tr.example.com/"),
+            Err("Invalid URL of translation server: ftp://tr.example.com".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn note_is_sent_once_into_every_language() {
+        let db = TestDb::new().await.unwrap();
+        let server = StubServer::always(json("200 OK", r#"{"translatedText": "Hallo"}"#)).await;
+        let translations = translations(&db, &server, "secret");
+        let note = Fixtures::new().text_note("Hello");
+
+        for _ in 0..2 {
+            assert_eq!(
+                translations.translate(note.id, &note.content).await,
+                Ok("Hallo".to_string())
+            );
+        }
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].starts_with("POST /libre/translate "));
+        let body: serde_json::Value =
+            serde_json::from_str(requests[0].split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "q": "Hello",
+                "source": "auto",
+                "target": "de",
+                "format": "text",
+                "api_key": "secret",
+            })
+        );
+
+        // Another language is asked for again.
+        translations.set_config(&TranslationConfig {
+            url: server.url("/libre").to_string(),
+            api_key: String::new(),
+            target: "fr".to_string(),
+        });
+        translations
+            .translate(note.id, &note.content)
+            .await
+            .unwrap();
+        assert_eq!(server.requests().len(), 2);
+        assert!(!server.requests()[1].contains("api_key"));
+    }
+
+    #[tokio::test]
+    async fn failures_tell_what_server_says() {
+        let db = TestDb::new().await.unwrap();
+        let note = Fixtures::new().text_note("Hello");
+
+        for (response, expected) in [
+            (
+                json("429 Too Many Requests", r#"{"error": "Slow down"}"#),
+                "Translation server does not take more requests now, try again later",
+            ),
+            (
+                json("400 Bad Request", r#"{"error": "de is not supported"}"#),
+                "Translation server has refused (400 Bad Request): de is not supported",
+            ),
+            (
+                http_response("502 Bad Gateway", &[], b"<html>Bad gateway</html>"),
+                "Translation server has failed (502 Bad Gateway)",
+            ),
+            (
+                json("200 OK", r#"{"detectedLanguage": "en"}"#),
+                "Translation server has failed (200 OK)",
+            ),
+        ] {
+            let server = StubServer::always(response).await;
+            let translations = translations(&db, &server, "");
+            assert_eq!(
+                translations.translate(note.id, &note.content).await,
+                Err(expected.to_string())
+            );
+        }
+
+        // Nothing has been stored, the note is sent again.
+        let server = StubServer::always(json("200 OK", r#"{"translatedText": "Hallo"}"#)).await;
+        let translations = translations(&db, &server, "");
+        assert_eq!(
+            translations.translate(note.id, &note.content).await,
+            Ok("Hallo".to_string())
+        );
+    }
+}
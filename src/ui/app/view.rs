@@ -4,12 +4,14 @@ use relm4::*;
 
 use super::model::*;
 use super::msg::*;
+use crate::app::init::Setup;
+use crate::state::State;
 use crate::ui::main::Main;
 use crate::ui::unlock::{Unlock, UnlockResult};
 
 #[relm4::component(pub)]
 impl Component for App {
-    type Init = ();
+    type Init = Setup;
     type Input = AppInput;
     type Output = ();
     type CommandOutput = ();
@@ -30,12 +32,20 @@ impl Component for App {
     }
 
     fn init(
-        _init: Self::Init,
+        setup: Self::Init,
         root: &Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
+        let window = State::read(&setup.dirs.state_file()).window;
+        if window.width > 0 && window.height > 0 {
+            root.set_default_size(window.width, window.height);
+        }
+        if window.maximized {
+            root.maximize();
+        }
+
         let unlock = Unlock::builder()
-            .launch(())
+            .launch(setup)
             .forward(sender.input_sender(), |result| match result {
                 UnlockResult::Quit => AppInput::Quit,
                 UnlockResult::Unlocked(gn) => AppInput::Unlocked(gn),
@@ -47,10 +57,6 @@ impl Component for App {
 
         let widgets = view_output!();
 
-        widgets
-            .window
-            .insert_action_group("author", Some(&crate::app::action::make_author_actions()));
-
         // widgets.window.insert_action_group(
         //     "main",
         //     Some(&crate::app::action::make_main_menu_actions(sender)),
@@ -0,0 +1,351 @@
+use std::path::PathBuf;
+
+use gtk::prelude::*;
+use gtk::{gio, glib};
+use nostr_sdk::prelude::{Timestamp, XOnlyPublicKey};
+use relm4::*;
+
+use crate::archive::{EventFilter, ImportReport};
+use crate::content::{EntityKind, Target};
+use crate::ui::entity_entry::*;
+use crate::Gnostique;
+
+/// Dialog for exporting stored events into JSONL file and importing
+/// them from there.
+#[derive(Debug)]
+pub struct ArchiveDialog {
+    gnostique: Gnostique,
+    visible: bool,
+    /// Whether export or import is running right now.
+    busy: bool,
+    /// Outcome of the last export or import, or its progress.
+    status: Option<String>,
+    kind: gtk::Entry,
+    author: Controller<EntityEntry>,
+    /// Author entered, once it is valid.
+    entered_author: Option<XOnlyPublicKey>,
+    /// Whether no author has been entered, so that events of anybody
+    /// are exported.
+    anybody: bool,
+    since: gtk::Entry,
+    until: gtk::Entry,
+}
+
+#[derive(Debug)]
+pub enum ArchiveDialogInput {
+    Show,
+    Hide,
+    /// Let user choose file to export events into.
+    ChooseExport,
+    /// Let user choose file to import events from.
+    ChooseImport,
+    ExportTo(PathBuf),
+    ImportFrom(PathBuf),
+    /// Valid author has been entered, or it is not valid anymore.
+    Author {
+        pubkey: Option<XOnlyPublicKey>,
+        empty: bool,
+    },
+}
+
+#[derive(Debug)]
+pub enum ArchiveCmd {
+    Exported(Result<u64, String>),
+    /// Import has processed this much so far.
+    Progress(ImportReport),
+    Imported(Result<ImportReport, String>),
+}
+
+#[relm4::component(pub)]
+impl Component for ArchiveDialog {
+    type Init = Gnostique;
+    type Input = ArchiveDialogInput;
+    type Output = ();
+    type CommandOutput = ArchiveCmd;
+
+    view! {
+        gtk::Window {
+            set_title: Some("Export and import events"),
+            set_default_size: (450, 300),
+            #[watch] set_visible: model.visible,
+
+            connect_close_request[sender] => move |_| {
+                sender.input(ArchiveDialogInput::Hide);
+                gtk::Inhibit(true)
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 16,
+                add_css_class: "form",
+
+                gtk::Grid {
+                    set_column_spacing: 16,
+                    set_row_spacing: 16,
+
+                    attach[0, 0, 1, 1] = &gtk::Label {
+                        set_text: "Kind",
+                        set_xalign: 1.0,
+                        add_css_class: "label",
+                    },
+                    attach: (&model.kind, 1, 0, 1, 1),
+
+                    attach[0, 1, 1, 1] = &gtk::Label {
+                        set_text: "Author",
+                        set_xalign: 1.0,
+                        add_css_class: "label",
+                    },
+                    attach: (model.author.widget(), 1, 1, 1, 1),
+
+                    attach[0, 2, 1, 1] = &gtk::Label {
+                        set_text: "Since",
+                        set_xalign: 1.0,
+                        add_css_class: "label",
+                    },
+                    attach: (&model.since, 1, 2, 1, 1),
+
+                    attach[0, 3, 1, 1] = &gtk::Label {
+                        set_text: "Until",
+                        set_xalign: 1.0,
+                        add_css_class: "label",
+                    },
+                    attach: (&model.until, 1, 3, 1, 1),
+                },
+
+                gtk::Label {
+                    #[watch] set_label: model.status.as_deref().unwrap_or_default(),
+                    #[watch] set_visible: model.status.is_some(),
+                    set_wrap: true,
+                    set_xalign: 0.0,
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 8,
+
+                    gtk::Spinner {
+                        #[watch] set_spinning: model.busy,
+                        #[watch] set_visible: model.busy,
+                    },
+
+                    gtk::Box { set_hexpand: true },
+
+                    gtk::Button::with_label("Import…") {
+                        #[watch] set_sensitive: !model.busy,
+                        connect_clicked => ArchiveDialogInput::ChooseImport
+                    },
+
+                    gtk::Button::with_label("Export…") {
+                        add_css_class: "suggested-action",
+                        #[watch] set_sensitive: !model.busy,
+                        connect_clicked => ArchiveDialogInput::ChooseExport
+                    }
+                }
+            }
+        }
+    }
+
+    fn init(
+        gnostique: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = ArchiveDialog {
+            author: EntityEntry::builder()
+                .launch((
+                    gnostique.clone(),
+                    EntityKind::Profile,
+                    "Anybody, or npub or hex",
+                ))
+                .forward(sender.input_sender(), |output| match output {
+                    EntityEntryOutput::Changed { target, empty } => ArchiveDialogInput::Author {
+                        pubkey: match target {
+                            Some(Target::Profile(pubkey, _)) => Some(pubkey),
+                            _ => None,
+                        },
+                        empty,
+                    },
+                    EntityEntryOutput::Activated => ArchiveDialogInput::ChooseExport,
+                }),
+            entered_author: None,
+            anybody: true,
+            gnostique,
+            visible: false,
+            busy: false,
+            status: None,
+            kind: gtk::Entry::new(),
+            since: gtk::Entry::new(),
+            until: gtk::Entry::new(),
+        };
+        model.kind.set_hexpand(true);
+        model
+            .kind
+            .set_placeholder_text(Some("Any kind, or number such as 1"));
+        model.since.set_placeholder_text(Some("YYYY-MM-DD"));
+        model.until.set_placeholder_text(Some("YYYY-MM-DD"));
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>, root: &Self::Root) {
+        match message {
+            ArchiveDialogInput::Show => {
+                self.status = None;
+                self.visible = true;
+            }
+
+            // Running import goes on in background.
+            ArchiveDialogInput::Hide => self.visible = false,
+
+            ArchiveDialogInput::ChooseExport => {
+                choose_file(root, gtk::FileChooserAction::Save, &sender, |path| {
+                    ArchiveDialogInput::ExportTo(path)
+                })
+            }
+
+            ArchiveDialogInput::ChooseImport => {
+                choose_file(root, gtk::FileChooserAction::Open, &sender, |path| {
+                    ArchiveDialogInput::ImportFrom(path)
+                })
+            }
+
+            ArchiveDialogInput::ExportTo(path) => match self.filter() {
+                Ok(filter) => {
+                    let gnostique = self.gnostique.clone();
+                    self.busy = true;
+                    self.status = Some("Exporting…".to_string());
+                    sender.oneshot_command(async move {
+                        ArchiveCmd::Exported(gnostique.export_events(&path, &filter).await)
+                    });
+                }
+                Err(e) => self.status = Some(e),
+            },
+
+            ArchiveDialogInput::ImportFrom(path) => {
+                let gnostique = self.gnostique.clone();
+                self.busy = true;
+                self.status = Some("Importing…".to_string());
+                sender.command(move |out, _shutdown| async move {
+                    let progress = out.clone();
+                    let result = gnostique
+                        .import_events(&path, move |r| progress.emit(ArchiveCmd::Progress(*r)))
+                        .await;
+                    out.emit(ArchiveCmd::Imported(result));
+                });
+            }
+
+            ArchiveDialogInput::Author { pubkey, empty } => {
+                self.entered_author = pubkey;
+                self.anybody = empty;
+            }
+        }
+    }
+
+    fn update_cmd(
+        &mut self,
+        message: Self::CommandOutput,
+        _sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        match message {
+            ArchiveCmd::Exported(result) => {
+                self.busy = false;
+                self.status = Some(match result {
+                    Ok(count) => format!("Exported {count} events"),
+                    Err(e) => format!("Could not export events: {e}"),
+                });
+            }
+            ArchiveCmd::Progress(report) => {
+                self.status = Some(format!("Importing… {}", report.describe()))
+            }
+            ArchiveCmd::Imported(result) => {
+                self.busy = false;
+                self.status = Some(match result {
+                    Ok(report) => format!("Done: {}", report.describe()),
+                    Err(e) => format!("Could not import events: {e}"),
+                });
+            }
+        }
+    }
+}
+
+impl ArchiveDialog {
+    /// Filter of exported events as entered by user.
+    fn filter(&self) -> Result<EventFilter, String> {
+        let kind = match self.kind.text().trim() {
+            "" => None,
+            k => Some(k.parse().map_err(|_| format!("Invalid kind: {k}"))?),
+        };
+
+        let author = match (self.entered_author, self.anybody) {
+            (Some(pubkey), _) => Some(pubkey),
+            (None, true) => None,
+            (None, false) => return Err("Invalid author".to_string()),
+        };
+
+        Ok(EventFilter {
+            kind,
+            author,
+            since: parse_date(&self.since.text(), false)?,
+            until: parse_date(&self.until.text(), true)?,
+        })
+    }
+}
+
+/// Parses date such as `2023-03-01` into the beginning of the day,
+/// or its end if `end_of_day` is set.
+fn parse_date(s: &str, end_of_day: bool) -> Result<Option<Timestamp>, String> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Ok(None);
+    }
+
+    let date = chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date: {s}"))?;
+    let time = if end_of_day {
+        date.and_hms_opt(23, 59, 59)
+    } else {
+        date.and_hms_opt(0, 0, 0)
+    };
+
+    Ok(time.map(|t| Timestamp::from(t.timestamp() as u64)))
+}
+
+/// Lets user choose file, which is then sent to the dialog by `message`.
+fn choose_file<F>(
+    root: &gtk::Window,
+    action: gtk::FileChooserAction,
+    sender: &ComponentSender<ArchiveDialog>,
+    message: F,
+) where
+    F: Fn(PathBuf) -> ArchiveDialogInput + 'static,
+{
+    let accept = match action {
+        gtk::FileChooserAction::Save => "Export",
+        _ => "Import",
+    };
+    let dialog = gtk::FileChooserNative::new(
+        Some(accept),
+        Some(root),
+        action,
+        Some(accept),
+        Some("Cancel"),
+    );
+    dialog.set_modal(true);
+    if action == gtk::FileChooserAction::Save {
+        dialog.set_current_name("events.jsonl");
+    }
+
+    dialog.connect_response(glib::clone!(@strong sender => move |d, response| {
+        if response == gtk::ResponseType::Accept {
+            if let Some(path) = d.file().and_then(|f: gio::File| f.path()) {
+                sender.input(message(path));
+            }
+        }
+        d.destroy();
+    }));
+
+    dialog.show();
+}
@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use gtk::gdk;
+use gtk::prelude::*;
+use relm4::prelude::*;
+use relm4::{gtk, ComponentParts};
+use reqwest::Url;
+
+use crate::content::Target;
+use crate::markdown::Block;
+use crate::nostr::{Article, Persona};
+
+/// A window for reading long-form articles (NIP-23). One instance
+/// of it is created and reused, like [`ImageViewer`](crate::ui::viewer::ImageViewer).
+pub struct ArticleWindow {
+    /// Whether the window is visible or hidden.
+    visible: bool,
+
+    /// Title of the displayed article.
+    title: String,
+
+    /// Author and publication date of the displayed article.
+    byline: String,
+
+    /// Rendered blocks of the article.
+    blocks: gtk::Box,
+
+    /// Pictures waiting for images from the given URLs.
+    pictures: HashMap<Url, Vec<gtk::Picture>>,
+}
+
+/// Messages coming to [`ArticleWindow`].
+#[derive(Debug)]
+pub enum ArticleWindowInput {
+    /// Display the article of the author and show the window, if hidden.
+    Show(Box<Article>, Box<Persona>),
+
+    /// Hide the window.
+    Hide,
+
+    /// Image displayed in the article has been downloaded and decoded.
+    ImageBitmap { url: Url, bitmap: Arc<gdk::Texture> },
+}
+
+#[derive(Debug)]
+pub enum ArticleWindowOutput {
+    /// Images from these URLs should be downloaded.
+    NeedImages(Vec<Url>),
+    /// User wants to see the profile, thread or hashtag.
+    Open(Target),
+}
+
+#[relm4::component(pub)]
+impl Component for ArticleWindow {
+    type Init = ();
+    type Input = ArticleWindowInput;
+    type Output = ArticleWindowOutput;
+    type CommandOutput = ();
+
+    view! {
+        gtk::Window {
+            #[watch] set_title: Some(&model.title),
+            set_default_size: (700, 800),
+            #[watch]
+            set_visible: model.visible,
+
+            connect_close_request[sender] => move |_| {
+                sender.input(ArticleWindowInput::Hide);
+                gtk::Inhibit(false)
+            },
+
+            gtk::ScrolledWindow {
+                set_hscrollbar_policy: gtk::PolicyType::Never,
+                set_vexpand: true,
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    add_css_class: "article",
+                    set_spacing: 12,
+
+                    gtk::Label {
+                        #[watch] set_label: &model.title,
+                        set_wrap: true,
+                        set_xalign: 0.0,
+                        add_css_class: "title",
+                    },
+
+                    gtk::Label {
+                        #[watch] set_label: &model.byline,
+                        set_xalign: 0.0,
+                        add_css_class: "byline",
+                    },
+
+                    #[local_ref]
+                    blocks -> gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_spacing: 12,
+                    }
+                }
+            }
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = ArticleWindow {
+            visible: false,
+            title: String::new(),
+            byline: String::new(),
+            blocks: gtk::Box::default(),
+            pictures: HashMap::new(),
+        };
+        let blocks = &model.blocks;
+        let widgets = view_output!();
+
+        ComponentParts { widgets, model }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>, _root: &Self::Root) {
+        match message {
+            ArticleWindowInput::Show(article, author) => {
+                self.show_article(&article, &author, &sender);
+                self.visible = true;
+            }
+            ArticleWindowInput::Hide => self.visible = false,
+            ArticleWindowInput::ImageBitmap { url, bitmap } => {
+                if let Some(pictures) = self.pictures.get(&url) {
+                    for picture in pictures {
+                        picture.set_paintable(Some(bitmap.as_ref()));
+                        picture.set_visible(true);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl ArticleWindow {
+    /// Replaces displayed article with `article` of `author`.
+    fn show_article(
+        &mut self,
+        article: &Article,
+        author: &Persona,
+        sender: &ComponentSender<Self>,
+    ) {
+        while let Some(child) = self.blocks.first_child() {
+            self.blocks.remove(&child);
+        }
+        self.pictures.clear();
+
+        self.title = article
+            .title
+            .clone()
+            .unwrap_or_else(|| "Untitled article".to_string());
+
+        let name = author.shown_name();
+        let published = article.published_at.unwrap_or(article.event.created_at);
+        self.byline = match chrono::NaiveDateTime::from_timestamp_opt(published.as_i64(), 0) {
+            Some(t) => format!("{name}, {}", t.format("%e %B %Y")),
+            None => name,
+        };
+
+        if let Some(url) = &article.image {
+            self.append_picture(url, "hero");
+        }
+
+        let blocks = crate::markdown::parse(&article.event.content);
+
+        for block in &blocks {
+            match block {
+                Block::Heading(level, markup) => {
+                    let label = self.append_label(markup, "heading");
+                    label.add_css_class(&format!("h{level}"));
+                }
+                Block::Paragraph(markup) => {
+                    self.append_label(markup, "paragraph");
+                }
+                Block::ListItem { marker, text } => {
+                    let markup = format!("{}  {text}", html_escape::encode_text(marker));
+                    let label = self.append_label(&markup, "list-item");
+                    label.set_margin_start(16);
+                }
+                Block::Quote(markup) => {
+                    self.append_label(markup, "quote");
+                }
+                Block::Code(code) => {
+                    let label = self.append_label(&html_escape::encode_text(code), "code");
+                    label.set_wrap(false);
+                }
+                Block::Image { url, alt } => {
+                    let picture = self.append_picture(url, "image");
+                    picture.set_alternative_text(Some(alt));
+                }
+                Block::Rule => self
+                    .blocks
+                    .append(&gtk::Separator::new(gtk::Orientation::Horizontal)),
+            }
+        }
+
+        let mut images = crate::markdown::images(&blocks);
+        images.extend(article.image.clone());
+        if !images.is_empty() {
+            sender
+                .output(ArticleWindowOutput::NeedImages(images))
+                .unwrap_or_default();
+        }
+
+        // Links to nostr entities open lanes, web links open in browser.
+        let mut child = self.blocks.first_child();
+        while let Some(widget) = child {
+            if let Some(label) = widget.downcast_ref::<gtk::Label>() {
+                label.connect_activate_link({
+                    let sender = sender.clone();
+                    move |_, uri| {
+                        let target = uri.parse().ok().and_then(|u| Target::from_uri(&u));
+
+                        match target {
+                            Some(target) => {
+                                sender
+                                    .output(ArticleWindowOutput::Open(target))
+                                    .unwrap_or_default();
+                                gtk::Inhibit(true)
+                            }
+                            None => gtk::Inhibit(uri.starts_with("nostr:")),
+                        }
+                    }
+                });
+            }
+            child = widget.next_sibling();
+        }
+    }
+
+    /// Appends label displaying `markup`.
+    fn append_label(&self, markup: &str, css: &str) -> gtk::Label {
+        let label = gtk::Label::new(None);
+        label.set_markup(markup);
+        label.set_wrap(true);
+        label.set_wrap_mode(gtk::pango::WrapMode::WordChar);
+        label.set_xalign(0.0);
+        label.set_selectable(true);
+        label.add_css_class(css);
+        self.blocks.append(&label);
+        label
+    }
+
+    /// Appends picture that displays image from `url` once it is downloaded.
+    fn append_picture(&mut self, url: &Url, css: &str) -> gtk::Picture {
+        let picture = gtk::Picture::new();
+        picture.set_can_shrink(true);
+        picture.set_visible(false);
+        picture.add_css_class(css);
+        self.blocks.append(&picture);
+
+        self.pictures
+            .entry(url.clone())
+            .or_default()
+            .push(picture.clone());
+        picture
+    }
+}
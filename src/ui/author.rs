@@ -25,6 +25,7 @@ impl WidgetTemplate for Author {
             #[name = "author_nip05"]
             gtk::Label {
                 add_css_class: "author-nip05",
+                set_use_markup: true,
                 set_yalign: 1.0,
                 set_visible: false
             }
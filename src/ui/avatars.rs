@@ -0,0 +1,164 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use gtk::{gdk, glib};
+use nostr_sdk::prelude::XOnlyPublicKey;
+use once_cell::sync::Lazy;
+use reqwest::Url;
+
+/// Avatar of authors who have none, or whose one has not been loaded yet.
+pub static ANONYMOUS_USER: Lazy<Arc<gdk::Texture>> = Lazy::new(|| {
+    Arc::new(
+        gdk::Texture::from_bytes(&glib::Bytes::from(include_bytes!(
+            "../../resources/user.svg"
+        )))
+        .unwrap(),
+    )
+});
+
+/// Size of identicons in pixels, they are scaled down as needed.
+const IDENTICON_SIZE: u32 = 96;
+
+/// Avatars are decoded at most this large in pixels, which is more than
+/// they are ever displayed. The original file stays in download cache.
+const AVATAR_SIZE: i32 = 128;
+
+/// Identicons of authors, every one is made only once.
+static IDENTICONS: Lazy<Mutex<HashMap<XOnlyPublicKey, Arc<gdk::Texture>>>> =
+    Lazy::new(Default::default);
+
+/// Avatar of `pubkey` until their real one is available: identicon
+/// made from the pubkey, or the anonymous user if that cannot be decoded.
+pub fn placeholder(pubkey: &XOnlyPublicKey) -> Arc<gdk::Texture> {
+    let mut identicons = IDENTICONS.lock().unwrap();
+
+    if let Some(texture) = identicons.get(pubkey) {
+        return texture.clone();
+    }
+
+    let bytes = glib::Bytes::from_owned(crate::identicon::identicon(pubkey, IDENTICON_SIZE));
+    match gdk::Texture::from_bytes(&bytes) {
+        Ok(texture) => {
+            let texture = Arc::new(texture);
+            identicons.insert(*pubkey, texture.clone());
+            texture
+        }
+        Err(_) => ANONYMOUS_USER.clone(),
+    }
+}
+
+/// Decodes avatar from `file`, downscaled to [`AVATAR_SIZE`] if it is
+/// larger. Animated avatars give their first frame. Blocks.
+pub fn decode(file: &Path) -> Result<gdk::Texture, String> {
+    use gtk::gdk_pixbuf::Pixbuf;
+
+    let pixbuf = match Pixbuf::file_info(file) {
+        Some((_, w, h)) if w > AVATAR_SIZE || h > AVATAR_SIZE => {
+            Pixbuf::from_file_at_scale(file, AVATAR_SIZE, AVATAR_SIZE, true)
+        }
+        _ => Pixbuf::from_file(file),
+    }
+    .map_err(|e| e.to_string())?;
+
+    Ok(gdk::Texture::for_pixbuf(&pixbuf))
+}
+
+/// Decoded avatars of authors. Every avatar is decoded only once and
+/// the same texture is then handed to all lanes and their notes.
+///
+/// The cache holds at most `capacity` textures, the least recently used
+/// ones are evicted first.
+#[derive(Debug)]
+pub struct Avatars {
+    capacity: usize,
+    /// Monotonic counter used to determine recency of use.
+    tick: u64,
+    textures: HashMap<XOnlyPublicKey, CachedAvatar>,
+    /// Authors whose avatars are being decoded right now.
+    decoding: HashSet<XOnlyPublicKey>,
+    /// Avatars which could not be decoded, they are not tried again.
+    bad: HashSet<Url>,
+}
+
+#[derive(Debug)]
+struct CachedAvatar {
+    url: Url,
+    bitmap: Arc<gdk::Texture>,
+    last_used: u64,
+}
+
+impl Avatars {
+    pub fn new(capacity: usize) -> Avatars {
+        Avatars {
+            capacity,
+            tick: 0,
+            textures: Default::default(),
+            decoding: Default::default(),
+            bad: Default::default(),
+        }
+    }
+
+    /// Returns decoded avatar of `pubkey` if it is cached and was obtained from `url`.
+    pub fn get(&mut self, pubkey: &XOnlyPublicKey, url: &Url) -> Option<Arc<gdk::Texture>> {
+        self.tick += 1;
+        let tick = self.tick;
+
+        self.textures
+            .get_mut(pubkey)
+            .filter(|cached| &cached.url == url)
+            .map(|cached| {
+                cached.last_used = tick;
+                cached.bitmap.clone()
+            })
+    }
+
+    /// Marks avatar of `pubkey` from `url` as being decoded. Returns
+    /// `false` if it already is, or if it could not be decoded before,
+    /// in which case there is no need to decode it again.
+    pub fn start_decoding(&mut self, pubkey: XOnlyPublicKey, url: &Url) -> bool {
+        !self.bad.contains(url) && self.decoding.insert(pubkey)
+    }
+
+    /// Stores decoded avatar of `pubkey`, evicting the least recently used
+    /// one if the cache is full.
+    pub fn insert(&mut self, pubkey: XOnlyPublicKey, url: Url, bitmap: Arc<gdk::Texture>) {
+        self.decoding.remove(&pubkey);
+        self.tick += 1;
+
+        self.textures.insert(
+            pubkey,
+            CachedAvatar {
+                url,
+                bitmap,
+                last_used: self.tick,
+            },
+        );
+
+        if self.textures.len() > self.capacity {
+            let oldest = self
+                .textures
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(pubkey, _)| *pubkey);
+
+            if let Some(pubkey) = oldest {
+                self.textures.remove(&pubkey);
+            }
+        }
+    }
+
+    /// Decoding of avatar of `pubkey` from `url` did not succeed, their
+    /// placeholder stays.
+    pub fn failed(&mut self, pubkey: &XOnlyPublicKey, url: Url) {
+        self.decoding.remove(pubkey);
+        self.bad.insert(url);
+    }
+
+    /// Forgets avatar of `pubkey` unless it was obtained from `url`.
+    pub fn invalidate(&mut self, pubkey: &XOnlyPublicKey, url: Option<&Url>) {
+        if self.textures.get(pubkey).map(|c| Some(&c.url)) != Some(url) {
+            self.textures.remove(pubkey);
+        }
+    }
+}
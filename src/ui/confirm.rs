@@ -0,0 +1,38 @@
+use gtk::prelude::*;
+use relm4::gtk;
+
+/// Asks user whether to go on with something that cannot be taken back.
+/// `confirmed` is called only if user chooses button labelled `action`;
+/// closing the dialog or cancelling leaves everything as it was.
+pub fn confirm(
+    widget: &impl IsA<gtk::Widget>,
+    question: &str,
+    detail: &str,
+    action: &str,
+    confirmed: impl Fn() + 'static,
+) {
+    let window = widget.root().and_then(|r| r.downcast::<gtk::Window>().ok());
+
+    let dialog = gtk::MessageDialog::new(
+        window.as_ref(),
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        gtk::MessageType::Question,
+        gtk::ButtonsType::None,
+        question,
+    );
+    dialog.set_secondary_text(Some(detail));
+    dialog.add_button("Cancel", gtk::ResponseType::Cancel);
+    dialog
+        .add_button(action, gtk::ResponseType::Accept)
+        .add_css_class("destructive-action");
+    dialog.set_default_response(gtk::ResponseType::Cancel);
+
+    dialog.connect_response(move |dialog, response| {
+        if response == gtk::ResponseType::Accept {
+            confirmed();
+        }
+        dialog.close();
+    });
+
+    dialog.show();
+}
@@ -0,0 +1,360 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use gtk::gdk;
+use gtk::prelude::*;
+use nostr_sdk::prelude::XOnlyPublicKey;
+use relm4::*;
+use reqwest::Url;
+
+use crate::nostr::{short_npub, Persona};
+use crate::ui::avatars::placeholder;
+use crate::Gnostique;
+
+/// At most this many users are listed, long lists would take ages to
+/// build and would download too many avatars.
+const MAX_LISTED: usize = 500;
+
+/// Window listing users whom somebody follows, or their followers known
+/// to this client. Clicking a user opens their profile.
+#[derive(Debug)]
+pub struct ContactsWindow {
+    gnostique: Gnostique,
+    visible: bool,
+    /// Whose contacts are listed, and whether followers rather than
+    /// followed users.
+    shown: Option<(XOnlyPublicKey, bool)>,
+    busy: bool,
+    status: Option<String>,
+    rows: gtk::ListBox,
+    contacts: Vec<Contact>,
+}
+
+/// Listed user with their row in the window.
+#[derive(Debug)]
+struct Contact {
+    pubkey: XOnlyPublicKey,
+    name: gtk::Label,
+    avatar: gtk::Image,
+}
+
+#[derive(Debug)]
+pub enum ContactsInput {
+    /// Show users followed by the user, or their followers if `true`.
+    Show(XOnlyPublicKey, bool),
+    Hide,
+    /// User has clicked the listed user at this index.
+    Open(usize),
+    /// Metadata of a user have arrived.
+    Metadata(Box<Persona>),
+    /// Avatar of a user has been decoded.
+    Avatar(XOnlyPublicKey, Arc<gdk::Texture>),
+}
+
+#[derive(Debug)]
+pub enum ContactsOutput {
+    /// User wants to see the profile.
+    Open(XOnlyPublicKey),
+    /// Avatars of listed users, at these URLs, should be displayed.
+    NeedAvatars(Vec<(XOnlyPublicKey, Url)>),
+}
+
+#[derive(Debug)]
+pub enum ContactsCmd {
+    Loaded {
+        shown: (XOnlyPublicKey, bool),
+        listed: Listed,
+    },
+}
+
+/// Users to be listed.
+#[derive(Debug, Default)]
+pub struct Listed {
+    /// Users with their metadata if known, and whether we follow them.
+    people: Vec<(XOnlyPublicKey, Option<Persona>, bool)>,
+    /// Number of all users, including those not listed.
+    total: usize,
+    /// Whether there is no contact list to take followed users from.
+    unknown: bool,
+}
+
+#[relm4::component(pub)]
+impl Component for ContactsWindow {
+    type Init = Gnostique;
+    type Input = ContactsInput;
+    type Output = ContactsOutput;
+    type CommandOutput = ContactsCmd;
+
+    view! {
+        gtk::Window {
+            #[watch] set_title: Some(model.title()),
+            set_default_size: (400, 550),
+            #[watch] set_visible: model.visible,
+
+            connect_close_request[sender] => move |_| {
+                sender.input(ContactsInput::Hide);
+                gtk::Inhibit(true)
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 16,
+                add_css_class: "form",
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 8,
+
+                    gtk::Label {
+                        #[watch] set_label: model.status.as_deref().unwrap_or_default(),
+                        set_hexpand: true,
+                        set_wrap: true,
+                        set_xalign: 0.0,
+                    },
+
+                    gtk::Spinner {
+                        #[watch] set_spinning: model.busy,
+                        #[watch] set_visible: model.busy,
+                    },
+                },
+
+                gtk::ScrolledWindow {
+                    set_vexpand: true,
+                    set_hscrollbar_policy: gtk::PolicyType::Never,
+                    set_child: Some(&model.rows),
+                },
+            }
+        }
+    }
+
+    fn init(
+        gnostique: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = ContactsWindow {
+            gnostique,
+            visible: false,
+            shown: None,
+            busy: false,
+            status: None,
+            rows: gtk::ListBox::new(),
+            contacts: Vec::new(),
+        };
+        model.rows.set_selection_mode(gtk::SelectionMode::None);
+        model.rows.set_activate_on_single_click(true);
+        model.rows.add_css_class("people");
+        {
+            let sender = sender.clone();
+            model.rows.connect_row_activated(move |_, row| {
+                if let Ok(index) = usize::try_from(row.index()) {
+                    sender.input(ContactsInput::Open(index));
+                }
+            });
+        }
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>, _root: &Self::Root) {
+        match message {
+            ContactsInput::Show(pubkey, followers) => {
+                self.clear();
+                self.shown = Some((pubkey, followers));
+                self.busy = true;
+                self.visible = true;
+
+                let gnostique = self.gnostique.clone();
+                sender.oneshot_command(async move {
+                    ContactsCmd::Loaded {
+                        shown: (pubkey, followers),
+                        listed: load(&gnostique, pubkey, followers).await,
+                    }
+                });
+            }
+
+            ContactsInput::Hide => {
+                self.visible = false;
+                self.clear();
+            }
+
+            ContactsInput::Open(index) => {
+                if let Some(contact) = self.contacts.get(index) {
+                    sender
+                        .output(ContactsOutput::Open(contact.pubkey))
+                        .unwrap_or_default();
+                }
+            }
+
+            ContactsInput::Metadata(persona) => {
+                if let Some(contact) = self.contact(&persona.pubkey) {
+                    contact.name.set_label(&persona.shown_name());
+                }
+            }
+
+            ContactsInput::Avatar(pubkey, bitmap) => {
+                if let Some(contact) = self.contact(&pubkey) {
+                    contact.avatar.set_paintable(Some(bitmap.as_ref()));
+                }
+            }
+        }
+    }
+
+    fn update_cmd(
+        &mut self,
+        message: Self::CommandOutput,
+        sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        match message {
+            // Window has been closed or shows somebody else meanwhile.
+            ContactsCmd::Loaded { shown, .. } if self.shown != Some(shown) => {}
+            ContactsCmd::Loaded { listed, .. } => {
+                self.busy = false;
+                self.show_listed(listed, &sender);
+            }
+        }
+    }
+}
+
+impl ContactsWindow {
+    fn title(&self) -> &'static str {
+        match self.shown {
+            Some((_, true)) => "Followers",
+            _ => "Following",
+        }
+    }
+
+    fn contact(&self, pubkey: &XOnlyPublicKey) -> Option<&Contact> {
+        self.contacts.iter().find(|c| c.pubkey == *pubkey)
+    }
+
+    /// Forgets listed users.
+    fn clear(&mut self) {
+        while let Some(row) = self.rows.first_child() {
+            self.rows.remove(&row);
+        }
+        self.contacts.clear();
+        self.status = None;
+        self.shown = None;
+        self.busy = false;
+    }
+
+    fn show_listed(&mut self, listed: Listed, sender: &ComponentSender<Self>) {
+        let followers = matches!(self.shown, Some((_, true)));
+        let mut avatars = Vec::new();
+
+        for (pubkey, persona, followed) in listed.people {
+            let avatar = gtk::Image::from_paintable(Some(placeholder(&pubkey).as_ref()));
+            avatar.set_pixel_size(32);
+
+            let name = gtk::Label::new(Some(
+                &persona
+                    .as_ref()
+                    .map(|p| p.shown_name())
+                    .unwrap_or_else(|| short_npub(&pubkey)),
+            ));
+            name.set_xalign(0.0);
+            name.set_hexpand(true);
+            name.set_ellipsize(gtk::pango::EllipsizeMode::End);
+            name.add_css_class("name");
+
+            let npub = gtk::Label::new(Some(&short_npub(&pubkey)));
+            npub.add_css_class("dim-label");
+
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+            row.append(&avatar);
+            row.append(&name);
+            if followed && !followers {
+                let mark = gtk::Label::new(Some("Followed by you"));
+                mark.add_css_class("followed");
+                row.append(&mark);
+            }
+            row.append(&npub);
+            self.rows.append(&row);
+
+            if let Some(url) = persona.and_then(|p| p.avatar) {
+                avatars.push((pubkey, url));
+            }
+
+            self.contacts.push(Contact {
+                pubkey,
+                name,
+                avatar,
+            });
+        }
+
+        let mut status = match (listed.unknown, followers) {
+            (true, _) => "No contact list of this user is known yet".to_string(),
+            (false, false) => format!("Follows {}", people(listed.total)),
+            (false, true) => format!("{} known to this client", people(listed.total)),
+        };
+        if listed.total > self.contacts.len() {
+            status.push_str(&format!(", the first {} shown", self.contacts.len()));
+        }
+        self.status = Some(status);
+
+        if !avatars.is_empty() {
+            sender
+                .output(ContactsOutput::NeedAvatars(avatars))
+                .unwrap_or_default();
+        }
+    }
+}
+
+fn people(n: usize) -> String {
+    match n {
+        1 => "1 person".to_string(),
+        n => format!("{n} people"),
+    }
+}
+
+/// Loads users followed by `pubkey`, or its followers. Users we follow
+/// go first, then those with known names. Metadata of those not known
+/// are requested, all at once, and arrive later.
+async fn load(gnostique: &Gnostique, pubkey: XOnlyPublicKey, followers: bool) -> Listed {
+    let pubkeys = if followers {
+        gnostique.followers_of(pubkey).await
+    } else {
+        match gnostique.following_of(pubkey).await {
+            Some(following) => following,
+            None => {
+                return Listed {
+                    unknown: true,
+                    ..Default::default()
+                }
+            }
+        }
+    };
+
+    let followed: HashSet<XOnlyPublicKey> = gnostique.follows().await.into_iter().collect();
+    let mut people = Vec::with_capacity(pubkeys.len());
+    for pubkey in &pubkeys {
+        let persona = gnostique.get_persona(*pubkey).await;
+        people.push((*pubkey, persona, followed.contains(pubkey)));
+    }
+
+    people.sort_by_cached_key(|(_, persona, followed)| {
+        (
+            !followed,
+            persona.is_none(),
+            persona.as_ref().map(|p| p.shown_name().to_lowercase()),
+        )
+    });
+    people.truncate(MAX_LISTED);
+
+    let unknown = people
+        .iter()
+        .filter(|(_, persona, _)| persona.is_none())
+        .map(|(pk, _, _)| *pk)
+        .collect();
+    gnostique.demand().metadata_of(unknown).await;
+
+    Listed {
+        people,
+        total: pubkeys.len(),
+        unknown: false,
+    }
+}
@@ -1,9 +1,18 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
 use gtk::prelude::*;
 use nostr_sdk::nostr::Event;
+use nostr_sdk::prelude::{EventId, Timestamp, ToBech32, Url};
 use relm4::prelude::*;
 use relm4::{gtk, ComponentParts};
 use serde_json::Value;
 
+use crate::metadata_history::ProfileChange;
+use crate::nostr::EventExt;
+use crate::outbox::Delivery;
+use crate::timestamps::{format_full, TimeFormat};
+
 /// A window that display all available information about a note.
 /// One instance of it is created and reused, therefore everytime
 /// the window shows, it has to be provided with fresh information
@@ -15,11 +24,21 @@ pub struct DetailsWindow {
     /// All available information about a text note.
     details: Option<Details>,
 
+    /// Result of signature verification of the displayed event,
+    /// `None` if it has not been verified yet.
+    verified: Option<Result<(), String>>,
+
     /// Buffer for `TextView` displaying event JSON.
     event_buffer: gtk::TextBuffer,
 
     /// Buffer for `TextView` displaying metadata JSON.
     metadata_buffer: gtk::TextBuffer,
+
+    /// Buffer for `TextView` displaying changes of the author's profile.
+    history_buffer: gtk::TextBuffer,
+
+    /// Table of tags of the displayed event.
+    tags: gtk::Grid,
 }
 
 /// Messages coming to [`DetailsWindow`].
@@ -30,17 +49,47 @@ pub enum DetailsWindowInput {
 
     /// Hide the window.
     Hide,
+
+    /// Copy the value into clipboard.
+    Copy(Field),
+
+    /// Verify signature of the displayed event.
+    Verify,
+
+    /// Ask for fresh responses of relays to the displayed event.
+    RefreshDeliveries,
+
+    /// Fresh responses of relays to the event with this ID.
+    Deliveries(EventId, Vec<Delivery>),
+}
+
+/// Messages going out of [`DetailsWindow`].
+#[derive(Debug)]
+pub enum DetailsWindowOutput {
+    /// Obtain fresh responses of relays to the event with this ID.
+    RefreshDeliveries(EventId),
+}
+
+/// Values displayed by [`DetailsWindow`] that can be copied into clipboard.
+#[derive(Debug)]
+pub enum Field {
+    IdHex,
+    IdNote,
+    IdNevent,
+    Author,
+    Json,
 }
 
 #[relm4::component(pub)]
 impl Component for DetailsWindow {
     type Init = ();
     type Input = DetailsWindowInput;
-    type Output = ();
+    type Output = DetailsWindowOutput;
     type CommandOutput = ();
 
     view! {
         gtk::Window {
+            set_default_size: (800, 600),
             #[watch]
             set_visible: model.visible,
             // set_modal: true,
@@ -61,28 +110,205 @@ impl Component for DetailsWindow {
                 gtk::Stack {
                     set_hexpand: true,
 
-                    add_child = &gtk::Box { } -> { set_title: "Text note" },
+                    add_child = &gtk::Grid {
+                        add_css_class: "form",
+                        set_column_spacing: 16,
+                        set_row_spacing: 8,
+
+                        attach[0, 0, 1, 1] = &gtk::Label {
+                            set_label: "ID",
+                            set_xalign: 1.0,
+                            add_css_class: "label",
+                        },
+                        attach[1, 0, 1, 1] = &gtk::Label {
+                            #[watch] set_label: &model.field(Field::IdHex),
+                            set_xalign: 0.0,
+                            set_selectable: true,
+                            set_hexpand: true,
+                            set_wrap: true,
+                            set_wrap_mode: gtk::pango::WrapMode::Char,
+                            add_css_class: "monospace",
+                        },
+                        attach[2, 0, 1, 1] = &gtk::Button::from_icon_name("edit-copy-symbolic") {
+                            connect_clicked => DetailsWindowInput::Copy(Field::IdHex)
+                        },
+
+                        attach[1, 1, 1, 1] = &gtk::Label {
+                            #[watch] set_label: &model.field(Field::IdNote),
+                            set_xalign: 0.0,
+                            set_selectable: true,
+                            set_wrap: true,
+                            set_wrap_mode: gtk::pango::WrapMode::Char,
+                            add_css_class: "monospace",
+                        },
+                        attach[2, 1, 1, 1] = &gtk::Button::from_icon_name("edit-copy-symbolic") {
+                            connect_clicked => DetailsWindowInput::Copy(Field::IdNote)
+                        },
+
+                        attach[1, 2, 1, 1] = &gtk::Label {
+                            #[watch] set_label: &model.field(Field::IdNevent),
+                            set_xalign: 0.0,
+                            set_selectable: true,
+                            set_wrap: true,
+                            set_wrap_mode: gtk::pango::WrapMode::Char,
+                            add_css_class: "monospace",
+                        },
+                        attach[2, 2, 1, 1] = &gtk::Button::from_icon_name("edit-copy-symbolic") {
+                            connect_clicked => DetailsWindowInput::Copy(Field::IdNevent)
+                        },
+
+                        attach[0, 3, 1, 1] = &gtk::Label {
+                            set_label: "Author",
+                            set_xalign: 1.0,
+                            add_css_class: "label",
+                        },
+                        attach[1, 3, 1, 1] = &gtk::Label {
+                            #[watch] set_label: &model.field(Field::Author),
+                            set_xalign: 0.0,
+                            set_selectable: true,
+                            set_wrap: true,
+                            set_wrap_mode: gtk::pango::WrapMode::Char,
+                            add_css_class: "monospace",
+                        },
+                        attach[2, 3, 1, 1] = &gtk::Button::from_icon_name("edit-copy-symbolic") {
+                            connect_clicked => DetailsWindowInput::Copy(Field::Author)
+                        },
+
+                        attach[0, 4, 1, 1] = &gtk::Label {
+                            set_label: "Client",
+                            set_xalign: 1.0,
+                            add_css_class: "label",
+                        },
+                        attach[1, 4, 1, 1] = &gtk::Label {
+                            #[watch]
+                            set_label: model.details.as_ref().and_then(|d| d.client.as_deref()).unwrap_or("unknown"),
+                            set_xalign: 0.0,
+                            set_selectable: true,
+                        },
+
+                        attach[0, 5, 1, 1] = &gtk::Label {
+                            set_label: "Seen on",
+                            set_xalign: 1.0,
+                            set_valign: gtk::Align::Start,
+                            add_css_class: "label",
+                        },
+                        attach[1, 5, 1, 1] = &gtk::Label {
+                            #[watch] set_label: &model.format_relays(),
+                            set_xalign: 0.0,
+                            set_selectable: true,
+                        },
+
+                        attach[0, 6, 1, 1] = &gtk::Label {
+                            set_label: "Signature",
+                            set_xalign: 1.0,
+                            add_css_class: "label",
+                        },
+                        attach[1, 6, 1, 1] = &gtk::Label {
+                            #[watch] set_label: &model.format_verified(),
+                            set_xalign: 0.0,
+                        },
+                        attach[2, 6, 1, 1] = &gtk::Button::with_label("Verify") {
+                            connect_clicked => DetailsWindowInput::Verify
+                        },
+
+                        attach[0, 7, 1, 1] = &gtk::Label {
+                            set_label: "Published to",
+                            set_xalign: 1.0,
+                            set_valign: gtk::Align::Start,
+                            add_css_class: "label",
+                            #[watch] set_visible: model.is_published(),
+                        },
+                        attach[1, 7, 1, 1] = &gtk::Label {
+                            #[watch] set_label: &model.format_deliveries(),
+                            #[watch] set_visible: model.is_published(),
+                            set_xalign: 0.0,
+                            set_selectable: true,
+                            set_wrap: true,
+                        },
+                        attach[2, 7, 1, 1] = &gtk::Button::from_icon_name("view-refresh-symbolic") {
+                            set_valign: gtk::Align::Start,
+                            set_tooltip_text: Some("Reload responses of relays"),
+                            #[watch] set_visible: model.is_published(),
+                            connect_clicked => DetailsWindowInput::RefreshDeliveries
+                        },
+
+                        attach[0, 8, 1, 1] = &gtk::Label {
+                            set_label: "Delegation",
+                            set_xalign: 1.0,
+                            add_css_class: "label",
+                            #[watch] set_visible: model.is_delegated(),
+                        },
+                        attach[1, 8, 1, 1] = &gtk::Label {
+                            #[watch] set_label: &model.format_delegation(),
+                            #[watch] set_visible: model.is_delegated(),
+                            set_xalign: 0.0,
+                            set_selectable: true,
+                            set_wrap: true,
+                            set_wrap_mode: gtk::pango::WrapMode::WordChar,
+                        },
+
+                        attach[0, 9, 1, 1] = &gtk::Label {
+                            set_label: "Language",
+                            set_xalign: 1.0,
+                            add_css_class: "label",
+                        },
+                        attach[1, 9, 1, 1] = &gtk::Label {
+                            #[watch]
+                            set_label: model.details.as_ref().map(|d| d.language.as_str()).unwrap_or_default(),
+                            set_xalign: 0.0,
+                            set_selectable: true,
+                            set_wrap: true,
+                        },
+                    } -> { set_title: "Text note" },
+
+                    add_child = &gtk::ScrolledWindow {
+                        #[wrap(Some)]
+                        set_child = &model.tags.clone() {
+                            add_css_class: "form",
+                            set_column_spacing: 16,
+                            set_row_spacing: 8,
+                        }
+                    } -> { set_title: "Tags" },
 
                     add_child = &gtk::Box { } -> { set_title: "Author" },
 
+                    add_child = &gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+
+                        gtk::ScrolledWindow {
+                            set_vexpand: true,
+                            #[wrap(Some)]
+                            set_child = &gtk::TextView {
+                                set_buffer: Some(&model.event_buffer),
+                                set_editable: false,
+                                set_monospace: true,
+                            }
+                        },
+
+                        gtk::Button::with_label("Copy JSON") {
+                            set_halign: gtk::Align::End,
+                            connect_clicked => DetailsWindowInput::Copy(Field::Json)
+                        }
+                    } -> { set_title: "Event" },
+
+                    #[name(metadata)]
                     add_child = &gtk::ScrolledWindow {
                         #[wrap(Some)]
                         set_child = &gtk::TextView {
-                            set_buffer: Some(&model.event_buffer),
+                            set_buffer: Some(&model.metadata_buffer),
                             set_editable: false,
                             set_monospace: true,
                         }
-                    } -> { set_title: "Event" },
+                    } -> { set_title: "Metadata" },
 
-                    #[name(metadata)]
                     add_child = &gtk::ScrolledWindow {
                         #[wrap(Some)]
                         set_child = &gtk::TextView {
-                            set_buffer: Some(&model.metadata_buffer),
+                            set_buffer: Some(&model.history_buffer),
                             set_editable: false,
                             set_monospace: true,
                         }
-                    } -> { set_title: "Metadata" }
+                    } -> { set_title: "Profile history" }
 
                 }
             }
@@ -97,8 +323,11 @@ impl Component for DetailsWindow {
         let model = DetailsWindow {
             visible: false,
             details: None,
+            verified: None,
             event_buffer: gtk::TextBuffer::new(None),
             metadata_buffer: gtk::TextBuffer::new(None),
+            history_buffer: gtk::TextBuffer::new(None),
+            tags: gtk::Grid::new(),
         };
         let widgets = view_output!();
 
@@ -108,8 +337,8 @@ impl Component for DetailsWindow {
     fn update(
         &mut self,
         message: Self::Input,
-        _sender: relm4::ComponentSender<Self>,
-        _root: &Self::Root,
+        sender: relm4::ComponentSender<Self>,
+        root: &Self::Root,
     ) {
         match message {
             DetailsWindowInput::Show(details) => {
@@ -117,10 +346,7 @@ impl Component for DetailsWindow {
 
                 self.event_buffer.insert(
                     &mut self.event_buffer.end_iter(),
-                    &format!(
-                        "\n\n\n//Event struct:\n\n{:#?}",
-                        serde_json::from_str::<Event>(&details.event_json).unwrap()
-                    ),
+                    &format!("\n\n\n//Event struct:\n\n{:#?}", details.event),
                 );
 
                 if let Some(metadata_json) = details.metadata_json.as_ref() {
@@ -133,10 +359,186 @@ impl Component for DetailsWindow {
                     }
                 }
 
+                self.history_buffer.set_text(&format_history(
+                    &details.profile_history,
+                    details.time_format,
+                ));
+
+                self.show_tags(&details.event);
                 self.details = Some(details);
+                self.verified = None;
                 self.visible = true;
             }
             DetailsWindowInput::Hide => self.visible = false,
+            DetailsWindowInput::Copy(field) => {
+                if self.details.is_some() {
+                    root.clipboard().set_text(&self.field(field));
+                }
+            }
+            DetailsWindowInput::Verify => {
+                self.verified = self
+                    .details
+                    .as_ref()
+                    .map(|d| d.event.verify().map_err(|e| e.to_string()));
+            }
+            DetailsWindowInput::RefreshDeliveries => {
+                if let Some(d) = &self.details {
+                    sender
+                        .output(DetailsWindowOutput::RefreshDeliveries(d.event.id))
+                        .unwrap_or_default();
+                }
+            }
+            DetailsWindowInput::Deliveries(id, deliveries) => {
+                if let Some(d) = self.details.as_mut().filter(|d| d.event.id == id) {
+                    d.deliveries = deliveries;
+                }
+            }
+        }
+    }
+}
+
+impl DetailsWindow {
+    /// Textual value of `field` of the displayed event.
+    fn field(&self, field: Field) -> String {
+        let details = match &self.details {
+            Some(d) => d,
+            None => return String::new(),
+        };
+
+        match field {
+            Field::IdHex => details.event.id.to_hex(),
+            Field::IdNote => details.event.id.to_bech32().unwrap_or_default(),
+            Field::IdNevent => details.event.nevent(&details.relays),
+            Field::Author => details.event.author().to_bech32().unwrap_or_default(),
+            Field::Json => details.event_json.clone(),
+        }
+    }
+
+    /// Lists relays where the event has been seen, after the relay
+    /// where it has been seen first, if that is known.
+    fn format_relays(&self) -> String {
+        match &self.details {
+            Some(d) if !d.relays.is_empty() => {
+                let mut lines: Vec<String> = d
+                    .relays
+                    .iter()
+                    .map(|r| match d.first_seen.get(r) {
+                        Some(t) => format!("{}  ({})", r, format_full(*t, d.time_format)),
+                        None => r.to_string(),
+                    })
+                    .collect();
+
+                if let Some((relay, t)) = d.first_seen.iter().min_by_key(|(_, t)| **t) {
+                    let first = format!(
+                        "First seen via {} at {}",
+                        relay,
+                        format_full(*t, d.time_format)
+                    );
+                    lines.insert(0, first);
+                }
+
+                lines.join("\n")
+            }
+            _ => "unknown".to_string(),
+        }
+    }
+
+    /// Whether the displayed event has been published by us.
+    fn is_published(&self) -> bool {
+        self.details
+            .as_ref()
+            .map(|d| !d.deliveries.is_empty())
+            .unwrap_or(false)
+    }
+
+    /// Lists relays and their responses to the displayed event.
+    fn format_deliveries(&self) -> String {
+        let deliveries = match &self.details {
+            Some(d) => &d.deliveries,
+            None => return String::new(),
+        };
+
+        deliveries
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Whether the displayed event has a delegation tag (NIP-26).
+    fn is_delegated(&self) -> bool {
+        self.details
+            .as_ref()
+            .map(|d| d.event.delegation().is_some())
+            .unwrap_or(false)
+    }
+
+    /// Tells who posted the displayed event for its author, or why
+    /// its delegation is ignored.
+    fn format_delegation(&self) -> String {
+        let event = match &self.details {
+            Some(d) => &d.event,
+            None => return String::new(),
+        };
+
+        match event.delegation() {
+            None => String::new(),
+            Some(Ok(_)) => format!(
+                "posted via {}",
+                event.pubkey.to_bech32().unwrap_or_default()
+            ),
+            Some(Err(e)) => format!("⚠ invalid, attributed to the signer: {e}"),
+        }
+    }
+
+    fn format_verified(&self) -> String {
+        match &self.verified {
+            None => "not verified yet".to_string(),
+            Some(Ok(())) => "✅ valid".to_string(),
+            Some(Err(e)) => format!("❌ invalid: {e}"),
+        }
+    }
+
+    /// Fills table of tags with those of `event`.
+    fn show_tags(&self, event: &Event) {
+        while let Some(child) = self.tags.first_child() {
+            self.tags.remove(&child);
+        }
+
+        let label = |text: &str, css: &str| {
+            let label = gtk::Label::new(Some(text));
+            label.set_xalign(0.0);
+            label.set_selectable(true);
+            label.set_wrap(true);
+            label.set_wrap_mode(gtk::pango::WrapMode::WordChar);
+            label.add_css_class(css);
+            label
+        };
+
+        for (i, header) in ["Kind", "Values", "Marker"].iter().enumerate() {
+            self.tags.attach(&label(header, "label"), i as i32, 0, 1, 1);
+        }
+
+        for (row, tag) in event.tags.iter().enumerate() {
+            let mut values = tag.as_vec();
+            let kind = if values.is_empty() {
+                String::new()
+            } else {
+                values.remove(0)
+            };
+
+            // Only `e` tags have markers, at their fourth position (NIP-10).
+            let marker = if kind == "e" && values.len() >= 3 {
+                values.remove(2)
+            } else {
+                String::new()
+            };
+
+            let row = row as i32 + 1;
+            self.tags.attach(&label(&kind, "monospace"), 0, row, 1, 1);
+            self.tags
+                .attach(&label(&values.join("\n"), "monospace"), 1, row, 1, 1);
+            self.tags.attach(&label(&marker, "monospace"), 2, row, 1, 1);
         }
     }
 }
@@ -148,13 +550,118 @@ fn pretty_content(metadata_json: &str) -> Option<String> {
     serde_json::to_string_pretty(&content_value).ok()
 }
 
+/// Changes of profile from the newest, each as lines of removed and
+/// added values, like a diff.
+fn format_history(changes: &[ProfileChange], time_format: TimeFormat) -> String {
+    if changes.is_empty() {
+        return "No name, picture or NIP-05 of the author is known yet.".to_string();
+    }
+
+    changes
+        .iter()
+        .map(|change| {
+            let mut lines = vec![format_full(change.created_at, time_format)];
+            if change.first {
+                lines[0].push_str("  (first known)");
+            }
+            for field in &change.fields {
+                if let Some(before) = &field.before {
+                    lines.push(format!("- {}: {}", field.field, before));
+                }
+                if let Some(after) = &field.after {
+                    lines.push(format!("+ {}: {}", field.field, after));
+                }
+            }
+            lines.join("\n")
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
 /// All available information about a note.
 // TODO: Could it be passed as input to Note widget?
 #[derive(Clone, Debug)]
 pub struct Details {
+    /// The note event.
+    pub event: Arc<Event>,
+
     /// Complete JSON of the note event.
     pub event_json: String,
 
     /// Complete JSON of the author metadata.
     pub metadata_json: Option<String>,
+
+    /// Client that created the note.
+    pub client: Option<String>,
+
+    /// What language detection says about the note.
+    pub language: String,
+
+    /// Relays on which the note was seen.
+    pub relays: Vec<Url>,
+
+    /// When the note was first seen on relays, where it is known.
+    pub first_seen: HashMap<Url, Timestamp>,
+
+    /// How the times of first sightings are shown.
+    pub time_format: TimeFormat,
+
+    /// Responses of relays to the note, if we have published it.
+    pub deliveries: Vec<Delivery>,
+
+    /// Changes of the author's profile, newest first.
+    pub profile_history: Vec<ProfileChange>,
+}
+
+#[cfg(test)]
+mod tests {
+    use nostr_sdk::prelude::Timestamp;
+
+    use super::*;
+    use crate::metadata_history::FieldChange;
+
+    #[test]
+    fn history_reads_like_a_diff() {
+        let format = TimeFormat::default();
+        let changes = [
+            ProfileChange {
+                created_at: Timestamp::from(20),
+                fields: vec![
+                    FieldChange {
+                        field: "name",
+                        before: Some("alice".to_string()),
+                        after: Some("bob".to_string()),
+                    },
+                    FieldChange {
+                        field: "picture",
+                        before: Some("a.png".to_string()),
+                        after: None,
+                    },
+                ],
+                first: false,
+            },
+            ProfileChange {
+                created_at: Timestamp::from(10),
+                fields: vec![FieldChange {
+                    field: "name",
+                    before: None,
+                    after: Some("alice".to_string()),
+                }],
+                first: true,
+            },
+        ];
+
+        assert_eq!(
+            format_history(&changes, format),
+            format!(
+                "{}\n- name: alice\n+ name: bob\n- picture: a.png\n\n{}  (first known)\n+ name: alice",
+                format_full(Timestamp::from(20), format),
+                format_full(Timestamp::from(10), format)
+            )
+        );
+        assert_eq!(
+            format_history(&[], format),
+            "No name, picture or NIP-05 of the author is known yet."
+        );
+    }
 }
@@ -0,0 +1,262 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use gtk::prelude::*;
+use gtk::{gdk, pango};
+use relm4::gtk;
+use reqwest::Url;
+
+use crate::nostr::Emoji;
+
+/// Custom emoji are displayed this large, in pixels.
+const EMOJI_SIZE: i32 = 20;
+
+/// Character standing in the text for custom emoji.
+const PLACEHOLDER: char = '\u{fffc}';
+
+/// Decoded custom emoji (NIP-30). Every emoji is decoded only once and
+/// the same texture is then handed to all lanes and their notes.
+///
+/// The cache holds at most `capacity` textures, the least recently used
+/// ones are evicted first.
+#[derive(Debug)]
+pub struct Emojis {
+    capacity: usize,
+    /// Monotonic counter used to determine recency of use.
+    tick: u64,
+    textures: HashMap<Url, CachedEmoji>,
+    /// Emoji being downloaded or decoded right now.
+    decoding: HashSet<Url>,
+}
+
+#[derive(Debug)]
+struct CachedEmoji {
+    bitmap: Arc<gdk::Texture>,
+    last_used: u64,
+}
+
+impl Emojis {
+    pub fn new(capacity: usize) -> Emojis {
+        Emojis {
+            capacity,
+            tick: 0,
+            textures: Default::default(),
+            decoding: Default::default(),
+        }
+    }
+
+    /// Returns decoded emoji from `url` if it is cached.
+    pub fn get(&mut self, url: &Url) -> Option<Arc<gdk::Texture>> {
+        self.tick += 1;
+        let tick = self.tick;
+
+        self.textures.get_mut(url).map(|cached| {
+            cached.last_used = tick;
+            cached.bitmap.clone()
+        })
+    }
+
+    /// Marks emoji from `url` as being obtained. Returns `false` if it
+    /// already is, in which case there is no need to obtain it again.
+    pub fn start_decoding(&mut self, url: Url) -> bool {
+        self.decoding.insert(url)
+    }
+
+    /// Stores decoded emoji from `url`, evicting the least recently used
+    /// one if the cache is full.
+    pub fn insert(&mut self, url: Url, bitmap: Arc<gdk::Texture>) {
+        self.decoding.remove(&url);
+        self.tick += 1;
+
+        self.textures.insert(
+            url,
+            CachedEmoji {
+                bitmap,
+                last_used: self.tick,
+            },
+        );
+
+        if self.textures.len() > self.capacity {
+            let oldest = self
+                .textures
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(url, _)| url.clone());
+
+            if let Some(url) = oldest {
+                self.textures.remove(&url);
+            }
+        }
+    }
+
+    /// Emoji from `url` could not be downloaded or decoded.
+    pub fn failed(&mut self, url: &Url) {
+        self.decoding.remove(url);
+    }
+}
+
+/// Label displaying Pango markup in which `:shortcode:` of custom emoji
+/// is replaced by its image. Space for every image is reserved within
+/// the text and the image is drawn over it. Emoji whose image is not
+/// available stay written as `:shortcode:`.
+#[derive(Debug)]
+pub struct EmojiLabel {
+    overlay: gtk::Overlay,
+    label: gtk::Label,
+    /// Displayed images and byte indices of their placeholders in text of the label.
+    pictures: Rc<RefCell<Vec<(gtk::Picture, i32)>>>,
+}
+
+impl EmojiLabel {
+    pub fn new() -> EmojiLabel {
+        let label = gtk::Label::new(None);
+        let overlay = gtk::Overlay::new();
+        overlay.set_child(Some(&label));
+
+        let pictures: Rc<RefCell<Vec<(gtk::Picture, i32)>>> = Default::default();
+
+        overlay.connect_get_child_position({
+            let label = label.clone();
+            let pictures = pictures.clone();
+            move |overlay, widget| {
+                let index = pictures
+                    .borrow()
+                    .iter()
+                    .find(|(p, _)| p.upcast_ref::<gtk::Widget>() == widget)
+                    .map(|(_, i)| *i)?;
+
+                let (x, y) = label.layout_offsets();
+                let pos = label.layout().index_to_pos(index);
+                let (x, y) = label.translate_coordinates(
+                    overlay,
+                    (x + pos.x() / pango::SCALE) as f64,
+                    (y + pos.y() / pango::SCALE) as f64,
+                )?;
+
+                Some(gdk::Rectangle::new(
+                    x as i32, y as i32, EMOJI_SIZE, EMOJI_SIZE,
+                ))
+            }
+        });
+
+        EmojiLabel {
+            overlay,
+            label,
+            pictures,
+        }
+    }
+
+    pub fn widget(&self) -> &gtk::Overlay {
+        &self.overlay
+    }
+
+    /// The label itself, to be styled and connected to.
+    pub fn label(&self) -> &gtk::Label {
+        &self.label
+    }
+
+    /// Displays `markup`, with those of `emojis` whose images are among
+    /// `bitmaps` drawn as images.
+    pub fn set_markup(
+        &self,
+        markup: &str,
+        emojis: &[Emoji],
+        bitmaps: &HashMap<Url, Arc<gdk::Texture>>,
+    ) {
+        let bitmap_of = |shortcode: &str| {
+            emojis
+                .iter()
+                .find(|e| e.shortcode == shortcode)
+                .and_then(|e| bitmaps.get(&e.url))
+        };
+
+        let (markup, shortcodes) = substitute(markup, |s| bitmap_of(s).is_some());
+        self.label.set_markup(&markup);
+
+        let displayed = std::mem::take(&mut *self.pictures.borrow_mut());
+        for (picture, _) in displayed {
+            self.overlay.remove_overlay(&picture);
+        }
+
+        if shortcodes.is_empty() {
+            self.label.set_attributes(None);
+            return;
+        }
+
+        let size = EMOJI_SIZE * pango::SCALE;
+        // The image sits on the baseline, a bit below it like letters do.
+        let rect = pango::Rectangle::new(0, -size * 4 / 5, size, size);
+        let attributes = pango::AttrList::new();
+
+        let text = self.label.text();
+        let indices = text.match_indices(PLACEHOLDER).map(|(i, _)| i as i32);
+
+        for (index, shortcode) in indices.zip(&shortcodes) {
+            let mut shape = pango::AttrShape::new(&rect, &rect);
+            shape.set_start_index(index as u32);
+            shape.set_end_index((index as usize + PLACEHOLDER.len_utf8()) as u32);
+            attributes.insert(shape);
+
+            let picture = gtk::Picture::new();
+            picture.set_paintable(bitmap_of(shortcode).map(|b| b.as_ref()));
+            picture.set_can_shrink(true);
+            picture.set_can_target(false);
+            picture.set_tooltip_text(Some(&format!(":{shortcode}:")));
+            self.overlay.add_overlay(&picture);
+            self.pictures.borrow_mut().push((picture, index));
+        }
+
+        self.label.set_attributes(Some(&attributes));
+    }
+}
+
+impl Default for EmojiLabel {
+    fn default() -> Self {
+        EmojiLabel::new()
+    }
+}
+
+/// Replaces `:shortcode:` in text of `markup` by placeholder character
+/// wherever `available` says that the emoji can be drawn. Shortcodes in
+/// links and in attributes are left as they are. Returns the new markup
+/// and the replaced shortcodes in order of their appearance.
+fn substitute<F>(markup: &str, available: F) -> (String, Vec<String>)
+where
+    F: Fn(&str) -> bool,
+{
+    let mut result = String::with_capacity(markup.len());
+    let mut shortcodes = Vec::new();
+    let mut in_tag = false;
+    let mut in_link = false;
+    let mut rest = markup;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '<' {
+            in_tag = true;
+            if rest.starts_with("<a ") {
+                in_link = true;
+            } else if rest.starts_with("</a>") {
+                in_link = false;
+            }
+        } else if c == '>' {
+            in_tag = false;
+        } else if c == ':' && !in_tag && !in_link {
+            let candidate = rest[1..].split(':').next().unwrap_or_default();
+            let closed = rest[1..].len() > candidate.len();
+
+            if closed && Emoji::is_shortcode(candidate) && available(candidate) {
+                result.push(PLACEHOLDER);
+                shortcodes.push(candidate.to_string());
+                rest = &rest[candidate.len() + 2..];
+                continue;
+            }
+        }
+
+        result.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    (result, shortcodes)
+}
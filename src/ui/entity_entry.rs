@@ -0,0 +1,237 @@
+use std::sync::Arc;
+
+use gtk::gdk;
+use gtk::prelude::*;
+use nostr_sdk::prelude::XOnlyPublicKey;
+use relm4::*;
+
+use crate::content::{EntityError, EntityKind, Target};
+use crate::nostr::short_npub;
+use crate::ui::avatars;
+use crate::Gnostique;
+
+/// Entry for npub, nprofile, note, nevent or hex, which tells right away
+/// whether what has been entered is valid, and if not, what is wrong
+/// with it. Known users entered are shown with their names and avatars.
+/// Parent learns the entered entity only once it is valid.
+#[derive(Debug)]
+pub struct EntityEntry {
+    gnostique: Gnostique,
+    kind: EntityKind,
+    entry: gtk::Entry,
+    parsed: Result<Target, EntityError>,
+    /// Name and avatar of the entered user, if known.
+    preview: Option<(XOnlyPublicKey, String, Arc<gdk::Texture>)>,
+}
+
+#[derive(Debug)]
+pub enum EntityEntryInput {
+    /// Text of the entry has changed.
+    Changed,
+    /// User has pressed Enter.
+    Activate,
+    /// Empty the entry.
+    Clear,
+}
+
+#[derive(Debug)]
+pub enum EntityEntryOutput {
+    /// Entered entity, `None` while nothing valid is entered. `empty`
+    /// tells apart nothing entered from something wrong.
+    Changed { target: Option<Target>, empty: bool },
+    /// User has pressed Enter with valid entity entered.
+    Activated,
+}
+
+#[derive(Debug)]
+pub enum EntityEntryCmd {
+    /// Name and avatar of `pubkey`, as far as they are known.
+    Preview {
+        pubkey: XOnlyPublicKey,
+        name: Option<String>,
+        avatar: Option<Arc<gdk::Texture>>,
+    },
+}
+
+#[relm4::component(pub)]
+impl Component for EntityEntry {
+    /// Gnostique, which entities are taken and placeholder of the entry.
+    type Init = (Gnostique, EntityKind, &'static str);
+    type Input = EntityEntryInput;
+    type Output = EntityEntryOutput;
+    type CommandOutput = EntityEntryCmd;
+
+    view! {
+        gtk::Box {
+            set_orientation: gtk::Orientation::Vertical,
+            set_spacing: 4,
+            add_css_class: "entity-entry",
+
+            #[local_ref]
+            entry -> gtk::Entry {
+                set_hexpand: true,
+                set_placeholder_text: Some(placeholder),
+                connect_changed => EntityEntryInput::Changed,
+                connect_activate => EntityEntryInput::Activate,
+                #[watch] set_secondary_icon_name: model.icon(),
+                #[watch] set_class_active: ("valid", model.parsed.is_ok()),
+                #[watch] set_class_active: ("invalid", model.error().is_some()),
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 6,
+                #[watch] set_visible: model.preview.is_some(),
+
+                gtk::Image {
+                    set_pixel_size: 24,
+                    #[watch] set_paintable: model.preview.as_ref().map(|(_, _, a)| a.as_ref()),
+                },
+
+                gtk::Label {
+                    set_xalign: 0.0,
+                    set_ellipsize: gtk::pango::EllipsizeMode::End,
+                    add_css_class: "name",
+                    #[watch] set_label: model.preview.as_ref().map(|(_, n, _)| n.as_str()).unwrap_or_default(),
+                },
+            },
+
+            gtk::Label {
+                set_xalign: 0.0,
+                set_wrap: true,
+                add_css_class: "error",
+                #[watch] set_visible: model.error().is_some(),
+                #[watch] set_label: &model.error().map(|e| e.to_string()).unwrap_or_default(),
+            },
+        }
+    }
+
+    fn init(
+        (gnostique, kind, placeholder): Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = EntityEntry {
+            gnostique,
+            kind,
+            entry: gtk::Entry::new(),
+            parsed: Err(EntityError::Empty),
+            preview: None,
+        };
+
+        let entry = &model.entry;
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>, _root: &Self::Root) {
+        match message {
+            EntityEntryInput::Changed => {
+                let parsed = Target::parse(&self.entry.text(), self.kind);
+                if parsed == self.parsed {
+                    return;
+                }
+                self.parsed = parsed;
+
+                match &self.parsed {
+                    Ok(Target::Profile(pubkey, _)) => {
+                        let pubkey = *pubkey;
+                        // Unknown users are shown by their npub until
+                        // their metadata are looked up.
+                        self.preview =
+                            Some((pubkey, short_npub(&pubkey), avatars::placeholder(&pubkey)));
+                        let gnostique = self.gnostique.clone();
+                        sender.oneshot_command(async move { preview(&gnostique, pubkey).await });
+                    }
+                    _ => self.preview = None,
+                }
+
+                sender
+                    .output(EntityEntryOutput::Changed {
+                        target: self.parsed.clone().ok(),
+                        empty: self.parsed == Err(EntityError::Empty),
+                    })
+                    .unwrap_or_default();
+            }
+
+            EntityEntryInput::Activate => {
+                if self.parsed.is_ok() {
+                    sender
+                        .output(EntityEntryOutput::Activated)
+                        .unwrap_or_default();
+                }
+            }
+
+            EntityEntryInput::Clear => self.entry.set_text(""),
+        }
+    }
+
+    fn update_cmd(
+        &mut self,
+        message: Self::CommandOutput,
+        _sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        match message {
+            EntityEntryCmd::Preview {
+                pubkey,
+                name,
+                avatar,
+            } => {
+                // Another user may have been entered meanwhile.
+                if let Some((shown, shown_name, shown_avatar)) = &mut self.preview {
+                    if *shown == pubkey {
+                        if let Some(name) = name {
+                            *shown_name = name;
+                        }
+                        if let Some(avatar) = avatar {
+                            *shown_avatar = avatar;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl EntityEntry {
+    /// What is wrong with entered text, nothing if it is empty.
+    fn error(&self) -> Option<&EntityError> {
+        match &self.parsed {
+            Err(EntityError::Empty) | Ok(_) => None,
+            Err(e) => Some(e),
+        }
+    }
+
+    fn icon(&self) -> Option<&'static str> {
+        match &self.parsed {
+            Ok(_) => Some("emblem-ok-symbolic"),
+            Err(EntityError::Empty) => None,
+            Err(_) => Some("dialog-error-symbolic"),
+        }
+    }
+}
+
+/// Looks up stored name of `pubkey` and its avatar, if already downloaded.
+async fn preview(gnostique: &Gnostique, pubkey: XOnlyPublicKey) -> EntityEntryCmd {
+    let persona = gnostique.get_persona(pubkey).await;
+    let file = match persona.as_ref().and_then(|p| p.avatar.as_ref()) {
+        Some(url) => gnostique.download().cached(url).await,
+        None => None,
+    };
+    let avatar = match file {
+        Some(file) => tokio::task::spawn_blocking(move || avatars::decode(&file).ok())
+            .await
+            .ok()
+            .flatten()
+            .map(Arc::new),
+        None => None,
+    };
+
+    EntityEntryCmd::Preview {
+        pubkey,
+        name: persona.map(|p| p.shown_name()),
+        avatar,
+    }
+}
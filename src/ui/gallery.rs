@@ -0,0 +1,142 @@
+use std::sync::Arc;
+
+use gtk::gdk;
+use gtk::prelude::*;
+use relm4::factory::{FactoryComponent, FactoryVecDeque};
+use relm4::prelude::*;
+use relm4::{gtk, FactorySender};
+use reqwest::Url;
+
+/// Widget displaying thumbnails of images that a text note refers to.
+#[derive(Debug)]
+pub struct Gallery {
+    images: FactoryVecDeque<Thumbnail>,
+}
+
+#[derive(Debug)]
+pub enum GalleryInput {
+    /// Image from `url` has been downloaded and decoded.
+    ImageBitmap { url: Url, bitmap: Arc<gdk::Texture> },
+    /// User wants to see image from `url` in full size.
+    Open(Url),
+}
+
+#[derive(Debug)]
+pub enum GalleryOutput {
+    /// Image from `url` should be opened in full size.
+    Open(Url),
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for Gallery {
+    type Input = GalleryInput;
+    type Output = GalleryOutput;
+    type Init = Vec<Url>;
+
+    #[rustfmt::skip]
+    view! {
+        gtk::Box {
+            add_css_class: "gallery",
+            #[watch]
+            set_visible: !model.images.is_empty(),
+
+            #[local_ref]
+            images_box -> gtk::FlowBox {
+                set_selection_mode: gtk::SelectionMode::None,
+                set_homogeneous: true,
+                set_max_children_per_line: 3,
+                set_column_spacing: 6,
+                set_row_spacing: 6,
+                set_hexpand: true,
+            }
+        }
+    }
+
+    fn init(
+        urls: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let mut images = FactoryVecDeque::new(gtk::FlowBox::default(), sender.input_sender());
+
+        {
+            let mut guard = images.guard();
+            for url in urls {
+                guard.push_back(url);
+            }
+        }
+
+        let model = Gallery { images };
+        let images_box = model.images.widget();
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        match message {
+            GalleryInput::ImageBitmap { url, bitmap } => {
+                let mut guard = self.images.guard();
+                for thumbnail in guard.iter_mut() {
+                    if thumbnail.url == url {
+                        thumbnail.bitmap = Some(bitmap.clone());
+                    }
+                }
+            }
+            GalleryInput::Open(url) => sender.output(GalleryOutput::Open(url)).unwrap_or_default(),
+        }
+    }
+}
+
+/// One image within [`Gallery`]. Shows a spinner until the image is available.
+#[derive(Debug)]
+pub struct Thumbnail {
+    url: Url,
+    bitmap: Option<Arc<gdk::Texture>>,
+}
+
+#[relm4::factory(pub)]
+impl FactoryComponent for Thumbnail {
+    type Init = Url;
+    type Input = ();
+    type Output = Url;
+    type CommandOutput = ();
+    type ParentInput = GalleryInput;
+    type ParentWidget = gtk::FlowBox;
+
+    #[rustfmt::skip]
+    view! {
+        gtk::Button {
+            add_css_class: "thumbnail",
+            set_tooltip_text: Some(self.url.as_str()),
+            connect_clicked[sender, url = self.url.clone()] => move |_| {
+                sender.output(url.clone())
+            },
+
+            gtk::Box {
+                set_halign: gtk::Align::Center,
+
+                gtk::Spinner {
+                    #[watch] set_visible: self.bitmap.is_none(),
+                    set_spinning: true,
+                },
+
+                gtk::Picture {
+                    #[watch] set_visible: self.bitmap.is_some(),
+                    #[watch] set_paintable: self.bitmap.as_ref().map(|b| b.as_ref()),
+                    set_content_fit: gtk::ContentFit::Cover,
+                    set_can_shrink: true,
+                    set_size_request: (160, 120),
+                }
+            }
+        }
+    }
+
+    fn output_to_parent_input(url: Self::Output) -> Option<Self::ParentInput> {
+        Some(GalleryInput::Open(url))
+    }
+
+    fn init_model(url: Self::Init, _index: &DynamicIndex, _sender: FactorySender<Self>) -> Self {
+        Thumbnail { url, bitmap: None }
+    }
+}
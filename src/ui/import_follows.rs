@@ -0,0 +1,505 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use gtk::gdk;
+use gtk::prelude::*;
+use nostr_sdk::prelude::XOnlyPublicKey;
+use relm4::*;
+use reqwest::Url;
+
+use crate::content::{EntityKind, Target};
+use crate::nostr::{parse_pubkey, short_npub, Persona};
+use crate::ui::avatars::placeholder;
+use crate::ui::entity_entry::*;
+use crate::Gnostique;
+
+/// Dialog for following, at once, everybody followed by another account
+/// or listed in pasted text. Found users are shown before following, so
+/// that some of them can be left out.
+#[derive(Debug)]
+pub struct ImportFollowsDialog {
+    gnostique: Gnostique,
+    visible: bool,
+    /// Whether follows are copied from another account, rather than
+    /// from a list of npubs.
+    from_account: bool,
+    /// Whether users are being looked up or followed right now.
+    busy: bool,
+    /// How many users have been found.
+    status: Option<String>,
+    /// What could not be understood or has failed.
+    error: Option<String>,
+    account: Controller<EntityEntry>,
+    /// Account entered, once it is valid.
+    entered: Option<XOnlyPublicKey>,
+    list: gtk::TextView,
+    rows: gtk::ListBox,
+    candidates: Vec<Candidate>,
+}
+
+/// User who may be followed, with their row in the dialog.
+#[derive(Debug)]
+struct Candidate {
+    pubkey: XOnlyPublicKey,
+    check: gtk::CheckButton,
+    name: gtk::Label,
+    avatar: gtk::Image,
+}
+
+#[derive(Debug)]
+pub enum ImportFollowsInput {
+    Show,
+    Hide,
+    /// Copy follows of an account, or read a list of npubs.
+    FromAccount(bool),
+    /// Valid account has been entered, or it is not valid anymore.
+    Account(Option<XOnlyPublicKey>),
+    /// Find users to follow according to what has been entered.
+    LookUp,
+    /// Select or deselect all found users.
+    SelectAll(bool),
+    /// Follow selected users.
+    Follow,
+    /// Metadata of a user have arrived.
+    Metadata(Box<Persona>),
+    /// Avatar of a user has been decoded.
+    Avatar(XOnlyPublicKey, Arc<gdk::Texture>),
+}
+
+#[derive(Debug)]
+pub enum ImportFollowsOutput {
+    /// These users are followed now.
+    Followed(Vec<XOnlyPublicKey>),
+    /// Avatars of found users, at these URLs, should be displayed.
+    NeedAvatars(Vec<(XOnlyPublicKey, Url)>),
+}
+
+#[derive(Debug)]
+pub enum ImportFollowsCmd {
+    Found(Result<Found, String>),
+    Followed(Result<Vec<XOnlyPublicKey>, String>),
+}
+
+/// Users found to be followed.
+#[derive(Debug)]
+pub struct Found {
+    /// Users not followed yet, with their metadata if already known.
+    people: Vec<(XOnlyPublicKey, Option<Persona>)>,
+    /// Lines which are not npubs.
+    invalid: Vec<String>,
+    /// Number of found users who are followed already.
+    already: usize,
+}
+
+#[relm4::component(pub)]
+impl Component for ImportFollowsDialog {
+    type Init = Gnostique;
+    type Input = ImportFollowsInput;
+    type Output = ImportFollowsOutput;
+    type CommandOutput = ImportFollowsCmd;
+
+    view! {
+        gtk::Window {
+            set_title: Some("Import follows"),
+            set_default_size: (450, 550),
+            #[watch] set_visible: model.visible,
+
+            connect_close_request[sender] => move |_| {
+                sender.input(ImportFollowsInput::Hide);
+                gtk::Inhibit(true)
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 16,
+                add_css_class: "form",
+
+                gtk::Box {
+                    add_css_class: "linked",
+                    set_halign: gtk::Align::Center,
+
+                    #[name = "account_button"]
+                    gtk::ToggleButton {
+                        set_label: "Follows of account",
+                        set_active: true,
+                        connect_toggled[sender] => move |b| {
+                            if b.is_active() {
+                                sender.input(ImportFollowsInput::FromAccount(true));
+                            }
+                        }
+                    },
+
+                    gtk::ToggleButton {
+                        set_label: "List of npubs",
+                        set_group: Some(&account_button),
+                        connect_toggled[sender] => move |b| {
+                            if b.is_active() {
+                                sender.input(ImportFollowsInput::FromAccount(false));
+                            }
+                        }
+                    },
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 8,
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_hexpand: true,
+
+                        #[local_ref]
+                        account -> gtk::Box {
+                            #[watch] set_visible: model.from_account,
+                        },
+
+                        gtk::ScrolledWindow {
+                            #[watch] set_visible: !model.from_account,
+                            set_min_content_height: 100,
+                            set_child: Some(&model.list),
+                        },
+                    },
+
+                    gtk::Button::with_label("Look up") {
+                        set_valign: gtk::Align::Start,
+                        #[watch] set_sensitive: !model.busy && (!model.from_account || model.entered.is_some()),
+                        connect_clicked => ImportFollowsInput::LookUp
+                    },
+                },
+
+                gtk::Label {
+                    #[watch] set_label: model.error.as_deref().unwrap_or_default(),
+                    #[watch] set_visible: model.error.is_some(),
+                    set_wrap: true,
+                    set_xalign: 0.0,
+                    add_css_class: "error",
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 8,
+                    #[watch] set_visible: model.status.is_some(),
+
+                    gtk::Label {
+                        #[watch] set_label: model.status.as_deref().unwrap_or_default(),
+                        set_hexpand: true,
+                        set_wrap: true,
+                        set_xalign: 0.0,
+                    },
+
+                    gtk::CheckButton {
+                        set_label: Some("All"),
+                        set_active: true,
+                        connect_toggled[sender] => move |b| {
+                            sender.input(ImportFollowsInput::SelectAll(b.is_active()));
+                        }
+                    },
+                },
+
+                gtk::ScrolledWindow {
+                    set_vexpand: true,
+                    set_hscrollbar_policy: gtk::PolicyType::Never,
+                    set_child: Some(&model.rows),
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 8,
+
+                    gtk::Spinner {
+                        #[watch] set_spinning: model.busy,
+                        #[watch] set_visible: model.busy,
+                    },
+
+                    gtk::Box { set_hexpand: true },
+
+                    gtk::Button::with_label("Cancel") {
+                        connect_clicked => ImportFollowsInput::Hide
+                    },
+
+                    gtk::Button::with_label("Follow selected") {
+                        add_css_class: "suggested-action",
+                        #[watch] set_sensitive: !model.busy && !model.candidates.is_empty(),
+                        connect_clicked => ImportFollowsInput::Follow
+                    }
+                }
+            }
+        }
+    }
+
+    fn init(
+        gnostique: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = ImportFollowsDialog {
+            gnostique: gnostique.clone(),
+            visible: false,
+            from_account: true,
+            busy: false,
+            status: None,
+            error: None,
+            account: EntityEntry::builder()
+                .launch((
+                    gnostique.clone(),
+                    EntityKind::Profile,
+                    "npub of the account",
+                ))
+                .forward(sender.input_sender(), |output| match output {
+                    EntityEntryOutput::Changed { target, .. } => {
+                        ImportFollowsInput::Account(match target {
+                            Some(Target::Profile(pubkey, _)) => Some(pubkey),
+                            _ => None,
+                        })
+                    }
+                    EntityEntryOutput::Activated => ImportFollowsInput::LookUp,
+                }),
+            entered: None,
+            list: gtk::TextView::new(),
+            rows: gtk::ListBox::new(),
+            candidates: Vec::new(),
+        };
+        model.list.set_wrap_mode(gtk::WrapMode::WordChar);
+        model.rows.set_selection_mode(gtk::SelectionMode::None);
+
+        let account = model.account.widget();
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>, _root: &Self::Root) {
+        match message {
+            ImportFollowsInput::Show => {
+                self.error = None;
+                self.visible = true;
+            }
+
+            ImportFollowsInput::Hide => self.visible = false,
+
+            ImportFollowsInput::FromAccount(from_account) => self.from_account = from_account,
+
+            ImportFollowsInput::Account(pubkey) => self.entered = pubkey,
+
+            ImportFollowsInput::LookUp => {
+                let gnostique = self.gnostique.clone();
+                let source = if self.from_account {
+                    match self.entered {
+                        Some(pubkey) => Source::Account(pubkey),
+                        None => {
+                            self.error = Some("Enter npub of the account".to_string());
+                            return;
+                        }
+                    }
+                } else {
+                    let buffer = self.list.buffer();
+                    let (start, end) = buffer.bounds();
+                    Source::List(buffer.text(&start, &end, false).to_string())
+                };
+
+                self.busy = true;
+                self.error = None;
+                sender.oneshot_command(async move {
+                    ImportFollowsCmd::Found(find(&gnostique, source).await)
+                });
+            }
+
+            ImportFollowsInput::SelectAll(selected) => {
+                for candidate in &self.candidates {
+                    candidate.check.set_active(selected);
+                }
+            }
+
+            ImportFollowsInput::Follow => {
+                let pubkeys: Vec<XOnlyPublicKey> = self
+                    .candidates
+                    .iter()
+                    .filter(|c| c.check.is_active())
+                    .map(|c| c.pubkey)
+                    .collect();
+                let gnostique = self.gnostique.clone();
+
+                self.busy = true;
+                self.error = None;
+                sender.oneshot_command(async move {
+                    ImportFollowsCmd::Followed(gnostique.follow_all(&pubkeys).await)
+                });
+            }
+
+            ImportFollowsInput::Metadata(persona) => {
+                if let Some(candidate) = self.candidate(&persona.pubkey) {
+                    candidate.name.set_label(&persona.shown_name());
+                }
+            }
+
+            ImportFollowsInput::Avatar(pubkey, bitmap) => {
+                if let Some(candidate) = self.candidate(&pubkey) {
+                    candidate.avatar.set_paintable(Some(bitmap.as_ref()));
+                }
+            }
+        }
+    }
+
+    fn update_cmd(
+        &mut self,
+        message: Self::CommandOutput,
+        sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        self.busy = false;
+
+        match message {
+            ImportFollowsCmd::Found(Ok(found)) => self.show_found(found, &sender),
+            ImportFollowsCmd::Found(Err(e)) => self.error = Some(e),
+            ImportFollowsCmd::Followed(Ok(followed)) => {
+                self.visible = false;
+                self.clear();
+                sender
+                    .output(ImportFollowsOutput::Followed(followed))
+                    .unwrap_or_default();
+            }
+            ImportFollowsCmd::Followed(Err(e)) => {
+                self.error = Some(format!("Could not follow: {e}"));
+            }
+        }
+    }
+}
+
+impl ImportFollowsDialog {
+    fn candidate(&self, pubkey: &XOnlyPublicKey) -> Option<&Candidate> {
+        self.candidates.iter().find(|c| c.pubkey == *pubkey)
+    }
+
+    /// Forgets found users.
+    fn clear(&mut self) {
+        while let Some(row) = self.rows.first_child() {
+            self.rows.remove(&row);
+        }
+        self.candidates.clear();
+        self.status = None;
+    }
+
+    /// Replaces found users with those just `found`.
+    fn show_found(&mut self, found: Found, sender: &ComponentSender<Self>) {
+        self.clear();
+
+        let mut avatars = Vec::new();
+
+        for (pubkey, persona) in found.people {
+            let check = gtk::CheckButton::new();
+            check.set_active(true);
+
+            let avatar = gtk::Image::from_paintable(Some(placeholder(&pubkey).as_ref()));
+            avatar.set_pixel_size(32);
+
+            let name = gtk::Label::new(Some(
+                &persona
+                    .as_ref()
+                    .map(|p| p.shown_name())
+                    .unwrap_or_else(|| short_npub(&pubkey)),
+            ));
+            name.set_xalign(0.0);
+            name.set_hexpand(true);
+            name.add_css_class("name");
+
+            let npub = gtk::Label::new(Some(&short_npub(&pubkey)));
+            npub.add_css_class("dim-label");
+
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+            row.append(&check);
+            row.append(&avatar);
+            row.append(&name);
+            row.append(&npub);
+            self.rows.append(&row);
+
+            if let Some(url) = persona.and_then(|p| p.avatar) {
+                avatars.push((pubkey, url));
+            }
+
+            self.candidates.push(Candidate {
+                pubkey,
+                check,
+                name,
+                avatar,
+            });
+        }
+
+        self.status = Some(match found.already {
+            0 => format!("{} people to follow", self.candidates.len()),
+            already => format!(
+                "{} people to follow, {already} already followed",
+                self.candidates.len()
+            ),
+        });
+        if !found.invalid.is_empty() {
+            self.error = Some(format!("Not npubs: {}", found.invalid.join(", ")));
+        }
+
+        if !avatars.is_empty() {
+            sender
+                .output(ImportFollowsOutput::NeedAvatars(avatars))
+                .unwrap_or_default();
+        }
+    }
+}
+
+/// Where users to follow come from.
+enum Source {
+    /// Account whose follows are copied.
+    Account(XOnlyPublicKey),
+    /// npubs, one per line.
+    List(String),
+}
+
+/// Finds users in `source` who are not followed yet. Metadata of those
+/// not known are requested, all at once, and arrive later.
+async fn find(gnostique: &Gnostique, source: Source) -> Result<Found, String> {
+    let mut invalid = Vec::new();
+
+    let pubkeys = match source {
+        Source::Account(pubkey) => gnostique.contact_list_of(pubkey).await?,
+        Source::List(text) => text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .filter_map(|(i, line)| {
+                let pubkey = parse_pubkey(line);
+                if pubkey.is_none() {
+                    invalid.push(format!("line {} ({})", i + 1, line.trim()));
+                }
+                pubkey
+            })
+            .collect(),
+    };
+
+    let me = gnostique.client().keys().public_key();
+    let followed: HashSet<XOnlyPublicKey> = gnostique.follows().await.into_iter().collect();
+    let mut seen = HashSet::new();
+    let mut already = 0;
+    let mut people = Vec::new();
+    let mut unknown = Vec::new();
+
+    for pubkey in pubkeys {
+        if pubkey == me || !seen.insert(pubkey) {
+            continue;
+        }
+        if followed.contains(&pubkey) {
+            already += 1;
+            continue;
+        }
+
+        let persona = gnostique.get_persona(pubkey).await;
+        if persona.is_none() {
+            unknown.push(pubkey);
+        }
+        people.push((pubkey, persona));
+    }
+
+    gnostique.demand().metadata_of(unknown).await;
+
+    Ok(Found {
+        people,
+        invalid,
+        already,
+    })
+}
@@ -0,0 +1,43 @@
+use nostr_sdk::nostr::secp256k1::XOnlyPublicKey;
+use nostr_sdk::nostr::Event;
+use serde::{Deserialize, Serialize};
+
+use crate::nostr::{EventExt, Repost};
+
+/// Which notes a lane hides, chosen for every lane separately.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LaneFilter {
+    pub hide_replies: bool,
+    pub hide_reposts: bool,
+    /// Only notes written or reposted by people we follow are shown.
+    pub follows_only: bool,
+    /// Notes with lower proof of work (NIP-13) are hidden.
+    pub min_pow: u8,
+    /// Notes in languages other than those chosen in settings are shown.
+    pub all_languages: bool,
+}
+
+impl LaneFilter {
+    /// Whether nothing is hidden.
+    pub fn is_default(&self) -> bool {
+        *self == LaneFilter::default()
+    }
+
+    /// Whether note of `event`, possibly reposted by `repost`, is shown.
+    /// Authors for whom `followed` holds count as followed.
+    pub fn accepts<F>(&self, event: &Event, repost: Option<&Repost>, followed: F) -> bool
+    where
+        F: Fn(&XOnlyPublicKey) -> bool,
+    {
+        let followed = followed(&event.pubkey)
+            || repost
+                .map(|r| followed(&r.event.pubkey))
+                .unwrap_or_default();
+
+        !(self.hide_replies && event.is_reply())
+            && !(self.hide_reposts && repost.is_some())
+            && (!self.follows_only || followed)
+            && event.pow() >= self.min_pow
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use nostr_sdk::nostr::secp256k1::XOnlyPublicKey;
+use nostr_sdk::nostr::{Event, EventId};
+use relm4::factory::DynamicIndex;
+
+use crate::nostr::EventExt;
+
+/// Displayed notes by something they show, such as their author, so that
+/// news about it are sent only to them. Notes are told apart by their
+/// event IDs, which also tell what to forget when a note goes away.
+#[derive(Debug)]
+pub struct NoteIndex<K, V = DynamicIndex> {
+    notes: HashMap<K, Vec<(EventId, V)>>,
+    /// Keys shown by every note.
+    keys: HashMap<EventId, Vec<K>>,
+}
+
+impl<K, V> Default for NoteIndex<K, V> {
+    fn default() -> Self {
+        NoteIndex {
+            notes: HashMap::new(),
+            keys: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> NoteIndex<K, V> {
+    /// Remembers that note `note` of event `id` shows `key`.
+    pub fn insert(&mut self, key: K, id: EventId, note: &V) {
+        let notes = self.notes.entry(key.clone()).or_default();
+        if !notes.iter().any(|(i, _)| *i == id) {
+            notes.push((id, note.clone()));
+            self.keys.entry(id).or_default().push(key);
+        }
+    }
+
+    /// Notes showing `key`.
+    pub fn get(&self, key: &K) -> impl Iterator<Item = &V> {
+        self.entries(key).iter().map(|(_, note)| note)
+    }
+
+    /// Notes showing `key`, with their event IDs.
+    pub fn entries(&self, key: &K) -> &[(EventId, V)] {
+        self.notes
+            .get(key)
+            .map(|n| n.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Forgets note of event `id`, which is not displayed anymore. Only
+    /// keys it shows are looked at.
+    pub fn remove(&mut self, id: &EventId) {
+        for key in self.keys.remove(id).unwrap_or_default() {
+            if let Some(notes) = self.notes.get_mut(&key) {
+                notes.retain(|(i, _)| i != id);
+                if notes.is_empty() {
+                    self.notes.remove(&key);
+                }
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.notes.clear();
+        self.keys.clear();
+    }
+}
+
+/// Authors whose names and avatars note of `event` displays: the author,
+/// or delegator, of the note and who has reposted it as `repost`.
+pub fn shown_authors(event: &Event, repost: Option<&Event>) -> Vec<XOnlyPublicKey> {
+    let mut authors = vec![event.author()];
+    if let Some(repost) = repost {
+        if repost.pubkey != event.author() {
+            authors.push(repost.pubkey);
+        }
+    }
+    authors
+}
+
+#[cfg(test)]
+mod tests {
+    use nostr_sdk::nostr::{Kind, Tag, Timestamp};
+
+    use super::*;
+    use crate::testing::Fixtures;
+
+    fn shown<K: Eq + Hash + Clone>(index: &NoteIndex<K, usize>, key: &K) -> Vec<usize> {
+        index.get(key).copied().collect()
+    }
+
+    #[test]
+    fn profile_reaches_only_notes_showing_author() {
+        let (alice, bob, carol) = (Fixtures::new(), Fixtures::new(), Fixtures::new());
+        let by_alice = alice.text_note("Hello");
+        let by_bob = bob.text_note("Hi");
+        let reposted = bob.text_note("Reposted");
+        let repost = carol.sign(
+            Kind::Repost,
+            "",
+            &[Tag::Event(reposted.id, None, None)],
+            Timestamp::now(),
+        );
+
+        // Notes 0 and 1 are displayed as they are, 2 as reposted by Carol.
+        let mut index: NoteIndex<XOnlyPublicKey, usize> = NoteIndex::default();
+        for (i, (event, repost)) in [
+            (&by_alice, None),
+            (&by_bob, None),
+            (&reposted, Some(&repost)),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            for author in shown_authors(event, repost) {
+                index.insert(author, event.id, &i);
+            }
+        }
+
+        assert_eq!(shown(&index, &alice.public_key()), [0]);
+        assert_eq!(shown(&index, &bob.public_key()), [1, 2]);
+        assert_eq!(shown(&index, &carol.public_key()), [2]);
+        assert!(shown(&index, &Fixtures::new().public_key()).is_empty());
+    }
+
+    #[test]
+    fn event_reaches_only_its_note() {
+        let alice = Fixtures::new();
+        let (first, second) = (alice.text_note("First"), alice.text_note("Second"));
+        let quoting = alice.text_note("Look at this");
+        let reaction = Fixtures::new().reaction(&first, "+");
+
+        // Notes 0 and 1 show themselves, 2 quotes the first one.
+        let mut index: NoteIndex<EventId, usize> = NoteIndex::default();
+        index.insert(first.id, first.id, &0);
+        index.insert(second.id, second.id, &1);
+        index.insert(first.id, quoting.id, &2);
+
+        let target = reaction.reacts_to().unwrap();
+        assert_eq!(shown(&index, &target), [0, 2]);
+        assert_eq!(shown(&index, &second.id), [1]);
+        assert!(shown(&index, &reaction.id).is_empty());
+
+        // The quoting note is gone, the reaction reaches the first one only.
+        index.remove(&quoting.id);
+        assert_eq!(shown(&index, &target), [0]);
+        index.remove(&first.id);
+        assert!(shown(&index, &target).is_empty());
+        assert_eq!(shown(&index, &second.id), [1]);
+    }
+
+    #[test]
+    fn removed_notes_leave_no_keys_behind() {
+        let (first, second) = (
+            Fixtures::new().text_note("1"),
+            Fixtures::new().text_note("2"),
+        );
+        let mut index: NoteIndex<&str, usize> = NoteIndex::default();
+        index.insert("alice", first.id, &0);
+        index.insert("alice", first.id, &0);
+        index.insert("alice", second.id, &1);
+        index.insert("bob", second.id, &1);
+
+        assert_eq!(shown(&index, &"alice"), [0, 1]);
+        assert_eq!(index.entries(&"bob"), [(second.id, 1)]);
+
+        index.remove(&second.id);
+        assert_eq!(shown(&index, &"alice"), [0]);
+        assert!(shown(&index, &"bob").is_empty());
+        assert_eq!(index.notes.len(), 1);
+        assert_eq!(index.keys.len(), 1);
+
+        // Notes never inserted change nothing.
+        index.remove(&second.id);
+        assert_eq!(shown(&index, &"alice"), [0]);
+
+        index.remove(&first.id);
+        assert!(index.notes.is_empty());
+        assert!(index.keys.is_empty());
+    }
+}
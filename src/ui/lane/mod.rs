@@ -1,4 +1,8 @@
+mod filter;
+mod index;
 pub mod model;
 pub mod view;
 
+pub use filter::LaneFilter;
+pub(crate) use index::{shown_authors, NoteIndex};
 pub use model::*;
@@ -1,28 +1,212 @@
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use gtk::gdk;
+use gtk::prelude::*;
 use nostr_sdk::nostr::secp256k1::XOnlyPublicKey;
-use nostr_sdk::nostr::{Event, EventId};
+use nostr_sdk::nostr::{Event, EventId, Kind, Timestamp};
 use relm4::factory::FactoryVecDeque;
 use relm4::prelude::*;
+use relm4::AsyncFactorySender;
 use reqwest::Url;
 
+use crate::content::{Collapse, Target};
 use crate::follow::Follow;
-use crate::nostr::{EventExt, Persona, Repost};
+use crate::handlers::Handler;
+use crate::nostr::{
+    is_unsupported_content, short_npub, Article, Emoji, EventExt, Persona, Quote, ReportSubject,
+    Repost,
+};
+use crate::notifications::Notification;
+use crate::preview::LinkPreview;
+use crate::reactions::ReactionCounts;
+use crate::stream_metrics::StreamMetrics;
+use crate::subscriptions::SubscriptionInfo;
+use crate::timestamps::{format_full, format_minute, TimeFormat};
+use crate::ui::avatars::placeholder;
 use crate::ui::details::Details;
-use crate::ui::lane_header::LaneHeader;
-use crate::ui::note::{Note, NoteInit};
+use crate::ui::lane::{shown_authors, LaneFilter, NoteIndex};
+use crate::ui::lane_header::{LaneHeader, LaneHeaderInput};
+use crate::ui::note::{highlighting, Note, NoteInit, NoteInput};
 use crate::ui::profilebox::model::Profilebox;
 
 #[derive(Debug)]
 pub struct Lane {
     pub(super) kind: LaneKind,
+    /// Our public key.
+    pub(super) me: XOnlyPublicKey,
     pub(super) text_notes: FactoryVecDeque<Note>,
     pub(super) hash_index: HashMap<EventId, DynamicIndex>,
+    /// Notes written or reposted by, or containing replies from, the given author.
+    pub(super) author_index: NoteIndex<XOnlyPublicKey>,
+    /// Notes mentioning the given user.
+    pub(super) mention_index: NoteIndex<XOnlyPublicKey>,
+    /// Notes displaying image from the given URL.
+    pub(super) image_index: NoteIndex<Url>,
+    /// Notes displaying custom emoji from the given URL.
+    pub(super) emoji_index: NoteIndex<Url>,
+    /// Notes displaying preview of web page at the given URL.
+    pub(super) preview_index: NoteIndex<Url>,
+    /// Notes quoting the given note.
+    pub(super) quote_index: NoteIndex<EventId>,
+    /// Displayed version of every article, by its author and identifier.
+    pub(super) article_index: HashMap<(XOnlyPublicKey, String), EventId>,
+    /// Best known applications displaying events of unsupported kinds.
+    pub(super) handlers: HashMap<u64, Handler>,
     pub(super) profile_box: Controller<Profilebox>,
     pub(super) header: Controller<LaneHeader>,
+    /// Rows of notifications lane.
+    pub(super) notifications: gtk::ListBox,
+    /// Displayed notifications and their rows.
+    pub(super) notification_rows: HashMap<EventId, (Notification, gtk::ListBoxRow)>,
+    /// Notifications user has not seen yet.
+    pub(super) unread: HashSet<EventId>,
+    /// Rows of firehose lane, the newest on top.
+    pub(super) raw_events: gtk::ListBox,
+    pub(super) raw_count: usize,
+    /// Numbers of received events by kinds, for firehose lane.
+    pub(super) kind_counts: String,
+    /// Open subscriptions, one per line with their filters below.
+    pub(super) subscriptions: String,
+    /// What is being searched for in search lane.
+    pub(super) search: String,
+    /// Identifies the last change of search text, so that only the latest
+    /// one is searched for once user stops typing.
+    pub(super) search_generation: u64,
+    /// Users found by search lane.
+    pub(super) people: gtk::ListBox,
+    /// Avatars and follow buttons of found users.
+    pub(super) people_rows: HashMap<XOnlyPublicKey, (gtk::Image, gtk::Button)>,
+    /// Notes in our bookmark list.
+    pub(super) bookmarks: HashSet<EventId>,
+    /// Notes pinned by the user of profile lane, in order of their pin
+    /// list. They are displayed above all other notes.
+    pub(super) pinned: Vec<EventId>,
+    /// How ages of notes are shown.
+    pub(super) time_format: TimeFormat,
+    /// How long notes can be before they are collapsed.
+    pub(super) collapse: Collapse,
+    /// Languages in which notes are shown, unless the filter shows all.
+    pub(super) languages: Vec<String>,
+    /// Whether notes can be translated.
+    pub(super) translatable: bool,
+    /// Whether keyboard shortcuts act on this lane.
+    pub(super) focused: bool,
+    /// Note selected by keyboard, on which shortcuts act.
+    pub(super) selected: Option<EventId>,
+    pub(super) search_entry: gtk::SearchEntry,
+    pub(super) notes_scroll: gtk::ScrolledWindow,
+    /// Note which the lane was scrolled to when Gnostique was closed,
+    /// it is kept on top once it arrives, until user scrolls elsewhere.
+    pub(super) anchor: Option<EventId>,
+    /// Identifies the last scrolling, so that the topmost note is
+    /// reported only once user stops scrolling.
+    pub(super) scroll_generation: u64,
+    /// Which notes are hidden.
+    pub(super) filter: LaneFilter,
+    /// Authors we follow, for filtering.
+    pub(super) follow: Follow,
+    /// Every text note this lane has accepted, whether displayed or hidden
+    /// by the filter, so that it can be displayed when filter changes.
+    pub(super) received: HashMap<EventId, ReceivedNote>,
+    /// Labels we have given to authors of received notes, those without
+    /// any are left out.
+    pub(super) labels: HashMap<XOnlyPublicKey, Vec<String>>,
+    /// Received text notes hidden by the filter, or collapsed.
+    pub(super) hidden: HashSet<EventId>,
+    /// Collapsed text notes of flooding authors, by the authors.
+    pub(super) folded: HashMap<XOnlyPublicKey, HashSet<EventId>>,
+    /// Flooding authors whose notes user wants to see anyway.
+    pub(super) unfolded: HashSet<XOnlyPublicKey>,
+    /// Rows of flooding authors whose notes are collapsed.
+    pub(super) folded_rows: gtk::Box,
+    /// Buttons of the rows, which tell how many notes are collapsed.
+    pub(super) folded_buttons: HashMap<XOnlyPublicKey, gtk::Button>,
+    /// Whether replies are displayed below notes they reply to,
+    /// rather than chronologically.
+    pub(super) threaded: bool,
+    /// Displayed replies by the notes they reply to, which do not
+    /// need to be displayed.
+    pub(super) children: HashMap<EventId, Vec<EventId>>,
+    /// Creation time of the newest note user has read, later notes are new.
+    pub(super) last_read: Option<Timestamp>,
+    /// Notes created later are displayed above the divider.
+    pub(super) divider: Option<Timestamp>,
+    /// Note which displays the divider above itself.
+    pub(super) divider_note: Option<EventId>,
+    /// Number of new notes, as displayed by the header.
+    pub(super) new_notes: usize,
+    /// Number of notes that have arrived above the visible ones
+    /// since user scrolled down.
+    pub(super) arrived: usize,
+}
+
+/// Text note as it has been received by lane.
+#[derive(Clone, Debug)]
+pub(super) struct ReceivedNote {
+    pub(super) event: Arc<Event>,
+    pub(super) relays: Vec<Url>,
+    pub(super) author: Option<Persona>,
+    pub(super) repost: Option<Repost>,
+    pub(super) mentions: Vec<Persona>,
+    pub(super) quotes: Vec<Quote>,
+    /// Whether the note comes from a flooding author.
+    pub(super) flooding: bool,
+    /// Reactions to the note and its zaps, kept up to date while
+    /// the note is waiting to be displayed.
+    pub(super) reactions: ReactionCounts,
+    /// Language of the note, if it could be told.
+    pub(super) language: Option<String>,
+    /// Whether it is our note which no relay has accepted yet.
+    pub(super) pending: bool,
+}
+
+impl ReceivedNote {
+    /// Whose flooding the note is part of: who reposted it, or who wrote it.
+    fn flooder(&self) -> XOnlyPublicKey {
+        self.repost
+            .as_ref()
+            .map(|r| r.event.pubkey)
+            .unwrap_or(self.event.pubkey)
+    }
+
+    fn flooder_name(&self) -> String {
+        let persona = match &self.repost {
+            Some(r) => r.author.clone(),
+            None => self.author.clone(),
+        };
+        persona
+            .unwrap_or_else(|| Persona::new(self.flooder()))
+            .shown_name()
+    }
+}
+
+/// What a new lane is created with.
+#[derive(Clone, Debug)]
+pub struct LaneInit {
+    pub kind: LaneKind,
+    /// Our public key, our own content is treated differently.
+    pub me: XOnlyPublicKey,
+    /// Notes in our bookmark list.
+    pub bookmarks: HashSet<EventId>,
+    pub time_format: TimeFormat,
+    pub collapse: Collapse,
+    /// Languages in which notes are shown.
+    pub languages: Vec<String>,
+    /// Whether notes can be translated.
+    pub translatable: bool,
+    /// Whether keyboard shortcuts act on the new lane.
+    pub focused: bool,
+    /// Note to scroll to once it arrives.
+    pub anchor: Option<EventId>,
+    pub filter: LaneFilter,
+    /// Authors we follow.
+    pub follow: Follow,
+    /// Whether replies are displayed below notes they reply to.
+    pub threaded: bool,
+    /// Creation time of the newest note read in the lane when it was last seen.
+    pub last_read: Option<Timestamp>,
 }
 
 #[derive(Clone, Debug)]
@@ -30,9 +214,65 @@ pub enum LaneKind {
     Profile(XOnlyPublicKey),
     Thread(EventId),
     Feed(Follow),
+    /// Everything that concerns us, see [`Notification`].
+    Notifications,
+    /// Stored text notes matching text typed by user.
+    Search,
+    /// Notes in our bookmark list (NIP-51).
+    Bookmarks,
+    /// Raw events as they arrive, for debugging.
+    Firehose,
+    /// Notes of followed authors created within the period, for catching
+    /// up on it. Notes arriving after it are not displayed.
+    Period(Follow, Period),
+}
+
+/// Time between two moments, both included.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Period {
+    pub since: Timestamp,
+    pub until: Timestamp,
+}
+
+impl Period {
+    /// The last day until now.
+    pub fn last_day() -> Period {
+        let now = Timestamp::now();
+        Period {
+            since: Timestamp::from(now.as_u64().saturating_sub(24 * 60 * 60)),
+            until: now,
+        }
+    }
+
+    pub fn contains(&self, time: Timestamp) -> bool {
+        self.since.as_i64() <= time.as_i64() && time.as_i64() <= self.until.as_i64()
+    }
+
+    /// Part of the period before `time`, `None` if there is none.
+    pub fn before(&self, time: Timestamp) -> Option<Period> {
+        let until = Timestamp::from(time.as_u64().checked_sub(1)?);
+        (until.as_i64() >= self.since.as_i64()).then_some(Period {
+            since: self.since,
+            until,
+        })
+    }
 }
 
 impl LaneKind {
+    /// Identifies the lane between runs of Gnostique.
+    pub fn key(&self) -> String {
+        match self {
+            LaneKind::Profile(pubkey) => format!("profile:{pubkey}"),
+            LaneKind::Thread(id) => format!("thread:{}", id.to_hex()),
+            LaneKind::Feed(_) => "feed".to_string(),
+            LaneKind::Notifications => "notifications".to_string(),
+            LaneKind::Search => "search".to_string(),
+            LaneKind::Bookmarks => "bookmarks".to_string(),
+            LaneKind::Firehose => "firehose".to_string(),
+            LaneKind::Period(..) => "period".to_string(),
+        }
+    }
+
     pub fn is_thread(&self, event_id: &EventId) -> bool {
         matches!(self, LaneKind::Thread(e) if e == event_id)
     }
@@ -45,15 +285,61 @@ impl LaneKind {
         matches!(self, LaneKind::Profile(_))
     }
 
+    pub fn is_notifications(&self) -> bool {
+        matches!(self, LaneKind::Notifications)
+    }
+
+    pub fn is_search(&self) -> bool {
+        matches!(self, LaneKind::Search)
+    }
+
+    pub fn is_bookmarks(&self) -> bool {
+        matches!(self, LaneKind::Bookmarks)
+    }
+
+    pub fn is_firehose(&self) -> bool {
+        matches!(self, LaneKind::Firehose)
+    }
+
+    /// Period displayed by period lane.
+    pub fn period(&self) -> Option<Period> {
+        match self {
+            LaneKind::Period(_, period) => Some(*period),
+            _ => None,
+        }
+    }
+
+    /// Whether new notes are pointed out. Lanes of such kinds display
+    /// newest notes on top, so that user reads them downwards.
+    pub fn shows_new_notes(&self) -> bool {
+        matches!(
+            self,
+            LaneKind::Feed(_) | LaneKind::Search | LaneKind::Bookmarks
+        )
+    }
+
     pub fn accepts(&self, event: &Event) -> bool {
         match self {
-            LaneKind::Feed(f) => f.follows(&event.pubkey) && event.replies_to().is_none(),
-            LaneKind::Profile(pubkey) => &event.pubkey == pubkey,
+            LaneKind::Feed(f) => f.follows(&event.author()) && event.replies_to().is_none(),
+            LaneKind::Period(f, period) => {
+                f.follows(&event.author())
+                    && event.replies_to().is_none()
+                    && period.contains(event.created_at)
+            }
+            LaneKind::Profile(pubkey) => event.author() == *pubkey,
             LaneKind::Thread(id) => {
                 event.id == *id
                     || event.replies_to() == Some(*id)
                     || matches!(event.thread_root(), Some((i, _)) if i == *id)
             }
+            // Notifications are displayed as rows, not as text notes.
+            LaneKind::Notifications => false,
+            // Search results arrive separately.
+            LaneKind::Search => false,
+            // The lane itself knows which notes are bookmarked.
+            LaneKind::Bookmarks => false,
+            // Raw events are displayed as rows.
+            LaneKind::Firehose => false,
         }
     }
 }
@@ -65,10 +351,45 @@ pub enum LaneMsg {
         relays: Vec<Url>,
         author: Option<Persona>,
         repost: Option<Repost>,
+        mentions: Vec<Persona>,
+        quotes: Vec<Quote>,
+        /// Whether the note should be collapsed with other notes
+        /// of the flooding author.
+        flooding: bool,
+        /// Reactions to the note and its zaps stored so far.
+        reactions: ReactionCounts,
+        /// Language of the note, if it could be told.
+        language: Option<String>,
+        /// Labels we have given to the author.
+        labels: Vec<String>,
+        /// Whether it is our note which no relay has accepted yet.
+        pending: bool,
     },
     UpdatedProfile {
         author: Persona,
     },
+    /// Time has passed, or the way it is shown has changed.
+    Tick(TimeFormat),
+    /// Notes are collapsed if longer than this now.
+    Collapse(Collapse),
+    /// Notes are shown in these languages now.
+    Languages(Vec<String>),
+    /// Notes can be translated now, or they cannot anymore.
+    Translatable(bool),
+    /// User wants to read the note with this content translated.
+    Translate(EventId, String),
+    /// Translation of the note has arrived, or why it has not.
+    Translated {
+        event_id: EventId,
+        translation: Result<String, String>,
+    },
+    /// Stored note replied to by displayed notes has been found.
+    Parent(Quote),
+    /// These are all labels of the author now.
+    Labels {
+        author: XOnlyPublicKey,
+        labels: Vec<String>,
+    },
     ShowDetails(Details),
     MetadataBitmap {
         pubkey: XOnlyPublicKey,
@@ -78,53 +399,740 @@ pub enum LaneMsg {
     Reaction {
         event: EventId,
         reaction: String,
+        /// Custom emoji the reaction consists of (NIP-30).
+        emoji: Option<Emoji>,
+    },
+    /// Event has been zapped with `sats`.
+    Zap {
+        event: EventId,
+        sats: u64,
     },
     Nip05Verified(XOnlyPublicKey),
-    LinkClicked(Url),
+    /// Text note has been seen on another relay.
+    SeenOn {
+        event: EventId,
+        relay: Url,
+    },
+    /// User wants to see the profile, thread or hashtag.
+    Open(Target),
+    /// Image embedded in notes has been downloaded and decoded.
+    ImageBitmap {
+        url: Url,
+        bitmap: Arc<gdk::Texture>,
+    },
+    /// Note wants to display images from these URLs.
+    NeedImages(Vec<Url>),
+    /// Custom emoji has been downloaded and decoded.
+    EmojiBitmap {
+        url: Url,
+        bitmap: Arc<gdk::Texture>,
+    },
+    /// Image from this URL should be shown in full size.
+    OpenImage(Url),
+    /// Preview of web page linked from notes has been obtained.
+    LinkPreview(Arc<LinkPreview>),
+    /// Note wants to display preview of web page at this URL.
+    NeedPreview(Url),
+    /// User wants to reply to the event.
+    Reply(Arc<Event>),
+    /// User wants to quote the event, which has been seen at the relays.
+    Quote(Arc<Event>, Vec<Url>),
+    /// Long-form article, newer than any of its previous versions.
+    Article {
+        article: Arc<Article>,
+        relays: Vec<Url>,
+        author: Option<Persona>,
+    },
+    /// User wants to read the article written by the author.
+    OpenArticle(Box<Article>, Box<Persona>),
+    /// Event of a kind which Gnostique cannot display, with the best
+    /// known application that can.
+    Unsupported {
+        event: Arc<Event>,
+        relays: Vec<Url>,
+        author: Option<Persona>,
+        handler: Option<Handler>,
+    },
+    /// These applications are the best known ones for their kinds now.
+    Handlers(Vec<(u64, Handler)>),
+    /// Text note has expired according to NIP-40 and should disappear.
+    Expired(EventId),
+    /// Our event has been accepted by a relay for the first time.
+    Delivered(EventId),
+    /// Event as it has arrived from `relay`, for firehose lane.
+    Raw {
+        relay: Url,
+        event: Arc<Event>,
+    },
+    /// Numbers of received events by kinds, the most frequent first, and
+    /// of raw events that have not got to firehose lane, with how the
+    /// processing of events keeps up and which subscriptions are open.
+    KindStats {
+        counts: Vec<(u64, u64)>,
+        skipped: u64,
+        stream: StreamMetrics,
+        subscriptions: Vec<SubscriptionInfo>,
+    },
+    /// Something new that concerns us has happened.
+    Notification(Notification),
+    /// Notifications stored from previous sessions, from the newest.
+    NotificationHistory(Vec<Notification>),
+    /// User wants to see what the notification is about.
+    NotificationClicked(EventId),
+    /// User has seen all notifications.
+    MarkAllRead,
+    /// User wants to write a new text note.
+    WriteNote,
+    /// User has changed text in search box.
+    SearchChanged(String),
+    /// User has stopped typing search text of the given generation.
+    SearchDebounced(u64, String),
+    /// Search for the text, as if user has typed it.
+    Search(String),
+    /// Text notes matching `query`.
+    SearchResults {
+        query: String,
+        notes: Vec<FoundNote>,
+    },
+    /// Users matching `query`.
+    PeopleResults {
+        query: String,
+        people: Vec<FoundPerson>,
+    },
+    /// User wants to follow the author.
+    Follow(XOnlyPublicKey),
+    /// The author is followed now.
+    Followed(XOnlyPublicKey),
+    /// Numbers of users the author follows, if their contact list is
+    /// known, and of their followers known to this client.
+    ContactCounts {
+        pubkey: XOnlyPublicKey,
+        following: Option<usize>,
+        followers: usize,
+    },
+    /// Fields of the author's profile which have changed recently.
+    ProfileChanges {
+        pubkey: XOnlyPublicKey,
+        fields: Vec<&'static str>,
+    },
+    /// Notes of the author should not be displayed anymore.
+    Muted(XOnlyPublicKey),
+    /// Our note has been deleted.
+    Deleted(EventId),
+    /// Reported note, or notes of reported user, should not be displayed anymore.
+    Reported(ReportSubject),
+    /// User wants to add the note to bookmarks, or remove it from there.
+    Bookmark(EventId, bool),
+    /// The note has been added to bookmarks, or removed from there.
+    Bookmarked(EventId, bool),
+    /// Stored bookmarked notes, for bookmarks lane.
+    BookmarkedNotes(Vec<FoundNote>),
+    /// Stored notes created within the period, for period lane.
+    PeriodNotes(Vec<FoundNote>),
+    /// User wants to see notes of another period.
+    SetPeriod(Period),
+    /// User wants to see notes older than the displayed ones.
+    EarlierNotes,
+    /// User wants to save notes of thread or profile lane into a file,
+    /// all notes of the thread if `whole`.
+    Export {
+        whole: bool,
+    },
+    /// These are all notes pinned by `author` now.
+    Pins {
+        author: XOnlyPublicKey,
+        pinned: Vec<EventId>,
+    },
+    /// Stored pinned notes have been loaded for profile lanes.
+    PinnedNotes(Vec<FoundNote>),
+    /// Keyboard shortcuts start or stop acting on this lane.
+    Focus(bool),
+    /// Select note next to the selected one, below it if `forward`.
+    Select {
+        forward: bool,
+    },
+    /// User wants to reply to the selected note.
+    ReplySelected,
+    /// User has scrolled the lane.
+    ScrollChanged,
+    /// User has stopped scrolling the lane after scrolling of the given generation.
+    ScrollDebounced(u64),
+    /// User wants to see the newest notes.
+    ScrollToTop,
+    /// User has changed which notes are hidden.
+    Filter(LaneFilter),
+    /// User wants replies displayed below notes they reply to,
+    /// or chronologically.
+    Threaded(bool),
+    /// User wants to see collapsed notes of the flooding author.
+    Unfold(XOnlyPublicKey),
+}
+
+/// User found by search.
+#[derive(Clone, Debug)]
+pub struct FoundPerson {
+    pub persona: Persona,
+    /// Whether we already follow the user.
+    pub followed: bool,
+}
+
+/// Text note found by search.
+#[derive(Clone, Debug)]
+pub struct FoundNote {
+    pub event: Arc<Event>,
+    pub relays: Vec<Url>,
+    pub author: Option<Persona>,
+    pub reactions: ReactionCounts,
 }
 
 #[derive(Debug)]
 pub enum LaneOutput {
     ShowDetails(Details),
     WriteNote,
+    /// Images from these URLs should be downloaded.
+    NeedImages(Vec<Url>),
+    /// Custom emoji from these URLs should be obtained.
+    NeedEmojis(Vec<Url>),
+    /// Image from this URL should be shown in full size.
+    OpenImage(Url),
+    /// Preview of web page at this URL should be obtained.
+    NeedPreview(Url),
+    /// User wants to see the profile, thread or hashtag.
+    Open(Target),
+    /// User wants to reply to the event.
+    Reply(Arc<Event>),
+    /// User wants to quote the event, which has been seen at the relays.
+    Quote(Arc<Event>, Vec<Url>),
+    /// User wants to read the article written by the author.
+    OpenArticle(Box<Article>, Box<Persona>),
+    /// User has seen notification about the event.
+    NotificationRead(EventId),
+    /// User has seen all notifications.
+    AllNotificationsRead,
+    /// Stored text notes and users matching the text should be found.
+    Search(String),
+    /// User wants to follow the author.
+    Follow(XOnlyPublicKey),
+    /// User wants to add the note to bookmarks, or remove it from there.
+    Bookmark(EventId, bool),
+    /// User wants to read the note with this content translated.
+    Translate(EventId, String),
+    /// Note replied to by a displayed note is not here, it should be
+    /// found, also at these hinted relays.
+    NeedParent(EventId, Vec<Url>),
+    /// User has clicked into the lane, keyboard shortcuts should act on it.
+    Focused(DynamicIndex),
+    /// Lane of the key is now scrolled to the note, or to the top if none.
+    Scrolled {
+        key: String,
+        anchor: Option<EventId>,
+    },
+    /// Lane of the key hides notes according to the filter now.
+    Filter {
+        key: String,
+        filter: LaneFilter,
+    },
+    /// Lane of the key displays replies below notes they reply to, or not.
+    Threaded {
+        key: String,
+        threaded: bool,
+    },
+    /// User has read notes of lane of the key created until the time.
+    Read {
+        key: String,
+        until: Timestamp,
+    },
+    /// Period lane wants notes created within the period, the newest ones
+    /// if there are too many.
+    LoadPeriod(Follow, Period),
+    /// User wants to save the notes with their authors into a file under
+    /// the title, together with the rest of the thread if it is given.
+    Export {
+        title: String,
+        notes: Vec<(Arc<Event>, Option<Persona>)>,
+        thread: Option<EventId>,
+    },
+}
+
+/// Media that a newly added note wants to display right away.
+#[derive(Debug, Default)]
+pub(super) struct WantedMedia {
+    pub(super) images: Vec<Url>,
+    /// Custom emoji written in content.
+    pub(super) emojis: Vec<Url>,
+    pub(super) preview: Option<Url>,
+    /// Note the note replies to, which is not here, and relays hinted for it.
+    pub(super) parent: Option<(EventId, Vec<Url>)>,
 }
 
 impl Lane {
-    /// New text note was received, let's handle it.
-    pub(super) fn text_note_received(
-        &mut self,
-        event: Arc<Event>,
-        relays: Vec<Url>,
-        author: Option<Persona>,
-        repost: Option<Repost>,
-    ) {
+    /// Position of note `event_id` in the pin list, if it is pinned.
+    fn pin_position(&self, event_id: &EventId) -> Option<usize> {
+        self.pinned.iter().position(|id| id == event_id)
+    }
+
+    /// Takes `pinned` notes of the user of profile lane. Notes whose pin
+    /// has changed move to where they belong now, notes of other authors
+    /// which are not pinned anymore disappear. Returns media wanted by
+    /// the moved notes.
+    pub(super) fn pins_changed(&mut self, pinned: Vec<EventId>) -> Vec<WantedMedia> {
+        let changed: Vec<EventId> = self
+            .pinned
+            .iter()
+            .filter(|id| !pinned.contains(id))
+            .chain(pinned.iter().filter(|id| !self.pinned.contains(id)))
+            .copied()
+            .collect();
+        self.pinned = pinned;
+
+        let mut wanted = Vec::new();
+        for id in changed {
+            let note = match self.received.get(&id).cloned() {
+                Some(note) => note,
+                None => continue,
+            };
+            if self.pin_position(&id).is_none() && !self.kind.accepts(&note.event) {
+                self.remove_note(&id);
+            } else {
+                self.remove_displayed(&id);
+                wanted.push(self.note_received(note));
+            }
+        }
+        wanted
+    }
+
+    /// Text note accepted by this lane was received, it is displayed
+    /// unless the filter hides it. Returns media that the note,
+    /// if newly displayed, wants to display right away.
+    pub(super) fn note_received(&mut self, mut note: ReceivedNote) -> WantedMedia {
+        let id = note.event.id;
+        // Once collapsed, the note stays so when it comes again.
+        note.flooding |= self
+            .received
+            .get(&id)
+            .map(|n| n.flooding)
+            .unwrap_or_default();
+        let shown = self.shows(&note);
+        let folded = self.folds(&note);
+        self.received.insert(id, note.clone());
+
+        self.unfold(&id);
+        if folded {
+            self.folded.entry(note.flooder()).or_default().insert(id);
+        }
+
+        if shown {
+            self.hidden.remove(&id);
+            self.text_note_received(note)
+        } else {
+            self.hidden.insert(id);
+            WantedMedia::default()
+        }
+    }
+
+    /// Whether the filter lets `note` be displayed and it is not collapsed.
+    /// Central note of thread always is.
+    fn shows(&self, note: &ReceivedNote) -> bool {
+        self.kind.is_thread(&note.event.id)
+            || (!self.folds(note)
+                && self
+                    .filter
+                    .accepts(&note.event, note.repost.as_ref(), |pubkey| {
+                        *pubkey == self.me || self.follow.follows(pubkey)
+                    })
+                && (self.filter.all_languages
+                    || crate::language::accepts(&self.languages, note.language.as_deref())))
+    }
+
+    /// Whether `note` is collapsed with other notes of its flooding author.
+    fn folds(&self, note: &ReceivedNote) -> bool {
+        note.flooding
+            && !self.kind.is_thread(&note.event.id)
+            && !self.unfolded.contains(&note.flooder())
+    }
+
+    /// Forgets that note `id` is collapsed.
+    fn unfold(&mut self, id: &EventId) {
+        self.folded.retain(|_, ids| {
+            ids.remove(id);
+            !ids.is_empty()
+        });
+    }
+
+    /// Bounds of the period of period lane.
+    pub(super) fn format_period(&self) -> String {
+        match self.kind.period() {
+            Some(period) => format!(
+                "From {} to {}",
+                format_minute(period.since, self.time_format),
+                format_minute(period.until, self.time_format)
+            ),
+            None => String::new(),
+        }
+    }
+
+    /// Number of hidden notes which are hidden by the filter, not collapsed.
+    pub(super) fn filtered(&self) -> usize {
+        self.hidden.len() - self.folded.values().map(|ids| ids.len()).sum::<usize>()
+    }
+
+    /// Shows a row for every flooding author whose notes are collapsed,
+    /// telling how many of them there are.
+    pub(super) fn show_folded(&mut self, sender: &AsyncFactorySender<Self>) {
+        let gone: Vec<XOnlyPublicKey> = self
+            .folded_buttons
+            .keys()
+            .filter(|pubkey| !self.folded.contains_key(pubkey))
+            .copied()
+            .collect();
+        for pubkey in gone {
+            if let Some(button) = self.folded_buttons.remove(&pubkey) {
+                self.folded_rows.remove(&button);
+            }
+        }
+
+        for (pubkey, ids) in &self.folded {
+            let name = ids
+                .iter()
+                .find_map(|id| self.received.get(id))
+                .map(|n| n.flooder_name())
+                .unwrap_or_default();
+            let label = match ids.len() {
+                1 => format!("1 more note from {name}"),
+                n => format!("{n} more notes from {name}"),
+            };
+
+            let button = self.folded_buttons.entry(*pubkey).or_insert_with(|| {
+                let button = gtk::Button::new();
+                button.add_css_class("flat");
+                button.add_css_class("folded");
+                button.set_tooltip_text(Some("Posting too many notes, click to show them"));
+                let pubkey = *pubkey;
+                let sender = sender.clone();
+                button.connect_clicked(move |_| sender.input(LaneMsg::Unfold(pubkey)));
+                self.folded_rows.append(&button);
+                button
+            });
+            if button.label().as_deref() != Some(label.as_str()) {
+                button.set_label(&label);
+            }
+        }
+
+        self.folded_rows
+            .set_visible(!self.folded_buttons.is_empty());
+    }
+
+    /// Hides displayed notes which the filter does not let through anymore
+    /// and displays hidden ones which it does now. Returns media wanted
+    /// by the newly displayed notes.
+    pub(super) fn apply_filter(&mut self) -> Vec<WantedMedia> {
+        let mut wanted = Vec::new();
+
+        let changed: Vec<(EventId, bool)> = self
+            .received
+            .values()
+            .map(|note| (note.event.id, self.shows(note)))
+            .filter(|(id, shown)| *shown == self.hidden.contains(id))
+            .collect();
+
+        for (id, shown) in changed {
+            if shown {
+                if let Some(note) = self.received.get(&id).cloned() {
+                    wanted.push(self.note_received(note));
+                }
+            } else {
+                self.remove_displayed(&id);
+                self.hidden.insert(id);
+            }
+        }
+
+        wanted
+    }
+
+    /// Displays all received notes again, for example to arrange
+    /// them into threads. Returns media wanted by the notes.
+    pub(super) fn relayout(&mut self) -> Vec<WantedMedia> {
+        let mut notes: Vec<ReceivedNote> = self.received.values().cloned().collect();
+        // Parents first, so that replies have where to go.
+        notes.sort_by_key(|n| n.event.created_at);
+
+        self.clear_displayed();
+        self.hidden.clear();
+        self.folded.clear();
+        notes
+            .into_iter()
+            .map(|note| self.note_received(note))
+            .collect()
+    }
+
+    /// Where reply created at `time` goes among replies to note at index
+    /// `parent`: below it, after subtrees of older replies.
+    fn reply_position(&self, parent: usize, time: i64) -> usize {
+        let depth = self.depth_at(parent);
+
+        let mut position = parent + 1;
+        for note in self.text_notes.iter().skip(parent + 1) {
+            // End of subtree of the parent, or a newer reply.
+            if note.depth <= depth || (note.depth == depth + 1 && note.time.timestamp() > time) {
+                break;
+            }
+            position += 1;
+        }
+        position
+    }
+
+    /// Number of notes in subtree of note at `index`, including itself.
+    fn subtree_len(&self, index: usize) -> usize {
+        let depth = self.depth_at(index);
+
+        1 + self
+            .text_notes
+            .iter()
+            .skip(index + 1)
+            .take_while(|n| n.depth > depth)
+            .count()
+    }
+
+    fn depth_at(&self, index: usize) -> usize {
+        self.text_notes
+            .get(index)
+            .map(|n| n.depth)
+            .unwrap_or_default()
+    }
+
+    /// Remembers `labels` of `author`, displayed notes of the author are
+    /// hidden behind a bar if there are any.
+    pub(super) fn labels_changed(&mut self, author: XOnlyPublicKey, labels: Vec<String>) {
+        let known = self
+            .labels
+            .get(&author)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        if known == labels.as_slice() {
+            return;
+        }
+
+        if labels.is_empty() {
+            self.labels.remove(&author);
+        } else {
+            self.labels.insert(author, labels.clone());
+        }
+        if self.kind.is_profile(&author) {
+            self.profile_box
+                .emit(crate::ui::profilebox::Input::Labels(labels.clone()));
+        }
+        self.send_to_author(&author, NoteInput::Labels { author, labels });
+    }
+
+    /// Displayed replies to `parent` tell what they reply to now.
+    pub(super) fn parent_received(&self, parent: Quote) {
+        for id in self.children.get(&parent.event.id).into_iter().flatten() {
+            if let Some(di) = self.hash_index.get(id) {
+                self.text_notes
+                    .send(di.current_index(), NoteInput::Parent(parent.clone()));
+            }
+        }
+    }
+
+    /// Moves displayed replies to note `parent`, which has just been
+    /// displayed, below it together with their own replies.
+    fn adopt_replies(&mut self, parent: EventId) {
+        let mut replies: Vec<(i64, EventId)> = self
+            .children
+            .get(&parent)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| {
+                let di = self.hash_index.get(id)?;
+                let note = self.text_notes.get(di.current_index())?;
+                Some((note.time.timestamp(), *id))
+            })
+            .collect();
+        replies.sort();
+
+        for (time, reply) in replies {
+            let (parent_index, reply_index) =
+                match (self.hash_index.get(&parent), self.hash_index.get(&reply)) {
+                    (Some(p), Some(r)) => (p.current_index(), r.current_index()),
+                    _ => continue,
+                };
+            let parent_depth = self.depth_at(parent_index);
+            let reply_depth = self.depth_at(reply_index);
+            if reply_depth == parent_depth + 1 && reply_index > parent_index {
+                // Already below the parent.
+                continue;
+            }
+
+            let len = self.subtree_len(reply_index);
+            let target = self.reply_position(parent_index, time);
+
+            let mut guard = self.text_notes.guard();
+            let start = if target <= reply_index {
+                for k in 0..len {
+                    guard.move_to(reply_index + k, target + k);
+                }
+                target
+            } else {
+                for _ in 0..len {
+                    guard.move_to(reply_index, target - 1);
+                }
+                target - len
+            };
+
+            for i in start..start + len {
+                if let Some(note) = guard.get_mut(i) {
+                    note.depth = note.depth - reply_depth + parent_depth + 1;
+                }
+            }
+        }
+    }
+
+    /// New text note was received, let's handle it. Returns media
+    /// that the note, if newly added, wants to display right away.
+    pub(super) fn text_note_received(&mut self, note: ReceivedNote) -> WantedMedia {
+        let ReceivedNote {
+            event,
+            relays,
+            author,
+            repost,
+            mentions,
+            quotes,
+            reactions,
+            pending,
+            ..
+        } = note;
         let event_id = event.id;
+        let author_pubkey = event.author();
+        let authors = shown_authors(&event, repost.as_ref().map(|r| &r.event));
+        let kind = event.kind.as_u64();
 
         // Add note iff it has not been added yet (they may arrive multiple times).
         if !self.hash_index.contains_key(&event.id) {
             let is_central = self.kind.is_thread(&event_id);
+            let (is_own, mentions_me) = highlighting(&event, &self.me);
+            let bookmarked = self.bookmarks.contains(&event_id);
+            let pinned = self.pin_position(&event_id);
             let event_time = event.created_at;
+            // Events of unsupported kinds display just a placeholder.
+            let unsupported = is_unsupported_content(event.kind);
+            let images = if unsupported {
+                Vec::new()
+            } else {
+                event.shown_images()
+            };
+            let preview = event.preview_url().filter(|_| !unsupported);
+            // Media of notes with content warning wait until revealed.
+            let labels = self.labels.get(&author_pubkey).cloned().unwrap_or_default();
+            let load_media = event.content_warning().is_none() && labels.is_empty();
+            let mentioned = event.mentions();
+            let mut quoted = crate::content::quoted(&event.content);
+            for id in event.quoted_events() {
+                if !quoted.iter().any(|(q, _)| *q == id) {
+                    quoted.push((id, vec![]));
+                }
+            }
+            let emojis: Vec<Url> = event
+                .emojis()
+                .into_iter()
+                .filter(|e| event.content.contains(&format!(":{}:", e.shortcode)))
+                .chain(reactions.emojis.iter().map(|(e, _)| e.clone()))
+                .map(|e| e.url)
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect();
+
+            let parent = event.replies_to();
+            // Replies go below their parent, if it is here.
+            let parent_index = parent
+                .filter(|_| self.threaded)
+                .and_then(|p| self.hash_index.get(&p))
+                .map(|di| di.current_index());
+            let depth = parent_index
+                .and_then(|i| self.text_notes.get(i))
+                .map(|n| n.depth + 1)
+                .unwrap_or_default();
+            // Replies not displayed below their parent tell what they reply to.
+            let parent_note = parent
+                .filter(|_| depth == 0 && kind == Kind::TextNote.as_u64())
+                .map(|p| match self.received.get(&p) {
+                    Some(received) => Ok(Quote {
+                        event: (*received.event).clone(),
+                        author: received.author.clone(),
+                    }),
+                    None => Err((p, event.tag_relay_hints().of_event(&p))),
+                });
 
             let init = NoteInit {
                 event,
                 relays,
                 author,
                 is_central,
+                is_own,
+                mentions_me,
+                labels,
+                bookmarked,
+                pinned: pinned.is_some(),
                 repost,
+                mentions,
+                quotes,
+                time_format: self.time_format,
+                depth,
+                collapse: self.collapse,
+                reactions,
+                translatable: self.translatable,
+                parent: parent_note.clone().and_then(Result::ok),
+                pending,
             };
 
+            // Notes arriving above those user is reading must not move them.
+            let visible_top = self.anchor.is_none().then(|| self.visible_top()).flatten();
+            let is_new = self
+                .last_read
+                .map(|t| event_time.as_i64() > t.as_i64())
+                .unwrap_or_default();
+            if is_new && self.new_notes == 0 {
+                // First of new notes, they go above what has been read.
+                self.divider = self.last_read;
+            }
+
             let di = if is_central {
                 // Central text note always goes first.
                 self.text_notes.guard().push_front(init)
+            } else if let Some(position) = pinned {
+                // Pinned notes go above others, in order of the pin list.
+                let idx = self
+                    .text_notes
+                    .iter()
+                    .position(|tn| {
+                        !tn.pinned
+                            || self
+                                .pin_position(&tn.event.id)
+                                .map(|p| p > position)
+                                .unwrap_or_default()
+                    })
+                    .unwrap_or(self.text_notes.len());
+                self.text_notes.guard().insert(idx, init)
+            } else if let Some(parent_index) = parent_index {
+                let idx = self.reply_position(parent_index, event_time.as_i64());
+                self.text_notes.guard().insert(idx, init)
             } else {
                 // Find index of first text note that was created later
-                // than the text note being inserted.
+                // than the text note being inserted. Replies displayed
+                // below their parents stay with them.
                 let idx = self.text_notes.iter().position(|tn| {
+                    if tn.depth > 0 || tn.pinned {
+                        return false;
+                    }
                     let ord = tn.time.timestamp().cmp(&event_time.as_i64());
                     match self.kind {
                         LaneKind::Profile(_) => ord == Ordering::Greater,
                         LaneKind::Thread(_) => ord == Ordering::Less,
-                        LaneKind::Feed(_) => ord == Ordering::Less,
+                        LaneKind::Feed(_)
+                        | LaneKind::Notifications
+                        | LaneKind::Search
+                        | LaneKind::Bookmarks
+                        | LaneKind::Firehose
+                        | LaneKind::Period(..) => ord == Ordering::Less,
                     }
                 });
 
@@ -137,8 +1145,537 @@ impl Lane {
                 }
             };
 
-            // At the end, let's remember (event_id -> dynamic index) pair.
+            // At the end, let's remember (event_id -> dynamic index) pair
+            // and which authors the note displays.
+            for pubkey in authors {
+                self.author_index.insert(pubkey, event_id, &di);
+            }
+            for pubkey in mentioned {
+                self.mention_index.insert(pubkey, event_id, &di);
+            }
+            for url in &images {
+                self.image_index.insert(url.clone(), event_id, &di);
+            }
+            for url in &emojis {
+                self.emoji_index.insert(url.clone(), event_id, &di);
+            }
+            for (id, _) in quoted {
+                self.quote_index.insert(id, event_id, &di);
+            }
+            if let Some(url) = &preview {
+                self.preview_index.insert(url.clone(), event_id, &di);
+            }
+            if let Some(handler) = self.handlers.get(&kind) {
+                self.text_notes
+                    .send(di.current_index(), NoteInput::Handler(handler.clone()));
+            }
             self.hash_index.insert(event_id, di);
+            if let Some(parent) = parent {
+                self.children.entry(parent).or_default().push(event_id);
+            }
+            if self.threaded {
+                self.adopt_replies(event_id);
+            }
+
+            // Notes inserted above would push the anchor down.
+            if self.anchor.is_some() {
+                self.scroll_to_anchor();
+            } else if let Some((top, offset)) = visible_top {
+                let above = self
+                    .hash_index
+                    .get(&top)
+                    .zip(self.hash_index.get(&event_id))
+                    .map(|(top, new)| new.current_index() < top.current_index())
+                    .unwrap_or_default();
+                if above {
+                    self.arrived += 1;
+                    self.scroll_to_note(top, offset);
+                }
+            }
+            self.update_new_notes();
+
+            // Emoji are part of the text, they are always displayed.
+            let parent = parent_note.and_then(Result::err);
+            if load_media {
+                WantedMedia {
+                    images,
+                    emojis,
+                    preview,
+                    parent,
+                }
+            } else {
+                WantedMedia {
+                    emojis,
+                    parent,
+                    ..Default::default()
+                }
+            }
+        } else {
+            WantedMedia::default()
+        }
+    }
+
+    /// Adds row displaying `notification`, either on top as the newest
+    /// one, or at the bottom when filling in history.
+    pub(super) fn add_notification(
+        &mut self,
+        notification: Notification,
+        on_top: bool,
+        sender: &AsyncFactorySender<Self>,
+    ) {
+        if self.notification_rows.contains_key(&notification.event_id) {
+            return;
+        }
+
+        let description = gtk::Label::new(Some(&notification.describe()));
+        description.set_xalign(0.0);
+        description.set_wrap(true);
+        description.set_wrap_mode(gtk::pango::WrapMode::WordChar);
+
+        let time = chrono::NaiveDateTime::from_timestamp_opt(notification.created_at.as_i64(), 0)
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        let time = gtk::Label::new(Some(&time));
+        time.set_xalign(0.0);
+        time.add_css_class("time");
+
+        let content = gtk::Box::new(gtk::Orientation::Vertical, 2);
+        content.append(&description);
+        content.append(&time);
+
+        let button = gtk::Button::new();
+        button.set_has_frame(false);
+        button.set_child(Some(&content));
+        button.connect_clicked({
+            let sender = sender.clone();
+            let id = notification.event_id;
+            move |_| sender.input(LaneMsg::NotificationClicked(id))
+        });
+
+        let row = gtk::ListBoxRow::new();
+        row.set_child(Some(&button));
+        if !notification.read {
+            row.add_css_class("unread");
+            self.unread.insert(notification.event_id);
+        }
+
+        if on_top {
+            self.notifications.prepend(&row);
+        } else {
+            self.notifications.append(&row);
+        }
+
+        self.notification_rows
+            .insert(notification.event_id, (notification, row));
+    }
+
+    /// Adds row of raw `event` from `relay` on top of firehose lane,
+    /// the oldest rows go away.
+    pub(super) fn add_raw(
+        &mut self,
+        relay: Url,
+        event: Arc<Event>,
+        sender: &AsyncFactorySender<Self>,
+    ) {
+        /// Firehose lane displays at most this many events.
+        const MAX_ROWS: usize = 500;
+
+        let text = format!(
+            "{}  {}  {}  {}",
+            event.kind.as_u64(),
+            short_npub(&event.pubkey),
+            relay,
+            format_full(event.created_at, self.time_format)
+        );
+
+        let label = gtk::Label::new(Some(&text));
+        label.set_xalign(0.0);
+        label.set_ellipsize(gtk::pango::EllipsizeMode::End);
+        label.add_css_class("monospace");
+
+        let button = gtk::Button::new();
+        button.set_has_frame(false);
+        button.set_child(Some(&label));
+        button.set_tooltip_text(Some("Show JSON of the event"));
+        button.connect_clicked({
+            let sender = sender.clone();
+            let time_format = self.time_format;
+            move |_| {
+                sender.output(LaneOutput::ShowDetails(Details {
+                    event_json: serde_json::to_string_pretty(event.as_ref()).unwrap_or_default(),
+                    event: event.clone(),
+                    metadata_json: None,
+                    client: event.client(),
+                    language: crate::language::describe(&event.content),
+                    relays: vec![relay.clone()],
+                    first_seen: HashMap::new(),
+                    time_format,
+                    deliveries: Vec::new(),
+                    profile_history: Vec::new(),
+                }))
+            }
+        });
+
+        self.raw_events.prepend(&button);
+        self.raw_count += 1;
+        if self.raw_count > MAX_ROWS {
+            if let Some(row) = self.raw_events.last_child() {
+                self.raw_events.remove(&row);
+                self.raw_count -= 1;
+            }
+        }
+    }
+
+    /// Replaces list of found users with `people`.
+    pub(super) fn show_people(
+        &mut self,
+        people: Vec<FoundPerson>,
+        sender: &AsyncFactorySender<Self>,
+    ) {
+        while let Some(row) = self.people.first_child() {
+            self.people.remove(&row);
+        }
+        self.people_rows.clear();
+
+        for FoundPerson { persona, followed } in people {
+            let pubkey = persona.pubkey;
+
+            let avatar = gtk::Image::from_paintable(Some(placeholder(&pubkey).as_ref()));
+            avatar.set_pixel_size(32);
+
+            let name = gtk::Label::new(Some(&persona.shown_name()));
+            name.set_xalign(0.0);
+            name.add_css_class("name");
+
+            let details = gtk::Box::new(gtk::Orientation::Vertical, 2);
+            details.set_hexpand(true);
+            details.append(&name);
+            if persona.show_nip05() {
+                let badge = gtk::Label::new(None);
+                badge.set_markup(&persona.format_nip05().unwrap_or_default());
+                badge.set_tooltip_text(persona.nip05_tooltip().as_deref());
+                badge.set_xalign(0.0);
+                badge.add_css_class("nip05");
+                details.append(&badge);
+            }
+
+            let profile = gtk::Button::with_label("Profile");
+            profile.connect_clicked({
+                let sender = sender.clone();
+                move |_| sender.output(LaneOutput::Open(Target::Profile(pubkey, Vec::new())))
+            });
+
+            let follow = gtk::Button::with_label(if followed { "Following" } else { "Follow" });
+            follow.set_sensitive(!followed);
+            follow.connect_clicked({
+                let sender = sender.clone();
+                move |_| sender.input(LaneMsg::Follow(pubkey))
+            });
+
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+            row.append(&avatar);
+            row.append(&details);
+            row.append(&profile);
+            row.append(&follow);
+
+            self.people.append(&row);
+            self.people_rows.insert(pubkey, (avatar, follow));
+        }
+
+        self.people.set_visible(!self.people_rows.is_empty());
+    }
+
+    pub fn kind(&self) -> &LaneKind {
+        &self.kind
+    }
+
+    /// Selects note next to the selected one, or the first one
+    /// if none is selected, and scrolls to it.
+    pub(super) fn move_selection(&mut self, forward: bool) {
+        let count = self.text_notes.len();
+        if count == 0 {
+            return;
+        }
+
+        let current = self
+            .selected
+            .and_then(|id| self.hash_index.get(&id))
+            .map(|di| di.current_index());
+        let index = match current {
+            Some(i) if forward => (i + 1).min(count - 1),
+            Some(i) => i.saturating_sub(1),
+            None => 0,
+        };
+
+        let list = self.text_notes.widget();
+        if let Some(row) = current.and_then(|i| list.row_at_index(i as i32)) {
+            row.remove_css_class("current");
+        }
+        if let Some(row) = list.row_at_index(index as i32) {
+            row.add_css_class("current");
+            // Scrolled window follows focus.
+            row.grab_focus();
+        }
+        self.selected = self.text_notes.get(index).map(|n| n.event.id);
+    }
+
+    /// Topmost visible note, none if the lane is scrolled to the top.
+    pub(super) fn top_note(&self) -> Option<EventId> {
+        self.visible_top().map(|(id, _)| id)
+    }
+
+    /// Topmost visible note and how far below its top the lane is scrolled,
+    /// none if the lane is scrolled to the top.
+    fn visible_top(&self) -> Option<(EventId, f64)> {
+        let offset = self.notes_scroll.vadjustment().value();
+        if offset <= 0.0 {
+            return None;
+        }
+
+        let list = self.text_notes.widget();
+        let row = list.row_at_y(offset as i32)?;
+        let y = row.compute_bounds(list).map(|b| b.y()).unwrap_or_default();
+        self.text_notes
+            .get(row.index() as usize)
+            .map(|n| (n.event.id, offset - y as f64))
+    }
+
+    /// Scrolls the anchor note to the top, once it is laid out.
+    /// Nothing happens if the note has not arrived.
+    pub(super) fn scroll_to_anchor(&self) {
+        if let Some(id) = self.anchor {
+            self.scroll_to_note(id, 0.0);
+        }
+    }
+
+    /// Scrolls the lane `offset` below top of note `id`, once it is laid out.
+    fn scroll_to_note(&self, id: EventId, offset: f64) {
+        let row = self.hash_index.get(&id).and_then(|di| {
+            self.text_notes
+                .widget()
+                .row_at_index(di.current_index() as i32)
+        });
+
+        if let Some(row) = row {
+            let list = self.text_notes.widget().clone();
+            let adjustment = self.notes_scroll.vadjustment();
+            gtk::glib::idle_add_local_once(move || {
+                if let Some(bounds) = row.compute_bounds(&list) {
+                    adjustment.set_value(bounds.y() as f64 + offset);
+                }
+            });
+        }
+    }
+
+    /// Marks all displayed notes read. Returns creation time of the newest
+    /// one, if user has not read it before.
+    pub(super) fn mark_read(&mut self) -> Option<Timestamp> {
+        self.arrived = 0;
+        let newest = self
+            .text_notes
+            .iter()
+            .map(|n| n.event.created_at)
+            .max_by_key(|t| t.as_i64())?;
+
+        if self.last_read.map(|t| t.as_i64() >= newest.as_i64()) == Some(true) {
+            return None;
+        }
+        self.last_read = Some(newest);
+        self.update_new_notes();
+        Some(newest)
+    }
+
+    /// Counts new notes for the header and displays the divider
+    /// above the newest of notes that are older than the new ones.
+    pub(super) fn update_new_notes(&mut self) {
+        if !self.kind.shows_new_notes() {
+            return;
+        }
+
+        let newer = |n: &Note, time: Option<Timestamp>| {
+            time.map(|t| n.event.created_at.as_i64() > t.as_i64())
+                .unwrap_or_default()
+        };
+
+        let new_notes = self
+            .text_notes
+            .iter()
+            .filter(|n| newer(n, self.last_read))
+            .count();
+        if new_notes != self.new_notes {
+            self.new_notes = new_notes;
+            self.header.emit(LaneHeaderInput::NewNotes(new_notes));
         }
+
+        // Divider is pointless if there is nothing above it.
+        let divider_note = if self.text_notes.iter().any(|n| newer(n, self.divider)) {
+            self.text_notes
+                .iter()
+                .find(|n| n.depth == 0 && !newer(n, self.divider))
+                .map(|n| n.event.id)
+        } else {
+            None
+        };
+
+        if divider_note != self.divider_note {
+            let mut guard = self.text_notes.guard();
+            for (id, divider) in [(self.divider_note, false), (divider_note, true)] {
+                let index = id
+                    .and_then(|id| self.hash_index.get(&id))
+                    .map(|di| di.current_index());
+                if let Some(note) = index.and_then(|i| guard.get_mut(i)) {
+                    note.divider = divider;
+                }
+            }
+            self.divider_note = divider_note;
+        }
+    }
+
+    /// Events displayed in this lane, including the focused note of thread.
+    pub fn displayed(&self) -> impl Iterator<Item = &EventId> {
+        let focus = match &self.kind {
+            LaneKind::Thread(id) => Some(id),
+            _ => None,
+        };
+        self.hash_index.keys().chain(focus)
+    }
+
+    /// Removes note of event `event_id` from this lane, it will not
+    /// be displayed even if filter changes.
+    pub(super) fn remove_note(&mut self, event_id: &EventId) {
+        self.received.remove(event_id);
+        self.hidden.remove(event_id);
+        self.unfold(event_id);
+        self.remove_displayed(event_id);
+    }
+
+    /// Removes displayed note of event `event_id`.
+    /// Its displayed replies move one level up.
+    pub(super) fn remove_displayed(&mut self, event_id: &EventId) {
+        self.article_index.retain(|_, id| id != event_id);
+        self.children
+            .values_mut()
+            .for_each(|ids| ids.retain(|id| id != event_id));
+        if self.selected == Some(*event_id) {
+            self.selected = None;
+        }
+
+        if let Some(di) = self.hash_index.remove(event_id) {
+            self.author_index.remove(event_id);
+            self.mention_index.remove(event_id);
+            self.image_index.remove(event_id);
+            self.emoji_index.remove(event_id);
+            self.preview_index.remove(event_id);
+            self.quote_index.remove(event_id);
+
+            let index = di.current_index();
+            let len = self.subtree_len(index);
+            let mut guard = self.text_notes.guard();
+            for i in index + 1..index + len {
+                if let Some(note) = guard.get_mut(i) {
+                    note.depth -= 1;
+                }
+            }
+            guard.remove(index);
+        }
+        self.update_new_notes();
+    }
+
+    /// Removes all notes from this lane.
+    pub(super) fn clear_notes(&mut self) {
+        self.clear_displayed();
+        self.received.clear();
+        self.hidden.clear();
+        self.folded.clear();
+    }
+
+    /// Removes all displayed notes, received ones can be displayed again.
+    fn clear_displayed(&mut self) {
+        self.text_notes.guard().clear();
+        self.hash_index.clear();
+        self.author_index.clear();
+        self.mention_index.clear();
+        self.image_index.clear();
+        self.emoji_index.clear();
+        self.preview_index.clear();
+        self.quote_index.clear();
+        self.article_index.clear();
+        self.children.clear();
+        self.divider_note = None;
+        self.selected = None;
+    }
+
+    /// Sends `msg` to the note of event `event_id`, if it is in this lane.
+    pub(super) fn send_to_event(&self, event_id: &EventId, msg: NoteInput) {
+        if let Some(di) = self.hash_index.get(event_id) {
+            self.text_notes.send(di.current_index(), msg);
+        }
+    }
+
+    /// Sends `msg` to all notes that display something from author `pubkey`.
+    pub(super) fn send_to_author(&self, pubkey: &XOnlyPublicKey, msg: NoteInput) {
+        for di in self.author_index.get(pubkey) {
+            self.text_notes.send(di.current_index(), msg.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nostr_sdk::prelude::{Marker, Tag};
+
+    use super::*;
+    use crate::testing::Fixtures;
+
+    fn period(since: u64, until: u64) -> Period {
+        Period {
+            since: Timestamp::from(since),
+            until: Timestamp::from(until),
+        }
+    }
+
+    #[test]
+    fn period_includes_both_moments() {
+        let p = period(100, 200);
+
+        assert!(!p.contains(Timestamp::from(99)));
+        assert!(p.contains(Timestamp::from(100)));
+        assert!(p.contains(Timestamp::from(200)));
+        assert!(!p.contains(Timestamp::from(201)));
+    }
+
+    #[test]
+    fn earlier_part_of_period_ends_just_before() {
+        let p = period(100, 200);
+
+        assert_eq!(p.before(Timestamp::from(150)), Some(period(100, 149)));
+        assert_eq!(p.before(Timestamp::from(101)), Some(period(100, 100)));
+        assert_eq!(p.before(Timestamp::from(100)), None);
+        assert_eq!(period(0, 10).before(Timestamp::from(0)), None);
+    }
+
+    #[test]
+    fn period_lane_takes_followed_notes_within_it() {
+        let (followed, other) = (Fixtures::new(), Fixtures::new());
+        let mut follow = Follow::new();
+        follow.add(followed.public_key());
+        let kind = LaneKind::Period(follow, period(100, 200));
+        let note = |author: &Fixtures, created_at: u64| {
+            author.sign(Kind::TextNote, "Hi", &[], Timestamp::from(created_at))
+        };
+
+        assert!(kind.accepts(&note(&followed, 150)));
+        assert!(!kind.accepts(&note(&followed, 250)));
+        assert!(!kind.accepts(&note(&other, 150)));
+        let parent = note(&other, 120);
+        let reply = followed.sign(
+            Kind::TextNote,
+            "Hi",
+            &[Tag::Event(parent.id, None, Some(Marker::Reply))],
+            Timestamp::from(150),
+        );
+        assert!(!kind.accepts(&reply));
+        assert_eq!(kind.period(), Some(period(100, 200)));
+        assert!(!kind.shows_new_notes());
     }
 }
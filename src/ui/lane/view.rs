@@ -1,10 +1,17 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 use gtk::prelude::*;
+use nostr_sdk::prelude::{Kind, XOnlyPublicKey};
 use relm4::factory::{AsyncFactoryComponent, FactoryVecDeque};
 use relm4::prelude::*;
 use relm4::{gtk, AsyncFactorySender};
 
+use crate::content::Target;
+use crate::nostr::{is_unsupported_content, short_npub, EventExt, Quote, ReportSubject};
+use crate::reactions::Reaction;
 use crate::ui::lane::model::*;
-use crate::ui::lane_header::LaneHeader;
+use crate::ui::lane_header::{LaneHeader, LaneHeaderInput, LaneHeaderOutput};
 use crate::ui::main::MainInput;
 use crate::ui::note::NoteInput;
 use crate::ui::profilebox;
@@ -12,7 +19,7 @@ use crate::ui::profilebox::model::Profilebox;
 
 #[relm4::factory(pub async)]
 impl AsyncFactoryComponent for Lane {
-    type Init = LaneKind;
+    type Init = LaneInit;
     type Input = LaneMsg;
     type Output = LaneOutput;
     type CommandOutput = ();
@@ -22,6 +29,16 @@ impl AsyncFactoryComponent for Lane {
     view! {
         gtk::Box {
             set_orientation: gtk::Orientation::Vertical,
+            add_css_class: "lane",
+            #[watch]
+            set_class_active: ("focused", self.focused),
+
+            add_controller = &gtk::GestureClick {
+                set_propagation_phase: gtk::PropagationPhase::Capture,
+                connect_pressed[sender, index] => move |_, _, _, _| {
+                    sender.output(LaneOutput::Focused(index.clone()))
+                }
+            },
 
             self.header.widget() { },
 
@@ -30,14 +47,136 @@ impl AsyncFactoryComponent for Lane {
                 set_visible: self.kind.is_a_profile(),
             },
 
-            // notes
+            self.search_entry.clone() {
+                set_visible: self.kind.is_search(),
+                set_placeholder_text: Some("Search notes and people, \"exact phrase\", npub…"),
+                connect_search_changed[sender] => move |entry| {
+                    sender.input(LaneMsg::SearchChanged(entry.text().to_string()))
+                }
+            },
+
+            // people found by search
+            self.people.clone() {
+                add_css_class: "people",
+                set_selection_mode: gtk::SelectionMode::None,
+                set_visible: false,
+            },
+
+            // notifications
             gtk::ScrolledWindow {
+                set_visible: self.kind.is_notifications(),
                 set_hscrollbar_policy: gtk::PolicyType::Never,
                 set_min_content_width: 600,
                 set_hexpand: true,
                 set_vexpand: true,
                 #[wrap(Some)]
-                set_child = self.text_notes.widget() {}
+                set_child = &self.notifications.clone() {
+                    add_css_class: "notifications",
+                    set_selection_mode: gtk::SelectionMode::None,
+                }
+            },
+
+            // raw events
+            gtk::Label {
+                add_css_class: "kind-counts",
+                set_visible: self.kind.is_firehose(),
+                set_xalign: 0.0,
+                set_wrap: true,
+                #[watch]
+                set_label: &self.kind_counts,
+            },
+            gtk::Expander {
+                set_visible: self.kind.is_firehose(),
+                #[watch]
+                set_label: Some(&format!(
+                    "Subscriptions ({})",
+                    self.subscriptions.lines().filter(|l| !l.starts_with(' ')).count()
+                )),
+                #[wrap(Some)]
+                set_child = &gtk::Label {
+                    add_css_class: "subscriptions",
+                    set_xalign: 0.0,
+                    set_wrap: true,
+                    set_selectable: true,
+                    #[watch]
+                    set_label: &self.subscriptions,
+                },
+            },
+            gtk::ScrolledWindow {
+                set_visible: self.kind.is_firehose(),
+                set_hscrollbar_policy: gtk::PolicyType::Never,
+                set_min_content_width: 600,
+                set_hexpand: true,
+                set_vexpand: true,
+                #[wrap(Some)]
+                set_child = &self.raw_events.clone() {
+                    add_css_class: "raw-events",
+                    set_selection_mode: gtk::SelectionMode::None,
+                }
+            },
+
+            gtk::Label {
+                add_css_class: "period",
+                set_visible: self.kind.period().is_some(),
+                set_xalign: 0.0,
+                set_wrap: true,
+                #[watch]
+                set_label: &self.format_period(),
+            },
+
+            gtk::Label {
+                add_css_class: "hidden-count",
+                set_xalign: 0.0,
+                #[watch]
+                set_visible: self.filtered() > 0,
+                #[watch]
+                set_label: &format!("{} hidden by filter", self.filtered()),
+            },
+
+            // collapsed notes of flooding authors
+            self.folded_rows.clone() {
+                set_orientation: gtk::Orientation::Vertical,
+                set_visible: false,
+            },
+
+            // notes
+            gtk::Overlay {
+                set_visible: !self.kind.is_notifications() && !self.kind.is_firehose(),
+                set_vexpand: true,
+
+                #[wrap(Some)]
+                set_child = &self.notes_scroll.clone() {
+                    set_hscrollbar_policy: gtk::PolicyType::Never,
+                    set_min_content_width: 600,
+                    set_hexpand: true,
+                    set_vexpand: true,
+                    #[wrap(Some)]
+                    set_child = self.text_notes.widget() {}
+                },
+
+                // notes arrived above while user was reading
+                add_overlay = &gtk::Button {
+                    add_css_class: "new-notes-pill",
+                    set_halign: gtk::Align::Center,
+                    set_valign: gtk::Align::Start,
+                    set_margin_top: 8,
+                    #[watch]
+                    set_visible: self.arrived > 0,
+                    #[watch]
+                    set_label: &match self.arrived {
+                        1 => "1 new note ↑".to_string(),
+                        n => format!("{n} new notes ↑"),
+                    },
+                    connect_clicked => LaneMsg::ScrollToTop,
+                }
+            },
+
+            gtk::Button {
+                add_css_class: "earlier-notes",
+                set_visible: self.kind.period().is_some(),
+                set_label: "Earlier notes",
+                set_tooltip_text: Some("Show notes older than the oldest one here, within the period"),
+                connect_clicked => LaneMsg::EarlierNotes,
             }
         }
     }
@@ -47,12 +186,34 @@ impl AsyncFactoryComponent for Lane {
         _index: &DynamicIndex,
         sender: AsyncFactorySender<Self>,
     ) -> Self {
+        let search_entry = gtk::SearchEntry::new();
+        if init.kind.is_search() {
+            // Search lane is opened to type into it right away.
+            search_entry.connect_map(|entry| {
+                entry.grab_focus();
+            });
+        }
+
+        let notes_scroll = gtk::ScrolledWindow::new();
+        notes_scroll.vadjustment().connect_value_changed({
+            let sender = sender.clone();
+            move |_| sender.input(LaneMsg::ScrollChanged)
+        });
+
         Self {
-            kind: init.clone(),
-            profile_box: Profilebox::builder().launch(()).detach(),
+            kind: init.kind.clone(),
+            me: init.me,
+            profile_box: Profilebox::builder().launch(init.me).detach(),
             header: LaneHeader::builder()
-                .launch(init)
-                .forward(sender.output_sender(), |_| LaneOutput::WriteNote),
+                .launch((init.kind, init.filter, init.threaded))
+                .forward(sender.input_sender(), |output| match output {
+                    LaneHeaderOutput::WriteNote => LaneMsg::WriteNote,
+                    LaneHeaderOutput::MarkAllRead => LaneMsg::MarkAllRead,
+                    LaneHeaderOutput::Filter(filter) => LaneMsg::Filter(filter),
+                    LaneHeaderOutput::Threaded(threaded) => LaneMsg::Threaded(threaded),
+                    LaneHeaderOutput::Period(period) => LaneMsg::SetPeriod(period),
+                    LaneHeaderOutput::Export { whole } => LaneMsg::Export { whole },
+                }),
 
             text_notes: FactoryVecDeque::new(
                 gtk::ListBox::builder()
@@ -61,16 +222,96 @@ impl AsyncFactoryComponent for Lane {
                 sender.input_sender(),
             ),
             hash_index: Default::default(),
+            author_index: Default::default(),
+            mention_index: Default::default(),
+            image_index: Default::default(),
+            emoji_index: Default::default(),
+            preview_index: Default::default(),
+            quote_index: Default::default(),
+            article_index: Default::default(),
+            handlers: Default::default(),
+            notifications: gtk::ListBox::new(),
+            notification_rows: Default::default(),
+            unread: Default::default(),
+            raw_events: gtk::ListBox::new(),
+            raw_count: 0,
+            kind_counts: String::new(),
+            subscriptions: String::new(),
+            search: String::new(),
+            search_generation: 0,
+            people: gtk::ListBox::new(),
+            people_rows: Default::default(),
+            bookmarks: init.bookmarks,
+            pinned: Vec::new(),
+            time_format: init.time_format,
+            collapse: init.collapse,
+            languages: init.languages,
+            translatable: init.translatable,
+            focused: init.focused,
+            selected: None,
+            search_entry,
+            notes_scroll,
+            anchor: init.anchor,
+            scroll_generation: 0,
+            filter: init.filter,
+            follow: init.follow,
+            received: Default::default(),
+            labels: Default::default(),
+            hidden: Default::default(),
+            folded: Default::default(),
+            unfolded: Default::default(),
+            folded_rows: gtk::Box::default(),
+            folded_buttons: Default::default(),
+            threaded: init.threaded,
+            children: Default::default(),
+            last_read: init.last_read,
+            divider: init.last_read,
+            divider_note: None,
+            new_notes: 0,
+            arrived: 0,
         }
     }
 
-    // fn output_to_parent_input(output: Self::Output) -> Option<Self::ParentInput> {
-    //     match output {
-    //         _ => Some(())
-    //         // LaneOutput::ShowDetails(details) => Some(Msg::ShowDetail(details)),
-    //         // LaneOutput::WriteNote => Some(Msg::WriteNote),
-    //     }
-    // }
+    fn output_to_parent_input(output: Self::Output) -> Option<Self::ParentInput> {
+        match output {
+            LaneOutput::ShowDetails(details) => Some(MainInput::ShowDetail(details)),
+            LaneOutput::WriteNote => Some(MainInput::WriteNote),
+            LaneOutput::NeedImages(urls) => Some(MainInput::DownloadImages(urls)),
+            LaneOutput::NeedEmojis(urls) => Some(MainInput::DownloadEmojis(urls)),
+            LaneOutput::OpenImage(url) => Some(MainInput::OpenImage(url)),
+            LaneOutput::NeedPreview(url) => Some(MainInput::NeedPreview(url)),
+            LaneOutput::Open(target) => Some(MainInput::from(target)),
+            LaneOutput::Reply(event) => Some(MainInput::Reply(event)),
+            LaneOutput::Quote(event, relays) => Some(MainInput::Quote(event, relays)),
+            LaneOutput::OpenArticle(article, author) => {
+                Some(MainInput::OpenArticle(article, author))
+            }
+            LaneOutput::NotificationRead(id) => Some(MainInput::NotificationRead(id)),
+            LaneOutput::AllNotificationsRead => Some(MainInput::AllNotificationsRead),
+            LaneOutput::Search(query) => Some(MainInput::Search(query)),
+            LaneOutput::Follow(pubkey) => Some(MainInput::Follow(pubkey)),
+            LaneOutput::Bookmark(id, bookmarked) => Some(MainInput::Bookmark(id, bookmarked)),
+            LaneOutput::Translate(id, content) => Some(MainInput::Translate(id, content)),
+            LaneOutput::NeedParent(id, relays) => Some(MainInput::NeedParent(id, relays)),
+            LaneOutput::Focused(index) => Some(MainInput::LaneFocused(index)),
+            LaneOutput::Scrolled { key, anchor } => Some(MainInput::LaneScrolled { key, anchor }),
+            LaneOutput::Filter { key, filter } => Some(MainInput::LaneFilter { key, filter }),
+            LaneOutput::Threaded { key, threaded } => {
+                Some(MainInput::LaneThreaded { key, threaded })
+            }
+            LaneOutput::Read { key, until } => Some(MainInput::LaneRead { key, until }),
+            LaneOutput::LoadPeriod(follow, period) => Some(MainInput::LoadPeriod(follow, period)),
+            LaneOutput::Export {
+                title,
+                notes,
+                thread,
+            } => Some(MainInput::Export {
+                title,
+                notes,
+                thread,
+            }),
+        }
+    }
 
     async fn update(&mut self, msg: Self::Input, sender: AsyncFactorySender<Self>) {
         match msg {
@@ -84,8 +325,23 @@ impl AsyncFactoryComponent for Lane {
                         author: author.clone(),
                     });
                 }
-                self.text_notes
-                    .broadcast(NoteInput::UpdatedProfile { author });
+                let pubkey = author.pubkey;
+                for note in self.received.values_mut() {
+                    if note.event.author() == pubkey {
+                        note.author = Some(author.clone());
+                    }
+                    if let Some(repost) = note.repost.as_mut().filter(|r| r.event.pubkey == pubkey)
+                    {
+                        repost.author = Some(author.clone());
+                    }
+                }
+                for di in self.mention_index.get(&pubkey) {
+                    self.text_notes.send(
+                        di.current_index(),
+                        NoteInput::MentionUpdated(author.clone()),
+                    );
+                }
+                self.send_to_author(&pubkey, NoteInput::UpdatedProfile { author });
             }
 
             LaneMsg::MetadataBitmap {
@@ -100,19 +356,69 @@ impl AsyncFactoryComponent for Lane {
                     })
                 };
 
-                self.text_notes.broadcast(NoteInput::MetadataBitmap {
-                    pubkey,
-                    url,
-                    bitmap,
-                });
+                if let Some((avatar, _)) = self.people_rows.get(&pubkey) {
+                    avatar.set_paintable(Some(bitmap.as_ref()));
+                }
+
+                self.send_to_author(
+                    &pubkey,
+                    NoteInput::MetadataBitmap {
+                        pubkey,
+                        url,
+                        bitmap,
+                    },
+                );
+            }
+
+            LaneMsg::Reaction {
+                event,
+                reaction,
+                emoji,
+            } => {
+                // Image of custom emoji is needed only if the note is here.
+                if let (Some(emoji), Some(di)) = (&emoji, self.hash_index.get(&event)) {
+                    self.emoji_index.insert(emoji.url.clone(), event, di);
+                    sender.output(LaneOutput::NeedEmojis(vec![emoji.url.clone()]));
+                }
+
+                // Notes waiting to be displayed keep counting.
+                if let Some(note) = self.received.get_mut(&event) {
+                    if let Some(reaction) = Reaction::of(&reaction, emoji.clone()) {
+                        note.reactions.add(reaction, 1);
+                    }
+                }
+
+                self.send_to_event(
+                    &event,
+                    NoteInput::Reaction {
+                        event,
+                        reaction,
+                        emoji,
+                    },
+                )
+            }
+
+            LaneMsg::Zap { event, sats } => {
+                if let Some(note) = self.received.get_mut(&event) {
+                    note.reactions.add_zap(sats);
+                }
+                self.send_to_event(&event, NoteInput::Zap(sats))
             }
 
-            LaneMsg::Reaction { event, reaction } => self
-                .text_notes
-                .broadcast(NoteInput::Reaction { event, reaction }),
+            LaneMsg::EmojiBitmap { url, bitmap } => {
+                for di in self.emoji_index.get(&url) {
+                    self.text_notes.send(
+                        di.current_index(),
+                        NoteInput::EmojiBitmap {
+                            url: url.clone(),
+                            bitmap: bitmap.clone(),
+                        },
+                    );
+                }
+            }
 
             LaneMsg::Nip05Verified(pubkey) => {
-                self.text_notes.broadcast(NoteInput::Nip05Verified(pubkey))
+                self.send_to_author(&pubkey, NoteInput::Nip05Verified(pubkey))
             }
 
             LaneMsg::NewTextNote {
@@ -120,24 +426,730 @@ impl AsyncFactoryComponent for Lane {
                 relays,
                 author,
                 repost,
+                mentions,
+                quotes,
+                flooding,
+                reactions,
+                language,
+                labels,
+                pending,
             } => {
-                self.text_notes.broadcast(NoteInput::TextNote {
+                self.labels_changed(event.author(), labels);
+
+                // Notes quoting this one can display it now.
+                let quoting = self.quote_index.entries(&event.id);
+                if !quoting.is_empty() {
+                    let quote = Quote {
+                        event: (*event).clone(),
+                        author: author.clone(),
+                    };
+                    for (_, di) in quoting {
+                        self.text_notes
+                            .send(di.current_index(), NoteInput::Quoted(quote.clone()));
+                    }
+                }
+                // And so can notes replying to it.
+                if self.children.contains_key(&event.id) {
+                    self.parent_received(Quote {
+                        event: (*event).clone(),
+                        author: author.clone(),
+                    });
+                }
+
+                let msg = NoteInput::TextNote {
                     event: event.clone(),
                     relays: relays.clone(),
                     author: author.clone(),
                     repost: repost.clone(),
-                });
+                };
+
+                // The note itself, if already present, and the note it replies to
+                // are the only ones interested.
+                self.send_to_event(&event.id, msg.clone());
+                if let (Some(r), Some(di)) = (&repost, self.hash_index.get(&event.id)) {
+                    // The note now displays who has reposted it.
+                    self.author_index.insert(r.event.pubkey, event.id, di);
+                }
+                if let Some(parent) = event.replies_to() {
+                    if let Some(di) = self.hash_index.get(&parent).cloned() {
+                        // Parent note now displays reply of this author.
+                        self.author_index.insert(event.author(), parent, &di);
+                        self.text_notes.send(di.current_index(), msg);
+                    }
+                }
+
+                // Search lane picks up also notes found by relays.
+                let found = (self.kind.is_search()
+                    && crate::search::matches(&self.search, &event.content))
+                    || (self.kind.is_bookmarks() && self.bookmarks.contains(&event.id))
+                    || self.pinned.contains(&event.id);
 
                 if self.kind.accepts(&event)
+                    || found
                     || repost
                         .as_ref()
                         .map(|r| self.kind.accepts(&r.event))
                         .unwrap_or_default()
                 {
-                    self.text_note_received(event, relays, author, repost)
+                    let wanted = self.note_received(ReceivedNote {
+                        event,
+                        relays,
+                        author,
+                        repost,
+                        mentions,
+                        quotes,
+                        flooding,
+                        reactions,
+                        language,
+                        pending,
+                    });
+                    request_media(&sender, wanted);
+                }
+            }
+            LaneMsg::Open(target) => sender.output(LaneOutput::Open(target)),
+
+            LaneMsg::ImageBitmap { url, bitmap } => {
+                for di in self.image_index.get(&url) {
+                    self.text_notes.send(
+                        di.current_index(),
+                        NoteInput::ImageBitmap {
+                            url: url.clone(),
+                            bitmap: bitmap.clone(),
+                        },
+                    );
+                }
+            }
+
+            LaneMsg::NeedImages(urls) => sender.output(LaneOutput::NeedImages(urls)),
+
+            LaneMsg::OpenImage(url) => sender.output(LaneOutput::OpenImage(url)),
+
+            LaneMsg::LinkPreview(preview) => {
+                for (id, di) in self.preview_index.entries(&preview.url).to_vec() {
+                    // Thumbnail of the page will arrive as any other image.
+                    if let Some(image) = &preview.image {
+                        self.image_index.insert(image.clone(), id, &di);
+                    }
+                    self.text_notes
+                        .send(di.current_index(), NoteInput::LinkPreview(preview.clone()));
+                }
+            }
+
+            LaneMsg::NeedPreview(url) => sender.output(LaneOutput::NeedPreview(url)),
+
+            LaneMsg::Reply(event) => sender.output(LaneOutput::Reply(event)),
+
+            LaneMsg::Quote(event, relays) => sender.output(LaneOutput::Quote(event, relays)),
+
+            LaneMsg::Article {
+                article,
+                relays,
+                author,
+            } => {
+                if self.kind.accepts(&article.event) {
+                    // Newer version of the article replaces the displayed one.
+                    let address = article.address();
+                    if let Some(old) = self.article_index.get(&address).copied() {
+                        if old == article.event.id {
+                            return;
+                        }
+                        self.remove_note(&old);
+                    }
+                    self.article_index.insert(address, article.event.id);
+
+                    let wanted = self.note_received(ReceivedNote {
+                        event: Arc::new(article.event.clone()),
+                        relays,
+                        author,
+                        repost: None,
+                        mentions: vec![],
+                        quotes: vec![],
+                        flooding: false,
+                        reactions: Default::default(),
+                        language: None,
+                        pending: false,
+                    });
+                    request_media(&sender, wanted);
+                }
+            }
+
+            LaneMsg::Unsupported {
+                event,
+                relays,
+                author,
+                handler,
+            } => {
+                if let Some(handler) = handler {
+                    self.handlers.insert(event.kind.as_u64(), handler);
+                }
+
+                let bookmarked = self.kind.is_bookmarks() && self.bookmarks.contains(&event.id);
+                if self.kind.accepts(&event) || bookmarked {
+                    let wanted = self.note_received(ReceivedNote {
+                        event,
+                        relays,
+                        author,
+                        repost: None,
+                        mentions: vec![],
+                        quotes: vec![],
+                        flooding: false,
+                        reactions: Default::default(),
+                        language: None,
+                        pending: false,
+                    });
+                    request_media(&sender, wanted);
+                }
+            }
+
+            LaneMsg::Handlers(handlers) => {
+                for (kind, handler) in handlers {
+                    if !is_unsupported_content(Kind::from(kind)) {
+                        continue;
+                    }
+                    for (i, note) in self.text_notes.iter().enumerate() {
+                        if note.event.kind.as_u64() == kind {
+                            self.text_notes.send(i, NoteInput::Handler(handler.clone()));
+                        }
+                    }
+                    self.handlers.insert(kind, handler);
+                }
+            }
+
+            LaneMsg::OpenArticle(article, author) => {
+                sender.output(LaneOutput::OpenArticle(article, author))
+            }
+
+            LaneMsg::WriteNote => sender.output(LaneOutput::WriteNote),
+
+            LaneMsg::Notification(notification) => {
+                if self.kind.is_notifications() {
+                    self.add_notification(notification, true, &sender);
+                    self.header
+                        .emit(LaneHeaderInput::Unread(self.unread.len() as u32));
+                }
+            }
+
+            LaneMsg::NotificationHistory(history) => {
+                if self.kind.is_notifications() {
+                    for notification in history {
+                        self.add_notification(notification, false, &sender);
+                    }
+                    self.header
+                        .emit(LaneHeaderInput::Unread(self.unread.len() as u32));
+                }
+            }
+
+            LaneMsg::NotificationClicked(id) => {
+                if let Some((notification, row)) = self.notification_rows.get(&id) {
+                    if self.unread.remove(&id) {
+                        row.remove_css_class("unread");
+                        sender.output(LaneOutput::NotificationRead(id));
+                        self.header
+                            .emit(LaneHeaderInput::Unread(self.unread.len() as u32));
+                    }
+
+                    let target = match notification.thread() {
+                        Some(thread) => Target::Note(thread, Vec::new()),
+                        None => Target::Profile(notification.author, Vec::new()),
+                    };
+                    sender.output(LaneOutput::Open(target));
+                }
+            }
+
+            LaneMsg::MarkAllRead => {
+                for id in self.unread.drain() {
+                    if let Some((_, row)) = self.notification_rows.get(&id) {
+                        row.remove_css_class("unread");
+                    }
+                }
+                self.header.emit(LaneHeaderInput::Unread(0));
+                sender.output(LaneOutput::AllNotificationsRead);
+            }
+
+            LaneMsg::SearchChanged(text) => {
+                /// Searching starts once user stops typing for this long.
+                const DEBOUNCE: Duration = Duration::from_millis(300);
+
+                self.search_generation += 1;
+                let generation = self.search_generation;
+                let sender = sender.clone();
+                relm4::spawn(async move {
+                    tokio::time::sleep(DEBOUNCE).await;
+                    sender.input(LaneMsg::SearchDebounced(generation, text));
+                });
+            }
+
+            LaneMsg::Search(text) => {
+                if self.kind.is_search() {
+                    self.search_entry.set_text(&text);
+                }
+            }
+
+            LaneMsg::SearchDebounced(generation, text) => {
+                if generation == self.search_generation && text.trim() != self.search {
+                    self.search = text.trim().to_string();
+
+                    // Pasted npub or nprofile leads directly to the profile.
+                    let entity = self.search.strip_prefix("nostr:").unwrap_or(&self.search);
+                    match Target::from_bech32(entity) {
+                        Some(target) => sender.output(LaneOutput::Open(target)),
+                        None => {
+                            self.clear_notes();
+                            self.show_people(Vec::new(), &sender);
+                            if !self.search.is_empty() {
+                                sender.output(LaneOutput::Search(self.search.clone()));
+                            }
+                        }
+                    }
+                }
+            }
+
+            LaneMsg::SearchResults { query, notes } => {
+                // Results of previous queries may still be arriving.
+                if self.kind.is_search() && query == self.search {
+                    for FoundNote {
+                        event,
+                        relays,
+                        author,
+                        reactions,
+                    } in notes
+                    {
+                        let wanted = self.note_received(ReceivedNote {
+                            event,
+                            relays,
+                            author,
+                            repost: None,
+                            mentions: vec![],
+                            quotes: vec![],
+                            flooding: false,
+                            reactions,
+                            language: None,
+                            pending: false,
+                        });
+                        request_media(&sender, wanted);
+                    }
+                }
+            }
+
+            LaneMsg::PeopleResults { query, people } => {
+                if self.kind.is_search() && query == self.search {
+                    self.show_people(people, &sender);
+                }
+            }
+
+            LaneMsg::Follow(pubkey) => sender.output(LaneOutput::Follow(pubkey)),
+
+            LaneMsg::ContactCounts {
+                pubkey,
+                following,
+                followers,
+            } => {
+                if self.kind.is_profile(&pubkey) {
+                    self.profile_box.emit(profilebox::Input::ContactCounts {
+                        pubkey,
+                        following,
+                        followers,
+                    });
+                }
+            }
+
+            LaneMsg::ProfileChanges { pubkey, fields } => {
+                if self.kind.is_profile(&pubkey) {
+                    self.profile_box
+                        .emit(profilebox::Input::RecentChanges(fields));
+                }
+            }
+
+            LaneMsg::Followed(pubkey) => {
+                if let LaneKind::Feed(follow) | LaneKind::Period(follow, _) = &mut self.kind {
+                    follow.add(pubkey);
+                }
+                if self.follow.add(pubkey) && self.filter.follows_only {
+                    for wanted in self.apply_filter() {
+                        request_media(&sender, wanted);
+                    }
+                }
+                if let Some((_, button)) = self.people_rows.get(&pubkey) {
+                    button.set_label("Following");
+                    button.set_sensitive(false);
+                }
+            }
+
+            LaneMsg::Reported(ReportSubject::Note { event_id, .. })
+            | LaneMsg::Deleted(event_id) => self.remove_note(&event_id),
+
+            LaneMsg::Reported(ReportSubject::User(pubkey)) | LaneMsg::Muted(pubkey) => {
+                let muted: Vec<_> = self
+                    .text_notes
+                    .iter()
+                    .map(|n| &n.event)
+                    .chain(self.received.values().map(|n| &n.event))
+                    .filter(|e| e.pubkey == pubkey || e.author() == pubkey)
+                    .map(|e| e.id)
+                    .collect();
+                for id in muted {
+                    self.remove_note(&id);
+                }
+            }
+
+            LaneMsg::Bookmark(id, bookmarked) => {
+                sender.output(LaneOutput::Bookmark(id, bookmarked))
+            }
+
+            LaneMsg::Bookmarked(id, bookmarked) => {
+                if bookmarked {
+                    self.bookmarks.insert(id);
+                } else {
+                    self.bookmarks.remove(&id);
+                }
+
+                if self.kind.is_bookmarks() && !bookmarked {
+                    self.remove_note(&id);
+                } else {
+                    self.send_to_event(&id, NoteInput::Bookmarked(bookmarked));
+                }
+            }
+
+            LaneMsg::BookmarkedNotes(notes) => {
+                if self.kind.is_bookmarks() {
+                    for FoundNote {
+                        event,
+                        relays,
+                        author,
+                        reactions,
+                    } in notes
+                    {
+                        if !self.bookmarks.contains(&event.id) {
+                            continue;
+                        }
+
+                        let wanted = self.note_received(ReceivedNote {
+                            event,
+                            relays,
+                            author,
+                            repost: None,
+                            mentions: vec![],
+                            quotes: vec![],
+                            flooding: false,
+                            reactions,
+                            language: None,
+                            pending: false,
+                        });
+                        request_media(&sender, wanted);
+                    }
+                }
+            }
+
+            LaneMsg::PeriodNotes(notes) => {
+                for FoundNote {
+                    event,
+                    relays,
+                    author,
+                    reactions,
+                } in notes
+                {
+                    if !self.kind.accepts(&event) || self.received.contains_key(&event.id) {
+                        continue;
+                    }
+
+                    let wanted = self.note_received(ReceivedNote {
+                        event,
+                        relays,
+                        author,
+                        repost: None,
+                        mentions: vec![],
+                        quotes: vec![],
+                        flooding: false,
+                        reactions,
+                        language: None,
+                        pending: false,
+                    });
+                    request_media(&sender, wanted);
+                }
+            }
+
+            LaneMsg::SetPeriod(period) => {
+                if let LaneKind::Period(follow, current) = &mut self.kind {
+                    if *current != period {
+                        *current = period;
+                        let follow = follow.clone();
+                        self.clear_notes();
+                        sender.output(LaneOutput::LoadPeriod(follow, period));
+                    }
+                }
+            }
+
+            LaneMsg::Export { whole } => {
+                let shown_name = |pubkey: &XOnlyPublicKey| {
+                    self.received
+                        .values()
+                        .filter_map(|n| n.author.as_ref())
+                        .find(|a| a.pubkey == *pubkey)
+                        .map(|a| a.shown_name())
+                        .unwrap_or_else(|| short_npub(pubkey))
+                };
+                let (title, thread) = match &self.kind {
+                    LaneKind::Thread(id) => {
+                        let title = match self.received.get(id) {
+                            Some(root) => format!("Thread by {}", shown_name(&root.event.pubkey)),
+                            None => "Thread".to_string(),
+                        };
+                        (title, whole.then_some(*id))
+                    }
+                    LaneKind::Profile(pubkey) => (format!("Notes by {}", shown_name(pubkey)), None),
+                    _ => return,
+                };
+
+                let notes = self
+                    .received
+                    .values()
+                    .map(|n| (n.event.clone(), n.author.clone()))
+                    .collect();
+                sender.output(LaneOutput::Export {
+                    title,
+                    notes,
+                    thread,
+                });
+            }
+
+            LaneMsg::EarlierNotes => {
+                if let LaneKind::Period(follow, period) = &self.kind {
+                    let oldest = self
+                        .received
+                        .values()
+                        .map(|n| n.event.created_at)
+                        .min_by_key(|t| t.as_i64())
+                        .unwrap_or(period.until);
+                    if let Some(earlier) = period.before(oldest) {
+                        sender.output(LaneOutput::LoadPeriod(follow.clone(), earlier));
+                    }
+                }
+            }
+
+            LaneMsg::Pins { author, pinned } => {
+                if self.kind.is_profile(&author) {
+                    for wanted in self.pins_changed(pinned) {
+                        request_media(&sender, wanted);
+                    }
+                }
+            }
+
+            LaneMsg::PinnedNotes(notes) => {
+                for FoundNote {
+                    event,
+                    relays,
+                    author,
+                    reactions,
+                } in notes
+                {
+                    if !self.pinned.contains(&event.id) {
+                        continue;
+                    }
+
+                    let wanted = self.note_received(ReceivedNote {
+                        event,
+                        relays,
+                        author,
+                        repost: None,
+                        mentions: vec![],
+                        quotes: vec![],
+                        flooding: false,
+                        reactions,
+                        language: None,
+                        pending: false,
+                    });
+                    request_media(&sender, wanted);
                 }
             }
-            LaneMsg::LinkClicked(uri) => println!("Clicked: {uri}"),
+
+            LaneMsg::Focus(focused) => {
+                self.focused = focused;
+                if focused && self.kind.is_search() {
+                    self.search_entry.grab_focus();
+                }
+                if focused {
+                    self.notes_read(&sender);
+                }
+            }
+
+            LaneMsg::Select { forward } => self.move_selection(forward),
+
+            LaneMsg::ReplySelected => {
+                let event = self
+                    .selected
+                    .and_then(|id| self.hash_index.get(&id))
+                    .and_then(|di| self.text_notes.get(di.current_index()))
+                    .map(|n| n.event.clone());
+                if let Some(event) = event {
+                    sender.output(LaneOutput::Reply(event));
+                }
+            }
+
+            LaneMsg::ScrollChanged => {
+                /// Topmost note is reported once user stops scrolling for this long.
+                const DEBOUNCE: Duration = Duration::from_millis(500);
+
+                self.scroll_generation += 1;
+                let generation = self.scroll_generation;
+                let sender = sender.clone();
+                relm4::spawn(async move {
+                    tokio::time::sleep(DEBOUNCE).await;
+                    sender.input(LaneMsg::ScrollDebounced(generation));
+                });
+            }
+
+            LaneMsg::ScrollDebounced(generation) => {
+                if generation == self.scroll_generation {
+                    let top = self.top_note();
+                    // User has scrolled away from where the lane was left.
+                    if self.anchor.is_some() && top != self.anchor {
+                        self.anchor = None;
+                    }
+                    // Newest notes are on top.
+                    if top.is_none() {
+                        self.notes_read(&sender);
+                    }
+                    sender.output(LaneOutput::Scrolled {
+                        key: self.kind.key(),
+                        anchor: top,
+                    });
+                }
+            }
+
+            LaneMsg::ScrollToTop => self.notes_scroll.vadjustment().set_value(0.0),
+
+            LaneMsg::Filter(filter) => {
+                self.filter = filter;
+                for wanted in self.apply_filter() {
+                    request_media(&sender, wanted);
+                }
+                sender.output(LaneOutput::Filter {
+                    key: self.kind.key(),
+                    filter,
+                });
+            }
+
+            LaneMsg::Threaded(threaded) => {
+                self.threaded = threaded;
+                for wanted in self.relayout() {
+                    request_media(&sender, wanted);
+                }
+                sender.output(LaneOutput::Threaded {
+                    key: self.kind.key(),
+                    threaded,
+                });
+            }
+
+            LaneMsg::Tick(time_format) => {
+                self.time_format = time_format;
+                self.text_notes.broadcast(NoteInput::Tick(time_format));
+            }
+            LaneMsg::Collapse(collapse) => {
+                self.collapse = collapse;
+                self.text_notes.broadcast(NoteInput::Collapse(collapse));
+            }
+            LaneMsg::Languages(languages) => {
+                if languages != self.languages {
+                    self.languages = languages;
+                    for wanted in self.apply_filter() {
+                        request_media(&sender, wanted);
+                    }
+                }
+            }
+            LaneMsg::Translatable(translatable) => {
+                self.translatable = translatable;
+                self.text_notes
+                    .broadcast(NoteInput::Translatable(translatable));
+            }
+            LaneMsg::Translate(id, content) => sender.output(LaneOutput::Translate(id, content)),
+            LaneMsg::Translated {
+                event_id,
+                translation,
+            } => self.send_to_event(&event_id, NoteInput::Translated(translation)),
+            LaneMsg::Parent(quote) => self.parent_received(quote),
+            LaneMsg::Labels { author, labels } => self.labels_changed(author, labels),
+            LaneMsg::SeenOn { event, relay } => {
+                self.send_to_event(&event, NoteInput::SeenOn(relay))
+            }
+            LaneMsg::Unfold(pubkey) => {
+                self.unfolded.insert(pubkey);
+                for wanted in self.apply_filter() {
+                    request_media(&sender, wanted);
+                }
+            }
+            LaneMsg::Raw { relay, event } => {
+                if self.kind.is_firehose() {
+                    self.add_raw(relay, event, &sender);
+                }
+            }
+
+            LaneMsg::KindStats {
+                counts,
+                skipped,
+                stream,
+                subscriptions,
+            } => {
+                if self.kind.is_firehose() {
+                    let mut counts: Vec<String> = counts
+                        .iter()
+                        .map(|(kind, n)| format!("kind {kind}: {n}"))
+                        .collect();
+                    if skipped > 0 {
+                        counts.push(format!("{skipped} not shown"));
+                    }
+                    self.kind_counts = format!("{}\n{}", stream, counts.join(", "));
+                    self.subscriptions = subscriptions
+                        .iter()
+                        .map(|s| s.to_string())
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                }
+            }
+
+            LaneMsg::Delivered(id) => self.send_to_event(&id, NoteInput::Delivered),
+
+            LaneMsg::Expired(id) => {
+                if self.kind.is_thread(&id) {
+                    // Thread lane would not make sense without its central note,
+                    // let's keep it, just marked as expired.
+                    self.send_to_event(&id, NoteInput::Expired);
+                } else {
+                    self.remove_note(&id);
+                }
+            }
+        }
+
+        self.show_folded(&sender);
+    }
+}
+
+/// Asks for media that newly displayed note wants.
+impl Lane {
+    /// User has seen all notes of the lane.
+    fn notes_read(&mut self, sender: &AsyncFactorySender<Self>) {
+        if !self.kind.shows_new_notes() {
+            return;
+        }
+        if let Some(until) = self.mark_read() {
+            sender.output(LaneOutput::Read {
+                key: self.kind.key(),
+                until,
+            });
         }
     }
 }
+
+fn request_media(sender: &AsyncFactorySender<Lane>, wanted: WantedMedia) {
+    if !wanted.images.is_empty() {
+        sender.output(LaneOutput::NeedImages(wanted.images));
+    }
+    if !wanted.emojis.is_empty() {
+        sender.output(LaneOutput::NeedEmojis(wanted.emojis));
+    }
+    if let Some(url) = wanted.preview {
+        sender.output(LaneOutput::NeedPreview(url));
+    }
+    if let Some((parent, relays)) = wanted.parent {
+        sender.output(LaneOutput::NeedParent(parent, relays));
+    }
+}
@@ -1,17 +1,72 @@
 use gtk::prelude::*;
+use nostr_sdk::prelude::Timestamp;
+use relm4::actions::{RelmAction, RelmActionGroup};
 use relm4::*;
 
-use crate::app::action::EditProfile;
-use crate::ui::lane::LaneKind;
+use crate::app::action::{
+    ClearCache, Clock24h, DesktopNotifications, EditProfile, ExportBackup, ImportFollows,
+    OpenLogFolder, Quit, RelativeTime, ShowArchive, ShowBookmarks, ShowNotifications, ShowOpen,
+    ShowOutbox, ShowPeriod, ShowPrune, ShowRelays, ShowSearch, ShowSettings, ShowShortcuts,
+};
+use crate::ui::lane::{LaneFilter, LaneKind, Period};
+
+relm4::new_action_group!(LaneActionGroup, "lane");
+relm4::new_stateful_action!(HideReplies, LaneActionGroup, "hide-replies", (), bool);
+relm4::new_stateful_action!(HideReposts, LaneActionGroup, "hide-reposts", (), bool);
+relm4::new_stateful_action!(FollowsOnly, LaneActionGroup, "follows-only", (), bool);
+relm4::new_stateful_action!(AllLanguages, LaneActionGroup, "all-languages", (), bool);
+relm4::new_stateful_action!(Threaded, LaneActionGroup, "threaded", (), bool);
+relm4::new_stateless_action!(Export, LaneActionGroup, "export");
+relm4::new_stateless_action!(ExportThread, LaneActionGroup, "export-thread");
 
 #[derive(Debug)]
-pub struct LaneHeader {}
+pub struct LaneHeader {
+    /// Number of notifications user has not seen yet.
+    unread: u32,
+    /// Number of notes user has not read yet.
+    new_notes: usize,
+    /// Which notes the lane hides.
+    filter: LaneFilter,
+}
+
+#[derive(Debug)]
+pub enum LaneHeaderInput {
+    /// Number of unseen notifications has changed.
+    Unread(u32),
+    /// Number of unread notes has changed.
+    NewNotes(usize),
+    HideReplies(bool),
+    HideReposts(bool),
+    FollowsOnly(bool),
+    AllLanguages(bool),
+    MinPow(u8),
+    Threaded(bool),
+}
+
+#[derive(Debug)]
+pub enum LaneHeaderOutput {
+    WriteNote,
+    /// User has seen all notifications.
+    MarkAllRead,
+    /// User has changed which notes the lane hides.
+    Filter(LaneFilter),
+    /// User wants replies below notes they reply to, or chronologically.
+    Threaded(bool),
+    /// User wants period lane to display another period.
+    Period(Period),
+    /// User wants to save notes of the lane into a file, all notes of
+    /// the thread if `whole`, not just those displayed.
+    Export {
+        whole: bool,
+    },
+}
 
 #[relm4::component(pub)]
 impl SimpleComponent for LaneHeader {
-    type Input = ();
-    type Init = LaneKind;
-    type Output = ();
+    type Input = LaneHeaderInput;
+    /// Kind of the lane, which notes it hides and whether it displays threads.
+    type Init = (LaneKind, LaneFilter, bool);
+    type Output = LaneHeaderOutput;
 
     view! {
         gtk::CenterBox {
@@ -23,7 +78,7 @@ impl SimpleComponent for LaneHeader {
                 gtk::Button::from_icon_name("mail-message-new-symbolic") {
                     set_has_frame: false,
                     set_tooltip_text: Some("Write new text note with the current identity"),
-                    connect_clicked[sender] => move |_| { sender.output(()).unwrap() }
+                    connect_clicked[sender] => move |_| { sender.output(LaneHeaderOutput::WriteNote).unwrap() }
                 }
             },
 
@@ -38,22 +93,34 @@ impl SimpleComponent for LaneHeader {
                 gtk::Label {
                     set_text: "Main identity",
                     add_css_class: "identity"
+                },
+                gtk::Button {
+                    add_css_class: "unread",
+                    set_tooltip_text: Some("Mark all notifications as read"),
+                    #[watch]
+                    set_visible: model.unread > 0,
+                    #[watch]
+                    set_label: &model.unread.to_string(),
+                    connect_clicked[sender] => move |_| { sender.output(LaneHeaderOutput::MarkAllRead).unwrap() }
+                },
+                gtk::Label {
+                    add_css_class: "new-notes",
+                    set_tooltip_text: Some("Notes created since you last read the lane"),
+                    #[watch]
+                    set_visible: model.new_notes > 0,
+                    #[watch]
+                    set_label: &format!("{} new", model.new_notes),
                 }
             },
 
             #[wrap(Some)]
             set_end_widget = &gtk::Box {
-                gtk::Button::from_icon_name("open-menu-symbolic") {
+                #[name = "menu_button"]
+                gtk::MenuButton {
+                    set_icon_name: "open-menu-symbolic",
                     set_has_frame: false,
                     set_tooltip_text: Some("Open menu to see list of actions"),
-                    connect_clicked => move |b| {
-                        let popover = gtk::PopoverMenu::builder()
-                            .menu_model(&main_menu)
-                            .has_arrow(false)
-                            .build();
-                        popover.set_parent(b);
-                        popover.popup();
-                    }
+                    set_menu_model: Some(&main_menu),
                 }
             },
         }
@@ -61,27 +128,298 @@ impl SimpleComponent for LaneHeader {
 
     menu! {
         main_menu: {
-            "Edit profile" => EditProfile
+            section! {
+                "Hide replies" => HideReplies,
+                "Hide reposts" => HideReposts,
+                "Only people I follow" => FollowsOnly,
+                "All languages" => AllLanguages,
+                custom: "min-pow"
+            },
+            section! {
+                custom: "period"
+            },
+            section! {
+                "Threaded replies" => Threaded,
+                "Export…" => Export,
+                "Export whole thread…" => ExportThread
+            },
+            "Edit profile" => EditProfile,
+            "Import follows…" => ImportFollows,
+            "Open profile or note…" => ShowOpen,
+            "Relays" => ShowRelays,
+            "Search" => ShowSearch,
+            "Notifications" => ShowNotifications,
+            "Bookmarks" => ShowBookmarks,
+            "Catch up on a period" => ShowPeriod,
+            "Outbox" => ShowOutbox,
+            "Desktop notifications" => DesktopNotifications,
+            "Relative times" => RelativeTime,
+            "24-hour clock" => Clock24h,
+            "Export backup…" => ExportBackup,
+            "Export and import events…" => ShowArchive,
+            "Database retention…" => ShowPrune,
+            "Settings…" => ShowSettings,
+            "Keyboard shortcuts" => ShowShortcuts,
+            "Open log folder" => OpenLogFolder,
+            "Clear cache" => ClearCache,
+            "Quit" => Quit
         }
     }
 
     fn init(
-        init: Self::Init,
-        _root: &Self::Root,
+        (kind, filter, threaded): Self::Init,
+        root: &Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        let model = LaneHeader {};
+        let model = LaneHeader {
+            unread: 0,
+            new_notes: 0,
+            filter,
+        };
 
-        let header = match init {
+        let header = match kind {
             LaneKind::Feed(_) => "Feed",
             LaneKind::Thread(_) => "Thread",
             LaneKind::Profile(_) => "User profile",
+            LaneKind::Notifications => "Notifications",
+            LaneKind::Search => "Search",
+            LaneKind::Bookmarks => "Bookmarks",
+            LaneKind::Firehose => "Firehose",
+            LaneKind::Period(..) => "Period",
         };
 
         let widgets = view_output!();
 
+        root.insert_action_group(
+            "lane",
+            Some(&lane_actions(&sender, &kind, &filter, threaded)),
+        );
+        if let Some(popover) = widgets
+            .menu_button
+            .popover()
+            .and_then(|p| p.downcast::<gtk::PopoverMenu>().ok())
+        {
+            popover.set_has_arrow(false);
+            popover.add_child(&min_pow_slider(&sender, filter.min_pow), "min-pow");
+            if let Some(period) = kind.period() {
+                popover.add_child(&period_picker(&sender, period), "period");
+            }
+        }
+
         ComponentParts { model, widgets }
     }
 
-    fn update(&mut self, _message: Self::Input, _sender: ComponentSender<Self>) {}
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        let mut filter = self.filter;
+        match message {
+            LaneHeaderInput::Unread(unread) => self.unread = unread,
+            LaneHeaderInput::NewNotes(new_notes) => self.new_notes = new_notes,
+            LaneHeaderInput::HideReplies(hide) => filter.hide_replies = hide,
+            LaneHeaderInput::HideReposts(hide) => filter.hide_reposts = hide,
+            LaneHeaderInput::FollowsOnly(only) => filter.follows_only = only,
+            LaneHeaderInput::AllLanguages(all) => filter.all_languages = all,
+            LaneHeaderInput::MinPow(min_pow) => filter.min_pow = min_pow,
+            LaneHeaderInput::Threaded(threaded) => {
+                sender.output(LaneHeaderOutput::Threaded(threaded)).unwrap()
+            }
+        }
+
+        if filter != self.filter {
+            self.filter = filter;
+            sender.output(LaneHeaderOutput::Filter(filter)).unwrap();
+        }
+    }
+}
+
+/// Toggles of the lane's filter and threads in its menu, and export of
+/// threads and profiles.
+fn lane_actions(
+    sender: &ComponentSender<LaneHeader>,
+    kind: &LaneKind,
+    filter: &LaneFilter,
+    threaded: bool,
+) -> gtk::gio::SimpleActionGroup {
+    let group = RelmActionGroup::<LaneActionGroup>::new();
+
+    let hide_replies: RelmAction<HideReplies> = RelmAction::new_stateful(&filter.hide_replies, {
+        let sender = sender.clone();
+        move |_, hide: &mut bool| {
+            *hide = !*hide;
+            sender.input(LaneHeaderInput::HideReplies(*hide));
+        }
+    });
+    let hide_reposts: RelmAction<HideReposts> = RelmAction::new_stateful(&filter.hide_reposts, {
+        let sender = sender.clone();
+        move |_, hide: &mut bool| {
+            *hide = !*hide;
+            sender.input(LaneHeaderInput::HideReposts(*hide));
+        }
+    });
+    let follows_only: RelmAction<FollowsOnly> = RelmAction::new_stateful(&filter.follows_only, {
+        let sender = sender.clone();
+        move |_, only: &mut bool| {
+            *only = !*only;
+            sender.input(LaneHeaderInput::FollowsOnly(*only));
+        }
+    });
+    let all_languages: RelmAction<AllLanguages> =
+        RelmAction::new_stateful(&filter.all_languages, {
+            let sender = sender.clone();
+            move |_, all: &mut bool| {
+                *all = !*all;
+                sender.input(LaneHeaderInput::AllLanguages(*all));
+            }
+        });
+    let threaded: RelmAction<Threaded> = RelmAction::new_stateful(&threaded, {
+        let sender = sender.clone();
+        move |_, threaded: &mut bool| {
+            *threaded = !*threaded;
+            sender.input(LaneHeaderInput::Threaded(*threaded));
+        }
+    });
+
+    group.add_action(&hide_replies);
+    group.add_action(&hide_reposts);
+    group.add_action(&follows_only);
+    group.add_action(&all_languages);
+    let export: RelmAction<Export> = RelmAction::new_stateless({
+        let sender = sender.clone();
+        move |_| {
+            sender
+                .output(LaneHeaderOutput::Export { whole: false })
+                .unwrap()
+        }
+    });
+    let export_thread: RelmAction<ExportThread> = RelmAction::new_stateless({
+        let sender = sender.clone();
+        move |_| {
+            sender
+                .output(LaneHeaderOutput::Export { whole: true })
+                .unwrap()
+        }
+    });
+
+    group.add_action(&threaded);
+    group.add_action(&export);
+    group.add_action(&export_thread);
+
+    let group = group.into_action_group();
+    // Only threads and profiles can be exported.
+    let enable = |name: &str, enabled: bool| {
+        if let Some(action) = group
+            .lookup_action(name)
+            .and_then(|a| a.downcast::<gtk::gio::SimpleAction>().ok())
+        {
+            action.set_enabled(enabled);
+        }
+    };
+    enable(
+        "export",
+        matches!(kind, LaneKind::Thread(_) | LaneKind::Profile(_)),
+    );
+    enable("export-thread", matches!(kind, LaneKind::Thread(_)));
+    group
+}
+
+/// Slider of minimum proof of work in the lane's menu.
+fn min_pow_slider(sender: &ComponentSender<LaneHeader>, min_pow: u8) -> gtk::Box {
+    let label = gtk::Label::new(Some("Minimum proof of work"));
+    label.set_xalign(0.0);
+
+    let scale = gtk::Scale::with_range(gtk::Orientation::Horizontal, 0.0, 32.0, 1.0);
+    scale.set_value(min_pow as f64);
+    scale.set_digits(0);
+    scale.set_draw_value(true);
+    scale.set_hexpand(true);
+    scale.connect_value_changed({
+        let sender = sender.clone();
+        move |s| sender.input(LaneHeaderInput::MinPow(s.value() as u8))
+    });
+
+    let slider = gtk::Box::new(gtk::Orientation::Vertical, 4);
+    slider.set_margin_start(12);
+    slider.set_margin_end(12);
+    slider.append(&label);
+    slider.append(&scale);
+    slider
+}
+
+/// Choice of the period of period lane in its menu, days in calendars
+/// and hours of them.
+fn period_picker(sender: &ComponentSender<LaneHeader>, period: Period) -> gtk::Box {
+    let (since_day, since_hour) = moment_picker(period.since);
+    let (until_day, until_hour) = moment_picker(period.until);
+
+    let grid = gtk::Grid::new();
+    grid.set_column_spacing(12);
+    grid.set_row_spacing(4);
+    for (column, (title, day, hour)) in [
+        ("From", &since_day, &since_hour),
+        ("To", &until_day, &until_hour),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let label = gtk::Label::new(Some(title));
+        label.set_xalign(0.0);
+        let hours = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        hours.append(&gtk::Label::new(Some("Hour")));
+        hours.append(hour);
+
+        grid.attach(&label, column as i32, 0, 1, 1);
+        grid.attach(day, column as i32, 1, 1, 1);
+        grid.attach(&hours, column as i32, 2, 1, 1);
+    }
+
+    let show = gtk::Button::with_label("Show period");
+    show.set_halign(gtk::Align::End);
+    show.connect_clicked({
+        let sender = sender.clone();
+        move |_| {
+            let since = moment(&since_day, &since_hour, false);
+            let until = moment(&until_day, &until_hour, true);
+            if let (Some(since), Some(until)) = (since, until) {
+                if since.as_i64() < until.as_i64() {
+                    sender
+                        .output(LaneHeaderOutput::Period(Period { since, until }))
+                        .unwrap();
+                }
+            }
+        }
+    });
+
+    let picker = gtk::Box::new(gtk::Orientation::Vertical, 8);
+    picker.set_margin_start(12);
+    picker.set_margin_end(12);
+    picker.append(&grid);
+    picker.append(&show);
+    picker
+}
+
+/// Calendar and spin button of hour showing local time of `time`.
+fn moment_picker(time: Timestamp) -> (gtk::Calendar, gtk::SpinButton) {
+    let day = gtk::Calendar::new();
+    let hour = gtk::SpinButton::with_range(0.0, 23.0, 1.0);
+    if let Ok(local) = gtk::glib::DateTime::from_unix_local(time.as_i64()) {
+        day.select_day(&local);
+        hour.set_value(local.hour() as f64);
+    }
+    (day, hour)
+}
+
+/// Beginning of the hour chosen by `day` and `hour`, or its last second
+/// if `end` is set.
+fn moment(day: &gtk::Calendar, hour: &gtk::SpinButton, end: bool) -> Option<Timestamp> {
+    let date = day.date();
+    let (minute, second) = if end { (59, 59.0) } else { (0, 0.0) };
+    let local = gtk::glib::DateTime::from_local(
+        date.year(),
+        date.month(),
+        date.day_of_month(),
+        hour.value_as_int(),
+        minute,
+        second,
+    )
+    .ok()?;
+    u64::try_from(local.to_unix()).ok().map(Timestamp::from)
 }
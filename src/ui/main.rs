@@ -1,47 +1,393 @@
-use std::path::PathBuf;
+use std::cell::RefCell;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Duration;
 
 use gtk::gdk;
 use gtk::prelude::*;
 use nostr_sdk::nostr::prelude::*;
 use relm4::component::*;
-use relm4::factory::AsyncFactoryVecDeque;
-use tracing::warn;
+use relm4::factory::{AsyncFactoryVecDeque, DynamicIndex};
+use tracing::{info, warn};
 
+use crate::config::Config;
+use crate::content::Target;
+use crate::download::Media;
+use crate::export::{ExportedNote, Format};
 use crate::follow::Follow;
+use crate::nostr::{Article, EventExt, Persona, Quote, RelayPolicy, ReportSubject};
+use crate::notifications::{Notification, NotificationKind};
+use crate::preview::LinkPreview;
+use crate::state::{State, WindowState};
+use crate::subscriptions::SubscriptionHandle;
+use crate::ui::archive::*;
+use crate::ui::article::*;
+use crate::ui::avatars::Avatars;
+use crate::ui::confirm::confirm;
+use crate::ui::contacts::*;
 use crate::ui::details::*;
 use crate::ui::editprofile::model::*;
+use crate::ui::emojis::Emojis;
+use crate::ui::import_follows::*;
 use crate::ui::lane::*;
+use crate::ui::open::*;
+use crate::ui::outbox::*;
+use crate::ui::pending::*;
+use crate::ui::prune::*;
+use crate::ui::relays::*;
+use crate::ui::report::*;
+use crate::ui::settings::*;
 use crate::ui::statusbar::*;
+use crate::ui::toast::*;
+use crate::ui::viewer::*;
 use crate::ui::writenote::model::*;
 use crate::Gnostique;
 
+/// Number of signal sent by Ctrl+C in terminal.
+const SIGINT: i32 = 2;
+
 pub struct Main {
     gnostique: Gnostique,
     lanes: AsyncFactoryVecDeque<Lane>,
+    /// Index of lane on which keyboard shortcuts act.
+    focused_lane: usize,
+    /// Whether Gnostique is ending.
+    quitting: bool,
+    /// Where lanes are scrolled and what they hide, remembered
+    /// when the window is closed.
+    state: Rc<RefCell<State>>,
     details: Controller<DetailsWindow>,
+    outbox: Controller<OutboxWindow>,
+    relays: Controller<RelaysWindow>,
+    viewer: Controller<ImageViewer>,
+    article: Controller<ArticleWindow>,
     status_bar: Controller<StatusBar>,
     write_note: Controller<WriteNote>,
     edit_profile: Controller<EditProfile>,
+    report: Controller<ReportDialog>,
+    import_follows: Controller<ImportFollowsDialog>,
+    open: Controller<OpenDialog>,
+    contacts: Controller<ContactsWindow>,
+    archive: Controller<ArchiveDialog>,
+    prune: Controller<PruneDialog>,
+    settings: Controller<SettingsDialog>,
+    /// Actions of main menu, some of them reflect configuration.
+    menu_actions: gtk::gio::SimpleActionGroup,
+    avatars: Avatars,
+    /// Decoded custom emoji (NIP-30), shared by all lanes.
+    emojis: Emojis,
+    /// Short messages displayed at the bottom of the window.
+    toasts: Controller<Toasts>,
+    /// Relay lists that user has already been offered to import.
+    offered_relay_lists: HashSet<EventId>,
+    /// Destructive actions which can still be undone.
+    pending: Pending,
+    /// Authors whose notes are shown in feed.
+    follow: Follow,
+    /// Notes in our bookmark list.
+    bookmarks: HashSet<EventId>,
+    /// Open subscriptions to relays by their owners, lanes by their keys.
+    subscriptions: HashMap<String, Vec<SubscriptionHandle>>,
 }
 
+/// Owner of subscription to notes of followed authors, which is open
+/// during the whole session, whether feed lane is open or not.
+const FEED_SUBSCRIPTION: &str = "session:feed";
+/// Owner of subscription to notes about us, they are announced even
+/// without notifications lane.
+const NOTIFICATIONS_SUBSCRIPTION: &str = "session:notifications";
+
 #[derive(Debug)]
 pub enum MainInput {
     Event(crate::stream::X),
     ShowDetail(Details),
+    /// Obtain fresh responses of relays to our event for the details window.
+    RefreshDeliveries(EventId),
     WriteNote,
     EditProfile,
+    /// Remove all downloaded files.
+    ClearCache,
+    /// Show events waiting for delivery to relays.
+    ShowOutbox,
+    /// Show relays and their status.
+    ShowRelays,
+    /// Let user choose where to save backup of the account.
+    ExportBackup,
+    /// Save backup of the account into the file.
+    SaveBackup(PathBuf),
+    /// Let user choose where to save notes of a lane, see [`LaneOutput::Export`].
+    Export {
+        title: String,
+        notes: Vec<(Arc<Event>, Option<Persona>)>,
+        thread: Option<EventId>,
+    },
+    /// Save the notes into the file.
+    SaveExport {
+        path: PathBuf,
+        format: Format,
+        title: String,
+        notes: Vec<(Arc<Event>, Option<Persona>)>,
+        thread: Option<EventId>,
+    },
+    /// Export has got this far, or it has ended.
+    ExportProgress(Option<String>),
+    /// Show dialog for exporting and importing events.
+    ShowArchive,
+    /// Show dialog for following users followed by another account.
+    ShowImportFollows,
+    /// Show dialog for opening a profile or a thread by its npub or note.
+    ShowOpen,
+    /// Users have been followed all at once.
+    FollowedMany(Vec<XOnlyPublicKey>),
+    /// Avatars at these URLs should be downloaded and displayed.
+    DownloadAvatars(Vec<(XOnlyPublicKey, Url)>),
+    /// Show users followed by the user, or their followers if `followers`.
+    ShowContacts {
+        pubkey: XOnlyPublicKey,
+        followers: bool,
+    },
+    /// Numbers of users the user follows, if their contact list is known,
+    /// and of their followers known to this client.
+    ContactCounts {
+        pubkey: XOnlyPublicKey,
+        following: Option<usize>,
+        followers: usize,
+    },
+    /// Fields of the user's profile which have changed recently.
+    ProfileChanges {
+        pubkey: XOnlyPublicKey,
+        fields: Vec<&'static str>,
+    },
+    /// Show dialog for retention of events in the database.
+    ShowPrune,
+    /// Show dialog for changing configuration.
+    ShowSettings,
+    /// Configuration has been changed in settings dialog.
+    SettingsSaved,
+    /// Prune old events, as it has not been done for a while.
+    PruneOverdue,
+    /// Show directory with log files in file manager.
+    OpenLogFolder,
+    /// Turn desktop notifications on or off.
+    SetDesktopNotifications(bool),
+    /// Show ages of notes relative to now, or as absolute times.
+    SetRelativeTime(bool),
+    /// Show absolute times with 24-hour clock, or with AM and PM.
+    SetClock24h(bool),
+    /// Time has passed, ages of notes should be refreshed.
+    Tick,
+    /// Stop or resume desktop notifications about events of the author.
+    MuteNotifications(XOnlyPublicKey, bool),
+    /// Hide or show again notes of the author.
+    Mute(XOnlyPublicKey, bool),
+    /// Ask user whether to delete our note.
+    ConfirmDelete(EventId),
+    /// Remove our note and publish its deletion, unless it is undone.
+    Delete(EventId),
+    /// Undo window of pending action has passed, it should be carried out.
+    CarryOut(u64),
+    /// Pending action should not be carried out after all.
+    Undo(u64),
+    /// Note whose deletion has been undone should be displayed again.
+    Restore(FoundNote),
+    /// Let user report a note or a user.
+    Report(ReportSubject),
+    /// Report has been published, `subject` should disappear if `hidden`.
+    Reported {
+        subject: ReportSubject,
+        hidden: bool,
+    },
+    /// Find stored text notes matching the text.
+    Search(String),
+    /// Text notes matching `query` have been found.
+    SearchResults {
+        query: String,
+        notes: Vec<FoundNote>,
+    },
+    /// Users matching `query` have been found.
+    PeopleResults {
+        query: String,
+        people: Vec<FoundPerson>,
+    },
+    /// Start following the author.
+    Follow(XOnlyPublicKey),
+    /// Add the note to bookmarks, or remove it from there.
+    Bookmark(EventId, bool),
+    /// Translate the note with this content.
+    Translate(EventId, String),
+    /// Translation of the note has arrived, or why it has not.
+    Translated {
+        event_id: EventId,
+        translation: Result<String, String>,
+    },
+    /// Note replied to by displayed notes should be found, or asked for
+    /// at our relays and these hinted ones.
+    NeedParent(EventId, Vec<Url>),
+    /// Stored note replied to by displayed notes has been found.
+    Parent(Quote),
+    /// Give the author the label, or remove it. Nothing is published.
+    Label {
+        author: XOnlyPublicKey,
+        label: String,
+        add: bool,
+    },
+    /// These are all labels of the author now.
+    Labels {
+        author: XOnlyPublicKey,
+        labels: Vec<String>,
+    },
+    /// Stored bookmarked notes have been loaded for bookmarks lane.
+    BookmarkedNotes(Vec<FoundNote>),
+    /// Open lane of notes of followed authors from the last day.
+    OpenPeriod,
+    /// Period lane wants notes created within the period.
+    LoadPeriod(Follow, Period),
+    /// Stored notes have been loaded for period lane.
+    PeriodNotes(Vec<FoundNote>),
+    /// Pin our note to our profile, or unpin it.
+    Pin(EventId, bool),
+    /// These are all notes pinned by `author` now.
+    Pins {
+        author: XOnlyPublicKey,
+        pinned: Vec<EventId>,
+    },
+    /// Stored pinned notes have been loaded for profile lanes.
+    PinnedNotes(Vec<FoundNote>),
+    /// Stored notifications have been loaded for notifications lane.
+    NotificationHistory(Vec<Notification>),
+    /// User has seen notification about the event.
+    NotificationRead(EventId),
+    /// User has seen all notifications.
+    AllNotificationsRead,
+    /// Use these relays, as user has agreed to import them.
+    ImportRelayList(Vec<RelayPolicy>),
+    /// Download images embedded in notes.
+    DownloadImages(Vec<Url>),
+    /// Image embedded in notes has been downloaded and decoded.
+    ImageBitmap {
+        url: Url,
+        bitmap: Arc<gdk::Texture>,
+    },
+    /// Obtain custom emoji used in notes and reactions.
+    DownloadEmojis(Vec<Url>),
+    /// Custom emoji was decoded off the main loop, or it failed when `bitmap` is `None`.
+    EmojiDecoded {
+        url: Url,
+        bitmap: Option<Arc<gdk::Texture>>,
+    },
+    /// Show image from `url` in full size.
+    OpenImage(Url),
+    /// Show a short message at the bottom of the window.
+    Toast(Toast),
+    /// Text note has expired and should not be displayed anymore.
+    Expired(EventId),
+    /// Our event has been accepted by a relay for the first time.
+    Delivered(EventId),
+    /// Add a new lane at the end, or focus the lane if it is already open.
+    OpenLane(LaneKind),
+    /// Open profile lane of the user, asking also relays hinted for them.
+    OpenProfile(XOnlyPublicKey, Option<Vec<Url>>),
+    /// Open thread lane of the note, asking also relays hinted for it.
+    OpenThread(EventId, Option<Vec<Url>>),
+    /// Search for notes with the hashtag.
+    OpenHashtag(String),
+    /// Obtain preview of web page linked from notes.
+    NeedPreview(Url),
+    /// Preview of web page linked from notes has been obtained.
+    LinkPreview(Arc<LinkPreview>),
+    /// Image to be shown in full size is ready.
+    ShowImage(ViewedImage),
     UpdateProfile(Metadata),
-    Send(String),
+    Send {
+        content: String,
+        tags: Vec<Tag>,
+    },
+    /// Publish an already signed event.
+    Publish(Box<Event>),
+    /// Write a reply to the event.
+    Reply(Arc<Event>),
+    /// Write a note quoting the event, which has been seen at the relays.
+    Quote(Arc<Event>, Vec<Url>),
+    /// Show the whole article of the author.
+    OpenArticle(Box<Article>, Box<Persona>),
     Noop,
+    /// User has clicked into the lane.
+    LaneFocused(DynamicIndex),
+    /// Let keyboard shortcuts act on lane next to the focused one,
+    /// the one to the right if `forward`.
+    FocusLane {
+        forward: bool,
+    },
+    /// Close the focused lane.
+    CloseLane,
+    /// Subscriptions to relays made for `owner`, which replace its
+    /// previous ones.
+    Subscribed {
+        owner: String,
+        handles: Vec<SubscriptionHandle>,
+    },
+    /// Lane has been scrolled to the note, or to the top if none.
+    LaneScrolled {
+        key: String,
+        anchor: Option<EventId>,
+    },
+    /// Lane hides notes according to the filter now.
+    LaneFilter {
+        key: String,
+        filter: LaneFilter,
+    },
+    /// Lane displays replies below notes they reply to, or not.
+    LaneThreaded {
+        key: String,
+        threaded: bool,
+    },
+    /// Notes of lane created until the time have been read.
+    LaneRead {
+        key: String,
+        until: Timestamp,
+    },
+    /// Let user type into search lane, which is opened unless it already is.
+    FocusSearch,
+    /// Select note next to the selected one in the focused lane.
+    SelectNote {
+        forward: bool,
+    },
+    /// Reply to the selected note of the focused lane.
+    ReplySelected,
+    /// Show window listing keyboard shortcuts.
+    ShowShortcuts,
+    /// Quit Gnostique, also if it keeps running without window.
+    Quit,
+    /// Connections are closed, Gnostique can end.
+    Exit,
     MetadataBitmap {
         pubkey: XOnlyPublicKey,
         url: Url,
         file: PathBuf,
     },
+    /// Avatar image was decoded off the main loop, or it failed when `bitmap` is `None`.
+    AvatarDecoded {
+        pubkey: XOnlyPublicKey,
+        url: Url,
+        bitmap: Option<Arc<gdk::Texture>>,
+    },
     Nip05Verified(XOnlyPublicKey),
 }
 
+impl From<Target> for MainInput {
+    fn from(target: Target) -> Self {
+        let hints = |relays: Vec<Url>| Some(relays).filter(|r| !r.is_empty());
+        match target {
+            Target::Profile(pubkey, relays) => MainInput::OpenProfile(pubkey, hints(relays)),
+            Target::Note(id, relays) => MainInput::OpenThread(id, hints(relays)),
+            Target::Hashtag(tag) => MainInput::OpenHashtag(tag),
+        }
+    }
+}
+
 #[relm4::component(pub async)]
 impl AsyncComponent for Main {
     type Init = Gnostique;
@@ -51,17 +397,23 @@ impl AsyncComponent for Main {
 
     #[rustfmt::skip]
     view! {
-        gtk::Box {
-            set_orientation: gtk::Orientation::Vertical,
+        gtk::Overlay {
+            #[wrap(Some)]
+            set_child = &gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
 
-            #[local_ref]
-            lanes_box -> gtk::Box {
-                set_orientation: gtk::Orientation::Horizontal,
-                set_vexpand: true,
+                #[local_ref]
+                lanes_box -> gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_vexpand: true,
+                },
+
+                #[local_ref]
+                status_bar -> gtk::Box { }
             },
 
             #[local_ref]
-            status_bar -> gtk::Box { }
+            add_overlay = toasts -> gtk::Revealer { }
         }
     }
 
@@ -70,39 +422,187 @@ impl AsyncComponent for Main {
         root: Self::Root,
         sender: AsyncComponentSender<Self>,
     ) -> AsyncComponentParts<Self> {
-        // relm4::spawn(crate::app::task::refresh_relay_information(
-        //     gnostique.clone(),
-        // ));
+        relm4::spawn(crate::app::task::refresh_relay_information(
+            gnostique.clone(),
+        ));
 
         relm4::spawn(crate::app::task::receive_events(
             gnostique.clone(),
             sender.clone(),
         ));
 
+        relm4::spawn(crate::app::task::purge_expired(
+            gnostique.clone(),
+            sender.clone(),
+        ));
+
+        relm4::spawn(crate::app::task::tick(sender.clone()));
+
+        relm4::spawn(crate::app::task::prune_when_overdue(
+            gnostique.clone(),
+            sender.clone(),
+        ));
+
+        let mut follow = Follow::new();
+        for pubkey in gnostique.follows().await {
+            follow.add(pubkey);
+        }
+
+        let bookmarks = gnostique.bookmarks().await.into_iter().collect();
+
+        relm4::spawn({
+            let gnostique = gnostique.clone();
+            let follow = follow.clone();
+            let sender = sender.clone();
+            async move {
+                gnostique.apply_relay_policies().await;
+                let handles =
+                    request_lane_content(gnostique.clone(), LaneKind::Feed(follow), Vec::new())
+                        .await;
+                sender.input(MainInput::Subscribed {
+                    owner: FEED_SUBSCRIPTION.to_string(),
+                    handles,
+                });
+                let handles =
+                    request_lane_content(gnostique, LaneKind::Notifications, Vec::new()).await;
+                sender.input(MainInput::Subscribed {
+                    owner: NOTIFICATIONS_SUBSCRIPTION.to_string(),
+                    handles,
+                });
+            }
+        });
+
+        crate::app::action::register_app_actions(sender.clone());
+
+        relm4::spawn(crate::app::task::acknowledge_published(
+            gnostique.clone(),
+            sender.clone(),
+        ));
+        relm4::spawn(crate::app::task::retry_outbox(gnostique.clone()));
+        relm4::spawn(crate::app::task::flush_relay_stats(gnostique.clone()));
+
         let mut model = Main {
             gnostique: gnostique.clone(),
             lanes: AsyncFactoryVecDeque::new(gtk::Box::default(), sender.input_sender()),
-            details: DetailsWindow::builder().launch(()).detach(),
-            status_bar: StatusBar::builder().launch(gnostique).detach(),
+            focused_lane: 0,
+            quitting: false,
+            state: Rc::new(RefCell::new(State::read(&gnostique.dirs().state_file()))),
+            details: DetailsWindow::builder()
+                .launch(())
+                .forward(sender.input_sender(), |output| match output {
+                    DetailsWindowOutput::RefreshDeliveries(id) => MainInput::RefreshDeliveries(id),
+                }),
+            outbox: OutboxWindow::builder().launch(gnostique.clone()).detach(),
+            relays: RelaysWindow::builder().launch(gnostique.clone()).forward(
+                sender.input_sender(),
+                |output| match output {
+                    RelaysWindowOutput::Toast(toast) => MainInput::Toast(toast),
+                },
+            ),
+            viewer: ImageViewer::builder().launch(()).detach(),
+            article: ArticleWindow::builder()
+                .launch(())
+                .forward(sender.input_sender(), |output| match output {
+                    ArticleWindowOutput::NeedImages(urls) => MainInput::DownloadImages(urls),
+                    ArticleWindowOutput::Open(target) => MainInput::from(target),
+                }),
+            status_bar: StatusBar::builder().launch(gnostique.clone()).detach(),
             edit_profile: EditProfile::builder()
                 .launch(())
                 .forward(sender.input_sender(), forward_edit_profile),
-            write_note: WriteNote::builder()
-                .launch(())
-                .forward(sender.input_sender(), |result| match result {
-                    WriteNoteResult::Send(c) => MainInput::Send(c),
+            report: ReportDialog::builder().launch(gnostique.clone()).forward(
+                sender.input_sender(),
+                |output| match output {
+                    ReportDialogOutput::Reported { subject, hidden } => {
+                        MainInput::Reported { subject, hidden }
+                    }
+                },
+            ),
+            archive: ArchiveDialog::builder().launch(gnostique.clone()).detach(),
+            import_follows: ImportFollowsDialog::builder()
+                .launch(gnostique.clone())
+                .forward(sender.input_sender(), |output| match output {
+                    ImportFollowsOutput::Followed(pubkeys) => MainInput::FollowedMany(pubkeys),
+                    ImportFollowsOutput::NeedAvatars(avatars) => {
+                        MainInput::DownloadAvatars(avatars)
+                    }
+                }),
+            open: OpenDialog::builder()
+                .launch(gnostique.clone())
+                .forward(sender.input_sender(), |OpenOutput::Open(target)| {
+                    MainInput::from(target)
+                }),
+            contacts: ContactsWindow::builder().launch(gnostique.clone()).forward(
+                sender.input_sender(),
+                |output| match output {
+                    ContactsOutput::Open(pubkey) => MainInput::OpenProfile(pubkey, None),
+                    ContactsOutput::NeedAvatars(avatars) => MainInput::DownloadAvatars(avatars),
+                },
+            ),
+            prune: PruneDialog::builder().launch(gnostique.clone()).detach(),
+            settings: SettingsDialog::builder().launch(gnostique.clone()).forward(
+                sender.input_sender(),
+                |output| match output {
+                    SettingsDialogOutput::Saved => MainInput::SettingsSaved,
+                },
+            ),
+            menu_actions: crate::app::action::make_main_menu_actions(
+                sender.clone(),
+                &gnostique.config(),
+            ),
+            write_note: WriteNote::builder().launch(gnostique.clone()).forward(
+                sender.input_sender(),
+                |result| match result {
+                    WriteNoteResult::Send { content, tags } => MainInput::Send { content, tags },
+                    WriteNoteResult::Publish(event) => MainInput::Publish(event),
+                    WriteNoteResult::Toast(toast) => MainInput::Toast(toast),
                     _ => MainInput::Noop,
+                },
+            ),
+            avatars: Avatars::new(1000),
+            emojis: Emojis::new(500),
+            toasts: Toasts::builder()
+                .launch(())
+                .forward(sender.input_sender(), |output| match output {
+                    ToastsOutput::Undo(id) => MainInput::Undo(id),
                 }),
+            offered_relay_lists: HashSet::new(),
+            pending: Pending::default(),
+            follow,
+            bookmarks,
+            subscriptions: HashMap::new(),
         };
 
         let lanes_box = model.lanes.widget();
         let status_bar = model.status_bar.widget();
+        let toasts = model.toasts.widget();
         let widgets = view_output!();
 
         {
+            let feed = LaneKind::Feed(model.follow.clone());
+            let (anchor, filter, threaded, last_read) = (
+                model.anchor(&feed),
+                model.filter(&feed),
+                model.threaded(&feed),
+                model.last_read(&feed),
+            );
             let mut guard = model.lanes.guard();
 
-            guard.push_back(LaneKind::Feed(Follow::new()));
+            guard.push_back(LaneInit {
+                kind: feed,
+                me: gnostique.client().keys().public_key(),
+                bookmarks: model.bookmarks.clone(),
+                time_format: gnostique.time_format(),
+                collapse: gnostique.collapse(),
+                languages: gnostique.languages(),
+                translatable: gnostique.translations().is_enabled(),
+                focused: true,
+                anchor,
+                filter,
+                follow: model.follow.clone(),
+                threaded,
+                last_read,
+            });
 
             // guard.push_back(LaneKind::Profile(
             //     "febbaba219357c6c64adfa2e01789f274aa60e90c289938bfc80dd91facb2899"
@@ -116,14 +616,25 @@ impl AsyncComponent for Main {
             // ));
         }
 
-        // widgets
-        //     .window
-        //     .insert_action_group("author", Some(&crate::app::action::make_author_actions()));
+        root.insert_action_group("main", Some(&model.menu_actions));
+        crate::ui::shortcuts::add_shortcuts(&root, sender.clone());
+        on_window_close(
+            &root,
+            gnostique.clone(),
+            model.state.clone(),
+            sender.clone(),
+        );
+        shutdown_on_quit(gnostique.clone());
+
+        if gnostique.is_debug() {
+            sender.input(MainInput::OpenLane(LaneKind::Firehose));
+        }
 
-        // widgets.window.insert_action_group(
-        //     "main",
-        //     Some(&crate::app::action::make_main_menu_actions(sender)),
-        // );
+        // Ctrl+C in terminal quits like from the menu, again without waiting.
+        gtk::glib::unix_signal_add_local(SIGINT, move || {
+            sender.input(MainInput::Quit);
+            gtk::glib::Continue(true)
+        });
 
         AsyncComponentParts { model, widgets }
     }
@@ -133,7 +644,7 @@ impl AsyncComponent for Main {
         widgets: &mut Self::Widgets,
         msg: Self::Input,
         sender: AsyncComponentSender<Self>,
-        _root: &Self::Root,
+        root: &Self::Root,
     ) {
         match msg {
             MainInput::Event(crate::stream::X::TextNote {
@@ -142,6 +653,13 @@ impl AsyncComponent for Main {
                 author,
                 avatar,
                 repost,
+                mentions,
+                quotes,
+                flooding,
+                reactions,
+                language,
+                labels,
+                pending,
             }) => {
                 let pubkey = event.pubkey;
                 let url = author.as_ref().and_then(|a| a.avatar.as_ref()).cloned();
@@ -151,28 +669,80 @@ impl AsyncComponent for Main {
                     relays,
                     author,
                     repost,
+                    mentions,
+                    quotes,
+                    flooding,
+                    reactions,
+                    language,
+                    labels,
+                    pending,
                 });
 
-                if let Some(ref file) = avatar {
-                    match gdk::Texture::from_filename(file) {
-                        Ok(bitmap) => {
-                            self.lanes.broadcast(LaneMsg::MetadataBitmap {
-                                pubkey,
-                                url: url.unwrap(),
-                                bitmap: Arc::new(bitmap),
-                            });
-                        }
-                        Err(e) => {
-                            warn!("Could not load '{:?}': {}", file, e);
-                        }
-                    }
+                if let (Some(file), Some(url)) = (avatar, url) {
+                    self.show_avatar(pubkey, url, file, &sender);
+                }
+            }
+
+            MainInput::Event(crate::stream::X::Article {
+                article,
+                relays,
+                author,
+                avatar,
+            }) => {
+                let pubkey = article.event.pubkey;
+                let url = author.as_ref().and_then(|a| a.avatar.as_ref()).cloned();
+
+                self.lanes.broadcast(LaneMsg::Article {
+                    article: Arc::from(article),
+                    relays,
+                    author,
+                });
+
+                if let (Some(file), Some(url)) = (avatar, url) {
+                    self.show_avatar(pubkey, url, file, &sender);
+                }
+            }
+
+            MainInput::Event(crate::stream::X::Unsupported {
+                event,
+                relays,
+                author,
+                avatar,
+                handler,
+            }) => {
+                let pubkey = event.pubkey;
+                let url = author.as_ref().and_then(|a| a.avatar.as_ref()).cloned();
+
+                self.lanes.broadcast(LaneMsg::Unsupported {
+                    event: Arc::new(event),
+                    relays,
+                    author,
+                    handler,
+                });
+
+                if let (Some(file), Some(url)) = (avatar, url) {
+                    self.show_avatar(pubkey, url, file, &sender);
                 }
             }
 
-            MainInput::Event(crate::stream::X::Reaction { event_id, content }) => {
-                self.lanes.broadcast(LaneMsg::Reaction {
+            MainInput::Event(crate::stream::X::Handlers(handlers)) => {
+                self.lanes.broadcast(LaneMsg::Handlers(handlers))
+            }
+
+            MainInput::Event(crate::stream::X::Reaction {
+                event_id,
+                content,
+                emoji,
+            }) => self.lanes.broadcast(LaneMsg::Reaction {
+                event: event_id,
+                reaction: content,
+                emoji,
+            }),
+
+            MainInput::Event(crate::stream::X::Zap { event_id, sats }) => {
+                self.lanes.broadcast(LaneMsg::Zap {
                     event: event_id,
-                    reaction: content,
+                    sats,
                 })
             }
 
@@ -180,78 +750,2099 @@ impl AsyncComponent for Main {
                 let url = persona.avatar.clone();
                 let pubkey = persona.pubkey;
 
+                // Picture may have changed, the cached one would be stale then.
+                self.avatars.invalidate(&pubkey, url.as_ref());
+
+                self.import_follows
+                    .emit(ImportFollowsInput::Metadata(Box::new(persona.clone())));
+                self.contacts
+                    .emit(ContactsInput::Metadata(Box::new(persona.clone())));
+
+                let profile_open = self.lanes.iter().any(|lane| {
+                    lane.map(|l| l.kind().is_profile(&pubkey))
+                        .unwrap_or_default()
+                });
+                if profile_open {
+                    relm4::spawn(load_profile_changes(
+                        self.gnostique.clone(),
+                        pubkey,
+                        sender.clone(),
+                    ));
+                }
+
                 self.lanes
                     .broadcast(LaneMsg::UpdatedProfile { author: persona });
 
-                if let Some(ref file) = avatar {
-                    match gdk::Texture::from_filename(file) {
-                        Ok(bitmap) => {
-                            self.lanes.broadcast(LaneMsg::MetadataBitmap {
-                                pubkey,
-                                url: url.unwrap(),
-                                bitmap: Arc::new(bitmap),
-                            });
+                if let (Some(file), Some(url)) = (avatar, url) {
+                    self.show_avatar(pubkey, url, file, &sender);
+                }
+            }
+
+            MainInput::Event(crate::stream::X::SeenOn { event_id, relay }) => {
+                self.lanes.broadcast(LaneMsg::SeenOn {
+                    event: event_id,
+                    relay,
+                })
+            }
+
+            MainInput::Event(crate::stream::X::AvatarAvailable { pubkey, url, file }) => {
+                self.show_avatar(pubkey, url, file, &sender)
+            }
+
+            MainInput::Event(crate::stream::X::RelayList { event_id, relays }) => {
+                if self.offered_relay_lists.insert(event_id) {
+                    offer_relay_list(root, relays, sender.clone());
+                }
+            }
+
+            MainInput::Event(crate::stream::X::Notification {
+                notification,
+                event,
+            }) => {
+                let active = root
+                    .root()
+                    .and_then(|r| r.downcast::<gtk::Window>().ok())
+                    .map(|w| w.is_active())
+                    .unwrap_or(false);
+
+                // Only notes are worth interrupting user.
+                let noteworthy = matches!(
+                    notification.kind,
+                    NotificationKind::Mention | NotificationKind::Reply
+                );
+
+                if noteworthy
+                    && !active
+                    && self
+                        .gnostique
+                        .notifications()
+                        .should_notify(notification.author)
+                        .await
+                {
+                    show_notification(&notification, &event);
+                }
+
+                self.lanes.broadcast(LaneMsg::Notification(notification));
+            }
+
+            MainInput::Mute(pubkey, true) => {
+                self.lanes.broadcast(LaneMsg::Muted(pubkey));
+                let id = self.pending.add(PendingAction::Mute(pubkey));
+                self.put_off(id, Toast::undoable("Author muted", id), &sender);
+            }
+
+            MainInput::Mute(pubkey, false) => {
+                // Muting that has not been published yet is just dropped.
+                match self.pending.find(&PendingAction::Mute(pubkey)) {
+                    Some(id) => {
+                        self.pending.take(id);
+                        sender.input(MainInput::Toast(Toast::info("Author unmuted")));
+                    }
+                    None => {
+                        let gnostique = self.gnostique.clone();
+                        let sender = sender.clone();
+                        relm4::spawn(async move {
+                            match gnostique.set_muted(pubkey, false).await {
+                                Ok(()) => {
+                                    sender.input(MainInput::Toast(Toast::info("Author unmuted")))
+                                }
+                                Err(e) => warn!("Could not change muting of {}: {}", pubkey, e),
+                            }
+                        });
+                    }
+                }
+            }
+
+            MainInput::ConfirmDelete(event_id) => {
+                let sender = sender.clone();
+                confirm(
+                    root,
+                    "Delete note?",
+                    "Relays are asked to delete the note, though some of them may keep it.",
+                    "Delete",
+                    move || sender.input(MainInput::Delete(event_id)),
+                );
+            }
+
+            MainInput::Delete(event_id) => {
+                self.lanes.broadcast(LaneMsg::Deleted(event_id));
+                let id = self.pending.add(PendingAction::Delete(event_id));
+                self.put_off(id, Toast::undoable("Note deleted", id), &sender);
+            }
+
+            // Actions which have been undone are not there anymore.
+            MainInput::CarryOut(id) => {
+                if let Some(action) = self.pending.take(id) {
+                    relm4::spawn(carry_out(self.gnostique.clone(), action, sender.clone()));
+                }
+            }
+
+            // Actions which have been carried out are not there anymore.
+            MainInput::Undo(id) => match self.pending.take(id) {
+                Some(PendingAction::Mute(_)) => {
+                    sender.input(MainInput::Toast(Toast::info("Muting undone")))
+                }
+                Some(PendingAction::Delete(event_id)) => {
+                    relm4::spawn(restore_note(
+                        self.gnostique.clone(),
+                        event_id,
+                        sender.clone(),
+                    ));
+                }
+                None => {}
+            },
+
+            MainInput::Restore(found) => self.lanes.broadcast(LaneMsg::NewTextNote {
+                event: found.event,
+                relays: found.relays,
+                author: found.author,
+                repost: None,
+                mentions: vec![],
+                quotes: vec![],
+                flooding: false,
+                reactions: found.reactions,
+                language: None,
+                labels: vec![],
+                pending: false,
+            }),
+
+            MainInput::Report(subject) => self.report.emit(ReportDialogInput::Show(subject)),
+
+            MainInput::Reported { subject, hidden } => {
+                if hidden {
+                    self.lanes.broadcast(LaneMsg::Reported(subject));
+                }
+                let author = match subject {
+                    ReportSubject::Note { author, .. } | ReportSubject::User(author) => author,
+                };
+                relm4::spawn(load_labels(self.gnostique.clone(), author, sender.clone()));
+                sender.input(MainInput::Toast(Toast::success("Reported")));
+            }
+
+            MainInput::Search(query) => {
+                relm4::spawn(search_relays(self.gnostique.clone(), query.clone()));
+                relm4::spawn(search_people(
+                    self.gnostique.clone(),
+                    self.follow.clone(),
+                    query.clone(),
+                    sender.clone(),
+                ));
+                relm4::spawn(search_notes(self.gnostique.clone(), query, sender.clone()));
+            }
+
+            MainInput::SearchResults { query, notes } => self
+                .lanes
+                .broadcast(LaneMsg::SearchResults { query, notes }),
+
+            MainInput::PeopleResults { query, people } => self
+                .lanes
+                .broadcast(LaneMsg::PeopleResults { query, people }),
+
+            MainInput::Follow(pubkey) => {
+                if self.follow.add(pubkey) {
+                    self.lanes.broadcast(LaneMsg::Followed(pubkey));
+
+                    let gnostique = self.gnostique.clone();
+                    let follow = self.follow.clone();
+                    let sender = sender.clone();
+                    relm4::spawn(async move {
+                        match gnostique.follow(pubkey).await {
+                            Ok(()) => {
+                                // Recent notes of the author show up in feed,
+                                // and new ones keep coming.
+                                gnostique
+                                    .client()
+                                    .req_events_of(
+                                        vec![Follow::subscriptions_of(vec![pubkey])],
+                                        None,
+                                    )
+                                    .await;
+                                let handles = request_lane_content(
+                                    gnostique,
+                                    LaneKind::Feed(follow),
+                                    Vec::new(),
+                                )
+                                .await;
+                                sender.input(MainInput::Subscribed {
+                                    owner: FEED_SUBSCRIPTION.to_string(),
+                                    handles,
+                                });
+                                sender.input(MainInput::Toast(Toast::success("Following")));
+                            }
+                            Err(e) => warn!("Could not follow {}: {}", pubkey, e),
+                        }
+                    });
+                }
+            }
+
+            MainInput::Translate(event_id, content) => {
+                let gnostique = self.gnostique.clone();
+                let sender = sender.clone();
+                relm4::spawn(async move {
+                    let translation = gnostique.translations().translate(event_id, &content).await;
+                    if let Err(e) = &translation {
+                        warn!("Could not translate {}: {}", event_id, e);
+                    }
+                    sender.input(MainInput::Translated {
+                        event_id,
+                        translation,
+                    });
+                });
+            }
+
+            MainInput::Translated {
+                event_id,
+                translation,
+            } => self.lanes.broadcast(LaneMsg::Translated {
+                event_id,
+                translation,
+            }),
+
+            MainInput::NeedParent(event_id, relays) => {
+                relm4::spawn(find_parent(
+                    self.gnostique.clone(),
+                    event_id,
+                    relays,
+                    sender.clone(),
+                ));
+            }
+
+            MainInput::Parent(quote) => self.lanes.broadcast(LaneMsg::Parent(quote)),
+
+            MainInput::Label { author, label, add } => {
+                let gnostique = self.gnostique.clone();
+                let sender = sender.clone();
+                relm4::spawn(async move {
+                    let labelled = if add {
+                        gnostique.labels().add(author, &label).await
+                    } else {
+                        gnostique.labels().remove(author, &label).await
+                    };
+                    if let Err(e) = labelled {
+                        warn!("Could not change label {} of {}: {}", label, author, e);
+                        sender.input(MainInput::Toast(Toast::error(format!(
+                            "Could not change label: {e}"
+                        ))));
+                    }
+                    load_labels(gnostique, author, sender).await;
+                });
+            }
+
+            MainInput::Labels { author, labels } => {
+                self.lanes.broadcast(LaneMsg::Labels { author, labels })
+            }
+
+            MainInput::Bookmark(id, bookmarked) => {
+                self.bookmarked(id, bookmarked, &sender);
+
+                let gnostique = self.gnostique.clone();
+                let sender = sender.clone();
+                relm4::spawn(async move {
+                    match gnostique.set_bookmarked(id, bookmarked).await {
+                        Ok(_) if bookmarked => {
+                            sender.input(MainInput::Toast(Toast::success("Bookmarked")))
                         }
+                        Ok(_) => sender.input(MainInput::Toast(Toast::info("Bookmark removed"))),
                         Err(e) => {
-                            warn!("Could not load '{:?}': {}", file, e);
+                            warn!("Could not change bookmark of {}: {}", id, e);
+                            sender.input(MainInput::Toast(Toast::error(format!(
+                                "Could not publish bookmarks: {e}"
+                            ))));
                         }
                     }
+                });
+            }
+
+            MainInput::Event(crate::stream::X::Muted(pubkeys)) => {
+                for pubkey in pubkeys {
+                    self.lanes.broadcast(LaneMsg::Muted(pubkey));
                 }
             }
 
-            MainInput::WriteNote => self.write_note.emit(WriteNoteInput::Show),
+            MainInput::Event(crate::stream::X::ContactList { author, following }) => {
+                // Open profiles of the author and of those they follow
+                // count differently now.
+                let counted: Vec<XOnlyPublicKey> = self
+                    .lanes
+                    .iter()
+                    .filter_map(|lane| match lane.map(|l| l.kind()) {
+                        Some(LaneKind::Profile(pubkey))
+                            if *pubkey == author || following.contains(pubkey) =>
+                        {
+                            Some(*pubkey)
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                for pubkey in counted {
+                    relm4::spawn(count_contacts(
+                        self.gnostique.clone(),
+                        pubkey,
+                        sender.clone(),
+                    ));
+                }
+            }
 
-            MainInput::Noop => {}
+            MainInput::Event(crate::stream::X::Raw { relay, event }) => {
+                self.lanes.broadcast(LaneMsg::Raw {
+                    relay,
+                    event: Arc::new(event),
+                })
+            }
 
-            MainInput::EditProfile => self.edit_profile.emit(EditProfileInput::Show),
+            MainInput::Event(crate::stream::X::Error { message }) => {
+                warn!("Persistence is broken: {}", message);
+                sender.input(MainInput::Toast(Toast::error(
+                    "Events cannot be stored in the database",
+                )));
+            }
 
-            MainInput::UpdateProfile(metadata) => {
-                let client = self.gnostique.client().clone();
-                relm4::spawn(async move { client.set_metadata(metadata).await })
-                    .await
-                    .unwrap()
-                    .unwrap();
+            MainInput::Event(crate::stream::X::Bookmarks(ids)) => {
+                let ids: HashSet<EventId> = ids.into_iter().collect();
+                let removed: Vec<EventId> = self.bookmarks.difference(&ids).copied().collect();
+                let added: Vec<EventId> = ids.difference(&self.bookmarks).copied().collect();
+
+                for id in removed {
+                    self.bookmarked(id, false, &sender);
+                }
+                for id in added {
+                    self.bookmarked(id, true, &sender);
+                }
             }
 
-            MainInput::Send(c) => {
-                let client = self.gnostique.client().clone();
-                relm4::spawn(async move {
-                    client
-                        .publish_text_note(
-                            c,
-                            &[Tag::Generic(
-                                TagKind::Custom("client".to_string()),
-                                vec!["Gnostique".to_string()],
-                            )],
-                        )
-                        .await
-                })
-                .await
-                .unwrap()
-                .unwrap();
+            MainInput::BookmarkedNotes(notes) => {
+                self.lanes.broadcast(LaneMsg::BookmarkedNotes(notes))
             }
 
-            MainInput::ShowDetail(details) => self.details.emit(DetailsWindowInput::Show(details)),
+            MainInput::OpenPeriod => self.open_lane(
+                LaneKind::Period(self.follow.clone(), Period::last_day()),
+                None,
+                &sender,
+            ),
 
-            MainInput::Nip05Verified(nip05) => self.lanes.broadcast(LaneMsg::Nip05Verified(nip05)),
+            MainInput::LoadPeriod(follow, period) => {
+                relm4::spawn(request_period(
+                    self.gnostique.clone(),
+                    follow,
+                    period,
+                    sender.clone(),
+                ));
+            }
 
-            MainInput::MetadataBitmap { pubkey, url, file } => {
-                match gdk::Texture::from_filename(&file) {
-                    Ok(bitmap) => {
-                        self.lanes.broadcast(LaneMsg::MetadataBitmap {
-                            pubkey,
-                            url,
-                            bitmap: Arc::new(bitmap),
-                        });
-                    }
-                    Err(e) => {
-                        warn!("Could not load '{:?}': {}", file, e);
+            MainInput::PeriodNotes(notes) => self.lanes.broadcast(LaneMsg::PeriodNotes(notes)),
+
+            MainInput::Pin(id, pinned) => {
+                let gnostique = self.gnostique.clone();
+                let sender = sender.clone();
+                relm4::spawn(async move {
+                    match gnostique.set_pinned(id, pinned).await {
+                        Ok(pins) => {
+                            sender.input(MainInput::Pins {
+                                author: gnostique.client().keys().public_key(),
+                                pinned: pins,
+                            });
+                            sender.input(MainInput::Toast(if pinned {
+                                Toast::success("Pinned to profile")
+                            } else {
+                                Toast::info("Unpinned")
+                            }));
+                        }
+                        Err(e) => {
+                            warn!("Could not change pin of {}: {}", id, e);
+                            sender.input(MainInput::Toast(Toast::error(format!(
+                                "Could not publish pins: {e}"
+                            ))));
+                        }
                     }
+                });
+            }
+
+            MainInput::Pins { author, pinned } => self.show_pins(author, pinned, &sender),
+
+            MainInput::Event(crate::stream::X::Pins { author, pinned }) => {
+                self.show_pins(author, pinned, &sender)
+            }
+
+            MainInput::PinnedNotes(notes) => self.lanes.broadcast(LaneMsg::PinnedNotes(notes)),
+
+            MainInput::NotificationHistory(history) => {
+                self.lanes.broadcast(LaneMsg::NotificationHistory(history))
+            }
+
+            MainInput::NotificationRead(id) => {
+                let gnostique = self.gnostique.clone();
+                relm4::spawn(async move { gnostique.notifications().mark_read(id).await });
+            }
+
+            MainInput::AllNotificationsRead => {
+                let gnostique = self.gnostique.clone();
+                relm4::spawn(async move { gnostique.notifications().mark_all_read().await });
+            }
+
+            MainInput::SetDesktopNotifications(enabled) => {
+                self.change_config(&sender, |c| c.general.desktop_notifications = enabled)
+            }
+
+            MainInput::SetRelativeTime(relative) => {
+                self.change_config(&sender, |c| c.time.relative = relative);
+                sender.input(MainInput::Tick);
+            }
+
+            MainInput::SetClock24h(clock_24h) => {
+                self.change_config(&sender, |c| c.time.clock_24h = clock_24h);
+                sender.input(MainInput::Tick);
+            }
+
+            MainInput::Tick => {
+                self.lanes
+                    .broadcast(LaneMsg::Tick(self.gnostique.time_format()));
+                let stats = self.gnostique.kind_stats();
+                if stats.is_firehose() {
+                    self.lanes.broadcast(LaneMsg::KindStats {
+                        counts: stats.counts(),
+                        skipped: stats.skipped(),
+                        stream: self.gnostique.stream_metrics(),
+                        subscriptions: self.gnostique.subscriptions().list(),
+                    });
                 }
             }
-        };
 
-        self.update_view(widgets, sender);
+            MainInput::MuteNotifications(pubkey, muted) => {
+                let gnostique = self.gnostique.clone();
+                let sender = sender.clone();
+                relm4::spawn(async move {
+                    match gnostique.notifications().set_muted(pubkey, muted).await {
+                        Ok(()) if muted => {
+                            sender.input(MainInput::Toast(Toast::info("Notifications muted")))
+                        }
+                        Ok(()) => {
+                            sender.input(MainInput::Toast(Toast::info("Notifications unmuted")))
+                        }
+                        Err(e) => warn!("Could not change muting of {}: {}", pubkey, e),
+                    }
+                });
+            }
+
+            MainInput::ImportRelayList(relays) => {
+                let gnostique = self.gnostique.clone();
+                let sender = sender.clone();
+                relm4::spawn(async move {
+                    match gnostique.import_relay_list(relays).await {
+                        Ok(()) => sender.input(MainInput::Toast(Toast::success("Relays imported"))),
+                        Err(e) => {
+                            warn!("Could not import relay list: {}", e);
+                            sender.input(MainInput::Toast(Toast::error(format!(
+                                "Could not import relays: {e}"
+                            ))));
+                        }
+                    }
+                });
+            }
+
+            MainInput::WriteNote => self.write_note.emit(WriteNoteInput::Show),
+
+            MainInput::Reply(event) => self.write_note.emit(WriteNoteInput::Reply(event)),
+
+            MainInput::Quote(event, relays) => {
+                self.write_note.emit(WriteNoteInput::Quote(event, relays))
+            }
+
+            MainInput::Noop => {}
+
+            MainInput::LaneFocused(index) => {
+                let index = index.current_index();
+                if index != self.focused_lane {
+                    self.focus_lane(index);
+                }
+            }
+
+            MainInput::FocusLane { forward } => {
+                let count = self.lanes.len();
+                if count > 0 {
+                    let step = if forward { 1 } else { count - 1 };
+                    self.focus_lane((self.focused_lane + step) % count);
+                }
+            }
+
+            MainInput::CloseLane => {
+                // There would be nothing left to show.
+                if self.lanes.len() > 1 {
+                    if let Some(lane) = self.lanes.get(self.focused_lane) {
+                        self.state.borrow_mut().anchors.remove(&lane.kind().key());
+                        // Relays can stop sending what nobody shows anymore.
+                        self.subscriptions.remove(&lane.kind().key());
+                    }
+                    self.lanes.guard().remove(self.focused_lane);
+                    self.focused_lane = self.focused_lane.min(self.lanes.len() - 1);
+                    self.lanes.send(self.focused_lane, LaneMsg::Focus(true));
+
+                    // Nobody wants raw events anymore.
+                    let firehose = self
+                        .lanes
+                        .iter()
+                        .any(|lane| lane.map(|l| l.kind().is_firehose()).unwrap_or_default());
+                    self.gnostique.kind_stats().set_firehose(firehose);
+                }
+            }
+
+            MainInput::Subscribed { owner, handles } => {
+                // Lane may have been closed before its subscriptions were made,
+                // dropped handles close them then.
+                let wanted = owner.starts_with("session:")
+                    || self
+                        .lanes
+                        .iter()
+                        .any(|lane| lane.map(|l| l.kind().key() == owner).unwrap_or_default());
+                if wanted {
+                    self.subscriptions.insert(owner, handles);
+                }
+            }
+
+            MainInput::LaneScrolled { key, anchor } => {
+                let anchors = &mut self.state.borrow_mut().anchors;
+                match anchor {
+                    Some(id) => anchors.insert(key, id),
+                    None => anchors.remove(&key),
+                };
+            }
+
+            MainInput::LaneFilter { key, filter } => {
+                let filters = &mut self.state.borrow_mut().filters;
+                if filter.is_default() {
+                    filters.remove(&key);
+                } else {
+                    filters.insert(key, filter);
+                }
+            }
+
+            MainInput::LaneThreaded { key, threaded } => {
+                let lanes = &mut self.state.borrow_mut().threaded;
+                if threaded {
+                    lanes.insert(key);
+                } else {
+                    lanes.remove(&key);
+                }
+            }
+
+            MainInput::LaneRead { key, until } => {
+                self.state.borrow_mut().last_read.insert(key, until);
+            }
+
+            MainInput::FocusSearch => self.open_lane(LaneKind::Search, None, &sender),
+
+            MainInput::SelectNote { forward } => self
+                .lanes
+                .send(self.focused_lane, LaneMsg::Select { forward }),
+
+            MainInput::ReplySelected => self.lanes.send(self.focused_lane, LaneMsg::ReplySelected),
+
+            MainInput::ShowShortcuts => {
+                let window = crate::ui::shortcuts::shortcuts_window();
+                window.set_transient_for(
+                    self.lanes
+                        .widget()
+                        .root()
+                        .and_then(|r| r.downcast::<gtk::Window>().ok())
+                        .as_ref(),
+                );
+                window.present();
+            }
+
+            MainInput::Quit if self.quitting => {
+                // Second attempt to quit does not wait for anything.
+                let gnostique = self.gnostique.clone();
+                relm4::spawn(async move { gnostique.shutdown().await });
+            }
+
+            MainInput::Quit => {
+                self.quitting = true;
+                if let Some(window) = root.root().and_then(|r| r.downcast::<gtk::Window>().ok()) {
+                    save_state(&window, &self.state, &self.gnostique.dirs().state_file());
+                    window.set_visible(false);
+                }
+                relm4::spawn(shutdown(self.gnostique.clone(), sender.clone()));
+            }
+
+            MainInput::Exit => {
+                let app = relm4::main_application();
+                app.withdraw_notification("background");
+                app.quit();
+            }
+
+            MainInput::EditProfile => self.edit_profile.emit(EditProfileInput::Show),
+
+            MainInput::ClearCache => {
+                let gnostique = self.gnostique.clone();
+                relm4::spawn(async move {
+                    if let Err(e) = gnostique.download().clear().await {
+                        warn!("Could not clear download cache: {}", e);
+                    }
+                });
+            }
+
+            MainInput::DownloadImages(urls) => {
+                for url in urls {
+                    relm4::spawn(download_image(self.gnostique.clone(), url, sender.clone()));
+                }
+            }
+
+            MainInput::DownloadEmojis(urls) => {
+                for url in urls {
+                    if let Some(bitmap) = self.emojis.get(&url) {
+                        self.lanes.broadcast(LaneMsg::EmojiBitmap { url, bitmap });
+                    } else if self.emojis.start_decoding(url.clone()) {
+                        relm4::spawn(download_emoji(self.gnostique.clone(), url, sender.clone()));
+                    }
+                }
+            }
+
+            MainInput::EmojiDecoded {
+                url,
+                bitmap: Some(bitmap),
+            } => {
+                self.emojis.insert(url.clone(), bitmap.clone());
+                self.lanes.broadcast(LaneMsg::EmojiBitmap { url, bitmap });
+            }
+
+            MainInput::EmojiDecoded { url, bitmap: None } => self.emojis.failed(&url),
+
+            MainInput::Toast(toast) => self.toasts.emit(ToastsInput::Show(toast)),
+
+            MainInput::Expired(id) => self.lanes.broadcast(LaneMsg::Expired(id)),
+
+            MainInput::Delivered(id) => self.lanes.broadcast(LaneMsg::Delivered(id)),
+
+            MainInput::OpenLane(kind) => self.open_lane(kind, None, &sender),
+
+            MainInput::OpenProfile(pubkey, hinted) => {
+                self.open_lane(LaneKind::Profile(pubkey), hinted, &sender)
+            }
+
+            MainInput::OpenThread(id, hinted) => {
+                self.open_lane(LaneKind::Thread(id), hinted, &sender)
+            }
+
+            MainInput::OpenHashtag(tag) => {
+                self.open_lane(LaneKind::Search, None, &sender);
+                self.lanes
+                    .send(self.focused_lane, LaneMsg::Search(format!("#{tag}")));
+                let previous = self.subscriptions.remove(&LaneKind::Search.key());
+                relm4::spawn(request_hashtag(
+                    self.gnostique.clone(),
+                    tag,
+                    previous,
+                    sender.clone(),
+                ));
+            }
+
+            MainInput::NeedPreview(url) => {
+                relm4::spawn(obtain_preview(self.gnostique.clone(), url, sender.clone()));
+            }
+
+            MainInput::LinkPreview(preview) => self.lanes.broadcast(LaneMsg::LinkPreview(preview)),
+
+            MainInput::ImageBitmap { url, bitmap } => {
+                self.article.emit(ArticleWindowInput::ImageBitmap {
+                    url: url.clone(),
+                    bitmap: bitmap.clone(),
+                });
+                self.lanes.broadcast(LaneMsg::ImageBitmap { url, bitmap })
+            }
+
+            MainInput::OpenArticle(article, author) => {
+                self.article.emit(ArticleWindowInput::Show(article, author))
+            }
+
+            MainInput::OpenImage(url) => {
+                relm4::spawn(open_image(self.gnostique.clone(), url, sender.clone()));
+            }
+
+            MainInput::ShowImage(image) => self.viewer.emit(ImageViewerInput::Show(image)),
+
+            MainInput::UpdateProfile(metadata) => match EventBuilder::set_metadata(metadata) {
+                Ok(builder) => {
+                    relm4::spawn(publish(self.gnostique.clone(), builder, sender.clone()));
+                }
+                Err(e) => warn!("Invalid metadata: {}", e),
+            },
+
+            MainInput::Send { content, tags } => {
+                let builder = EventBuilder::new_text_note(content, &tags);
+                relm4::spawn(publish(self.gnostique.clone(), builder, sender.clone()));
+            }
+
+            MainInput::Publish(event) => {
+                let gnostique = self.gnostique.clone();
+                let sender = sender.clone();
+                relm4::spawn(async move {
+                    if let Err(e) = gnostique.outbox().publish(*event).await {
+                        warn!("Could not publish event: {}", e);
+                        sender.input(MainInput::Toast(Toast::error(format!(
+                            "Could not publish: {e}"
+                        ))));
+                    }
+                });
+            }
+
+            MainInput::ShowOutbox => self.outbox.emit(OutboxWindowInput::Show),
+
+            MainInput::ShowRelays => self.relays.emit(RelaysWindowInput::Show),
+
+            MainInput::ExportBackup => {
+                let window = root.root().and_then(|r| r.downcast::<gtk::Window>().ok());
+                let dialog = gtk::FileChooserNative::new(
+                    Some("Export backup"),
+                    window.as_ref(),
+                    gtk::FileChooserAction::Save,
+                    Some("Export"),
+                    Some("Cancel"),
+                );
+                dialog.set_modal(true);
+                dialog.set_current_name("gnostique-backup.json");
+
+                dialog.connect_response(gtk::glib::clone!(@strong sender => move |d, response| {
+                    if response == gtk::ResponseType::Accept {
+                        if let Some(path) = d.file().and_then(|f: gtk::gio::File| f.path()) {
+                            sender.input(MainInput::SaveBackup(path));
+                        }
+                    }
+                    d.destroy();
+                }));
+
+                dialog.show();
+            }
+
+            MainInput::Export {
+                title,
+                notes,
+                thread,
+            } => {
+                let window = root.root().and_then(|r| r.downcast::<gtk::Window>().ok());
+                let dialog = gtk::FileChooserNative::new(
+                    Some("Export notes"),
+                    window.as_ref(),
+                    gtk::FileChooserAction::Save,
+                    Some("Export"),
+                    Some("Cancel"),
+                );
+                dialog.set_modal(true);
+                let html = gtk::FileFilter::new();
+                html.set_name(Some("HTML page"));
+                html.add_suffix("html");
+                let markdown = gtk::FileFilter::new();
+                markdown.set_name(Some("Markdown"));
+                markdown.add_suffix("md");
+                dialog.add_filter(&html);
+                dialog.add_filter(&markdown);
+                dialog.set_current_name(&format!("{}.html", crate::export::file_stem(&title)));
+
+                dialog.connect_response(
+                    gtk::glib::clone!(@strong sender, @strong markdown => move |d, response| {
+                        if response == gtk::ResponseType::Accept {
+                            if let Some(mut path) = d.file().and_then(|f: gtk::gio::File| f.path()) {
+                                // Extension of the name tells the format, or the chosen filter.
+                                let format = match path.extension().and_then(|e| e.to_str()) {
+                                    Some("md" | "markdown") => Format::Markdown,
+                                    Some(_) => Format::Html,
+                                    None if d.filter().as_ref() == Some(&markdown) => Format::Markdown,
+                                    None => Format::Html,
+                                };
+                                if path.extension().is_none() {
+                                    path.set_extension(format.extension());
+                                }
+                                sender.input(MainInput::SaveExport {
+                                    path,
+                                    format,
+                                    title: title.clone(),
+                                    notes: notes.clone(),
+                                    thread,
+                                });
+                            }
+                        }
+                        d.destroy();
+                    }),
+                );
+
+                dialog.show();
+            }
+
+            MainInput::SaveExport {
+                path,
+                format,
+                title,
+                notes,
+                thread,
+            } => {
+                relm4::spawn(export_notes(
+                    self.gnostique.clone(),
+                    path,
+                    format,
+                    title,
+                    notes,
+                    thread,
+                    sender.clone(),
+                ));
+            }
+
+            MainInput::ExportProgress(progress) => {
+                self.status_bar.emit(StatusBarInput::Progress(progress))
+            }
+
+            MainInput::ShowArchive => self.archive.emit(ArchiveDialogInput::Show),
+
+            MainInput::ShowImportFollows => self.import_follows.emit(ImportFollowsInput::Show),
+
+            MainInput::ShowOpen => self.open.emit(OpenInput::Show),
+
+            MainInput::FollowedMany(pubkeys) => {
+                for pubkey in &pubkeys {
+                    if self.follow.add(*pubkey) {
+                        self.lanes.broadcast(LaneMsg::Followed(*pubkey));
+                    }
+                }
+
+                let toast = match pubkeys.len() {
+                    0 => Toast::info("Nobody new to follow"),
+                    1 => Toast::success("Following 1 more person"),
+                    n => Toast::success(format!("Following {n} more people")),
+                };
+                sender.input(MainInput::Toast(toast));
+
+                if !pubkeys.is_empty() {
+                    let gnostique = self.gnostique.clone();
+                    relm4::spawn(async move {
+                        // Recent notes of the new follows show up in feed.
+                        gnostique
+                            .client()
+                            .req_events_of(vec![Follow::subscriptions_of(pubkeys)], None)
+                            .await;
+                    });
+                }
+            }
+
+            MainInput::DownloadAvatars(avatars) => {
+                let gnostique = self.gnostique.clone();
+                let sender = sender.clone();
+                relm4::spawn(async move {
+                    for (pubkey, url) in avatars {
+                        let result = gnostique
+                            .download()
+                            .to_cached_file(&url, Media::Avatar)
+                            .await;
+                        if let Some(file) = result.file() {
+                            sender.input(MainInput::MetadataBitmap { pubkey, url, file });
+                        }
+                    }
+                });
+            }
+
+            MainInput::ShowContacts { pubkey, followers } => {
+                self.contacts.emit(ContactsInput::Show(pubkey, followers))
+            }
+
+            MainInput::ContactCounts {
+                pubkey,
+                following,
+                followers,
+            } => self.lanes.broadcast(LaneMsg::ContactCounts {
+                pubkey,
+                following,
+                followers,
+            }),
+
+            MainInput::ProfileChanges { pubkey, fields } => self
+                .lanes
+                .broadcast(LaneMsg::ProfileChanges { pubkey, fields }),
+
+            MainInput::ShowPrune => self
+                .prune
+                .emit(PruneDialogInput::Show(self.displayed_events())),
+
+            MainInput::ShowSettings => self.settings.emit(SettingsDialogInput::Show),
+
+            MainInput::SettingsSaved => {
+                let config = self.gnostique.config();
+                crate::app::action::sync_main_menu_actions(&self.menu_actions, &config);
+                crate::ui::theme::apply_theme(config.appearance.theme);
+                crate::ui::theme::load_user_style(&self.gnostique.dirs().style_file());
+                sender.input(MainInput::Tick);
+                self.lanes
+                    .broadcast(LaneMsg::Collapse(config.appearance.collapse));
+                self.lanes
+                    .broadcast(LaneMsg::Languages(config.general.languages.clone()));
+                self.lanes.broadcast(LaneMsg::Translatable(
+                    self.gnostique.translations().is_enabled(),
+                ));
+                sender.input(MainInput::Toast(Toast::success("Settings saved")));
+            }
+
+            MainInput::OpenLogFolder => {
+                let dir = crate::app::logging::log_dir(self.gnostique.dirs());
+                let uri = gtk::gio::File::for_path(dir).uri();
+                gtk::show_uri(None::<&gtk::Window>, &uri, gtk::gdk::CURRENT_TIME);
+            }
+
+            MainInput::PruneOverdue => {
+                let gnostique = self.gnostique.clone();
+                let keep = self.displayed_events();
+                let sender = sender.clone();
+                relm4::spawn(async move {
+                    let me = gnostique.client().keys().public_key();
+                    match gnostique.retention().prune(me, &keep, false, |_| {}).await {
+                        Ok(report) if report.events > 0 => {
+                            sender.input(MainInput::Toast(Toast::info(report.describe())))
+                        }
+                        Ok(_) => {}
+                        Err(e) => warn!("Could not prune the database: {}", e),
+                    }
+                });
+            }
+
+            MainInput::SaveBackup(path) => {
+                let gnostique = self.gnostique.clone();
+                let sender = sender.clone();
+                relm4::spawn(async move {
+                    match crate::backup::export(&gnostique, &path).await {
+                        Ok(()) => sender.input(MainInput::Toast(Toast::success("Backup exported"))),
+                        Err(e) => {
+                            warn!("Could not export backup: {}", e);
+                            sender.input(MainInput::Toast(Toast::error(format!(
+                                "Could not export backup: {e}"
+                            ))));
+                        }
+                    }
+                });
+            }
+
+            MainInput::ShowDetail(mut details) => {
+                // Relays may have been added since the note was displayed.
+                let relays = self.gnostique.textnote_relays(details.event.id).await;
+                if !relays.is_empty() {
+                    details.relays = relays;
+                }
+                details.first_seen = self.gnostique.textnote_first_seen(details.event.id).await;
+                details.deliveries = self.gnostique.outbox().deliveries(details.event.id).await;
+                details.profile_history = self
+                    .gnostique
+                    .metadata_history()
+                    .changes(details.event.author())
+                    .await;
+                self.details.emit(DetailsWindowInput::Show(details))
+            }
+
+            MainInput::RefreshDeliveries(id) => {
+                let deliveries = self.gnostique.outbox().deliveries(id).await;
+                self.details
+                    .emit(DetailsWindowInput::Deliveries(id, deliveries));
+            }
+
+            MainInput::Nip05Verified(nip05) => self.lanes.broadcast(LaneMsg::Nip05Verified(nip05)),
+
+            MainInput::MetadataBitmap { pubkey, url, file } => {
+                self.show_avatar(pubkey, url, file, &sender)
+            }
+
+            MainInput::AvatarDecoded {
+                pubkey,
+                url,
+                bitmap: Some(bitmap),
+            } => {
+                self.avatars.insert(pubkey, url.clone(), bitmap.clone());
+                self.import_follows
+                    .emit(ImportFollowsInput::Avatar(pubkey, bitmap.clone()));
+                self.contacts
+                    .emit(ContactsInput::Avatar(pubkey, bitmap.clone()));
+                self.lanes.broadcast(LaneMsg::MetadataBitmap {
+                    pubkey,
+                    url,
+                    bitmap,
+                });
+            }
+
+            MainInput::AvatarDecoded {
+                pubkey,
+                url,
+                bitmap: None,
+            } => {
+                // Corrupt file would be decoded again and again.
+                self.avatars.failed(&pubkey, url.clone());
+                let gnostique = self.gnostique.clone();
+                relm4::spawn(async move { gnostique.download().mark_bad(&url).await });
+            }
+        };
+
+        self.update_view(widgets, sender);
+    }
+}
+
+impl Main {
+    /// Changes configuration, user is told if it cannot be saved.
+    fn change_config<F>(&self, sender: &AsyncComponentSender<Self>, change: F)
+    where
+        F: FnOnce(&mut Config),
+    {
+        if let Err(e) = self.gnostique.update_config(change) {
+            sender.input(MainInput::Toast(Toast::error(e)));
+        }
+    }
+
+    /// Note which lane of `kind` was scrolled to when it was last seen.
+    fn anchor(&self, kind: &LaneKind) -> Option<EventId> {
+        self.state.borrow().anchors.get(&kind.key()).copied()
+    }
+
+    /// Which notes lane of `kind` hid when it was last seen.
+    fn filter(&self, kind: &LaneKind) -> LaneFilter {
+        self.state
+            .borrow()
+            .filters
+            .get(&kind.key())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Whether lane of `kind` displayed threads when it was last seen.
+    fn threaded(&self, kind: &LaneKind) -> bool {
+        self.state.borrow().threaded.contains(&kind.key())
+    }
+
+    /// Creation time of the newest note read in lane of `kind`.
+    fn last_read(&self, kind: &LaneKind) -> Option<Timestamp> {
+        self.state.borrow().last_read.get(&kind.key()).copied()
+    }
+
+    /// Focuses lane of `kind` if it is open, otherwise adds it at the end
+    /// and asks for its content, also at relays `hinted` for it.
+    fn open_lane(
+        &mut self,
+        kind: LaneKind,
+        hinted: Option<Vec<Url>>,
+        sender: &AsyncComponentSender<Self>,
+    ) {
+        let key = kind.key();
+        let open = self
+            .lanes
+            .iter()
+            .position(|lane| lane.map(|l| l.kind().key() == key).unwrap_or_default());
+        if let Some(index) = open {
+            self.focus_lane(index);
+            return;
+        }
+
+        if kind.is_notifications() {
+            let gnostique = self.gnostique.clone();
+            let sender = sender.clone();
+            relm4::spawn(async move {
+                let history = gnostique.notifications().history(200).await;
+                sender.input(MainInput::NotificationHistory(history));
+            });
+        }
+
+        if kind.is_firehose() {
+            self.gnostique.kind_stats().set_firehose(true);
+        }
+
+        if let LaneKind::Profile(pubkey) = kind {
+            relm4::spawn(count_contacts(
+                self.gnostique.clone(),
+                pubkey,
+                sender.clone(),
+            ));
+            relm4::spawn(load_labels(self.gnostique.clone(), pubkey, sender.clone()));
+            relm4::spawn(load_profile_changes(
+                self.gnostique.clone(),
+                pubkey,
+                sender.clone(),
+            ));
+
+            let gnostique = self.gnostique.clone();
+            let sender = sender.clone();
+            relm4::spawn(async move {
+                let pinned = gnostique.pins_of(pubkey).await;
+                if !pinned.is_empty() {
+                    sender.input(MainInput::Pins {
+                        author: pubkey,
+                        pinned,
+                    });
+                }
+            });
+        }
+
+        if let LaneKind::Period(follow, period) = &kind {
+            relm4::spawn(request_period(
+                self.gnostique.clone(),
+                follow.clone(),
+                *period,
+                sender.clone(),
+            ));
+        }
+
+        if kind.is_bookmarks() {
+            let ids = self.bookmarks.iter().copied().collect();
+            relm4::spawn(load_notes(
+                self.gnostique.clone(),
+                ids,
+                sender.clone(),
+                MainInput::BookmarkedNotes,
+            ));
+        }
+
+        relm4::spawn({
+            let gnostique = self.gnostique.clone();
+            let kind = kind.clone();
+            let sender = sender.clone();
+            async move {
+                let handles =
+                    request_lane_content(gnostique, kind, hinted.unwrap_or_default()).await;
+                // Search lane may have got a subscription to hashtag meanwhile.
+                if !handles.is_empty() {
+                    sender.input(MainInput::Subscribed {
+                        owner: key,
+                        handles,
+                    });
+                }
+            }
+        });
+        self.lanes.send(self.focused_lane, LaneMsg::Focus(false));
+        let (anchor, filter, threaded, last_read) = (
+            self.anchor(&kind),
+            self.filter(&kind),
+            self.threaded(&kind),
+            self.last_read(&kind),
+        );
+        self.lanes.guard().push_back(LaneInit {
+            kind,
+            me: self.gnostique.client().keys().public_key(),
+            bookmarks: self.bookmarks.clone(),
+            time_format: self.gnostique.time_format(),
+            collapse: self.gnostique.collapse(),
+            languages: self.gnostique.languages(),
+            translatable: self.gnostique.translations().is_enabled(),
+            focused: true,
+            anchor,
+            filter,
+            follow: self.follow.clone(),
+            threaded,
+            last_read,
+        });
+        self.focused_lane = self.lanes.len() - 1;
+    }
+
+    /// Shows `toast` offering to undo pending action `id`, which is
+    /// carried out once the undo window passes.
+    fn put_off(&self, id: u64, toast: Toast, sender: &AsyncComponentSender<Self>) {
+        sender.input(MainInput::Toast(toast));
+        let sender = sender.clone();
+        relm4::spawn(async move {
+            tokio::time::sleep(UNDO_WINDOW).await;
+            sender.input(MainInput::CarryOut(id));
+        });
+    }
+
+    /// Makes keyboard shortcuts act on lane at `index`.
+    fn focus_lane(&mut self, index: usize) {
+        if index < self.lanes.len() {
+            self.lanes.send(self.focused_lane, LaneMsg::Focus(false));
+            self.focused_lane = index;
+            self.lanes.send(index, LaneMsg::Focus(true));
+        }
+    }
+
+    /// Events displayed in any lane.
+    fn displayed_events(&self) -> HashSet<EventId> {
+        self.lanes
+            .iter()
+            .flatten()
+            .flat_map(|lane| lane.displayed())
+            .copied()
+            .collect()
+    }
+
+    /// Lets all lanes know that note `id` has been added to bookmarks
+    /// or removed from there. Newly bookmarked note is shown in bookmarks lanes.
+    fn bookmarked(&mut self, id: EventId, bookmarked: bool, sender: &AsyncComponentSender<Self>) {
+        let changed = if bookmarked {
+            self.bookmarks.insert(id)
+        } else {
+            self.bookmarks.remove(&id)
+        };
+
+        if changed {
+            self.lanes.broadcast(LaneMsg::Bookmarked(id, bookmarked));
+            if bookmarked {
+                relm4::spawn(load_notes(
+                    self.gnostique.clone(),
+                    vec![id],
+                    sender.clone(),
+                    MainInput::BookmarkedNotes,
+                ));
+            }
+        }
+    }
+
+    /// Lets profile lane of `author`, if open, display their `pinned`
+    /// notes above others.
+    fn show_pins(
+        &mut self,
+        author: XOnlyPublicKey,
+        pinned: Vec<EventId>,
+        sender: &AsyncComponentSender<Self>,
+    ) {
+        let open = self.lanes.iter().any(|lane| {
+            lane.map(|l| l.kind().is_profile(&author))
+                .unwrap_or_default()
+        });
+        if !open {
+            return;
+        }
+
+        self.lanes.broadcast(LaneMsg::Pins {
+            author,
+            pinned: pinned.clone(),
+        });
+        relm4::spawn(load_notes(
+            self.gnostique.clone(),
+            pinned,
+            sender.clone(),
+            MainInput::PinnedNotes,
+        ));
+    }
+
+    /// Distributes avatar of `pubkey` from `file` to all lanes. Already decoded
+    /// avatars are reused, others are decoded in the background first.
+    fn show_avatar(
+        &mut self,
+        pubkey: XOnlyPublicKey,
+        url: Url,
+        file: PathBuf,
+        sender: &AsyncComponentSender<Self>,
+    ) {
+        if let Some(bitmap) = self.avatars.get(&pubkey, &url) {
+            self.import_follows
+                .emit(ImportFollowsInput::Avatar(pubkey, bitmap.clone()));
+            self.contacts
+                .emit(ContactsInput::Avatar(pubkey, bitmap.clone()));
+            self.lanes.broadcast(LaneMsg::MetadataBitmap {
+                pubkey,
+                url,
+                bitmap,
+            });
+        } else if self.avatars.start_decoding(pubkey, &url) {
+            let sender = sender.clone();
+            relm4::spawn_blocking(move || {
+                let bitmap = match crate::ui::avatars::decode(&file) {
+                    Ok(bitmap) => Some(Arc::new(bitmap)),
+                    Err(e) => {
+                        warn!("Could not load '{:?}': {}", file, e);
+                        None
+                    }
+                };
+
+                sender.input(MainInput::AvatarDecoded {
+                    pubkey,
+                    url,
+                    bitmap,
+                });
+            });
+        }
+    }
+}
+
+/// Quits Gnostique when the window, in which `widget` will be, is closed.
+/// If Gnostique should keep running, the window is just hidden, after
+/// size of the window together with `state` of lanes is remembered.
+fn on_window_close(
+    widget: &gtk::Overlay,
+    gnostique: Gnostique,
+    state: Rc<RefCell<State>>,
+    sender: AsyncComponentSender<Main>,
+) {
+    widget.connect_realize(move |widget| {
+        let window = widget.root().and_then(|r| r.downcast::<gtk::Window>().ok());
+
+        if let Some(window) = window {
+            let gnostique = gnostique.clone();
+            let state = state.clone();
+            let sender = sender.clone();
+            window.connect_close_request(move |window| {
+                if gnostique.config().general.keep_running {
+                    save_state(window, &state, &gnostique.dirs().state_file());
+                    window.set_visible(false);
+                    announce_background();
+                } else {
+                    sender.input(MainInput::Quit);
+                }
+                gtk::Inhibit(true)
+            });
+        }
+    });
+}
+
+/// Makes sure that Gnostique ends politely also when the application
+/// quits some other way than by [`MainInput::Quit`].
+fn shutdown_on_quit(gnostique: Gnostique) {
+    relm4::main_application().connect_shutdown(move |_| {
+        let (done, wait) = std::sync::mpsc::channel();
+        let gnostique = gnostique.clone();
+        relm4::spawn(async move {
+            gnostique.shutdown().await;
+            done.send(()).unwrap_or_default();
+        });
+
+        // Main loop has ended, nothing else can happen meanwhile.
+        let _ = wait.recv_timeout(2 * crate::shutdown::TIMEOUT);
+    });
+}
+
+/// Writes size of `window` together with `state` of lanes into `path`.
+fn save_state(window: &gtk::Window, state: &RefCell<State>, path: &Path) {
+    let (width, height) = window.default_size();
+    let mut state = state.borrow_mut();
+    state.window = WindowState {
+        width,
+        height,
+        maximized: window.is_maximized(),
+    };
+    if let Err(e) = state.write(path) {
+        warn!("{}", e);
+    }
+}
+
+/// Tells user that Gnostique keeps running without window, and how to get
+/// the window back or quit.
+fn announce_background() {
+    let notification = gtk::gio::Notification::new("Gnostique keeps running");
+    notification.set_body(Some(
+        "Events are still collected and you will be notified about replies and mentions.",
+    ));
+    notification.set_default_action("app.show-window");
+    notification.add_button("Open window", "app.show-window");
+    notification.add_button("Quit", "app.quit");
+
+    relm4::main_application().send_notification(Some("background"), &notification);
+}
+
+/// Ends Gnostique, then lets the application quit.
+async fn shutdown(gnostique: Gnostique, sender: AsyncComponentSender<Main>) {
+    gnostique.outbox().cleanup().await;
+    gnostique.shutdown().await;
+    sender.input(MainInput::Exit);
+}
+
+/// Downloads image embedded in a note and decodes it off the main loop.
+async fn download_image(gnostique: Gnostique, url: Url, sender: AsyncComponentSender<Main>) {
+    if let Some(file) = gnostique
+        .download()
+        .to_cached_file(&url, Media::Image)
+        .await
+        .file()
+    {
+        let decoded = relm4::spawn_blocking(move || {
+            gdk::Texture::from_filename(&file).map_err(|e| format!("{file:?}: {e}"))
+        })
+        .await;
+
+        match decoded {
+            Ok(Ok(bitmap)) => sender.input(MainInput::ImageBitmap {
+                url,
+                bitmap: Arc::new(bitmap),
+            }),
+            Ok(Err(e)) => warn!("Could not load {}", e),
+            Err(e) => warn!("Could not load image from {}: {}", url, e),
+        }
+    }
+}
+
+/// Downloads custom emoji and decodes it off the main loop. Emoji that
+/// cannot be obtained stay written as their shortcodes.
+async fn download_emoji(gnostique: Gnostique, url: Url, sender: AsyncComponentSender<Main>) {
+    let bitmap = match gnostique
+        .download()
+        .to_cached_file(&url, Media::Emoji)
+        .await
+        .file()
+    {
+        Some(file) => relm4::spawn_blocking(move || gdk::Texture::from_filename(&file))
+            .await
+            .ok()
+            .and_then(|r| {
+                r.map_err(|e| warn!("Could not load emoji {}: {}", url, e))
+                    .ok()
+            })
+            .map(Arc::new),
+        None => None,
+    };
+
+    sender.input(MainInput::EmojiDecoded { url, bitmap });
+}
+
+/// Finds stored notes matching `query` together with their authors,
+/// whose avatars are shown if already downloaded.
+async fn search_notes(gnostique: Gnostique, query: String, sender: AsyncComponentSender<Main>) {
+    /// At most this many notes are found.
+    const LIMIT: u32 = 100;
+
+    let events = gnostique.search_notes(&query, LIMIT).await;
+    let mut notes = Vec::with_capacity(events.len());
+    let mut avatars = HashSet::new();
+
+    for event in events {
+        let author = gnostique.get_persona(event.pubkey).await;
+        if let Some(url) = author.as_ref().and_then(|a| a.avatar.clone()) {
+            avatars.insert((event.pubkey, url));
+        }
+
+        notes.push(FoundNote {
+            relays: gnostique.textnote_relays(event.id).await,
+            reactions: gnostique.reactions().counts(event.id).await,
+            event: Arc::new(event),
+            author,
+        });
+    }
+
+    sender.input(MainInput::SearchResults { query, notes });
+
+    for (pubkey, url) in avatars {
+        if let Some(file) = gnostique.download().cached(&url).await {
+            sender.input(MainInput::MetadataBitmap { pubkey, url, file });
+        }
+    }
+}
+
+/// Loads notes `ids`, bookmarked or pinned, from database together with
+/// their authors, whose avatars are shown if already downloaded. Notes
+/// that have not been stored are asked for at relays.
+async fn load_notes(
+    gnostique: Gnostique,
+    ids: Vec<EventId>,
+    sender: AsyncComponentSender<Main>,
+    loaded: fn(Vec<FoundNote>) -> MainInput,
+) {
+    let mut events = Vec::with_capacity(ids.len());
+    let mut missing = Vec::new();
+
+    for id in ids {
+        match gnostique.get_textnote(id).await {
+            Some(event) => events.push(event),
+            None => missing.push(id.to_hex()),
+        }
+    }
+
+    if !missing.is_empty() {
+        let filter = SubscriptionFilter::new().ids(missing);
+        gnostique.client().req_events_of(vec![filter], None).await;
+    }
+
+    let (notes, avatars) = found_notes(&gnostique, events).await;
+    sender.input(loaded(notes));
+    send_avatars(&gnostique, avatars, &sender).await;
+}
+
+/// How long relays have to send the rest of a thread being exported.
+const EXPORT_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Progress of export is shown after this many notes.
+const EXPORT_PROGRESS_EVERY: usize = 50;
+
+/// Writes `notes` with their authors into file at `path`, after fetching
+/// the rest of `thread` if it is given. Avatars are read from the cache,
+/// those which are not there are left out.
+async fn export_notes(
+    gnostique: Gnostique,
+    path: PathBuf,
+    format: Format,
+    title: String,
+    mut notes: Vec<(Arc<Event>, Option<Persona>)>,
+    thread: Option<EventId>,
+    sender: AsyncComponentSender<Main>,
+) {
+    if let Some(id) = thread {
+        sender.input(MainInput::ExportProgress(Some(
+            "Fetching the whole thread…".to_string(),
+        )));
+        let filters = vec![
+            SubscriptionFilter::new().id(id.to_hex()),
+            SubscriptionFilter::new().kind(Kind::TextNote).event(id),
+        ];
+        match gnostique
+            .client()
+            .get_events_of(filters, Some(EXPORT_FETCH_TIMEOUT))
+            .await
+        {
+            Ok(events) => {
+                let mut known: HashSet<EventId> = notes.iter().map(|(e, _)| e.id).collect();
+                for event in events {
+                    if known.insert(event.id) {
+                        let author = gnostique.get_persona(event.pubkey).await;
+                        notes.push((Arc::new(event), author));
+                    }
+                }
+            }
+            Err(e) => warn!("Could not fetch thread {}: {}", id, e),
+        }
+    }
+
+    let total = notes.len();
+    let mut names: HashMap<XOnlyPublicKey, String> = HashMap::new();
+    let mut avatars: HashMap<XOnlyPublicKey, Option<String>> = HashMap::new();
+    let mut exported = Vec::with_capacity(total);
+    for (i, (event, author)) in notes.into_iter().enumerate() {
+        let avatar_url = author.as_ref().and_then(|a| a.avatar.clone());
+        if format == Format::Html && !avatars.contains_key(&event.pubkey) {
+            let uri = match avatar_url {
+                Some(url) => match gnostique.download().cached(&url).await {
+                    Some(file) => tokio::fs::read(file)
+                        .await
+                        .ok()
+                        .and_then(|bytes| crate::export::data_uri(&bytes)),
+                    None => None,
+                },
+                None => None,
+            };
+            avatars.insert(event.pubkey, uri);
+        }
+
+        for pubkey in event
+            .mentions()
+            .into_iter()
+            .chain(crate::ui::writenote::model::mentioned(&event.content))
+        {
+            if let Entry::Vacant(name) = names.entry(pubkey) {
+                if let Some(persona) = gnostique.get_persona(pubkey).await {
+                    name.insert(persona.shown_name());
+                }
+            }
+        }
+
+        exported.push(ExportedNote {
+            author: author
+                .map(|a| a.shown_name())
+                .unwrap_or_else(|| crate::nostr::short_npub(&event.pubkey)),
+            avatar: avatars.get(&event.pubkey).cloned().flatten(),
+            event,
+        });
+
+        if (i + 1) % EXPORT_PROGRESS_EVERY == 0 {
+            sender.input(MainInput::ExportProgress(Some(format!(
+                "Exporting {}/{} notes",
+                i + 1,
+                total
+            ))));
+        }
+    }
+
+    let text = crate::export::render(format, &title, &exported, &names, gnostique.time_format());
+    let written = tokio::fs::write(&path, text).await;
+    sender.input(MainInput::ExportProgress(None));
+
+    match written {
+        Ok(()) => {
+            info!("Exported {} notes to {}", total, path.display());
+            sender.input(MainInput::Toast(Toast::success(match total {
+                1 => "Exported 1 note".to_string(),
+                n => format!("Exported {n} notes"),
+            })));
+        }
+        Err(e) => {
+            warn!("Could not export notes to {}: {}", path.display(), e);
+            sender.input(MainInput::Toast(Toast::error(format!(
+                "Could not export notes: {e}"
+            ))));
+        }
+    }
+}
+
+/// Most notes of a period asked for at once, both stored ones and those
+/// from relays. Earlier ones are asked for when user wants them.
+const PERIOD_PAGE: usize = 50;
+
+/// Loads stored notes of `follow`ed authors created within `period` for
+/// period lane, and asks relays for them too. Notes created after the
+/// period do not matter, so there is nothing to wait for once relays
+/// have sent what they have.
+async fn request_period(
+    gnostique: Gnostique,
+    follow: Follow,
+    period: Period,
+    sender: AsyncComponentSender<Main>,
+) {
+    let authors: Vec<XOnlyPublicKey> = follow.following().copied().collect();
+    if authors.is_empty() {
+        return;
+    }
+
+    let events = gnostique
+        .textnotes_between(&authors, period.since, period.until, PERIOD_PAGE as u32)
+        .await;
+    let (notes, avatars) = found_notes(&gnostique, events).await;
+    sender.input(MainInput::PeriodNotes(notes));
+    send_avatars(&gnostique, avatars, &sender).await;
+
+    let filter = SubscriptionFilter::new()
+        .kind(Kind::TextNote)
+        .authors(authors)
+        .since(period.since)
+        .until(period.until)
+        .limit(PERIOD_PAGE);
+    let key = LaneKind::Period(follow, period).key();
+    let handle = gnostique.subscribe(&key, vec![filter], Vec::new()).await;
+    sender.input(MainInput::Subscribed {
+        owner: key,
+        handles: vec![handle],
+    });
+}
+
+/// Stored `events` with what lanes display along with them, and avatars
+/// of their authors that can be displayed.
+async fn found_notes(
+    gnostique: &Gnostique,
+    events: Vec<Event>,
+) -> (Vec<FoundNote>, HashSet<(XOnlyPublicKey, Url)>) {
+    let mut notes = Vec::with_capacity(events.len());
+    let mut avatars = HashSet::new();
+
+    for event in events {
+        let author = gnostique.get_persona(event.pubkey).await;
+        if let Some(url) = author.as_ref().and_then(|a| a.avatar.clone()) {
+            avatars.insert((event.pubkey, url));
+        }
+
+        notes.push(FoundNote {
+            relays: gnostique.textnote_relays(event.id).await,
+            reactions: gnostique.reactions().counts(event.id).await,
+            event: Arc::new(event),
+            author,
+        });
+    }
+
+    (notes, avatars)
+}
+
+/// Displays cached `avatars` of authors.
+async fn send_avatars(
+    gnostique: &Gnostique,
+    avatars: HashSet<(XOnlyPublicKey, Url)>,
+    sender: &AsyncComponentSender<Main>,
+) {
+    for (pubkey, url) in avatars {
+        if let Some(file) = gnostique.download().cached(&url).await {
+            sender.input(MainInput::MetadataBitmap { pubkey, url, file });
+        }
+    }
+}
+
+/// Carries out pending `action` once it cannot be undone anymore.
+async fn carry_out(
+    gnostique: Gnostique,
+    action: PendingAction,
+    sender: AsyncComponentSender<Main>,
+) {
+    let (result, what) = match action {
+        PendingAction::Mute(pubkey) => (gnostique.set_muted(pubkey, true).await, "mute"),
+        PendingAction::Delete(event_id) => (gnostique.delete_note(event_id).await, "delete"),
+    };
+
+    if let Err(e) = result {
+        warn!("Could not carry out {:?}: {}", action, e);
+        sender.input(MainInput::Toast(Toast::error(format!(
+            "Could not {what}: {e}"
+        ))));
+    }
+}
+
+/// Loads our note whose deletion has been undone, so that it can be
+/// displayed again.
+async fn restore_note(gnostique: Gnostique, event_id: EventId, sender: AsyncComponentSender<Main>) {
+    let event = match gnostique.get_textnote(event_id).await {
+        Some(event) => event,
+        None => return,
+    };
+    let pubkey = event.pubkey;
+    let author = gnostique.get_persona(pubkey).await;
+    let avatar = author.as_ref().and_then(|a| a.avatar.clone());
+
+    sender.input(MainInput::Restore(FoundNote {
+        relays: gnostique.textnote_relays(event.id).await,
+        reactions: gnostique.reactions().counts(event.id).await,
+        author,
+        event: Arc::new(event),
+    }));
+
+    if let Some(url) = avatar {
+        if let Some(file) = gnostique.download().cached(&url).await {
+            sender.input(MainInput::MetadataBitmap { pubkey, url, file });
+        }
+    }
+}
+
+/// Announces stored note `event_id` to notes replying to it. Note which is
+/// not stored is asked for, it will be announced when it arrives.
+async fn find_parent(
+    gnostique: Gnostique,
+    event_id: EventId,
+    relays: Vec<Url>,
+    sender: AsyncComponentSender<Main>,
+) {
+    match gnostique.get_textnote(event_id).await {
+        Some(event) => {
+            let author = gnostique.get_persona(event.pubkey).await;
+            sender.input(MainInput::Parent(Quote { event, author }));
+        }
+        None => gnostique.demand().text_note(event_id, None, relays).await,
+    }
+}
+
+/// Finds stored users matching `query`, whose avatars are shown
+/// if already downloaded.
+async fn search_people(
+    gnostique: Gnostique,
+    follow: Follow,
+    query: String,
+    sender: AsyncComponentSender<Main>,
+) {
+    /// At most this many users are found.
+    const LIMIT: u32 = 20;
+
+    let personas = gnostique.search_personas(&query, LIMIT).await;
+    let avatars: Vec<_> = personas
+        .iter()
+        .filter_map(|p| Some((p.pubkey, p.avatar.clone()?)))
+        .collect();
+
+    let people = personas
+        .into_iter()
+        .map(|persona| FoundPerson {
+            followed: follow.follows(&persona.pubkey),
+            persona,
+        })
+        .collect();
+
+    sender.input(MainInput::PeopleResults { query, people });
+
+    for (pubkey, url) in avatars {
+        if let Some(file) = gnostique.download().cached(&url).await {
+            sender.input(MainInput::MetadataBitmap { pubkey, url, file });
+        }
+    }
+}
+
+/// Asks relays supporting search (NIP-50) for notes matching `query`.
+/// Found notes arrive as any other events and search lane picks them up.
+async fn search_relays(gnostique: Gnostique, query: String) {
+    /// Every relay is asked for at most this many notes.
+    const LIMIT: usize = 50;
+
+    let filter = SubscriptionFilter::new()
+        .kind(Kind::TextNote)
+        .search(query)
+        .limit(LIMIT);
+
+    for (url, relay) in gnostique.client().relays().await {
+        if relay.opts().read() && gnostique.relay_info().supports(&url, 50).await {
+            info!("Searching at {}", url);
+            relay.req_events_of(vec![filter.clone()], None);
+        }
+    }
+}
+
+/// Shows desktop notification about `event`, clicking which opens its thread.
+fn show_notification(notification: &Notification, event: &Event) {
+    let name = notification
+        .author_name
+        .clone()
+        .unwrap_or_else(|| event.pubkey.to_string().chars().take(12).collect());
+
+    let title = match notification.kind {
+        NotificationKind::Reply => format!("{name} replied to your note"),
+        _ => format!("{name} mentioned you"),
+    };
+    let body = notification.content.clone();
+
+    let notification = gtk::gio::Notification::new(&title);
+    notification.set_body(Some(&body));
+    notification.set_default_action_and_target_value(
+        "app.open-thread",
+        Some(&event.id.to_hex().to_variant()),
+    );
+
+    relm4::main_application().send_notification(Some(&event.id.to_hex()), &notification);
+}
+
+/// Counts users followed by `pubkey` and its followers known to this
+/// client, for its profile lane.
+async fn count_contacts(
+    gnostique: Gnostique,
+    pubkey: XOnlyPublicKey,
+    sender: AsyncComponentSender<Main>,
+) {
+    let following = gnostique.following_of(pubkey).await.map(|f| f.len());
+    let followers = gnostique.followers_of(pubkey).await.len();
+    sender.input(MainInput::ContactCounts {
+        pubkey,
+        following,
+        followers,
+    });
+}
+
+/// Announces labels of `author`, for its notes and profile lane.
+async fn load_labels(
+    gnostique: Gnostique,
+    author: XOnlyPublicKey,
+    sender: AsyncComponentSender<Main>,
+) {
+    let labels = gnostique.labels().of(author).await;
+    sender.input(MainInput::Labels { author, labels });
+}
+
+/// Announces which fields of profile of `pubkey` have changed recently,
+/// for its profile lane.
+async fn load_profile_changes(
+    gnostique: Gnostique,
+    pubkey: XOnlyPublicKey,
+    sender: AsyncComponentSender<Main>,
+) {
+    let fields = gnostique.metadata_history().recently_changed(pubkey).await;
+    sender.input(MainInput::ProfileChanges { pubkey, fields });
+}
+
+/// Subscribes to events to be displayed in a newly opened lane, which
+/// keep coming while the lane holds the handles. Relays `hinted` by the
+/// link which has opened it are asked once, and so are relays of the
+/// user of a profile lane.
+async fn request_lane_content(
+    gnostique: Gnostique,
+    kind: LaneKind,
+    mut hinted: Vec<Url>,
+) -> Vec<SubscriptionHandle> {
+    let name = kind.key();
+    if let LaneKind::Profile(pubkey) = &kind {
+        let gossip = gnostique.gossip();
+        let mut theirs = gossip.write_relays(*pubkey).await;
+        theirs.extend(gossip.nip05_relays(*pubkey).await);
+        for url in theirs {
+            if !hinted.contains(&url) {
+                hinted.push(url);
+            }
+        }
+    }
+    let filters = match kind {
+        LaneKind::Profile(pubkey) => vec![
+            SubscriptionFilter::new()
+                .kind(Kind::Metadata)
+                .author(pubkey)
+                .limit(1),
+            SubscriptionFilter::new()
+                .kind(Kind::TextNote)
+                .author(pubkey)
+                .limit(100),
+            SubscriptionFilter::new()
+                .kind(Kind::Custom(crate::nostr::ARTICLE_KIND))
+                .author(pubkey)
+                .limit(20),
+            SubscriptionFilter::new()
+                .kind(Kind::Custom(crate::nostr::PIN_LIST_KIND))
+                .author(pubkey)
+                .limit(1),
+            // Whom they follow, and some of their followers.
+            SubscriptionFilter::new()
+                .kind(Kind::ContactList)
+                .author(pubkey)
+                .limit(1),
+            SubscriptionFilter::new()
+                .kind(Kind::ContactList)
+                .pubkey(pubkey)
+                .limit(50),
+        ],
+        LaneKind::Thread(id) => vec![
+            SubscriptionFilter::new().id(id.to_hex()),
+            SubscriptionFilter::new().kind(Kind::TextNote).event(id),
+        ],
+        LaneKind::Feed(follow) => return request_feed(&gnostique, &follow).await,
+        // Search goes through stored notes only.
+        LaneKind::Search => vec![],
+        // Bookmarked notes are loaded from database first.
+        LaneKind::Bookmarks => vec![],
+        // Whatever arrives for other lanes.
+        LaneKind::Firehose => vec![],
+        // Notes of the period are asked for a page at a time.
+        LaneKind::Period(..) => vec![],
+        LaneKind::Notifications => vec![SubscriptionFilter::new()
+            .pubkey(gnostique.client().keys().public_key())
+            .kinds(vec![
+                Kind::TextNote,
+                Kind::Reaction,
+                Kind::ContactList,
+                Kind::Custom(crate::nostr::ZAP_RECEIPT_KIND),
+            ])
+            .limit(100)],
+    };
+
+    if filters.is_empty() {
+        return Vec::new();
+    }
+    if !hinted.is_empty() {
+        gnostique
+            .demand()
+            .request_hinted(filters.clone(), hinted)
+            .await;
+    }
+    vec![gnostique.subscribe(&name, filters, Vec::new()).await]
+}
+
+/// Subscribes to notes with hashtag `tag`, search lane picks up those
+/// that arrive. Subscription of `previous` hashtag is changed into it.
+async fn request_hashtag(
+    gnostique: Gnostique,
+    tag: String,
+    previous: Option<Vec<SubscriptionHandle>>,
+    sender: AsyncComponentSender<Main>,
+) {
+    let filters = vec![SubscriptionFilter::new()
+        .kind(Kind::TextNote)
+        .hashtag(tag.to_lowercase())
+        .limit(100)];
+
+    let handle = match previous.and_then(|mut handles| handles.pop()) {
+        Some(mut handle) => {
+            handle.refresh(filters).await;
+            handle
+        }
+        None => {
+            gnostique
+                .subscribe(&LaneKind::Search.key(), filters, Vec::new())
+                .await
+        }
+    };
+    sender.input(MainInput::Subscribed {
+        owner: LaneKind::Search.key(),
+        handles: vec![handle],
+    });
+}
+
+/// Asks for notes of followed authors on relays they write to. Authors
+/// whose relay lists are not known are asked for on all our relays,
+/// together with the relay lists, so that next time they can be found
+/// at their own relays.
+async fn request_feed(gnostique: &Gnostique, follow: &Follow) -> Vec<SubscriptionHandle> {
+    let (grouped, unknown) = gnostique
+        .gossip()
+        .group_by_relays(follow.following().copied())
+        .await;
+
+    let relays = gnostique.client().relays().await;
+    let mut handles = Vec::new();
+    for (url, authors) in grouped {
+        if relays.contains_key(&url) {
+            handles.push(
+                gnostique
+                    .subscribe("feed", vec![Follow::subscriptions_of(authors)], vec![url])
+                    .await,
+            );
+        }
+    }
+
+    if !unknown.is_empty() {
+        let filters = vec![
+            SubscriptionFilter::new()
+                .kind(Kind::Custom(crate::nostr::RELAY_LIST_KIND))
+                .authors(unknown.clone()),
+            Follow::subscriptions_of(unknown),
+        ];
+        handles.push(
+            gnostique
+                .subscribe("feed of authors without relay lists", filters, Vec::new())
+                .await,
+        );
+    }
+
+    handles
+}
+
+/// Obtains preview of web page at `url` and then its thumbnail, which
+/// is downloaded like any other embedded image. Nothing happens if
+/// the page cannot be previewed, the note then shows just the link.
+async fn obtain_preview(gnostique: Gnostique, url: Url, sender: AsyncComponentSender<Main>) {
+    if let Some(preview) = gnostique.previews().get(&url).await {
+        let image = preview.image.clone();
+        sender.input(MainInput::LinkPreview(Arc::new(preview)));
+
+        if let Some(image) = image {
+            download_image(gnostique, image, sender).await;
+        }
+    }
+}
+
+/// Prepares image from `url` for full size display.
+/// Asks user whether to use relays from relay list published by another client.
+fn offer_relay_list(
+    root: &gtk::Overlay,
+    relays: Vec<RelayPolicy>,
+    sender: AsyncComponentSender<Main>,
+) {
+    let window = root.root().and_then(|r| r.downcast::<gtk::Window>().ok());
+
+    let listed = relays
+        .iter()
+        .map(|r| match (r.read, r.write) {
+            (true, false) => format!("{} (read)", r.url),
+            (false, true) => format!("{} (write)", r.url),
+            _ => r.url.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let dialog = gtk::MessageDialog::new(
+        window.as_ref(),
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        gtk::MessageType::Question,
+        gtk::ButtonsType::YesNo,
+        "Import relay list?",
+    );
+    dialog.set_secondary_text(Some(&format!(
+        "Your relay list published elsewhere differs from relays used here:\n\n{listed}"
+    )));
+
+    dialog.connect_response(move |dialog, response| {
+        if response == gtk::ResponseType::Yes {
+            sender.input(MainInput::ImportRelayList(relays.clone()));
+        }
+        dialog.close();
+    });
+
+    dialog.show();
+}
+
+async fn publish(gnostique: Gnostique, builder: EventBuilder, sender: AsyncComponentSender<Main>) {
+    if let Err(e) = gnostique.publish(builder).await {
+        warn!("Could not publish event: {}", e);
+        sender.input(MainInput::Toast(Toast::error(format!(
+            "Could not publish: {e}"
+        ))));
+    }
+}
+
+async fn open_image(gnostique: Gnostique, url: Url, sender: AsyncComponentSender<Main>) {
+    if let Some(file) = gnostique
+        .download()
+        .to_cached_file(&url, Media::Image)
+        .await
+        .file()
+    {
+        let decoded = relm4::spawn_blocking({
+            let file = file.clone();
+            move || decode_for_display(&file)
+        })
+        .await;
+
+        match decoded {
+            Ok(Ok(bitmap)) => sender.input(MainInput::ShowImage(ViewedImage {
+                url,
+                file,
+                bitmap: Arc::new(bitmap),
+            })),
+            Ok(Err(e)) => warn!("Could not load {:?}: {}", file, e),
+            Err(e) => warn!("Could not load image from {}: {}", url, e),
+        }
     }
 }
 
@@ -1,13 +1,69 @@
 pub mod app;
+pub(crate) mod archive;
+pub(crate) mod article;
 pub(crate) mod author;
+pub(crate) mod avatars;
+pub(crate) mod confirm;
+pub(crate) mod contacts;
 pub(crate) mod details;
 pub mod editprofile;
+pub(crate) mod emojis;
+pub(crate) mod entity_entry;
+pub(crate) mod gallery;
+pub(crate) mod import_follows;
 pub mod lane;
 pub mod lane_header;
 pub mod main;
 pub(crate) mod note;
+pub(crate) mod open;
+pub(crate) mod outbox;
+pub(crate) mod pending;
 pub mod profilebox;
+pub(crate) mod prune;
+pub(crate) mod relays;
 pub(crate) mod replies;
+pub(crate) mod report;
+pub(crate) mod settings;
+pub(crate) mod shortcuts;
 pub(crate) mod statusbar;
+pub(crate) mod theme;
+pub(crate) mod toast;
 mod unlock;
+pub(crate) mod viewer;
 pub mod writenote;
+
+use gtk::prelude::ApplicationExt;
+use relm4::{gtk, RelmApp};
+
+use crate::app::init::Setup;
+
+/// Runs the graphical application until its window is closed, or until
+/// user quits if it should keep running without window.
+pub fn run(setup: Setup) {
+    let app = RelmApp::new("com.jirijakes.gnostique");
+
+    // GTK and resources
+    gtk::glib::set_application_name("Gnostique");
+    gtk::gio::resources_register_include!("resources.gresource").unwrap();
+    let provider = gtk::CssProvider::new();
+    provider.load_from_resource("/com/jirijakes/gnostique/ui/style.css");
+    if let Some(display) = gtk::gdk::Display::default() {
+        gtk::StyleContext::add_provider_for_display(
+            &display,
+            &provider,
+            gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
+        )
+    };
+
+    let icon_theme = gtk::IconTheme::for_display(&gtk::gdk::Display::default().unwrap());
+    icon_theme.add_resource_path("/com/jirijakes/gnostique/icons");
+
+    theme::apply_theme(setup.config.appearance.theme);
+    theme::load_user_style(&setup.dirs.style_file());
+
+    // Starting Gnostique again, while it keeps running without window,
+    // brings the window back.
+    relm4::main_application().connect_activate(|_| crate::app::action::present_window());
+
+    app.run::<app::App>(setup);
+}
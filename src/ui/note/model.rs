@@ -1,30 +1,152 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use chrono::{DateTime, Utc};
 use gtk::gdk;
+use gtk::prelude::*;
+use nostr_sdk::nostr::prelude::XOnlyPublicKey;
 use nostr_sdk::nostr::*;
 use relm4::component::{AsyncComponentController, AsyncController};
 use relm4::prelude::*;
 
+use super::msg::NoteOutput;
+use crate::content::{Collapse, Target};
+use crate::handlers::Handler;
+use crate::lightning::{Invoice, PaymentRequest};
 use crate::nostr::*;
+use crate::preview::LinkPreview;
+use crate::timestamps::{format_full, TimeFormat};
+use crate::ui::emojis::EmojiLabel;
+use crate::ui::gallery::Gallery;
 use crate::ui::replies::{Replies, RepliesInput};
 
+/// Notes with at least this proof of work display it.
+const NOTABLE_POW: u8 = 10;
+
+/// Preview of the note replied to has at most this many words, the rest
+/// would not fit on its line anyway.
+const PARENT_PREVIEW_WORDS: usize = 40;
+
+/// Replies deeper in threaded lane are not indented any further.
+pub(super) const MAX_INDENT: usize = 3;
+
 #[derive(Debug)]
 pub struct Note {
     pub(super) content: String,
+    /// Label displaying the content.
+    pub(super) content_label: EmojiLabel,
+    /// How long the content can be before it is collapsed.
+    pub(super) collapse: Collapse,
+    /// Whether the content is longer than it can be.
+    pub(super) collapsible: bool,
+    /// Whether only beginning of the content is displayed, if it is too long.
+    pub(super) collapsed: bool,
+    /// Custom emoji the note defines.
+    pub(super) emojis: Vec<Emoji>,
+    /// Custom emoji already downloaded, of both content and reactions.
+    pub(super) emoji_bitmaps: HashMap<Url, Arc<gdk::Texture>>,
+    /// Subject of the note according to NIP-14.
+    pub(super) subject: Option<String>,
     pub(super) is_central: bool,
+    /// Whether we are the author of the note.
+    pub(super) is_own: bool,
+    /// Whether the note, not our own, mentions us by a `p` tag.
+    pub(super) mentions_me: bool,
+    /// Whether the note is in our bookmarks.
+    pub(super) bookmarked: bool,
+    /// Whether the note is pinned to profile displayed by the lane,
+    /// above all other notes.
+    pub pinned: bool,
     pub(super) author: Persona,
+    /// Known users mentioned in this note.
+    pub(super) mentions: HashMap<XOnlyPublicKey, Persona>,
     pub(super) show_hidden_buttons: bool,
     pub(super) avatar: Arc<gdk::Texture>,
     pub(super) likes: u32,
     pub(super) dislikes: u32,
+    /// Reactions by custom emoji, every emoji counted separately.
+    pub(super) custom_reactions: Vec<CustomReaction>,
+    pub(super) custom_reactions_box: gtk::FlowBox,
+    /// Number of zaps of the note and zapped amount in sats.
+    pub(super) zaps: u32,
+    pub(super) sats: u64,
     pub time: DateTime<Utc>,
-    pub(super) event: Arc<Event>,
+    /// How deep in thread the note is displayed in threaded lane, zero
+    /// for notes displayed chronologically.
+    pub depth: usize,
+    /// Whether notes above this one are new and this one is not.
+    pub divider: bool,
+    pub event: Arc<Event>,
     pub(super) relays: Vec<Url>,
     pub(super) replies: AsyncController<Replies>,
+    pub(super) gallery: Controller<Gallery>,
+    /// URLs of images embedded in this note.
+    pub(super) images: Vec<Url>,
+    /// URL of web page whose preview is displayed under the content.
+    pub(super) preview_url: Option<Url>,
+    pub(super) preview: Option<Arc<LinkPreview>>,
+    pub(super) preview_bitmap: Option<Arc<gdk::Texture>>,
+    /// Reason of content warning, if the note has one.
+    pub(super) content_warning: Option<String>,
+    /// Labels we have given to the author, which hide the content
+    /// just like a content warning.
+    pub(super) labels: Vec<String>,
+    /// Whether content hidden behind content warning is shown.
+    pub(super) revealed: bool,
+    /// Time after which the note should disappear according to NIP-40.
+    pub(super) expiration: Option<Timestamp>,
+    /// Whether the note has already expired but is still displayed.
+    pub(super) expired: bool,
+    /// Whether it is our note which no relay has accepted yet.
+    pub(super) pending: bool,
     pub(super) repost_author: Option<Persona>,
     pub(super) repost: Option<Event>,
     pub(super) age: String,
+    /// How the age of the note is shown.
+    pub(super) time_format: TimeFormat,
+    /// Cards of notes quoted by this note.
+    pub(super) quotes: gtk::Box,
+    /// Notes already displayed as quoted.
+    pub(super) quoted: Vec<EventId>,
+    /// Cards of lightning invoices and LNURLs written in the content.
+    pub(super) payments: gtk::Box,
+    /// Invoices of the cards and labels telling when they expire.
+    pub(super) invoice_expiries: Vec<(Invoice, gtk::Label)>,
+    /// Long-form article, if this note displays one instead of text note.
+    pub(super) article: Option<Article>,
+    /// Whether the event is of a kind Gnostique cannot display, so that
+    /// the note is just its placeholder.
+    pub(super) unsupported: bool,
+    /// Application which can display the unsupported event.
+    pub(super) handler: Option<Handler>,
+    /// Who signed the note on behalf of its author, if it is delegated (NIP-26).
+    pub(super) delegatee: Option<XOnlyPublicKey>,
+    /// Whether the content can be translated.
+    pub(super) translatable: bool,
+    /// Whether translation has been requested and has not arrived yet.
+    pub(super) translating: bool,
+    /// Translation of the content, or why it could not be obtained.
+    pub(super) translation: Option<Result<String, String>>,
+    /// Whether the translation is shown below the original.
+    pub(super) show_translation: bool,
+    /// Text note this note replies to, if any, and relays hinted for it.
+    pub(super) parent: Option<(EventId, Vec<Url>)>,
+    /// The note replied to, once it is known.
+    pub(super) parent_note: Option<Quote>,
+}
+
+/// Reactions to the note by the same custom emoji.
+#[derive(Debug)]
+pub(super) struct CustomReaction {
+    pub(super) emoji: Emoji,
+    pub(super) count: u32,
+    pub(super) label: EmojiLabel,
+}
+
+impl CustomReaction {
+    fn markup(&self) -> String {
+        format!(":{}: {}", self.emoji.shortcode, self.count)
+    }
 }
 
 impl Note {
@@ -62,46 +184,585 @@ impl Note {
         }
     }
 
+    /// Renders content of the note again and displays it.
+    pub(super) fn refresh_content(&mut self) {
+        (self.content, self.collapsible) = self.render_content();
+        self.content_label
+            .set_markup(&self.content, &self.emojis, &self.emoji_bitmaps);
+    }
+
+    /// Counts `count` reactions by custom `emoji`.
+    pub(super) fn add_custom_reaction(&mut self, emoji: Emoji, count: u32) {
+        let reaction = match self.custom_reactions.iter_mut().find(|r| r.emoji == emoji) {
+            Some(r) => r,
+            None => {
+                let label = EmojiLabel::new();
+                label.widget().add_css_class("custom-reaction");
+                self.custom_reactions_box.insert(label.widget(), -1);
+                self.custom_reactions.push(CustomReaction {
+                    emoji,
+                    count: 0,
+                    label,
+                });
+                self.custom_reactions.last_mut().unwrap()
+            }
+        };
+
+        reaction.count += count;
+        reaction.label.set_markup(
+            &reaction.markup(),
+            std::slice::from_ref(&reaction.emoji),
+            &self.emoji_bitmaps,
+        );
+        self.custom_reactions_box.set_visible(true);
+    }
+
+    /// Displays custom emoji from `url`, wherever the note uses it.
+    pub(super) fn emoji_bitmap(&mut self, url: Url, bitmap: Arc<gdk::Texture>) {
+        self.emoji_bitmaps.insert(url.clone(), bitmap);
+
+        if self.emojis.iter().any(|e| e.url == url) {
+            self.content_label
+                .set_markup(&self.content, &self.emojis, &self.emoji_bitmaps);
+        }
+
+        for reaction in self.custom_reactions.iter().filter(|r| r.emoji.url == url) {
+            reaction.label.set_markup(
+                &reaction.markup(),
+                std::slice::from_ref(&reaction.emoji),
+                &self.emoji_bitmaps,
+            );
+        }
+    }
+
+    /// Renders content of the note, showing names of known users
+    /// where they are mentioned. Articles show just their summary.
+    /// Tells also whether the content is too long, only its beginning
+    /// is rendered then if the note is collapsed.
+    pub(super) fn render_content(&self) -> (String, bool) {
+        /// Articles without summary show this many characters of their text.
+        const SUMMARY_LEN: usize = 280;
+
+        if self.unsupported {
+            let text = format!(
+                "Unsupported event (kind {}) from {}",
+                self.event.kind.as_u64(),
+                self.author.shown_name()
+            );
+            return (format!("<i>{}</i>", html_escape::encode_text(&text)), false);
+        }
+
+        if let Some(article) = &self.article {
+            let summary = match &article.summary {
+                Some(s) => s.clone(),
+                None => {
+                    let mut s: String = article.event.content.chars().take(SUMMARY_LEN).collect();
+                    if article.event.content.chars().count() > SUMMARY_LEN {
+                        s.push('…');
+                    }
+                    s
+                }
+            };
+            return (html_escape::encode_text(&summary).to_string(), false);
+        }
+
+        let name_of = |pubkey: &XOnlyPublicKey| {
+            if pubkey == &self.author.pubkey {
+                self.author.known_name().map(|n| n.to_string())
+            } else {
+                self.mentions
+                    .get(pubkey)
+                    .and_then(|p| p.known_name())
+                    .map(|n| n.to_string())
+            }
+        };
+
+        let (content, tags) = (&self.event.content, &self.event.tags);
+        match crate::content::to_collapsed_markup(content, tags, name_of, self.collapse) {
+            Some(collapsed) if self.collapsed => (collapsed, true),
+            Some(_) => (crate::content::to_markup(content, tags, name_of), true),
+            None => (crate::content::to_markup(content, tags, name_of), false),
+        }
+    }
+
+    /// Displays `quote` as a card under the content, unless already displayed.
+    /// Quoted notes never display their own quotes, so that quote chains
+    /// do not nest any deeper. Clicking the card opens thread of the quote.
+    pub(super) fn add_quote(&mut self, quote: Quote, sender: &FactorySender<Note>) {
+        if self.quoted.contains(&quote.event.id) {
+            return;
+        }
+        self.quoted.push(quote.event.id);
+
+        let author = quote
+            .author
+            .unwrap_or_else(|| Persona::new(quote.event.pubkey));
+
+        let name = gtk::Label::new(Some(&author.shown_name()));
+        name.set_xalign(0.0);
+        name.add_css_class("author-name");
+
+        let content = gtk::Label::new(None);
+        content.set_markup(&crate::content::to_markup(
+            &quote.event.content,
+            &quote.event.tags,
+            |pubkey| {
+                (pubkey == &author.pubkey)
+                    .then(|| author.known_name().map(|n| n.to_string()))
+                    .flatten()
+            },
+        ));
+        content.set_xalign(0.0);
+        content.set_wrap(true);
+        content.set_wrap_mode(gtk::pango::WrapMode::WordChar);
+        content.set_lines(6);
+        content.set_ellipsize(gtk::pango::EllipsizeMode::End);
+        // The card only shows what is quoted, its links lead nowhere.
+        content.connect_activate_link(|_, _| gtk::Inhibit(true));
+
+        let card = gtk::Box::new(gtk::Orientation::Vertical, 4);
+        card.add_css_class("quote");
+        card.append(&name);
+        card.append(&content);
+
+        let id = quote.event.id;
+        let hinted = crate::content::quoted(&self.event.content)
+            .into_iter()
+            .find(|(quoted, _)| *quoted == id)
+            .map(|(_, relays)| relays)
+            .unwrap_or_default();
+        let click = gtk::GestureClick::new();
+        click.connect_released({
+            let sender = sender.clone();
+            move |_, _, _, _| sender.output(NoteOutput::Open(Target::Note(id, hinted.clone())))
+        });
+        card.add_controller(&click);
+
+        self.quotes.append(&card);
+    }
+
+    /// Displays card of lightning payment `request` with buttons
+    /// to copy it and to pay it by a wallet.
+    pub(super) fn add_payment_request(&mut self, request: PaymentRequest) {
+        let card = gtk::Box::new(gtk::Orientation::Vertical, 4);
+        card.add_css_class("payment-request");
+
+        let title = gtk::Label::new(None);
+        title.set_xalign(0.0);
+        title.add_css_class("title");
+        card.append(&title);
+
+        match &request {
+            PaymentRequest::Invoice(invoice) => {
+                title.set_label(&match invoice.amount_sats() {
+                    Some(sats) => format!("⚡ Invoice for {sats} sats"),
+                    None => "⚡ Invoice for any amount".to_string(),
+                });
+
+                if let Some(description) = invoice.description.as_deref().filter(|d| !d.is_empty())
+                {
+                    let label = gtk::Label::new(Some(description));
+                    label.set_xalign(0.0);
+                    label.set_wrap(true);
+                    label.set_wrap_mode(gtk::pango::WrapMode::WordChar);
+                    label.add_css_class("description");
+                    card.append(&label);
+                }
+
+                let expiry = gtk::Label::new(None);
+                expiry.set_xalign(0.0);
+                expiry.add_css_class("expiry");
+                card.append(&expiry);
+                self.invoice_expiries.push((invoice.clone(), expiry));
+            }
+            PaymentRequest::Lnurl { url, .. } => {
+                title.set_label("⚡ LNURL");
+
+                let service = gtk::Label::new(url.host_str());
+                service.set_xalign(0.0);
+                service.set_tooltip_text(Some(url.as_str()));
+                service.add_css_class("description");
+                card.append(&service);
+            }
+        }
+
+        let copy = gtk::Button::with_label("Copy");
+        copy.connect_clicked({
+            let text = request.text().to_string();
+            move |button| button.clipboard().set_text(&text)
+        });
+
+        let open = gtk::Button::with_label("Open in wallet");
+        open.connect_clicked({
+            let uri = request.uri();
+            move |_| gtk::show_uri(None::<&gtk::Window>, &uri, gdk::CURRENT_TIME)
+        });
+
+        let buttons = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+        buttons.append(&copy);
+        buttons.append(&open);
+        card.append(&buttons);
+
+        self.payments.append(&card);
+        self.refresh_invoice_expiries();
+    }
+
+    /// Tells when invoices expire, or that they already have.
+    pub(super) fn refresh_invoice_expiries(&self) {
+        let now = Timestamp::now();
+
+        for (invoice, label) in &self.invoice_expiries {
+            let when = format_full(invoice.expires_at(), self.time_format);
+            if invoice.is_expired(now) {
+                label.set_label(&format!("Expired {when}"));
+                if let Some(card) = label.parent() {
+                    card.add_css_class("expired");
+                }
+            } else {
+                label.set_label(&format!("Expires {when}"));
+            }
+        }
+    }
+
+    /// Where the application which can display the unsupported event does so.
+    pub(super) fn handler_url(&self) -> Option<Url> {
+        self.handler.as_ref()?.url_for(&self.event, &self.relays)
+    }
+
+    pub(super) fn format_handler(&self) -> String {
+        match &self.handler {
+            Some(handler) => format!("Open in {}", handler.shown_name()),
+            None => String::new(),
+        }
+    }
+
+    /// Whether content of this note is visible.
+    pub(super) fn show_content(&self) -> bool {
+        (self.content_warning.is_none() && self.labels.is_empty()) || self.revealed
+    }
+
+    /// Whether the note offers translation of its content. Articles are
+    /// too long to be sent for translation.
+    pub(super) fn can_translate(&self) -> bool {
+        self.translatable
+            && self.show_content()
+            && self.article.is_none()
+            && !self.unsupported
+            && !self.event.content.trim().is_empty()
+    }
+
+    pub(super) fn format_translate(&self) -> &'static str {
+        match (&self.translation, self.show_translation) {
+            (Some(Ok(_)), true) => "Show original",
+            (Some(Ok(_)), false) => "Show translation",
+            _ if self.translating => "Translating…",
+            _ => "Translate",
+        }
+    }
+
+    /// Translation displayed below the content, if any.
+    pub(super) fn shown_translation(&self) -> Option<&str> {
+        match &self.translation {
+            Some(Ok(translation)) if self.show_translation => Some(translation),
+            _ => None,
+        }
+    }
+
+    /// Why the content could not be translated, if it could not.
+    pub(super) fn translation_error(&self) -> Option<&str> {
+        match &self.translation {
+            Some(Err(e)) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Asks for media of content which has been hidden until now.
+    pub(super) fn load_media(&self, sender: &FactorySender<Note>) {
+        if !self.images.is_empty() {
+            sender.output(NoteOutput::NeedImages(self.images.clone()));
+        }
+        if let Some(url) = &self.preview_url {
+            sender.output(NoteOutput::NeedPreview(url.clone()));
+        }
+    }
+
+    /// Whether the note tells what it replies to, which it does unless
+    /// it is displayed below it.
+    pub(super) fn show_parent(&self) -> bool {
+        self.parent.is_some() && self.depth == 0
+    }
+
+    pub(super) fn format_replying_to(&self) -> String {
+        replying_to(self.parent_note.as_ref())
+    }
+
+    /// Beginning of the note replied to on a single line.
+    pub(super) fn format_parent_preview(&self) -> String {
+        parent_preview(self.parent_note.as_ref())
+    }
+
+    pub(super) fn format_content_warning(&self) -> String {
+        content_warning(self.content_warning.as_deref(), &self.labels)
+    }
+
+    /// Whether preview of the linked web page is displayed.
+    pub(super) fn show_preview(&self) -> bool {
+        self.show_content() && self.preview.is_some()
+    }
+
+    /// Domain of the previewed web page.
+    pub(super) fn format_preview_domain(&self) -> String {
+        self.preview
+            .as_ref()
+            .and_then(|p| p.url.domain())
+            .unwrap_or_default()
+            .to_string()
+    }
+
     /// Generates textual representation of the age of this text note. It is
     /// relatively fuzzy and serves to inform reader about the rough duration
-    /// since the note was broadcast.
+    /// since the note was broadcast, unless absolute times are preferred.
     pub(super) fn format_age(&self) -> String {
-        use chrono::*;
+        crate::timestamps::format_age(self.event.created_at, Timestamp::now(), self.time_format)
+    }
 
-        let created_at =
-            NaiveDateTime::from_timestamp_opt(self.event.created_at.as_i64(), 0).unwrap();
-        let duration = Utc::now().naive_utc().signed_duration_since(created_at);
+    /// Generates badge with proof of work of the note, if it is notable.
+    pub(super) fn format_pow(&self) -> Option<String> {
+        let pow = self.event.pow();
+        (pow >= NOTABLE_POW).then(|| format!("⛏ {pow}"))
+    }
 
-        if duration.num_weeks() > 0 {
-            // If the duration is a week or more, let's show date instead of
-            // duration since creation.
-            let utc = DateTime::<Utc>::from_utc(created_at, Utc);
-            let local = utc.with_timezone(&Local);
+    /// Generates badge with zapped amount, if the note has been zapped.
+    pub(super) fn format_zaps(&self) -> Option<String> {
+        (self.zaps > 0).then(|| format!("⚡ {}", self.sats))
+    }
 
-            if local.year() == Local::now().year() {
-                // It's this year ⇒ just day and month
-                local.format("%e %b").to_string()
-            } else {
-                // It's before this year ⇒ day and month and year
-                local.format("&e %b %Y").to_string()
-            }
-        } else if duration.num_days() > 0 {
-            format!("{}d", duration.num_days())
-        } else if duration.num_hours() > 0 {
-            format!("{}h", duration.num_hours())
-        } else if duration.num_minutes() > 0 {
-            format!("{}m", duration.num_minutes())
+    pub(super) fn format_zaps_tooltip(&self) -> String {
+        match self.zaps {
+            1 => format!("1 zap, {} sats", self.sats),
+            n => format!("{n} zaps, {} sats", self.sats),
+        }
+    }
+
+    /// Generates badge with number of relays the note has been seen on.
+    pub(super) fn format_relays(&self) -> String {
+        format!("📡 {}", self.relays.len())
+    }
+
+    /// Generates tooltip for relay badge, listing the relays.
+    pub(super) fn format_relays_tooltip(&self) -> String {
+        let relays: Vec<&str> = self.relays.iter().map(|r| r.as_str()).collect();
+        format!("Seen on:\n{}", relays.join("\n"))
+    }
+
+    /// Generates hint about who posted the note for its author, if delegated.
+    pub(super) fn format_delegatee(&self) -> Option<String> {
+        let delegatee = Persona::new(self.delegatee?);
+        Some(format!("posted via {}", delegatee.short_pubkey()))
+    }
+
+    /// Generates hint about expiration of the note, if it expires within a day.
+    pub(super) fn format_expiration(&self) -> Option<String> {
+        let expiration = self.expiration?.as_i64();
+        let remaining = expiration - Timestamp::now().as_i64();
+
+        if self.expired || remaining <= 0 {
+            Some("Expired".to_string())
+        } else if remaining < 60 {
+            Some("Expires in < 1m".to_string())
+        } else if remaining < 60 * 60 {
+            Some(format!("Expires in {}m", remaining / 60))
+        } else if remaining < 24 * 60 * 60 {
+            Some(format!("Expires in {}h", remaining / 60 / 60))
         } else {
-            "< 1m".to_string()
+            None
         }
     }
 
     /// Generates tooltip for note age indicator. It always shows precise time.
     pub(super) fn format_age_tooltip(&self) -> String {
-        let format = "%A, %e %B %Y, %T";
-        let local = self.time.with_timezone(&chrono::Local).format(format);
-        let utc = self.time.format(format);
+        let local = crate::timestamps::format_full(self.event.created_at, self.time_format);
+        let utc = self.time.format("%A, %e %B %Y, %T");
 
         format!("<b>Local:</b> {local}\n<b>UTC:</b> {utc}")
     }
 }
+
+/// Whether `event` is ours, also when delegated by us, and whether it
+/// otherwise mentions `me`, for which notes are highlighted.
+pub fn highlighting(event: &Event, me: &XOnlyPublicKey) -> (bool, bool) {
+    let is_own = event.author() == *me;
+    (is_own, !is_own && event.mentions().contains(me))
+}
+
+/// Why content is hidden: its content `warning` (NIP-36), if any, and
+/// `labels` we have given to its author.
+fn content_warning(warning: Option<&str>, labels: &[String]) -> String {
+    let warning = match warning {
+        Some("") => Some("Content warning".to_string()),
+        Some(reason) => Some(format!("Content warning: {reason}")),
+        None => None,
+    };
+    let labelled = (!labels.is_empty()).then(|| {
+        let labels: Vec<&str> = labels.iter().map(|l| crate::labels::describe(l)).collect();
+        format!("You have marked the author as {}", labels.join(", "))
+    });
+
+    match (warning, labelled) {
+        (Some(warning), Some(labelled)) => format!("{warning}. {labelled}"),
+        (Some(text), None) | (None, Some(text)) => text,
+        (None, None) => "Content warning".to_string(),
+    }
+}
+
+/// Who `parent` replied to is by, if it is known yet.
+fn replying_to(parent: Option<&Quote>) -> String {
+    match parent {
+        Some(parent) => {
+            let name = match &parent.author {
+                Some(author) => author.shown_name(),
+                None => Persona::new(parent.event.pubkey).shown_name(),
+            };
+            format!("Replying to @{name}")
+        }
+        None => "Replying to a note not received yet".to_string(),
+    }
+}
+
+/// Beginning of `parent` replied to on a single line.
+fn parent_preview(parent: Option<&Quote>) -> String {
+    parent
+        .map(|p| {
+            p.event
+                .content
+                .split_whitespace()
+                .take(PARENT_PREVIEW_WORDS)
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Fixtures;
+
+    #[test]
+    fn own_notes_and_mentions_of_us_are_highlighted() {
+        let (me, other) = (Fixtures::new(), Fixtures::new());
+        let mention = |pubkey| [Tag::PubKey(pubkey, None)];
+
+        for (event, expected) in [
+            (me.text_note("Mine"), (true, false)),
+            (
+                me.sign(
+                    Kind::TextNote,
+                    "Me",
+                    &mention(me.public_key()),
+                    Timestamp::now(),
+                ),
+                (true, false),
+            ),
+            (
+                other.sign(
+                    Kind::TextNote,
+                    "You",
+                    &mention(me.public_key()),
+                    Timestamp::now(),
+                ),
+                (false, true),
+            ),
+            (
+                other.sign(
+                    Kind::TextNote,
+                    "Them",
+                    &mention(other.public_key()),
+                    Timestamp::now(),
+                ),
+                (false, false),
+            ),
+            (
+                other.sign(
+                    Kind::TextNote,
+                    "Delegated",
+                    &[
+                        me.delegation(&other, "kind=1"),
+                        mention(me.public_key())[0].clone(),
+                    ],
+                    Timestamp::now(),
+                ),
+                (true, false),
+            ),
+        ] {
+            assert_eq!(
+                highlighting(&event, &me.public_key()),
+                expected,
+                "{}",
+                event.content
+            );
+        }
+    }
+
+    #[test]
+    fn hidden_content_tells_why() {
+        let labels = vec![crate::labels::NSFW.to_string(), "bot".to_string()];
+
+        assert_eq!(content_warning(Some(""), &[]), "Content warning");
+        assert_eq!(
+            content_warning(Some("spoilers"), &[]),
+            "Content warning: spoilers"
+        );
+        assert_eq!(
+            content_warning(None, &labels),
+            "You have marked the author as NSFW, bot"
+        );
+        assert_eq!(
+            content_warning(Some("spoilers"), &labels[..1]),
+            "Content warning: spoilers. You have marked the author as NSFW"
+        );
+    }
+
+    #[test]
+    fn reply_tells_whom_it_replies_to() {
+        let author = Fixtures::new();
+        let event = author.text_note("Hello");
+        let mut persona = Persona::new(author.public_key());
+        persona.name = Some("alice".to_string());
+
+        let named = Quote {
+            event: event.clone(),
+            author: Some(persona),
+        };
+        assert_eq!(replying_to(Some(&named)), "Replying to @alice");
+
+        let anonymous = Quote {
+            event,
+            author: None,
+        };
+        assert_eq!(
+            replying_to(Some(&anonymous)),
+            format!(
+                "Replying to @{}",
+                Persona::new(author.public_key()).shown_name()
+            )
+        );
+        assert_eq!(replying_to(None), "Replying to a note not received yet");
+    }
+
+    #[test]
+    fn parent_preview_is_one_line_of_few_words() {
+        let words: Vec<String> = (0..50).map(|i| format!("w{i}")).collect();
+        let content = format!("  {}\n\n{}", words[..10].join(" "), words[10..].join("\t"));
+        let parent = Quote {
+            event: Fixtures::new().text_note(&content),
+            author: None,
+        };
+
+        assert_eq!(
+            parent_preview(Some(&parent)),
+            words[..PARENT_PREVIEW_WORDS].join(" ")
+        );
+        assert_eq!(parent_preview(None), "");
+    }
+}
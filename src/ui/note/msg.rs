@@ -3,7 +3,12 @@ use std::sync::Arc;
 use gtk::gdk;
 use nostr_sdk::prelude::*;
 
+use crate::content::{Collapse, Target};
+use crate::handlers::Handler;
 use crate::nostr::*;
+use crate::preview::LinkPreview;
+use crate::reactions::ReactionCounts;
+use crate::timestamps::TimeFormat;
 use crate::ui::details::Details;
 
 /// Initial
@@ -12,7 +17,35 @@ pub struct NoteInit {
     pub relays: Vec<Url>,
     pub author: Option<Persona>,
     pub is_central: bool,
+    /// Whether we are the author of the note.
+    pub is_own: bool,
+    /// Whether the note, not our own, mentions us by a `p` tag.
+    pub mentions_me: bool,
+    /// Labels we have given to the author, which hide the content.
+    pub labels: Vec<String>,
+    /// Whether the note is in our bookmarks.
+    pub bookmarked: bool,
+    /// Whether the note is pinned to profile displayed by the lane.
+    pub pinned: bool,
     pub repost: Option<Repost>,
+    /// Known users mentioned in the note.
+    pub mentions: Vec<Persona>,
+    /// Already available notes quoted by the note.
+    pub quotes: Vec<Quote>,
+    pub time_format: TimeFormat,
+    /// How deep in thread the note is displayed.
+    pub depth: usize,
+    /// How long the content can be before it is collapsed.
+    pub collapse: Collapse,
+    /// Reactions to the note and its zaps known so far.
+    pub reactions: ReactionCounts,
+    /// Whether notes can be translated, that is a translation server is
+    /// configured.
+    pub translatable: bool,
+    /// Already available note this note replies to.
+    pub parent: Option<Quote>,
+    /// Whether it is our note which no relay has accepted yet.
+    pub pending: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -36,7 +69,11 @@ pub enum NoteInput {
     Reaction {
         event: EventId,
         reaction: String,
+        /// Custom emoji the reaction consists of (NIP-30).
+        emoji: Option<Emoji>,
     },
+    /// The note has been zapped with this many sats.
+    Zap(u64),
 
     Nip05Verified(XOnlyPublicKey),
     TextNote {
@@ -45,11 +82,91 @@ pub enum NoteInput {
         author: Option<Persona>,
         repost: Option<Repost>,
     },
-    Tick,
+    /// The note has been seen on another relay.
+    SeenOn(Url),
+    /// Time has passed, or the way it is shown has changed.
+    Tick(TimeFormat),
+    /// Image embedded in this note has been downloaded and decoded.
+    ImageBitmap {
+        url: Url,
+        bitmap: Arc<gdk::Texture>,
+    },
+    /// Custom emoji used by this note or its reactions has been downloaded and decoded.
+    EmojiBitmap {
+        url: Url,
+        bitmap: Arc<gdk::Texture>,
+    },
+    /// Show content hidden behind content warning.
+    Reveal,
+    /// Show whole long content, or just its beginning again.
+    ToggleCollapsed,
+    /// Limits of collapsed content have changed.
+    Collapse(Collapse),
+    /// Show embedded image in full size.
+    OpenImage(Url),
+    /// Preview of the web page linked from this note has been obtained.
+    LinkPreview(Arc<LinkPreview>),
+    /// Profile of a user mentioned in this note has some new data.
+    MentionUpdated(Persona),
+    /// Open the previewed web page in browser.
+    OpenPreview,
+    /// Write a reply to this note.
+    Reply,
+    /// Write a note quoting this note.
+    Quote,
+    /// Note quoted by this note has arrived.
+    Quoted(Quote),
+    /// Read the whole article displayed by this note.
+    OpenArticle,
+    /// Application which can display this event of unsupported kind.
+    Handler(Handler),
+    /// Open this event in the application which can display it.
+    OpenInHandler,
+    /// The note has expired according to NIP-40.
+    Expired,
+    /// Our note has been accepted by a relay.
+    Delivered,
+    /// Add this note to bookmarks, or remove it from there.
+    ToggleBookmark,
+    /// This note has been added to bookmarks, or removed from there.
+    Bookmarked(bool),
+    /// Translate the content, or switch between the translation and
+    /// the original.
+    Translate,
+    /// Translation of the content has arrived, or why it has not.
+    Translated(Result<String, String>),
+    /// Translation server has been configured, or it has been removed.
+    Translatable(bool),
+    /// Note this note replies to has arrived.
+    Parent(Quote),
+    /// Open thread of the note this note replies to.
+    OpenParent,
+    /// These are all labels we have given to the author now.
+    Labels {
+        author: XOnlyPublicKey,
+        labels: Vec<String>,
+    },
 }
 
 #[derive(Debug)]
 pub enum NoteOutput {
     ShowDetails(Details),
-    LinkClicked(String),
+    /// User wants to see the profile, thread or hashtag.
+    Open(Target),
+    /// Images from these URLs should be downloaded.
+    NeedImages(Vec<Url>),
+    /// Image from this URL should be shown in full size.
+    OpenImage(Url),
+    /// Preview of web page at this URL should be obtained.
+    NeedPreview(Url),
+    /// User wants to reply to the event.
+    Reply(Arc<Event>),
+    /// User wants to quote the event, which has been seen at the relays.
+    Quote(Arc<Event>, Vec<Url>),
+    /// User wants to read the article written by the author.
+    OpenArticle(Box<Article>, Box<Persona>),
+    /// User wants to add the note to bookmarks, or remove it from there.
+    Bookmark(EventId, bool),
+    /// User wants to read the note with this content translated.
+    Translate(EventId, String),
 }
@@ -1,19 +1,26 @@
-use std::time::Duration;
+use std::collections::HashMap;
+use std::sync::Arc;
 
 use chrono::{TimeZone, Utc};
 use gtk::gdk;
 use gtk::pango::WrapMode;
 use gtk::prelude::*;
-use nostr_sdk::prelude::ToBech32;
+use nostr_sdk::prelude::{Kind, Timestamp, ToBech32};
 use relm4::component::{AsyncComponent, AsyncComponentController};
 use relm4::prelude::*;
+use tracing::info;
 
 use super::model::*;
 use super::msg::*;
 use crate::app::action::*;
+use crate::content::Target;
 use crate::nostr::*;
+use crate::reactions::Reaction;
 use crate::ui::author::Author;
+use crate::ui::avatars::placeholder;
 use crate::ui::details::Details;
+use crate::ui::emojis::EmojiLabel;
+use crate::ui::gallery::{Gallery, GalleryInput, GalleryOutput};
 use crate::ui::lane::LaneMsg;
 use crate::ui::replies::{Replies, RepliesInput};
 
@@ -44,6 +51,21 @@ impl FactoryComponent for Note {
     view! {
         gtk::Box {
             set_orientation: gtk::Orientation::Vertical,
+            #[watch] set_margin_start: 24 * self.depth.min(MAX_INDENT) as i32,
+
+            // boundary between new notes and read ones
+            gtk::Label {
+                add_css_class: "new-divider",
+                set_label: "— new —",
+                #[watch] set_visible: self.divider,
+            },
+
+            gtk::Label {
+                add_css_class: "pinned",
+                set_label: "📌 Pinned",
+                set_xalign: 0.0,
+                set_visible: self.pinned,
+            },
 
             // reposter
             gtk::Box {
@@ -62,22 +84,53 @@ impl FactoryComponent for Note {
                 Author {
                     #[template_child]
                     author_name {
-                        #[watch] set_label?: self.repost_author.as_ref().and_then(|a| a.name.as_ref()),
-                        #[watch] set_visible: self.repost_author.as_ref().and_then(|a| a.name.as_ref()).is_some(),
+                        #[watch] set_label?: &self.repost_author.as_ref().map(|a| a.shown_name()),
                     },
                     #[template_child]
                     author_pubkey {
-                        #[watch] set_label?: &self.repost_author.as_ref().map(|a| a.format_pubkey(8, 16)),
-                        #[watch] set_visible: self.repost_author.as_ref().and_then(|a| a.name.as_ref()).is_none(),
+                        set_visible: false,
+                    },
+                },
+            },
+
+            // note replied to
+            gtk::Button {
+                add_css_class: "replying-to",
+                set_has_frame: false,
+                #[watch] set_visible: self.show_parent(),
+                connect_clicked => NoteInput::OpenParent,
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 2,
+
+                    gtk::Label {
+                        set_xalign: 0.0,
+                        set_ellipsize: gtk::pango::EllipsizeMode::End,
+                        #[watch] set_label: &self.format_replying_to(),
+                    },
+
+                    gtk::Label {
+                        add_css_class: "parent-preview",
+                        set_xalign: 0.0,
+                        set_single_line_mode: true,
+                        set_ellipsize: gtk::pango::EllipsizeMode::End,
+                        #[watch] set_visible: self.parent_note.is_some(),
+                        #[watch] set_label: &self.format_parent_preview(),
                     },
                 },
             },
 
+            #[name(note_box)]
             gtk::Box {
                 set_orientation: gtk::Orientation::Horizontal,
                 set_hexpand: true,
                 add_css_class: "text-note",
                 add_css_class: if self.is_central { "central" } else { "text-note" },
+                #[watch] set_class_active: ("expired", self.expired),
+                #[watch] set_class_active: ("pending", self.pending),
+                set_class_active: ("own", self.is_own),
+                set_class_active: ("mentions-me", self.mentions_me),
 
                 // left column
                 gtk::Box {
@@ -116,69 +169,251 @@ impl FactoryComponent for Note {
 
                     // author
                     gtk::Overlay {
-                        #[template]
-                        #[name(author)]
-                        Author {
-                            #[watch]
-                            set_tooltip_markup: Some(&self.author.tooltip()),
-
-                            #[template_child]
-                            author_name {
-                                #[watch] set_label?: self.author.name.as_ref(),
-                                #[watch] set_visible: self.author.name.is_some(),
+                        gtk::Box {
+                            set_orientation: gtk::Orientation::Horizontal,
+                            set_spacing: 8,
+
+                            #[template]
+                            #[name(author)]
+                            Author {
+                                #[watch]
+                                set_tooltip_markup: Some(&self.author.tooltip()),
+
+                                #[template_child]
+                                author_name {
+                                    #[watch] set_label: &self.author.shown_name(),
+                                },
+                                #[template_child]
+                                author_pubkey {
+                                    #[watch] set_label: &self.author.format_pubkey(8, 16),
+                                    #[watch] set_visible: self.author.has_name() && !self.author.show_nip05(),
+                                },
+                                #[template_child]
+                                author_nip05 {
+                                    #[watch] set_label?: &self.author.format_nip05(),
+                                    #[watch] set_tooltip_text: self.author.nip05_tooltip().as_deref(),
+                                    #[watch] set_visible: self.author.show_nip05(),
+                                },
+
+                                add_controller = &gtk::GestureClick::new() {
+                                    set_button: 3,
+                                    connect_pressed[author, author_menu = author_menu.clone()] => move |_, _, x, y| {
+                                        let popover = gtk::PopoverMenu::builder()
+                                            .menu_model(&author_menu)
+                                            .has_arrow(false)
+                                            .pointing_to(&gdk::Rectangle::new(x as i32, y as i32, 1, 1))
+                                            .build();
+
+                                        popover.set_parent(author.widget_ref());
+                                        popover.popup();
+                                    }
+                                }
                             },
-                            #[template_child]
-                            author_pubkey {
-                                #[watch] set_label: &self.author.format_pubkey(8, 16),
-                                #[watch] set_visible: !self.author.show_nip05(),
+
+                            gtk::Label {
+                                add_css_class: "badge",
+                                add_css_class: "own-badge",
+                                set_label: "you",
+                                set_valign: gtk::Align::Center,
+                                set_visible: self.is_own,
                             },
-                            #[template_child]
-                            author_nip05 {
-                                #[watch] set_label?: &self.author.format_nip05(),
-                                #[watch] set_visible: self.author.show_nip05(),
+
+                            gtk::Label {
+                                add_css_class: "badge",
+                                add_css_class: "pending-badge",
+                                set_label: "sending…",
+                                set_tooltip_text: Some("No relay has accepted the note yet"),
+                                set_valign: gtk::Align::Center,
+                                #[watch] set_visible: self.pending,
                             },
 
-                            add_controller = &gtk::GestureClick::new() {
-                                set_button: 3,
-                                connect_pressed[author] => move |_, _, x, y| {
-                                    let popover = gtk::PopoverMenu::builder()
-                                        .menu_model(&author_menu)
-                                        .has_arrow(false)
-                                        .pointing_to(&gdk::Rectangle::new(x as i32, y as i32, 1, 1))
-                                        .build();
-
-                                    popover.set_parent(author.widget_ref());
-                                    popover.popup();
-                                }
-                            }
+                            gtk::Label {
+                                add_css_class: "badge",
+                                add_css_class: "mention-badge",
+                                set_label: "mentions you",
+                                set_valign: gtk::Align::Center,
+                                set_visible: self.mentions_me,
+                            },
                         },
                         add_overlay = &gtk::Label {
                             set_valign: gtk::Align::Start,
                             set_halign: gtk::Align::End,
-                            set_tooltip_markup: Some(&self.format_age_tooltip()),
+                            #[watch] set_tooltip_markup: Some(&self.format_age_tooltip()),
                             add_css_class: "note-age",
                             #[watch] set_label: &self.age,
                         }
                     },
 
-                    #[name(content)]
+                    // content warning
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_spacing: 12,
+                        add_css_class: "content-warning",
+                        #[watch] set_visible: !self.show_content(),
+
+                        gtk::Label {
+                            #[watch] set_label: &self.format_content_warning(),
+                            set_wrap: true,
+                            set_xalign: 0.0,
+                            set_hexpand: true,
+                        },
+
+                        gtk::Button::with_label("Show") {
+                            connect_clicked => NoteInput::Reveal
+                        }
+                    },
+
+                    // subject
                     gtk::Label {
-                        #[watch]
-                        set_markup: &self.content,
-                        set_wrap: true,
-                        set_wrap_mode: WrapMode::WordChar,
-                        set_halign: gtk::Align::Start,
-                        set_valign: gtk::Align::Start,
-                        set_vexpand: true,
+                        set_label: self.subject.as_deref().unwrap_or_default(),
+                        set_visible: self.subject.is_some(),
+                        #[watch] set_tooltip_text: self.subject.as_deref(),
+                        set_ellipsize: gtk::pango::EllipsizeMode::End,
                         set_xalign: 0.0,
+                        add_css_class: "subject",
+                    },
+
+                    gtk::Overlay {
+                        #[watch] set_visible: self.show_content(),
+                        set_vexpand: true,
+                        set_child: Some(self.content_label.widget()),
+
+                        // fading bottom of collapsed content
+                        add_overlay = &gtk::Box {
+                            add_css_class: "fade",
+                            set_valign: gtk::Align::End,
+                            set_can_target: false,
+                            #[watch] set_visible: self.collapsible && self.collapsed,
+                        },
+                    },
+
+                    gtk::Button {
+                        add_css_class: "show-more",
+                        set_has_frame: false,
+                        set_halign: gtk::Align::Start,
+                        #[watch] set_visible: self.show_content() && self.collapsible,
+                        #[watch] set_label: if self.collapsed { "Show more" } else { "Show less" },
+                        connect_clicked => NoteInput::ToggleCollapsed
+                    },
+
+                    gtk::Label {
+                        add_css_class: "translation",
+                        set_wrap: true,
+                        set_wrap_mode: gtk::pango::WrapMode::WordChar,
                         set_selectable: true,
-                        add_css_class: "content",
+                        set_xalign: 0.0,
+                        #[watch] set_visible: self.can_translate() && self.shown_translation().is_some(),
+                        #[watch] set_label: self.shown_translation().unwrap_or_default(),
+                    },
+
+                    gtk::Label {
+                        add_css_class: "error",
+                        set_wrap: true,
+                        set_xalign: 0.0,
+                        #[watch] set_visible: self.can_translate() && self.translation_error().is_some(),
+                        #[watch] set_label: self.translation_error().unwrap_or_default(),
+                    },
 
-                        connect_activate_link[sender] => move |_, uri| {
-                            if uri.starts_with("nostr") {
-                                sender.output(NoteOutput::LinkClicked(uri.to_string()));
-                                gtk::Inhibit(true)
-                            } else { gtk::Inhibit(false) }
+                    gtk::Button {
+                        add_css_class: "translate",
+                        set_has_frame: false,
+                        set_halign: gtk::Align::Start,
+                        #[watch] set_visible: self.can_translate(),
+                        #[watch] set_sensitive: !self.translating,
+                        #[watch] set_label: self.format_translate(),
+                        connect_clicked => NoteInput::Translate
+                    },
+
+                    gtk::Button::with_label("Read article") {
+                        set_visible: self.article.is_some(),
+                        set_halign: gtk::Align::Start,
+                        connect_clicked => NoteInput::OpenArticle
+                    },
+
+                    // actions with unsupported event
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_spacing: 8,
+                        set_visible: self.unsupported,
+
+                        gtk::Button::with_label("Copy nevent") {
+                            set_action_name: Some("main.copy"),
+                            #[watch] set_action_target_value: Some(&self.event.nevent(&self.relays).to_variant()),
+                        },
+
+                        gtk::Button {
+                            #[watch] set_label: &self.format_handler(),
+                            #[watch] set_tooltip_text: self.handler_url().as_ref().map(|u| u.as_str()),
+                            #[watch] set_visible: self.handler_url().is_some(),
+                            connect_clicked => NoteInput::OpenInHandler
+                        }
+                    },
+
+                    gtk::Box {
+                        #[watch] set_visible: self.show_content() && !self.images.is_empty(),
+                        append: self.gallery.widget(),
+                    },
+
+                    // quoted notes
+                    gtk::Box {
+                        #[watch] set_visible: self.show_content() && !self.quoted.is_empty(),
+                        append: &self.quotes,
+                    },
+
+                    // lightning invoices
+                    gtk::Box {
+                        #[watch] set_visible: self.show_content() && self.payments.first_child().is_some(),
+                        append: &self.payments,
+                    },
+
+                    // link preview
+                    gtk::Button {
+                        add_css_class: "link-preview",
+                        #[watch] set_visible: self.show_preview(),
+                        #[watch] set_tooltip_text: self.preview.as_ref().map(|p| p.url.as_str()),
+                        connect_clicked => NoteInput::OpenPreview,
+
+                        gtk::Box {
+                            set_orientation: gtk::Orientation::Horizontal,
+                            set_spacing: 12,
+
+                            gtk::Picture {
+                                #[watch] set_visible: self.preview_bitmap.is_some(),
+                                #[watch] set_paintable: self.preview_bitmap.as_ref().map(|b| b.as_ref()),
+                                set_content_fit: gtk::ContentFit::Cover,
+                                set_can_shrink: true,
+                                set_width_request: 120,
+                                set_height_request: 80,
+                            },
+
+                            gtk::Box {
+                                set_orientation: gtk::Orientation::Vertical,
+                                set_spacing: 4,
+                                set_hexpand: true,
+
+                                gtk::Label {
+                                    #[watch] set_label: self.preview.as_ref().map(|p| p.title.as_str()).unwrap_or_default(),
+                                    set_wrap: true,
+                                    set_xalign: 0.0,
+                                    add_css_class: "title",
+                                },
+
+                                gtk::Label {
+                                    #[watch] set_label: self.preview.as_ref().and_then(|p| p.description.as_deref()).unwrap_or_default(),
+                                    #[watch] set_visible: self.preview.as_ref().and_then(|p| p.description.as_ref()).is_some(),
+                                    set_wrap: true,
+                                    set_lines: 3,
+                                    set_ellipsize: gtk::pango::EllipsizeMode::End,
+                                    set_xalign: 0.0,
+                                    add_css_class: "description",
+                                },
+
+                                gtk::Label {
+                                    #[watch] set_label: &self.format_preview_domain(),
+                                    set_xalign: 0.0,
+                                    add_css_class: "domain",
+                                }
+                            }
                         }
                     },
 
@@ -186,11 +421,18 @@ impl FactoryComponent for Note {
 
                     // reactions
                     gtk::Grid {
+                        set_visible: !self.unsupported,
                         // set_column_spacing: 20,
                         set_column_homogeneous: true,
                         set_hexpand: true,
                         add_css_class: "reactions",
 
+                        attach[0, 1, 1, 1] =
+                            &gtk::Button::from_icon_name("mail-reply-sender-symbolic") {
+                                set_halign: gtk::Align::Center,
+                                set_tooltip_text: Some("Reply"),
+                                connect_clicked => NoteInput::Reply
+                            },
                         attach[1, 1, 1, 1] =
                             &gtk::Button {
                                 set_halign: gtk::Align::Center,
@@ -229,7 +471,16 @@ impl FactoryComponent for Note {
                                 set_spacing: 4,
                                 set_halign: gtk::Align::Center,
 
-                                gtk::Button::from_icon_name("gnostique-repost-symbolic") { }
+                                gtk::Button::from_icon_name("gnostique-repost-symbolic") {
+                                    set_tooltip_text: Some("Quote"),
+                                    connect_clicked => NoteInput::Quote
+                                },
+
+                                gtk::Button {
+                                    #[watch] set_icon_name: if self.bookmarked { "starred-symbolic" } else { "non-starred-symbolic" },
+                                    #[watch] set_tooltip_text: Some(if self.bookmarked { "Remove bookmark" } else { "Bookmark" }),
+                                    connect_clicked => NoteInput::ToggleBookmark
+                                }
                             },
                         attach[4, 1, 1, 1] =
                             &gtk::MenuButton {
@@ -239,6 +490,9 @@ impl FactoryComponent for Note {
                             }
                     },
 
+                    // reactions by custom emoji
+                    append: &self.custom_reactions_box,
+
                     // status
                     gtk::Box {
                         set_orientation: gtk::Orientation::Horizontal,
@@ -248,19 +502,60 @@ impl FactoryComponent for Note {
                         add_css_class: "status",
 
                         gtk::Label {
-                            set_label: &self.relays.iter().map(|u| u.domain().unwrap()).collect::<Vec<_>>().join("   "),
-                            set_visible: !self.relays.is_empty(),
+                            #[watch] set_label: &self.format_zaps().unwrap_or_default(),
+                            #[watch] set_tooltip_text: Some(&self.format_zaps_tooltip()),
+                            #[watch] set_visible: self.zaps > 0,
+                            add_css_class: "zaps",
+                        },
+
+                        gtk::Label {
+                            set_label: &self.format_pow().unwrap_or_default(),
+                            set_visible: self.format_pow().is_some(),
+                            set_tooltip_text: Some("Proof of work (NIP-13)"),
+                            add_css_class: "pow",
+                        },
+
+                        gtk::Label {
+                            #[watch] set_label: &self.format_expiration().unwrap_or_default(),
+                            #[watch] set_visible: self.format_expiration().is_some(),
+                            add_css_class: "expiration",
+                        },
+
+                        gtk::Label {
+                            #[watch] set_label: &self.format_relays(),
+                            #[watch] set_tooltip_text: Some(&self.format_relays_tooltip()),
+                            #[watch] set_visible: !self.relays.is_empty(),
                             set_xalign: 1.0,
                             add_css_class: "relays",
                         },
 
                         gtk::Label {
-                            set_label?: &self.event.client().as_ref().map(|c| format!("Sent by {c}")),
+                            set_label: &self.format_delegatee().unwrap_or_default(),
+                            set_tooltip_text: self.delegatee.and_then(|d| d.to_bech32().ok()).as_deref(),
+                            set_visible: self.delegatee.is_some(),
+                            add_css_class: "client",
+                        },
+
+                        gtk::Label {
+                            set_label?: &self.event.client().as_ref().map(|c| format!("via {c}")),
                             set_xalign: 1.0,
                             set_visible: self.event.client().is_some(),
                             add_css_class: "client",
                         }                    }
                 },
+                add_controller = &gtk::GestureClick::new() {
+                    set_button: 3,
+                    connect_pressed[note_box, note_menu = note_menu.clone()] => move |_, _, x, y| {
+                        let popover = gtk::PopoverMenu::builder()
+                            .menu_model(&note_menu)
+                            .has_arrow(false)
+                            .pointing_to(&gdk::Rectangle::new(x as i32, y as i32, 1, 1))
+                            .build();
+
+                        popover.set_parent(&note_box);
+                        popover.popup();
+                    }
+                },
                 add_controller = &gtk::EventControllerMotion::new() {
                     connect_enter[sender] => move |_, _, _| { sender.input(NoteInput::FocusIn) },
                     connect_leave[sender] => move |_| { sender.input(NoteInput::FocusOut) }
@@ -273,58 +568,224 @@ impl FactoryComponent for Note {
         author_menu: {
             "Copy pubkey as hex" => Copy(self.author.pubkey.to_string()),
             "Copy pubkey as bech32" => Copy(self.author.pubkey.to_bech32().unwrap()),
+            section! {
+                "Mute" => Mute(self.author.pubkey.to_string()),
+                "Unmute" => Unmute(self.author.pubkey.to_string())
+            },
+            section! {
+                "Mute notifications" => MuteNotifications(self.author.pubkey.to_string()),
+                "Unmute notifications" => UnmuteNotifications(self.author.pubkey.to_string())
+            }
         },
 
         note_menu: {
             section! {
                 "Copy event ID as hex" => Copy(self.event.id.to_hex()),
-                "Copy event ID as bech32" => Copy(self.event.id.to_string())
+                "Copy event ID as bech32" => Copy(self.event.id.to_bech32().unwrap()),
+                "Copy event ID with relays" => Copy(self.event.nevent(&self.relays))
+            },
+            section! {
+                "Copy author's npub" => Copy(self.author.pubkey.to_bech32().unwrap()),
+                "Copy content" => Copy(self.event.content.clone())
             }
+        },
+
+        report_note_menu: {
+            "Report…" => ReportNote((self.event.id.to_hex(), self.event.pubkey.to_string()))
+        },
+
+        report_author_menu: {
+            "Mark as NSFW" => LabelAuthor((self.author.pubkey.to_string(), crate::labels::NSFW.to_string())),
+            "Mark as spam" => LabelAuthor((self.author.pubkey.to_string(), crate::labels::SPAM.to_string())),
+            "Report user…" => ReportUser(self.author.pubkey.to_string())
+        },
+
+        delete_note_menu: {
+            section! {
+                "Pin to profile" => PinNote(self.event.id.to_hex()),
+                "Unpin" => UnpinNote(self.event.id.to_hex())
+            },
+            "Delete…" => DeleteNote(self.event.id.to_hex())
         }
     }
 
+    fn init_widgets(
+        &mut self,
+        _index: &DynamicIndex,
+        root: &Self::Root,
+        _returned_widget: &gtk::ListBoxRow,
+        sender: FactorySender<Self>,
+    ) -> Self::Widgets {
+        let widgets = view_output!();
+
+        // There is no point in reporting ourselves.
+        if !self.is_own {
+            note_menu.append_section(None, &report_note_menu);
+            author_menu.append_section(None, &report_author_menu);
+        } else {
+            note_menu.append_section(None, &delete_note_menu);
+        }
+
+        widgets
+    }
+
     fn output_to_parent_input(output: Self::Output) -> Option<Self::ParentInput> {
         match output {
             NoteOutput::ShowDetails(details) => Some(LaneMsg::ShowDetails(details)),
-            NoteOutput::LinkClicked(uri) => uri.parse().map(LaneMsg::LinkClicked).ok(),
+            NoteOutput::Open(target) => Some(LaneMsg::Open(target)),
+            NoteOutput::NeedImages(urls) => Some(LaneMsg::NeedImages(urls)),
+            NoteOutput::OpenImage(url) => Some(LaneMsg::OpenImage(url)),
+            NoteOutput::NeedPreview(url) => Some(LaneMsg::NeedPreview(url)),
+            NoteOutput::Reply(event) => Some(LaneMsg::Reply(event)),
+            NoteOutput::Quote(event, relays) => Some(LaneMsg::Quote(event, relays)),
+            NoteOutput::OpenArticle(article, author) => Some(LaneMsg::OpenArticle(article, author)),
+            NoteOutput::Bookmark(id, bookmarked) => Some(LaneMsg::Bookmark(id, bookmarked)),
+            NoteOutput::Translate(id, content) => Some(LaneMsg::Translate(id, content)),
         }
     }
 
     fn init_model(init: Self::Init, _index: &DynamicIndex, sender: FactorySender<Self>) -> Self {
-        relm4::spawn(async move {
-            let mut int = tokio::time::interval(Duration::from_secs(30));
-            loop {
-                int.tick().await;
-                sender.input(NoteInput::Tick);
-            }
-        });
-
         let replies = Replies::builder().launch(()).detach();
-        let author = init.author.unwrap_or(Persona::new(init.event.pubkey));
+        // Events of unsupported kinds display just a placeholder.
+        let unsupported = is_unsupported_content(init.event.kind);
+        let images = if unsupported {
+            Vec::new()
+        } else {
+            init.event.shown_images()
+        };
+        let preview_url = init.event.preview_url().filter(|_| !unsupported);
+        let gallery = Gallery::builder()
+            .launch(images.clone())
+            .forward(sender.input_sender(), |GalleryOutput::Open(url)| {
+                NoteInput::OpenImage(url)
+            });
+        // Delegated notes are attributed to their delegators (NIP-26).
+        let delegatee = matches!(init.event.delegation(), Some(Ok(_))).then_some(init.event.pubkey);
+        let author = init.author.unwrap_or(Persona::new(init.event.author()));
+        let author_pubkey = author.pubkey;
+        let article = Article::from_event(&init.event);
         let repost_author = init
             .repost
             .as_ref()
             .map(|r| r.author.clone().unwrap_or(Persona::new(r.event.pubkey)));
         let repost = init.repost.map(|r| r.event);
+        let parent = init
+            .event
+            .replies_to()
+            .filter(|_| init.event.kind == Kind::TextNote)
+            .map(|id| (id, init.event.tag_relay_hints().of_event(&id)));
+
+        let content_label = EmojiLabel::new();
+        content_label.widget().set_halign(gtk::Align::Start);
+        content_label.widget().set_valign(gtk::Align::Start);
+        let label = content_label.label();
+        label.set_wrap(true);
+        label.set_wrap_mode(WrapMode::WordChar);
+        label.set_xalign(0.0);
+        label.set_selectable(true);
+        label.add_css_class("content");
+        label.connect_activate_link({
+            let sender = sender.clone();
+            move |_, uri| {
+                if uri.starts_with("nostr") {
+                    match uri.parse().ok().and_then(|u| Target::from_uri(&u)) {
+                        Some(target) => sender.output(NoteOutput::Open(target)),
+                        None => info!("Unsupported link {uri}"),
+                    }
+                    gtk::Inhibit(true)
+                } else {
+                    gtk::Inhibit(false)
+                }
+            }
+        });
 
-        Self {
+        let custom_reactions_box = gtk::FlowBox::new();
+        custom_reactions_box.set_selection_mode(gtk::SelectionMode::None);
+        custom_reactions_box.set_max_children_per_line(12);
+        custom_reactions_box.set_visible(false);
+        custom_reactions_box.add_css_class("custom-reactions");
+
+        let mut note = Self {
             author,
+            mentions: init.mentions.into_iter().map(|p| (p.pubkey, p)).collect(),
             is_central: init.is_central,
-            content: init.event.augment_content(),
+            is_own: init.is_own,
+            mentions_me: init.mentions_me,
+            bookmarked: init.bookmarked,
+            pinned: init.pinned,
+            content: String::new(),
+            content_label,
+            collapse: init.collapse,
+            collapsible: false,
+            // Central note is what the lane is about, it is read whole.
+            collapsed: !init.is_central,
+            emojis: init.event.emojis(),
+            emoji_bitmaps: HashMap::new(),
+            subject: article
+                .as_ref()
+                .and_then(|a| a.title.clone())
+                .or_else(|| init.event.subject()),
+            expiration: init.event.expiration(),
+            expired: false,
+            pending: init.pending,
+            content_warning: init.event.content_warning(),
+            labels: init.labels,
             show_hidden_buttons: false,
-            avatar: ANONYMOUS_USER.clone(),
-            likes: 0,
-            dislikes: 0,
+            avatar: placeholder(&author_pubkey),
+            likes: init.reactions.likes,
+            dislikes: init.reactions.dislikes,
+            custom_reactions: Vec::new(),
+            custom_reactions_box,
+            zaps: init.reactions.zaps,
+            sats: init.reactions.sats,
             time: Utc
                 .timestamp_opt(init.event.created_at.as_i64(), 0)
                 .unwrap(),
+            depth: init.depth,
+            divider: false,
             event: init.event,
             relays: init.relays,
             replies,
+            gallery,
+            images,
+            preview_url,
+            preview: None,
+            preview_bitmap: None,
+            revealed: false,
             repost_author,
             repost,
             age: String::new(),
+            time_format: init.time_format,
+            quotes: gtk::Box::new(gtk::Orientation::Vertical, 8),
+            quoted: Vec::new(),
+            payments: gtk::Box::new(gtk::Orientation::Vertical, 8),
+            invoice_expiries: Vec::new(),
+            article,
+            unsupported,
+            handler: None,
+            delegatee,
+            translatable: init.translatable,
+            translating: false,
+            translation: None,
+            show_translation: false,
+            parent,
+            parent_note: init.parent,
+        };
+
+        note.refresh_content();
+        note.age = note.format_age();
+        for quote in init.quotes {
+            note.add_quote(quote, &sender);
+        }
+        for (emoji, count) in init.reactions.emojis {
+            note.add_custom_reaction(emoji, count);
         }
+        if note.article.is_none() && !note.unsupported {
+            for request in crate::content::payment_requests(&note.event.content) {
+                note.add_payment_request(request);
+            }
+        }
+        note
     }
 
     fn update(&mut self, message: Self::Input, sender: FactorySender<Self>) {
@@ -332,7 +793,15 @@ impl FactoryComponent for Note {
             NoteInput::UpdatedProfile { author } => {
                 if self.author.pubkey == author.pubkey {
                     self.author = author.clone();
+                    self.refresh_content();
                 };
+                if let Some(reposter) = self
+                    .repost_author
+                    .as_mut()
+                    .filter(|r| r.pubkey == author.pubkey)
+                {
+                    *reposter = author.clone();
+                }
 
                 self.replies.emit(RepliesInput::UpdatedProfile { author });
             }
@@ -358,32 +827,157 @@ impl FactoryComponent for Note {
             } => self.receive(event, relays, author, repost),
             NoteInput::Nip05Verified(pubkey) => {
                 if pubkey == self.author.pubkey {
-                    self.author.nip05_verified = true;
+                    self.author.nip05_verified = Nip05Status::Verified(Timestamp::now());
                 }
                 self.replies.emit(RepliesInput::Nip05Verified(pubkey));
             }
 
-            NoteInput::Reaction { event, reaction } => {
+            NoteInput::Reaction {
+                event,
+                reaction,
+                emoji,
+            } => {
                 if self.event.id == event {
-                    if reaction == "+" || reaction == "🤙" {
-                        self.likes += 1;
-                    } else if reaction == "-" {
-                        self.dislikes += 1;
+                    match Reaction::of(&reaction, emoji) {
+                        Some(Reaction::Like) => self.likes += 1,
+                        Some(Reaction::Dislike) => self.dislikes += 1,
+                        Some(Reaction::Emoji(emoji)) => self.add_custom_reaction(emoji, 1),
+                        None => {}
                     }
                 }
             }
+            NoteInput::Zap(sats) => {
+                self.zaps += 1;
+                self.sats += sats;
+            }
             NoteInput::ShowDetails => {
-                let event_json = match &self.repost {
-                    Some(e) => serde_json::to_string_pretty(e).unwrap(),
-                    None => serde_json::to_string_pretty(self.event.as_ref()).unwrap(),
+                let event = match &self.repost {
+                    Some(e) => Arc::new(e.clone()),
+                    None => self.event.clone(),
                 };
                 let details = Details {
-                    event_json,
+                    event_json: serde_json::to_string_pretty(&event).unwrap(),
+                    event,
                     metadata_json: Some(self.author.metadata_json.clone()),
+                    client: self.event.client(),
+                    language: crate::language::describe(&self.event.content),
+                    relays: self.relays.clone(),
+                    first_seen: HashMap::new(),
+                    time_format: self.time_format,
+                    deliveries: Vec::new(),
+                    profile_history: Vec::new(),
                 };
                 sender.output(NoteOutput::ShowDetails(details));
             }
-            NoteInput::Tick => self.age = self.format_age(),
+            NoteInput::SeenOn(relay) => {
+                if !self.relays.contains(&relay) {
+                    self.relays.push(relay);
+                }
+            }
+            NoteInput::Tick(time_format) => {
+                self.time_format = time_format;
+                self.age = self.format_age();
+                self.refresh_invoice_expiries();
+            }
+            NoteInput::ImageBitmap { url, bitmap } => {
+                if self.preview.as_ref().and_then(|p| p.image.as_ref()) == Some(&url) {
+                    self.preview_bitmap = Some(bitmap.clone());
+                }
+                self.gallery.emit(GalleryInput::ImageBitmap { url, bitmap })
+            }
+            NoteInput::EmojiBitmap { url, bitmap } => self.emoji_bitmap(url, bitmap),
+            NoteInput::MentionUpdated(persona) => {
+                self.mentions.insert(persona.pubkey, persona);
+                self.refresh_content();
+            }
+            NoteInput::LinkPreview(preview) => self.preview = Some(preview),
+            NoteInput::Expired => self.expired = true,
+            NoteInput::Delivered => self.pending = false,
+            NoteInput::ToggleBookmark => {
+                sender.output(NoteOutput::Bookmark(self.event.id, !self.bookmarked))
+            }
+            NoteInput::Bookmarked(bookmarked) => self.bookmarked = bookmarked,
+            NoteInput::Reply => sender.output(NoteOutput::Reply(self.event.clone())),
+            NoteInput::Quote => {
+                sender.output(NoteOutput::Quote(self.event.clone(), self.relays.clone()))
+            }
+            NoteInput::Quoted(quote) => self.add_quote(quote, &sender),
+            NoteInput::OpenArticle => {
+                if let Some(article) = &self.article {
+                    sender.output(NoteOutput::OpenArticle(
+                        Box::new(article.clone()),
+                        Box::new(self.author.clone()),
+                    ))
+                }
+            }
+            NoteInput::Handler(handler) => self.handler = Some(handler),
+            NoteInput::OpenInHandler => {
+                if let Some(url) = self.handler_url() {
+                    gtk::show_uri(None::<&gtk::Window>, url.as_str(), gdk::CURRENT_TIME);
+                }
+            }
+            NoteInput::OpenPreview => {
+                if let Some(preview) = &self.preview {
+                    gtk::show_uri(
+                        None::<&gtk::Window>,
+                        preview.url.as_str(),
+                        gdk::CURRENT_TIME,
+                    );
+                }
+            }
+            NoteInput::OpenImage(url) => sender.output(NoteOutput::OpenImage(url)),
+            NoteInput::ToggleCollapsed => {
+                self.collapsed = !self.collapsed;
+                self.refresh_content();
+            }
+            NoteInput::Collapse(collapse) => {
+                if collapse != self.collapse {
+                    self.collapse = collapse;
+                    self.refresh_content();
+                }
+            }
+            NoteInput::Translate => match &self.translation {
+                Some(Ok(_)) => self.show_translation = !self.show_translation,
+                _ if self.translating => {}
+                _ => {
+                    self.translating = true;
+                    self.translation = None;
+                    sender.output(NoteOutput::Translate(
+                        self.event.id,
+                        self.event.content.clone(),
+                    ));
+                }
+            },
+            NoteInput::Translated(translation) => {
+                self.translating = false;
+                self.show_translation = translation.is_ok();
+                self.translation = Some(translation);
+            }
+            NoteInput::Translatable(translatable) => self.translatable = translatable,
+            NoteInput::Parent(parent) => {
+                if self.parent.as_ref().map(|(id, _)| *id) == Some(parent.event.id) {
+                    self.parent_note = Some(parent);
+                }
+            }
+            NoteInput::Labels { author, labels } => {
+                if author == self.author.pubkey {
+                    let revealed = self.show_content();
+                    self.labels = labels;
+                    // Content hidden when the note arrived has no media yet.
+                    if !revealed && self.show_content() {
+                        self.load_media(&sender);
+                    }
+                }
+            }
+            NoteInput::OpenParent => {
+                if let Some((id, relays)) = &self.parent {
+                    sender.output(NoteOutput::Open(Target::Note(*id, relays.clone())));
+                }
+            }
+            NoteInput::Reveal => {
+                self.revealed = true;
+                self.load_media(&sender);
+            }
         }
     }
 }
@@ -0,0 +1,112 @@
+use gtk::prelude::*;
+use relm4::*;
+
+use crate::content::{EntityKind, Target};
+use crate::ui::entity_entry::*;
+use crate::Gnostique;
+
+/// Dialog opening a profile or a thread by its npub, nprofile, note,
+/// nevent, hex or `nostr:` URI.
+#[derive(Debug)]
+pub struct OpenDialog {
+    visible: bool,
+    entry: Controller<EntityEntry>,
+    entered: Option<Target>,
+}
+
+#[derive(Debug)]
+pub enum OpenInput {
+    Show,
+    Hide,
+    Entered(Option<Target>),
+    Open,
+}
+
+#[derive(Debug)]
+pub enum OpenOutput {
+    Open(Target),
+}
+
+#[relm4::component(pub)]
+impl Component for OpenDialog {
+    type Init = Gnostique;
+    type Input = OpenInput;
+    type Output = OpenOutput;
+    type CommandOutput = ();
+
+    view! {
+        gtk::Window {
+            set_title: Some("Open profile or note"),
+            set_default_size: (450, -1),
+            #[watch] set_visible: model.visible,
+
+            connect_close_request[sender] => move |_| {
+                sender.input(OpenInput::Hide);
+                gtk::Inhibit(true)
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 16,
+                add_css_class: "form",
+
+                #[local_ref]
+                entry -> gtk::Box {},
+
+                gtk::Button::with_label("Open") {
+                    set_halign: gtk::Align::End,
+                    add_css_class: "suggested-action",
+                    #[watch] set_sensitive: model.entered.is_some(),
+                    connect_clicked => OpenInput::Open,
+                },
+            }
+        }
+    }
+
+    fn init(
+        gnostique: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = OpenDialog {
+            visible: false,
+            entry: EntityEntry::builder()
+                .launch((
+                    gnostique,
+                    EntityKind::Any,
+                    "npub, note, nevent or nostr: URI",
+                ))
+                .forward(sender.input_sender(), |output| match output {
+                    EntityEntryOutput::Changed { target, .. } => OpenInput::Entered(target),
+                    EntityEntryOutput::Activated => OpenInput::Open,
+                }),
+            entered: None,
+        };
+
+        let entry = model.entry.widget();
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>, _root: &Self::Root) {
+        match message {
+            OpenInput::Show => {
+                self.entry.emit(EntityEntryInput::Clear);
+                self.visible = true;
+            }
+
+            OpenInput::Hide => self.visible = false,
+
+            OpenInput::Entered(target) => self.entered = target,
+
+            OpenInput::Open => {
+                if let Some(target) = self.entered.take() {
+                    self.visible = false;
+                    self.entry.emit(EntityEntryInput::Clear);
+                    sender.output(OpenOutput::Open(target)).unwrap_or_default();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use gtk::prelude::*;
+use nostr_sdk::prelude::EventId;
+use relm4::prelude::*;
+use relm4::{gtk, ComponentParts};
+
+use crate::outbox::PendingEvent;
+use crate::Gnostique;
+
+/// A window listing published events that no relay has accepted yet.
+/// User can send them again or give up on them.
+pub struct OutboxWindow {
+    gnostique: Gnostique,
+
+    /// Whether the window is visible or hidden.
+    visible: bool,
+
+    /// Whether the list of pending events is being loaded.
+    loading: bool,
+
+    /// Number of displayed pending events.
+    count: usize,
+
+    /// List of pending events.
+    events: gtk::Box,
+}
+
+/// Messages coming to [`OutboxWindow`].
+#[derive(Debug)]
+pub enum OutboxWindowInput {
+    /// Load pending events and show the window, if hidden.
+    Show,
+
+    /// Hide the window.
+    Hide,
+
+    /// Load pending events again.
+    Refresh,
+
+    /// Pending events have been loaded.
+    Loaded(Vec<PendingEvent>),
+
+    /// Send the event again.
+    Retry(EventId),
+
+    /// Remove the event from outbox.
+    Discard(EventId),
+}
+
+#[relm4::component(pub)]
+impl Component for OutboxWindow {
+    type Init = Gnostique;
+    type Input = OutboxWindowInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::Window {
+            set_title: Some("Outbox"),
+            set_default_size: (600, 400),
+            #[watch]
+            set_visible: model.visible,
+
+            connect_close_request[sender] => move |_| {
+                sender.input(OutboxWindowInput::Hide);
+                gtk::Inhibit(false)
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                add_css_class: "form",
+                set_spacing: 8,
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+
+                    gtk::Label {
+                        #[watch] set_label: &model.format_summary(),
+                        set_hexpand: true,
+                        set_xalign: 0.0,
+                    },
+
+                    gtk::Button::from_icon_name("view-refresh-symbolic") {
+                        set_tooltip_text: Some("Reload pending events"),
+                        #[watch] set_sensitive: !model.loading,
+                        connect_clicked => OutboxWindowInput::Refresh
+                    },
+                },
+
+                gtk::ScrolledWindow {
+                    set_vexpand: true,
+                    #[wrap(Some)]
+                    set_child = &model.events.clone() {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_spacing: 12,
+                    }
+                }
+            }
+        }
+    }
+
+    fn init(
+        gnostique: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = OutboxWindow {
+            gnostique,
+            visible: false,
+            loading: false,
+            count: 0,
+            events: gtk::Box::default(),
+        };
+        let widgets = view_output!();
+
+        ComponentParts { widgets, model }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>, _root: &Self::Root) {
+        match message {
+            OutboxWindowInput::Show => {
+                self.visible = true;
+                self.load(sender);
+            }
+            OutboxWindowInput::Hide => self.visible = false,
+            OutboxWindowInput::Refresh => self.load(sender),
+            OutboxWindowInput::Loaded(pending) => {
+                self.loading = false;
+                self.count = pending.len();
+                self.show_events(&pending, sender);
+            }
+            OutboxWindowInput::Retry(id) => {
+                let outbox = self.gnostique.outbox().clone();
+                relm4::spawn(async move {
+                    outbox.retry_now(id).await;
+                    sender.input(OutboxWindowInput::Refresh);
+                });
+            }
+            OutboxWindowInput::Discard(id) => {
+                let outbox = self.gnostique.outbox().clone();
+                relm4::spawn(async move {
+                    outbox.discard(id).await;
+                    sender.input(OutboxWindowInput::Refresh);
+                });
+            }
+        }
+    }
+}
+
+impl OutboxWindow {
+    /// Loads pending events in the background.
+    fn load(&mut self, sender: ComponentSender<Self>) {
+        self.loading = true;
+
+        let outbox = self.gnostique.outbox().clone();
+        relm4::spawn(async move {
+            sender.input(OutboxWindowInput::Loaded(outbox.pending().await));
+        });
+    }
+
+    fn format_summary(&self) -> String {
+        match self.count {
+            _ if self.loading => "Loading…".to_string(),
+            0 => "All published events have been delivered.".to_string(),
+            1 => "1 event waits for delivery.".to_string(),
+            n => format!("{n} events wait for delivery."),
+        }
+    }
+
+    /// Fills the list with `pending` events.
+    fn show_events(&self, pending: &[PendingEvent], sender: ComponentSender<Self>) {
+        while let Some(child) = self.events.first_child() {
+            self.events.remove(&child);
+        }
+
+        let label = |text: &str, css: &str| {
+            let label = gtk::Label::new(Some(text));
+            label.set_xalign(0.0);
+            label.set_wrap(true);
+            label.set_wrap_mode(gtk::pango::WrapMode::WordChar);
+            label.add_css_class(css);
+            label
+        };
+
+        for p in pending {
+            let row = gtk::Box::new(gtk::Orientation::Vertical, 4);
+
+            let content: String = p.event.content.chars().take(200).collect();
+            row.append(&label(&content, "content"));
+
+            let created = chrono::NaiveDateTime::from_timestamp_opt(p.event.created_at.as_i64(), 0)
+                .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default();
+            row.append(&label(
+                &format!(
+                    "Kind {}, created {created}, attempts: {}",
+                    p.event.kind.as_u64(),
+                    p.attempts
+                ),
+                "label",
+            ));
+
+            for d in &p.deliveries {
+                row.append(&label(&d.to_string(), "monospace"));
+            }
+
+            let buttons = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+            buttons.set_halign(gtk::Align::End);
+
+            let id = p.event.id;
+
+            let retry = gtk::Button::with_label("Retry");
+            retry.connect_clicked({
+                let sender = sender.clone();
+                move |_| sender.input(OutboxWindowInput::Retry(id))
+            });
+            buttons.append(&retry);
+
+            let discard = gtk::Button::with_label("Discard");
+            discard.add_css_class("destructive-action");
+            discard.connect_clicked({
+                let sender = sender.clone();
+                move |_| sender.input(OutboxWindowInput::Discard(id))
+            });
+            buttons.append(&discard);
+
+            row.append(&buttons);
+            self.events.append(&row);
+            self.events
+                .append(&gtk::Separator::new(gtk::Orientation::Horizontal));
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use nostr_sdk::prelude::{EventId, XOnlyPublicKey};
+
+/// Actions wait this long before they are carried out.
+pub const UNDO_WINDOW: Duration = Duration::from_secs(10);
+
+/// Destructive action which can still be undone.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PendingAction {
+    /// The user is to be added to our published mute list.
+    Mute(XOnlyPublicKey),
+    /// Deletion of our note is to be published.
+    Delete(EventId),
+}
+
+/// Actions waiting for [`UNDO_WINDOW`] to pass. They live only as long as
+/// the window, those still waiting when Gnostique ends are dropped.
+#[derive(Debug, Default)]
+pub struct Pending {
+    last_id: u64,
+    actions: HashMap<u64, PendingAction>,
+}
+
+impl Pending {
+    /// Puts off `action` and returns its identifier. If the same action
+    /// is already waiting, its identifier is returned instead.
+    pub fn add(&mut self, action: PendingAction) -> u64 {
+        if let Some(id) = self.find(&action) {
+            return id;
+        }
+        self.last_id += 1;
+        self.actions.insert(self.last_id, action);
+        self.last_id
+    }
+
+    /// Identifier of waiting `action`, if it is waiting.
+    pub fn find(&self, action: &PendingAction) -> Option<u64> {
+        self.actions
+            .iter()
+            .find(|(_, a)| *a == action)
+            .map(|(id, _)| *id)
+    }
+
+    /// Takes action `id` out to be either carried out or undone. Every
+    /// action can be taken just once, so it is never both.
+    pub fn take(&mut self, id: u64) -> Option<PendingAction> {
+        self.actions.remove(&id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Fixtures;
+
+    #[test]
+    fn undoing_before_the_delay_cancels_the_action() {
+        let mut pending = Pending::default();
+        let id = pending.add(PendingAction::Mute(Fixtures::new().public_key()));
+
+        // Undo comes first, the delay runs out afterwards.
+        assert!(pending.take(id).is_some());
+        assert_eq!(pending.take(id), None);
+    }
+
+    #[test]
+    fn carrying_out_expires_the_action() {
+        let mut pending = Pending::default();
+        let action = PendingAction::Delete(Fixtures::new().text_note("bye").id);
+        let id = pending.add(action.clone());
+
+        assert_eq!(pending.take(id), Some(action.clone()));
+        assert_eq!(pending.find(&action), None);
+        // Doing the same again is a new action.
+        assert_ne!(pending.add(action), id);
+    }
+
+    #[test]
+    fn undoing_after_carrying_out_does_nothing() {
+        let mut pending = Pending::default();
+        let mute = PendingAction::Mute(Fixtures::new().public_key());
+        let delete = PendingAction::Delete(Fixtures::new().text_note("bye").id);
+        let mute_id = pending.add(mute);
+        let delete_id = pending.add(delete.clone());
+
+        assert!(pending.take(mute_id).is_some());
+        assert_eq!(pending.take(mute_id), None);
+        // Other waiting actions stay as they are.
+        assert_eq!(pending.find(&delete), Some(delete_id));
+    }
+
+    #[test]
+    fn the_same_action_waits_just_once() {
+        let mut pending = Pending::default();
+        let action = PendingAction::Mute(Fixtures::new().public_key());
+
+        let id = pending.add(action.clone());
+        assert_eq!(pending.add(action), id);
+        assert!(pending.take(id).is_some());
+        assert_eq!(pending.take(id), None);
+    }
+}
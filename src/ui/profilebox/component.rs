@@ -1,5 +1,5 @@
 use gtk::prelude::*;
-use nostr_sdk::prelude::ToBech32;
+use nostr_sdk::prelude::{ToBech32, XOnlyPublicKey};
 use relm4::*;
 
 use super::model::{Input, Profilebox};
@@ -8,7 +8,7 @@ use super::model::{Input, Profilebox};
 impl Component for Profilebox {
     type Input = Input;
     type Output = ();
-    type Init = ();
+    type Init = XOnlyPublicKey;
     type CommandOutput = ();
 
     view! {
@@ -40,17 +40,27 @@ impl Component for Profilebox {
                         set_selectable: true,
                         set_xalign: 0.0,
                         add_css_class: "name",
-                        #[watch] set_label?: model.author.as_ref().and_then(|a| a.name.as_ref()),
+                        #[watch] set_label?: &model.author.as_ref().map(|a| a.shown_name()),
                     },
 
                     gtk::Label {
                         set_selectable: true,
                         set_xalign: 0.0,
                         add_css_class: "nip05",
+                        set_use_markup: true,
                         #[watch] set_label?: &model.author.as_ref().and_then(|a| a.format_nip05()),
+                        #[watch] set_tooltip_text: model.author.as_ref().and_then(|a| a.nip05_tooltip()).as_deref(),
                     },
                 },
 
+                gtk::Label {
+                    set_xalign: 0.0,
+                    add_css_class: "profile-changed",
+                    set_tooltip_text: Some("Impersonators often take names or NIP-05 of others. See Profile history in details of their notes."),
+                    #[watch] set_visible: !model.recent_changes.is_empty(),
+                    #[watch] set_label?: &model.recent_changes_label(),
+                },
+
                 gtk::Label {
                     set_selectable: true,
                     set_xalign: 0.0,
@@ -65,16 +75,61 @@ impl Component for Profilebox {
                     add_css_class: "about",
                     #[watch] set_label?: &model.author.as_ref().and_then(|a| a.about.as_ref()),
                 },
+
+                // following + known followers
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    add_css_class: "contacts",
+                    #[watch] set_visible: model.followers.is_some(),
+
+                    gtk::Button {
+                        add_css_class: "flat",
+                        set_action_name: Some("main.show-following"),
+                        #[watch] set_label: &model.following_label(),
+                        #[watch] set_action_target_value: model.contacts_target().as_ref(),
+                        #[watch] set_sensitive: model.following.is_some(),
+                    },
+
+                    gtk::Button {
+                        add_css_class: "flat",
+                        set_action_name: Some("main.show-followers"),
+                        set_tooltip_text: Some("Only followers whose contact lists this client has seen"),
+                        #[watch] set_label?: &model.followers_label(),
+                        #[watch] set_action_target_value: model.contacts_target().as_ref(),
+                    },
+                },
+
+                // labels we have given, which can be removed
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 8,
+                    add_css_class: "labels",
+                    #[watch] set_visible: !model.labels.is_empty(),
+
+                    gtk::Label {
+                        set_label: "Marked as",
+                    },
+
+                    append: &model.labels_box,
+                },
+            },
+
+            gtk::Button::with_label("Report…") {
+                set_valign: gtk::Align::Start,
+                set_action_name: Some("main.report-user"),
+                #[watch] set_action_target_value: model.author.as_ref().map(|a| a.pubkey.to_string().to_variant()).as_ref(),
+                #[watch] set_visible: model.can_report(),
+                add_css_class: "report",
             },
         }
     }
 
     fn init(
-        _init: Self::Init,
+        me: Self::Init,
         root: &Self::Root,
         _sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        let model = Profilebox::new();
+        let model = Profilebox::new(me);
         let widgets = view_output!();
 
         ComponentParts { model, widgets }
@@ -82,7 +137,28 @@ impl Component for Profilebox {
 
     fn update(&mut self, message: Self::Input, _sender: ComponentSender<Self>, _root: &Self::Root) {
         match message {
-            Input::UpdatedProfile { author } => self.author = Some(author),
+            Input::UpdatedProfile { author } => {
+                // Another author, their avatar is yet to come.
+                if self.author.as_ref().map(|a| a.pubkey) != Some(author.pubkey) {
+                    self.avatar = crate::ui::avatars::placeholder(&author.pubkey);
+                }
+                self.author = Some(author);
+                self.refresh_labels();
+            }
+            Input::Labels(labels) => {
+                self.labels = labels;
+                self.refresh_labels();
+            }
+            Input::RecentChanges(fields) => self.recent_changes = fields,
+            Input::ContactCounts {
+                pubkey,
+                following,
+                followers,
+            } => {
+                self.counted = Some(pubkey);
+                self.following = following;
+                self.followers = Some(followers);
+            }
             Input::MetadataBitmap { bitmap, url } => {
                 if let Some(author) = &self.author {
                     if author.avatar == Some(url.clone()) {
@@ -1,29 +1,128 @@
 use std::sync::Arc;
 
 use gtk::gdk::Texture;
+use gtk::prelude::*;
+use nostr_sdk::prelude::XOnlyPublicKey;
 use reqwest::Url;
 
-use crate::nostr::{Persona, ANONYMOUS_USER};
+use crate::metadata_history::RECENT_DAYS;
+use crate::nostr::Persona;
+use crate::ui::avatars::ANONYMOUS_USER;
 
 #[derive(Debug)]
 pub struct Profilebox {
+    /// Our public key.
+    pub me: XOnlyPublicKey,
     pub author: Option<Persona>,
     pub avatar: Arc<Texture>,
     pub banner: Option<Arc<Texture>>,
+    /// Whose contacts are counted, the author may be yet to come.
+    pub counted: Option<XOnlyPublicKey>,
+    /// Number of users the author follows, if their contact list is known.
+    pub following: Option<usize>,
+    /// Number of users known to this client who follow the author.
+    pub followers: Option<usize>,
+    /// Labels we have given to the author.
+    pub labels: Vec<String>,
+    /// Buttons removing the labels.
+    pub labels_box: gtk::Box,
+    /// Fields among name and NIP-05 the author has changed recently.
+    pub recent_changes: Vec<&'static str>,
 }
 
 impl Profilebox {
-    pub fn new() -> Self {
+    pub fn new(me: XOnlyPublicKey) -> Self {
         Self {
+            me,
             author: None,
             avatar: ANONYMOUS_USER.clone(),
             banner: None,
+            counted: None,
+            following: None,
+            followers: None,
+            labels: Vec::new(),
+            labels_box: gtk::Box::new(gtk::Orientation::Horizontal, 4),
+            recent_changes: Vec::new(),
         }
     }
+
+    /// Whether the displayed user may be reported, i.e. it is not us.
+    pub fn can_report(&self) -> bool {
+        matches!(&self.author, Some(a) if a.pubkey != self.me)
+    }
+
+    pub fn following_label(&self) -> String {
+        match self.following {
+            Some(n) => format!("{n} following"),
+            None => "Following unknown".to_string(),
+        }
+    }
+
+    pub fn followers_label(&self) -> Option<String> {
+        self.followers.map(|n| match n {
+            1 => "1 follower known to this client".to_string(),
+            n => format!("{n} followers known to this client"),
+        })
+    }
+
+    /// Replaces buttons removing the labels by those of current labels.
+    pub fn refresh_labels(&self) {
+        while let Some(child) = self.labels_box.first_child() {
+            self.labels_box.remove(&child);
+        }
+
+        let author = match &self.author {
+            Some(author) => author.pubkey.to_string(),
+            None => return,
+        };
+        for label in &self.labels {
+            let button = gtk::Button::with_label(&format!("{} ✕", crate::labels::describe(label)));
+            button.add_css_class("author-label");
+            button.set_tooltip_text(Some(
+                "Remove the label, notes of the author are shown again",
+            ));
+            button.set_action_name(Some("main.unlabel-author"));
+            button.set_action_target_value(Some(&(author.clone(), label.clone()).to_variant()));
+            self.labels_box.append(&button);
+        }
+    }
+
+    /// Warning that the author has changed their name or NIP-05 lately,
+    /// which impersonators do. `None` if they have not.
+    pub fn recent_changes_label(&self) -> Option<String> {
+        if self.recent_changes.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "⚠ {} changed in the last {} days",
+            self.recent_changes.join(" and "),
+            RECENT_DAYS
+        ))
+    }
+
+    /// Target of actions listing contacts of the author.
+    pub fn contacts_target(&self) -> Option<gtk::glib::Variant> {
+        self.counted.map(|pubkey| pubkey.to_string().to_variant())
+    }
 }
 
 #[derive(Debug)]
 pub enum Input {
-    UpdatedProfile { author: Persona },
-    MetadataBitmap { url: Url, bitmap: Arc<Texture> },
+    UpdatedProfile {
+        author: Persona,
+    },
+    MetadataBitmap {
+        url: Url,
+        bitmap: Arc<Texture>,
+    },
+    /// Numbers of users the author follows, and of their known followers.
+    ContactCounts {
+        pubkey: XOnlyPublicKey,
+        following: Option<usize>,
+        followers: usize,
+    },
+    /// These are all labels we have given to the author now.
+    Labels(Vec<String>),
+    /// Fields of profile which the author has changed recently.
+    RecentChanges(Vec<&'static str>),
 }
@@ -0,0 +1,201 @@
+use std::collections::HashSet;
+
+use gtk::prelude::*;
+use nostr_sdk::prelude::EventId;
+use relm4::*;
+
+use crate::retention::PruneReport;
+use crate::Gnostique;
+
+/// Dialog for setting how long events are kept in the database and for
+/// pruning older ones right away.
+#[derive(Debug)]
+pub struct PruneDialog {
+    gnostique: Gnostique,
+    visible: bool,
+    /// Events displayed in lanes, they are kept however old.
+    keep: HashSet<EventId>,
+    /// Whether pruning is running right now.
+    busy: bool,
+    /// Outcome of the last pruning, or its progress.
+    status: Option<String>,
+    keep_days: gtk::SpinButton,
+    vacuum: gtk::CheckButton,
+}
+
+#[derive(Debug)]
+pub enum PruneDialogInput {
+    /// Show the dialog, events in `keep` are displayed and must stay.
+    Show(HashSet<EventId>),
+    Hide,
+    Prune,
+}
+
+#[derive(Debug)]
+pub enum PruneCmd {
+    /// Pruning has removed this much so far.
+    Progress(PruneReport),
+    Pruned(Result<PruneReport, String>),
+}
+
+#[relm4::component(pub)]
+impl Component for PruneDialog {
+    type Init = Gnostique;
+    type Input = PruneDialogInput;
+    type Output = ();
+    type CommandOutput = PruneCmd;
+
+    view! {
+        gtk::Window {
+            set_title: Some("Database retention"),
+            set_default_size: (400, 200),
+            #[watch] set_visible: model.visible,
+
+            connect_close_request[sender] => move |_| {
+                sender.input(PruneDialogInput::Hide);
+                gtk::Inhibit(true)
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 16,
+                add_css_class: "form",
+
+                gtk::Grid {
+                    set_column_spacing: 16,
+                    set_row_spacing: 16,
+
+                    attach[0, 0, 1, 1] = &gtk::Label {
+                        set_text: "Keep events for days",
+                        set_xalign: 1.0,
+                        add_css_class: "label",
+                    },
+                    attach: (&model.keep_days, 1, 0, 1, 1),
+                    attach: (&model.vacuum, 1, 1, 1, 1),
+                },
+
+                gtk::Label {
+                    set_text: "Our own, bookmarked and displayed events are always kept. Zero keeps everything.",
+                    set_wrap: true,
+                    set_xalign: 0.0,
+                    add_css_class: "dim-label",
+                },
+
+                gtk::Label {
+                    #[watch] set_label: model.status.as_deref().unwrap_or_default(),
+                    #[watch] set_visible: model.status.is_some(),
+                    set_wrap: true,
+                    set_xalign: 0.0,
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 8,
+
+                    gtk::Spinner {
+                        #[watch] set_spinning: model.busy,
+                        #[watch] set_visible: model.busy,
+                    },
+
+                    gtk::Box { set_hexpand: true },
+
+                    gtk::Button::with_label("Prune now") {
+                        add_css_class: "destructive-action",
+                        #[watch] set_sensitive: !model.busy,
+                        connect_clicked => PruneDialogInput::Prune
+                    }
+                }
+            }
+        }
+    }
+
+    fn init(
+        gnostique: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let keep_days = gtk::SpinButton::with_range(0.0, 3650.0, 1.0);
+        keep_days.set_hexpand(true);
+        keep_days.set_value(gnostique.retention().keep_days() as f64);
+        keep_days.connect_value_changed({
+            let gnostique = gnostique.clone();
+            move |s| {
+                let days = s.value() as u32;
+                if let Err(e) = gnostique.update_config(|c| c.general.keep_days = days) {
+                    tracing::warn!("{}", e);
+                }
+            }
+        });
+
+        let model = PruneDialog {
+            gnostique,
+            visible: false,
+            keep: HashSet::new(),
+            busy: false,
+            status: None,
+            keep_days,
+            vacuum: gtk::CheckButton::with_label("Compact the database afterwards"),
+        };
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>, _root: &Self::Root) {
+        match message {
+            PruneDialogInput::Show(keep) => {
+                self.keep = keep;
+                self.keep_days
+                    .set_value(self.gnostique.retention().keep_days() as f64);
+                if !self.busy {
+                    self.status = None;
+                }
+                self.visible = true;
+            }
+
+            // Running pruning goes on in background.
+            PruneDialogInput::Hide => self.visible = false,
+
+            PruneDialogInput::Prune => {
+                let gnostique = self.gnostique.clone();
+                let keep = self.keep.clone();
+                let vacuum = self.vacuum.is_active();
+                self.busy = true;
+                self.status = Some("Pruning…".to_string());
+
+                sender.command(move |out, _shutdown| async move {
+                    let me = gnostique.client().keys().public_key();
+                    let progress = out.clone();
+                    let result = gnostique
+                        .retention()
+                        .prune(me, &keep, vacuum, move |r| {
+                            progress.emit(PruneCmd::Progress(*r))
+                        })
+                        .await;
+                    out.emit(PruneCmd::Pruned(result));
+                });
+            }
+        }
+    }
+
+    fn update_cmd(
+        &mut self,
+        message: Self::CommandOutput,
+        _sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        match message {
+            PruneCmd::Progress(report) => {
+                self.status = Some(format!("Pruning… {}", report.describe()))
+            }
+            PruneCmd::Pruned(result) => {
+                self.busy = false;
+                self.status = Some(match result {
+                    Ok(report) => format!("Done: {}", report.describe()),
+                    Err(e) => format!("Could not prune the database: {e}"),
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,675 @@
+use std::time::Duration;
+
+use gtk::prelude::*;
+use nostr_sdk::prelude::Timestamp;
+use nostr_sdk::relay::RelayStatus;
+use relm4::prelude::*;
+use relm4::{gtk, ComponentParts};
+use reqwest::Url;
+
+use crate::nostr::RelayPolicy;
+use crate::relay_info::RelayInformation;
+use crate::relay_stats::Counts;
+use crate::timestamps::format_full;
+use crate::ui::toast::Toast;
+use crate::Gnostique;
+
+/// Connected relays that have sent no events for this many seconds are
+/// flagged as candidates for removal.
+const IDLE_AFTER: u64 = 60 * 60;
+
+/// A window listing relays of the client together with their current
+/// status. While visible, the status is kept up to date.
+pub struct RelaysWindow {
+    gnostique: Gnostique,
+
+    /// Whether the window is visible or hidden.
+    visible: bool,
+
+    /// Relays as they were last loaded.
+    relays: Vec<RelayRow>,
+
+    /// Whether numbers of events are shown over all sessions
+    /// rather than just this one.
+    all_time: bool,
+
+    /// Table of relays.
+    table: gtk::Grid,
+
+    /// Relay whose details are displayed, together with its information
+    /// document, if available.
+    selected: Option<(Url, Option<RelayInformation>)>,
+}
+
+/// Current state of one relay.
+#[derive(Clone, Debug)]
+pub struct RelayRow {
+    pub url: Url,
+    pub status: RelayStatus,
+    /// Seconds since an event was last received from the relay.
+    pub last_event_ago: Option<i64>,
+    /// When the relay first became known.
+    pub first_seen: Option<Timestamp>,
+    /// Information document (NIP-11) of the relay, if known.
+    pub information: Option<RelayInformation>,
+    /// Events are requested from the relay.
+    pub read: bool,
+    /// Our events are published to the relay.
+    pub write: bool,
+    /// Events received in this session.
+    pub session: Counts,
+    /// Events received over all sessions.
+    pub all_time: Counts,
+    /// Relay is connected, but has sent no events for a long time.
+    pub idle: bool,
+}
+
+/// Messages coming to [`RelaysWindow`].
+#[derive(Debug)]
+pub enum RelaysWindowInput {
+    /// Load relays and show the window, if hidden.
+    Show,
+
+    /// Hide the window.
+    Hide,
+
+    /// Time to refresh status of relays, if the window is visible.
+    Tick,
+
+    /// Relays have been loaded.
+    Loaded(Vec<RelayRow>),
+
+    /// Show numbers of events over all sessions, or just this one.
+    AllTime(bool),
+
+    /// Show details of the relay.
+    Select(Url),
+
+    /// Fetch information document of the selected relay again.
+    RefreshInformation,
+
+    /// Information document of the relay has been obtained.
+    Information(Url, Option<Box<RelayInformation>>),
+
+    /// Change whether events are requested from the relay.
+    SetRead(Url, bool),
+
+    /// Change whether our events are published to the relay.
+    SetWrite(Url, bool),
+
+    /// Publish our profile, contact list and relay list to relays we
+    /// write to that do not have them.
+    BroadcastProfile,
+}
+
+#[derive(Debug)]
+pub enum RelaysWindowOutput {
+    /// Tell user something, e. g. that the change has failed.
+    Toast(Toast),
+}
+
+#[relm4::component(pub)]
+impl Component for RelaysWindow {
+    type Init = Gnostique;
+    type Input = RelaysWindowInput;
+    type Output = RelaysWindowOutput;
+    type CommandOutput = ();
+
+    view! {
+        gtk::Window {
+            set_title: Some("Relays"),
+            set_default_size: (600, 400),
+            #[watch]
+            set_visible: model.visible,
+
+            connect_close_request[sender] => move |_| {
+                sender.input(RelaysWindowInput::Hide);
+                gtk::Inhibit(false)
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                add_css_class: "form",
+                set_spacing: 8,
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 8,
+
+                    gtk::Label {
+                        #[watch] set_label: &model.format_summary(),
+                        set_hexpand: true,
+                        set_xalign: 0.0,
+                    },
+
+                    gtk::Button {
+                        set_label: "Broadcast my profile",
+                        set_tooltip_text: Some(
+                            "Publish profile, contact list and relay list to relays we write to that miss them"
+                        ),
+                        connect_clicked => RelaysWindowInput::BroadcastProfile,
+                    },
+
+                    gtk::Box {
+                        add_css_class: "linked",
+
+                        #[name = "session_button"]
+                        gtk::ToggleButton {
+                            set_label: "This session",
+                            set_active: true,
+                            connect_toggled[sender] => move |b| {
+                                if b.is_active() {
+                                    sender.input(RelaysWindowInput::AllTime(false));
+                                }
+                            }
+                        },
+
+                        gtk::ToggleButton {
+                            set_label: "All time",
+                            set_group: Some(&session_button),
+                            connect_toggled[sender] => move |b| {
+                                if b.is_active() {
+                                    sender.input(RelaysWindowInput::AllTime(true));
+                                }
+                            }
+                        },
+                    },
+                },
+
+                gtk::ScrolledWindow {
+                    set_vexpand: true,
+                    #[wrap(Some)]
+                    set_child = &model.table.clone() {
+                        set_column_spacing: 16,
+                        set_row_spacing: 8,
+                    }
+                },
+
+                gtk::Separator {
+                    #[watch] set_visible: model.selected.is_some(),
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 8,
+                    #[watch] set_visible: model.selected.is_some(),
+
+                    gtk::Label {
+                        #[watch] set_markup: &model.format_information(),
+                        set_hexpand: true,
+                        set_xalign: 0.0,
+                        set_selectable: true,
+                        set_wrap: true,
+                    },
+
+                    gtk::Button::from_icon_name("view-refresh-symbolic") {
+                        set_valign: gtk::Align::Start,
+                        set_tooltip_text: Some("Fetch relay information again"),
+                        connect_clicked => RelaysWindowInput::RefreshInformation
+                    },
+                }
+            }
+        }
+    }
+
+    fn init(
+        gnostique: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        relm4::spawn({
+            let sender = sender.clone();
+            async move {
+                let mut int = tokio::time::interval(Duration::from_secs(2));
+                loop {
+                    int.tick().await;
+                    sender.input(RelaysWindowInput::Tick);
+                }
+            }
+        });
+
+        let model = RelaysWindow {
+            gnostique,
+            visible: false,
+            relays: Vec::new(),
+            all_time: false,
+            table: gtk::Grid::new(),
+            selected: None,
+        };
+        let widgets = view_output!();
+
+        ComponentParts { widgets, model }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>, _root: &Self::Root) {
+        match message {
+            RelaysWindowInput::Show => {
+                self.visible = true;
+                self.load(sender);
+            }
+            RelaysWindowInput::Hide => self.visible = false,
+            RelaysWindowInput::Tick => {
+                if self.visible {
+                    self.load(sender);
+                }
+            }
+            RelaysWindowInput::Loaded(relays) => {
+                self.relays = relays;
+                self.show_relays(sender);
+            }
+            RelaysWindowInput::AllTime(all_time) => {
+                self.all_time = all_time;
+                self.show_relays(sender);
+            }
+            RelaysWindowInput::Select(url) => {
+                let information = self
+                    .relays
+                    .iter()
+                    .find(|r| r.url == url)
+                    .and_then(|r| r.information.clone());
+                self.selected = Some((url, information));
+            }
+            RelaysWindowInput::RefreshInformation => {
+                if let Some((url, _)) = &self.selected {
+                    let gnostique = self.gnostique.clone();
+                    let url = url.clone();
+                    relm4::spawn(async move {
+                        let information = gnostique.relay_info().refresh(&url).await;
+                        sender.input(RelaysWindowInput::Information(
+                            url,
+                            information.map(Box::new),
+                        ));
+                    });
+                }
+            }
+            RelaysWindowInput::SetRead(url, read) => {
+                if let Some(row) = self.relays.iter_mut().find(|r| r.url == url) {
+                    row.read = read;
+                    let policy = row.policy();
+                    self.set_policy(policy, sender.clone());
+                }
+            }
+            RelaysWindowInput::SetWrite(url, write) => {
+                if let Some(row) = self.relays.iter_mut().find(|r| r.url == url) {
+                    row.write = write;
+                    let policy = row.policy();
+                    self.set_policy(policy, sender.clone());
+                }
+            }
+            RelaysWindowInput::BroadcastProfile => {
+                let gnostique = self.gnostique.clone();
+                relm4::spawn(async move {
+                    let toast = match gnostique.broadcast_profile().await {
+                        (0, _) => Toast::info("All relays we write to have our profile"),
+                        (events, relays) => {
+                            Toast::success(format!("Published {events} events to {relays} relays"))
+                        }
+                    };
+                    sender
+                        .output(RelaysWindowOutput::Toast(toast))
+                        .unwrap_or_default();
+                });
+            }
+            RelaysWindowInput::Information(url, information) => {
+                if let Some(selected) = self.selected.as_mut().filter(|(u, _)| *u == url) {
+                    selected.1 = information.map(|i| *i);
+                }
+            }
+        }
+    }
+}
+
+impl RelayRow {
+    fn counts(&self, all_time: bool) -> &Counts {
+        if all_time {
+            &self.all_time
+        } else {
+            &self.session
+        }
+    }
+
+    fn policy(&self) -> RelayPolicy {
+        RelayPolicy {
+            url: self.url.clone(),
+            read: self.read,
+            write: self.write,
+        }
+    }
+}
+
+impl RelaysWindow {
+    /// Stores and applies `policy` in the background.
+    fn set_policy(&self, policy: RelayPolicy, sender: ComponentSender<Self>) {
+        let gnostique = self.gnostique.clone();
+        relm4::spawn(async move {
+            let url = policy.url.clone();
+            if let Err(e) = gnostique.set_relay_policy(policy).await {
+                tracing::warn!("Could not change relay policy: {}", e);
+                sender
+                    .output(RelaysWindowOutput::Toast(Toast::error(format!(
+                        "Could not change relay {url}: {e}"
+                    ))))
+                    .unwrap_or_default();
+            }
+        });
+    }
+
+    /// Loads relays in the background.
+    fn load(&self, sender: ComponentSender<Self>) {
+        let gnostique = self.gnostique.clone();
+        relm4::spawn(async move {
+            sender.input(RelaysWindowInput::Loaded(load_relays(&gnostique).await));
+        });
+    }
+
+    fn format_summary(&self) -> String {
+        let connected = self
+            .relays
+            .iter()
+            .filter(|r| r.status == RelayStatus::Connected)
+            .count();
+        let idle = self.relays.iter().filter(|r| r.idle).count();
+
+        match idle {
+            0 => format!("{connected}/{} relays connected", self.relays.len()),
+            _ => format!(
+                "{connected}/{} relays connected, {idle} of them idle",
+                self.relays.len()
+            ),
+        }
+    }
+
+    /// Relays from the most active, as shown.
+    fn sorted_relays(&self) -> Vec<&RelayRow> {
+        let mut relays: Vec<&RelayRow> = self.relays.iter().collect();
+        relays.sort_by(|a, b| {
+            b.counts(self.all_time)
+                .events
+                .cmp(&a.counts(self.all_time).events)
+                .then_with(|| a.url.as_str().cmp(b.url.as_str()))
+        });
+        relays
+    }
+
+    /// Describes the selected relay according to its information document.
+    fn format_information(&self) -> String {
+        use html_escape::encode_text as text;
+
+        let (url, information) = match &self.selected {
+            Some(s) => s,
+            None => return String::new(),
+        };
+
+        let information = match information {
+            Some(i) => i,
+            None => return format!("<b>{}</b>\n\nNo information available.", text(url.as_str())),
+        };
+
+        let mut lines = vec![format!(
+            "<b>{}</b>",
+            text(information.name.as_deref().unwrap_or(url.as_str()))
+        )];
+
+        if let Some(description) = &information.description {
+            lines.push(text(description).to_string());
+        }
+
+        lines.push(String::new());
+
+        let mut field = |name: &str, value: Option<String>| {
+            if let Some(value) = value.filter(|v| !v.is_empty()) {
+                lines.push(format!("<b>{name}:</b> {}", text(&value)));
+            }
+        };
+
+        field(
+            "Software",
+            information
+                .software
+                .as_ref()
+                .map(|s| match &information.version {
+                    Some(v) => format!("{s} {v}"),
+                    None => s.clone(),
+                }),
+        );
+        field("Contact", information.contact.clone());
+        field(
+            "Supported NIPs",
+            Some(
+                information
+                    .supported_nips
+                    .iter()
+                    .map(|n| n.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+        );
+
+        if let Some(l) = &information.limitation {
+            field(
+                "Maximal message length",
+                l.max_message_length.map(|v| v.to_string()),
+            );
+            field(
+                "Maximal subscriptions",
+                l.max_subscriptions.map(|v| v.to_string()),
+            );
+            field("Maximal filters", l.max_filters.map(|v| v.to_string()));
+            field("Maximal limit", l.max_limit.map(|v| v.to_string()));
+            field(
+                "Maximal event tags",
+                l.max_event_tags.map(|v| v.to_string()),
+            );
+            field(
+                "Maximal content length",
+                l.max_content_length.map(|v| v.to_string()),
+            );
+            field(
+                "Minimal proof of work",
+                l.min_pow_difficulty.map(|v| v.to_string()),
+            );
+            field(
+                "Authentication required",
+                l.auth_required.then(|| "yes".to_string()),
+            );
+            field(
+                "Payment required",
+                l.payment_required.then(|| "yes".to_string()),
+            );
+        }
+
+        field("Payments", information.payments_url.clone());
+
+        lines.join("\n")
+    }
+
+    /// Fills table of relays.
+    fn show_relays(&self, sender: ComponentSender<Self>) {
+        while let Some(child) = self.table.first_child() {
+            self.table.remove(&child);
+        }
+
+        let label = |text: &str| {
+            let label = gtk::Label::new(Some(text));
+            label.set_xalign(0.0);
+            label
+        };
+
+        for (i, header) in [
+            "Relay",
+            "Status",
+            "Last active",
+            "Events",
+            "Data",
+            "Read",
+            "Write",
+            "",
+        ]
+        .iter()
+        .enumerate()
+        {
+            let header = label(header);
+            header.add_css_class("label");
+            self.table.attach(&header, i as i32, 0, 1, 1);
+        }
+
+        for (row, relay) in self.sorted_relays().into_iter().enumerate() {
+            let row = row as i32 + 1;
+            let counts = relay.counts(self.all_time);
+
+            let url = label(relay.url.as_str());
+            url.set_selectable(true);
+            url.set_hexpand(true);
+            if relay.idle {
+                url.add_css_class("idle-relay");
+                url.set_tooltip_text(Some(
+                    "Connected, but no events for a long time. Consider removing it.",
+                ));
+            }
+
+            let status = gtk::Label::new(None);
+            status.set_xalign(0.0);
+            status.set_markup(&format_status(&relay.status));
+
+            let last_event = label(&format_ago(relay.last_event_ago));
+            if let Some(t) = relay.first_seen {
+                let first_seen = format_full(t, self.gnostique.time_format());
+                last_event.set_tooltip_text(Some(&format!("First seen {first_seen}")));
+            }
+
+            let events = label(&counts.events.to_string());
+            if self.all_time {
+                events.set_tooltip_text(Some(&format!("In {} sessions", counts.sessions)));
+            }
+            let bytes = label(&format_bytes(counts.bytes));
+
+            let badges = label(&format_badges(relay.information.as_ref(), relay.idle));
+            badges.add_css_class("badges");
+
+            let details = gtk::Button::from_icon_name("dialog-information-symbolic");
+            details.set_has_frame(false);
+            details.set_tooltip_text(Some("Show relay information"));
+            details.connect_clicked({
+                let sender = sender.clone();
+                let url = relay.url.clone();
+                move |_| sender.input(RelaysWindowInput::Select(url.clone()))
+            });
+
+            self.table.attach(&url, 0, row, 1, 1);
+            self.table.attach(&status, 1, row, 1, 1);
+            self.table.attach(&last_event, 2, row, 1, 1);
+            self.table.attach(&events, 3, row, 1, 1);
+            self.table.attach(&bytes, 4, row, 1, 1);
+            let read = gtk::CheckButton::new();
+            read.set_active(relay.read);
+            read.set_tooltip_text(Some("Request events from this relay"));
+            read.connect_toggled({
+                let sender = sender.clone();
+                let url = relay.url.clone();
+                move |b| sender.input(RelaysWindowInput::SetRead(url.clone(), b.is_active()))
+            });
+
+            let write = gtk::CheckButton::new();
+            write.set_active(relay.write);
+            write.set_tooltip_text(Some("Publish our events to this relay"));
+            write.connect_toggled({
+                let sender = sender.clone();
+                let url = relay.url.clone();
+                move |b| sender.input(RelaysWindowInput::SetWrite(url.clone(), b.is_active()))
+            });
+
+            self.table.attach(&read, 5, row, 1, 1);
+            self.table.attach(&write, 6, row, 1, 1);
+            self.table.attach(&badges, 7, row, 1, 1);
+            self.table.attach(&details, 8, row, 1, 1);
+        }
+    }
+}
+
+/// Obtains current status of all relays of the client.
+async fn load_relays(gnostique: &Gnostique) -> Vec<RelayRow> {
+    let mut relays = Vec::new();
+    let stats = gnostique.relay_stats();
+    let session = stats.session();
+    let all_time = stats.all_time().await;
+    let activity = gnostique.relay_activity().await;
+    let now = Timestamp::now().as_i64();
+
+    for (url, relay) in gnostique.client().relays().await {
+        let session = session.get(&url).copied().unwrap_or_default();
+        let all_time = all_time.get(&url).copied().unwrap_or_default();
+        let activity = activity.get(&url).copied().unwrap_or_default();
+        let last_event_ago = activity.last_event_at.map(|t| now - t.as_i64());
+        let status = relay.status().await;
+
+        // Relays are not blamed for time before this session.
+        let quiet = match session.last_event_at {
+            Some(t) => (now - t.as_i64()).max(0) as u64,
+            None => stats.uptime(),
+        };
+
+        relays.push(RelayRow {
+            information: gnostique.relay_info().stored(&url).await,
+            read: relay.opts().read(),
+            write: relay.opts().write(),
+            idle: status == RelayStatus::Connected && quiet > IDLE_AFTER,
+            url,
+            status,
+            last_event_ago,
+            first_seen: activity.first_seen_at,
+            session,
+            all_time,
+        });
+    }
+
+    relays
+}
+
+fn format_status(status: &RelayStatus) -> String {
+    match status {
+        RelayStatus::Connected => r##"<span color="#00ff00">●</span> Connected"##.to_string(),
+        RelayStatus::Disconnected | RelayStatus::Terminated => {
+            r#"<span color="red">●</span> Disconnected"#.to_string()
+        }
+        _ => r#"<span color="orange">●</span> Connecting"#.to_string(),
+    }
+}
+
+/// Flags from limitations of relay that user should be aware of,
+/// and whether it is `idle`.
+fn format_badges(information: Option<&RelayInformation>, idle: bool) -> String {
+    let mut badges = vec![];
+
+    if idle {
+        badges.push("💤 idle");
+    }
+
+    if let Some(i) = information {
+        if i.payment_required() {
+            badges.push("💰 paid");
+        }
+        if i.auth_required() {
+            badges.push("🔒 auth");
+        }
+    }
+
+    badges.join("  ")
+}
+
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        b if b < 1024 => format!("{b} B"),
+        b if b < 1024 * 1024 => format!("{:.1} kB", b as f64 / 1024.0),
+        b => format!("{:.1} MB", b as f64 / (1024.0 * 1024.0)),
+    }
+}
+
+fn format_ago(seconds: Option<i64>) -> String {
+    match seconds {
+        None => "never".to_string(),
+        Some(s) if s < 60 => "just now".to_string(),
+        Some(s) if s < 60 * 60 => format!("{} min ago", s / 60),
+        Some(s) if s < 24 * 60 * 60 => format!("{} h ago", s / 60 / 60),
+        Some(s) => format!("{} d ago", s / 24 / 60 / 60),
+    }
+}
@@ -4,14 +4,14 @@ use std::sync::Arc;
 
 use gtk::prelude::*;
 use nostr_sdk::nostr::{Event, EventId};
-use nostr_sdk::prelude::XOnlyPublicKey;
+use nostr_sdk::prelude::{Timestamp, XOnlyPublicKey};
 use relm4::component::{AsyncComponentParts, SimpleAsyncComponent};
 use relm4::factory::{AsyncFactoryComponent, AsyncFactoryVecDeque};
 use relm4::prelude::*;
 use relm4::{gtk, AsyncComponentSender, AsyncFactorySender};
 
 use super::author::Author;
-use crate::nostr::Persona;
+use crate::nostr::{Nip05Status, Persona};
 
 /// Widget displaying list of replies to a text note.
 #[derive(Debug)]
@@ -117,17 +117,17 @@ impl AsyncFactoryComponent for Reply {
 
                 #[template_child]
                 author_name {
-                    #[watch] set_label?: self.author.name.as_ref(),
-                    #[watch] set_visible: self.author.name.is_some(),
+                    #[watch] set_label: &self.author.shown_name(),
                 },
                 #[template_child]
                 author_pubkey {
                     #[watch] set_label: &self.author.format_pubkey(8, 8),
-                    #[watch] set_visible: !self.author.show_nip05(),
+                    #[watch] set_visible: self.author.has_name() && !self.author.show_nip05(),
                 },
                 #[template_child]
                 author_nip05 {
                     #[watch] set_label?: &self.author.format_nip05(),
+                    #[watch] set_tooltip_text: self.author.nip05_tooltip().as_deref(),
                     #[watch] set_visible: self.author.show_nip05(),
                 }
             },
@@ -164,7 +164,7 @@ impl AsyncFactoryComponent for Reply {
             }
             ReplyInput::Nip05Verified(pubkey) => {
                 if pubkey == self.author.pubkey {
-                    self.author.nip05_verified = true;
+                    self.author.nip05_verified = Nip05Status::Verified(Timestamp::now());
                 }
             }
         }
@@ -0,0 +1,215 @@
+use gtk::prelude::*;
+use relm4::*;
+
+use crate::nostr::{ReportSubject, ReportType};
+use crate::Gnostique;
+
+/// Dialog for reporting notes and users according to NIP-56.
+#[derive(Debug)]
+pub struct ReportDialog {
+    gnostique: Gnostique,
+    visible: bool,
+    /// What is being reported.
+    subject: Option<ReportSubject>,
+    /// Whether the report is being published right now.
+    sending: bool,
+    /// Why the report could not be published.
+    error: Option<String>,
+    report_type: gtk::DropDown,
+    comment: gtk::Entry,
+    hide: gtk::CheckButton,
+}
+
+#[derive(Debug)]
+pub enum ReportDialogInput {
+    /// Let user report `subject`.
+    Show(ReportSubject),
+    Cancel,
+    Send,
+}
+
+#[derive(Debug)]
+pub enum ReportDialogOutput {
+    /// Report of `subject` has been published. It should not be displayed
+    /// anymore if `hidden` is set.
+    Reported {
+        subject: ReportSubject,
+        hidden: bool,
+    },
+}
+
+#[relm4::component(pub)]
+impl Component for ReportDialog {
+    type Init = Gnostique;
+    type Input = ReportDialogInput;
+    type Output = ReportDialogOutput;
+    type CommandOutput = Result<(), String>;
+
+    view! {
+        gtk::Window {
+            set_widget_name: "report",
+            set_default_size: (400, 200),
+            #[watch] set_title: Some(model.title()),
+            #[watch] set_visible: model.visible,
+
+            connect_close_request[sender] => move |_| {
+                sender.input(ReportDialogInput::Cancel);
+                gtk::Inhibit(true)
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 16,
+                add_css_class: "form",
+
+                gtk::Grid {
+                    set_column_spacing: 16,
+                    set_row_spacing: 16,
+
+                    attach[0, 0, 1, 1] = &gtk::Label {
+                        set_text: "Reason",
+                        set_xalign: 1.0,
+                        add_css_class: "label",
+                    },
+
+                    attach: (&model.report_type, 1, 0, 1, 1),
+
+                    attach[0, 1, 1, 1] = &gtk::Label {
+                        set_text: "Comment",
+                        set_xalign: 1.0,
+                        add_css_class: "label",
+                    },
+
+                    attach: (&model.comment, 1, 1, 1, 1),
+                    attach: (&model.hide, 1, 2, 1, 1),
+                },
+
+                gtk::Label {
+                    #[watch] set_label: model.error.as_deref().unwrap_or_default(),
+                    #[watch] set_visible: model.error.is_some(),
+                    set_wrap: true,
+                    set_xalign: 0.0,
+                    add_css_class: "error",
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 8,
+
+                    gtk::Spinner {
+                        #[watch] set_spinning: model.sending,
+                        #[watch] set_visible: model.sending,
+                    },
+
+                    gtk::Box { set_hexpand: true },
+
+                    gtk::Button::with_label("Cancel") {
+                        connect_clicked => ReportDialogInput::Cancel
+                    },
+
+                    gtk::Button::with_label("Report") {
+                        add_css_class: "destructive-action",
+                        #[watch] set_sensitive: !model.sending,
+                        connect_clicked => ReportDialogInput::Send
+                    }
+                }
+            }
+        }
+    }
+
+    fn init(
+        gnostique: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let types: Vec<&str> = ReportType::ALL.iter().map(|t| t.describe()).collect();
+
+        let model = ReportDialog {
+            gnostique,
+            visible: false,
+            subject: None,
+            sending: false,
+            error: None,
+            report_type: gtk::DropDown::from_strings(&types),
+            comment: gtk::Entry::new(),
+            hide: gtk::CheckButton::with_label("Do not show it to me anymore"),
+        };
+        model.report_type.set_hexpand(true);
+        model.comment.set_placeholder_text(Some("Optional"));
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>, _root: &Self::Root) {
+        match message {
+            ReportDialogInput::Show(subject) => {
+                self.subject = Some(subject);
+                self.error = None;
+                self.sending = false;
+                self.report_type.set_selected(0);
+                self.comment.set_text("");
+                self.hide.set_active(true);
+                self.visible = true;
+            }
+
+            // Publishing cannot be taken back, the dialog stays until it is done.
+            ReportDialogInput::Cancel if self.sending => {}
+            ReportDialogInput::Cancel => self.visible = false,
+
+            ReportDialogInput::Send => {
+                if let Some(subject) = self.subject {
+                    let report_type = ReportType::ALL
+                        .get(self.report_type.selected() as usize)
+                        .copied()
+                        .unwrap_or(ReportType::Other);
+                    let comment = self.comment.text().to_string();
+                    let hide = self.hide.is_active();
+                    let gnostique = self.gnostique.clone();
+
+                    self.sending = true;
+                    self.error = None;
+                    sender.oneshot_command(async move {
+                        gnostique
+                            .report(subject, report_type, &comment, hide)
+                            .await
+                            .map(|_| ())
+                    });
+                }
+            }
+        }
+    }
+
+    fn update_cmd(
+        &mut self,
+        result: Self::CommandOutput,
+        sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        self.sending = false;
+
+        match (result, self.subject) {
+            (Ok(()), Some(subject)) => {
+                self.visible = false;
+                sender
+                    .output(ReportDialogOutput::Reported {
+                        subject,
+                        hidden: self.hide.is_active(),
+                    })
+                    .unwrap_or_default();
+            }
+            (Ok(()), None) => self.visible = false,
+            (Err(e), _) => self.error = Some(format!("Could not publish the report: {e}")),
+        }
+    }
+}
+
+impl ReportDialog {
+    fn title(&self) -> &'static str {
+        match self.subject {
+            Some(ReportSubject::User(_)) => "Report user",
+            _ => "Report note",
+        }
+    }
+}
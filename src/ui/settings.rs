@@ -0,0 +1,426 @@
+use gtk::prelude::*;
+use relm4::*;
+
+use crate::config::{Config, FloodAction, Theme, TranslationConfig, UploadConfig};
+use crate::Gnostique;
+
+/// Dialog for changing configuration, which is then written into
+/// the configuration file.
+#[derive(Debug)]
+pub struct SettingsDialog {
+    gnostique: Gnostique,
+    visible: bool,
+    /// Why the settings could not be saved.
+    error: Option<String>,
+    desktop_notifications: gtk::CheckButton,
+    keep_running: gtk::CheckButton,
+    min_pow: gtk::SpinButton,
+    languages: gtk::Entry,
+    keep_days: gtk::SpinButton,
+    max_cache_mb: gtk::SpinButton,
+    relay_info_hours: gtk::SpinButton,
+    theme: gtk::DropDown,
+    collapse_lines: gtk::SpinButton,
+    collapse_chars: gtk::SpinButton,
+    relative_time: gtk::CheckButton,
+    clock_24h: gtk::CheckButton,
+    link_previews: gtk::CheckButton,
+    client_tag: gtk::CheckButton,
+    flood_notes: gtk::SpinButton,
+    flood_window: gtk::SpinButton,
+    flood_action: gtk::DropDown,
+    flood_exempt_follows: gtk::CheckButton,
+    translation_url: gtk::Entry,
+    translation_key: gtk::PasswordEntry,
+    translation_target: gtk::Entry,
+    upload_server: gtk::Entry,
+    upload_fallbacks: gtk::Entry,
+    upload_on_send: gtk::CheckButton,
+}
+
+#[derive(Debug)]
+pub enum SettingsDialogInput {
+    Show,
+    Hide,
+    Save,
+}
+
+#[derive(Debug)]
+pub enum SettingsDialogOutput {
+    /// Settings have been changed and saved.
+    Saved,
+}
+
+#[relm4::component(pub)]
+impl Component for SettingsDialog {
+    type Init = Gnostique;
+    type Input = SettingsDialogInput;
+    type Output = SettingsDialogOutput;
+    type CommandOutput = ();
+
+    view! {
+        gtk::Window {
+            set_title: Some("Settings"),
+            set_default_size: (500, 350),
+            #[watch] set_visible: model.visible,
+
+            connect_close_request[sender] => move |_| {
+                sender.input(SettingsDialogInput::Hide);
+                gtk::Inhibit(true)
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 16,
+                add_css_class: "form",
+
+                #[local_ref]
+                pages -> gtk::Notebook {
+                    set_vexpand: true,
+                },
+
+                gtk::Label {
+                    #[watch] set_label: model.error.as_deref().unwrap_or_default(),
+                    #[watch] set_visible: model.error.is_some(),
+                    set_wrap: true,
+                    set_xalign: 0.0,
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 8,
+
+                    gtk::Box { set_hexpand: true },
+
+                    gtk::Button::with_label("Cancel") {
+                        connect_clicked => SettingsDialogInput::Hide
+                    },
+
+                    gtk::Button::with_label("Save") {
+                        add_css_class: "suggested-action",
+                        connect_clicked => SettingsDialogInput::Save
+                    }
+                }
+            }
+        }
+    }
+
+    fn init(
+        gnostique: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = SettingsDialog {
+            gnostique,
+            visible: false,
+            error: None,
+            desktop_notifications: gtk::CheckButton::with_label("Desktop notifications"),
+            keep_running: gtk::CheckButton::with_label("Keep running when the window is closed"),
+            min_pow: gtk::SpinButton::with_range(0.0, 32.0, 1.0),
+            languages: gtk::Entry::builder()
+                .placeholder_text("All, or codes such as eng, deu")
+                .build(),
+            keep_days: gtk::SpinButton::with_range(0.0, 3650.0, 1.0),
+            max_cache_mb: gtk::SpinButton::with_range(50.0, 100_000.0, 50.0),
+            relay_info_hours: gtk::SpinButton::with_range(1.0, 720.0, 1.0),
+            theme: gtk::DropDown::from_strings(&["System", "Light", "Dark"]),
+            collapse_lines: gtk::SpinButton::with_range(0.0, 1000.0, 1.0),
+            collapse_chars: gtk::SpinButton::with_range(0.0, 100_000.0, 100.0),
+            relative_time: gtk::CheckButton::with_label("Relative times, such as 5m"),
+            clock_24h: gtk::CheckButton::with_label("24-hour clock"),
+            link_previews: gtk::CheckButton::with_label("Fetch previews of linked web pages"),
+            client_tag: gtk::CheckButton::with_label("Tell that notes come from Gnostique"),
+            flood_notes: gtk::SpinButton::with_range(0.0, 1000.0, 1.0),
+            flood_window: gtk::SpinButton::with_range(1.0, 3600.0, 10.0),
+            flood_action: gtk::DropDown::from_strings(&["Collapse", "Drop"]),
+            flood_exempt_follows: gtk::CheckButton::with_label(
+                "People we follow may post any number",
+            ),
+            translation_url: gtk::Entry::builder()
+                .placeholder_text("https://libretranslate.example.com")
+                .build(),
+            translation_key: gtk::PasswordEntry::builder().show_peek_icon(true).build(),
+            translation_target: gtk::Entry::builder().placeholder_text("en").build(),
+            upload_server: gtk::Entry::builder()
+                .placeholder_text("https://files.example.com")
+                .build(),
+            upload_fallbacks: gtk::Entry::builder()
+                .placeholder_text("None, or URLs separated by spaces")
+                .build(),
+            upload_on_send: gtk::CheckButton::with_label(
+                "Upload attached files only when the note is sent",
+            ),
+        };
+
+        let mut general_note =
+            "Notes too short to tell their language are always shown.".to_string();
+        if crate::spelling::available().is_empty() {
+            general_note.push_str(
+                " Spelling of notes is not checked, as no Hunspell dictionaries, such as hunspell-en-us, are installed.",
+            );
+        }
+
+        let pages = gtk::Notebook::new();
+        add_page(
+            &pages,
+            "General",
+            &[
+                ("", model.desktop_notifications.upcast_ref()),
+                ("", model.keep_running.upcast_ref()),
+                ("Minimum proof of work", model.min_pow.upcast_ref()),
+                ("Show notes in languages", model.languages.upcast_ref()),
+                ("Keep events for days", model.keep_days.upcast_ref()),
+            ],
+            Some(&general_note),
+        );
+        add_page(
+            &pages,
+            "Network",
+            &[
+                ("Cache size in MB", model.max_cache_mb.upcast_ref()),
+                (
+                    "Refresh relay information after hours",
+                    model.relay_info_hours.upcast_ref(),
+                ),
+            ],
+            Some("Changes take effect after restart."),
+        );
+        add_page(
+            &pages,
+            "Appearance",
+            &[
+                ("Theme", model.theme.upcast_ref()),
+                ("Collapse notes longer than lines", model.collapse_lines.upcast_ref()),
+                ("Or characters", model.collapse_chars.upcast_ref()),
+                ("", model.relative_time.upcast_ref()),
+                ("", model.clock_24h.upcast_ref()),
+            ],
+            Some("With zero, notes are not collapsed. Styles in style.css next to config.toml are loaded on saving."),
+        );
+        add_page(
+            &pages,
+            "Privacy",
+            &[
+                ("", model.link_previews.upcast_ref()),
+                ("", model.client_tag.upcast_ref()),
+            ],
+            None,
+        );
+        add_page(
+            &pages,
+            "Spam",
+            &[
+                ("Flooding with more notes than", model.flood_notes.upcast_ref()),
+                ("Within seconds", model.flood_window.upcast_ref()),
+                ("Notes of flooding authors", model.flood_action.upcast_ref()),
+                ("", model.flood_exempt_follows.upcast_ref()),
+            ],
+            Some("With zero, nobody is flooding. Authors who never are can be listed in config.toml."),
+        );
+        add_page(
+            &pages,
+            "Translation",
+            &[
+                ("LibreTranslate server", model.translation_url.upcast_ref()),
+                ("API key", model.translation_key.upcast_ref()),
+                ("Translate into", model.translation_target.upcast_ref()),
+            ],
+            Some("Content of notes you translate is sent to this server, which learns what you read and when. Leave the server empty not to translate."),
+        );
+        add_page(
+            &pages,
+            "Uploads",
+            &[
+                ("NIP-96 server", model.upload_server.upcast_ref()),
+                ("When it fails, try", model.upload_fallbacks.upcast_ref()),
+                ("", model.upload_on_send.upcast_ref()),
+            ],
+            Some("Files attached to notes are uploaded to these servers, which learn your public key. Leave the server empty not to attach files."),
+        );
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>, _root: &Self::Root) {
+        match message {
+            SettingsDialogInput::Show => {
+                self.load(&self.gnostique.config());
+                self.error = None;
+                self.visible = true;
+            }
+
+            SettingsDialogInput::Hide => self.visible = false,
+
+            SettingsDialogInput::Save => {
+                let saved = crate::language::parse_list(&self.languages.text())
+                    .and_then(|languages| Ok((languages, self.translation()?, self.upload()?)))
+                    .and_then(|(languages, translation, upload)| {
+                        self.gnostique.update_config(|c| {
+                            self.store(c);
+                            c.general.languages = languages;
+                            c.translation = translation;
+                            c.upload = upload;
+                        })
+                    });
+                match saved {
+                    Ok(()) => {
+                        self.visible = false;
+                        sender
+                            .output(SettingsDialogOutput::Saved)
+                            .unwrap_or_default();
+                    }
+                    Err(e) => self.error = Some(e),
+                }
+            }
+        }
+    }
+}
+
+impl SettingsDialog {
+    /// Shows values of `config` in the dialog.
+    fn load(&self, config: &Config) {
+        self.desktop_notifications
+            .set_active(config.general.desktop_notifications);
+        self.keep_running.set_active(config.general.keep_running);
+        self.min_pow.set_value(config.general.min_pow as f64);
+        self.languages
+            .set_text(&config.general.languages.join(", "));
+        self.keep_days.set_value(config.general.keep_days as f64);
+        self.max_cache_mb
+            .set_value(config.network.max_cache_mb as f64);
+        self.relay_info_hours
+            .set_value(config.network.relay_info_hours as f64);
+        self.theme.set_selected(match config.appearance.theme {
+            Theme::System => 0,
+            Theme::Light => 1,
+            Theme::Dark => 2,
+        });
+        self.collapse_lines
+            .set_value(config.appearance.collapse.lines as f64);
+        self.collapse_chars
+            .set_value(config.appearance.collapse.chars as f64);
+        self.relative_time.set_active(config.time.relative);
+        self.clock_24h.set_active(config.time.clock_24h);
+        self.link_previews.set_active(config.privacy.link_previews);
+        self.client_tag.set_active(config.privacy.client_tag);
+        self.flood_notes.set_value(config.spam.max_notes as f64);
+        self.flood_window.set_value(config.spam.window_secs as f64);
+        self.flood_action.set_selected(match config.spam.action {
+            FloodAction::Collapse => 0,
+            FloodAction::Drop => 1,
+        });
+        self.flood_exempt_follows
+            .set_active(config.spam.exempt_follows);
+        self.translation_url.set_text(&config.translation.url);
+        self.translation_key.set_text(&config.translation.api_key);
+        self.translation_target.set_text(&config.translation.target);
+        self.upload_server.set_text(&config.upload.server);
+        self.upload_fallbacks
+            .set_text(&config.upload.fallbacks.join(" "));
+        self.upload_on_send.set_active(config.upload.on_send);
+    }
+
+    /// Upload servers as entered, if their URLs are valid.
+    fn upload(&self) -> Result<UploadConfig, String> {
+        let server = self.upload_server.text().trim().to_string();
+        let fallbacks: Vec<String> = self
+            .upload_fallbacks
+            .text()
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .map(ToString::to_string)
+            .collect();
+        for url in std::iter::once(&server)
+            .filter(|s| !s.is_empty())
+            .chain(&fallbacks)
+        {
+            crate::upload::server_url(url)?;
+        }
+        if server.is_empty() && !fallbacks.is_empty() {
+            return Err("Upload server is missing, fallbacks are tried only after it".to_string());
+        }
+
+        Ok(UploadConfig {
+            server,
+            fallbacks,
+            on_send: self.upload_on_send.is_active(),
+        })
+    }
+
+    /// Translation server as entered, if its URL is valid.
+    fn translation(&self) -> Result<TranslationConfig, String> {
+        let url = self.translation_url.text().trim().to_string();
+        if !url.is_empty() {
+            crate::translate::endpoint(&url)?;
+        }
+        let target = self.translation_target.text().trim().to_lowercase();
+        if target.is_empty() {
+            return Err("Language to translate into is missing, such as en".to_string());
+        }
+
+        Ok(TranslationConfig {
+            url,
+            api_key: self.translation_key.text().trim().to_string(),
+            target,
+        })
+    }
+
+    /// Puts values from the dialog into `config`.
+    fn store(&self, config: &mut Config) {
+        config.general.desktop_notifications = self.desktop_notifications.is_active();
+        config.general.keep_running = self.keep_running.is_active();
+        config.general.min_pow = self.min_pow.value() as u8;
+        config.general.keep_days = self.keep_days.value() as u32;
+        config.network.max_cache_mb = self.max_cache_mb.value() as u64;
+        config.network.relay_info_hours = self.relay_info_hours.value() as u64;
+        config.appearance.theme = match self.theme.selected() {
+            0 => Theme::System,
+            1 => Theme::Light,
+            _ => Theme::Dark,
+        };
+        config.appearance.collapse.lines = self.collapse_lines.value() as usize;
+        config.appearance.collapse.chars = self.collapse_chars.value() as usize;
+        config.time.relative = self.relative_time.is_active();
+        config.time.clock_24h = self.clock_24h.is_active();
+        config.privacy.link_previews = self.link_previews.is_active();
+        config.privacy.client_tag = self.client_tag.is_active();
+        config.spam.max_notes = self.flood_notes.value() as u32;
+        config.spam.window_secs = self.flood_window.value() as u64;
+        config.spam.action = match self.flood_action.selected() {
+            0 => FloodAction::Collapse,
+            _ => FloodAction::Drop,
+        };
+        config.spam.exempt_follows = self.flood_exempt_follows.is_active();
+    }
+}
+
+/// Adds page of labelled `rows` titled `title`, with `note` below them.
+/// Rows without label are check buttons, which have their own.
+fn add_page(pages: &gtk::Notebook, title: &str, rows: &[(&str, &gtk::Widget)], note: Option<&str>) {
+    let grid = gtk::Grid::new();
+    grid.set_column_spacing(16);
+    grid.set_row_spacing(16);
+    grid.set_margin_all(16);
+
+    for (row, (label, widget)) in rows.iter().enumerate() {
+        if !label.is_empty() {
+            let label = gtk::Label::new(Some(label));
+            label.set_xalign(1.0);
+            label.add_css_class("label");
+            grid.attach(&label, 0, row as i32, 1, 1);
+        }
+        widget.set_hexpand(true);
+        grid.attach(*widget, 1, row as i32, 1, 1);
+    }
+
+    if let Some(note) = note {
+        let note = gtk::Label::new(Some(note));
+        note.set_xalign(0.0);
+        note.add_css_class("dim-label");
+        grid.attach(&note, 1, rows.len() as i32, 1, 1);
+    }
+
+    pages.append_page(&grid, Some(&gtk::Label::new(Some(title))));
+}
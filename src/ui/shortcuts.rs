@@ -0,0 +1,71 @@
+//! Keyboard shortcuts of the main window and the window listing them.
+
+use gtk::prelude::*;
+use relm4::{gtk, AsyncComponentSender};
+
+use crate::ui::main::{Main, MainInput};
+
+/// Trigger, whether it is a key that could be typed into text,
+/// and what the shortcut does.
+type Shortcut = (&'static str, bool, fn() -> MainInput);
+
+/// Makes keyboard shortcuts work anywhere in the window of `widget`.
+pub fn add_shortcuts(widget: &impl IsA<gtk::Widget>, sender: AsyncComponentSender<Main>) {
+    let shortcuts: [Shortcut; 11] = [
+        ("<Control>n", false, || MainInput::WriteNote),
+        ("<Control>k", false, || MainInput::FocusSearch),
+        ("<Control>f", false, || MainInput::FocusSearch),
+        ("<Control>w", false, || MainInput::CloseLane),
+        ("<Control>Tab", false, || MainInput::FocusLane {
+            forward: true,
+        }),
+        (
+            "<Control><Shift>Tab|<Control><Shift>ISO_Left_Tab",
+            false,
+            || MainInput::FocusLane { forward: false },
+        ),
+        ("j|Down", true, || MainInput::SelectNote { forward: true }),
+        ("k|Up", true, || MainInput::SelectNote { forward: false }),
+        ("r", true, || MainInput::ReplySelected),
+        ("<Control>question", false, || MainInput::ShowShortcuts),
+        ("<Control>q", false, || MainInput::Quit),
+    ];
+
+    let controller = gtk::ShortcutController::new();
+    controller.set_scope(gtk::ShortcutScope::Global);
+    controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+
+    for (trigger, typeable, input) in shortcuts {
+        let sender = sender.clone();
+        let action = gtk::CallbackAction::new(move |widget, _| {
+            if typeable && is_typing(widget) {
+                return false;
+            }
+            sender.input(input());
+            true
+        });
+
+        controller.add_shortcut(&gtk::Shortcut::new(
+            gtk::ShortcutTrigger::parse_string(trigger).as_ref(),
+            Some(&action),
+        ));
+    }
+
+    widget.add_controller(&controller);
+}
+
+/// Whether user is typing text in the window of `widget`.
+fn is_typing(widget: &gtk::Widget) -> bool {
+    widget
+        .root()
+        .and_then(|root| root.focus())
+        .map(|focus| focus.is::<gtk::Text>() || focus.is::<gtk::TextView>())
+        .unwrap_or_default()
+}
+
+/// Window describing keyboard shortcuts.
+pub fn shortcuts_window() -> gtk::ShortcutsWindow {
+    gtk::Builder::from_resource("/com/jirijakes/gnostique/ui/shortcuts.ui")
+        .object("shortcuts")
+        .expect("Shortcuts window is missing in resources")
+}
@@ -20,11 +20,21 @@ pub struct RelayStatus {
 #[derive(Debug)]
 pub struct StatusBar {
     relay_status: Option<RelayStatus>,
+    /// Number of published events not yet accepted by any relay.
+    pending: u32,
+    /// Whether storing into the database keeps failing.
+    degraded: bool,
+    /// How a longer task in the background goes, if any is running.
+    progress: Option<String>,
 }
 
 #[derive(Debug)]
 pub enum StatusBarInput {
     UpdateRelayStatus(RelayStatus),
+    UpdatePending(u32),
+    SetDegraded(bool),
+    /// Longer task has got this far, or it has ended.
+    Progress(Option<String>),
 }
 
 #[relm4::component(pub)]
@@ -45,6 +55,29 @@ impl SimpleComponent for StatusBar {
                 set_hexpand: true,
             },
 
+            gtk::Label {
+                add_css_class: "progress",
+                #[watch] set_visible: model.progress.is_some(),
+                #[watch] set_label: model.progress.as_deref().unwrap_or_default(),
+            },
+
+            gtk::Label {
+                #[watch] set_visible: model.degraded,
+                set_markup: r#"<span color="red">Database errors</span>"#,
+                set_tooltip_text: Some("Received events are displayed, but they cannot be stored"),
+            },
+
+            gtk::Button {
+                set_has_frame: false,
+                #[watch] set_visible: model.pending > 0,
+                set_tooltip_text: Some("Published events waiting for delivery to relays"),
+                set_action_name: Some("main.outbox"),
+                #[wrap(Some)]
+                set_child = &gtk::Label {
+                    #[watch] set_label: &format!("Pending: {}", model.pending),
+                }
+            },
+
             gtk::Box {
                 set_orientation: gtk::Orientation::Horizontal,
                 #[watch] set_visible: model.relay_status.is_some(),
@@ -52,6 +85,7 @@ impl SimpleComponent for StatusBar {
 
                 gtk::Button {
                     #[watch] set_tooltip_markup: Some(&model.format_relay_status_tooltip()),
+                    set_action_name: Some("main.relays"),
                     #[wrap(Some)]
                     set_child = &gtk::Label {
                         #[watch] set_markup?: &model.format_relay_status(),
@@ -66,9 +100,18 @@ impl SimpleComponent for StatusBar {
         root: &Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        relm4::spawn(update_relay_status(gnostique.client().clone(), sender));
-
-        let model = StatusBar { relay_status: None };
+        relm4::spawn(update_relay_status(
+            gnostique.client().clone(),
+            sender.clone(),
+        ));
+        relm4::spawn(update_pending(gnostique, sender));
+
+        let model = StatusBar {
+            relay_status: None,
+            pending: 0,
+            degraded: false,
+            progress: None,
+        };
         let widgets = view_output!();
 
         ComponentParts { model, widgets }
@@ -77,6 +120,9 @@ impl SimpleComponent for StatusBar {
     fn update(&mut self, message: Self::Input, _sender: ComponentSender<Self>) {
         match message {
             StatusBarInput::UpdateRelayStatus(status) => self.relay_status = Some(status),
+            StatusBarInput::UpdatePending(pending) => self.pending = pending,
+            StatusBarInput::SetDegraded(degraded) => self.degraded = degraded,
+            StatusBarInput::Progress(progress) => self.progress = progress,
         }
     }
 }
@@ -89,8 +135,9 @@ impl StatusBar {
             ref disconnected,
         }) = self.relay_status
         {
+            let total = connected.len() + connecting.len() + disconnected.len();
             Some(format!(
-                r###"Relays: <span color="#00ff00">{}</span>  <span color="orange">{}</span>  <span color="red">{}</span>"###,
+                r###"<span color="#00ff00">{}</span>/{total} relays connected  <span color="orange">{}</span>  <span color="red">{}</span>"###,
                 connected.len(),
                 connecting.len(),
                 disconnected.len()
@@ -177,3 +224,17 @@ async fn update_relay_status(client: Client, sender: ComponentSender<StatusBar>)
         }));
     }
 }
+
+/// Periodically counts events waiting in outbox.
+async fn update_pending(gnostique: Gnostique, sender: ComponentSender<StatusBar>) {
+    let mut int = interval(Duration::from_secs(5));
+
+    loop {
+        int.tick().await;
+        let pending = gnostique.outbox().pending_count().await;
+        sender.input(StatusBarInput::UpdatePending(pending));
+        sender.input(StatusBarInput::SetDegraded(
+            gnostique.db_health().is_degraded(),
+        ));
+    }
+}
@@ -0,0 +1,83 @@
+//! Look of the application: light or dark variant of GTK theme, and
+//! user's own styles from `style.css`, which are layered on top of ours.
+
+use std::cell::{Cell, RefCell};
+use std::path::Path;
+use std::rc::Rc;
+
+use relm4::gtk;
+use tracing::{info, warn};
+
+use crate::config::Theme;
+
+thread_local! {
+    /// Provider of user's styles currently in use, if any.
+    static USER_STYLE: RefCell<Option<gtk::CssProvider>> = const { RefCell::new(None) };
+}
+
+/// Switches to light or dark variant of GTK theme.
+pub fn apply_theme(theme: Theme) {
+    let settings = match gtk::Settings::default() {
+        Some(settings) => settings,
+        None => return,
+    };
+
+    match theme {
+        Theme::System => settings.reset_property("gtk-application-prefer-dark-theme"),
+        Theme::Light => settings.set_gtk_application_prefer_dark_theme(false),
+        Theme::Dark => settings.set_gtk_application_prefer_dark_theme(true),
+    }
+}
+
+/// Replaces user's styles by those in file at `path`. Nothing is loaded
+/// if the file does not exist. If it cannot be parsed, only built-in styles
+/// are used, as partially applied styles could make the window unusable.
+pub fn load_user_style(path: &Path) {
+    let display = match gtk::gdk::Display::default() {
+        Some(display) => display,
+        None => return,
+    };
+
+    USER_STYLE.with(|current| {
+        if let Some(provider) = current.borrow_mut().take() {
+            gtk::StyleContext::remove_provider_for_display(&display, &provider);
+        }
+
+        let css = match std::fs::read(path) {
+            Ok(css) => css,
+            Err(_) => return,
+        };
+
+        let provider = gtk::CssProvider::new();
+        let valid = Rc::new(Cell::new(true));
+        provider.connect_parsing_error({
+            let valid = valid.clone();
+            let path = path.to_path_buf();
+            move |_, section, error| {
+                warn!(
+                    "Invalid style in {} on line {}: {}",
+                    path.display(),
+                    section.start_location().lines() + 1,
+                    error
+                );
+                valid.set(false);
+            }
+        });
+        provider.load_from_data(&css);
+
+        if valid.get() {
+            gtk::StyleContext::add_provider_for_display(
+                &display,
+                &provider,
+                gtk::STYLE_PROVIDER_PRIORITY_USER,
+            );
+            *current.borrow_mut() = Some(provider);
+            info!("Loaded styles from {}", path.display());
+        } else {
+            warn!(
+                "Ignoring styles in {}, using built-in ones only",
+                path.display()
+            );
+        }
+    });
+}
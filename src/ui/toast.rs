@@ -0,0 +1,226 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use gtk::prelude::*;
+use relm4::*;
+
+use crate::ui::pending::UNDO_WINDOW;
+
+/// Toasts which are not errors disappear after this long by default.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// At most this many toasts wait to be shown, newer ones are dropped.
+const MAX_QUEUED: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToastKind {
+    Info,
+    Success,
+    Error,
+}
+
+impl ToastKind {
+    fn css_class(self) -> &'static str {
+        match self {
+            ToastKind::Info => "info",
+            ToastKind::Success => "success",
+            ToastKind::Error => "error",
+        }
+    }
+}
+
+/// Short message shown at the bottom of the window.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Toast {
+    pub text: String,
+    pub kind: ToastKind,
+    /// How long the toast is shown. If `None`, it stays until dismissed.
+    pub timeout: Option<Duration>,
+    /// Pending action which the toast offers to undo.
+    pub undo: Option<u64>,
+}
+
+impl Toast {
+    pub fn info(text: impl Into<String>) -> Toast {
+        Toast {
+            text: text.into(),
+            kind: ToastKind::Info,
+            timeout: Some(DEFAULT_TIMEOUT),
+            undo: None,
+        }
+    }
+
+    /// Toast offering to undo pending action `id` while it waits.
+    pub fn undoable(text: impl Into<String>, id: u64) -> Toast {
+        Toast {
+            text: text.into(),
+            kind: ToastKind::Info,
+            timeout: Some(UNDO_WINDOW),
+            undo: Some(id),
+        }
+    }
+
+    pub fn success(text: impl Into<String>) -> Toast {
+        Toast {
+            text: text.into(),
+            kind: ToastKind::Success,
+            timeout: Some(DEFAULT_TIMEOUT),
+            undo: None,
+        }
+    }
+
+    /// Error stays until user dismisses it.
+    pub fn error(text: impl Into<String>) -> Toast {
+        Toast {
+            text: text.into(),
+            kind: ToastKind::Error,
+            timeout: None,
+            undo: None,
+        }
+    }
+}
+
+/// Shows toasts one at a time, in order in which they have come.
+#[derive(Debug)]
+pub struct Toasts {
+    /// Displayed toast.
+    current: Option<Toast>,
+    /// Toasts waiting until the current one disappears.
+    queue: VecDeque<Toast>,
+    /// Identifies the displayed toast, so that only that one gets hidden
+    /// once its timeout elapses.
+    generation: u64,
+}
+
+#[derive(Debug)]
+pub enum ToastsInput {
+    Show(Toast),
+    /// User has closed the displayed toast.
+    Dismiss,
+    /// User wants to undo action of the displayed toast.
+    Undo,
+    /// Timeout of toast of the given generation has elapsed.
+    Expired(u64),
+}
+
+#[derive(Debug)]
+pub enum ToastsOutput {
+    /// Pending action of this identifier should be undone.
+    Undo(u64),
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for Toasts {
+    type Init = ();
+    type Input = ToastsInput;
+    type Output = ToastsOutput;
+
+    view! {
+        gtk::Revealer {
+            set_halign: gtk::Align::Center,
+            set_valign: gtk::Align::End,
+            set_transition_type: gtk::RevealerTransitionType::SlideUp,
+            #[watch] set_reveal_child: model.current.is_some(),
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 8,
+                #[watch] set_css_classes: &["toast", model.kind().css_class()],
+
+                gtk::Label {
+                    #[watch] set_label: model.current.as_ref().map(|t| t.text.as_str()).unwrap_or_default(),
+                    set_wrap: true,
+                },
+
+                gtk::Button {
+                    set_label: "Undo",
+                    set_has_frame: false,
+                    #[watch] set_visible: model.current.as_ref().map(|t| t.undo.is_some()).unwrap_or(false),
+                    connect_clicked => ToastsInput::Undo
+                },
+
+                gtk::Button {
+                    set_icon_name: "window-close-symbolic",
+                    set_has_frame: false,
+                    set_tooltip_text: Some("Dismiss"),
+                    #[watch] set_visible: model.current.as_ref().map(|t| t.timeout.is_none()).unwrap_or(false),
+                    connect_clicked => ToastsInput::Dismiss
+                }
+            }
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = Toasts {
+            current: None,
+            queue: VecDeque::new(),
+            generation: 0,
+        };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        match message {
+            ToastsInput::Show(toast) => {
+                // The same message coming again and again is shown once.
+                let repeated = self.current.iter().chain(&self.queue).any(|t| t == &toast);
+
+                if self.current.is_none() {
+                    self.show(toast, &sender);
+                } else if !repeated && self.queue.len() < MAX_QUEUED {
+                    self.queue.push_back(toast);
+                }
+            }
+            ToastsInput::Dismiss => self.next(&sender),
+            ToastsInput::Undo => {
+                if let Some(id) = self.current.as_ref().and_then(|t| t.undo) {
+                    sender.output(ToastsOutput::Undo(id)).unwrap_or_default();
+                }
+                self.next(&sender);
+            }
+            ToastsInput::Expired(generation) => {
+                if generation == self.generation {
+                    self.next(&sender);
+                }
+            }
+        }
+    }
+}
+
+impl Toasts {
+    fn kind(&self) -> ToastKind {
+        self.current
+            .as_ref()
+            .map(|t| t.kind)
+            .unwrap_or(ToastKind::Info)
+    }
+
+    /// Shows the next waiting toast, if there is any.
+    fn next(&mut self, sender: &ComponentSender<Self>) {
+        self.current = None;
+        if let Some(toast) = self.queue.pop_front() {
+            self.show(toast, sender);
+        }
+    }
+
+    fn show(&mut self, toast: Toast, sender: &ComponentSender<Self>) {
+        self.generation += 1;
+
+        if let Some(timeout) = toast.timeout {
+            let generation = self.generation;
+            let sender = sender.clone();
+            relm4::spawn(async move {
+                tokio::time::sleep(timeout).await;
+                sender.input(ToastsInput::Expired(generation));
+            });
+        }
+
+        self.current = Some(toast);
+    }
+}
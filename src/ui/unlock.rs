@@ -1,13 +1,23 @@
+use std::path::PathBuf;
+
 use futures_util::FutureExt;
 use gtk::prelude::*;
+use gtk::{gio, glib};
 use relm4::*;
 use secrecy::{Secret, SecretString};
 
-use crate::app::init::make_gnostique;
+use crate::app::init::{make_gnostique, Setup};
+use crate::backup::Backup;
 use crate::Gnostique;
 
 #[derive(Debug)]
-pub struct Unlock;
+pub struct Unlock {
+    setup: Setup,
+    /// Backup to be restored once unlocked.
+    backup: Option<Backup>,
+    /// Outcome of reading backup.
+    backup_status: Option<String>,
+}
 
 #[derive(Debug)]
 pub enum UnlockResult {
@@ -18,6 +28,10 @@ pub enum UnlockResult {
 #[derive(Debug)]
 pub enum UnlockInput {
     Unlock(SecretString),
+    /// Let user choose backup file to restore.
+    ChooseBackup,
+    /// Restore backup from the file.
+    RestoreFrom(PathBuf),
 }
 
 #[derive(Debug)]
@@ -28,7 +42,7 @@ pub enum UnlockCmd {
 
 #[relm4::component(pub)]
 impl Component for Unlock {
-    type Init = ();
+    type Init = Setup;
     type Input = UnlockInput;
     type Output = UnlockResult;
     type CommandOutput = UnlockCmd;
@@ -87,6 +101,32 @@ impl Component for Unlock {
                             connect_clicked[sender] => move |_| sender.output(UnlockResult::Quit).unwrap_or_default(),
                         }
                     }
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 8,
+
+                    gtk::Button {
+                        set_halign: gtk::Align::Start,
+                        set_label: "Restore from backup…",
+                        add_css_class: "flat",
+                        connect_clicked => UnlockInput::ChooseBackup
+                    },
+
+                    gtk::Label {
+                        set_xalign: 0.0,
+                        set_wrap: true,
+                        #[watch] set_label: model.backup_status.as_deref().unwrap_or_default(),
+                        #[watch] set_visible: model.backup_status.is_some(),
+                    },
+
+                    #[name(republish)]
+                    gtk::CheckButton {
+                        set_label: Some("Publish restored follows, mutes and relays"),
+                        set_active: true,
+                        #[watch] set_visible: model.backup.is_some(),
+                    }
                 }
 
             },
@@ -116,11 +156,15 @@ impl Component for Unlock {
     }
 
     fn init(
-        _init: Self::Init,
+        setup: Self::Init,
         root: &Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        let model = Unlock;
+        let model = Unlock {
+            setup,
+            backup: None,
+            backup_status: None,
+        };
         let widgets = view_output!();
 
         ComponentParts { model, widgets }
@@ -155,11 +199,63 @@ impl Component for Unlock {
                 widgets.password.set_text("");
                 widgets.spinner.start();
                 stack.set_visible_child(&widgets.spinner_page);
-                sender.oneshot_command(make_gnostique(password).map(|result| match result {
+                let backup = self.backup.take();
+                let republish = widgets.republish.is_active();
+                let setup = self.setup.clone();
+                let unlocked = async move {
+                    let gnostique = make_gnostique(password, setup).await?;
+                    if let Some(backup) = backup {
+                        crate::backup::restore(&gnostique, backup, republish).await?;
+                    }
+                    Ok(gnostique)
+                };
+                sender.oneshot_command(unlocked.map(|result| match result {
                     Ok(gn) => UnlockCmd::Unlocked(gn),
                     Err(e) => UnlockCmd::Error(e),
                 }));
             }
+
+            UnlockInput::ChooseBackup => {
+                let window = stack.root().and_then(|r| r.downcast::<gtk::Window>().ok());
+                let dialog = gtk::FileChooserNative::new(
+                    Some("Restore from backup"),
+                    window.as_ref(),
+                    gtk::FileChooserAction::Open,
+                    Some("Restore"),
+                    Some("Cancel"),
+                );
+                dialog.set_modal(true);
+
+                dialog.connect_response(glib::clone!(@strong sender => move |d, response| {
+                    if response == gtk::ResponseType::Accept {
+                        if let Some(path) = d.file().and_then(|f: gio::File| f.path()) {
+                            sender.input(UnlockInput::RestoreFrom(path));
+                        }
+                    }
+                    d.destroy();
+                }));
+
+                dialog.show();
+            }
+
+            UnlockInput::RestoreFrom(path) => {
+                let key_file = self.setup.dirs.key_file();
+                let restored = Backup::read(&path)
+                    .and_then(|b| crate::backup::restore_key(&b, key_file).map(|_| b));
+
+                match restored {
+                    Ok(backup) => {
+                        self.backup_status = Some(
+                            "Backup will be restored once unlocked by its password.".to_string(),
+                        );
+                        self.backup = Some(backup);
+                    }
+                    Err(e) => {
+                        self.backup_status = Some(format!("Could not restore backup: {e}"));
+                        self.backup = None;
+                    }
+                }
+            }
         }
 
         self.update_view(widgets, sender);
@@ -0,0 +1,290 @@
+use std::cell::Cell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use gtk::prelude::*;
+use gtk::{gdk, gio, glib};
+use relm4::prelude::*;
+use relm4::{gtk, ComponentParts};
+use reqwest::Url;
+use tracing::{info, warn};
+
+/// Images larger than this (in either dimension) are downscaled for display.
+pub const MAX_DISPLAY_SIZE: i32 = 4096;
+
+/// A window displaying one image in full size. One instance of it is
+/// created and reused, like [`DetailsWindow`](crate::ui::details::DetailsWindow).
+pub struct ImageViewer {
+    /// Whether the window is visible or hidden.
+    visible: bool,
+
+    /// Currently displayed image.
+    image: Option<ViewedImage>,
+
+    /// Current zoom factor, 1.0 is the original size.
+    zoom: f64,
+}
+
+/// Image displayed in [`ImageViewer`].
+#[derive(Debug)]
+pub struct ViewedImage {
+    /// Original URL of the image.
+    pub url: Url,
+
+    /// Cached file with the image.
+    pub file: PathBuf,
+
+    /// Decoded, possibly downscaled, image.
+    pub bitmap: Arc<gdk::Texture>,
+}
+
+/// Messages coming to [`ImageViewer`].
+#[derive(Debug)]
+pub enum ImageViewerInput {
+    /// Display the image and show the window, if hidden.
+    Show(ViewedImage),
+
+    /// Hide the window.
+    Hide,
+
+    /// Multiply current zoom by the factor.
+    Zoom(f64),
+
+    /// Display the image in its original size.
+    ResetZoom,
+
+    /// Copy original URL of the image into clipboard.
+    CopyUrl,
+
+    /// Ask user where to save the image.
+    SaveAs,
+
+    /// Save the image into this file.
+    Save(PathBuf),
+}
+
+#[relm4::component(pub)]
+impl Component for ImageViewer {
+    type Init = ();
+    type Input = ImageViewerInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        #[name(window)]
+        gtk::Window {
+            set_default_size: (900, 700),
+            #[watch]
+            set_visible: model.visible,
+            #[watch]
+            set_title: model.image.as_ref().map(|i| i.url.as_str()),
+
+            connect_close_request[sender] => move |_| {
+                sender.input(ImageViewerInput::Hide);
+                gtk::Inhibit(false)
+            },
+
+            add_controller = &gtk::EventControllerKey {
+                connect_key_pressed[sender] => move |_, key, _, _| {
+                    if key == gdk::Key::Escape {
+                        sender.input(ImageViewerInput::Hide);
+                        gtk::Inhibit(true)
+                    } else {
+                        gtk::Inhibit(false)
+                    }
+                }
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                add_css_class: "viewer",
+
+                #[name(scrolled)]
+                gtk::ScrolledWindow {
+                    set_hexpand: true,
+                    set_vexpand: true,
+
+                    #[wrap(Some)]
+                    set_child = &gtk::Picture {
+                        #[watch]
+                        set_paintable: model.image.as_ref().map(|i| i.bitmap.as_ref()),
+                        #[watch]
+                        set_width_request: model.display_size().0,
+                        #[watch]
+                        set_height_request: model.display_size().1,
+                        set_can_shrink: true,
+                        set_halign: gtk::Align::Center,
+                        set_valign: gtk::Align::Center,
+                    },
+
+                    add_controller = &gtk::EventControllerScroll {
+                        set_flags: gtk::EventControllerScrollFlags::VERTICAL,
+                        connect_scroll[sender] => move |_, _, dy| {
+                            sender.input(ImageViewerInput::Zoom(if dy < 0.0 { 1.1 } else { 1.0 / 1.1 }));
+                            gtk::Inhibit(true)
+                        }
+                    },
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 8,
+                    add_css_class: "buttons",
+
+                    gtk::Button::from_icon_name("zoom-out-symbolic") {
+                        set_tooltip_text: Some("Zoom out"),
+                        connect_clicked => ImageViewerInput::Zoom(1.0 / 1.25)
+                    },
+
+                    gtk::Button::from_icon_name("zoom-original-symbolic") {
+                        set_tooltip_text: Some("Original size"),
+                        connect_clicked => ImageViewerInput::ResetZoom
+                    },
+
+                    gtk::Button::from_icon_name("zoom-in-symbolic") {
+                        set_tooltip_text: Some("Zoom in"),
+                        connect_clicked => ImageViewerInput::Zoom(1.25)
+                    },
+
+                    gtk::Box { set_hexpand: true },
+
+                    gtk::Button::with_label("Copy URL") {
+                        connect_clicked => ImageViewerInput::CopyUrl
+                    },
+
+                    gtk::Button::with_label("Save as…") {
+                        add_css_class: "suggested-action",
+                        connect_clicked => ImageViewerInput::SaveAs
+                    }
+                }
+            }
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: &Self::Root,
+        sender: relm4::ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = ImageViewer {
+            visible: false,
+            image: None,
+            zoom: 1.0,
+        };
+        let widgets = view_output!();
+
+        // Drag to pan, remembering where the dragging started.
+        let start = Rc::new(Cell::new((0.0, 0.0)));
+        let drag = gtk::GestureDrag::new();
+        drag.connect_drag_begin(
+            glib::clone!(@strong start, @weak widgets.scrolled as scrolled => move |_, _, _| {
+                start.set((scrolled.hadjustment().value(), scrolled.vadjustment().value()));
+            }),
+        );
+        drag.connect_drag_update(
+            glib::clone!(@strong start, @weak widgets.scrolled as scrolled => move |_, dx, dy| {
+                let (x, y) = start.get();
+                scrolled.hadjustment().set_value(x - dx);
+                scrolled.vadjustment().set_value(y - dy);
+            }),
+        );
+        widgets.scrolled.add_controller(&drag);
+
+        ComponentParts { widgets, model }
+    }
+
+    fn update(
+        &mut self,
+        message: Self::Input,
+        sender: relm4::ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        match message {
+            ImageViewerInput::Show(image) => {
+                self.image = Some(image);
+                self.zoom = 1.0;
+                self.visible = true;
+            }
+            ImageViewerInput::Hide => self.visible = false,
+            ImageViewerInput::Zoom(factor) => self.zoom = (self.zoom * factor).clamp(0.05, 20.0),
+            ImageViewerInput::ResetZoom => self.zoom = 1.0,
+            ImageViewerInput::CopyUrl => {
+                if let Some(image) = &self.image {
+                    root.clipboard().set_text(image.url.as_str());
+                }
+            }
+            ImageViewerInput::SaveAs => {
+                if let Some(image) = &self.image {
+                    let dialog = gtk::FileChooserNative::new(
+                        Some("Save image"),
+                        Some(root),
+                        gtk::FileChooserAction::Save,
+                        Some("Save"),
+                        Some("Cancel"),
+                    );
+                    dialog.set_modal(true);
+
+                    let name = image
+                        .url
+                        .path_segments()
+                        .and_then(|mut s| s.next_back())
+                        .filter(|s| !s.is_empty())
+                        .unwrap_or("image");
+                    dialog.set_current_name(name);
+
+                    dialog.connect_response(glib::clone!(@strong sender => move |d, response| {
+                        if response == gtk::ResponseType::Accept {
+                            if let Some(path) = d.file().and_then(|f: gio::File| f.path()) {
+                                sender.input(ImageViewerInput::Save(path));
+                            }
+                        }
+                        d.destroy();
+                    }));
+
+                    dialog.show();
+                }
+            }
+            ImageViewerInput::Save(path) => {
+                if let Some(image) = &self.image {
+                    match std::fs::copy(&image.file, &path) {
+                        Ok(_) => info!("Image {} saved as {:?}", image.url, path),
+                        Err(e) => warn!("Could not save image as {:?}: {}", path, e),
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl ImageViewer {
+    /// Size of displayed image for the current zoom.
+    fn display_size(&self) -> (i32, i32) {
+        match &self.image {
+            Some(image) => (
+                (image.bitmap.width() as f64 * self.zoom) as i32,
+                (image.bitmap.height() as f64 * self.zoom) as i32,
+            ),
+            None => (-1, -1),
+        }
+    }
+}
+
+/// Decodes image from `file` for display, downscaling it if it is larger
+/// than [`MAX_DISPLAY_SIZE`]. Blocks.
+pub fn decode_for_display(file: &std::path::Path) -> Result<gdk::Texture, String> {
+    use gtk::gdk_pixbuf::Pixbuf;
+
+    let too_large = matches!(
+        Pixbuf::file_info(file),
+        Some((_, w, h)) if w > MAX_DISPLAY_SIZE || h > MAX_DISPLAY_SIZE
+    );
+
+    if too_large {
+        Pixbuf::from_file_at_scale(file, MAX_DISPLAY_SIZE, MAX_DISPLAY_SIZE, true)
+            .map(|p| gdk::Texture::for_pixbuf(&p))
+            .map_err(|e| e.to_string())
+    } else {
+        gdk::Texture::from_filename(file).map_err(|e| e.to_string())
+    }
+}
@@ -1,11 +1,34 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
 use gtk::prelude::*;
+use gtk::{gdk, gio, glib};
+use nostr_sdk::prelude::{Kind, Timestamp, ToBech32, Url};
+use relm4::actions::{RelmAction, RelmActionGroup};
 use relm4::*;
+use tracing::warn;
 
 use super::model::*;
+use crate::drafts::Draft;
+use crate::nostr::{mine_event, EventExt, Persona};
+use crate::spelling::Speller;
+use crate::ui::toast::Toast;
+use crate::Gnostique;
+
+/// At most this many users are offered to be mentioned.
+const MAX_COMPLETIONS: u32 = 8;
+
+/// How often the note is saved into its draft while it is being written.
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+relm4::new_action_group!(SpellingActionGroup, "spelling");
+relm4::new_stateful_action!(CorrectWord, SpellingActionGroup, "correct", String, ());
 
 #[relm4::component(pub)]
 impl SimpleComponent for WriteNote {
-    type Init = ();
+    type Init = Gnostique;
     type Input = WriteNoteInput;
     type Output = WriteNoteResult;
 
@@ -17,27 +40,69 @@ impl SimpleComponent for WriteNote {
             set_default_size: (400, 400),
             #[watch] set_visible: model.visible,
 
+            connect_close_request[sender] => move |_| {
+                sender.input(WriteNoteInput::Cancel);
+                gtk::Inhibit(true)
+            },
+
             gtk::Box {
                 set_orientation: gtk::Orientation::Vertical,
                 add_css_class: "form",
 
+                // earlier draft of the same note
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 8,
+                    add_css_class: "unfinished-draft",
+                    #[watch] set_visible: model.unfinished.is_some(),
+
+                    gtk::Label {
+                        set_hexpand: true,
+                        set_xalign: 0.0,
+                        set_ellipsize: gtk::pango::EllipsizeMode::End,
+                        #[watch] set_label: &model.format_unfinished(),
+                    },
+
+                    gtk::Button::with_label("Continue") {
+                        connect_clicked => WriteNoteInput::RestoreUnfinished
+                    },
+
+                    gtk::Button::with_label("Dismiss") {
+                        set_has_frame: false,
+                        connect_clicked => WriteNoteInput::DismissUnfinished
+                    },
+                },
+
                 gtk::Grid {
                     set_column_spacing: 16,
                     set_row_spacing: 16,
 
                     attach[0, 0, 1, 1] = &gtk::Label {
+                        set_text: "Subject",
+                        set_xalign: 1.0,
+                        add_css_class: "label",
+                    },
+
+                    attach[1, 0, 1, 1] = &gtk::Entry {
+                        set_buffer: &model.subject,
+                        set_hexpand: true,
+                        set_placeholder_text: Some("Optional"),
+                    },
+
+                    attach[0, 1, 1, 1] = &gtk::Label {
                         set_text: "Content",
                         set_xalign: 1.0,
                         set_valign: gtk::Align::Start,
                         add_css_class: "label",
                     },
 
-                    attach[1, 0, 1, 1] = &gtk::ScrolledWindow {
+                    attach[1, 1, 1, 1] = &gtk::ScrolledWindow {
                         set_hexpand: true,
                         set_hscrollbar_policy: gtk::PolicyType::Never,
                         set_min_content_height: 180,
 
-                        gtk::TextView {
+                        #[wrap(Some)]
+                        set_child = &model.text_view.clone() {
                             set_buffer: Some(&model.buffer),
                             set_top_margin: 4,
                             set_left_margin: 4,
@@ -47,6 +112,25 @@ impl SimpleComponent for WriteNote {
                             add_css_class: "multiline",
                         }
                     },
+
+                    attach[0, 2, 1, 1] = &gtk::Label {
+                        set_text: "Proof of work",
+                        set_xalign: 1.0,
+                        add_css_class: "label",
+                    },
+
+                    attach[1, 2, 1, 1] = &gtk::SpinButton {
+                        set_adjustment: &model.difficulty,
+                        set_halign: gtk::Align::Start,
+                        set_tooltip_text: Some("Number of leading zero bits of note ID (NIP-13), zero for none"),
+                    },
+                },
+
+                #[local_ref]
+                attachments_box -> gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 4,
+                    add_css_class: "attachments",
                 },
 
                 gtk::Box {
@@ -54,14 +138,61 @@ impl SimpleComponent for WriteNote {
                     set_hexpand: true,
                     set_spacing: 8,
 
+                    gtk::Button {
+                        set_icon_name: "mail-attachment-symbolic",
+                        #[watch] set_sensitive: model.gnostique.uploads().is_enabled(),
+                        #[watch] set_tooltip_text: Some(if model.gnostique.uploads().is_enabled() {
+                            "Attach files"
+                        } else {
+                            "Set upload server in settings to attach files"
+                        }),
+                        connect_clicked => WriteNoteInput::Attach
+                    },
+
+                    #[local_ref]
+                    dictionary -> gtk::DropDown {
+                        set_tooltip_text: Some("Spell checking"),
+                        set_visible: !model.dictionaries.is_empty(),
+                    },
+
+                    gtk::MenuButton {
+                        set_label: "Drafts",
+                        set_tooltip_text: Some("Continue or delete other drafts"),
+                        #[wrap(Some)]
+                        set_popover = &gtk::Popover {
+                            connect_show => WriteNoteInput::ListDrafts,
+
+                            gtk::ScrolledWindow {
+                                set_hscrollbar_policy: gtk::PolicyType::Never,
+                                set_propagate_natural_height: true,
+                                set_max_content_height: 300,
+                                set_min_content_width: 320,
+                                set_child: Some(&model.drafts_list),
+                            }
+                        },
+                    },
+
+                    gtk::Spinner {
+                        #[watch] set_spinning: model.mining.is_some(),
+                        #[watch] set_visible: model.mining.is_some(),
+                    },
+
+                    gtk::Label {
+                        #[watch] set_label: &format!("Mining… {} hashes tried", model.hashes),
+                        #[watch] set_visible: model.mining.is_some(),
+                    },
+
                     gtk::Box { set_hexpand: true },
 
                     gtk::Button::with_label("Cancel") {
+                        #[watch] set_tooltip_text: model.mining.as_ref().map(|_| "Stop mining"),
                         connect_clicked => WriteNoteInput::Cancel
                     },
 
                     gtk::Button::with_label("Send") {
                         add_css_class: "suggested-action",
+                        #[watch] set_sensitive: model.mining.is_none() && !model.is_uploading(),
+                        #[watch] set_tooltip_text: model.is_uploading().then_some("Attached files are being uploaded"),
                         connect_clicked => WriteNoteInput::Send
                     }
                 }
@@ -70,40 +201,993 @@ impl SimpleComponent for WriteNote {
     }
 
     fn init(
-        _init: Self::Init,
+        gnostique: Self::Init,
         root: &Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        let model = WriteNote {
+        let completion_list = gtk::ListBox::new();
+        completion_list.connect_row_activated({
+            let sender = sender.clone();
+            move |_, row| sender.input(WriteNoteInput::AcceptCompletion(Some(row.index() as usize)))
+        });
+
+        let completion = gtk::Popover::new();
+        completion.set_autohide(false);
+        completion.set_can_focus(false);
+        completion.set_has_arrow(false);
+        completion.set_position(gtk::PositionType::Bottom);
+        completion.set_child(Some(&completion_list));
+
+        let dictionaries = crate::spelling::available();
+        let mut shown = vec!["No spell checking"];
+        shown.extend(dictionaries.iter().map(String::as_str));
+        let dictionary = gtk::DropDown::from_strings(&shown);
+
+        let buffer = gtk::TextBuffer::new(None);
+        let misspelled = gtk::TextTag::new(Some("misspelled"));
+        misspelled.set_underline(gtk::pango::Underline::Error);
+        buffer.tag_table().add(&misspelled);
+
+        let mut model = WriteNote {
+            keys: gnostique.client().keys(),
+            gnostique,
             visible: false,
-            buffer: gtk::TextBuffer::new(None),
+            buffer,
+            subject: gtk::EntryBuffer::default(),
+            reply_to: None,
+            quoting: None,
+            difficulty: gtk::Adjustment::new(0.0, 0.0, 32.0, 1.0, 4.0, 0.0),
+            mining: None,
+            hashes: 0,
+            text_view: gtk::TextView::new(),
+            completion,
+            completion_list,
+            completion_query: None,
+            completions: Vec::new(),
+            dictionaries,
+            dictionary,
+            speller: None,
+            misspelled,
+            corrections: gtk::gio::Menu::new(),
+            corrected: None,
+            draft_id: Draft::new_id(),
+            saved: Default::default(),
+            unfinished: None,
+            drafts: Vec::new(),
+            drafts_list: gtk::ListBox::new(),
+            attachments: Vec::new(),
+            attachments_box: gtk::Box::default(),
+            next_attachment: 0,
+            uploaded: Vec::new(),
+            send_after_upload: false,
         };
+        model
+            .drafts_list
+            .set_selection_mode(gtk::SelectionMode::None);
+        model.drafts_list.add_css_class("drafts");
+        let dictionary = &model.dictionary;
+        let attachments_box = &model.attachments_box;
         let widgets = view_output!();
 
+        model.init_spelling(&sender);
+        model.init_attachments(&sender);
+
+        relm4::spawn({
+            let sender = sender.clone();
+            async move {
+                let mut int = tokio::time::interval(AUTOSAVE_INTERVAL);
+                loop {
+                    int.tick().await;
+                    sender.input(WriteNoteInput::Autosave);
+                }
+            }
+        });
+
+        model.completion.set_parent(&model.text_view);
+
+        model.buffer.connect_changed({
+            let sender = sender.clone();
+            move |_| sender.input(WriteNoteInput::ContentChanged)
+        });
+        model.buffer.connect_mark_set({
+            let sender = sender.clone();
+            move |buffer, _, mark| {
+                if mark == &buffer.get_insert() {
+                    sender.input(WriteNoteInput::ContentChanged)
+                }
+            }
+        });
+
+        // While users are offered, keys navigate among them instead of moving cursor.
+        let keys = gtk::EventControllerKey::new();
+        keys.set_propagation_phase(gtk::PropagationPhase::Capture);
+        keys.connect_key_pressed({
+            let completion = model.completion.clone();
+            move |_, key, _, _| {
+                if !completion.is_visible() {
+                    return gtk::Inhibit(false);
+                }
+
+                let input = match key {
+                    gdk::Key::Down => WriteNoteInput::MoveCompletion(1),
+                    gdk::Key::Up => WriteNoteInput::MoveCompletion(-1),
+                    gdk::Key::Return | gdk::Key::KP_Enter | gdk::Key::Tab => {
+                        WriteNoteInput::AcceptCompletion(None)
+                    }
+                    gdk::Key::Escape => WriteNoteInput::DismissCompletion,
+                    _ => return gtk::Inhibit(false),
+                };
+                sender.input(input);
+                gtk::Inhibit(true)
+            }
+        });
+        model.text_view.add_controller(&keys);
+
         ComponentParts { model, widgets }
     }
 
     fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
         match message {
             WriteNoteInput::Hide => {
+                if let Some(cancel) = self.mining.take() {
+                    cancel.store(true, Ordering::Relaxed);
+                }
                 self.visible = false;
+                self.completion.popdown();
                 self.buffer.set_text("");
+                self.subject.set_text("");
+                self.reply_to = None;
+                self.quoting = None;
+                self.draft_id = Draft::new_id();
+                self.saved = Default::default();
+                self.unfinished = None;
+                for attachment in self.attachments.drain(..) {
+                    if let UploadState::Uploading(task) = attachment.state {
+                        task.abort();
+                    }
+                    self.attachments_box.remove(&attachment.row);
+                }
+                self.uploaded.clear();
+                self.send_after_upload = false;
+            }
+            WriteNoteInput::Show => {
+                if !self.visible {
+                    self.visible = true;
+                    self.find_unfinished(&sender);
+                }
+            }
+            WriteNoteInput::Reply(event) => {
+                if let Some(subject) = event.subject() {
+                    self.subject.set_text(&WriteNote::reply_subject(&subject));
+                }
+                self.reply_to = Some(event);
+                self.saved = (self.subject.text().to_string(), self.content());
+                self.visible = true;
+                self.find_unfinished(&sender);
+            }
+            WriteNoteInput::Quote(event, relays) => {
+                // Reference to the quoted note is appended, user writes above it.
+                let cursor = self.buffer.end_iter().offset();
+                self.buffer.insert(
+                    &mut self.buffer.end_iter(),
+                    &format!("\n\nnostr:{}", event.nevent(&relays)),
+                );
+                self.buffer
+                    .place_cursor(&self.buffer.iter_at_offset(cursor));
+                self.quoting = Some((event, relays));
+                self.saved = (self.subject.text().to_string(), self.content());
+                self.visible = true;
+                self.find_unfinished(&sender);
             }
-            WriteNoteInput::Show => self.visible = true,
             WriteNoteInput::Cancel => {
-                sender.output(WriteNoteResult::Cancel).unwrap_or_default();
-                sender.input(WriteNoteInput::Hide)
+                // While mining, cancelling stops it and keeps the note.
+                if let Some(cancel) = self.mining.take() {
+                    cancel.store(true, Ordering::Relaxed);
+                } else {
+                    self.save_draft();
+                    sender.output(WriteNoteResult::Cancel).unwrap_or_default();
+                    sender.input(WriteNoteInput::Hide)
+                }
             }
             WriteNoteInput::Send => {
-                let content = self
-                    .buffer
-                    .text(&self.buffer.start_iter(), &self.buffer.end_iter(), true)
-                    .to_string();
+                // Files waiting for the note to be sent are uploaded first.
+                let waiting: Vec<u64> = self
+                    .attachments
+                    .iter()
+                    .filter(|a| matches!(a.state, UploadState::Waiting))
+                    .map(|a| a.id)
+                    .collect();
+                if !waiting.is_empty() {
+                    for id in waiting {
+                        self.start_upload(id, &sender);
+                    }
+                    self.send_after_upload = true;
+                    return;
+                }
+
+                let content = self.content();
+                let tags = self.tags(&content);
+                let difficulty = self.difficulty.value() as u8;
+
+                if difficulty == 0 {
+                    self.discard_draft();
+                    sender
+                        .output(WriteNoteResult::Send { content, tags })
+                        .unwrap_or_default();
+                    sender.input(WriteNoteInput::Hide);
+                    return;
+                }
+
+                let cancel = Arc::new(AtomicBool::new(false));
+                self.mining = Some(cancel.clone());
+                self.hashes = 0;
+
+                let keys = self.keys.clone();
+                relm4::spawn_blocking(move || {
+                    let mined = mine_event(
+                        &keys,
+                        Kind::TextNote,
+                        &content,
+                        &tags,
+                        difficulty,
+                        &cancel,
+                        |hashes| sender.input(WriteNoteInput::Mining(hashes)),
+                    );
+                    sender.input(WriteNoteInput::Mined(mined.map(|e| e.map(Box::new))));
+                });
+            }
+            WriteNoteInput::Mining(hashes) => {
+                if self.mining.is_some() {
+                    self.hashes = hashes;
+                }
+            }
+            WriteNoteInput::Mined(Ok(Some(event))) => {
+                self.mining = None;
+                self.discard_draft();
                 sender
-                    .output(WriteNoteResult::Send(content))
+                    .output(WriteNoteResult::Publish(event))
                     .unwrap_or_default();
                 sender.input(WriteNoteInput::Hide)
             }
+            WriteNoteInput::Mined(Ok(None)) => self.mining = None,
+            WriteNoteInput::Mined(Err(e)) => {
+                warn!("Could not mine text note: {}", e);
+                self.mining = None;
+                sender
+                    .output(WriteNoteResult::Toast(Toast::error(format!(
+                        "Could not mine the note: {e}"
+                    ))))
+                    .unwrap_or_default();
+            }
+            WriteNoteInput::ContentChanged => {
+                self.check_spelling();
+                let query = self.completion_query();
+                if query == self.completion_query {
+                    return;
+                }
+                self.completion_query = query.clone();
+
+                match query {
+                    Some(query) => {
+                        let gnostique = self.gnostique.clone();
+                        relm4::spawn(async move {
+                            let personas = gnostique.search_personas(&query, MAX_COMPLETIONS).await;
+                            sender.input(WriteNoteInput::Completions { query, personas });
+                        });
+                    }
+                    None => self.completion.popdown(),
+                }
+            }
+            WriteNoteInput::Completions { query, personas } => {
+                // User may have typed further in the meantime.
+                if self.completion_query.as_ref() == Some(&query) {
+                    self.show_completions(personas);
+                }
+            }
+            WriteNoteInput::MoveCompletion(step) => {
+                let count = self.completions.len() as i32;
+                if count > 0 {
+                    let current = self
+                        .completion_list
+                        .selected_row()
+                        .map(|r| r.index())
+                        .unwrap_or(-1);
+                    let next = (current + step).rem_euclid(count);
+                    self.completion_list
+                        .select_row(self.completion_list.row_at_index(next).as_ref());
+                }
+            }
+            WriteNoteInput::AcceptCompletion(index) => {
+                let index = index.or_else(|| {
+                    self.completion_list
+                        .selected_row()
+                        .map(|r| r.index() as usize)
+                });
+                if let Some(persona) = index.and_then(|i| self.completions.get(i)).cloned() {
+                    self.insert_mention(&persona);
+                }
+                self.completion.popdown();
+            }
+            WriteNoteInput::DismissCompletion => self.completion.popdown(),
+            WriteNoteInput::DictionaryChosen => {
+                let chosen = self.chosen_dictionary();
+                let pubkey = self.keys.public_key().to_string();
+                let stored = self.gnostique.update_config(|c| {
+                    c.spelling
+                        .dictionaries
+                        .insert(pubkey, chosen.clone().unwrap_or_default());
+                });
+                if let Err(e) = stored {
+                    warn!("Could not remember dictionary: {}", e);
+                }
+                self.load_speller(chosen, &sender);
+            }
+            WriteNoteInput::SpellerLoaded(name, speller) => {
+                // Another dictionary may have been chosen meanwhile.
+                if self.chosen_dictionary().as_ref() == Some(&name) {
+                    self.speller = speller;
+                    self.check_spelling();
+                }
+            }
+            WriteNoteInput::OfferCorrections(x, y) => self.offer_corrections(x, y),
+            WriteNoteInput::Correct(correction) => {
+                if let Some((start, end)) = self.corrected.take() {
+                    let mut start = self.buffer.iter_at_offset(start);
+                    let mut end = self.buffer.iter_at_offset(end);
+                    self.buffer.delete(&mut start, &mut end);
+                    self.buffer.insert(&mut start, &correction);
+                }
+                self.corrections.remove_all();
+            }
+            WriteNoteInput::Autosave => {
+                if self.visible && self.mining.is_none() {
+                    self.save_draft();
+                }
+            }
+            WriteNoteInput::Unfinished(draft) => {
+                // User may have closed the composer or started another note meanwhile.
+                let reply_to = draft.reply_to.as_ref().map(|e| e.id);
+                let quote = draft.quoting.as_ref().map(|(e, _)| e.id);
+                if self.visible
+                    && draft.id != self.draft_id
+                    && reply_to == self.reply_to.as_ref().map(|e| e.id)
+                    && quote == self.quoting.as_ref().map(|(e, _)| e.id)
+                {
+                    self.unfinished = Some(draft);
+                }
+            }
+            WriteNoteInput::RestoreUnfinished => {
+                if let Some(draft) = self.unfinished.take() {
+                    self.restore_draft(draft);
+                }
+            }
+            WriteNoteInput::DismissUnfinished => self.unfinished = None,
+            WriteNoteInput::ListDrafts => {
+                let gnostique = self.gnostique.clone();
+                relm4::spawn(async move {
+                    match gnostique.drafts().list().await {
+                        Ok(drafts) => sender.input(WriteNoteInput::Drafts(drafts)),
+                        Err(e) => warn!("Could not load drafts: {}", e),
+                    }
+                });
+            }
+            WriteNoteInput::Drafts(drafts) => {
+                self.drafts = drafts
+                    .into_iter()
+                    .filter(|d| d.id != self.draft_id)
+                    .collect();
+                self.show_drafts(&sender);
+            }
+            WriteNoteInput::OpenDraft(id) => {
+                if let Some(draft) = self.drafts.iter().find(|d| d.id == id).cloned() {
+                    if let Some(popover) = self
+                        .drafts_list
+                        .ancestor(gtk::Popover::static_type())
+                        .and_then(|p| p.downcast::<gtk::Popover>().ok())
+                    {
+                        popover.popdown();
+                    }
+                    self.restore_draft(draft);
+                }
+            }
+            WriteNoteInput::Attach => self.choose_attachments(&sender),
+            WriteNoteInput::AttachFiles { paths, images_only } => {
+                let (accepted, rejected) = attachable(paths, images_only);
+                if !rejected.is_empty() {
+                    sender
+                        .output(WriteNoteResult::Toast(Toast::error(format!(
+                            "Only images can be attached this way, not {}",
+                            rejected.join(", ")
+                        ))))
+                        .unwrap_or_default();
+                }
+                for path in accepted {
+                    self.attach(path, &sender);
+                }
+            }
+            WriteNoteInput::UploadProgress { id, sent, total } => {
+                if let Some(attachment) = self.attachments.iter().find(|a| a.id == id) {
+                    attachment
+                        .progress
+                        .set_fraction(sent as f64 / total.max(1) as f64);
+                }
+            }
+            WriteNoteInput::Uploaded { id, uploaded } => {
+                let index = match self.attachments.iter().position(|a| a.id == id) {
+                    Some(index) => index,
+                    None => return,
+                };
+                match uploaded {
+                    Ok(uploaded) => {
+                        let attachment = self.attachments.remove(index);
+                        self.attachments_box.remove(&attachment.row);
+                        self.insert_link(&uploaded.url);
+                        self.uploaded.push(uploaded);
+                    }
+                    Err(e) => {
+                        let attachment = &mut self.attachments[index];
+                        attachment.state = UploadState::Failed;
+                        attachment.progress.set_visible(false);
+                        attachment.error.set_label(&e);
+                        attachment.error.set_visible(true);
+                        attachment.button.set_label("Remove");
+                    }
+                }
+
+                if self.send_after_upload {
+                    match uploads_outcome(self.attachments.iter().map(|a| &a.state)) {
+                        UploadsOutcome::Pending => {}
+                        UploadsOutcome::Failed => {
+                            self.send_after_upload = false;
+                            sender
+                                .output(WriteNoteResult::Toast(Toast::error(
+                                    "The note has not been sent, some files could not be uploaded",
+                                )))
+                                .unwrap_or_default();
+                        }
+                        UploadsOutcome::Done => {
+                            self.send_after_upload = false;
+                            sender.input(WriteNoteInput::Send);
+                        }
+                    }
+                }
+            }
+            WriteNoteInput::RemoveAttachment(id) => {
+                if let Some(index) = self.attachments.iter().position(|a| a.id == id) {
+                    let attachment = self.attachments.remove(index);
+                    if let UploadState::Uploading(task) = attachment.state {
+                        task.abort();
+                    }
+                    self.attachments_box.remove(&attachment.row);
+                    // Note changed while waiting for uploads, user sends it again.
+                    self.send_after_upload = false;
+                }
+            }
+            WriteNoteInput::DeleteDraft(id) => {
+                self.drafts.retain(|d| d.id != id);
+                if self.unfinished.as_ref().map(|d| d.id) == Some(id) {
+                    self.unfinished = None;
+                }
+                self.show_drafts(&sender);
+
+                let gnostique = self.gnostique.clone();
+                relm4::spawn(async move {
+                    if let Err(e) = gnostique.drafts().delete(id).await {
+                        warn!("Could not delete draft: {}", e);
+                    }
+                });
+            }
+        }
+    }
+}
+
+impl WriteNote {
+    /// Lets user choose files to be attached.
+    fn choose_attachments(&self, sender: &ComponentSender<WriteNote>) {
+        let window = self
+            .text_view
+            .root()
+            .and_then(|r| r.downcast::<gtk::Window>().ok());
+        let dialog = gtk::FileChooserNative::new(
+            Some("Attach files"),
+            window.as_ref(),
+            gtk::FileChooserAction::Open,
+            Some("Attach"),
+            Some("Cancel"),
+        );
+        dialog.set_modal(true);
+        dialog.set_select_multiple(true);
+
+        dialog.connect_response({
+            let sender = sender.clone();
+            move |d, response| {
+                if response == gtk::ResponseType::Accept {
+                    let files = d.files();
+                    let paths: Vec<PathBuf> = (0..files.n_items())
+                        .filter_map(|i| files.item(i))
+                        .filter_map(|f| f.downcast::<gio::File>().ok())
+                        .filter_map(|f| f.path())
+                        .collect();
+                    if !paths.is_empty() {
+                        sender.input(WriteNoteInput::AttachFiles {
+                            paths,
+                            images_only: false,
+                        });
+                    }
+                }
+                d.destroy();
+            }
+        });
+
+        dialog.show();
+    }
+
+    /// Attaches images pasted or dropped onto the text.
+    fn init_attachments(&self, sender: &ComponentSender<WriteNote>) {
+        let drop = gtk::DropTarget::new(gdk::FileList::static_type(), gdk::DragAction::COPY);
+        // Text view would take the files for text otherwise.
+        drop.set_propagation_phase(gtk::PropagationPhase::Capture);
+        drop.connect_drop({
+            let sender = sender.clone();
+            move |_, value, _, _| match value.get::<gdk::FileList>() {
+                Ok(files) => {
+                    let paths = files.files().iter().filter_map(|f| f.path()).collect();
+                    sender.input(WriteNoteInput::AttachFiles {
+                        paths,
+                        images_only: true,
+                    });
+                    true
+                }
+                Err(_) => false,
+            }
+        });
+        self.text_view.add_controller(&drop);
+
+        let pasted_dir = self.gnostique.dirs().cache_dir().join("pasted");
+        self.text_view.connect_paste_clipboard({
+            let sender = sender.clone();
+            move |text_view| {
+                let clipboard = text_view.clipboard();
+                let formats = clipboard.formats();
+                let files = formats.contains_type(gdk::FileList::static_type())
+                    || formats.contain_mime_type("text/uri-list");
+                let image = formats.contains_type(gdk::Texture::static_type())
+                    || formats.mime_types().iter().any(|m| m.starts_with("image/"));
+                if !files && !image {
+                    return;
+                }
+                // Pasted files and images are attached instead of pasted as text.
+                text_view.stop_signal_emission_by_name("paste-clipboard");
+
+                let sender = sender.clone();
+                let pasted_dir = pasted_dir.clone();
+                relm4::spawn_local(async move {
+                    let paths = if files {
+                        pasted_files(&clipboard).await
+                    } else {
+                        pasted_image(&clipboard, &pasted_dir).await
+                    };
+                    match paths {
+                        Ok(paths) => sender.input(WriteNoteInput::AttachFiles {
+                            paths,
+                            images_only: true,
+                        }),
+                        Err(e) => warn!("Could not paste: {}", e),
+                    }
+                });
+            }
+        });
+    }
+
+    /// Attaches file at `path`, which is uploaded right away, or once
+    /// the note is sent, as settings say.
+    fn attach(&mut self, path: PathBuf, sender: &ComponentSender<WriteNote>) {
+        let id = self.next_attachment;
+        self.next_attachment += 1;
+
+        let row = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+        row.add_css_class("attachment");
+        if crate::upload::is_image(&path) {
+            let thumbnail = gtk::Image::from_file(&path);
+            thumbnail.set_pixel_size(48);
+            thumbnail.add_css_class("thumbnail");
+            row.append(&thumbnail);
+        }
+        let name = gtk::Label::new(path.file_name().and_then(|n| n.to_str()));
+        name.set_ellipsize(gtk::pango::EllipsizeMode::Middle);
+        name.set_max_width_chars(30);
+        let progress = gtk::ProgressBar::new();
+        progress.set_hexpand(true);
+        progress.set_valign(gtk::Align::Center);
+        let error = gtk::Label::new(None);
+        error.set_hexpand(true);
+        error.set_xalign(0.0);
+        error.set_wrap(true);
+        error.set_visible(false);
+        error.add_css_class("error");
+        let button = gtk::Button::with_label("Remove");
+        button.connect_clicked({
+            let sender = sender.clone();
+            move |_| sender.input(WriteNoteInput::RemoveAttachment(id))
+        });
+        row.append(&name);
+        row.append(&progress);
+        row.append(&error);
+        row.append(&button);
+        self.attachments_box.append(&row);
+
+        self.attachments.push(Attachment {
+            id,
+            path,
+            row,
+            progress,
+            error,
+            button,
+            state: UploadState::Waiting,
+        });
+        if self.send_after_upload || !self.gnostique.config().upload.on_send {
+            self.start_upload(id, sender);
+        } else if let Some(attachment) = self.attachments.last() {
+            attachment.progress.set_visible(false);
         }
     }
+
+    /// Starts uploading attached file `id`, showing its progress below the note.
+    fn start_upload(&mut self, id: u64, sender: &ComponentSender<WriteNote>) {
+        let attachment = match self.attachments.iter_mut().find(|a| a.id == id) {
+            Some(attachment) => attachment,
+            None => return,
+        };
+
+        let gnostique = self.gnostique.clone();
+        let keys = self.keys.clone();
+        let path = attachment.path.clone();
+        let sender = sender.clone();
+        let task = relm4::spawn(async move {
+            let progress = {
+                let sender = sender.clone();
+                move |sent, total| sender.input(WriteNoteInput::UploadProgress { id, sent, total })
+            };
+            let uploaded = gnostique.uploads().upload(&keys, &path, progress).await;
+            sender.input(WriteNoteInput::Uploaded { id, uploaded });
+        });
+
+        attachment.state = UploadState::Uploading(task);
+        attachment.progress.set_fraction(0.0);
+        attachment.progress.set_visible(true);
+        attachment.error.set_visible(false);
+        attachment.button.set_label("Cancel");
+    }
+
+    /// Appends link to uploaded file at `url` to the note.
+    fn insert_link(&self, url: &Url) {
+        let content = self.content();
+        let separator = if content.is_empty() || content.ends_with(char::is_whitespace) {
+            ""
+        } else {
+            "\n"
+        };
+        self.buffer
+            .insert(&mut self.buffer.end_iter(), &format!("{separator}{url}"));
+    }
+
+    /// Saves the note into its draft if it has changed since last time.
+    /// Draft of note without any text is deleted.
+    fn save_draft(&mut self) {
+        let draft = self.draft();
+        let saved = (draft.subject.clone(), draft.content.clone());
+        if saved == self.saved {
+            return;
+        }
+        self.saved = saved;
+
+        let gnostique = self.gnostique.clone();
+        relm4::spawn(async move {
+            let saved = if draft.is_empty() {
+                gnostique.drafts().delete(draft.id).await
+            } else {
+                gnostique.drafts().save(&draft).await
+            };
+            if let Err(e) = saved {
+                warn!("Could not save draft: {}", e);
+            }
+        });
+    }
+
+    /// Deletes draft of the note, which is being published.
+    fn discard_draft(&mut self) {
+        self.saved = (self.subject.text().to_string(), self.content());
+
+        let gnostique = self.gnostique.clone();
+        let id = self.draft_id;
+        relm4::spawn(async move {
+            if let Err(e) = gnostique.drafts().delete(id).await {
+                warn!("Could not delete draft: {}", e);
+            }
+        });
+    }
+
+    /// Looks up earlier draft of the note being started.
+    fn find_unfinished(&self, sender: &ComponentSender<WriteNote>) {
+        let gnostique = self.gnostique.clone();
+        let sender = sender.clone();
+        let reply_to = self.reply_to.as_ref().map(|e| e.id);
+        let quote = self.quoting.as_ref().map(|(e, _)| e.id);
+        relm4::spawn(async move {
+            match gnostique.drafts().latest(reply_to, quote).await {
+                Ok(Some(draft)) => sender.input(WriteNoteInput::Unfinished(draft)),
+                Ok(None) => {}
+                Err(e) => warn!("Could not look up drafts: {}", e),
+            }
+        });
+    }
+
+    /// Continues writing `draft`. The note written so far is kept as
+    /// its own draft.
+    fn restore_draft(&mut self, draft: Draft) {
+        self.save_draft();
+
+        self.draft_id = draft.id;
+        self.saved = (draft.subject.clone(), draft.content.clone());
+        self.subject.set_text(&draft.subject);
+        self.buffer.set_text(&draft.content);
+        self.reply_to = draft.reply_to;
+        self.quoting = draft.quoting;
+        self.unfinished = None;
+    }
+
+    /// Fills drafts list with drafts other than the one being written.
+    fn show_drafts(&self, sender: &ComponentSender<WriteNote>) {
+        while let Some(row) = self.drafts_list.first_child() {
+            self.drafts_list.remove(&row);
+        }
+
+        if self.drafts.is_empty() {
+            let label = gtk::Label::new(Some("No other drafts"));
+            label.add_css_class("dim-label");
+            self.drafts_list.append(&label);
+            return;
+        }
+
+        let time_format = self.gnostique.config().time;
+        let now = Timestamp::now();
+        for draft in &self.drafts {
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+
+            let label = gtk::Label::new(Some(&draft.summary()));
+            label.set_hexpand(true);
+            label.set_xalign(0.0);
+            label.set_ellipsize(gtk::pango::EllipsizeMode::End);
+            label.set_max_width_chars(40);
+            let age = gtk::Label::new(Some(&crate::timestamps::format_age(
+                draft.updated_at,
+                now,
+                time_format,
+            )));
+            age.add_css_class("dim-label");
+
+            let open = gtk::Button::with_label("Continue");
+            open.connect_clicked({
+                let sender = sender.clone();
+                let id = draft.id;
+                move |_| sender.input(WriteNoteInput::OpenDraft(id))
+            });
+            let delete = gtk::Button::from_icon_name("user-trash-symbolic");
+            delete.set_tooltip_text(Some("Delete draft"));
+            delete.set_has_frame(false);
+            delete.connect_clicked({
+                let sender = sender.clone();
+                let id = draft.id;
+                move |_| sender.input(WriteNoteInput::DeleteDraft(id))
+            });
+
+            row.append(&label);
+            row.append(&age);
+            row.append(&open);
+            row.append(&delete);
+            self.drafts_list.append(&row);
+        }
+    }
+
+    /// Loads dictionary last chosen by our identity, or the one of our
+    /// locale, and lets user choose another one and correct misspelled
+    /// words from their context menu.
+    fn init_spelling(&mut self, sender: &ComponentSender<WriteNote>) {
+        let pubkey = self.keys.public_key().to_string();
+        let chosen = match self.gnostique.config().spelling.dictionaries.get(&pubkey) {
+            Some(name) if name.is_empty() => None,
+            Some(name) if self.dictionaries.contains(name) => Some(name.clone()),
+            _ => crate::spelling::default_dictionary(&self.dictionaries),
+        };
+        let position = chosen
+            .as_ref()
+            .and_then(|name| self.dictionaries.iter().position(|d| d == name))
+            .map(|i| i as u32 + 1)
+            .unwrap_or(0);
+        self.dictionary.set_selected(position);
+        self.load_speller(chosen, sender);
+
+        self.dictionary.connect_selected_notify({
+            let sender = sender.clone();
+            move |_| sender.input(WriteNoteInput::DictionaryChosen)
+        });
+
+        let group = RelmActionGroup::<SpellingActionGroup>::new();
+        let correct: RelmAction<CorrectWord> = RelmAction::new_with_target_value({
+            let sender = sender.clone();
+            move |_, correction: String| sender.input(WriteNoteInput::Correct(correction))
+        });
+        group.add_action(&correct);
+        self.text_view
+            .insert_action_group("spelling", Some(&group.into_action_group()));
+        self.text_view.set_extra_menu(Some(&self.corrections));
+
+        // Corrections are prepared before the context menu opens.
+        let click = gtk::GestureClick::new();
+        click.set_button(gdk::BUTTON_SECONDARY);
+        click.set_propagation_phase(gtk::PropagationPhase::Capture);
+        click.connect_pressed({
+            let sender = sender.clone();
+            move |_, _, x, y| sender.input(WriteNoteInput::OfferCorrections(x, y))
+        });
+        self.text_view.add_controller(&click);
+    }
+
+    /// Stops checking spelling with the current dictionary and starts
+    /// loading dictionary `name`, if any.
+    fn load_speller(&mut self, name: Option<String>, sender: &ComponentSender<WriteNote>) {
+        self.speller = None;
+        self.check_spelling();
+
+        if let Some(name) = name {
+            let sender = sender.clone();
+            relm4::spawn_blocking(move || {
+                let speller = match Speller::load(&name) {
+                    Ok(speller) => Some(Arc::new(speller)),
+                    Err(e) => {
+                        warn!("Spelling is not checked: {}", e);
+                        None
+                    }
+                };
+                sender.input(WriteNoteInput::SpellerLoaded(name, speller));
+            });
+        }
+    }
+
+    /// Marks misspelled words, if spelling is checked.
+    fn check_spelling(&self) {
+        let (start, end) = self.buffer.bounds();
+        self.buffer.remove_tag(&self.misspelled, &start, &end);
+        for (start, end) in self.misspelled_words() {
+            self.buffer.apply_tag(
+                &self.misspelled,
+                &self.buffer.iter_at_offset(start),
+                &self.buffer.iter_at_offset(end),
+            );
+        }
+    }
+
+    /// Puts corrections of misspelled word at `x`, `y` into context menu.
+    fn offer_corrections(&mut self, x: f64, y: f64) {
+        self.corrections.remove_all();
+        self.corrected = None;
+
+        let speller = match &self.speller {
+            Some(speller) => speller.clone(),
+            None => return,
+        };
+        let (x, y) =
+            self.text_view
+                .window_to_buffer_coords(gtk::TextWindowType::Widget, x as i32, y as i32);
+        let offset = match self.text_view.iter_at_location(x, y) {
+            Some(iter) => iter.offset(),
+            None => return,
+        };
+        let (start, end) = match self
+            .misspelled_words()
+            .into_iter()
+            .find(|(start, end)| (*start..=*end).contains(&offset))
+        {
+            Some(word) => word,
+            None => return,
+        };
+
+        let word = self.buffer.text(
+            &self.buffer.iter_at_offset(start),
+            &self.buffer.iter_at_offset(end),
+            false,
+        );
+        let suggestions = speller.suggest(&word);
+        if suggestions.is_empty() {
+            self.corrections.append(Some("No corrections"), None);
+        }
+        for suggestion in suggestions {
+            self.corrections.append_item(
+                &RelmAction::<CorrectWord>::to_menu_item_with_target_value(
+                    &suggestion,
+                    &suggestion,
+                ),
+            );
+        }
+        self.corrected = Some((start, end));
+    }
+
+    /// Offers `personas` in a popup below the cursor.
+    fn show_completions(&mut self, personas: Vec<Persona>) {
+        while let Some(row) = self.completion_list.first_child() {
+            self.completion_list.remove(&row);
+        }
+
+        if personas.is_empty() {
+            self.completions.clear();
+            self.completion.popdown();
+            return;
+        }
+
+        for persona in &personas {
+            let label = gtk::Label::new(None);
+            label.set_xalign(0.0);
+            let name = html_escape::encode_text(&persona.shown_name()).to_string();
+            match persona.format_nip05() {
+                Some(nip05) => label.set_markup(&format!("{name}  {nip05}")),
+                None => label.set_markup(&name),
+            }
+            self.completion_list.append(&label);
+        }
+        self.completions = personas;
+        self.completion_list
+            .select_row(self.completion_list.row_at_index(0).as_ref());
+
+        // Popup points at the cursor.
+        let cursor = self.buffer.iter_at_mark(&self.buffer.get_insert());
+        let location = self.text_view.iter_location(&cursor);
+        let (x, y) = self.text_view.buffer_to_window_coords(
+            gtk::TextWindowType::Widget,
+            location.x(),
+            location.y(),
+        );
+        self.completion
+            .set_pointing_to(Some(&gdk::Rectangle::new(x, y, 1, location.height())));
+        self.completion.popup();
+    }
+
+    /// Replaces `@` and text typed after it with reference to `persona`.
+    fn insert_mention(&mut self, persona: &Persona) {
+        let (query, npub) = match (self.completion_query.take(), persona.pubkey.to_bech32()) {
+            (Some(query), Ok(npub)) => (query, npub),
+            _ => return,
+        };
+
+        let mut end = self.buffer.iter_at_mark(&self.buffer.get_insert());
+        let mut start = end;
+        start.backward_chars(query.chars().count() as i32 + 1);
+
+        self.buffer.delete(&mut start, &mut end);
+        self.buffer.insert(&mut start, &format!("nostr:{npub} "));
+    }
+}
+
+/// Local files copied into `clipboard`.
+async fn pasted_files(clipboard: &gdk::Clipboard) -> Result<Vec<PathBuf>, String> {
+    let value = clipboard
+        .read_value_future(gdk::FileList::static_type(), glib::PRIORITY_DEFAULT)
+        .await
+        .map_err(|e| e.to_string())?;
+    let files = value.get::<gdk::FileList>().map_err(|e| e.to_string())?;
+    Ok(files.files().iter().filter_map(|f| f.path()).collect())
+}
+
+/// Image copied into `clipboard`, saved as PNG into `dir`, so that it can
+/// be uploaded as a file.
+async fn pasted_image(clipboard: &gdk::Clipboard, dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let texture = clipboard
+        .read_texture_future()
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Clipboard has no image")?;
+
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let file = dir.join(format!("pasted-{}.png", Draft::new_id()));
+    texture.save_to_png(&file).map_err(|e| e.to_string())?;
+    Ok(vec![file])
 }
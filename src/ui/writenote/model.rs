@@ -1,7 +1,110 @@
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use gtk::prelude::*;
+use nostr_sdk::prelude::*;
+use tokio::task::JoinHandle;
+
+use crate::content::{Target, Token};
+use crate::drafts::Draft;
+use crate::nostr::{EventExt, Persona};
+use crate::spelling::Speller;
+use crate::ui::toast::Toast;
+use crate::upload::Uploaded;
+use crate::Gnostique;
+
+/// Completion of mentions is offered after typing `@` and at least
+/// this many characters.
+pub const MIN_COMPLETION_LEN: usize = 2;
+
 #[derive(Debug)]
 pub struct WriteNote {
+    pub gnostique: Gnostique,
+    pub keys: Keys,
     pub visible: bool,
     pub buffer: gtk::TextBuffer,
+    pub subject: gtk::EntryBuffer,
+    /// Text note which is being replied to, if any.
+    pub reply_to: Option<Arc<Event>>,
+    /// Text note which is being quoted, if any, and relays where it has been seen.
+    pub quoting: Option<(Arc<Event>, Vec<Url>)>,
+    /// Required proof of work of the note (NIP-13), zero if none.
+    pub difficulty: gtk::Adjustment,
+    /// Signal to stop mining, while the note is being mined.
+    pub mining: Option<Arc<AtomicBool>>,
+    /// Number of hashes tried so far by mining.
+    pub hashes: u128,
+    pub text_view: gtk::TextView,
+    /// Popup offering users to be mentioned.
+    pub completion: gtk::Popover,
+    pub completion_list: gtk::ListBox,
+    /// Text typed after `@` that users are offered for.
+    pub completion_query: Option<String>,
+    /// Users currently offered in the popup.
+    pub completions: Vec<Persona>,
+    /// Installed dictionaries, offered after not checking spelling at all.
+    pub dictionaries: Vec<String>,
+    pub dictionary: gtk::DropDown,
+    /// Checker of spelling in the chosen dictionary, once it is loaded.
+    pub speller: Option<Arc<Speller>>,
+    /// Tag of misspelled words in the buffer.
+    pub misspelled: gtk::TextTag,
+    /// Corrections offered in context menu of the text.
+    pub corrections: gtk::gio::Menu,
+    /// Character offsets of the misspelled word which corrections are for.
+    pub corrected: Option<(i32, i32)>,
+    /// ID of the draft which the note is saved as.
+    pub draft_id: i64,
+    /// Subject and content last saved, so that only changes are saved.
+    pub saved: (String, String),
+    /// Earlier draft of the same note, which user may continue.
+    pub unfinished: Option<Draft>,
+    /// Other drafts, offered in their list.
+    pub drafts: Vec<Draft>,
+    pub drafts_list: gtk::ListBox,
+    /// Files being uploaded, or whose upload has failed.
+    pub attachments: Vec<Attachment>,
+    pub attachments_box: gtk::Box,
+    /// ID of the next attached file.
+    pub next_attachment: u64,
+    /// Files uploaded for the note, described by its `imeta` tags while
+    /// the note links them.
+    pub uploaded: Vec<Uploaded>,
+    /// Whether the note is sent once attached files are uploaded.
+    pub send_after_upload: bool,
+}
+
+/// File attached to the note until it is uploaded.
+#[derive(Debug)]
+pub struct Attachment {
+    pub id: u64,
+    pub path: PathBuf,
+    pub row: gtk::Box,
+    pub progress: gtk::ProgressBar,
+    /// Why the upload has failed.
+    pub error: gtk::Label,
+    pub button: gtk::Button,
+    pub state: UploadState,
+}
+
+#[derive(Debug)]
+pub enum UploadState {
+    /// The file is uploaded once the note is sent.
+    Waiting,
+    Uploading(JoinHandle<()>),
+    Failed,
+}
+
+/// Where uploads of files attached to a note being sent have got.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UploadsOutcome {
+    /// Some files are still waiting or being uploaded.
+    Pending,
+    /// All files are uploaded, the note can be sent.
+    Done,
+    /// Some file could not be uploaded, the note is not sent.
+    Failed,
 }
 
 #[derive(Debug)]
@@ -9,11 +112,446 @@ pub enum WriteNoteInput {
     Hide,
     Cancel,
     Show,
+    /// Show the window for writing a reply to the event.
+    Reply(Arc<Event>),
+    /// Show the window for writing a note quoting the event,
+    /// which has been seen at the relays.
+    Quote(Arc<Event>, Vec<Url>),
     Send,
+    /// Mining has tried this many hashes.
+    Mining(u128),
+    /// Mining has finished, `None` if it has been cancelled.
+    Mined(Result<Option<Box<Event>>, String>),
+    /// Content or position of cursor has changed.
+    ContentChanged,
+    /// Users matching `query` have been found.
+    Completions {
+        query: String,
+        personas: Vec<Persona>,
+    },
+    /// Select the next (positive) or previous (negative) offered user.
+    MoveCompletion(i32),
+    /// Mention the offered user at the index, or the selected one if `None`.
+    AcceptCompletion(Option<usize>),
+    /// Hide offered users.
+    DismissCompletion,
+    /// User has chosen another dictionary.
+    DictionaryChosen,
+    /// Dictionary has been loaded, or has failed to, for spell checking.
+    SpellerLoaded(String, Option<Arc<Speller>>),
+    /// User has right-clicked the text at these coordinates.
+    OfferCorrections(f64, f64),
+    /// Replace the misspelled word with this correction.
+    Correct(String),
+    /// Save the note into its draft, if it has changed.
+    Autosave,
+    /// Earlier draft of the note being started has been found.
+    Unfinished(Draft),
+    /// Continue the earlier draft of the note.
+    RestoreUnfinished,
+    /// Do not offer the earlier draft anymore.
+    DismissUnfinished,
+    /// Drafts list has been opened, drafts should be loaded.
+    ListDrafts,
+    Drafts(Vec<Draft>),
+    /// Continue writing the draft with this ID.
+    OpenDraft(i64),
+    DeleteDraft(i64),
+    /// Choose files to be attached.
+    Attach,
+    /// Attach these files. Pasted and dropped files must be images.
+    AttachFiles {
+        paths: Vec<PathBuf>,
+        images_only: bool,
+    },
+    UploadProgress {
+        id: u64,
+        sent: u64,
+        total: u64,
+    },
+    Uploaded {
+        id: u64,
+        uploaded: Result<Uploaded, String>,
+    },
+    /// Stop uploading the attached file, or forget its failed upload.
+    RemoveAttachment(u64),
 }
 
 #[derive(Debug)]
 pub enum WriteNoteResult {
     Cancel,
-    Send(String),
+    Send {
+        content: String,
+        tags: Vec<Tag>,
+    },
+    /// Publish an already signed note.
+    Publish(Box<Event>),
+    /// Tell user something, e. g. that the note could not be prepared.
+    Toast(Toast),
+}
+
+impl WriteNote {
+    /// Subject of reply to a note with `subject`, according to NIP-14.
+    pub fn reply_subject(subject: &str) -> String {
+        if subject.to_lowercase().starts_with("re:") {
+            subject.to_string()
+        } else {
+            format!("Re: {subject}")
+        }
+    }
+
+    /// Whether some of the attached files are still being uploaded.
+    pub fn is_uploading(&self) -> bool {
+        self.attachments
+            .iter()
+            .any(|a| matches!(a.state, UploadState::Uploading(_)))
+    }
+
+    /// The note being written, as its draft.
+    pub fn draft(&self) -> Draft {
+        Draft {
+            id: self.draft_id,
+            subject: self.subject.text().to_string(),
+            content: self.content(),
+            reply_to: self.reply_to.clone(),
+            quoting: self.quoting.clone(),
+            updated_at: Timestamp::now(),
+        }
+    }
+
+    pub fn format_unfinished(&self) -> String {
+        match &self.unfinished {
+            Some(draft) => format!(
+                "Unfinished draft ({}): {}",
+                crate::timestamps::format_age(
+                    draft.updated_at,
+                    Timestamp::now(),
+                    self.gnostique.config().time
+                ),
+                draft.summary()
+            ),
+            None => String::new(),
+        }
+    }
+
+    /// Text of the note being written.
+    pub fn content(&self) -> String {
+        self.buffer
+            .text(&self.buffer.start_iter(), &self.buffer.end_iter(), true)
+            .to_string()
+    }
+
+    /// Dictionary chosen in the toolbar, `None` if spelling is not checked.
+    pub fn chosen_dictionary(&self) -> Option<String> {
+        match self.dictionary.selected() {
+            0 | gtk::INVALID_LIST_POSITION => None,
+            i => self.dictionaries.get(i as usize - 1).cloned(),
+        }
+    }
+
+    /// Character offsets of misspelled words in the text, except for the
+    /// one being typed at the cursor.
+    pub fn misspelled_words(&self) -> Vec<(i32, i32)> {
+        let speller = match &self.speller {
+            Some(speller) => speller,
+            None => return Vec::new(),
+        };
+        let text = self.content();
+        let cursor = self.buffer.iter_at_mark(&self.buffer.get_insert()).offset();
+        let chars = |byte: usize| text[..byte].chars().count() as i32;
+
+        speller
+            .misspelled(&text)
+            .into_iter()
+            .map(|range| (chars(range.start), chars(range.end)))
+            .filter(|(_, end)| *end != cursor)
+            .collect()
+    }
+
+    /// Text typed after `@` right before the cursor, if it is long enough
+    /// to offer users to be mentioned.
+    pub fn completion_query(&self) -> Option<String> {
+        let cursor = self.buffer.iter_at_mark(&self.buffer.get_insert());
+        let mut line_start = cursor;
+        line_start.set_line_offset(0);
+
+        let before = self.buffer.text(&line_start, &cursor, false);
+        completion_query(&before).map(ToString::to_string)
+    }
+
+    /// Tags of the note being written: its subject, the client, users
+    /// mentioned in `content`, quoted note if still referenced from
+    /// `content`, uploaded files it links and, if it is a reply, references to the thread and
+    /// its participants according to NIP-10.
+    pub fn tags(&self, content: &str) -> Vec<Tag> {
+        let mut tags = reference_tags(
+            content,
+            self.reply_to.as_deref(),
+            self.quoting
+                .as_ref()
+                .map(|(e, r)| (e.as_ref(), r.as_slice())),
+        );
+
+        for uploaded in &self.uploaded {
+            if content.contains(uploaded.url.as_str()) {
+                tags.push(uploaded.imeta());
+            }
+        }
+
+        let subject = self.subject.text().trim().to_string();
+        if !subject.is_empty() {
+            tags.push(Tag::Subject(subject));
+        }
+
+        if self.gnostique.config().privacy.client_tag {
+            tags.push(Tag::Generic(
+                TagKind::Custom("client".to_string()),
+                vec!["Gnostique".to_string()],
+            ));
+        }
+
+        tags
+    }
+}
+
+/// Files among `paths` which can be attached, all of them or only images,
+/// and names of those which cannot.
+pub fn attachable(paths: Vec<PathBuf>, images_only: bool) -> (Vec<PathBuf>, Vec<String>) {
+    let (accepted, rejected): (Vec<PathBuf>, Vec<PathBuf>) = paths
+        .into_iter()
+        .partition(|p| !images_only || crate::upload::is_image(p));
+    let rejected = rejected
+        .iter()
+        .filter_map(|p| p.file_name())
+        .map(|n| n.to_string_lossy().to_string())
+        .collect();
+    (accepted, rejected)
+}
+
+/// Outcome of uploads of attached files in `states`, once all of them
+/// have started. Any failure fails them all.
+pub fn uploads_outcome<'a>(states: impl IntoIterator<Item = &'a UploadState>) -> UploadsOutcome {
+    let mut outcome = UploadsOutcome::Done;
+    for state in states {
+        match state {
+            UploadState::Failed => return UploadsOutcome::Failed,
+            UploadState::Waiting | UploadState::Uploading(_) => outcome = UploadsOutcome::Pending,
+        }
+    }
+    outcome
+}
+
+/// Tags of note with `content` referencing other notes and users: the thread
+/// and its participants, if the note replies to `reply_to`, according to
+/// NIP-10, note `quoting`, if still referenced from `content`, with relays
+/// where it has been seen, and users mentioned in `content`.
+pub fn reference_tags(
+    content: &str,
+    reply_to: Option<&Event>,
+    quoting: Option<(&Event, &[Url])>,
+) -> Vec<Tag> {
+    let mut tags = vec![];
+
+    if let Some(event) = reply_to {
+        match event.thread_root() {
+            Some((root, relay)) => {
+                tags.push(Tag::Event(
+                    root,
+                    relay.map(|r| r.to_string()),
+                    Some(Marker::Root),
+                ));
+                tags.push(Tag::Event(event.id, None, Some(Marker::Reply)));
+            }
+            None => tags.push(Tag::Event(event.id, None, Some(Marker::Root))),
+        }
+
+        tags.push(Tag::PubKey(event.pubkey, None));
+        for pubkey in event.mentions() {
+            if pubkey != event.pubkey {
+                tags.push(Tag::PubKey(pubkey, None));
+            }
+        }
+    }
+
+    if let Some((event, relays)) = quoting {
+        if crate::content::quoted(content)
+            .iter()
+            .any(|(id, _)| id == &event.id)
+        {
+            tags.push(Tag::Event(
+                event.id,
+                relays.first().map(|r| r.to_string()),
+                Some(Marker::Custom("mention".to_string())),
+            ));
+            if !tags.contains(&Tag::PubKey(event.pubkey, None)) {
+                tags.push(Tag::PubKey(event.pubkey, None));
+            }
+        }
+    }
+
+    for pubkey in mentioned(content) {
+        if !tags.contains(&Tag::PubKey(pubkey, None)) {
+            tags.push(Tag::PubKey(pubkey, None));
+        }
+    }
+
+    tags
+}
+
+/// Text after `@` at the end of `text`, if the `@` starts a word and the text
+/// is long enough.
+pub fn completion_query(text: &str) -> Option<&str> {
+    let at = text.rfind('@')?;
+    let query = &text[at + 1..];
+
+    let starts_word = text[..at]
+        .chars()
+        .next_back()
+        .map(char::is_whitespace)
+        .unwrap_or(true);
+
+    if starts_word
+        && query.chars().count() >= MIN_COMPLETION_LEN
+        && !query.contains(char::is_whitespace)
+        && !query.contains('@')
+    {
+        Some(query)
+    } else {
+        None
+    }
+}
+
+/// Users referenced from `content` by `npub` or `nprofile`, in order
+/// of their first appearance.
+pub fn mentioned(content: &str) -> Vec<XOnlyPublicKey> {
+    let mut mentioned = Vec::new();
+
+    for token in crate::content::tokenize(content) {
+        if let Token::Nostr(entity) = token {
+            if let Some(Target::Profile(pubkey, _)) = Target::from_bech32(entity) {
+                if !mentioned.contains(&pubkey) {
+                    mentioned.push(pubkey);
+                }
+            }
+        }
+    }
+
+    mentioned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Fixtures;
+
+    #[test]
+    fn query_follows_at_starting_word() {
+        assert_eq!(completion_query("Hello @al"), Some("al"));
+        assert_eq!(completion_query("@alice"), Some("alice"));
+        assert_eq!(completion_query("Hello @a"), None);
+        assert_eq!(completion_query("mail@example"), None);
+        assert_eq!(completion_query("@alice and"), None);
+    }
+
+    #[test]
+    fn mentioned_once_in_order() {
+        let (alice, bob) = (Fixtures::new().public_key(), Fixtures::new().public_key());
+        let nprofile = Profile::new(bob, vec!["wss://relay.example.com"])
+            .to_bech32()
+            .unwrap();
+        let content = format!(
+            "nostr:{nprofile}, {} and again nostr:{}",
+            alice.to_bech32().unwrap(),
+            bob.to_bech32().unwrap()
+        );
+
+        assert_eq!(mentioned(&content), vec![bob, alice]);
+    }
+
+    #[test]
+    fn reply_to_root_starts_thread() {
+        let (alice, bob) = (Fixtures::new(), Fixtures::new());
+        let root = alice.text_note("Hello");
+        let mention = format!("nostr:{}", bob.public_key().to_bech32().unwrap());
+
+        assert_eq!(
+            reference_tags(&mention, Some(&root), None),
+            vec![
+                Tag::Event(root.id, None, Some(Marker::Root)),
+                Tag::PubKey(alice.public_key(), None),
+                Tag::PubKey(bob.public_key(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn reply_within_thread_keeps_participants_once() {
+        let (alice, bob) = (Fixtures::new(), Fixtures::new());
+        let root = alice.text_note("Hello");
+        let reply = bob.reply(&root, None, "Hi");
+        let mention = format!("nostr:{}", alice.public_key().to_bech32().unwrap());
+
+        assert_eq!(
+            reference_tags(&mention, Some(&reply), None),
+            vec![
+                Tag::Event(root.id, None, Some(Marker::Root)),
+                Tag::Event(reply.id, None, Some(Marker::Reply)),
+                Tag::PubKey(bob.public_key(), None),
+                Tag::PubKey(alice.public_key(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn quote_is_tagged_only_while_referenced() {
+        let alice = Fixtures::new();
+        let quoted = alice.text_note("Quote me");
+        let relays = [Url::parse("wss://relay.example.com").unwrap()];
+        let content = format!("Look: nostr:{}", quoted.id.to_bech32().unwrap());
+
+        assert_eq!(
+            reference_tags(&content, None, Some((&quoted, &relays))),
+            vec![
+                Tag::Event(
+                    quoted.id,
+                    Some("wss://relay.example.com/".to_string()),
+                    Some(Marker::Custom("mention".to_string()))
+                ),
+                Tag::PubKey(alice.public_key(), None),
+            ]
+        );
+        assert!(reference_tags("Never mind", None, Some((&quoted, &relays))).is_empty());
+    }
+
+    #[test]
+    fn pasted_and_dropped_files_must_be_images() {
+        let paths: Vec<PathBuf> = ["/tmp/photo.JPG", "/tmp/clip.mp4", "/tmp/notes.txt"]
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+
+        assert_eq!(
+            attachable(paths.clone(), true),
+            (
+                vec![PathBuf::from("/tmp/photo.JPG")],
+                vec!["clip.mp4".to_string(), "notes.txt".to_string()]
+            )
+        );
+        assert_eq!(attachable(paths.clone(), false), (paths, vec![]));
+    }
+
+    #[tokio::test]
+    async fn note_is_sent_once_all_files_are_uploaded() {
+        let uploading = || UploadState::Uploading(tokio::spawn(async {}));
+
+        assert_eq!(uploads_outcome([]), UploadsOutcome::Done);
+        assert_eq!(
+            uploads_outcome(&[uploading(), UploadState::Waiting]),
+            UploadsOutcome::Pending
+        );
+        assert_eq!(
+            uploads_outcome(&[uploading(), UploadState::Failed]),
+            UploadsOutcome::Failed
+        );
+    }
 }
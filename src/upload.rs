@@ -0,0 +1,716 @@
+//! Upload of files attached to notes to HTTP file storage servers with
+//! NIP-96 API, which user configures. Uploads are authorized by events
+//! signed by the identity which attaches the files (NIP-98). Uploaded
+//! files are linked from the note and described by its `imeta` tags
+//! (NIP-92) with what the server tells about them.
+
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use base64::Engine;
+use futures_util::stream;
+use nostr_sdk::prelude::*;
+use reqwest::{Body, Client, StatusCode};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::config::UploadConfig;
+
+/// Kind of events authorizing HTTP requests (NIP-98).
+const HTTP_AUTH_KIND: u64 = 27235;
+
+/// Files are sent in chunks of this size, progress is reported after each.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// How long the whole upload may take.
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// How long the server may take to describe itself.
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How many times, and how often, server still processing the file is
+/// asked whether it is done.
+const PROCESSING_CHECKS: u32 = 30;
+const PROCESSING_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Clone)]
+pub struct Uploads(Arc<UploadsInner>);
+
+struct UploadsInner {
+    http: Client,
+    config: RwLock<UploadConfig>,
+}
+
+/// File stored by a server.
+#[derive(Clone, Debug)]
+pub struct Uploaded {
+    pub url: Url,
+    /// What the server tells about the file, such as its `dim` or `x`
+    /// (hash), as pairs of NIP-94 tag names and values.
+    pub metadata: Vec<(String, String)>,
+}
+
+impl Uploaded {
+    /// Tag describing the file in the note linking it (NIP-92).
+    pub fn imeta(&self) -> Tag {
+        let mut values = vec![format!("url {}", self.url)];
+        values.extend(
+            self.metadata
+                .iter()
+                .filter(|(name, _)| name != "url")
+                .map(|(name, value)| format!("{name} {value}")),
+        );
+        Tag::Generic(TagKind::Custom("imeta".to_string()), values)
+    }
+}
+
+/// How the server describes itself at `/.well-known/nostr/nip96.json`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ServerInfo {
+    api_url: String,
+    /// Server which user should upload to instead.
+    delegated_to_url: Option<String>,
+    /// Content types the server accepts, all if none.
+    content_types: Vec<String>,
+    plans: Plans,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Plans {
+    free: Plan,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Plan {
+    max_byte_size: Option<u64>,
+}
+
+/// What the server responds to upload.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Response {
+    status: String,
+    message: Option<String>,
+    /// Where to ask whether the file has been processed, if it has not yet.
+    processing_url: Option<String>,
+    nip94_event: Option<Nip94Event>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Nip94Event {
+    tags: Vec<Vec<String>>,
+}
+
+impl Uploads {
+    pub fn new(http: Client) -> Uploads {
+        Uploads(Arc::new(UploadsInner {
+            http,
+            config: RwLock::new(UploadConfig::default()),
+        }))
+    }
+
+    pub fn set_config(&self, config: &UploadConfig) {
+        *self.0.config.write().unwrap() = config.clone();
+    }
+
+    /// Whether a server is configured, files cannot be attached otherwise.
+    pub fn is_enabled(&self) -> bool {
+        !self.0.config.read().unwrap().server.trim().is_empty()
+    }
+
+    /// Uploads file at `path` on behalf of `keys` to the configured
+    /// server, or to the first fallback which accepts it. `progress` is
+    /// told how many bytes of how many have been sent.
+    pub async fn upload<F>(&self, keys: &Keys, path: &Path, progress: F) -> Result<Uploaded, String>
+    where
+        F: Fn(u64, u64) + Clone + Send + Sync + 'static,
+    {
+        let config = self.0.config.read().unwrap().clone();
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("file")
+            .to_string();
+        let data = tokio::fs::read(path)
+            .await
+            .map_err(|e| format!("Cannot read {name}: {e}"))?;
+        let file = File {
+            mime: mime_type(path),
+            hash: sha256::Hash::hash(&data).to_string(),
+            name,
+            data: Arc::new(data),
+        };
+
+        let servers: Vec<&str> = std::iter::once(config.server.as_str())
+            .chain(config.fallbacks.iter().map(String::as_str))
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .collect();
+        if servers.is_empty() {
+            return Err("No server to upload to is set in settings".to_string());
+        }
+
+        let mut errors = Vec::new();
+        for server in &servers {
+            match self.upload_to(server, keys, &file, progress.clone()).await {
+                Ok(uploaded) => {
+                    info!("Uploaded {} to {}", file.name, uploaded.url);
+                    return Ok(uploaded);
+                }
+                Err(e) => {
+                    warn!("Could not upload {} to {}: {}", file.name, server, e);
+                    errors.push(if servers.len() > 1 {
+                        format!("{server}: {e}")
+                    } else {
+                        e
+                    });
+                }
+            }
+        }
+
+        Err(errors.join("; "))
+    }
+
+    async fn upload_to<F>(
+        &self,
+        server: &str,
+        keys: &Keys,
+        file: &File,
+        progress: F,
+    ) -> Result<Uploaded, String>
+    where
+        F: Fn(u64, u64) + Send + Sync + 'static,
+    {
+        let info = self.discover(server).await?;
+        let api = Url::parse(&info.api_url)
+            .map_err(|_| format!("Server tells invalid upload URL {}", info.api_url))?;
+
+        let size = file.data.len() as u64;
+        if let Some(max) = info.plans.free.max_byte_size {
+            if size > max {
+                return Err(format!(
+                    "{} has {}, server accepts at most {}",
+                    file.name,
+                    format_bytes(size),
+                    format_bytes(max)
+                ));
+            }
+        }
+        if !accepts(&info.content_types, file.mime) {
+            return Err(format!(
+                "Server does not accept files of type {}",
+                file.mime
+            ));
+        }
+
+        let boundary = format!("gnostique-{}", &file.hash[..32]);
+        let (body, length) = multipart(file, &boundary, progress);
+        let response = self
+            .0
+            .http
+            .post(api.clone())
+            .timeout(UPLOAD_TIMEOUT)
+            .header(
+                reqwest::header::AUTHORIZATION,
+                authorization(keys, &api, "POST", &file.hash)?,
+            )
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .header(reqwest::header::CONTENT_LENGTH, length)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| format!("Server cannot be reached: {e}"))?;
+
+        let (status, mut response) = read_response(response).await?;
+        match status {
+            StatusCode::PAYLOAD_TOO_LARGE => {
+                return Err(format!("{} is too large for the server", file.name))
+            }
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => {
+                return Err(format!(
+                    "Server does not accept files of type {}",
+                    file.mime
+                ))
+            }
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                return Err(format!(
+                    "Server has refused the upload: {}",
+                    response.message.unwrap_or_else(|| status.to_string())
+                ))
+            }
+            _ => {}
+        }
+
+        // Server may need a while to process the file before it is stored.
+        let mut checks = 0;
+        while response.nip94_event.is_none() && response.status != "error" {
+            let processing = match (&response.processing_url, checks < PROCESSING_CHECKS) {
+                (Some(url), true) => url.clone(),
+                _ => break,
+            };
+            checks += 1;
+            tokio::time::sleep(PROCESSING_INTERVAL).await;
+            let polled = self
+                .0
+                .http
+                .get(&processing)
+                .timeout(DISCOVERY_TIMEOUT)
+                .send()
+                .await
+                .map_err(|e| format!("Server cannot be reached: {e}"))?;
+            let (_, polled) = read_response(polled).await?;
+            response = Response {
+                processing_url: polled.processing_url.or(response.processing_url),
+                ..polled
+            };
+        }
+
+        match response.nip94_event {
+            Some(event) if response.status != "error" => uploaded(event),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| format!("Server has failed ({status})"))),
+        }
+    }
+
+    /// Finds out where and what `server` accepts.
+    async fn discover(&self, server: &str) -> Result<ServerInfo, String> {
+        let mut info = self.server_info(server).await?;
+        if info.api_url.is_empty() {
+            match info.delegated_to_url.take() {
+                Some(delegated) => info = self.server_info(&delegated).await?,
+                None => return Err("Server does not tell where to upload".to_string()),
+            }
+        }
+        Ok(info)
+    }
+
+    async fn server_info(&self, server: &str) -> Result<ServerInfo, String> {
+        let url = server_url(server)?
+            .join(".well-known/nostr/nip96.json")
+            .map_err(|e| e.to_string())?;
+        let response = self
+            .0
+            .http
+            .get(url)
+            .timeout(DISCOVERY_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| format!("Server cannot be reached: {e}"))?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "Server does not support NIP-96 ({})",
+                response.status()
+            ));
+        }
+        let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+        serde_json::from_slice(&bytes).map_err(|_| "Server does not support NIP-96".to_string())
+    }
+}
+
+/// File being uploaded.
+struct File {
+    name: String,
+    mime: &'static str,
+    /// SHA-256 of the data, in hex.
+    hash: String,
+    data: Arc<Vec<u8>>,
+}
+
+/// URL of server entered by user, if it is valid.
+pub fn server_url(server: &str) -> Result<Url, String> {
+    let mut base = server.trim().to_string();
+    if !base.ends_with('/') {
+        base.push('/');
+    }
+
+    Url::parse(&base)
+        .ok()
+        .filter(|u| u.scheme() == "https" || u.scheme() == "http")
+        .ok_or_else(|| format!("Invalid URL of upload server: {}", server.trim()))
+}
+
+/// Value of `Authorization` header of request to `url` with `method` and
+/// body with `payload` hash, signed by `keys` (NIP-98).
+fn authorization(keys: &Keys, url: &Url, method: &str, payload: &str) -> Result<String, String> {
+    let tag = |name: &str, value: &str| {
+        Tag::Generic(TagKind::Custom(name.to_string()), vec![value.to_string()])
+    };
+    let event = EventBuilder::new(
+        Kind::Custom(HTTP_AUTH_KIND),
+        "",
+        &[
+            tag("u", url.as_str()),
+            tag("method", method),
+            tag("payload", payload),
+        ],
+    )
+    .to_event(keys)
+    .map_err(|e| e.to_string())?;
+    let json = event.as_json().map_err(|e| e.to_string())?;
+
+    Ok(format!(
+        "Nostr {}",
+        base64::engine::general_purpose::STANDARD.encode(json)
+    ))
+}
+
+/// Form with `file`, which reports to `progress` as it is sent, and its length.
+fn multipart<F>(file: &File, boundary: &str, progress: F) -> (Body, u64)
+where
+    F: Fn(u64, u64) + Send + Sync + 'static,
+{
+    let name = file.name.replace(['"', '\r', '\n'], "_");
+    let mut head = String::new();
+    for (field, value) in [
+        ("size", file.data.len().to_string()),
+        ("content_type", file.mime.to_string()),
+    ] {
+        head.push_str(&format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"{field}\"\r\n\r\n{value}\r\n"
+        ));
+    }
+    head.push_str(&format!(
+        "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{name}\"\r\nContent-Type: {}\r\n\r\n",
+        file.mime
+    ));
+    let tail = format!("\r\n--{boundary}--\r\n");
+
+    let total = (head.len() + file.data.len() + tail.len()) as u64;
+    let data = file.data.clone();
+    let chunks = (0..data.len()).step_by(CHUNK_SIZE).map(move |start| {
+        let end = (start + CHUNK_SIZE).min(data.len());
+        (data[start..end].to_vec(), end)
+    });
+
+    let head_len = head.len() as u64;
+    let parts = std::iter::once((head.into_bytes(), 0))
+        .chain(chunks)
+        .chain(std::iter::once((tail.into_bytes(), usize::MAX)))
+        .map(move |(bytes, sent)| {
+            let sent = match sent {
+                usize::MAX => total,
+                sent => head_len + sent as u64,
+            };
+            progress(sent, total);
+            Ok::<_, std::io::Error>(bytes)
+        });
+
+    (Body::wrap_stream(stream::iter(parts)), total)
+}
+
+async fn read_response(response: reqwest::Response) -> Result<(StatusCode, Response), String> {
+    let status = response.status();
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Server has not responded: {e}"))?;
+    let parsed = serde_json::from_slice(&bytes).unwrap_or_default();
+    Ok((status, parsed))
+}
+
+/// Stored file as described by the server.
+fn uploaded(event: Nip94Event) -> Result<Uploaded, String> {
+    let metadata: Vec<(String, String)> = event
+        .tags
+        .into_iter()
+        .filter_map(|tag| match tag.as_slice() {
+            [name, value, ..] => Some((name.clone(), value.clone())),
+            _ => None,
+        })
+        .collect();
+    let url = metadata
+        .iter()
+        .find(|(name, _)| name == "url")
+        .and_then(|(_, url)| Url::parse(url).ok())
+        .ok_or_else(|| "Server has not told where the file is".to_string())?;
+
+    Ok(Uploaded { url, metadata })
+}
+
+/// Whether server accepting `content_types`, all if none, accepts `mime`.
+fn accepts(content_types: &[String], mime: &str) -> bool {
+    content_types.is_empty()
+        || content_types.iter().any(|accepted| {
+            accepted == mime
+                || accepted
+                    .strip_suffix("/*")
+                    .map(|prefix| mime.split('/').next() == Some(prefix))
+                    .unwrap_or(false)
+        })
+}
+
+/// Whether file at `path` is an image, judging by its name.
+pub fn is_image(path: &Path) -> bool {
+    mime_type(path).starts_with("image/")
+}
+
+/// Content type of file at `path`, told by its extension.
+fn mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+    match extension.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "svg" => "image/svg+xml",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "mov" => "video/quicktime",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        b if b < 1024 => format!("{b} B"),
+        b if b < 1024 * 1024 => format!("{:.1} kB", b as f64 / 1024.0),
+        b => format!("{:.1} MB", b as f64 / (1024.0 * 1024.0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::testing::{http_response, StubServer, TempDir};
+
+    /// Value of header `name` of HTTP `request`.
+    fn header<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+        request.lines().find_map(|l| {
+            let (n, value) = l.split_once(':')?;
+            n.eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    }
+
+    fn json(status: &str, body: &str) -> Vec<u8> {
+        http_response(
+            status,
+            &[("Content-Type", "application/json")],
+            body.as_bytes(),
+        )
+    }
+
+    /// NIP-96 server which stores files of at most `max_size` bytes.
+    async fn nip96_server(max_size: u64) -> StubServer {
+        StubServer::new(move |request| {
+            let host = header(request, "host").unwrap();
+            if request.starts_with("GET /.well-known/nostr/nip96.json ") {
+                json(
+                    "200 OK",
+                    &format!(
+                        r#"{{"api_url": "http://{host}/upload", "content_types": ["image/*"],
+                            "plans": {{"free": {{"max_byte_size": {max_size}}}}}}}"#
+                    ),
+                )
+            } else if request.starts_with("POST /upload ") {
+                json(
+                    "201 Created",
+                    r#"{"status": "success", "nip94_event": {"tags": [
+                        ["url", "https://files.example.com/abc.png"],
+                        ["dim", "1x1"], ["m", "image/png"], ["x"]
+                    ]}}"#,
+                )
+            } else {
+                http_response("404 Not Found", &[], b"")
+            }
+        })
+        .await
+    }
+
+    fn uploads(servers: &[&StubServer]) -> Uploads {
+        let uploads = Uploads::new(Client::new());
+        let urls: Vec<String> = servers.iter().map(|s| s.url("/").to_string()).collect();
+        uploads.set_config(&UploadConfig {
+            server: urls[0].clone(),
+            fallbacks: urls[1..].to_vec(),
+            on_send: false,
+        });
+        uploads
+    }
+
+    #[tokio::test]
+    async fn file_is_uploaded_to_first_server_accepting_it() {
+        let dir = TempDir::new();
+        let path = dir.path().join("photo.PNG");
+        std::fs::write(&path, b"not really a png").unwrap();
+        let keys = Keys::generate();
+        let (broken, working) = (
+            StubServer::always(http_response("404 Not Found", &[], b"")).await,
+            nip96_server(1000).await,
+        );
+        let progress: Arc<Mutex<Vec<(u64, u64)>>> = Default::default();
+        let reported = progress.clone();
+
+        let uploaded = uploads(&[&broken, &working])
+            .upload(&keys, &path, move |sent, total| {
+                reported.lock().unwrap().push((sent, total))
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(uploaded.url.as_str(), "https://files.example.com/abc.png");
+        assert_eq!(
+            uploaded.imeta(),
+            Tag::Generic(
+                TagKind::Custom("imeta".to_string()),
+                vec![
+                    "url https://files.example.com/abc.png".to_string(),
+                    "dim 1x1".to_string(),
+                    "m image/png".to_string(),
+                ]
+            )
+        );
+        let progress = progress.lock().unwrap();
+        let (sent, total) = *progress.last().unwrap();
+        assert_eq!(sent, total);
+        assert!(progress.windows(2).all(|w| w[0].0 <= w[1].0));
+
+        let post = working.requests().pop().unwrap();
+        assert!(post.contains("filename=\"photo.PNG\"\r\nContent-Type: image/png"));
+        assert!(post.contains("not really a png"));
+        // Authorized by an event of the uploader for this very request.
+        let token = header(&post, "authorization")
+            .unwrap()
+            .strip_prefix("Nostr ")
+            .unwrap();
+        let json = base64::engine::general_purpose::STANDARD
+            .decode(token)
+            .unwrap();
+        let event = Event::from_json(String::from_utf8(json).unwrap()).unwrap();
+        event.verify().unwrap();
+        assert_eq!(event.pubkey, keys.public_key());
+        assert_eq!(event.kind.as_u64(), HTTP_AUTH_KIND);
+        let tag = |name: &str| {
+            event.tags.iter().find_map(|t| match t.as_vec().as_slice() {
+                [n, value] if n == name => Some(value.clone()),
+                _ => None,
+            })
+        };
+        assert_eq!(tag("u"), Some(working.url("/upload").to_string()));
+        assert_eq!(tag("method").as_deref(), Some("POST"));
+        assert_eq!(
+            tag("payload"),
+            Some(sha256::Hash::hash(b"not really a png").to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn refusals_of_all_servers_are_told() {
+        let dir = TempDir::new();
+        let (large, document) = (dir.path().join("large.png"), dir.path().join("doc.pdf"));
+        std::fs::write(&large, vec![0; 2048]).unwrap();
+        std::fs::write(&document, b"%PDF").unwrap();
+        let server = nip96_server(1024).await;
+        let other = StubServer::always(json("200 OK", "{}")).await;
+
+        let keys = Keys::generate();
+        assert_eq!(
+            uploads(&[&server])
+                .upload(&keys, &large, |_, _| ())
+                .await
+                .map(|u| u.url),
+            Err("large.png has 2.0 kB, server accepts at most 1.0 kB".to_string())
+        );
+        let error = uploads(&[&server, &other])
+            .upload(&keys, &document, |_, _| ())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error,
+            format!(
+                "{}: Server does not accept files of type application/pdf; \
+                 {}: Server does not tell where to upload",
+                server.url("/"),
+                other.url("/")
+            )
+        );
+        // Nothing has been posted.
+        assert!(server.requests().iter().all(|r| r.starts_with("GET ")));
+    }
+
+    #[test]
+    fn server_tells_where_file_is() {
+        let described = |tags: &[&[&str]]| {
+            uploaded(Nip94Event {
+                tags: tags
+                    .iter()
+                    .map(|t| t.iter().map(|s| s.to_string()).collect())
+                    .collect(),
+            })
+        };
+
+        assert!(described(&[&["url", "https://example.com/a.png"]]).is_ok());
+        for tags in [
+            &[][..],
+            &[&["url"][..]],
+            &[&["url", "a.png"]],
+            &[&["ox", "x"]],
+        ] {
+            assert_eq!(
+                described(tags).map(|u| u.url),
+                Err("Server has not told where the file is".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn accepted_content_types_may_be_wildcards() {
+        let types = |t: &[&str]| t.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert!(accepts(&[], "application/pdf"));
+        assert!(accepts(&types(&["image/*"]), "image/png"));
+        assert!(!accepts(&types(&["image/*"]), "video/mp4"));
+        assert!(accepts(&types(&["image/png", "video/mp4"]), "video/mp4"));
+        assert!(!accepts(&types(&["image/png"]), "image/jpeg"));
+    }
+
+    #[test]
+    fn files_are_told_by_their_extensions() {
+        assert!(is_image(Path::new("a/Photo.JPG")));
+        assert!(is_image(Path::new("drawing.svg")));
+        assert!(!is_image(Path::new("clip.mp4")));
+        assert!(!is_image(Path::new("png")));
+        assert_eq!(mime_type(Path::new("song.mp3")), "audio/mpeg");
+        assert_eq!(
+            mime_type(Path::new("notes.txt")),
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn sizes_and_server_urls_are_readable() {
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 kB");
+        assert_eq!(format_bytes(5 * 1024 * 1024), "5.0 MB");
+
+        assert_eq!(
+            server_url(" https://files.example.com/nip96 ")
+                .unwrap()
+                .as_str(),
+            "https://files.example.com/nip96/"
+        );
+        assert_eq!(
+            server_url("files.example.com"),
+            Err("Invalid URL of upload server: files.example.com".to_string())
+        );
+    }
+}